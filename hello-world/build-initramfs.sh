@@ -0,0 +1,22 @@
+#!/usr/bin/sh
+# Packs the userland the kernel boots into, with the hello binaries as /init.
+# Put anything else that should be in it under rootfs/, it is copied as it is.
+
+set -e
+
+cd "$(dirname "$0")"
+
+for arch in la rv; do
+    staging=$(mktemp -d)
+
+    if [ -d rootfs ]; then
+        cp -a rootfs/. "$staging"
+    fi
+
+    cp "hello-$arch" "$staging/init"
+    chmod 755 "$staging/init"
+
+    (cd "$staging" && find . | cpio -o -H newc --owner 0:0) > "initramfs-$arch.cpio"
+
+    rm -rf "$staging"
+done
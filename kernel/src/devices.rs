@@ -0,0 +1,233 @@
+use abstractions::IUsizeAlias;
+use address::PhysicalAddress;
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use allocation::FrameAllocator;
+use allocation_abstractions::{FrameRangeDesc, IFrameAllocator};
+use core::{net::Ipv4Addr, ptr::NonNull};
+use device_tree::{DeviceTree, Node, Range};
+use hermit_sync::SpinMutex;
+use kernel_abstractions::IKernel;
+use platform_specific::{device_phys_to_virt, phys_to_virt};
+use virtio::{
+    IDmaAllocator, IMmioRegisters, ITransport, MmioRegisters, PciMemoryWindow, VirtioError,
+    VirtioMmio, VirtioNet, VirtioPci, PAGE_SIZE, VIRTIO_DEVICE_NET, VIRTIO_MMIO_COMPATIBLE,
+};
+
+use crate::kernel::Kernel;
+
+// The defaults of QEMU's user networking
+const GUEST_ADDRESS: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+const GUEST_PREFIX_LEN: u8 = 24;
+const GATEWAY: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+/// The `compatible` string of PCI host bridges with ECAM, like the one of QEMU's `virt` machines
+const PCI_HOST_COMPATIBLE: &str = "pci-host-ecam-generic";
+
+/// ECAM gives every bus 1MiB of configuration space and every device 32KiB of it
+const ECAM_BUS_SHIFT: u64 = 20;
+const ECAM_DEVICE_SHIFT: u64 = 15;
+const PCI_DEVICES: u64 = 32;
+const PCI_BUSES: u64 = 256;
+
+/// The space code in the first cell of a PCI address, for 32 and 64 bit memory
+const PCI_SPACE_MEMORY_32: u32 = 2;
+const PCI_SPACE_MEMORY_64: u32 = 3;
+
+struct KernelDma {
+    allocator: Arc<SpinMutex<FrameAllocator>>,
+}
+
+impl IDmaAllocator for KernelDma {
+    fn allocate(&self, pages: usize) -> Option<(usize, NonNull<u8>)> {
+        let range = self.allocator.lock().alloc_contiguous(pages)?;
+
+        let physical = range.start.as_usize();
+        // Handed back through `deallocate`
+        core::mem::forget(range);
+
+        let virt = NonNull::new(phys_to_virt(physical) as *mut u8)?;
+        unsafe { virt.write_bytes(0, pages * PAGE_SIZE) };
+
+        Some((physical, virt))
+    }
+
+    unsafe fn deallocate(&self, physical: usize, _virt: NonNull<u8>, pages: usize) {
+        let range = unsafe {
+            FrameRangeDesc::new(PhysicalAddress::from_usize(physical), pages * PAGE_SIZE)
+        };
+
+        self.allocator.lock().dealloc_range(range);
+    }
+}
+
+/// Discovers devices from the bootloader's device tree and hands them to the kernel.
+pub(crate) fn probe(kernel: &Kernel, allocator: Arc<SpinMutex<FrameAllocator>>) {
+    let Some(address) = platform_abstractions::device_tree_address() else {
+        log::info!("No device tree, skipping device discovery");
+        return;
+    };
+
+    let tree = match unsafe { DeviceTree::from_raw(phys_to_virt(address) as *const u8) } {
+        Ok(tree) => tree,
+        Err(e) => {
+            log::warn!("Bad device tree at {:#x}: {:?}", address, e);
+            return;
+        }
+    };
+
+    let dma: Arc<dyn IDmaAllocator> = Arc::new(KernelDma { allocator });
+
+    // QEMU's riscv64 machine has its cards on virtio-mmio, the LoongArch one only behind PCI
+    let transports = mmio_transports(&tree).chain(pci_transports(&tree));
+
+    for (name, transport) in transports {
+        if transport.device_id() != VIRTIO_DEVICE_NET {
+            log::info!(
+                "Ignoring virtio device {} at {}",
+                transport.device_id(),
+                name
+            );
+            continue;
+        }
+
+        match VirtioNet::new(transport, dma.clone()) {
+            Ok(net) => {
+                log::info!("virtio-net at {} is {}", name, GUEST_ADDRESS);
+
+                kernel.network().add_interface(
+                    Box::new(net),
+                    GUEST_ADDRESS,
+                    GUEST_PREFIX_LEN,
+                    Some(GATEWAY),
+                );
+
+                // The static configuration only fits one card
+                return;
+            }
+            Err(e) => log::warn!("Failed to set up virtio-net at {}: {:?}", name, e),
+        }
+    }
+}
+
+/// The registers of a device at `physical`.
+fn device_registers(physical: u64) -> Option<Box<dyn IMmioRegisters>> {
+    let base = NonNull::new(device_phys_to_virt(physical as usize) as *mut u8)?;
+
+    Some(Box::new(unsafe { MmioRegisters::new(base) }))
+}
+
+fn compatible_nodes<'a>(tree: &DeviceTree<'a>, compatible: &str) -> Vec<Node<'a>> {
+    tree.find_compatible(compatible).unwrap_or_else(|e| {
+        log::warn!("Bad device tree: {:?}", e);
+        Vec::new()
+    })
+}
+
+fn mmio_transports<'a>(
+    tree: &DeviceTree<'a>,
+) -> impl Iterator<Item = (String, Box<dyn ITransport>)> + 'a {
+    compatible_nodes(tree, VIRTIO_MMIO_COMPATIBLE)
+        .into_iter()
+        .filter_map(|node| {
+            let region = node.reg().first().copied()?;
+            let registers = device_registers(region.address)?;
+
+            // Empty transports report no device, QEMU keeps plenty of them
+            let transport = VirtioMmio::probe(registers).ok()?;
+
+            Some((
+                node.name.to_string(),
+                Box::new(transport) as Box<dyn ITransport>,
+            ))
+        })
+}
+
+/// Walks every bus below the PCI host bridges for virtio devices, lazily so nothing is set up
+/// once a card is found on virtio-mmio.
+fn pci_transports<'a>(
+    tree: &DeviceTree<'a>,
+) -> impl Iterator<Item = (String, Box<dyn ITransport>)> + 'a {
+    compatible_nodes(tree, PCI_HOST_COMPATIBLE)
+        .into_iter()
+        .flat_map(|host| {
+            let Some(ecam) = host.reg().first().copied() else {
+                return Vec::new();
+            };
+
+            let Some(mut window) = memory_window(&host) else {
+                log::warn!("PCI host bridge {} forwards no memory", host.name);
+                return Vec::new();
+            };
+
+            let buses = (ecam.size >> ECAM_BUS_SHIFT).min(PCI_BUSES);
+
+            // Virtio devices only have function 0
+            (0..buses)
+                .flat_map(|bus| (0..PCI_DEVICES).map(move |device| (bus, device)))
+                .filter_map(|(bus, device)| {
+                    let config =
+                        ecam.address + (bus << ECAM_BUS_SHIFT | device << ECAM_DEVICE_SHIFT);
+
+                    let transport =
+                        VirtioPci::probe(device_registers(config)?, &mut window, &|address| {
+                            device_registers(address).unwrap()
+                        });
+
+                    match transport {
+                        Ok(transport) => Some((
+                            format!("pci {:02x}:{:02x}.0", bus, device),
+                            Box::new(transport) as Box<dyn ITransport>,
+                        )),
+                        // An empty slot or a device of another vendor
+                        Err(VirtioError::NoDevice | VirtioError::BadMagic) => None,
+                        Err(e) => {
+                            log::warn!(
+                                "Failed to set up virtio at pci {:02x}:{:02x}.0: {:?}",
+                                bus,
+                                device,
+                                e
+                            );
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+}
+
+/// The memory window of a PCI host bridge from its `ranges`, 32 bit memory is preferred as
+/// every BAR can live there.
+fn memory_window(host: &Node) -> Option<PciMemoryWindow> {
+    let ranges = host.ranges();
+
+    [PCI_SPACE_MEMORY_32, PCI_SPACE_MEMORY_64]
+        .into_iter()
+        .find_map(|wanted| {
+            ranges.iter().find_map(|range| match pci_address(range)? {
+                (space, bus) if space == wanted => {
+                    Some(PciMemoryWindow::new(bus, range.parent, range.size))
+                }
+                _ => None,
+            })
+        })
+}
+
+/// The space code and the address of the 3 cells of a PCI address.
+fn pci_address(range: &Range) -> Option<(u32, u64)> {
+    let cells = range
+        .child
+        .chunks_exact(4)
+        .map(|cell| u32::from_be_bytes(cell.try_into().unwrap()))
+        .collect::<Vec<_>>();
+
+    match cells[..] {
+        [high, mid, low] => Some(((high >> 24) & 0b11, (mid as u64) << 32 | low as u64)),
+        _ => None,
+    }
+}
@@ -1,24 +1,143 @@
-use alloc::sync::Arc;
+use abstractions::IUsizeAlias;
+use address::PhysicalAddress;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use allocation::FrameAllocator;
-use allocation_abstractions::IFrameAllocator;
-use filesystem_abstractions::DirectoryTreeNode;
+use allocation_abstractions::{FrameDesc, IFrameAllocator, IFrameReclaimer};
+use block_abstractions::BlockDevices;
+use core::ptr::NonNull;
+use devfs::{DevFileSystem, DevFileSystemType, DeviceNumber, SerialDevice};
+use ext4fs::Ext4FileSystemType;
+use fat32fs::Fat32FileSystemType;
+use filesystem_abstractions::{
+    DirectoryTreeNode, FileLockManager, IPageAllocator, MountEntry, MountFlags, MountTable,
+    PageCache,
+};
 use hermit_sync::SpinMutex;
+use ipc::{
+    IpcTable, MessageQueue, MqueueFileSystem, MqueueFileSystemType, SemaphoreSets,
+    SharedMemorySegment, MSGMNI, SHMMNI,
+};
 use kernel_abstractions::{IKernel, IKernelSerial};
 use linux_syscalls::SyscallContext;
 use linux_task_abstractions::ILinuxTask;
 use mmu_abstractions::IMMU;
+use network_stack::NetworkStack;
+use platform_specific::phys_to_virt;
+use procfs::{ProcFileSystem, ProcFileSystemType};
+use threading::{IClock, TimerQueue};
 use timing::TimeSpec;
+use tmpfs::{TmpFileSystem, TmpFileSystemType};
+use unix_socket::UnixNamespace;
 
-use crate::serial::KernelSerial;
+use crate::{proc::KernelProcSource, serial::KernelSerial};
 
 pub(crate) struct Kernel {
     serial: Arc<KernelSerial>,
     allocator: Arc<SpinMutex<FrameAllocator>>,
+    timer: Arc<TimerQueue>,
+    network: Arc<NetworkStack>,
+    unix_sockets: Arc<UnixNamespace>,
+    locks: Arc<FileLockManager>,
+    fs: Arc<SpinMutex<Arc<DirectoryTreeNode>>>,
+    mounts: Arc<MountTable>,
+    shared_memory: Arc<IpcTable<SharedMemorySegment>>,
+    semaphores: Arc<SemaphoreSets>,
+    message_queues: Arc<IpcTable<MessageQueue>>,
+    mqueue: Arc<DirectoryTreeNode>,
+    proc: Arc<KernelProcSource>,
+    dev: Arc<DevFileSystem>,
+    disks: Arc<BlockDevices>,
 }
 
 impl Kernel {
     pub fn new(serial: Arc<KernelSerial>, allocator: Arc<SpinMutex<FrameAllocator>>) -> Arc<Self> {
-        Arc::new(Self { serial, allocator })
+        let clock = Arc::new(KernelClock);
+        let timer = TimerQueue::new(clock.clone());
+
+        // Start ticking before anyone registers a timer
+        clock.arm(None);
+
+        // Files may keep a quarter of the memory in the page cache
+        let capacity = allocator.lock().total_frames() / 4;
+        let pages = Arc::new(KernelPageAllocator {
+            allocator: allocator.clone(),
+            reclaimed: SpinMutex::new(None),
+        });
+        let cache = PageCache::new(pages.clone(), capacity);
+
+        // And give back what is not mapped when the memory runs out
+        allocator
+            .lock()
+            .set_reclaimer(Some(Arc::new(PageCacheReclaimer {
+                cache: Arc::downgrade(&cache),
+                pages,
+            })));
+
+        let mounts = Arc::new(MountTable::new(Some(cache.clone())));
+
+        let fs = Arc::new(SpinMutex::new(mount_root(
+            clock.clone(),
+            cache.clone(),
+            &mounts,
+        )));
+        let proc = KernelProcSource::new(allocator.clone(), timer.clone(), &mounts);
+        mount_proc(&fs.lock(), proc.clone(), &mounts);
+        let dev = mount_dev(&fs.lock(), serial.clone(), &mounts);
+        mount_shm(&fs.lock(), clock.clone(), cache, &mounts);
+
+        // Not mounted anywhere at boot, `mq_open` reaches the queues through this root
+        let mqueue_fs = MqueueFileSystem::new();
+        let mqueue = DirectoryTreeNode::from_filesystem(None, mqueue_fs.clone(), Some(""));
+
+        let disks = Arc::new(BlockDevices::new());
+        mounts.register(TmpFileSystemType::new(clock.clone()));
+        mounts.register(ProcFileSystemType::new(proc.clone()));
+        mounts.register(DevFileSystemType::new(dev.clone()));
+        mounts.register(MqueueFileSystemType::new(mqueue_fs));
+        mounts.register(Ext4FileSystemType::new(disks.clone(), clock.clone()));
+        mounts.register(Fat32FileSystemType::new(disks.clone(), clock.clone()));
+
+        Arc::new(Self {
+            serial,
+            allocator,
+            timer,
+            network: NetworkStack::new(clock),
+            unix_sockets: UnixNamespace::new(fs.clone()),
+            locks: Arc::new(FileLockManager::new()),
+            fs,
+            mounts,
+            shared_memory: Arc::new(IpcTable::new(SHMMNI)),
+            semaphores: Arc::new(SemaphoreSets::new()),
+            message_queues: Arc::new(IpcTable::new(MSGMNI)),
+            mqueue,
+            proc,
+            dev,
+            disks,
+        })
+    }
+
+    pub fn proc(&self) -> &KernelProcSource {
+        &self.proc
+    }
+
+    /// The registry of `/dev`, where drivers add their device nodes.
+    pub fn dev(&self) -> &Arc<DevFileSystem> {
+        &self.dev
+    }
+
+    /// The disks `mount` finds filesystems on, where drivers add their block devices.
+    pub fn disks(&self) -> &Arc<BlockDevices> {
+        &self.disks
+    }
+
+    /// Writes back everything the page cache holds, before the machine goes down.
+    pub fn sync(&self) {
+        if let Some(Err(e)) = self.mounts.page_cache().map(|cache| cache.sync_all()) {
+            log::warn!("Failed to write back the page cache: {:?}", e);
+        }
     }
 
     pub fn create_syscall_contenxt_for(
@@ -38,7 +157,7 @@ impl IKernel for Kernel {
     }
 
     fn fs(&self) -> Arc<SpinMutex<Arc<DirectoryTreeNode>>> {
-        todo!()
+        self.fs.clone()
     }
 
     fn allocator(&self) -> Arc<SpinMutex<dyn IFrameAllocator>> {
@@ -51,6 +170,223 @@ impl IKernel for Kernel {
     }
 
     fn time(&self) -> TimeSpec {
-        todo!()
+        self.timer.now()
+    }
+
+    fn timer(&self) -> Arc<TimerQueue> {
+        self.timer.clone()
+    }
+
+    fn network(&self) -> Arc<NetworkStack> {
+        self.network.clone()
+    }
+
+    fn unix_sockets(&self) -> Arc<UnixNamespace> {
+        self.unix_sockets.clone()
+    }
+
+    fn locks(&self) -> Arc<FileLockManager> {
+        self.locks.clone()
+    }
+
+    fn mounts(&self) -> Arc<MountTable> {
+        self.mounts.clone()
+    }
+
+    fn shared_memory(&self) -> Arc<IpcTable<SharedMemorySegment>> {
+        self.shared_memory.clone()
+    }
+
+    fn semaphores(&self) -> Arc<SemaphoreSets> {
+        self.semaphores.clone()
+    }
+
+    fn message_queues(&self) -> Arc<IpcTable<MessageQueue>> {
+        self.message_queues.clone()
+    }
+
+    fn mqueue(&self) -> Arc<DirectoryTreeNode> {
+        self.mqueue.clone()
+    }
+}
+
+/// Builds the tree the kernel starts with: a tmpfs as `/` and another one at `/tmp`, both going
+/// through the page cache.
+fn mount_root(
+    clock: Arc<KernelClock>,
+    cache: Arc<PageCache>,
+    mounts: &MountTable,
+) -> Arc<DirectoryTreeNode> {
+    let root = DirectoryTreeNode::from_cached_filesystem(
+        None,
+        TmpFileSystem::new(clock.clone()),
+        cache.clone(),
+        Some(""),
+    );
+
+    root.mkdir("tmp").expect("Failed to create /tmp");
+
+    let tmp = DirectoryTreeNode::from_cached_filesystem(
+        Some(root.clone()),
+        TmpFileSystem::new(clock),
+        cache,
+        None,
+    );
+    root.mount_as(tmp, Some("tmp"))
+        .expect("Failed to mount /tmp");
+
+    mounts.add(boot_mount("tmpfs", "/", "tmpfs"));
+    mounts.add(boot_mount("tmpfs", "/tmp", "tmpfs"));
+
+    root
+}
+
+/// Mounts procfs at `/proc`.
+fn mount_proc(root: &Arc<DirectoryTreeNode>, source: Arc<KernelProcSource>, mounts: &MountTable) {
+    root.mkdir("proc").expect("Failed to create /proc");
+
+    let proc =
+        DirectoryTreeNode::from_filesystem(Some(root.clone()), ProcFileSystem::new(source), None);
+    root.mount_as(proc, Some("proc"))
+        .expect("Failed to mount /proc");
+
+    mounts.add(boot_mount("proc", "/proc", "proc"));
+}
+
+/// Mounts devfs at `/dev`, with the serial port as the console.
+fn mount_dev(
+    root: &Arc<DirectoryTreeNode>,
+    serial: Arc<KernelSerial>,
+    mounts: &MountTable,
+) -> Arc<DevFileSystem> {
+    // The time counter is the only entropy source so far, it varies with how long the boot took
+    let dev = DevFileSystem::with_defaults(platform_abstractions::time_counter());
+
+    let serial = SerialDevice::new(serial);
+    for (name, number) in [
+        ("tty", DeviceNumber::TTY),
+        ("console", DeviceNumber::CONSOLE),
+        ("ttyS0", DeviceNumber::serial(0)),
+    ] {
+        dev.register(name, number, serial.clone())
+            .expect("Failed to register the serial port");
+    }
+
+    root.mkdir("dev").expect("Failed to create /dev");
+
+    let node = DirectoryTreeNode::from_filesystem(Some(root.clone()), dev.clone(), None);
+    root.mount_as(node, Some("dev"))
+        .expect("Failed to mount /dev");
+
+    mounts.add(boot_mount("devtmpfs", "/dev", "devtmpfs"));
+
+    dev
+}
+
+/// Mounts a tmpfs at `/dev/shm`, where POSIX shared memory objects are files.
+fn mount_shm(
+    root: &Arc<DirectoryTreeNode>,
+    clock: Arc<KernelClock>,
+    cache: Arc<PageCache>,
+    mounts: &MountTable,
+) {
+    let dev = root.open_child("dev").expect("Failed to open /dev");
+
+    let shm = DirectoryTreeNode::from_cached_filesystem(
+        Some(dev.clone()),
+        TmpFileSystem::new(clock),
+        cache,
+        None,
+    );
+    dev.mount_as(shm, Some("shm"))
+        .expect("Failed to mount /dev/shm");
+
+    mounts.add(boot_mount("tmpfs", "/dev/shm", "tmpfs"));
+}
+
+/// The entry of a filesystem the kernel mounts itself, which are all writable.
+fn boot_mount(source: &str, target: &str, fs_type: &str) -> MountEntry {
+    MountEntry {
+        source: source.into(),
+        target: target.into(),
+        fs_type: fs_type.into(),
+        flags: MountFlags::empty(),
+    }
+}
+
+/// Gives the page cache whole frames, accessed through the kernel's linear mapping.
+struct KernelPageAllocator {
+    allocator: Arc<SpinMutex<FrameAllocator>>,
+    // Collects the frames freed while the allocator is reclaiming, it is locked then
+    reclaimed: SpinMutex<Option<Vec<FrameDesc>>>,
+}
+
+impl IPageAllocator for KernelPageAllocator {
+    fn allocate(&self) -> Option<(usize, NonNull<u8>)> {
+        let frame = self.allocator.lock().alloc_frame()?;
+        let physical = frame.0.as_usize();
+
+        // Handed back through `deallocate`
+        core::mem::forget(frame);
+
+        Some((physical, NonNull::new(phys_to_virt(physical) as *mut u8)?))
+    }
+
+    unsafe fn deallocate(&self, physical: usize, _page: NonNull<u8>) {
+        let frame = unsafe { FrameDesc::new(PhysicalAddress::from_usize(physical)) };
+
+        if let Some(reclaimed) = self.reclaimed.lock().as_mut() {
+            reclaimed.push(frame);
+            return;
+        }
+
+        self.allocator.lock().dealloc(frame);
+    }
+}
+
+/// Lets the frame allocator take pages back from the page cache.
+struct PageCacheReclaimer {
+    cache: Weak<PageCache>,
+    pages: Arc<KernelPageAllocator>,
+}
+
+impl IFrameReclaimer for PageCacheReclaimer {
+    fn reclaim(&self, count: usize) -> Vec<FrameDesc> {
+        let Some(cache) = self.cache.upgrade() else {
+            return Vec::new();
+        };
+
+        *self.pages.reclaimed.lock() = Some(Vec::new());
+
+        // Not when the cache is the one allocating, it evicts on its own then
+        cache.try_shrink(count);
+
+        self.pages.reclaimed.lock().take().unwrap_or_default()
+    }
+}
+
+struct KernelClock;
+
+impl KernelClock {
+    /// Keeps interrupting even without pending timers, so the network stack is still polled
+    const TICK: TimeSpec = TimeSpec::new_unchecked(0, 10_000_000);
+}
+
+impl IClock for KernelClock {
+    fn now(&self) -> TimeSpec {
+        TimeSpec::from_ticks(
+            platform_abstractions::time_counter() as i64,
+            platform_abstractions::time_counter_frequency(),
+        )
+    }
+
+    fn arm(&self, deadline: Option<TimeSpec>) {
+        let tick = self.now() + Self::TICK;
+        let deadline = deadline.map_or(tick, |deadline| deadline.min(tick));
+
+        let nanos = deadline.total_nanoseconds().max(0) as u128;
+        let frequency = platform_abstractions::time_counter_frequency() as u128;
+
+        platform_abstractions::set_timer((nanos * frequency / 1_000_000_000) as u64);
     }
 }
@@ -11,6 +11,7 @@ use abstractions::IUsizeAlias;
 use address::{PhysicalAddress, VirtualAddress, VirtualAddressRange};
 use alloc::sync::Arc;
 use allocation::FrameAllocator;
+use filesystem_abstractions::OpenFlags;
 use hermit_sync::SpinMutex;
 use kernel_abstractions::IKernel;
 use linux_loader::{LinuxLoader, ProcessContext, RawMemorySpace};
@@ -25,17 +26,16 @@ use task_abstractions::ITask;
 use threading::block_on;
 use trap_abstractions::ISyscallPayloadMut;
 
-use crate::{
-    kernel::Kernel, serial::KernelSerial, syscalls::handle_syscall_async, tty::TeletypewriterFile,
-};
+use crate::{kernel::Kernel, serial::KernelSerial, syscalls::handle_syscall_async};
 
 extern crate alloc;
 
+mod devices;
 mod kernel;
 mod logging;
+mod proc;
 mod serial;
 mod syscalls;
-mod tty;
 
 // The entry point from the underlying HAL
 // We need to do some initialization and then begin our main logic
@@ -71,10 +71,22 @@ extern "C" fn __kernel_start_main() -> ! {
 
     let serial = KernelSerial::new();
 
-    let kernel = Kernel::new(serial, allocator);
+    let kernel = Kernel::new(serial, allocator.clone());
 
-    match main(kernel) {
-        Ok(_) => unsafe { platform_abstractions::machine_shutdown(false) },
+    devices::probe(&kernel, allocator);
+
+    {
+        let root = kernel.fs().lock().clone();
+
+        initramfs::unpack(INITRAMFS, &root).expect("Failed to unpack the initramfs");
+    }
+
+    match main(kernel.clone()) {
+        Ok(_) => {
+            kernel.sync();
+
+            unsafe { platform_abstractions::machine_shutdown(false) }
+        }
         Err(msg) => panic!("{}", msg),
     }
 }
@@ -83,6 +95,9 @@ fn main(kernel: Arc<Kernel>) -> Result<(), &'static str> {
     let task = create_task(&kernel);
     let ctx = kernel.create_syscall_contenxt_for(task.clone());
 
+    // The only task there is, so it is always the one making system calls
+    kernel.proc().set_current(Some(&task.process()));
+
     let task_closure = run_task(ctx);
 
     // activate page tabe for the task
@@ -100,11 +115,12 @@ fn main(kernel: Arc<Kernel>) -> Result<(), &'static str> {
     Ok(())
 }
 
+// The userland, see `hello-world/build-initramfs.sh`
 #[cfg(target_arch = "loongarch64")]
-static ELF: &[u8] = include_bytes!("../../hello-world/hello-la");
+static INITRAMFS: &[u8] = include_bytes!("../../hello-world/initramfs-la.cpio");
 
 #[cfg(target_arch = "riscv64")]
-static ELF: &[u8] = include_bytes!("../../hello-world/hello-rv");
+static INITRAMFS: &[u8] = include_bytes!("../../hello-world/initramfs-rv.cpio");
 
 fn create_task(kernel: &Kernel) -> Arc<dyn ILinuxTask> {
     let mmu: Arc<SpinMutex<dyn IMMU>> =
@@ -114,18 +130,28 @@ fn create_task(kernel: &Kernel) -> Arc<dyn ILinuxTask> {
 
     let memory_space: RawMemorySpace = (mmu, kernel.allocator());
 
-    let loader = LinuxLoader::from_elf(&ELF, "", ctx, &memory_space).unwrap();
+    let root = kernel.fs().lock().clone();
+    let init = root
+        .open("/init", Some(&root))
+        .expect("No /init in the initramfs");
+
+    let loader = LinuxLoader::from_elf(&init, "/init", ctx, &memory_space).unwrap();
 
     let task = LinuxProcess::new(loader, 0);
     {
         let process = task.process();
 
-        let tty = TeletypewriterFile::new(kernel.serial());
+        kernel.proc().set_init(process.clone());
+
+        let console = root
+            .open("/dev/console", Some(&root))
+            .expect("No /dev/console")
+            .open_as_file(OpenFlags::O_RDWR, 0);
 
         let mut fd_table = process.fd_table().lock();
-        fd_table.allocate_at(tty.clone(), 0).unwrap();
-        fd_table.allocate_at(tty.clone(), 1).unwrap();
-        fd_table.allocate_at(tty, 2).unwrap();
+        fd_table.allocate_at(console.clone(), 0).unwrap();
+        fd_table.allocate_at(console.clone(), 1).unwrap();
+        fd_table.allocate_at(console, 2).unwrap();
     }
 
     task
@@ -166,6 +192,10 @@ async fn handle_user_trap(sys_ctx: &SyscallContext, return_reason: UserInterrupt
 
             payload.trap_ctx.set_return_value(ret);
         }
+        UserInterrupt::Timer => {
+            sys_ctx.kernel.timer().process_expired();
+            sys_ctx.kernel.network().poll();
+        }
         _ => unimplemented!("Unhandled user interrupt: {:?}", return_reason),
     }
 
@@ -0,0 +1,97 @@
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use allocation::FrameAllocator;
+use constants::PAGE_SIZE;
+use filesystem_abstractions::{MountEntry, MountTable};
+use hermit_sync::SpinMutex;
+use procfs::{IProcSource, MemoryStatistics};
+use task_abstractions::IProcess;
+use threading::{IClock, TimerQueue};
+use timing::TimeSpec;
+
+/// What the kernel shows in `/proc`.
+pub(crate) struct KernelProcSource {
+    allocator: Arc<SpinMutex<FrameAllocator>>,
+    timer: Arc<TimerQueue>,
+    // Weak, as the table holds the procfs type which holds this
+    mounts: Weak<MountTable>,
+    init: SpinMutex<Option<Arc<dyn IProcess>>>,
+    current: SpinMutex<Option<Weak<dyn IProcess>>>,
+}
+
+impl KernelProcSource {
+    pub fn new(
+        allocator: Arc<SpinMutex<FrameAllocator>>,
+        timer: Arc<TimerQueue>,
+        mounts: &Arc<MountTable>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            allocator,
+            timer,
+            mounts: Arc::downgrade(mounts),
+            init: SpinMutex::new(None),
+            current: SpinMutex::new(None),
+        })
+    }
+
+    /// Sets the first process, all others are found as its descendants.
+    pub fn set_init(&self, init: Arc<dyn IProcess>) {
+        *self.init.lock() = Some(init);
+    }
+
+    /// Sets the process whose system calls are being handled.
+    pub fn set_current(&self, process: Option<&Arc<dyn IProcess>>) {
+        *self.current.lock() = process.map(Arc::downgrade);
+    }
+}
+
+impl IProcSource for KernelProcSource {
+    fn current(&self) -> Option<Arc<dyn IProcess>> {
+        self.current.lock().as_ref().and_then(Weak::upgrade)
+    }
+
+    fn processes(&self) -> Vec<Arc<dyn IProcess>> {
+        let mut processes = Vec::new();
+        let mut pending: Vec<_> = self.init.lock().iter().cloned().collect();
+
+        while let Some(process) = pending.pop() {
+            pending.extend(process.children());
+            processes.push(process);
+        }
+
+        processes
+    }
+
+    fn memory(&self) -> MemoryStatistics {
+        let allocator = self.allocator.lock();
+        let (_, kernel_heap, _) = global_heap::heap_statistics();
+
+        MemoryStatistics {
+            total: allocator.total_frames() * PAGE_SIZE,
+            free: allocator.free_frames() * PAGE_SIZE,
+            kernel_heap,
+        }
+    }
+
+    fn uptime(&self) -> TimeSpec {
+        self.timer.now()
+    }
+
+    fn cpuinfo(&self) -> String {
+        format!(
+            "processor\t: 0\nmodel name\t: {}\n\n",
+            platform_specific::PLATFORM_STRING.to_str().unwrap()
+        )
+    }
+
+    fn mounts(&self) -> Vec<MountEntry> {
+        match self.mounts.upgrade() {
+            Some(mounts) => mounts.entries(),
+            None => Vec::new(),
+        }
+    }
+}
@@ -1,6 +1,31 @@
 use linux_syscalls::{SyscallContext, SyscallResult};
 use platform_specific::{
-    syscall_ids::{SYSCALL_ID_EXIT, SYSCALL_ID_WRITE},
+    syscall_ids::{
+        SYSCALL_ID_ACCEPT, SYSCALL_ID_ACCEPT4, SYSCALL_ID_BIND, SYSCALL_ID_CLOSE,
+        SYSCALL_ID_CONNECT, SYSCALL_ID_COPY_FILE_RANGE, SYSCALL_ID_EXECVE, SYSCALL_ID_EXIT,
+        SYSCALL_ID_FACCESSAT, SYSCALL_ID_FACCESSAT2, SYSCALL_ID_FALLOCATE, SYSCALL_ID_FCHMODAT,
+        SYSCALL_ID_FCHOWNAT, SYSCALL_ID_FCNTL64, SYSCALL_ID_FDATASYNC, SYSCALL_ID_FGETXATTR,
+        SYSCALL_ID_FLISTXATTR, SYSCALL_ID_FLOCK, SYSCALL_ID_FREMOVEXATTR, SYSCALL_ID_FSETXATTR,
+        SYSCALL_ID_FSYNC, SYSCALL_ID_FTRUNCATE64, SYSCALL_ID_GETEGID, SYSCALL_ID_GETEUID,
+        SYSCALL_ID_GETGID, SYSCALL_ID_GETGROUPS, SYSCALL_ID_GETPEERNAME, SYSCALL_ID_GETRESGID,
+        SYSCALL_ID_GETRESUID, SYSCALL_ID_GETSOCKNAME, SYSCALL_ID_GETSOCKOPT, SYSCALL_ID_GETUID,
+        SYSCALL_ID_GETXATTR, SYSCALL_ID_INOTIFY_ADD_WATCH, SYSCALL_ID_INOTIFY_INIT1,
+        SYSCALL_ID_INOTIFY_RM_WATCH, SYSCALL_ID_LGETXATTR, SYSCALL_ID_LISTEN, SYSCALL_ID_LISTXATTR,
+        SYSCALL_ID_LLISTXATTR, SYSCALL_ID_LREMOVEXATTR, SYSCALL_ID_LSETXATTR,
+        SYSCALL_ID_MEMFD_CREATE, SYSCALL_ID_MOUNT, SYSCALL_ID_MQ_GETSETATTR, SYSCALL_ID_MQ_NOTIFY,
+        SYSCALL_ID_MQ_OPEN, SYSCALL_ID_MQ_TIMEDRECEIVE, SYSCALL_ID_MQ_TIMEDSEND,
+        SYSCALL_ID_MQ_UNLINK, SYSCALL_ID_MSGCTL, SYSCALL_ID_MSGGET, SYSCALL_ID_MSGRCV,
+        SYSCALL_ID_MSGSND, SYSCALL_ID_NANOSLEEP, SYSCALL_ID_PIPE2, SYSCALL_ID_READ,
+        SYSCALL_ID_RECVFROM, SYSCALL_ID_RECVMSG, SYSCALL_ID_REMOVEXATTR, SYSCALL_ID_RENAMEAT2,
+        SYSCALL_ID_SEMCTL, SYSCALL_ID_SEMGET, SYSCALL_ID_SEMOP, SYSCALL_ID_SEMTIMEDOP,
+        SYSCALL_ID_SENDFILE, SYSCALL_ID_SENDMSG, SYSCALL_ID_SENDTO, SYSCALL_ID_SETFSGID,
+        SYSCALL_ID_SETFSUID, SYSCALL_ID_SETGID, SYSCALL_ID_SETGROUPS, SYSCALL_ID_SETREGID,
+        SYSCALL_ID_SETRESGID, SYSCALL_ID_SETRESUID, SYSCALL_ID_SETREUID, SYSCALL_ID_SETSOCKOPT,
+        SYSCALL_ID_SETUID, SYSCALL_ID_SETXATTR, SYSCALL_ID_SHMAT, SYSCALL_ID_SHMCTL,
+        SYSCALL_ID_SHMDT, SYSCALL_ID_SHMGET, SYSCALL_ID_SHUTDOWN, SYSCALL_ID_SOCKET,
+        SYSCALL_ID_SOCKETPAIR, SYSCALL_ID_SPLICE, SYSCALL_ID_SYNC, SYSCALL_ID_TEE,
+        SYSCALL_ID_UMOUNT, SYSCALL_ID_UNLINKAT, SYSCALL_ID_UTIMENSAT, SYSCALL_ID_WRITE,
+    },
     SyscallPayload,
 };
 use trap_abstractions::ISyscallPayload;
@@ -15,8 +40,99 @@ pub async fn handle_syscall_async(p: &SyscallPayload<'_, &SyscallContext>) -> Sy
     }
 
     match p.syscall_id() {
+        SYSCALL_ID_READ => syscall!(sys_read, 3).await,
         SYSCALL_ID_WRITE => syscall!(sys_write, 3).await,
         SYSCALL_ID_EXIT => syscall!(sys_exit, 1),
+        SYSCALL_ID_NANOSLEEP => syscall!(sys_nanosleep, 2).await,
+        SYSCALL_ID_SOCKET => syscall!(sys_socket, 3),
+        SYSCALL_ID_SOCKETPAIR => syscall!(sys_socketpair, 4),
+        SYSCALL_ID_BIND => syscall!(sys_bind, 3),
+        SYSCALL_ID_LISTEN => syscall!(sys_listen, 2),
+        SYSCALL_ID_ACCEPT => syscall!(sys_accept, 3).await,
+        SYSCALL_ID_ACCEPT4 => syscall!(sys_accept4, 4).await,
+        SYSCALL_ID_CONNECT => syscall!(sys_connect, 3).await,
+        SYSCALL_ID_GETSOCKNAME => syscall!(sys_getsockname, 3),
+        SYSCALL_ID_GETPEERNAME => syscall!(sys_getpeername, 3),
+        SYSCALL_ID_SENDTO => syscall!(sys_sendto, 6).await,
+        SYSCALL_ID_RECVFROM => syscall!(sys_recvfrom, 6).await,
+        SYSCALL_ID_SETSOCKOPT => syscall!(sys_setsockopt, 5),
+        SYSCALL_ID_GETSOCKOPT => syscall!(sys_getsockopt, 5),
+        SYSCALL_ID_SHUTDOWN => syscall!(sys_shutdown, 2),
+        SYSCALL_ID_SENDMSG => syscall!(sys_sendmsg, 3).await,
+        SYSCALL_ID_RECVMSG => syscall!(sys_recvmsg, 3).await,
+        SYSCALL_ID_GETUID => syscall!(sys_getuid, 0),
+        SYSCALL_ID_GETEUID => syscall!(sys_geteuid, 0),
+        SYSCALL_ID_GETGID => syscall!(sys_getgid, 0),
+        SYSCALL_ID_GETEGID => syscall!(sys_getegid, 0),
+        SYSCALL_ID_GETRESUID => syscall!(sys_getresuid, 3),
+        SYSCALL_ID_GETRESGID => syscall!(sys_getresgid, 3),
+        SYSCALL_ID_SETUID => syscall!(sys_setuid, 1),
+        SYSCALL_ID_SETGID => syscall!(sys_setgid, 1),
+        SYSCALL_ID_SETREUID => syscall!(sys_setreuid, 2),
+        SYSCALL_ID_SETREGID => syscall!(sys_setregid, 2),
+        SYSCALL_ID_SETRESUID => syscall!(sys_setresuid, 3),
+        SYSCALL_ID_SETRESGID => syscall!(sys_setresgid, 3),
+        SYSCALL_ID_SETFSUID => syscall!(sys_setfsuid, 1),
+        SYSCALL_ID_SETFSGID => syscall!(sys_setfsgid, 1),
+        SYSCALL_ID_GETGROUPS => syscall!(sys_getgroups, 2),
+        SYSCALL_ID_SETGROUPS => syscall!(sys_setgroups, 2),
+        SYSCALL_ID_FCHMODAT => syscall!(sys_fchmodat, 3),
+        SYSCALL_ID_FCHOWNAT => syscall!(sys_fchownat, 5),
+        SYSCALL_ID_UTIMENSAT => syscall!(sys_utimensat, 4),
+        SYSCALL_ID_FACCESSAT => syscall!(sys_faccessat, 3),
+        SYSCALL_ID_UNLINKAT => syscall!(sys_unlinkat, 3),
+        SYSCALL_ID_RENAMEAT2 => syscall!(sys_renameat2, 5),
+        SYSCALL_ID_EXECVE => syscall!(sys_execve, 3),
+        SYSCALL_ID_FACCESSAT2 => syscall!(sys_faccessat2, 4),
+        SYSCALL_ID_FTRUNCATE64 => syscall!(sys_ftruncate, 2),
+        SYSCALL_ID_FALLOCATE => syscall!(sys_fallocate, 4),
+        SYSCALL_ID_SYNC => syscall!(sys_sync, 0),
+        SYSCALL_ID_FSYNC => syscall!(sys_fsync, 1),
+        SYSCALL_ID_FDATASYNC => syscall!(sys_fdatasync, 1),
+        SYSCALL_ID_SETXATTR => syscall!(sys_setxattr, 5),
+        SYSCALL_ID_LSETXATTR => syscall!(sys_lsetxattr, 5),
+        SYSCALL_ID_FSETXATTR => syscall!(sys_fsetxattr, 5),
+        SYSCALL_ID_GETXATTR => syscall!(sys_getxattr, 4),
+        SYSCALL_ID_LGETXATTR => syscall!(sys_lgetxattr, 4),
+        SYSCALL_ID_FGETXATTR => syscall!(sys_fgetxattr, 4),
+        SYSCALL_ID_LISTXATTR => syscall!(sys_listxattr, 3),
+        SYSCALL_ID_LLISTXATTR => syscall!(sys_llistxattr, 3),
+        SYSCALL_ID_FLISTXATTR => syscall!(sys_flistxattr, 3),
+        SYSCALL_ID_REMOVEXATTR => syscall!(sys_removexattr, 2),
+        SYSCALL_ID_LREMOVEXATTR => syscall!(sys_lremovexattr, 2),
+        SYSCALL_ID_FREMOVEXATTR => syscall!(sys_fremovexattr, 2),
+        SYSCALL_ID_CLOSE => syscall!(sys_close, 1),
+        SYSCALL_ID_FLOCK => syscall!(sys_flock, 2).await,
+        SYSCALL_ID_FCNTL64 => syscall!(sys_fcntl, 3).await,
+        SYSCALL_ID_INOTIFY_INIT1 => syscall!(sys_inotify_init1, 1),
+        SYSCALL_ID_INOTIFY_ADD_WATCH => syscall!(sys_inotify_add_watch, 3),
+        SYSCALL_ID_INOTIFY_RM_WATCH => syscall!(sys_inotify_rm_watch, 2),
+        SYSCALL_ID_MOUNT => syscall!(sys_mount, 5),
+        SYSCALL_ID_UMOUNT => syscall!(sys_umount2, 2),
+        SYSCALL_ID_PIPE2 => syscall!(sys_pipe2, 2),
+        SYSCALL_ID_SENDFILE => syscall!(sys_sendfile, 4).await,
+        SYSCALL_ID_SPLICE => syscall!(sys_splice, 6).await,
+        SYSCALL_ID_TEE => syscall!(sys_tee, 4).await,
+        SYSCALL_ID_COPY_FILE_RANGE => syscall!(sys_copy_file_range, 6).await,
+        SYSCALL_ID_MEMFD_CREATE => syscall!(sys_memfd_create, 2),
+        SYSCALL_ID_SHMGET => syscall!(sys_shmget, 3),
+        SYSCALL_ID_SHMAT => syscall!(sys_shmat, 3),
+        SYSCALL_ID_SHMDT => syscall!(sys_shmdt, 1),
+        SYSCALL_ID_SHMCTL => syscall!(sys_shmctl, 3),
+        SYSCALL_ID_SEMGET => syscall!(sys_semget, 3),
+        SYSCALL_ID_SEMOP => syscall!(sys_semop, 3).await,
+        SYSCALL_ID_SEMTIMEDOP => syscall!(sys_semtimedop, 4).await,
+        SYSCALL_ID_SEMCTL => syscall!(sys_semctl, 4),
+        SYSCALL_ID_MSGGET => syscall!(sys_msgget, 2),
+        SYSCALL_ID_MSGSND => syscall!(sys_msgsnd, 4).await,
+        SYSCALL_ID_MSGRCV => syscall!(sys_msgrcv, 5).await,
+        SYSCALL_ID_MSGCTL => syscall!(sys_msgctl, 3),
+        SYSCALL_ID_MQ_OPEN => syscall!(sys_mq_open, 4),
+        SYSCALL_ID_MQ_UNLINK => syscall!(sys_mq_unlink, 1),
+        SYSCALL_ID_MQ_TIMEDSEND => syscall!(sys_mq_timedsend, 5).await,
+        SYSCALL_ID_MQ_TIMEDRECEIVE => syscall!(sys_mq_timedreceive, 5).await,
+        SYSCALL_ID_MQ_NOTIFY => syscall!(sys_mq_notify, 2),
+        SYSCALL_ID_MQ_GETSETATTR => syscall!(sys_mq_getsetattr, 3),
         id => panic!("Unimplemented syscall: {}", id),
     }
 }
@@ -22,3 +22,11 @@ pub trait IFrameAllocator {
 
     fn dealloc_range(&mut self, range: FrameRangeDesc);
 }
+
+/// Holds frames it can give back when the allocator runs out, like a cache.
+pub trait IFrameReclaimer: Send + Sync {
+    /// Frees up to `count` frames and returns them.
+    ///
+    /// The allocator asking is locked, so the frames must be handed back here instead of deallocated.
+    fn reclaim(&self, count: usize) -> Vec<FrameDesc>;
+}
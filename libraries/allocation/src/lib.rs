@@ -4,8 +4,8 @@
 
 use abstractions::operations::IUsizeAlias;
 use address::PhysicalAddress;
-use alloc::vec::Vec;
-use allocation_abstractions::{FrameDesc, FrameRangeDesc, IFrameAllocator};
+use alloc::{sync::Arc, vec::Vec};
+use allocation_abstractions::{FrameDesc, FrameRangeDesc, IFrameAllocator, IFrameReclaimer};
 
 #[cfg(feature = "std")]
 extern crate std;
@@ -18,6 +18,7 @@ pub struct FrameAllocator {
     // current should always point to the last frame that can be allocated
     current: PhysicalAddress,
     recycled: Vec<PhysicalAddress>,
+    reclaimer: Option<Arc<dyn IFrameReclaimer>>,
 }
 
 impl FrameAllocator {
@@ -27,9 +28,15 @@ impl FrameAllocator {
             bottom,
             current: bottom,
             recycled: Vec::new(),
+            reclaimer: None,
         }
     }
 
+    /// Asks `reclaimer` for frames before failing an allocation.
+    pub fn set_reclaimer(&mut self, reclaimer: Option<Arc<dyn IFrameReclaimer>>) {
+        self.reclaimer = reclaimer;
+    }
+
     pub fn top(&self) -> PhysicalAddress {
         self.top
     }
@@ -37,10 +44,29 @@ impl FrameAllocator {
     pub fn bottom(&self) -> PhysicalAddress {
         self.bottom
     }
-}
 
-impl IFrameAllocator for FrameAllocator {
-    fn alloc_frame(&mut self) -> Option<FrameDesc> {
+    /// Frames between the bottom and the top, allocated or not.
+    pub fn total_frames(&self) -> usize {
+        (self.top - self.bottom).as_usize() / constants::PAGE_SIZE
+    }
+
+    /// Frames that can still be allocated.
+    pub fn free_frames(&self) -> usize {
+        self.recycled.len() + (self.top - self.current).as_usize() / constants::PAGE_SIZE
+    }
+
+    /// Takes back what the reclaimer can spare of `count` frames.
+    fn reclaim(&mut self, count: usize) {
+        let Some(reclaimer) = self.reclaimer.clone() else {
+            return;
+        };
+
+        for frame in reclaimer.reclaim(count) {
+            self.dealloc(frame);
+        }
+    }
+
+    fn take_frame(&mut self) -> Option<FrameDesc> {
         match self.recycled.pop() {
             Some(pa) => Some(unsafe { FrameDesc::new(pa) }),
             None => match self.current {
@@ -52,12 +78,28 @@ impl IFrameAllocator for FrameAllocator {
             },
         }
     }
+}
+
+impl IFrameAllocator for FrameAllocator {
+    fn alloc_frame(&mut self) -> Option<FrameDesc> {
+        if let Some(frame) = self.take_frame() {
+            return Some(frame);
+        }
+
+        // Out of frames, make room with what the reclaimer gives back
+        self.reclaim(1);
+
+        self.take_frame()
+    }
 
     fn alloc_frames(&mut self, count: usize) -> Option<Vec<FrameDesc>> {
         let mut frames = Vec::with_capacity(count);
 
-        let avaliable =
-            self.recycled.len() + (self.top - self.bottom).as_usize() / (constants::PAGE_SIZE);
+        if self.free_frames() < count {
+            self.reclaim(count - self.free_frames());
+        }
+
+        let avaliable = self.free_frames();
 
         match count {
             count if count <= avaliable => {
@@ -141,3 +183,48 @@ impl IFrameAllocator for FrameAllocator {
         core::mem::forget(range);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hermit_sync::SpinMutex;
+
+    use super::*;
+
+    /// Hands back the frames it was given.
+    struct HeldFrames(SpinMutex<Vec<FrameDesc>>);
+
+    impl IFrameReclaimer for HeldFrames {
+        fn reclaim(&self, count: usize) -> Vec<FrameDesc> {
+            let mut held = self.0.lock();
+            let keep = held.len().saturating_sub(count);
+
+            held.split_off(keep)
+        }
+    }
+
+    #[test]
+    fn test_out_of_frames_asks_the_reclaimer() {
+        let bottom = PhysicalAddress::from_usize(0x1000);
+        let mut allocator = FrameAllocator::new(bottom + 3 * constants::PAGE_SIZE, bottom);
+        let held = Arc::new(HeldFrames(SpinMutex::new(Vec::new())));
+        allocator.set_reclaimer(Some(held.clone()));
+
+        let frames = allocator.alloc_frames(3).unwrap();
+        assert!(allocator.alloc_frame().is_none());
+
+        held.0.lock().extend(frames);
+
+        let frame = allocator.alloc_frame().unwrap();
+        assert_eq!(held.0.lock().len(), 2);
+
+        let frames = allocator.alloc_frames(2).unwrap();
+        assert!(held.0.lock().is_empty());
+        assert!(allocator.alloc_frames(1).is_none());
+
+        allocator.dealloc(frame);
+        for frame in frames {
+            allocator.dealloc(frame);
+        }
+        assert_eq!(allocator.free_frames(), 3);
+    }
+}
@@ -0,0 +1,392 @@
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use hermit_sync::SpinMutex;
+
+use crate::{check_range, IBlockDevice};
+
+struct CachedBlock {
+    data: Box<[u8]>,
+    dirty: bool,
+    last_used: u64,
+}
+
+struct CacheInner {
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// Cached blocks by the tick of their last use, the first one is evicted next
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+/// A write-back cache of the least recently used blocks of a device.
+///
+/// Written blocks reach the device when they are evicted, on [`IBlockDevice::flush`] and when
+/// the cache is dropped.
+pub struct BlockCache {
+    device: Arc<dyn IBlockDevice>,
+    block_size: usize,
+    capacity: usize,
+    inner: SpinMutex<CacheInner>,
+}
+
+impl BlockCache {
+    /// Caches up to `capacity` blocks of `device`.
+    pub fn new(device: Arc<dyn IBlockDevice>, capacity: usize) -> BlockCache {
+        BlockCache {
+            block_size: device.block_size(),
+            device,
+            capacity: capacity.max(1),
+            inner: SpinMutex::new(CacheInner {
+                blocks: BTreeMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    pub fn device(&self) -> &Arc<dyn IBlockDevice> {
+        &self.device
+    }
+
+    /// Reads `buffer.len()` bytes at byte `offset` of the device, which may span blocks.
+    pub fn read_at(&self, offset: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+        self.check_bytes(offset, buffer.len())?;
+
+        let mut inner = self.inner.lock();
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let (block, in_block, len) = self.split(offset, done, buffer.len());
+
+            let cached = self.entry(&mut inner, block, true)?;
+            buffer[done..done + len].copy_from_slice(&cached.data[in_block..in_block + len]);
+
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `buffer` at byte `offset` of the device, which may span blocks.
+    pub fn write_at(&self, offset: u64, buffer: &[u8]) -> FileSystemResult<()> {
+        if self.device.is_readonly() {
+            return Err(FileSystemError::ReadOnly);
+        }
+
+        self.check_bytes(offset, buffer.len())?;
+
+        let mut inner = self.inner.lock();
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let (block, in_block, len) = self.split(offset, done, buffer.len());
+
+            // A block that is overwritten completely does not have to be read first
+            let cached = self.entry(&mut inner, block, len != self.block_size)?;
+            cached.data[in_block..in_block + len].copy_from_slice(&buffer[done..done + len]);
+            cached.dirty = true;
+
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Drops every cached block without writing it back, for when the device changed underneath.
+    pub fn invalidate(&self) {
+        let mut inner = self.inner.lock();
+
+        inner.blocks.clear();
+        inner.recency.clear();
+    }
+
+    fn check_bytes(&self, offset: u64, len: usize) -> FileSystemResult<()> {
+        let size = self.device.block_count() * self.block_size as u64;
+
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= size => Ok(()),
+            _ => Err(FileSystemError::InvalidInput),
+        }
+    }
+
+    /// The block, the offset in it and the length of the next piece of a transfer of `total`
+    /// bytes at `offset`, of which `done` bytes are done.
+    fn split(&self, offset: u64, done: usize, total: usize) -> (u64, usize, usize) {
+        let position = offset + done as u64;
+
+        let block = position / self.block_size as u64;
+        let in_block = (position % self.block_size as u64) as usize;
+        let len = (self.block_size - in_block).min(total - done);
+
+        (block, in_block, len)
+    }
+
+    /// The cached copy of `block`, read from the device if `fill` is set.
+    fn entry<'a>(
+        &self,
+        inner: &'a mut CacheInner,
+        block: u64,
+        fill: bool,
+    ) -> FileSystemResult<&'a mut CachedBlock> {
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        match inner.blocks.get_mut(&block) {
+            Some(cached) => {
+                inner.recency.remove(&cached.last_used);
+                cached.last_used = tick;
+            }
+            None => {
+                if inner.blocks.len() >= self.capacity {
+                    self.evict(inner)?;
+                }
+
+                let mut data = vec![0; self.block_size].into_boxed_slice();
+
+                if fill {
+                    self.device.read_blocks(block, &mut data)?;
+                }
+
+                inner.blocks.insert(
+                    block,
+                    CachedBlock {
+                        data,
+                        dirty: false,
+                        last_used: tick,
+                    },
+                );
+            }
+        }
+
+        inner.recency.insert(tick, block);
+
+        Ok(inner.blocks.get_mut(&block).unwrap())
+    }
+
+    fn evict(&self, inner: &mut CacheInner) -> FileSystemResult<()> {
+        let Some((&tick, &block)) = inner.recency.first_key_value() else {
+            return Ok(());
+        };
+
+        let victim = &inner.blocks[&block];
+
+        // Keep the block if it can not be written back, nothing is lost that way
+        if victim.dirty {
+            self.device.write_blocks(block, &victim.data)?;
+        }
+
+        inner.recency.remove(&tick);
+        inner.blocks.remove(&block);
+
+        Ok(())
+    }
+
+    fn write_back(&self) -> FileSystemResult<()> {
+        let mut inner = self.inner.lock();
+
+        for (&block, cached) in inner.blocks.iter_mut().filter(|(_, c)| c.dirty) {
+            self.device.write_blocks(block, &cached.data)?;
+            cached.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl IBlockDevice for BlockCache {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        self.device.block_count()
+    }
+
+    fn read_blocks(&self, first: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+        check_range(self, first, buffer.len())?;
+
+        self.read_at(first * self.block_size as u64, buffer)
+    }
+
+    fn write_blocks(&self, first: u64, buffer: &[u8]) -> FileSystemResult<()> {
+        check_range(self, first, buffer.len())?;
+
+        self.write_at(first * self.block_size as u64, buffer)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.write_back()?;
+
+        self.device.flush()
+    }
+
+    fn is_readonly(&self) -> bool {
+        self.device.is_readonly()
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("Failed to write back cached blocks: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::MemoryBlockDevice;
+
+    /// Counts the transfers that reach the device.
+    struct CountingDevice {
+        inner: MemoryBlockDevice,
+        reads: AtomicUsize,
+        writes: AtomicUsize,
+    }
+
+    impl CountingDevice {
+        fn new(block_count: u64) -> Arc<CountingDevice> {
+            Arc::new(CountingDevice {
+                inner: MemoryBlockDevice::new(512, block_count),
+                reads: AtomicUsize::new(0),
+                writes: AtomicUsize::new(0),
+            })
+        }
+
+        fn reads(&self) -> usize {
+            self.reads.load(Ordering::Relaxed)
+        }
+
+        fn writes(&self) -> usize {
+            self.writes.load(Ordering::Relaxed)
+        }
+    }
+
+    impl IBlockDevice for CountingDevice {
+        fn block_size(&self) -> usize {
+            self.inner.block_size()
+        }
+
+        fn block_count(&self) -> u64 {
+            self.inner.block_count()
+        }
+
+        fn read_blocks(&self, first: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            self.inner.read_blocks(first, buffer)
+        }
+
+        fn write_blocks(&self, first: u64, buffer: &[u8]) -> FileSystemResult<()> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+            self.inner.write_blocks(first, buffer)
+        }
+    }
+
+    #[test]
+    fn test_reads_are_cached() {
+        let device = CountingDevice::new(8);
+        let cache = BlockCache::new(device.clone(), 4);
+
+        let mut buffer = [0; 16];
+        cache.read_at(0, &mut buffer).unwrap();
+        cache.read_at(100, &mut buffer).unwrap();
+
+        assert_eq!(device.reads(), 1);
+    }
+
+    #[test]
+    fn test_write_back() {
+        let device = CountingDevice::new(8);
+        let cache = BlockCache::new(device.clone(), 4);
+
+        cache.write_at(510, b"hello").unwrap();
+        assert_eq!(device.writes(), 0);
+
+        let mut buffer = [0; 5];
+        cache.read_at(510, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hello");
+
+        cache.flush().unwrap();
+        assert_eq!(device.writes(), 2);
+
+        let mut raw = [0; 1024];
+        device.read_blocks(0, &mut raw).unwrap();
+        assert_eq!(&raw[510..515], b"hello");
+
+        // Nothing is dirty anymore
+        cache.flush().unwrap();
+        assert_eq!(device.writes(), 2);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let device = CountingDevice::new(8);
+        let cache = BlockCache::new(device.clone(), 2);
+
+        let mut buffer = [0; 1];
+        cache.read_at(0, &mut buffer).unwrap();
+        cache.write_at(512, &[7]).unwrap();
+        cache.read_at(0, &mut buffer).unwrap();
+
+        // Block 1 was used least recently, it is written back to make room
+        cache.read_at(1024, &mut buffer).unwrap();
+        assert_eq!(device.writes(), 1);
+        assert_eq!(device.reads(), 3);
+
+        cache.read_at(0, &mut buffer).unwrap();
+        assert_eq!(device.reads(), 3);
+
+        cache.read_at(512, &mut buffer).unwrap();
+        assert_eq!(buffer, [7]);
+        assert_eq!(device.reads(), 4);
+    }
+
+    #[test]
+    fn test_whole_block_writes_skip_reading() {
+        let device = CountingDevice::new(8);
+        let cache = BlockCache::new(device.clone(), 4);
+
+        cache.write_blocks(2, &[1; 1024]).unwrap();
+        assert_eq!(device.reads(), 0);
+
+        let mut buffer = [0; 1536];
+        cache.read_blocks(1, &mut buffer).unwrap();
+        assert_eq!(buffer[..512], [0; 512]);
+        assert_eq!(buffer[512..], [1; 1024]);
+    }
+
+    #[test]
+    fn test_drop_writes_back() {
+        let device = CountingDevice::new(8);
+
+        {
+            let cache = BlockCache::new(device.clone(), 4);
+            cache.write_at(3000, b"data").unwrap();
+        }
+
+        let mut buffer = [0; 512];
+        device.read_blocks(5, &mut buffer).unwrap();
+        assert_eq!(&buffer[440..444], b"data");
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let device = CountingDevice::new(8);
+        let cache = BlockCache::new(device.clone(), 4);
+
+        assert_eq!(
+            cache.read_at(4095, &mut [0; 2]),
+            Err(FileSystemError::InvalidInput)
+        );
+        assert_eq!(
+            cache.write_at(u64::MAX, &[0]),
+            Err(FileSystemError::InvalidInput)
+        );
+        assert_eq!(
+            cache.read_blocks(0, &mut [0; 10]),
+            Err(FileSystemError::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,57 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod cache;
+mod memory;
+mod registry;
+
+pub use cache::BlockCache;
+pub use memory::MemoryBlockDevice;
+pub use registry::BlockDevices;
+
+/// A device addressed in fixed size blocks, like a disk or a disk image.
+pub trait IBlockDevice: Send + Sync {
+    fn block_size(&self) -> usize;
+
+    fn block_count(&self) -> u64;
+
+    /// Reads the blocks starting at `first` into `buffer`, whose length must be a multiple of
+    /// the block size.
+    fn read_blocks(&self, first: u64, buffer: &mut [u8]) -> FileSystemResult<()>;
+
+    /// Writes `buffer`, whose length must be a multiple of the block size, to the blocks
+    /// starting at `first`.
+    fn write_blocks(&self, first: u64, buffer: &[u8]) -> FileSystemResult<()>;
+
+    /// Makes written blocks durable.
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn is_readonly(&self) -> bool {
+        false
+    }
+}
+
+/// Checks that a transfer of `len` bytes starting at block `first` is whole blocks inside the
+/// device, returns the number of blocks.
+pub fn check_range(device: &dyn IBlockDevice, first: u64, len: usize) -> FileSystemResult<u64> {
+    let block_size = device.block_size();
+
+    if !len.is_multiple_of(block_size) {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    let count = (len / block_size) as u64;
+
+    match first.checked_add(count) {
+        Some(end) if end <= device.block_count() => Ok(count),
+        _ => Err(FileSystemError::InvalidInput),
+    }
+}
@@ -0,0 +1,104 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use hermit_sync::SpinMutex;
+
+use crate::{check_range, IBlockDevice};
+
+/// A block device held in memory.
+pub struct MemoryBlockDevice {
+    block_size: usize,
+    data: SpinMutex<Vec<u8>>,
+}
+
+impl MemoryBlockDevice {
+    /// A zeroed device of `block_count` blocks.
+    pub fn new(block_size: usize, block_count: u64) -> MemoryBlockDevice {
+        MemoryBlockDevice {
+            block_size,
+            data: SpinMutex::new(vec![0; block_size * block_count as usize]),
+        }
+    }
+
+    /// A device over an existing image, whose length must be a multiple of `block_size`.
+    pub fn from_image(block_size: usize, image: Vec<u8>) -> FileSystemResult<MemoryBlockDevice> {
+        if block_size == 0 || !image.len().is_multiple_of(block_size) {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        Ok(MemoryBlockDevice {
+            block_size,
+            data: SpinMutex::new(image),
+        })
+    }
+
+    pub fn into_image(self) -> Vec<u8> {
+        self.data.into_inner()
+    }
+}
+
+impl IBlockDevice for MemoryBlockDevice {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.data.lock().len() / self.block_size) as u64
+    }
+
+    fn read_blocks(&self, first: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+        check_range(self, first, buffer.len())?;
+
+        let offset = first as usize * self.block_size;
+        buffer.copy_from_slice(&self.data.lock()[offset..offset + buffer.len()]);
+
+        Ok(())
+    }
+
+    fn write_blocks(&self, first: u64, buffer: &[u8]) -> FileSystemResult<()> {
+        check_range(self, first, buffer.len())?;
+
+        let offset = first as usize * self.block_size;
+        self.data.lock()[offset..offset + buffer.len()].copy_from_slice(buffer);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let device = MemoryBlockDevice::new(512, 4);
+
+        device.write_blocks(1, &[0xaa; 1024]).unwrap();
+
+        let mut buffer = [0; 512];
+        device.read_blocks(2, &mut buffer).unwrap();
+        assert_eq!(buffer, [0xaa; 512]);
+
+        device.read_blocks(0, &mut buffer).unwrap();
+        assert_eq!(buffer, [0; 512]);
+    }
+
+    #[test]
+    fn test_rejects_bad_ranges() {
+        let device = MemoryBlockDevice::new(512, 4);
+
+        assert_eq!(
+            device.read_blocks(0, &mut [0; 100]),
+            Err(FileSystemError::InvalidInput)
+        );
+        assert_eq!(
+            device.write_blocks(3, &[0; 1024]),
+            Err(FileSystemError::InvalidInput)
+        );
+        assert_eq!(
+            device.read_blocks(u64::MAX, &mut [0; 512]),
+            Err(FileSystemError::InvalidInput)
+        );
+
+        assert!(MemoryBlockDevice::from_image(512, vec![0; 700]).is_err());
+    }
+}
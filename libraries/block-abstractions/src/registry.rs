@@ -0,0 +1,66 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use hermit_sync::SpinMutex;
+
+use crate::IBlockDevice;
+
+/// The block devices of a kernel by name, where filesystems on disks find what to mount.
+#[derive(Default)]
+pub struct BlockDevices {
+    devices: SpinMutex<BTreeMap<String, Arc<dyn IBlockDevice>>>,
+}
+
+impl BlockDevices {
+    pub fn new() -> BlockDevices {
+        BlockDevices::default()
+    }
+
+    /// Adds a device called `name`, like `vda`, which fails with `AlreadyExists` if there is one.
+    pub fn register(&self, name: &str, device: Arc<dyn IBlockDevice>) -> FileSystemResult<()> {
+        let mut devices = self.devices.lock();
+
+        if devices.contains_key(name) {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        devices.insert(String::from(name), device);
+
+        Ok(())
+    }
+
+    /// The device `source` names, either by its name or its path in `/dev`.
+    pub fn get(&self, source: &str) -> FileSystemResult<Arc<dyn IBlockDevice>> {
+        let name = source.strip_prefix("/dev/").unwrap_or(source);
+
+        self.devices
+            .lock()
+            .get(name)
+            .cloned()
+            .ok_or(FileSystemError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBlockDevice;
+
+    #[test]
+    fn test_finds_by_name_and_path() {
+        let devices = BlockDevices::new();
+        let disk: Arc<dyn IBlockDevice> = Arc::new(MemoryBlockDevice::new(512, 8));
+
+        devices.register("vda", disk.clone()).unwrap();
+        assert_eq!(
+            devices.register("vda", disk.clone()).err(),
+            Some(FileSystemError::AlreadyExists)
+        );
+
+        assert!(Arc::ptr_eq(&devices.get("vda").unwrap(), &disk));
+        assert!(Arc::ptr_eq(&devices.get("/dev/vda").unwrap(), &disk));
+        assert_eq!(
+            devices.get("/dev/vdb").err(),
+            Some(FileSystemError::NotFound)
+        );
+    }
+}
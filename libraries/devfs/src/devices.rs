@@ -0,0 +1,194 @@
+use alloc::sync::Arc;
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use hermit_sync::SpinMutex;
+use kernel_abstractions::IKernelSerial;
+
+use crate::ICharDevice;
+
+/// `/dev/null`, reads nothing and discards whatever is written.
+pub struct NullDevice;
+
+impl NullDevice {
+    pub fn new() -> Arc<NullDevice> {
+        Arc::new(NullDevice)
+    }
+}
+
+impl ICharDevice for NullDevice {
+    fn read(&self, _offset: usize, _buffer: &mut [u8]) -> FileSystemResult<usize> {
+        Ok(0)
+    }
+
+    fn write(&self, _offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        Ok(buffer.len())
+    }
+}
+
+/// `/dev/zero`, reads as many zeros as asked for and discards whatever is written.
+pub struct ZeroDevice;
+
+impl ZeroDevice {
+    pub fn new() -> Arc<ZeroDevice> {
+        Arc::new(ZeroDevice)
+    }
+}
+
+impl ICharDevice for ZeroDevice {
+    fn read(&self, _offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        buffer.fill(0);
+
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        Ok(buffer.len())
+    }
+}
+
+/// `/dev/full`, reads zeros like `/dev/zero` but is always out of space for writes.
+pub struct FullDevice;
+
+impl FullDevice {
+    pub fn new() -> Arc<FullDevice> {
+        Arc::new(FullDevice)
+    }
+}
+
+impl ICharDevice for FullDevice {
+    fn read(&self, _offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        buffer.fill(0);
+
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _offset: usize, _buffer: &[u8]) -> FileSystemResult<usize> {
+        Err(FileSystemError::SpaceNotEnough)
+    }
+}
+
+/// `/dev/random` and `/dev/urandom`, a xoshiro256** generator that never blocks.
+///
+/// It is not cryptographically secure. Written bytes are mixed into the state, so whoever has
+/// entropy can feed it in.
+pub struct RandomDevice {
+    state: SpinMutex<[u64; 4]>,
+}
+
+impl RandomDevice {
+    pub fn new(seed: u64) -> Arc<RandomDevice> {
+        let mut state = [0; 4];
+        let mut seed = seed;
+
+        for word in state.iter_mut() {
+            *word = split_mix(&mut seed);
+        }
+
+        Arc::new(RandomDevice {
+            state: SpinMutex::new(state),
+        })
+    }
+
+    fn next(state: &mut [u64; 4]) -> u64 {
+        let result = state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = state[1] << 17;
+
+        state[2] ^= state[0];
+        state[3] ^= state[1];
+        state[1] ^= state[2];
+        state[0] ^= state[3];
+        state[2] ^= t;
+        state[3] = state[3].rotate_left(45);
+
+        result
+    }
+}
+
+/// SplitMix64, spreads a seed over the state so similar seeds give unrelated states.
+fn split_mix(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl ICharDevice for RandomDevice {
+    fn read(&self, _offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let mut state = self.state.lock();
+
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = Self::next(&mut state).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn write(&self, _offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        let mut state = self.state.lock();
+
+        for chunk in buffer.chunks(8) {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+
+            let mut seed = u64::from_le_bytes(bytes) ^ Self::next(&mut state);
+            state[0] ^= split_mix(&mut seed);
+        }
+
+        // An all-zero state would only ever give zeros
+        if state.iter().all(|&word| word == 0) {
+            state[0] = 1;
+        }
+
+        Ok(buffer.len())
+    }
+}
+
+/// A serial port, like `/dev/ttyS0`. Reads return what has arrived so far without waiting.
+pub struct SerialDevice {
+    serial: Arc<dyn IKernelSerial>,
+}
+
+impl SerialDevice {
+    pub fn new(serial: Arc<dyn IKernelSerial>) -> Arc<SerialDevice> {
+        Arc::new(SerialDevice { serial })
+    }
+}
+
+unsafe impl Send for SerialDevice {}
+unsafe impl Sync for SerialDevice {}
+
+impl ICharDevice for SerialDevice {
+    fn read(&self, _offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let mut read = 0;
+
+        for byte in buffer.iter_mut() {
+            match self.serial.recv() {
+                Some(received) => *byte = received,
+                None => break,
+            }
+
+            read += 1;
+        }
+
+        Ok(read)
+    }
+
+    fn write(&self, _offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        let mut written = 0;
+
+        for &byte in buffer {
+            if self.serial.send(byte).is_err() {
+                break;
+            }
+
+            written += 1;
+        }
+
+        match written {
+            0 if !buffer.is_empty() => Err(FileSystemError::WriteZero),
+            written => Ok(written),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The `/dev` filesystem, a flat directory of character devices that drivers register.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicU64, Ordering};
+use filesystem_abstractions::{
+    FileSystemError, FileSystemResult, IFileSystem, IFileSystemType, IInode, MountFlags,
+};
+use hermit_sync::SpinMutex;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod devices;
+mod node;
+
+pub use devices::{FullDevice, NullDevice, RandomDevice, SerialDevice, ZeroDevice};
+pub use node::{DevRootInode, DeviceInode};
+
+use node::Device;
+
+/// What backs a character device node. The offset is the one of the open file, most devices
+/// ignore it.
+pub trait ICharDevice: Send + Sync {
+    fn read(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize>;
+
+    fn write(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize>;
+}
+
+/// The major and minor number of a device, see `Documentation/admin-guide/devices.txt` of Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceNumber {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl DeviceNumber {
+    pub const NULL: DeviceNumber = DeviceNumber::new(1, 3);
+    pub const ZERO: DeviceNumber = DeviceNumber::new(1, 5);
+    pub const FULL: DeviceNumber = DeviceNumber::new(1, 7);
+    pub const RANDOM: DeviceNumber = DeviceNumber::new(1, 8);
+    pub const URANDOM: DeviceNumber = DeviceNumber::new(1, 9);
+    pub const TTY: DeviceNumber = DeviceNumber::new(5, 0);
+    pub const CONSOLE: DeviceNumber = DeviceNumber::new(5, 1);
+
+    pub const fn new(major: u32, minor: u32) -> DeviceNumber {
+        DeviceNumber { major, minor }
+    }
+
+    /// The `n`th serial port, `ttyS<n>`.
+    pub const fn serial(n: u32) -> DeviceNumber {
+        DeviceNumber::new(4, 64 + n)
+    }
+
+    /// Encodes the number as `st_rdev`, like `makedev` of glibc.
+    pub const fn encode(self) -> u64 {
+        let major = self.major as u64;
+        let minor = self.minor as u64;
+
+        ((major & 0xffff_f000) << 32)
+            | ((major & 0x0000_0fff) << 8)
+            | ((minor & 0xffff_ff00) << 12)
+            | (minor & 0x0000_00ff)
+    }
+}
+
+/// The registry of device nodes, which is also the filesystem showing them.
+pub struct DevFileSystem {
+    this: Weak<DevFileSystem>,
+    devices: SpinMutex<BTreeMap<String, Device>>,
+    next_inode: AtomicU64,
+}
+
+impl DevFileSystem {
+    const ROOT_INODE: u64 = 1;
+
+    /// Creates a filesystem without any device.
+    pub fn new() -> Arc<DevFileSystem> {
+        Arc::new_cyclic(|this| DevFileSystem {
+            this: this.clone(),
+            devices: SpinMutex::new(BTreeMap::new()),
+            next_inode: AtomicU64::new(Self::ROOT_INODE + 1),
+        })
+    }
+
+    /// Creates a filesystem with `null`, `zero`, `full`, `random` and `urandom`, the random
+    /// devices share one generator that starts from `seed`.
+    pub fn with_defaults(seed: u64) -> Arc<DevFileSystem> {
+        let fs = Self::new();
+        let random = RandomDevice::new(seed);
+
+        let devices: [(&str, DeviceNumber, Arc<dyn ICharDevice>); 5] = [
+            ("null", DeviceNumber::NULL, NullDevice::new()),
+            ("zero", DeviceNumber::ZERO, ZeroDevice::new()),
+            ("full", DeviceNumber::FULL, FullDevice::new()),
+            ("random", DeviceNumber::RANDOM, random.clone()),
+            ("urandom", DeviceNumber::URANDOM, random),
+        ];
+
+        for (name, number, device) in devices {
+            fs.register(name, number, device)
+                .expect("The filesystem is empty");
+        }
+
+        fs
+    }
+
+    /// Adds a device node called `name`, which fails with `AlreadyExists` if there is one.
+    pub fn register(
+        &self,
+        name: &str,
+        number: DeviceNumber,
+        device: Arc<dyn ICharDevice>,
+    ) -> FileSystemResult<()> {
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        if name.contains('/') || name.contains('\0') {
+            return Err(FileSystemError::PathContainsInvalidCharacter);
+        }
+
+        let mut devices = self.devices.lock();
+
+        if devices.contains_key(name) {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        devices.insert(
+            name.to_string(),
+            Device {
+                number,
+                inode_id: self.next_inode.fetch_add(1, Ordering::Relaxed),
+                device,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Removes the device node called `name`. Files already open on it keep working.
+    pub fn unregister(&self, name: &str) -> FileSystemResult<()> {
+        match self.devices.lock().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(FileSystemError::NotFound),
+        }
+    }
+}
+
+impl IFileSystem for DevFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        DevRootInode::new(self.this.upgrade().unwrap())
+    }
+
+    fn name(&self) -> &str {
+        "devtmpfs"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+}
+
+/// Lets `mount` show the device nodes elsewhere, every mount is the same registry.
+pub struct DevFileSystemType {
+    fs: Arc<DevFileSystem>,
+}
+
+impl DevFileSystemType {
+    pub fn new(fs: Arc<DevFileSystem>) -> Arc<DevFileSystemType> {
+        Arc::new(DevFileSystemType { fs })
+    }
+}
+
+impl IFileSystemType for DevFileSystemType {
+    fn name(&self) -> &str {
+        "devtmpfs"
+    }
+
+    fn create(&self, _source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        Ok(self.fs.clone())
+    }
+
+    fn is_cacheable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use filesystem_abstractions::{
+        DirectoryEntryType, DirectoryTreeNode, FileStatistics, FileStatisticsMode, IFile, OpenFlags,
+    };
+    use test_utilities::kernel::TestSerial;
+
+    use super::*;
+
+    fn setup(devfs: Arc<DevFileSystem>) -> Arc<DirectoryTreeNode> {
+        let root = DirectoryTreeNode::from_empty(None, String::new());
+        let dev = DirectoryTreeNode::from_filesystem(Some(root.clone()), devfs, Some("dev"));
+        root.mount_as(dev, Some("dev")).unwrap();
+
+        root
+    }
+
+    fn stat(root: &Arc<DirectoryTreeNode>, path: &str) -> FileStatistics {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        root.open(path, Some(root))
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+
+        stat
+    }
+
+    fn names(root: &Arc<DirectoryTreeNode>) -> Vec<String> {
+        let mut names: Vec<_> = root
+            .open("/dev", Some(root))
+            .unwrap()
+            .read_dir()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.filename)
+            .filter(|name| name != "." && name != "..")
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_defaults() {
+        let root = setup(DevFileSystem::with_defaults(0));
+
+        assert_eq!(names(&root), ["full", "null", "random", "urandom", "zero"]);
+
+        let null = root.open("/dev/null", Some(&root)).unwrap();
+        assert_eq!(null.metadata().entry_type, DirectoryEntryType::CharDevice);
+        assert_eq!(null.readat(0, &mut [0; 16]), Ok(0));
+        assert_eq!(null.writeat(0, &[1; 16]), Ok(16));
+
+        let mut buffer = [0xff; 16];
+        let zero = root.open("/dev/zero", Some(&root)).unwrap();
+        assert_eq!(zero.readat(100, &mut buffer), Ok(16));
+        assert_eq!(buffer, [0; 16]);
+        assert_eq!(zero.writeat(0, &[1; 16]), Ok(16));
+
+        let mut buffer = [0xff; 16];
+        let full = root.open("/dev/full", Some(&root)).unwrap();
+        assert_eq!(full.readat(0, &mut buffer), Ok(16));
+        assert_eq!(buffer, [0; 16]);
+        assert_eq!(
+            full.writeat(0, &[1; 16]),
+            Err(FileSystemError::SpaceNotEnough)
+        );
+    }
+
+    #[test]
+    fn test_stat() {
+        let root = setup(DevFileSystem::with_defaults(0));
+
+        let null = stat(&root, "/dev/null");
+        assert_eq!(null.mode.bits(), FileStatisticsMode::CHAR.bits() | 0o666);
+        assert_eq!(null.rdev, 0x103);
+
+        let urandom = stat(&root, "/dev/urandom");
+        assert_eq!(urandom.rdev, 0x109);
+        assert_ne!(urandom.inode_id, null.inode_id);
+
+        let dev = stat(&root, "/dev");
+        assert_eq!(dev.mode.bits(), FileStatisticsMode::DIR.bits() | 0o755);
+
+        // Large numbers are split around the 8 low bits of the minor number, like Linux
+        assert_eq!(
+            DeviceNumber::new(0x12345, 0x6789a).encode(),
+            0x0001_2000_6783_459a
+        );
+        assert_eq!(DeviceNumber::serial(1).encode(), 0x441);
+    }
+
+    #[test]
+    fn test_random() {
+        let read = |devfs: &Arc<DevFileSystem>, name: &str| {
+            let root = setup(devfs.clone());
+            let mut buffer = [0; 37];
+            let device = root.open(name, Some(&root)).unwrap();
+            assert_eq!(device.readat(0, &mut buffer), Ok(37));
+
+            buffer
+        };
+
+        let devfs = DevFileSystem::with_defaults(42);
+        let first = read(&devfs, "/dev/urandom");
+        let second = read(&devfs, "/dev/random");
+
+        assert_ne!(first, second);
+        assert_ne!(first, [0; 37]);
+
+        // The same seed gives the same bytes, unless something is written in between
+        let other = DevFileSystem::with_defaults(42);
+        assert_eq!(read(&other, "/dev/random"), first);
+
+        let mixed = DevFileSystem::with_defaults(42);
+        let root = setup(mixed.clone());
+        let random = root.open("/dev/random", Some(&root)).unwrap();
+        assert_eq!(random.writeat(0, b"entropy"), Ok(7));
+        assert_ne!(read(&mixed, "/dev/random"), first);
+    }
+
+    #[test]
+    fn test_registry() {
+        let devfs = DevFileSystem::new();
+        let root = setup(devfs.clone());
+        let serial = Arc::new(TestSerial::new());
+
+        assert!(names(&root).is_empty());
+
+        // The tree still knows the node while the file is open, a fresh one does not
+        let fresh = setup(devfs.clone());
+        assert_eq!(
+            fresh.open("/dev/ttyS0", Some(&fresh)).err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        devfs
+            .register(
+                "ttyS0",
+                DeviceNumber::serial(0),
+                SerialDevice::new(serial.clone()),
+            )
+            .unwrap();
+
+        assert_eq!(
+            devfs.register("ttyS0", DeviceNumber::serial(0), NullDevice::new()),
+            Err(FileSystemError::AlreadyExists)
+        );
+        assert_eq!(
+            devfs.register("a/b", DeviceNumber::NULL, NullDevice::new()),
+            Err(FileSystemError::PathContainsInvalidCharacter)
+        );
+        assert_eq!(
+            devfs.register("..", DeviceNumber::NULL, NullDevice::new()),
+            Err(FileSystemError::InvalidInput)
+        );
+
+        assert_eq!(names(&root), ["ttyS0"]);
+        assert_eq!(stat(&root, "/dev/ttyS0").rdev, 0x440);
+
+        // Reads and writes go through the open file like for any other file
+        let file = root
+            .open("/dev/ttyS0", Some(&root))
+            .unwrap()
+            .open_as_file(OpenFlags::O_RDWR, 0);
+
+        assert_eq!(file.write(b"hello"), 5);
+        assert_eq!(file.write(b"!\n"), 2);
+        assert_eq!(serial.content(), b"hello!\n");
+
+        let mut buffer = [0; 8];
+        assert_eq!(file.read(&mut buffer), 0);
+
+        serial.input(b"input");
+        assert_eq!(file.read(&mut buffer), 5);
+        assert_eq!(&buffer[..5], b"input");
+
+        devfs.unregister("ttyS0").unwrap();
+        assert_eq!(devfs.unregister("ttyS0"), Err(FileSystemError::NotFound));
+        assert!(names(&root).is_empty());
+
+        // The tree still knows the node while the file is open, a fresh one does not
+        let fresh = setup(devfs.clone());
+        assert_eq!(
+            fresh.open("/dev/ttyS0", Some(&fresh)).err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        // The open file stays usable after the node is gone
+        assert_eq!(file.write(b"bye"), 3);
+        assert_eq!(serial.content(), b"hello!\nbye");
+    }
+
+    #[test]
+    fn test_read_only_directory() {
+        let root = setup(DevFileSystem::with_defaults(0));
+        let dev = root.open("/dev", Some(&root)).unwrap();
+
+        assert_eq!(
+            dev.inode().unwrap().touch("file").err(),
+            Some(FileSystemError::NotPermitted)
+        );
+        assert_eq!(
+            dev.inode().unwrap().mkdir("dir").err(),
+            Some(FileSystemError::NotPermitted)
+        );
+        assert_eq!(
+            dev.inode().unwrap().remove("null"),
+            Err(FileSystemError::NotPermitted)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IInode, InodeMetadata,
+};
+use timing::TimeSpec;
+
+use crate::{DevFileSystem, DeviceNumber, ICharDevice};
+
+/// A registered device node.
+pub(crate) struct Device {
+    pub number: DeviceNumber,
+    pub inode_id: u64,
+    pub device: Arc<dyn ICharDevice>,
+}
+
+/// The directory holding all device nodes, what is in it is up to the registry.
+pub struct DevRootInode {
+    fs: Arc<DevFileSystem>,
+}
+
+impl DevRootInode {
+    pub(crate) fn new(fs: Arc<DevFileSystem>) -> Arc<DevRootInode> {
+        Arc::new(DevRootInode { fs })
+    }
+}
+
+impl IInode for DevRootInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: "",
+            entry_type: DirectoryEntryType::Directory,
+            size: 0,
+        }
+    }
+
+    fn mkdir(&self, _name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn rmdir(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn remove(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn touch(&self, _name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        Ok(self
+            .fs
+            .devices
+            .lock()
+            .keys()
+            .map(|name| DirectoryEntry {
+                filename: name.clone(),
+                entry_type: DirectoryEntryType::CharDevice,
+            })
+            .collect())
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let devices = self.fs.devices.lock();
+        let device = devices.get(name).ok_or(FileSystemError::NotFound)?;
+
+        Ok(DeviceInode::new(name, device))
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        fill_stat(
+            stat,
+            DevFileSystem::ROOT_INODE,
+            FileStatisticsMode::DIR,
+            0o755,
+            0,
+        );
+        stat.link_count = 2;
+
+        Ok(())
+    }
+}
+
+/// A character device, reads and writes go to the driver and offsets mean whatever it wants.
+pub struct DeviceInode {
+    name: String,
+    number: DeviceNumber,
+    inode_id: u64,
+    device: Arc<dyn ICharDevice>,
+}
+
+impl DeviceInode {
+    fn new(name: &str, device: &Device) -> Arc<DeviceInode> {
+        Arc::new(DeviceInode {
+            name: name.to_string(),
+            number: device.number,
+            inode_id: device.inode_id,
+            device: device.device.clone(),
+        })
+    }
+}
+
+impl IInode for DeviceInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: DirectoryEntryType::CharDevice,
+            size: 0,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        self.device.read(offset, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        self.device.write(offset, buffer)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        fill_stat(
+            stat,
+            self.inode_id,
+            FileStatisticsMode::CHAR,
+            0o666,
+            self.number.encode(),
+        );
+
+        Ok(())
+    }
+}
+
+fn fill_stat(
+    stat: &mut FileStatistics,
+    inode_id: u64,
+    file_type: FileStatisticsMode,
+    permissions: u32,
+    rdev: u64,
+) {
+    stat.device_id = 0;
+    stat.inode_id = inode_id;
+    stat.mode = FileStatisticsMode::from_bits_retain(file_type.bits() | permissions);
+    stat.link_count = 1;
+    stat.uid = 0;
+    stat.gid = 0;
+    stat.rdev = rdev;
+    stat.size = 0;
+    stat.block_size = 4096;
+    stat.block_count = 0;
+    stat.atime = TimeSpec::zero();
+    stat.mtime = TimeSpec::zero();
+    stat.ctime = TimeSpec::zero();
+}
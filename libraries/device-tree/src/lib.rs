@@ -0,0 +1,558 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A minimal reader of flattened device trees (`.dtb`), just enough to discover devices.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// The oldest version whose layout we understand, the one every current bootloader emits
+const FDT_MIN_VERSION: u32 = 16;
+
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Cells of `reg` when the parent does not say otherwise, as the specification defines
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceTreeError {
+    BadMagic,
+    UnsupportedVersion,
+    Truncated,
+    Malformed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub address: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Property<'a> {
+    pub name: &'a str,
+    pub value: &'a [u8],
+}
+
+/// An entry of `ranges`, a window of the bus below a node in the address space of its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range<'a> {
+    /// Where the window starts on the bus below, in cells whose meaning is up to its binding
+    pub child: &'a [u8],
+    pub parent: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    /// The unit name, like `virtio_mmio@10001000`
+    pub name: &'a str,
+    /// 0 for the root node
+    pub depth: usize,
+    pub properties: Vec<Property<'a>>,
+    /// `#address-cells` and `#size-cells` of the parent, which describe our `reg`
+    address_cells: u32,
+    size_cells: u32,
+    /// Our own `#address-cells` and `#size-cells`, which describe the bus below
+    child_address_cells: u32,
+    child_size_cells: u32,
+}
+
+impl<'a> Node<'a> {
+    pub fn property(&self, name: &str) -> Option<&'a [u8]> {
+        self.properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.value)
+    }
+
+    /// The entries of the `compatible` string list.
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> {
+        self.property("compatible")
+            .unwrap_or_default()
+            .split(|b| *b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible().any(|c| c == compatible)
+    }
+
+    pub fn reg(&self) -> Vec<Region> {
+        let cells = (self.address_cells + self.size_cells) as usize;
+
+        if cells == 0 {
+            return Vec::new();
+        }
+
+        self.property("reg")
+            .unwrap_or_default()
+            .chunks_exact(cells * 4)
+            .map(|entry| {
+                let (address, size) = entry.split_at(self.address_cells as usize * 4);
+
+                Region {
+                    address: read_cells(address),
+                    size: read_cells(size),
+                }
+            })
+            .collect()
+    }
+
+    pub fn ranges(&self) -> Vec<Range<'a>> {
+        let child = self.child_address_cells as usize * 4;
+        let parent = self.address_cells as usize * 4;
+        let size = self.child_size_cells as usize * 4;
+
+        if child + parent + size == 0 {
+            return Vec::new();
+        }
+
+        self.property("ranges")
+            .unwrap_or_default()
+            .chunks_exact(child + parent + size)
+            .map(|entry| Range {
+                child: &entry[..child],
+                parent: read_cells(&entry[child..child + parent]),
+                size: read_cells(&entry[child + parent..]),
+            })
+            .collect()
+    }
+
+    pub fn interrupts(&self) -> Vec<u32> {
+        self.property("interrupts")
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .map(read_cells_u32)
+            .collect()
+    }
+}
+
+fn read_cells_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().unwrap())
+}
+
+fn read_cells(bytes: &[u8]) -> u64 {
+    bytes
+        .chunks_exact(4)
+        .fold(0, |value, cell| value << 32 | read_cells_u32(cell) as u64)
+}
+
+pub struct DeviceTree<'a> {
+    structure: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> DeviceTree<'a> {
+    pub fn parse(blob: &'a [u8]) -> Result<DeviceTree<'a>, DeviceTreeError> {
+        let header = |index: usize| -> Result<u32, DeviceTreeError> {
+            blob.get(index * 4..index * 4 + 4)
+                .map(read_cells_u32)
+                .ok_or(DeviceTreeError::Truncated)
+        };
+
+        if header(0)? != FDT_MAGIC {
+            return Err(DeviceTreeError::BadMagic);
+        }
+
+        if header(5)? < FDT_MIN_VERSION {
+            return Err(DeviceTreeError::UnsupportedVersion);
+        }
+
+        let total_size = header(1)? as usize;
+        let structure_offset = header(2)? as usize;
+        let strings_offset = header(3)? as usize;
+        let strings_size = header(8)? as usize;
+        let structure_size = header(9)? as usize;
+
+        if blob.len() < total_size || total_size < FDT_HEADER_SIZE {
+            return Err(DeviceTreeError::Truncated);
+        }
+
+        let section = |offset: usize, size: usize| {
+            offset
+                .checked_add(size)
+                .filter(|end| *end <= total_size)
+                .map(|end| &blob[offset..end])
+                .ok_or(DeviceTreeError::Malformed)
+        };
+
+        Ok(DeviceTree {
+            structure: section(structure_offset, structure_size)?,
+            strings: section(strings_offset, strings_size)?,
+        })
+    }
+
+    /// Parses the device tree the bootloader left at `address`.
+    ///
+    /// # Safety
+    ///
+    /// `address` must point to readable memory holding at least the header, and the whole
+    /// tree if the magic matches. The memory must stay untouched for the returned lifetime.
+    pub unsafe fn from_raw(address: *const u8) -> Result<DeviceTree<'static>, DeviceTreeError> {
+        let header = unsafe { core::slice::from_raw_parts(address, FDT_HEADER_SIZE) };
+
+        if read_cells_u32(&header[..4]) != FDT_MAGIC {
+            return Err(DeviceTreeError::BadMagic);
+        }
+
+        let total_size = read_cells_u32(&header[4..8]) as usize;
+
+        DeviceTree::parse(unsafe { core::slice::from_raw_parts(address, total_size) })
+    }
+
+    /// All nodes in depth-first order.
+    pub fn nodes(&self) -> Result<Vec<Node<'a>>, DeviceTreeError> {
+        let mut nodes = Vec::new();
+        // `#address-cells` and `#size-cells` every open node declares for its children
+        let mut cells: Vec<(u32, u32)> = Vec::new();
+        // Index into `nodes` of every open node
+        let mut open: Vec<usize> = Vec::new();
+
+        let mut offset = 0;
+
+        loop {
+            let token = self.read_u32(offset)?;
+            offset += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = self.read_string(self.structure, offset)?;
+                    offset = align(offset + name.len() + 1);
+
+                    let (address_cells, size_cells) = cells
+                        .last()
+                        .copied()
+                        .unwrap_or((DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS));
+
+                    open.push(nodes.len());
+                    cells.push((DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS));
+
+                    nodes.push(Node {
+                        name,
+                        depth: open.len() - 1,
+                        properties: Vec::new(),
+                        address_cells,
+                        size_cells,
+                        child_address_cells: DEFAULT_ADDRESS_CELLS,
+                        child_size_cells: DEFAULT_SIZE_CELLS,
+                    });
+                }
+                FDT_END_NODE => {
+                    open.pop().ok_or(DeviceTreeError::Malformed)?;
+                    cells.pop();
+                }
+                FDT_PROP => {
+                    let len = self.read_u32(offset)? as usize;
+                    let name_offset = self.read_u32(offset + 4)? as usize;
+                    offset += 8;
+
+                    let value = self
+                        .structure
+                        .get(offset..offset + len)
+                        .ok_or(DeviceTreeError::Truncated)?;
+                    offset = align(offset + len);
+
+                    let name = self.read_string(self.strings, name_offset)?;
+                    let node = *open.last().ok_or(DeviceTreeError::Malformed)?;
+
+                    match name {
+                        "#address-cells" if len == 4 => {
+                            cells.last_mut().unwrap().0 = read_cells_u32(value);
+                            nodes[node].child_address_cells = read_cells_u32(value);
+                        }
+                        "#size-cells" if len == 4 => {
+                            cells.last_mut().unwrap().1 = read_cells_u32(value);
+                            nodes[node].child_size_cells = read_cells_u32(value);
+                        }
+                        _ => (),
+                    }
+
+                    nodes[node].properties.push(Property { name, value });
+                }
+                FDT_NOP => (),
+                FDT_END => break,
+                _ => return Err(DeviceTreeError::Malformed),
+            }
+        }
+
+        match open.is_empty() {
+            true => Ok(nodes),
+            false => Err(DeviceTreeError::Malformed),
+        }
+    }
+
+    /// Nodes whose `compatible` list contains `compatible`.
+    pub fn find_compatible(&self, compatible: &str) -> Result<Vec<Node<'a>>, DeviceTreeError> {
+        Ok(self
+            .nodes()?
+            .into_iter()
+            .filter(|node| node.is_compatible(compatible))
+            .collect())
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32, DeviceTreeError> {
+        self.structure
+            .get(offset..offset + 4)
+            .map(read_cells_u32)
+            .ok_or(DeviceTreeError::Truncated)
+    }
+
+    fn read_string(&self, section: &'a [u8], offset: usize) -> Result<&'a str, DeviceTreeError> {
+        let bytes = section.get(offset..).ok_or(DeviceTreeError::Truncated)?;
+        let len = bytes
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(DeviceTreeError::Truncated)?;
+
+        core::str::from_utf8(&bytes[..len]).map_err(|_| DeviceTreeError::Malformed)
+    }
+}
+
+const fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use super::*;
+
+    /// Assembles a blob the way `dtc` lays it out.
+    #[derive(Default)]
+    struct Builder {
+        structure: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl Builder {
+        fn begin(mut self, name: &str) -> Self {
+            self.token(FDT_BEGIN_NODE);
+            self.structure.extend_from_slice(name.as_bytes());
+            self.structure.push(0);
+            self.pad();
+            self
+        }
+
+        fn end(mut self) -> Self {
+            self.token(FDT_END_NODE);
+            self
+        }
+
+        fn property(mut self, name: &str, value: &[u8]) -> Self {
+            let name_offset = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+
+            self.token(FDT_PROP);
+            self.token(value.len() as u32);
+            self.token(name_offset);
+            self.structure.extend_from_slice(value);
+            self.pad();
+            self
+        }
+
+        fn cells(self, name: &str, cells: &[u32]) -> Self {
+            let value = cells
+                .iter()
+                .flat_map(|c| c.to_be_bytes())
+                .collect::<Vec<_>>();
+
+            self.property(name, &value)
+        }
+
+        fn token(&mut self, value: u32) {
+            self.structure.extend_from_slice(&value.to_be_bytes());
+        }
+
+        fn pad(&mut self) {
+            self.structure.resize(align(self.structure.len()), 0);
+        }
+
+        fn build(mut self) -> Vec<u8> {
+            self.token(FDT_END);
+
+            let structure_offset = FDT_HEADER_SIZE + 16;
+            let strings_offset = structure_offset + self.structure.len();
+            let total_size = strings_offset + self.strings.len();
+
+            let header = [
+                FDT_MAGIC,
+                total_size as u32,
+                structure_offset as u32,
+                strings_offset as u32,
+                FDT_HEADER_SIZE as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structure.len() as u32,
+            ];
+
+            let mut blob = header
+                .iter()
+                .flat_map(|c| c.to_be_bytes())
+                .collect::<Vec<_>>();
+
+            // an empty memory reservation map
+            blob.extend_from_slice(&[0; 16]);
+            blob.extend_from_slice(&self.structure);
+            blob.extend_from_slice(&self.strings);
+            blob
+        }
+    }
+
+    fn qemu_like_tree() -> Vec<u8> {
+        Builder::default()
+            .begin("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("soc")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("virtio_mmio@10008000")
+            .cells("interrupts", &[8])
+            .cells("reg", &[0, 0x1000_8000, 0, 0x1000])
+            .property("compatible", b"virtio,mmio\0")
+            .end()
+            .begin("serial@10000000")
+            .property("compatible", b"ns16550a\0")
+            .end()
+            .begin("virtio_mmio@10001000")
+            .cells("interrupts", &[1])
+            .cells("reg", &[0, 0x1000_1000, 0, 0x1000])
+            .property("compatible", b"virtio,mmio\0")
+            .end()
+            .end()
+            .end()
+            .build()
+    }
+
+    #[test]
+    fn test_find_compatible() {
+        let blob = qemu_like_tree();
+        let tree = DeviceTree::parse(&blob).unwrap();
+
+        let devices = tree.find_compatible("virtio,mmio").unwrap();
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "virtio_mmio@10008000");
+        assert_eq!(
+            devices[1].reg(),
+            vec![Region {
+                address: 0x1000_1000,
+                size: 0x1000
+            }]
+        );
+        assert_eq!(devices[1].interrupts(), vec![1]);
+        assert_eq!(devices[1].depth, 2);
+    }
+
+    #[test]
+    fn test_cells_of_parent() {
+        let blob = Builder::default()
+            .begin("")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[1])
+            .begin("device@2000")
+            .cells("reg", &[0x2000, 0x100, 0x3000, 0x10])
+            .end()
+            .end()
+            .build();
+
+        let tree = DeviceTree::parse(&blob).unwrap();
+        let nodes = tree.nodes().unwrap();
+
+        assert_eq!(
+            nodes[1].reg(),
+            vec![
+                Region {
+                    address: 0x2000,
+                    size: 0x100
+                },
+                Region {
+                    address: 0x3000,
+                    size: 0x10
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        // The windows of QEMU's PCI host bridge, with 3 cells of PCI address below
+        const IO_RANGE: [u32; 7] = [0x0100_0000, 0, 0, 0, 0x1800_4000, 0, 0xc000];
+        const MEMORY_RANGE: [u32; 7] =
+            [0x0200_0000, 0, 0x4000_0000, 0, 0x4000_0000, 0, 0x4000_0000];
+
+        let blob = Builder::default()
+            .begin("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("pcie@20000000")
+            .cells("#address-cells", &[3])
+            .cells("#size-cells", &[2])
+            .cells("ranges", &[IO_RANGE, MEMORY_RANGE].concat())
+            .end()
+            .end()
+            .build();
+
+        let tree = DeviceTree::parse(&blob).unwrap();
+        let ranges = tree.nodes().unwrap()[1].ranges();
+
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].parent, 0x1800_4000);
+        assert_eq!(ranges[0].size, 0xc000);
+        assert_eq!(ranges[1].child, &[2, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0]);
+        assert_eq!(ranges[1].parent, 0x4000_0000);
+        assert_eq!(ranges[1].size, 0x4000_0000);
+    }
+
+    #[test]
+    fn test_string_list() {
+        let blob = Builder::default()
+            .begin("")
+            .property("compatible", b"riscv-virtio\0simple-bus\0")
+            .end()
+            .build();
+
+        let tree = DeviceTree::parse(&blob).unwrap();
+        let root = &tree.nodes().unwrap()[0];
+
+        assert_eq!(
+            root.compatible().map(String::from).collect::<Vec<_>>(),
+            vec!["riscv-virtio", "simple-bus"]
+        );
+        assert!(root.is_compatible("simple-bus"));
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert_eq!(
+            DeviceTree::parse(&[0u8; 64]).err(),
+            Some(DeviceTreeError::BadMagic)
+        );
+
+        let mut blob = qemu_like_tree();
+        blob.truncate(blob.len() - 8);
+
+        assert_eq!(
+            DeviceTree::parse(&blob).err(),
+            Some(DeviceTreeError::Truncated)
+        );
+    }
+}
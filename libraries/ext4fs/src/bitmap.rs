@@ -0,0 +1,367 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    crc::crc32c,
+    group::{GROUP_BLOCK_UNINIT, GROUP_INODE_UNINIT},
+    superblock::{RO_COMPAT_GDT_CSUM, RO_COMPAT_METADATA_CSUM},
+    volume::Volume,
+};
+
+fn test_bit(bitmap: &[u8], bit: u32) -> bool {
+    bitmap[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], bit: u32) {
+    bitmap[(bit / 8) as usize] |= 1 << (bit % 8);
+}
+
+fn clear_bit(bitmap: &mut [u8], bit: u32) {
+    bitmap[(bit / 8) as usize] &= !(1 << (bit % 8));
+}
+
+/// The first clear bit in `from..to`.
+fn find_clear(bitmap: &[u8], from: u32, to: u32) -> Option<u32> {
+    let mut bit = from;
+
+    while bit < to {
+        // Skip bytes that are full
+        if bit.is_multiple_of(8) && bitmap[(bit / 8) as usize] == 0xff {
+            bit += 8;
+            continue;
+        }
+
+        if !test_bit(bitmap, bit) {
+            return Some(bit);
+        }
+
+        bit += 1;
+    }
+
+    None
+}
+
+impl Volume {
+    fn uninit_bg(&self) -> bool {
+        self.superblock
+            .has_ro_compat(RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM)
+    }
+
+    /// Builds the block bitmap of a group whose bitmap was never initialized, in which only the
+    /// metadata of the filesystem is in use.
+    fn initial_block_bitmap(&self, group: u32) -> Vec<u8> {
+        let block_size = self.block_size();
+        let first = self.geometry.group_first_block(group);
+        let blocks = self.geometry.group_blocks(group);
+
+        let mut bitmap = vec![0; block_size];
+
+        let mut mark = |block: u64, count: u64| {
+            for block in block..block + count {
+                if block >= first && block < first + blocks as u64 {
+                    set_bit(&mut bitmap, (block - first) as u32);
+                }
+            }
+        };
+
+        for (block, count) in self.geometry.base_metadata(group) {
+            mark(block, count);
+        }
+
+        let table_blocks = (self.geometry.inodes_per_group as usize * self.geometry.inode_size)
+            .div_ceil(block_size) as u64;
+
+        for descriptor in self.groups.iter() {
+            mark(descriptor.block_bitmap(), 1);
+            mark(descriptor.inode_bitmap(), 1);
+            mark(descriptor.inode_table(), table_blocks);
+        }
+
+        for bit in blocks..(block_size * 8) as u32 {
+            set_bit(&mut bitmap, bit);
+        }
+
+        bitmap
+    }
+
+    fn block_bitmap(&self, group: u32) -> FileSystemResult<Vec<u8>> {
+        let descriptor = &self.groups[group as usize];
+
+        match descriptor.has_flag(GROUP_BLOCK_UNINIT) {
+            true => Ok(self.initial_block_bitmap(group)),
+            false => self.disk.read_metadata(descriptor.block_bitmap()),
+        }
+    }
+
+    fn write_block_bitmap(&mut self, group: u32, bitmap: Vec<u8>) {
+        let checksum = crc32c(
+            self.checksum_seed,
+            &bitmap[..(self.geometry.blocks_per_group / 8) as usize],
+        );
+
+        let metadata_csum = self.metadata_csum();
+        let descriptor = &mut self.groups[group as usize];
+
+        if metadata_csum {
+            descriptor.set_block_bitmap_checksum(checksum);
+        }
+
+        descriptor.clear_flag(GROUP_BLOCK_UNINIT);
+
+        let block = descriptor.block_bitmap();
+        self.disk.write_metadata(block, bitmap);
+        self.mark_group_dirty(group);
+    }
+
+    fn inode_bitmap(&self, group: u32) -> FileSystemResult<Vec<u8>> {
+        let descriptor = &self.groups[group as usize];
+
+        if !descriptor.has_flag(GROUP_INODE_UNINIT) {
+            return self.disk.read_metadata(descriptor.inode_bitmap());
+        }
+
+        let mut bitmap = vec![0; self.block_size()];
+
+        for bit in self.geometry.inodes_per_group..(self.block_size() * 8) as u32 {
+            set_bit(&mut bitmap, bit);
+        }
+
+        Ok(bitmap)
+    }
+
+    fn write_inode_bitmap(&mut self, group: u32, bitmap: Vec<u8>) {
+        let checksum = crc32c(
+            self.checksum_seed,
+            &bitmap[..(self.geometry.inodes_per_group / 8) as usize],
+        );
+
+        let metadata_csum = self.metadata_csum();
+        let descriptor = &mut self.groups[group as usize];
+
+        if metadata_csum {
+            descriptor.set_inode_bitmap_checksum(checksum);
+        }
+
+        descriptor.clear_flag(GROUP_INODE_UNINIT);
+
+        let block = descriptor.inode_bitmap();
+        self.disk.write_metadata(block, bitmap);
+        self.mark_group_dirty(group);
+    }
+
+    /// Allocates up to `count` contiguous blocks as close after `goal` as possible, returns the
+    /// first block and how many were allocated.
+    pub fn allocate_blocks(&mut self, goal: u64, count: u32) -> FileSystemResult<(u64, u32)> {
+        self.ensure_writable()?;
+
+        if self.superblock.free_blocks_count() == 0 {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        let goal = match goal >= self.geometry.first_data_block && goal < self.geometry.blocks_count
+        {
+            true => goal,
+            false => self.geometry.first_data_block,
+        };
+
+        let start_group = self.geometry.group_of_block(goal);
+        let group_count = self.geometry.group_count;
+
+        for i in 0..=group_count {
+            let group = (start_group + i) % group_count;
+
+            if self.groups[group as usize].free_blocks() == 0 {
+                continue;
+            }
+
+            let blocks = self.geometry.group_blocks(group);
+            let mut bitmap = self.block_bitmap(group)?;
+
+            // Only the first visit of the goal's group starts at the goal
+            let from = match i {
+                0 => (goal - self.geometry.group_first_block(group)) as u32,
+                _ => 0,
+            };
+
+            let Some(first) = find_clear(&bitmap, from, blocks) else {
+                continue;
+            };
+
+            let mut len = 0;
+
+            while len < count && first + len < blocks && !test_bit(&bitmap, first + len) {
+                set_bit(&mut bitmap, first + len);
+                len += 1;
+            }
+
+            self.write_block_bitmap(group, bitmap);
+
+            let descriptor = &mut self.groups[group as usize];
+            descriptor.set_free_blocks(descriptor.free_blocks() - len);
+
+            let free = self.superblock.free_blocks_count();
+            self.superblock.set_free_blocks_count(free - len as u64);
+
+            return Ok((self.geometry.group_first_block(group) + first as u64, len));
+        }
+
+        Err(FileSystemError::SpaceNotEnough)
+    }
+
+    pub fn free_blocks(&mut self, start: u64, count: u64) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        let mut block = start;
+        let end = start + count;
+
+        while block < end {
+            let group = self.geometry.group_of_block(block);
+            let first = self.geometry.group_first_block(group);
+            let last = (first + self.geometry.group_blocks(group) as u64).min(end);
+
+            let mut bitmap = self.block_bitmap(group)?;
+            let mut freed = 0;
+
+            for block in block..last {
+                let bit = (block - first) as u32;
+
+                if !test_bit(&bitmap, bit) {
+                    log::warn!("ext4: freeing free block {}", block);
+                    continue;
+                }
+
+                clear_bit(&mut bitmap, bit);
+                self.disk.forget(block);
+                freed += 1;
+            }
+
+            self.write_block_bitmap(group, bitmap);
+
+            let descriptor = &mut self.groups[group as usize];
+            descriptor.set_free_blocks(descriptor.free_blocks() + freed);
+
+            let free = self.superblock.free_blocks_count();
+            self.superblock.set_free_blocks_count(free + freed as u64);
+
+            block = last;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates an inode, preferring the group of `near`.
+    pub fn allocate_inode(&mut self, near: u32, directory: bool) -> FileSystemResult<u32> {
+        self.ensure_writable()?;
+
+        if self.superblock.free_inodes_count() == 0 {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        let start_group = self.geometry.group_of_inode(near);
+        let group_count = self.geometry.group_count;
+        let per_group = self.geometry.inodes_per_group;
+
+        for i in 0..group_count {
+            let group = (start_group + i) % group_count;
+
+            if self.groups[group as usize].free_inodes() == 0 {
+                continue;
+            }
+
+            let mut bitmap = self.inode_bitmap(group)?;
+
+            // Inodes before the first one are reserved for the filesystem itself
+            let from = match group {
+                0 => self.superblock.first_inode() - 1,
+                _ => 0,
+            };
+
+            let Some(bit) = find_clear(&bitmap, from, per_group) else {
+                continue;
+            };
+
+            set_bit(&mut bitmap, bit);
+            self.write_inode_bitmap(group, bitmap);
+
+            let uninit_bg = self.uninit_bg();
+            let descriptor = &mut self.groups[group as usize];
+
+            descriptor.set_free_inodes(descriptor.free_inodes() - 1);
+
+            if directory {
+                descriptor.set_used_dirs(descriptor.used_dirs() + 1);
+            }
+
+            if uninit_bg && bit >= per_group - descriptor.itable_unused() {
+                descriptor.set_itable_unused(per_group - bit - 1);
+            }
+
+            let free = self.superblock.free_inodes_count();
+            self.superblock.set_free_inodes_count(free - 1);
+
+            return Ok(group * per_group + bit + 1);
+        }
+
+        Err(FileSystemError::SpaceNotEnough)
+    }
+
+    pub fn free_inode(&mut self, number: u32, directory: bool) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        let group = self.geometry.group_of_inode(number);
+        let bit = (number - 1) % self.geometry.inodes_per_group;
+
+        let mut bitmap = self.inode_bitmap(group)?;
+
+        if !test_bit(&bitmap, bit) {
+            log::warn!("ext4: freeing free inode {}", number);
+            return Ok(());
+        }
+
+        clear_bit(&mut bitmap, bit);
+        self.write_inode_bitmap(group, bitmap);
+
+        let descriptor = &mut self.groups[group as usize];
+        descriptor.set_free_inodes(descriptor.free_inodes() + 1);
+
+        if directory {
+            descriptor.set_used_dirs(descriptor.used_dirs().saturating_sub(1));
+        }
+
+        let free = self.superblock.free_inodes_count();
+        self.superblock.set_free_inodes_count(free + 1);
+
+        Ok(())
+    }
+
+    /// Whether `number` is marked in use in its inode bitmap.
+    #[cfg(test)]
+    pub fn inode_in_use(&self, number: u32) -> FileSystemResult<bool> {
+        let group = self.geometry.group_of_inode(number);
+        let bitmap = self.inode_bitmap(group)?;
+
+        Ok(test_bit(
+            &bitmap,
+            (number - 1) % self.geometry.inodes_per_group,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_clear() {
+        let mut bitmap = vec![0xff; 4];
+        assert_eq!(find_clear(&bitmap, 0, 32), None);
+
+        clear_bit(&mut bitmap, 19);
+        assert_eq!(find_clear(&bitmap, 0, 32), Some(19));
+        assert_eq!(find_clear(&bitmap, 20, 32), None);
+        assert_eq!(find_clear(&bitmap, 0, 19), None);
+
+        set_bit(&mut bitmap, 19);
+        assert!(test_bit(&bitmap, 19));
+    }
+}
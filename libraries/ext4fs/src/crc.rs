@@ -0,0 +1,89 @@
+//! The checksums ext4 and jbd2 use. Neither is inverted before or after, callers pass the seed.
+
+const fn crc32_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+const fn crc16_table(polynomial: u16) -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+static CRC32C: [u32; 256] = crc32_table(0x82f6_3b78);
+static CRC16: [u16; 256] = crc16_table(0xa001);
+
+/// Castagnoli CRC32, used by metadata_csum and jbd2 v2/v3 checksums.
+pub(crate) fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = CRC32C[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    crc
+}
+
+/// CRC16 of the older uninit_bg group descriptor checksums.
+pub(crate) fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc = CRC16[((crc ^ byte as u16) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        assert_eq!(!crc32c(!0, b"123456789"), 0xe306_9283);
+        assert_eq!(crc16(0, b"123456789"), 0xbb3d);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let whole = crc32c(!0, b"hello world");
+        let split = crc32c(crc32c(!0, b"hello "), b"world");
+
+        assert_eq!(whole, split);
+    }
+}
@@ -0,0 +1,454 @@
+use alloc::{string::String, vec, vec::Vec};
+use filesystem_abstractions::{DirectoryEntryType, FileSystemError, FileSystemResult};
+
+use crate::{
+    crc::crc32c,
+    inode::{Inode, FLAG_INDEX},
+    raw::{le16, le32, set_le16, set_le32},
+    volume::Volume,
+};
+
+/// Size of the fake entry at the end of leaf blocks holding their checksum.
+const TAIL_SIZE: usize = 12;
+const TAIL_FILE_TYPE: u8 = 0xde;
+
+pub(crate) const TYPE_UNKNOWN: u8 = 0;
+pub(crate) const TYPE_FILE: u8 = 1;
+pub(crate) const TYPE_DIRECTORY: u8 = 2;
+pub(crate) const TYPE_SYMLINK: u8 = 7;
+
+pub(crate) fn entry_type_of(file_type: u8) -> DirectoryEntryType {
+    match file_type {
+        1 => DirectoryEntryType::File,
+        2 => DirectoryEntryType::Directory,
+        3 => DirectoryEntryType::CharDevice,
+        4 => DirectoryEntryType::BlockDevice,
+        5 => DirectoryEntryType::NamedPipe,
+        6 => DirectoryEntryType::Socket,
+        7 => DirectoryEntryType::Symlink,
+        _ => DirectoryEntryType::Unknown,
+    }
+}
+
+/// Bytes an entry with a name of `name_len` bytes takes at least.
+pub(crate) fn entry_size(name_len: usize) -> usize {
+    (8 + name_len + 3) & !3
+}
+
+/// An entry in a directory block.
+pub(crate) struct RawEntry<'a> {
+    pub offset: usize,
+    pub inode: u32,
+    pub rec_len: usize,
+    pub name: &'a [u8],
+    pub file_type: u8,
+}
+
+/// The entries of the first `space` bytes of a directory block, including unused ones.
+pub(crate) fn parse_entries(
+    block: &[u8],
+    space: usize,
+    filetype: bool,
+) -> FileSystemResult<Vec<RawEntry<'_>>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while offset < space {
+        if offset + 8 > space {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        let rec_len = match le16(block, offset + 4) as usize {
+            // Only 64KiB blocks need lengths that do not fit
+            0 | 65535 => block.len(),
+            len => len,
+        };
+
+        let name_len = block[offset + 6] as usize;
+
+        if rec_len < 8
+            || !rec_len.is_multiple_of(4)
+            || offset + rec_len > space
+            || 8 + name_len > rec_len
+        {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        entries.push(RawEntry {
+            offset,
+            inode: le32(block, offset),
+            rec_len,
+            name: &block[offset + 8..offset + 8 + name_len],
+            file_type: match filetype {
+                true => block[offset + 7],
+                false => TYPE_UNKNOWN,
+            },
+        });
+
+        offset += rec_len;
+    }
+
+    Ok(entries)
+}
+
+fn write_entry(
+    block: &mut [u8],
+    offset: usize,
+    inode: u32,
+    rec_len: usize,
+    name: &[u8],
+    file_type: u8,
+) {
+    set_le32(block, offset, inode);
+    set_le16(block, offset + 4, rec_len as u16);
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = file_type;
+    block[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+}
+
+/// Puts an entry into the first `space` bytes of a leaf block, if there is room.
+pub(crate) fn leaf_insert(
+    block: &mut [u8],
+    space: usize,
+    name: &[u8],
+    inode: u32,
+    file_type: u8,
+    filetype: bool,
+) -> bool {
+    let needed = entry_size(name.len());
+    let file_type = if filetype { file_type } else { 0 };
+
+    let Ok(entries) = parse_entries(block, space, filetype) else {
+        return false;
+    };
+
+    let slot = entries.iter().find_map(|entry| {
+        let used = match entry.inode {
+            0 => 0,
+            _ => entry_size(entry.name.len()),
+        };
+
+        (entry.rec_len - used >= needed).then_some((entry.offset, entry.rec_len, used))
+    });
+
+    let Some((offset, rec_len, used)) = slot else {
+        return false;
+    };
+
+    match used {
+        0 => write_entry(block, offset, inode, rec_len, name, file_type),
+        _ => {
+            set_le16(block, offset + 4, used as u16);
+            write_entry(block, offset + used, inode, rec_len - used, name, file_type);
+        }
+    }
+
+    true
+}
+
+/// Removes the entry `name` from a block, returns its inode.
+fn leaf_remove(block: &mut [u8], name: &[u8], filetype: bool) -> FileSystemResult<Option<u32>> {
+    let found = {
+        let entries = parse_entries(block, block.len(), filetype)?;
+
+        entries
+            .iter()
+            .position(|e| e.inode != 0 && e.name == name)
+            .map(|i| {
+                let previous = i
+                    .checked_sub(1)
+                    .map(|p| (entries[p].offset, entries[p].rec_len));
+                (
+                    entries[i].offset,
+                    entries[i].rec_len,
+                    entries[i].inode,
+                    previous,
+                )
+            })
+    };
+
+    let Some((offset, rec_len, inode, previous)) = found else {
+        return Ok(None);
+    };
+
+    match previous {
+        // The first entry of a block can not be merged away
+        None => set_le32(block, offset, 0),
+        Some((previous, previous_len)) => {
+            set_le16(block, previous + 4, (previous_len + rec_len) as u16)
+        }
+    }
+
+    Ok(Some(inode))
+}
+
+/// Fills `space` bytes of a block with one unused entry.
+pub(crate) fn init_leaf(block: &mut [u8], space: usize) {
+    block[..space].fill(0);
+    set_le16(block, 4, space as u16);
+}
+
+fn has_tail(block: &[u8]) -> bool {
+    let tail = block.len() - TAIL_SIZE;
+
+    le32(block, tail) == 0
+        && le16(block, tail + 4) as usize == TAIL_SIZE
+        && block[tail + 6] == 0
+        && block[tail + 7] == TAIL_FILE_TYPE
+}
+
+/// A directory entry found by a lookup.
+pub(crate) struct Found {
+    pub inode: u32,
+    pub file_type: u8,
+}
+
+impl Volume {
+    /// Bytes of a leaf block available to entries.
+    pub fn leaf_space(&self) -> usize {
+        match self.metadata_csum() {
+            true => self.block_size() - TAIL_SIZE,
+            false => self.block_size(),
+        }
+    }
+
+    pub fn dir_blocks(&self, dir: &Inode) -> u32 {
+        (dir.size() / self.block_size() as u64) as u32
+    }
+
+    pub fn read_dir_block(&self, dir: &Inode, logical: u32) -> FileSystemResult<Vec<u8>> {
+        let physical = self.map_block(dir, logical)?;
+
+        self.disk.read_metadata(physical)
+    }
+
+    /// Writes a block of entries, refreshing its checksum tail.
+    pub fn write_dir_leaf(
+        &mut self,
+        dir: &Inode,
+        logical: u32,
+        mut block: Vec<u8>,
+    ) -> FileSystemResult<()> {
+        if self.metadata_csum() {
+            let tail = self.block_size() - TAIL_SIZE;
+
+            write_entry(&mut block, tail, 0, TAIL_SIZE, &[], TAIL_FILE_TYPE);
+
+            let checksum = crc32c(dir.checksum_seed(self.checksum_seed), &block[..tail]);
+            set_le32(&mut block, tail + 8, checksum);
+        }
+
+        let physical = self.map_block(dir, logical)?;
+        self.disk.write_metadata(physical, block);
+
+        Ok(())
+    }
+
+    /// Adds a block to the end of the directory, returns its logical number.
+    pub fn append_dir_block(&mut self, dir: &mut Inode) -> FileSystemResult<u32> {
+        let logical = self.dir_blocks(dir);
+
+        self.allocate_range(dir, logical, 1)?;
+        dir.set_size(dir.size() + self.block_size() as u64);
+
+        Ok(logical)
+    }
+
+    pub fn lookup(&self, dir: &Inode, name: &[u8]) -> FileSystemResult<Option<Found>> {
+        if dir.has_flag(FLAG_INDEX) {
+            match self.htree_lookup(dir, name) {
+                Err(FileSystemError::Unimplemented) => (),
+                other => return other,
+            }
+        }
+
+        for logical in 0..self.dir_blocks(dir) {
+            let block = self.read_dir_block(dir, logical)?;
+
+            for entry in parse_entries(&block, block.len(), self.has_filetype())? {
+                if entry.inode != 0 && entry.name == name {
+                    return Ok(Some(Found {
+                        inode: entry.inode,
+                        file_type: entry.file_type,
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Every entry but `.` and `..`, as name, inode and file type.
+    pub fn list(&self, dir: &Inode) -> FileSystemResult<Vec<(String, u32, u8)>> {
+        let mut listed = Vec::new();
+
+        for logical in 0..self.dir_blocks(dir) {
+            let block = self.read_dir_block(dir, logical)?;
+
+            for entry in parse_entries(&block, block.len(), self.has_filetype())? {
+                if entry.inode == 0 || entry.name == b"." || entry.name == b".." {
+                    continue;
+                }
+
+                listed.push((
+                    String::from_utf8_lossy(entry.name).into_owned(),
+                    entry.inode,
+                    entry.file_type,
+                ));
+            }
+        }
+
+        Ok(listed)
+    }
+
+    pub fn is_empty_dir(&self, dir: &Inode) -> FileSystemResult<bool> {
+        Ok(self.list(dir)?.is_empty())
+    }
+
+    pub fn add_entry(
+        &mut self,
+        dir: &mut Inode,
+        name: &[u8],
+        inode: u32,
+        file_type: u8,
+    ) -> FileSystemResult<()> {
+        if dir.has_flag(FLAG_INDEX) {
+            match self.htree_insert(dir, name, inode, file_type) {
+                // Linux falls back the same way, fsck can index the directory again
+                Err(FileSystemError::Unimplemented) => {
+                    log::warn!("ext4: dropping the unusable index of inode {}", dir.number);
+                    dir.set_flags(dir.flags() & !FLAG_INDEX);
+                }
+                other => return other,
+            }
+        }
+
+        let space = self.leaf_space();
+        let filetype = self.has_filetype();
+
+        for logical in 0..self.dir_blocks(dir) {
+            let mut block = self.read_dir_block(dir, logical)?;
+
+            // Blocks that are not plain leaves can not take entries
+            if self.metadata_csum() && !has_tail(&block) {
+                continue;
+            }
+
+            if leaf_insert(&mut block, space, name, inode, file_type, filetype) {
+                return self.write_dir_leaf(dir, logical, block);
+            }
+        }
+
+        let logical = self.append_dir_block(dir)?;
+
+        let mut block = vec![0; self.block_size()];
+        init_leaf(&mut block, space);
+        leaf_insert(&mut block, space, name, inode, file_type, filetype);
+
+        self.write_dir_leaf(dir, logical, block)
+    }
+
+    /// Removes the entry `name`, returns its inode.
+    pub fn remove_entry(&mut self, dir: &Inode, name: &[u8]) -> FileSystemResult<u32> {
+        let filetype = self.has_filetype();
+
+        let candidates: Vec<u32> = match dir.has_flag(FLAG_INDEX) {
+            true => match self.htree_leaf(dir, name) {
+                Ok(leaf) => vec![leaf],
+                Err(_) => (0..self.dir_blocks(dir)).collect(),
+            },
+            false => (0..self.dir_blocks(dir)).collect(),
+        };
+
+        for logical in candidates.iter().copied().chain(0..self.dir_blocks(dir)) {
+            let mut block = self.read_dir_block(dir, logical)?;
+
+            if let Some(inode) = leaf_remove(&mut block, name, filetype)? {
+                self.write_dir_leaf(dir, logical, block)?;
+                return Ok(inode);
+            }
+        }
+
+        Err(FileSystemError::NotFound)
+    }
+
+    /// Writes the first block of a new directory.
+    pub fn init_dir(&mut self, dir: &mut Inode, parent: u32) -> FileSystemResult<()> {
+        let logical = self.append_dir_block(dir)?;
+        let space = self.leaf_space();
+        let file_type = if self.has_filetype() {
+            TYPE_DIRECTORY
+        } else {
+            0
+        };
+
+        let mut block = vec![0; self.block_size()];
+        write_entry(&mut block, 0, dir.number, 12, b".", file_type);
+        write_entry(&mut block, 12, parent, space - 12, b"..", file_type);
+
+        self.write_dir_leaf(dir, logical, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut block = vec![0; 1024];
+        init_leaf(&mut block, 1012);
+        write_entry(&mut block, 1012, 0, TAIL_SIZE, &[], TAIL_FILE_TYPE);
+        assert!(has_tail(&block));
+
+        assert!(leaf_insert(&mut block, 1012, b"first", 12, TYPE_FILE, true));
+        assert!(leaf_insert(
+            &mut block,
+            1012,
+            b"second",
+            13,
+            TYPE_DIRECTORY,
+            true
+        ));
+
+        let entries = parse_entries(&block, 1012, true).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].rec_len, 16);
+        assert_eq!(entries[1].name, b"second");
+        assert_eq!(entries[1].rec_len, 1012 - 16);
+
+        // The first entry is emptied in place, later ones merge into their predecessor
+        assert_eq!(leaf_remove(&mut block, b"first", true).unwrap(), Some(12));
+        assert_eq!(leaf_remove(&mut block, b"first", true).unwrap(), None);
+
+        assert!(leaf_insert(&mut block, 1012, b"third", 14, TYPE_FILE, true));
+        assert_eq!(parse_entries(&block, 1012, true).unwrap()[0].name, b"third");
+
+        assert_eq!(leaf_remove(&mut block, b"second", true).unwrap(), Some(13));
+        assert_eq!(parse_entries(&block, 1012, true).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_full_block() {
+        let mut block = vec![0; 1024];
+        init_leaf(&mut block, 1024);
+
+        let name = [b'x'; 200];
+        let mut count = 0;
+
+        while leaf_insert(&mut block, 1024, &name, 12, TYPE_FILE, true) {
+            count += 1;
+        }
+
+        assert_eq!(count, 1024 / entry_size(200));
+    }
+
+    #[test]
+    fn test_rejects_corrupted_entries() {
+        let mut block = vec![0; 1024];
+        init_leaf(&mut block, 1024);
+        set_le16(&mut block, 4, 2000);
+
+        assert!(parse_entries(&block, 1024, true).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use block_abstractions::{BlockCache, IBlockDevice};
+use filesystem_abstractions::FileSystemResult;
+
+/// Device blocks kept by the cache under the filesystem.
+const CACHE_CAPACITY: usize = 2048;
+
+/// Filesystem blocks over a cached device.
+///
+/// File contents are written to the cache as they come, metadata is held back until the next
+/// commit so the journal can make it durable as a whole.
+pub(crate) struct Disk {
+    cache: BlockCache,
+    block_size: usize,
+    /// Metadata blocks written since the last commit
+    pending: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Disk {
+    pub fn new(device: Arc<dyn IBlockDevice>, block_size: usize) -> Disk {
+        Disk {
+            cache: BlockCache::new(device, CACHE_CAPACITY),
+            block_size,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.cache.is_readonly()
+    }
+
+    /// Reads bytes at `offset` of the device, bypassing pending metadata.
+    pub fn read_at(&self, offset: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+        self.cache.read_at(offset, buffer)
+    }
+
+    /// Writes bytes at `offset` of the device, bypassing the journal.
+    pub fn write_at(&self, offset: u64, buffer: &[u8]) -> FileSystemResult<()> {
+        self.cache.write_at(offset, buffer)
+    }
+
+    pub fn read_block(&self, block: u64) -> FileSystemResult<Vec<u8>> {
+        let mut data = vec![0; self.block_size];
+        self.read_at(block * self.block_size as u64, &mut data)?;
+
+        Ok(data)
+    }
+
+    pub fn write_block(&self, block: u64, data: &[u8]) -> FileSystemResult<()> {
+        debug_assert_eq!(data.len(), self.block_size);
+
+        self.write_at(block * self.block_size as u64, data)
+    }
+
+    /// The latest contents of a metadata block, including writes that are not committed yet.
+    pub fn read_metadata(&self, block: u64) -> FileSystemResult<Vec<u8>> {
+        match self.pending.get(&block) {
+            Some(data) => Ok(data.clone()),
+            None => self.read_block(block),
+        }
+    }
+
+    pub fn write_metadata(&mut self, block: u64, data: Vec<u8>) {
+        debug_assert_eq!(data.len(), self.block_size);
+
+        self.pending.insert(block, data);
+    }
+
+    /// Drops the pending write of a block that was freed, it may be reused for file contents.
+    pub fn forget(&mut self, block: u64) {
+        self.pending.remove(&block);
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn take_pending(&mut self) -> BTreeMap<u64, Vec<u8>> {
+        core::mem::take(&mut self.pending)
+    }
+
+    /// Writes cached blocks back and waits for the device.
+    pub fn flush(&self) -> FileSystemResult<()> {
+        self.cache.flush()
+    }
+}
@@ -0,0 +1,439 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    crc::crc32c,
+    inode::{Inode, BLOCK_MAP_SIZE},
+    raw::{le16, le32, set_le16, set_le32},
+    volume::Volume,
+};
+
+const MAGIC: u16 = 0xf30a;
+const HEADER_SIZE: usize = 12;
+const ENTRY_SIZE: usize = 12;
+
+/// Entries of the tree root kept in the inode.
+const ROOT_ENTRIES: usize = (BLOCK_MAP_SIZE - HEADER_SIZE) / ENTRY_SIZE;
+
+/// Longest initialized extent, lengths above it mark uninitialized extents.
+const MAX_INIT_LEN: u32 = 32768;
+const MAX_UNINIT_LEN: u32 = 32767;
+
+/// Deepest tree the driver follows, a corrupted tree could otherwise loop.
+const MAX_DEPTH: u16 = 5;
+
+/// A run of logical blocks of a file stored in contiguous physical blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Extent {
+    pub logical: u32,
+    pub len: u32,
+    pub physical: u64,
+    /// The blocks are allocated but read as zeros
+    pub uninit: bool,
+}
+
+impl Extent {
+    pub fn end(&self) -> u64 {
+        self.logical as u64 + self.len as u64
+    }
+
+    pub fn max_len(uninit: bool) -> u32 {
+        match uninit {
+            true => MAX_UNINIT_LEN,
+            false => MAX_INIT_LEN,
+        }
+    }
+
+    /// Whether `next` continues this extent on disk and can be merged into it.
+    fn can_merge(&self, next: &Extent) -> bool {
+        self.end() == next.logical as u64
+            && self.physical + self.len as u64 == next.physical
+            && self.uninit == next.uninit
+            && self.len + next.len <= Extent::max_len(self.uninit)
+    }
+}
+
+struct Header {
+    entries: usize,
+    max: usize,
+    depth: u16,
+}
+
+fn parse_header(node: &[u8]) -> FileSystemResult<Header> {
+    let header = Header {
+        entries: le16(node, 0x2) as usize,
+        max: le16(node, 0x4) as usize,
+        depth: le16(node, 0x6),
+    };
+
+    if le16(node, 0x0) != MAGIC
+        || header.entries > header.max
+        || HEADER_SIZE + header.max * ENTRY_SIZE > node.len()
+        || header.depth > MAX_DEPTH
+    {
+        return Err(FileSystemError::FileSystemCorrupted);
+    }
+
+    Ok(header)
+}
+
+fn write_header(node: &mut [u8], entries: usize, max: usize, depth: u16) {
+    set_le16(node, 0x0, MAGIC);
+    set_le16(node, 0x2, entries as u16);
+    set_le16(node, 0x4, max as u16);
+    set_le16(node, 0x6, depth);
+    set_le32(node, 0x8, 0);
+}
+
+fn leaf_entry(node: &[u8], index: usize) -> Extent {
+    let offset = HEADER_SIZE + index * ENTRY_SIZE;
+    let raw_len = le16(node, offset + 4) as u32;
+
+    Extent {
+        logical: le32(node, offset),
+        len: match raw_len > MAX_INIT_LEN {
+            true => raw_len - MAX_INIT_LEN,
+            false => raw_len,
+        },
+        physical: (le16(node, offset + 6) as u64) << 32 | le32(node, offset + 8) as u64,
+        uninit: raw_len > MAX_INIT_LEN,
+    }
+}
+
+fn write_leaf_entry(node: &mut [u8], index: usize, extent: &Extent) {
+    let offset = HEADER_SIZE + index * ENTRY_SIZE;
+    let raw_len = match extent.uninit {
+        true => extent.len + MAX_INIT_LEN,
+        false => extent.len,
+    };
+
+    set_le32(node, offset, extent.logical);
+    set_le16(node, offset + 4, raw_len as u16);
+    set_le16(node, offset + 6, (extent.physical >> 32) as u16);
+    set_le32(node, offset + 8, extent.physical as u32);
+}
+
+/// The first logical block and the child node of an index entry.
+fn index_entry(node: &[u8], index: usize) -> (u32, u64) {
+    let offset = HEADER_SIZE + index * ENTRY_SIZE;
+
+    (
+        le32(node, offset),
+        (le16(node, offset + 8) as u64) << 32 | le32(node, offset + 4) as u64,
+    )
+}
+
+fn write_index_entry(node: &mut [u8], index: usize, logical: u32, child: u64) {
+    let offset = HEADER_SIZE + index * ENTRY_SIZE;
+
+    set_le32(node, offset, logical);
+    set_le32(node, offset + 4, child as u32);
+    set_le16(node, offset + 8, (child >> 32) as u16);
+    set_le16(node, offset + 10, 0);
+}
+
+/// Merges neighbours that continue each other, `extents` must be sorted.
+pub(crate) fn merge_extents(extents: &mut Vec<Extent>) {
+    let mut merged: Vec<Extent> = Vec::with_capacity(extents.len());
+
+    for extent in extents.drain(..).filter(|e| e.len > 0) {
+        match merged.last_mut() {
+            Some(last) if last.can_merge(&extent) => last.len += extent.len,
+            _ => merged.push(extent),
+        }
+    }
+
+    *extents = merged;
+}
+
+impl Volume {
+    /// Entries of an extent tree block.
+    fn node_capacity(&self) -> usize {
+        (self.block_size() - HEADER_SIZE) / ENTRY_SIZE
+    }
+
+    fn node_checksum(&self, inode: &Inode, node: &[u8], max: usize) -> u32 {
+        let tail = HEADER_SIZE + max * ENTRY_SIZE;
+
+        crc32c(inode.checksum_seed(self.checksum_seed), &node[..tail])
+    }
+
+    fn read_node(&self, inode: &Inode, block: u64) -> FileSystemResult<Vec<u8>> {
+        let node = self.disk.read_metadata(block)?;
+        let header = parse_header(&node)?;
+
+        if self.metadata_csum() {
+            let tail = HEADER_SIZE + header.max * ENTRY_SIZE;
+
+            if tail + 4 <= node.len()
+                && le32(&node, tail) != self.node_checksum(inode, &node, header.max)
+            {
+                log::warn!(
+                    "ext4: checksum mismatch in extent block {} of inode {}",
+                    block,
+                    inode.number
+                );
+                return Err(FileSystemError::FileSystemCorrupted);
+            }
+        }
+
+        Ok(node)
+    }
+
+    /// The extent holding `logical`, if it is mapped.
+    pub fn extent_find(&self, inode: &Inode, logical: u32) -> FileSystemResult<Option<Extent>> {
+        let mut node = inode.block_map().to_vec();
+
+        loop {
+            let header = parse_header(&node)?;
+
+            // The last entry starting at or before the block
+            let position = (0..header.entries)
+                .take_while(|&i| le32(&node, HEADER_SIZE + i * ENTRY_SIZE) <= logical)
+                .last();
+
+            let Some(position) = position else {
+                return Ok(None);
+            };
+
+            if header.depth == 0 {
+                let extent = leaf_entry(&node, position);
+
+                return Ok(match (logical as u64) < extent.end() {
+                    true => Some(extent),
+                    false => None,
+                });
+            }
+
+            let (_, child) = index_entry(&node, position);
+            node = self.read_node(inode, child)?;
+        }
+    }
+
+    fn collect(
+        &self,
+        inode: &Inode,
+        node: &[u8],
+        extents: &mut Vec<Extent>,
+        nodes: &mut Vec<u64>,
+    ) -> FileSystemResult<()> {
+        let header = parse_header(node)?;
+
+        for i in 0..header.entries {
+            match header.depth {
+                0 => extents.push(leaf_entry(node, i)),
+                _ => {
+                    let (_, child) = index_entry(node, i);
+                    let child_node = self.read_node(inode, child)?;
+
+                    if parse_header(&child_node)?.depth + 1 != header.depth {
+                        return Err(FileSystemError::FileSystemCorrupted);
+                    }
+
+                    nodes.push(child);
+                    self.collect(inode, &child_node, extents, nodes)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every extent of the inode, sorted, and the blocks of the tree nodes.
+    pub fn extent_load(&self, inode: &Inode) -> FileSystemResult<(Vec<Extent>, Vec<u64>)> {
+        let mut extents = Vec::new();
+        let mut nodes = Vec::new();
+
+        self.collect(inode, inode.block_map(), &mut extents, &mut nodes)?;
+
+        Ok((extents, nodes))
+    }
+
+    /// Rebuilds the tree of the inode from sorted `extents`, reusing the `nodes` of the old tree
+    /// and freeing those that are left over.
+    pub fn extent_store(
+        &mut self,
+        inode: &mut Inode,
+        extents: &[Extent],
+        nodes: Vec<u64>,
+    ) -> FileSystemResult<()> {
+        let capacity = self.node_capacity();
+        let goal = extents.first().map_or(0, |e| e.physical);
+
+        // Get every node up front, running out of space halfway would leave a broken tree
+        let mut needed = 0;
+        let mut entries = extents.len();
+
+        while entries > ROOT_ENTRIES {
+            entries = entries.div_ceil(capacity);
+            needed += entries;
+        }
+
+        let mut blocks = nodes;
+        let reused = blocks.len().min(needed);
+
+        while blocks.len() < needed {
+            match self.allocate_blocks(goal, 1) {
+                Ok((block, _)) => blocks.push(block),
+                Err(e) => {
+                    for &block in &blocks[reused..] {
+                        self.free_blocks(block, 1)?;
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        inode.add_blocks((needed - reused) as i64, self.block_size());
+
+        let spare = blocks.split_off(needed);
+        let mut blocks = blocks.into_iter();
+
+        // Entries of the level being built, as first logical block and child node
+        let mut level: Vec<(u32, u64)> = Vec::new();
+        let mut depth = 0;
+
+        if extents.len() > ROOT_ENTRIES {
+            for leaf in extents.chunks(capacity) {
+                let block = blocks.next().unwrap();
+
+                let mut node = vec![0; self.block_size()];
+                write_header(&mut node, leaf.len(), capacity, 0);
+
+                for (i, extent) in leaf.iter().enumerate() {
+                    write_leaf_entry(&mut node, i, extent);
+                }
+
+                self.write_node(inode, block, node, capacity);
+                level.push((leaf[0].logical, block));
+            }
+
+            depth = 1;
+
+            while level.len() > ROOT_ENTRIES {
+                let mut upper = Vec::new();
+
+                for children in level.chunks(capacity) {
+                    let block = blocks.next().unwrap();
+
+                    let mut node = vec![0; self.block_size()];
+                    write_header(&mut node, children.len(), capacity, depth);
+
+                    for (i, &(logical, child)) in children.iter().enumerate() {
+                        write_index_entry(&mut node, i, logical, child);
+                    }
+
+                    self.write_node(inode, block, node, capacity);
+                    upper.push((children[0].0, block));
+                }
+
+                level = upper;
+                depth += 1;
+            }
+        }
+
+        let root = inode.block_map_mut();
+        root.fill(0);
+
+        match depth {
+            0 => {
+                write_header(root, extents.len(), ROOT_ENTRIES, 0);
+
+                for (i, extent) in extents.iter().enumerate() {
+                    write_leaf_entry(root, i, extent);
+                }
+            }
+            _ => {
+                write_header(root, level.len(), ROOT_ENTRIES, depth);
+
+                for (i, &(logical, child)) in level.iter().enumerate() {
+                    write_index_entry(root, i, logical, child);
+                }
+            }
+        }
+
+        for block in spare {
+            self.free_blocks(block, 1)?;
+            inode.add_blocks(-1, self.block_size());
+        }
+
+        Ok(())
+    }
+
+    /// Initializes an empty tree in the inode.
+    pub fn extent_init(&self, inode: &mut Inode) {
+        let root = inode.block_map_mut();
+        root.fill(0);
+
+        write_header(root, 0, ROOT_ENTRIES, 0);
+    }
+
+    fn write_node(&mut self, inode: &Inode, block: u64, mut node: Vec<u8>, max: usize) {
+        if self.metadata_csum() {
+            let checksum = self.node_checksum(inode, &node, max);
+            set_le32(&mut node, HEADER_SIZE + max * ENTRY_SIZE, checksum);
+        }
+
+        self.disk.write_metadata(block, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(logical: u32, len: u32, physical: u64) -> Extent {
+        Extent {
+            logical,
+            len,
+            physical,
+            uninit: false,
+        }
+    }
+
+    #[test]
+    fn test_entries_roundtrip() {
+        let mut node = vec![0; 60];
+        write_header(&mut node, 2, 4, 0);
+
+        let uninit = Extent {
+            uninit: true,
+            ..extent(100, 7, 0x1_0000_0042)
+        };
+
+        write_leaf_entry(&mut node, 0, &extent(0, 32768, 500));
+        write_leaf_entry(&mut node, 1, &uninit);
+
+        assert_eq!(parse_header(&node).unwrap().entries, 2);
+        assert_eq!(leaf_entry(&node, 0), extent(0, 32768, 500));
+        assert_eq!(leaf_entry(&node, 1), uninit);
+    }
+
+    #[test]
+    fn test_rejects_bad_headers() {
+        let mut node = vec![0; 60];
+        assert!(parse_header(&node).is_err());
+
+        write_header(&mut node, 5, 4, 0);
+        assert!(parse_header(&node).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut extents = vec![
+            extent(0, 4, 100),
+            extent(4, 2, 104),
+            extent(6, 1, 200),
+            extent(7, 0, 300),
+            extent(8, 1, 201),
+        ];
+
+        merge_extents(&mut extents);
+
+        assert_eq!(
+            extents,
+            [extent(0, 6, 100), extent(6, 1, 200), extent(8, 1, 201)]
+        );
+    }
+}
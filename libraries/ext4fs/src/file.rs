@@ -0,0 +1,404 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    extent::{merge_extents, Extent},
+    inode::{Inode, BLOCK_MAP_SIZE, FLAG_EXTENTS, FLAG_INLINE_DATA, MODE_SYMLINK},
+    volume::Volume,
+};
+
+/// Blocks mapped for a write, `fresh` ones hold garbage and have to be written completely.
+pub(crate) struct Run {
+    pub logical: u32,
+    pub physical: u64,
+    pub len: u32,
+    pub fresh: bool,
+}
+
+impl Volume {
+    /// Whether the symlink target lives in the block map of the inode.
+    pub fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        inode.file_type() == MODE_SYMLINK
+            && inode.size() < BLOCK_MAP_SIZE as u64
+            && !inode.has_flag(FLAG_EXTENTS | FLAG_INLINE_DATA)
+    }
+
+    /// The physical blocks from `logical` on that are contiguous, up to `max` of them, and
+    /// whether they are uninitialized. `None` for a hole.
+    fn map_run(
+        &self,
+        inode: &Inode,
+        logical: u32,
+        max: u32,
+    ) -> FileSystemResult<Option<(u64, u32, bool)>> {
+        if inode.has_flag(FLAG_INLINE_DATA) {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        if inode.has_flag(FLAG_EXTENTS) {
+            return Ok(self.extent_find(inode, logical)?.map(|extent| {
+                let offset = logical - extent.logical;
+
+                (
+                    extent.physical + offset as u64,
+                    (extent.len - offset).min(max),
+                    extent.uninit,
+                )
+            }));
+        }
+
+        Ok(self
+            .indirect_find(inode, logical)?
+            .map(|physical| (physical, 1, false)))
+    }
+
+    /// The physical block of `logical`, which must be mapped.
+    pub fn map_block(&self, inode: &Inode, logical: u32) -> FileSystemResult<u64> {
+        match self.map_run(inode, logical, 1)? {
+            Some((physical, _, _)) => Ok(physical),
+            None => Err(FileSystemError::FileSystemCorrupted),
+        }
+    }
+
+    /// The physical blocks of the first `count` logical blocks, which must all be mapped.
+    pub fn block_list(&self, inode: &Inode, count: u32) -> FileSystemResult<Vec<u64>> {
+        let mut blocks = Vec::with_capacity(count as usize);
+
+        while (blocks.len() as u32) < count {
+            let logical = blocks.len() as u32;
+
+            match self.map_run(inode, logical, count - logical)? {
+                Some((physical, len, _)) => blocks.extend(physical..physical + len as u64),
+                None => return Err(FileSystemError::FileSystemCorrupted),
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    pub fn read_contents(
+        &self,
+        inode: &Inode,
+        offset: u64,
+        buffer: &mut [u8],
+    ) -> FileSystemResult<usize> {
+        let size = inode.size();
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let len = ((size - offset) as usize).min(buffer.len());
+        let block_size = self.block_size() as u64;
+
+        let mut done = 0;
+
+        while done < len {
+            let position = offset + done as u64;
+            let logical = (position / block_size) as u32;
+            let in_block = position % block_size;
+
+            let blocks_left = (len - done).div_ceil(block_size as usize) as u32 + 1;
+            let run = self.map_run(inode, logical, blocks_left)?;
+
+            let run_bytes = match run {
+                Some((_, blocks, _)) => blocks as u64 * block_size - in_block,
+                None => block_size - in_block,
+            };
+
+            let piece = &mut buffer[done..done + run_bytes.min((len - done) as u64) as usize];
+
+            match run {
+                Some((physical, _, false)) => {
+                    self.disk.read_at(physical * block_size + in_block, piece)?
+                }
+                _ => piece.fill(0),
+            }
+
+            done += piece.len();
+        }
+
+        Ok(len)
+    }
+
+    /// Maps `count` blocks from `first` for writing, allocating holes and converting
+    /// uninitialized extents.
+    fn map_for_write(
+        &mut self,
+        inode: &mut Inode,
+        first: u32,
+        count: u32,
+    ) -> FileSystemResult<Vec<Run>> {
+        if inode.has_flag(FLAG_INLINE_DATA) {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        if !inode.has_flag(FLAG_EXTENTS) {
+            let mut runs = Vec::new();
+            let mut goal = self.goal(inode);
+
+            for logical in first..first + count {
+                let (physical, fresh) = self.indirect_allocate(inode, logical, goal)?;
+                goal = physical + 1;
+
+                runs.push(Run {
+                    logical,
+                    physical,
+                    len: 1,
+                    fresh,
+                });
+            }
+
+            return Ok(runs);
+        }
+
+        let (mut extents, nodes) = self.extent_load(inode)?;
+        let end = first as u64 + count as u64;
+
+        let mut runs = Vec::new();
+        let mut allocated = Vec::new();
+        let mut changed = false;
+        let mut logical = first as u64;
+
+        let result = loop {
+            if logical >= end {
+                break Ok(());
+            }
+
+            let index = extents.partition_point(|e| e.end() <= logical);
+
+            match extents.get(index).copied() {
+                Some(extent) if (extent.logical as u64) <= logical => {
+                    let offset = (logical - extent.logical as u64) as u32;
+                    let len = (extent.end().min(end) - logical) as u32;
+
+                    if extent.uninit {
+                        // Split off the written part and mark it initialized
+                        let mut pieces = Vec::new();
+
+                        if offset > 0 {
+                            pieces.push(Extent {
+                                len: offset,
+                                ..extent
+                            });
+                        }
+
+                        pieces.push(Extent {
+                            logical: logical as u32,
+                            len,
+                            physical: extent.physical + offset as u64,
+                            uninit: false,
+                        });
+
+                        if offset + len < extent.len {
+                            pieces.push(Extent {
+                                logical: logical as u32 + len,
+                                len: extent.len - offset - len,
+                                physical: extent.physical + (offset + len) as u64,
+                                uninit: true,
+                            });
+                        }
+
+                        extents.splice(index..index + 1, pieces);
+                        changed = true;
+                    }
+
+                    runs.push(Run {
+                        logical: logical as u32,
+                        physical: extent.physical + offset as u64,
+                        len,
+                        fresh: extent.uninit,
+                    });
+
+                    logical += len as u64;
+                }
+                next => {
+                    let hole_end = next.map_or(end, |e| (e.logical as u64).min(end));
+                    let wanted = ((hole_end - logical) as u32).min(Extent::max_len(false));
+
+                    let goal = match index {
+                        0 => self.goal(inode),
+                        _ => extents[index - 1].physical + extents[index - 1].len as u64,
+                    };
+
+                    let (physical, len) = match self.allocate_blocks(goal, wanted) {
+                        Ok(allocated) => allocated,
+                        Err(e) => break Err(e),
+                    };
+
+                    inode.add_blocks(len as i64, self.block_size());
+                    allocated.push((physical, len as u64));
+
+                    extents.insert(
+                        index,
+                        Extent {
+                            logical: logical as u32,
+                            len,
+                            physical,
+                            uninit: false,
+                        },
+                    );
+                    changed = true;
+
+                    runs.push(Run {
+                        logical: logical as u32,
+                        physical,
+                        len,
+                        fresh: true,
+                    });
+
+                    logical += len as u64;
+                }
+            }
+        };
+
+        let result = result.and_then(|_| match changed {
+            true => {
+                merge_extents(&mut extents);
+                self.extent_store(inode, &extents, nodes)
+            }
+            false => Ok(()),
+        });
+
+        // Nothing was written yet, the tree is left as it was and the new blocks given back
+        if let Err(e) = result {
+            for (physical, count) in allocated {
+                self.free_blocks(physical, count)?;
+                inode.add_blocks(-(count as i64), self.block_size());
+            }
+
+            return Err(e);
+        }
+
+        Ok(runs)
+    }
+
+    /// Maps `count` blocks from `first`, allocating those that are not mapped yet without
+    /// touching their contents.
+    pub fn allocate_range(
+        &mut self,
+        inode: &mut Inode,
+        first: u32,
+        count: u32,
+    ) -> FileSystemResult<()> {
+        self.map_for_write(inode, first, count).map(|_| ())
+    }
+
+    /// Where blocks of the inode should be allocated when nothing better is known.
+    pub fn goal(&self, inode: &Inode) -> u64 {
+        let group = self.geometry.group_of_inode(inode.number);
+
+        self.geometry.group_first_block(group)
+    }
+
+    pub fn write_contents(
+        &mut self,
+        inode: &mut Inode,
+        offset: u64,
+        data: &[u8],
+    ) -> FileSystemResult<usize> {
+        self.ensure_writable()?;
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let block_size = self.block_size() as u64;
+        let end = offset
+            .checked_add(data.len() as u64)
+            .ok_or(FileSystemError::InvalidInput)?;
+
+        if (end - 1) / block_size > u32::MAX as u64 {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        let first = (offset / block_size) as u32;
+        let last = ((end - 1) / block_size) as u32;
+
+        let runs = self.map_for_write(inode, first, last - first + 1)?;
+
+        for run in runs {
+            let run_start = run.logical as u64 * block_size;
+            let run_end = run_start + run.len as u64 * block_size;
+
+            let from = offset.max(run_start);
+            let to = end.min(run_end);
+            let piece = &data[(from - offset) as usize..(to - offset) as usize];
+
+            if !run.fresh || (from == run_start && to == run_end) {
+                self.disk
+                    .write_at(run.physical * block_size + (from - run_start), piece)?;
+                continue;
+            }
+
+            // A fresh block that is written partially is zeroed around the data
+            let mut blocks = vec![0; (run.len as u64 * block_size) as usize];
+            blocks[(from - run_start) as usize..(to - run_start) as usize].copy_from_slice(piece);
+
+            self.disk.write_at(run.physical * block_size, &blocks)?;
+        }
+
+        if end > inode.size() {
+            inode.set_size(end);
+        }
+
+        inode.touch(self.now());
+        self.write_inode(inode)?;
+
+        Ok(data.len())
+    }
+
+    /// Frees the blocks of the inode past `size` and sets its size, the inode is not written.
+    pub fn truncate_contents(&mut self, inode: &mut Inode, size: u64) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        if self.is_fast_symlink(inode) {
+            inode.block_map_mut().fill(0);
+            inode.set_size(size);
+            return Ok(());
+        }
+
+        let block_size = self.block_size() as u64;
+        let keep = size.div_ceil(block_size);
+
+        if size < inode.size() && !size.is_multiple_of(block_size) {
+            // Growing the file again later must read zeros past the current end
+            if let Some((physical, _, false)) =
+                self.map_run(inode, (size / block_size) as u32, 1)?
+            {
+                let tail = vec![0; (block_size - size % block_size) as usize];
+                self.disk
+                    .write_at(physical * block_size + size % block_size, &tail)?;
+            }
+        }
+
+        if inode.has_flag(FLAG_EXTENTS) {
+            let (mut extents, nodes) = self.extent_load(inode)?;
+
+            if extents.last().is_some_and(|e| e.end() > keep) {
+                let mut freed = Vec::new();
+
+                for extent in extents.iter_mut().filter(|e| e.end() > keep) {
+                    let kept = keep.saturating_sub(extent.logical as u64) as u32;
+
+                    freed.push((extent.physical + kept as u64, (extent.len - kept) as u64));
+                    extent.len = kept;
+                }
+
+                for (physical, count) in freed {
+                    self.free_blocks(physical, count)?;
+                    inode.add_blocks(-(count as i64), self.block_size());
+                }
+
+                merge_extents(&mut extents);
+                self.extent_store(inode, &extents, nodes)?;
+            }
+        } else if !inode.has_flag(FLAG_INLINE_DATA) {
+            self.indirect_truncate(inode, keep)?;
+        }
+
+        inode.set_size(size);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+use alloc::vec::Vec;
+
+use crate::{
+    crc::{crc16, crc32c},
+    raw::{le16, le32, set_le16},
+    superblock::{
+        Superblock, INCOMPAT_META_BG, RO_COMPAT_GDT_CSUM, RO_COMPAT_METADATA_CSUM,
+        RO_COMPAT_SPARSE_SUPER,
+    },
+};
+
+/// The inode table and bitmap are not initialized, every inode is free.
+pub(crate) const GROUP_INODE_UNINIT: u16 = 0x1;
+/// The block bitmap is not initialized, only the group's own metadata is in use.
+pub(crate) const GROUP_BLOCK_UNINIT: u16 = 0x2;
+
+/// Where the block groups and their metadata live, derived from the superblock.
+pub(crate) struct Geometry {
+    pub block_size: usize,
+    pub blocks_count: u64,
+    pub first_data_block: u64,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: usize,
+    pub group_count: u32,
+    pub desc_size: usize,
+    sparse_super: bool,
+    meta_bg: bool,
+    first_meta_bg: u32,
+    reserved_gdt_blocks: u32,
+}
+
+impl Geometry {
+    pub fn new(superblock: &Superblock) -> Geometry {
+        let first_data_block = superblock.first_data_block() as u64;
+        let blocks_per_group = superblock.blocks_per_group();
+
+        Geometry {
+            block_size: superblock.block_size(),
+            blocks_count: superblock.blocks_count(),
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group: superblock.inodes_per_group(),
+            inode_size: superblock.inode_size(),
+            group_count: (superblock.blocks_count() - first_data_block)
+                .div_ceil(blocks_per_group as u64) as u32,
+            desc_size: superblock.desc_size(),
+            sparse_super: superblock.has_ro_compat(RO_COMPAT_SPARSE_SUPER),
+            meta_bg: superblock.has_incompat(INCOMPAT_META_BG),
+            first_meta_bg: superblock.first_meta_bg(),
+            reserved_gdt_blocks: superblock.reserved_gdt_blocks(),
+        }
+    }
+
+    pub fn descs_per_block(&self) -> u32 {
+        (self.block_size / self.desc_size) as u32
+    }
+
+    /// Blocks holding the descriptors of every group.
+    pub fn desc_blocks(&self) -> u32 {
+        self.group_count.div_ceil(self.descs_per_block())
+    }
+
+    pub fn group_first_block(&self, group: u32) -> u64 {
+        self.first_data_block + group as u64 * self.blocks_per_group as u64
+    }
+
+    /// Blocks in `group`, the last group may be shorter than the others.
+    pub fn group_blocks(&self, group: u32) -> u32 {
+        let first = self.group_first_block(group);
+
+        (self.blocks_count - first).min(self.blocks_per_group as u64) as u32
+    }
+
+    pub fn group_of_block(&self, block: u64) -> u32 {
+        ((block - self.first_data_block) / self.blocks_per_group as u64) as u32
+    }
+
+    pub fn group_of_inode(&self, inode: u32) -> u32 {
+        (inode - 1) / self.inodes_per_group
+    }
+
+    /// Whether `group` holds a copy of the superblock.
+    pub fn has_super(&self, group: u32) -> bool {
+        fn power_of(mut n: u32, base: u32) -> bool {
+            while n > 1 && n.is_multiple_of(base) {
+                n /= base;
+            }
+
+            n == 1
+        }
+
+        group <= 1
+            || !self.sparse_super
+            || power_of(group, 3)
+            || power_of(group, 5)
+            || power_of(group, 7)
+    }
+
+    /// The block of the primary copy of the `index`th descriptor block.
+    pub fn desc_block_location(&self, index: u32) -> u64 {
+        if !self.meta_bg || index < self.first_meta_bg {
+            return self.first_data_block + 1 + index as u64;
+        }
+
+        let group = index * self.descs_per_block();
+
+        self.group_first_block(group) + self.has_super(group) as u64
+    }
+
+    /// The block and offset in it of the descriptor of `group`.
+    pub fn desc_location(&self, group: u32) -> (u64, usize) {
+        let index = group / self.descs_per_block();
+        let offset = (group % self.descs_per_block()) as usize * self.desc_size;
+
+        (self.desc_block_location(index), offset)
+    }
+
+    /// Ranges of blocks at the start of `group` taken by superblock and descriptor copies.
+    pub fn base_metadata(&self, group: u32) -> Vec<(u64, u64)> {
+        let first = self.group_first_block(group);
+        let has_super = self.has_super(group);
+
+        let mut ranges = Vec::new();
+
+        if has_super {
+            ranges.push((first, 1));
+        }
+
+        let meta_group = group / self.descs_per_block();
+
+        if !self.meta_bg || meta_group < self.first_meta_bg {
+            if has_super {
+                let old_desc_blocks = match self.meta_bg {
+                    true => self.first_meta_bg,
+                    false => self.desc_blocks() + self.reserved_gdt_blocks,
+                };
+
+                ranges.push((first + 1, old_desc_blocks as u64));
+            }
+        } else {
+            let index = group % self.descs_per_block();
+
+            if index == 0 || index == 1 || index == self.descs_per_block() - 1 {
+                ranges.push((first + has_super as u64, 1));
+            }
+        }
+
+        ranges
+    }
+}
+
+pub(crate) struct GroupDescriptor {
+    raw: Vec<u8>,
+}
+
+impl GroupDescriptor {
+    pub fn new(raw: Vec<u8>) -> GroupDescriptor {
+        GroupDescriptor { raw }
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn is_64bit(&self) -> bool {
+        self.raw.len() >= 64
+    }
+
+    fn hi_lo32(&self, hi: usize, lo: usize) -> u64 {
+        let high = match self.is_64bit() {
+            true => le32(&self.raw, hi) as u64,
+            false => 0,
+        };
+
+        (high << 32) | le32(&self.raw, lo) as u64
+    }
+
+    fn hi_lo16(&self, hi: usize, lo: usize) -> u32 {
+        let high = match self.is_64bit() {
+            true => le16(&self.raw, hi) as u32,
+            false => 0,
+        };
+
+        (high << 16) | le16(&self.raw, lo) as u32
+    }
+
+    fn set_hi_lo16(&mut self, hi: usize, lo: usize, value: u32) {
+        set_le16(&mut self.raw, lo, value as u16);
+
+        if self.is_64bit() {
+            set_le16(&mut self.raw, hi, (value >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.hi_lo32(0x20, 0x0)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.hi_lo32(0x24, 0x4)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.hi_lo32(0x28, 0x8)
+    }
+
+    pub fn free_blocks(&self) -> u32 {
+        self.hi_lo16(0x2c, 0xc)
+    }
+
+    pub fn set_free_blocks(&mut self, count: u32) {
+        self.set_hi_lo16(0x2c, 0xc, count);
+    }
+
+    pub fn free_inodes(&self) -> u32 {
+        self.hi_lo16(0x2e, 0xe)
+    }
+
+    pub fn set_free_inodes(&mut self, count: u32) {
+        self.set_hi_lo16(0x2e, 0xe, count);
+    }
+
+    pub fn used_dirs(&self) -> u32 {
+        self.hi_lo16(0x30, 0x10)
+    }
+
+    pub fn set_used_dirs(&mut self, count: u32) {
+        self.set_hi_lo16(0x30, 0x10, count);
+    }
+
+    pub fn flags(&self) -> u16 {
+        le16(&self.raw, 0x12)
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags() & flag != 0
+    }
+
+    pub fn clear_flag(&mut self, flag: u16) {
+        let flags = self.flags() & !flag;
+        set_le16(&mut self.raw, 0x12, flags);
+    }
+
+    pub fn itable_unused(&self) -> u32 {
+        self.hi_lo16(0x32, 0x1c)
+    }
+
+    pub fn set_itable_unused(&mut self, count: u32) {
+        self.set_hi_lo16(0x32, 0x1c, count);
+    }
+
+    pub fn set_block_bitmap_checksum(&mut self, checksum: u32) {
+        self.set_hi_lo16(0x38, 0x18, checksum);
+    }
+
+    pub fn set_inode_bitmap_checksum(&mut self, checksum: u32) {
+        self.set_hi_lo16(0x3a, 0x1a, checksum);
+    }
+
+    fn checksum(&self, superblock: &Superblock, seed: u32, group: u32) -> Option<u16> {
+        if superblock.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            let mut crc = crc32c(seed, &group.to_le_bytes());
+            crc = crc32c(crc, &self.raw[..0x1e]);
+            crc = crc32c(crc, &[0, 0]);
+            crc = crc32c(crc, &self.raw[0x20..]);
+
+            return Some(crc as u16);
+        }
+
+        if superblock.has_ro_compat(RO_COMPAT_GDT_CSUM) {
+            let mut crc = crc16(!0, superblock.uuid());
+            crc = crc16(crc, &group.to_le_bytes());
+            crc = crc16(crc, &self.raw[..0x1e]);
+            crc = crc16(crc, &self.raw[0x20..]);
+
+            return Some(crc);
+        }
+
+        None
+    }
+
+    pub fn verify_checksum(&self, superblock: &Superblock, seed: u32, group: u32) -> bool {
+        match self.checksum(superblock, seed, group) {
+            Some(checksum) => le16(&self.raw, 0x1e) == checksum,
+            None => true,
+        }
+    }
+
+    pub fn update_checksum(&mut self, superblock: &Superblock, seed: u32, group: u32) {
+        if let Some(checksum) = self.checksum(superblock, seed, group) {
+            set_le16(&mut self.raw, 0x1e, checksum);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(meta_bg: bool) -> Geometry {
+        Geometry {
+            block_size: 1024,
+            blocks_count: 8192 * 40,
+            first_data_block: 1,
+            blocks_per_group: 8192,
+            inodes_per_group: 2048,
+            inode_size: 256,
+            group_count: 40,
+            desc_size: 64,
+            sparse_super: true,
+            meta_bg,
+            first_meta_bg: 0,
+            reserved_gdt_blocks: 63,
+        }
+    }
+
+    #[test]
+    fn test_sparse_super() {
+        let geometry = geometry(false);
+
+        let backups: Vec<u32> = (0..40).filter(|&g| geometry.has_super(g)).collect();
+        assert_eq!(backups, [0, 1, 3, 5, 7, 9, 25, 27]);
+    }
+
+    #[test]
+    fn test_base_metadata() {
+        let geometry = geometry(false);
+
+        assert_eq!(geometry.desc_blocks(), 3);
+        assert_eq!(geometry.base_metadata(0), [(1, 1), (2, 66)]);
+        assert_eq!(
+            geometry.base_metadata(3),
+            [(8192 * 3 + 1, 1), (8192 * 3 + 2, 66)]
+        );
+        assert!(geometry.base_metadata(2).is_empty());
+    }
+
+    #[test]
+    fn test_meta_bg() {
+        let geometry = geometry(true);
+
+        // 16 descriptors per block, each meta group keeps its own descriptor block
+        assert_eq!(geometry.desc_location(17), (8192 * 16 + 1, 64));
+        assert_eq!(geometry.base_metadata(16), [(8192 * 16 + 1, 1)]);
+        assert_eq!(geometry.base_metadata(17), [(8192 * 17 + 1, 1)]);
+        assert!(geometry.base_metadata(18).is_empty());
+        assert_eq!(geometry.base_metadata(31), [(8192 * 31 + 1, 1)]);
+        assert_eq!(geometry.base_metadata(25), [(8192 * 25 + 1, 1)]);
+    }
+}
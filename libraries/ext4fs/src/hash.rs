@@ -0,0 +1,222 @@
+//! The name hashes indexed directories are sorted by.
+
+pub(crate) const HASH_LEGACY: u8 = 0;
+pub(crate) const HASH_HALF_MD4: u8 = 1;
+pub(crate) const HASH_TEA: u8 = 2;
+
+/// Hashes at or above this one mean the end of the directory to readdir.
+const EOF_HASH: u32 = 0x7fff_ffff << 1;
+
+fn tea_transform(buffer: &mut [u32; 4], input: &[u32]) {
+    const DELTA: u32 = 0x9e37_79b9;
+
+    let (mut b0, mut b1) = (buffer[0], buffer[1]);
+    let (a, b, c, d) = (input[0], input[1], input[2], input[3]);
+    let mut sum: u32 = 0;
+
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+
+    buffer[0] = buffer[0].wrapping_add(b0);
+    buffer[1] = buffer[1].wrapping_add(b1);
+}
+
+fn half_md4_transform(buffer: &mut [u32; 4], input: &[u32]) {
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+
+    fn f(x: u32, y: u32, z: u32) -> u32 {
+        z ^ (x & (y ^ z))
+    }
+
+    fn g(x: u32, y: u32, z: u32) -> u32 {
+        (x & y).wrapping_add((x ^ y) & z)
+    }
+
+    fn h(x: u32, y: u32, z: u32) -> u32 {
+        x ^ y ^ z
+    }
+
+    let [mut a, mut b, mut c, mut d] = *buffer;
+
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s)
+        };
+    }
+
+    round!(f, a, b, c, d, input[0], 3);
+    round!(f, d, a, b, c, input[1], 7);
+    round!(f, c, d, a, b, input[2], 11);
+    round!(f, b, c, d, a, input[3], 19);
+    round!(f, a, b, c, d, input[4], 3);
+    round!(f, d, a, b, c, input[5], 7);
+    round!(f, c, d, a, b, input[6], 11);
+    round!(f, b, c, d, a, input[7], 19);
+
+    round!(g, a, b, c, d, input[1].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[3].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[5].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[7].wrapping_add(K2), 13);
+    round!(g, a, b, c, d, input[0].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[2].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[4].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round!(h, a, b, c, d, input[3].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[7].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[2].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[6].wrapping_add(K3), 15);
+    round!(h, a, b, c, d, input[1].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[5].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[0].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buffer[0] = buffer[0].wrapping_add(a);
+    buffer[1] = buffer[1].wrapping_add(b);
+    buffer[2] = buffer[2].wrapping_add(c);
+    buffer[3] = buffer[3].wrapping_add(d);
+}
+
+/// Widens a name byte the way the C implementation does for a signed or unsigned `char`.
+fn widen(byte: u8, unsigned: bool) -> u32 {
+    match unsigned {
+        true => byte as u32,
+        false => byte as i8 as i32 as u32,
+    }
+}
+
+fn legacy_hash(name: &[u8], unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1): (u32, u32) = (0x12a3_fe2d, 0x37ab_e8f9);
+
+    for &byte in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ widen(byte, unsigned).wrapping_mul(7_152_373));
+
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+
+        hash1 = hash0;
+        hash0 = hash;
+    }
+
+    hash0 << 1
+}
+
+/// Packs up to `output.len() * 4` bytes of `name` into words, padding with its length.
+fn to_words(name: &[u8], len: usize, output: &mut [u32], unsigned: bool) {
+    let mut pad = len as u32 | (len as u32) << 8;
+    pad |= pad << 16;
+
+    let mut value = pad;
+    let bytes = name.iter().take(output.len() * 4);
+    let mut words = output.iter_mut();
+
+    for (i, &byte) in bytes.enumerate() {
+        value = widen(byte, unsigned).wrapping_add(value << 8);
+
+        if i % 4 == 3 {
+            *words.next().unwrap() = value;
+            value = pad;
+        }
+    }
+
+    if let Some(word) = words.next() {
+        *word = value;
+    }
+
+    for word in words {
+        *word = pad;
+    }
+}
+
+/// The major hash of `name` in a directory indexed with `version`, `None` if the version is
+/// unknown to this driver.
+pub(crate) fn dx_hash(name: &[u8], version: u8, seed: [u32; 4], unsigned: bool) -> Option<u32> {
+    let mut buffer = match seed.iter().any(|&word| word != 0) {
+        true => seed,
+        false => [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+    };
+
+    let hash = match version {
+        HASH_LEGACY => legacy_hash(name, unsigned),
+        HASH_HALF_MD4 => {
+            let mut input = [0; 8];
+
+            for (i, chunk) in name.chunks(32).enumerate() {
+                to_words(chunk, name.len() - i * 32, &mut input, unsigned);
+                half_md4_transform(&mut buffer, &input);
+            }
+
+            buffer[1]
+        }
+        HASH_TEA => {
+            let mut input = [0; 4];
+
+            for (i, chunk) in name.chunks(16).enumerate() {
+                to_words(chunk, name.len() - i * 16, &mut input, unsigned);
+                tea_transform(&mut buffer, &input);
+            }
+
+            buffer[0]
+        }
+        _ => return None,
+    };
+
+    Some(match hash & !1 {
+        EOF_HASH => EOF_HASH - 2,
+        hash => hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u32; 4] = [0x8a14d509, 0xaf4f2e83, 0x3aae9986, 0x4d60276e];
+
+    #[test]
+    fn test_known_hashes() {
+        // Computed by debugfs's dx_hash with the seed above
+        assert_eq!(
+            dx_hash(b"hello", HASH_HALF_MD4, SEED, false),
+            Some(0x3dc1_85a6)
+        );
+        assert_eq!(dx_hash(b"hello", HASH_TEA, SEED, false), Some(0x5083_3b68));
+        assert_eq!(
+            dx_hash(b"hello", HASH_LEGACY, SEED, false),
+            Some(0x3225_2546)
+        );
+
+        // Names longer than one round of the hash
+        let long = b"abcdefghijklmnopqrstuvwxyz0123456789ABCDEF";
+        assert_eq!(dx_hash(long, HASH_HALF_MD4, SEED, false), Some(0x0982_7d64));
+        assert_eq!(dx_hash(long, HASH_TEA, SEED, false), Some(0xd8f5_6342));
+    }
+
+    #[test]
+    fn test_signedness() {
+        let name = "h\u{e9}llo".as_bytes();
+
+        assert_eq!(
+            dx_hash(b"plain", HASH_HALF_MD4, SEED, false),
+            dx_hash(b"plain", HASH_HALF_MD4, SEED, true)
+        );
+        assert_eq!(dx_hash(name, HASH_HALF_MD4, SEED, false), Some(0x9706_572e));
+        assert_ne!(
+            dx_hash(name, HASH_HALF_MD4, SEED, false),
+            dx_hash(name, HASH_HALF_MD4, SEED, true)
+        );
+        assert_eq!(dx_hash(name, 6, SEED, false), None);
+    }
+}
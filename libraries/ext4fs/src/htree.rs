@@ -0,0 +1,472 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    crc::crc32c,
+    dir::{entry_size, init_leaf, leaf_insert, parse_entries, Found},
+    hash::dx_hash,
+    inode::Inode,
+    raw::{le16, le32, set_le16, set_le32},
+    superblock::INCOMPAT_LARGEDIR,
+    volume::Volume,
+};
+
+/// Where the root information follows the `.` and `..` entries of the first block.
+const ROOT_INFO: usize = 0x18;
+/// Where the entries of an index node follow the empty entry disguising it.
+const NODE_ENTRIES: usize = 0x8;
+const ENTRY_SIZE: usize = 8;
+const TAIL_SIZE: usize = 8;
+
+/// Set in the hash of an index entry whose leaf continues the hash of the previous leaf.
+const COLLISION: u32 = 1;
+
+/// An index block on the path to a leaf.
+struct Frame {
+    logical: u32,
+    block: Vec<u8>,
+    /// Offset of the limit and count fields, the entries follow them
+    entries: usize,
+    /// The entry that was followed
+    position: usize,
+}
+
+impl Frame {
+    fn limit(&self) -> usize {
+        le16(&self.block, self.entries) as usize
+    }
+
+    fn count(&self) -> usize {
+        le16(&self.block, self.entries + 2) as usize
+    }
+
+    fn set_count(&mut self, count: usize) {
+        set_le16(&mut self.block, self.entries + 2, count as u16);
+    }
+
+    fn is_full(&self) -> bool {
+        self.count() >= self.limit()
+    }
+
+    /// The lowest hash found under an entry, the first entry covers everything below the second.
+    fn hash(&self, index: usize) -> u32 {
+        match index {
+            0 => 0,
+            _ => le32(&self.block, self.entries + index * ENTRY_SIZE),
+        }
+    }
+
+    fn child(&self, index: usize) -> u32 {
+        le32(&self.block, self.entries + index * ENTRY_SIZE + 4)
+    }
+
+    /// Every entry as hash and child.
+    fn entry_list(&self) -> Vec<(u32, u32)> {
+        (0..self.count())
+            .map(|i| (self.hash(i), self.child(i)))
+            .collect()
+    }
+
+    /// Replaces the entries, the hash of the first one is not stored.
+    fn set_entries(&mut self, entries: &[(u32, u32)]) {
+        for (i, &(hash, child)) in entries.iter().enumerate() {
+            let offset = self.entries + i * ENTRY_SIZE;
+
+            if i > 0 {
+                set_le32(&mut self.block, offset, hash);
+            }
+
+            set_le32(&mut self.block, offset + 4, child);
+        }
+
+        self.set_count(entries.len());
+    }
+
+    fn insert(&mut self, index: usize, hash: u32, child: u32) {
+        let mut entries = self.entry_list();
+        entries.insert(index, (hash, child));
+
+        self.set_entries(&entries);
+    }
+
+    /// The last entry whose hash is at most `hash`.
+    fn find(&self, hash: u32) -> usize {
+        (1..self.count())
+            .take_while(|&i| self.hash(i) <= hash)
+            .last()
+            .unwrap_or(0)
+    }
+}
+
+impl Volume {
+    fn node_limit(&self) -> usize {
+        let tail = match self.metadata_csum() {
+            true => TAIL_SIZE,
+            false => 0,
+        };
+
+        (self.block_size() - NODE_ENTRIES - tail) / ENTRY_SIZE
+    }
+
+    /// Levels of index nodes below the root an index may grow to.
+    fn max_levels(&self) -> u8 {
+        match self.superblock.has_incompat(INCOMPAT_LARGEDIR) {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    fn dx_checksum(&self, dir: &Inode, block: &[u8], entries: usize) -> Option<(usize, u32)> {
+        let limit = le16(block, entries) as usize;
+        let count = le16(block, entries + 2) as usize;
+        let tail = entries + limit * ENTRY_SIZE;
+
+        if tail + TAIL_SIZE > block.len() {
+            return None;
+        }
+
+        let mut crc = crc32c(
+            dir.checksum_seed(self.checksum_seed),
+            &block[..entries + count * ENTRY_SIZE],
+        );
+        crc = crc32c(crc, &block[tail..tail + 4]);
+        crc = crc32c(crc, &[0; 4]);
+
+        Some((tail, crc))
+    }
+
+    /// Reads an index block, `Unimplemented` means the index can not be used.
+    fn read_frame(&self, dir: &Inode, logical: u32, root: bool) -> FileSystemResult<Frame> {
+        let block = self.read_dir_block(dir, logical)?;
+
+        let entries = match root {
+            true => {
+                if le32(&block, ROOT_INFO) != 0 || block[ROOT_INFO + 5] != 8 {
+                    return Err(FileSystemError::Unimplemented);
+                }
+
+                ROOT_INFO + 8
+            }
+            false => {
+                if le32(&block, 0) != 0 || le16(&block, 4) as usize != self.block_size() {
+                    return Err(FileSystemError::Unimplemented);
+                }
+
+                NODE_ENTRIES
+            }
+        };
+
+        let frame = Frame {
+            logical,
+            block,
+            entries,
+            position: 0,
+        };
+
+        if frame.count() == 0
+            || frame.count() > frame.limit()
+            || entries + frame.limit() * ENTRY_SIZE > self.block_size()
+        {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        if self.metadata_csum() {
+            match self.dx_checksum(dir, &frame.block, entries) {
+                Some((tail, checksum)) if le32(&frame.block, tail + 4) == checksum => (),
+                _ => {
+                    log::warn!(
+                        "ext4: checksum mismatch in index block {} of inode {}",
+                        logical,
+                        dir.number
+                    );
+                    return Err(FileSystemError::FileSystemCorrupted);
+                }
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn write_frame(&mut self, dir: &Inode, frame: &Frame) -> FileSystemResult<()> {
+        let mut block = frame.block.clone();
+
+        if self.metadata_csum() {
+            if let Some((tail, checksum)) = self.dx_checksum(dir, &block, frame.entries) {
+                set_le32(&mut block, tail, 0);
+                set_le32(&mut block, tail + 4, checksum);
+            }
+        }
+
+        let physical = self.map_block(dir, frame.logical)?;
+        self.disk.write_metadata(physical, block);
+
+        Ok(())
+    }
+
+    fn name_hash(&self, root: &[u8], name: &[u8]) -> FileSystemResult<u32> {
+        dx_hash(
+            name,
+            root[ROOT_INFO + 4],
+            self.superblock.hash_seed(),
+            self.superblock.hash_unsigned(),
+        )
+        .ok_or(FileSystemError::Unimplemented)
+    }
+
+    /// The index blocks from the root down to the one pointing at the leaf of `hash`.
+    fn probe(&self, dir: &Inode, name: &[u8]) -> FileSystemResult<(Vec<Frame>, u32)> {
+        let mut root = self.read_frame(dir, 0, true)?;
+        let levels = root.block[ROOT_INFO + 6];
+
+        if levels > self.max_levels() {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        let hash = self.name_hash(&root.block, name)?;
+        root.position = root.find(hash);
+
+        let mut frames = vec![root];
+
+        for _ in 0..levels {
+            let parent = frames.last().unwrap();
+            let mut frame = self.read_frame(dir, parent.child(parent.position), false)?;
+
+            frame.position = frame.find(hash);
+            frames.push(frame);
+        }
+
+        Ok((frames, hash))
+    }
+
+    /// The leaf block that holds `name` if it exists.
+    pub fn htree_leaf(&self, dir: &Inode, name: &[u8]) -> FileSystemResult<u32> {
+        let (frames, _) = self.probe(dir, name)?;
+        let last = frames.last().unwrap();
+
+        Ok(last.child(last.position))
+    }
+
+    pub fn htree_lookup(&self, dir: &Inode, name: &[u8]) -> FileSystemResult<Option<Found>> {
+        let (frames, hash) = self.probe(dir, name)?;
+        let last = frames.last().unwrap();
+        let filetype = self.has_filetype();
+
+        let mut position = last.position;
+
+        loop {
+            let block = self.read_dir_block(dir, last.child(position))?;
+
+            let found = parse_entries(&block, block.len(), filetype)?
+                .into_iter()
+                .find(|e| e.inode != 0 && e.name == name);
+
+            if let Some(entry) = found {
+                return Ok(Some(Found {
+                    inode: entry.inode,
+                    file_type: entry.file_type,
+                }));
+            }
+
+            position += 1;
+
+            // Names of the same hash may continue in the next leaf, possibly under the next node
+            if position == last.count() {
+                return match frames.len() {
+                    1 => Ok(None),
+                    _ => Err(FileSystemError::Unimplemented),
+                };
+            }
+
+            if last.hash(position) != hash | COLLISION {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Moves the entries of a full root into a new index node below it.
+    fn grow_root(&mut self, dir: &mut Inode, frames: &mut Vec<Frame>) -> FileSystemResult<()> {
+        let levels = frames[0].block[ROOT_INFO + 6];
+
+        if levels >= self.max_levels() {
+            log::warn!("ext4: the index of inode {} is full", dir.number);
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        let logical = self.append_dir_block(dir)?;
+
+        let mut node = Frame {
+            logical,
+            block: vec![0; self.block_size()],
+            entries: NODE_ENTRIES,
+            position: frames[0].position,
+        };
+
+        set_le16(&mut node.block, 4, self.block_size() as u16);
+        set_le16(&mut node.block, NODE_ENTRIES, self.node_limit() as u16);
+        node.set_entries(&frames[0].entry_list());
+
+        let root = &mut frames[0];
+        root.set_entries(&[(0, logical)]);
+        root.position = 0;
+        root.block[ROOT_INFO + 6] = levels + 1;
+
+        self.write_frame(dir, &node)?;
+        self.write_frame(dir, &frames[0])?;
+
+        frames.insert(1, node);
+
+        Ok(())
+    }
+
+    /// Makes room for one more entry in `frames[level]`, splitting full index blocks up to the
+    /// root. Returns the level of the frame afterwards, which moves down when the root grows.
+    fn make_room(
+        &mut self,
+        dir: &mut Inode,
+        frames: &mut Vec<Frame>,
+        level: usize,
+    ) -> FileSystemResult<usize> {
+        if !frames[level].is_full() {
+            return Ok(level);
+        }
+
+        if level == 0 {
+            self.grow_root(dir, frames)?;
+            return Ok(1);
+        }
+
+        let level = self.make_room(dir, frames, level - 1)? + 1;
+
+        let entries = frames[level].entry_list();
+        let split = entries.len() / 2;
+
+        let logical = self.append_dir_block(dir)?;
+
+        let mut upper = Frame {
+            logical,
+            block: vec![0; self.block_size()],
+            entries: NODE_ENTRIES,
+            position: 0,
+        };
+
+        set_le16(&mut upper.block, 4, self.block_size() as u16);
+        set_le16(&mut upper.block, NODE_ENTRIES, self.node_limit() as u16);
+        upper.set_entries(&entries[split..]);
+
+        let lower = &mut frames[level];
+        lower.set_entries(&entries[..split]);
+
+        let parent_position = frames[level - 1].position;
+        frames[level - 1].insert(parent_position + 1, entries[split].0, logical);
+
+        self.write_frame(dir, &frames[level])?;
+        self.write_frame(dir, &upper)?;
+        self.write_frame(dir, &frames[level - 1])?;
+
+        // Keep following the half that covers the position
+        if frames[level].position >= split {
+            upper.position = frames[level].position - split;
+            frames[level] = upper;
+            frames[level - 1].position += 1;
+        }
+
+        Ok(level)
+    }
+
+    pub fn htree_insert(
+        &mut self,
+        dir: &mut Inode,
+        name: &[u8],
+        inode: u32,
+        file_type: u8,
+    ) -> FileSystemResult<()> {
+        let (mut frames, hash) = self.probe(dir, name)?;
+
+        let space = self.leaf_space();
+        let filetype = self.has_filetype();
+
+        let last = frames.len() - 1;
+        let leaf = frames[last].child(frames[last].position);
+        let mut block = self.read_dir_block(dir, leaf)?;
+
+        if leaf_insert(&mut block, space, name, inode, file_type, filetype) {
+            return self.write_dir_leaf(dir, leaf, block);
+        }
+
+        let last = self.make_room(dir, &mut frames, last)?;
+
+        // Sort what the leaf holds by hash and move the upper half of the bytes to a new leaf
+        let mut entries = Vec::new();
+
+        for entry in parse_entries(&block, space, filetype)? {
+            if entry.inode != 0 {
+                let entry_hash = self.name_hash(&frames[0].block, entry.name)?;
+                entries.push((
+                    entry_hash,
+                    entry.name.to_vec(),
+                    entry.inode,
+                    entry.file_type,
+                ));
+            }
+        }
+
+        if entries.len() < 2 {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        entries.sort_by_key(|e| e.0);
+
+        let total: usize = entries.iter().map(|e| entry_size(e.1.len())).sum();
+        let mut moved = 0;
+        let mut split = entries.len();
+
+        while split > 1 && moved < total / 2 {
+            split -= 1;
+            moved += entry_size(entries[split].1.len());
+        }
+
+        let split_hash = entries[split].0;
+        let continued = entries[split - 1].0 == split_hash;
+
+        let new_leaf = self.append_dir_block(dir)?;
+
+        let mut lower = vec![0; self.block_size()];
+        let mut upper = vec![0; self.block_size()];
+        init_leaf(&mut lower, space);
+        init_leaf(&mut upper, space);
+
+        for (i, (_, entry_name, entry_inode, entry_type)) in entries.iter().enumerate() {
+            let target = match i < split {
+                true => &mut lower,
+                false => &mut upper,
+            };
+
+            leaf_insert(
+                target,
+                space,
+                entry_name,
+                *entry_inode,
+                *entry_type,
+                filetype,
+            );
+        }
+
+        let target = match hash >= split_hash {
+            true => &mut upper,
+            false => &mut lower,
+        };
+
+        if !leaf_insert(target, space, name, inode, file_type, filetype) {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        self.write_dir_leaf(dir, leaf, lower)?;
+        self.write_dir_leaf(dir, new_leaf, upper)?;
+
+        let frame = &mut frames[last];
+        let position = frame.position;
+        frame.insert(position + 1, split_hash | continued as u32, new_leaf);
+
+        self.write_frame(dir, &frames[last])
+    }
+}
@@ -0,0 +1,242 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    inode::Inode,
+    raw::{le32, set_le32},
+    volume::Volume,
+};
+
+/// Block pointers in the inode that point at data directly.
+const DIRECT_BLOCKS: u64 = 12;
+const SINGLE_INDIRECT: usize = 12;
+const DOUBLE_INDIRECT: usize = 13;
+const TRIPLE_INDIRECT: usize = 14;
+
+/// Indices to follow from the inode down to the pointer of `logical`, the first one indexes the
+/// inode's own pointers.
+fn path(logical: u32, per_block: u64) -> Option<Vec<usize>> {
+    let mut logical = logical as u64;
+
+    if logical < DIRECT_BLOCKS {
+        return Some(vec![logical as usize]);
+    }
+
+    logical -= DIRECT_BLOCKS;
+
+    if logical < per_block {
+        return Some(vec![SINGLE_INDIRECT, logical as usize]);
+    }
+
+    logical -= per_block;
+
+    if logical < per_block * per_block {
+        return Some(vec![
+            DOUBLE_INDIRECT,
+            (logical / per_block) as usize,
+            (logical % per_block) as usize,
+        ]);
+    }
+
+    logical -= per_block * per_block;
+
+    if logical < per_block * per_block * per_block {
+        return Some(vec![
+            TRIPLE_INDIRECT,
+            (logical / (per_block * per_block)) as usize,
+            (logical / per_block % per_block) as usize,
+            (logical % per_block) as usize,
+        ]);
+    }
+
+    None
+}
+
+impl Volume {
+    fn pointers_per_block(&self) -> u64 {
+        (self.block_size() / 4) as u64
+    }
+
+    /// The block of the classic block map holding `logical`, if it is mapped.
+    pub fn indirect_find(&self, inode: &Inode, logical: u32) -> FileSystemResult<Option<u64>> {
+        let Some(path) = path(logical, self.pointers_per_block()) else {
+            return Ok(None);
+        };
+
+        let mut pointer = le32(inode.block_map(), path[0] * 4);
+
+        for &index in path[1..].iter() {
+            if pointer == 0 {
+                return Ok(None);
+            }
+
+            let block = self.disk.read_metadata(pointer as u64)?;
+            pointer = le32(&block, index * 4);
+        }
+
+        Ok(match pointer {
+            0 => None,
+            pointer => Some(pointer as u64),
+        })
+    }
+
+    /// Maps `logical` in the classic block map, allocating the data block and any missing
+    /// indirect block near `goal`. Returns the data block and whether it was just allocated.
+    pub fn indirect_allocate(
+        &mut self,
+        inode: &mut Inode,
+        logical: u32,
+        goal: u64,
+    ) -> FileSystemResult<(u64, bool)> {
+        let path = path(logical, self.pointers_per_block()).ok_or(FileSystemError::InvalidInput)?;
+        let block_size = self.block_size();
+
+        // The block holding the pointer being followed, none while it is in the inode
+        let mut parent: Option<(u64, Vec<u8>)> = None;
+        let mut fresh = false;
+        let mut pointer = 0;
+
+        for (depth, &index) in path.iter().enumerate() {
+            pointer = match &parent {
+                None => le32(inode.block_map(), index * 4) as u64,
+                Some((_, data)) => le32(data, index * 4) as u64,
+            };
+
+            let is_data = depth + 1 == path.len();
+
+            if pointer == 0 {
+                // Blocks in the 32-bit block map can not live past 2^32
+                let (block, _) = self.allocate_blocks(goal, 1)?;
+
+                if block > u32::MAX as u64 {
+                    self.free_blocks(block, 1)?;
+                    return Err(FileSystemError::SpaceNotEnough);
+                }
+
+                inode.add_blocks(1, block_size);
+
+                match &mut parent {
+                    None => set_le32(inode.block_map_mut(), index * 4, block as u32),
+                    Some((parent_block, data)) => {
+                        set_le32(data, index * 4, block as u32);
+                        self.disk.write_metadata(*parent_block, data.clone());
+                    }
+                }
+
+                if !is_data {
+                    self.disk.write_metadata(block, vec![0; block_size]);
+                }
+
+                pointer = block;
+                fresh = is_data;
+            }
+
+            if !is_data {
+                parent = Some((pointer, self.disk.read_metadata(pointer)?));
+            }
+        }
+
+        Ok((pointer, fresh))
+    }
+
+    /// Frees what an indirect block of `depth` levels starting at logical block `base` maps at
+    /// or after `keep`, returns whether the indirect block itself was freed.
+    fn free_indirect(
+        &mut self,
+        inode: &mut Inode,
+        block: u64,
+        depth: u32,
+        base: u64,
+        keep: u64,
+    ) -> FileSystemResult<bool> {
+        let per_block = self.pointers_per_block();
+        let span = per_block.pow(depth - 1);
+
+        let mut data = self.disk.read_metadata(block)?;
+        let mut changed = false;
+
+        for i in 0..per_block as usize {
+            let pointer = le32(&data, i * 4) as u64;
+            let start = base + i as u64 * span;
+
+            if pointer == 0 || start + span <= keep {
+                continue;
+            }
+
+            let freed = match depth {
+                1 => {
+                    self.free_blocks(pointer, 1)?;
+                    inode.add_blocks(-1, self.block_size());
+                    true
+                }
+                _ => self.free_indirect(inode, pointer, depth - 1, start, keep)?,
+            };
+
+            if freed {
+                set_le32(&mut data, i * 4, 0);
+                changed = true;
+            }
+        }
+
+        if data.iter().all(|&b| b == 0) {
+            self.free_blocks(block, 1)?;
+            inode.add_blocks(-1, self.block_size());
+
+            return Ok(true);
+        }
+
+        if changed {
+            self.disk.write_metadata(block, data);
+        }
+
+        Ok(false)
+    }
+
+    /// Frees every block of the classic block map at or after logical block `keep`.
+    pub fn indirect_truncate(&mut self, inode: &mut Inode, keep: u64) -> FileSystemResult<()> {
+        let per_block = self.pointers_per_block();
+
+        for i in (keep.min(DIRECT_BLOCKS) as usize)..DIRECT_BLOCKS as usize {
+            let pointer = le32(inode.block_map(), i * 4) as u64;
+
+            if pointer != 0 {
+                self.free_blocks(pointer, 1)?;
+                inode.add_blocks(-1, self.block_size());
+                set_le32(inode.block_map_mut(), i * 4, 0);
+            }
+        }
+
+        let mut base = DIRECT_BLOCKS;
+
+        for (slot, depth) in [
+            (SINGLE_INDIRECT, 1),
+            (DOUBLE_INDIRECT, 2),
+            (TRIPLE_INDIRECT, 3),
+        ] {
+            let pointer = le32(inode.block_map(), slot * 4) as u64;
+
+            if pointer != 0 && self.free_indirect(inode, pointer, depth, base, keep)? {
+                set_le32(inode.block_map_mut(), slot * 4, 0);
+            }
+
+            base += per_block.pow(depth);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        assert_eq!(path(3, 256), Some(vec![3]));
+        assert_eq!(path(12, 256), Some(vec![12, 0]));
+        assert_eq!(path(12 + 256, 256), Some(vec![13, 0, 0]));
+        assert_eq!(path(12 + 256 + 257, 256), Some(vec![13, 1, 1]));
+        assert_eq!(path(12 + 256 + 65536, 256), Some(vec![14, 0, 0, 0]));
+        assert_eq!(path(u32::MAX, 256), None);
+    }
+}
@@ -0,0 +1,379 @@
+use alloc::vec::Vec;
+use filesystem_abstractions::DirectoryEntryType;
+use timing::TimeSpec;
+
+use crate::{
+    crc::crc32c,
+    raw::{le16, le32, set_le16, set_le32},
+};
+
+pub(crate) const ROOT_INODE: u32 = 2;
+
+pub(crate) const MODE_TYPE_MASK: u16 = 0xf000;
+pub(crate) const MODE_FIFO: u16 = 0x1000;
+pub(crate) const MODE_CHAR: u16 = 0x2000;
+pub(crate) const MODE_DIRECTORY: u16 = 0x4000;
+pub(crate) const MODE_BLOCK: u16 = 0x6000;
+pub(crate) const MODE_FILE: u16 = 0x8000;
+pub(crate) const MODE_SYMLINK: u16 = 0xa000;
+pub(crate) const MODE_SOCKET: u16 = 0xc000;
+
+/// The directory is indexed by an htree.
+pub(crate) const FLAG_INDEX: u32 = 0x1000;
+/// `i_blocks` counts filesystem blocks instead of sectors.
+pub(crate) const FLAG_HUGE_FILE: u32 = 0x40000;
+/// The block map is an extent tree.
+pub(crate) const FLAG_EXTENTS: u32 = 0x80000;
+/// The contents live in the inode itself.
+pub(crate) const FLAG_INLINE_DATA: u32 = 0x1000_0000;
+
+/// Size of the block map in `i_block`, which also holds short symlink targets.
+pub(crate) const BLOCK_MAP_SIZE: usize = 60;
+
+const GOOD_OLD_INODE_SIZE: usize = 128;
+
+/// An on-disk inode.
+pub(crate) struct Inode {
+    pub number: u32,
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    pub fn new(number: u32, raw: Vec<u8>) -> Inode {
+        Inode { number, raw }
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn has_extra(&self, end: usize) -> bool {
+        self.raw.len() > GOOD_OLD_INODE_SIZE
+            && GOOD_OLD_INODE_SIZE + self.extra_isize() as usize >= end
+    }
+
+    pub fn mode(&self) -> u16 {
+        le16(&self.raw, 0x0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        set_le16(&mut self.raw, 0x0, mode);
+    }
+
+    pub fn file_type(&self) -> u16 {
+        self.mode() & MODE_TYPE_MASK
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == MODE_DIRECTORY
+    }
+
+    pub fn entry_type(&self) -> DirectoryEntryType {
+        match self.file_type() {
+            MODE_FIFO => DirectoryEntryType::NamedPipe,
+            MODE_CHAR => DirectoryEntryType::CharDevice,
+            MODE_DIRECTORY => DirectoryEntryType::Directory,
+            MODE_BLOCK => DirectoryEntryType::BlockDevice,
+            MODE_FILE => DirectoryEntryType::File,
+            MODE_SYMLINK => DirectoryEntryType::Symlink,
+            MODE_SOCKET => DirectoryEntryType::Socket,
+            _ => DirectoryEntryType::Unknown,
+        }
+    }
+
+    pub fn uid(&self) -> u32 {
+        le16(&self.raw, 0x2) as u32 | (le16(&self.raw, 0x78) as u32) << 16
+    }
+
+    pub fn gid(&self) -> u32 {
+        le16(&self.raw, 0x18) as u32 | (le16(&self.raw, 0x7a) as u32) << 16
+    }
+
+    pub fn set_uid(&mut self, uid: u32) {
+        set_le16(&mut self.raw, 0x2, uid as u16);
+        set_le16(&mut self.raw, 0x78, (uid >> 16) as u16);
+    }
+
+    pub fn set_gid(&mut self, gid: u32) {
+        set_le16(&mut self.raw, 0x18, gid as u16);
+        set_le16(&mut self.raw, 0x7a, (gid >> 16) as u16);
+    }
+
+    pub fn size(&self) -> u64 {
+        le32(&self.raw, 0x4) as u64 | (le32(&self.raw, 0x6c) as u64) << 32
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        set_le32(&mut self.raw, 0x4, size as u32);
+        set_le32(&mut self.raw, 0x6c, (size >> 32) as u32);
+    }
+
+    fn time(&self, offset: usize, extra: usize) -> TimeSpec {
+        let mut seconds = le32(&self.raw, offset) as i32 as i64;
+        let mut nanoseconds = 0;
+
+        if self.has_extra(extra + 4) {
+            let extra = le32(&self.raw, extra);
+
+            seconds += ((extra & 0x3) as i64) << 32;
+            nanoseconds = (extra >> 2) as i64;
+        }
+
+        TimeSpec {
+            tv_sec: seconds,
+            tv_nsec: nanoseconds,
+        }
+    }
+
+    fn set_time(&mut self, offset: usize, extra: usize, time: TimeSpec) {
+        set_le32(&mut self.raw, offset, time.tv_sec as u32);
+
+        if self.has_extra(extra + 4) {
+            // The epoch bits extend the signed 32-bit seconds past 2038
+            let epoch = ((time.tv_sec - time.tv_sec as i32 as i64) >> 32) as u32 & 0x3;
+            set_le32(&mut self.raw, extra, (time.tv_nsec as u32) << 2 | epoch);
+        }
+    }
+
+    pub fn access_time(&self) -> TimeSpec {
+        self.time(0x8, 0x8c)
+    }
+
+    pub fn change_time(&self) -> TimeSpec {
+        self.time(0xc, 0x84)
+    }
+
+    pub fn modify_time(&self) -> TimeSpec {
+        self.time(0x10, 0x88)
+    }
+
+    pub fn set_access_time(&mut self, time: TimeSpec) {
+        self.set_time(0x8, 0x8c, time);
+    }
+
+    pub fn set_change_time(&mut self, time: TimeSpec) {
+        self.set_time(0xc, 0x84, time);
+    }
+
+    pub fn set_modify_time(&mut self, time: TimeSpec) {
+        self.set_time(0x10, 0x88, time);
+    }
+
+    pub fn set_creation_time(&mut self, time: TimeSpec) {
+        if self.has_extra(0x98) {
+            self.set_time(0x90, 0x94, time);
+        }
+    }
+
+    /// Sets the modification and change times, as writing contents does.
+    pub fn touch(&mut self, now: TimeSpec) {
+        self.set_modify_time(now);
+        self.set_change_time(now);
+    }
+
+    pub fn set_deletion_time(&mut self, seconds: u32) {
+        set_le32(&mut self.raw, 0x14, seconds);
+    }
+
+    pub fn links(&self) -> u16 {
+        le16(&self.raw, 0x1a)
+    }
+
+    pub fn set_links(&mut self, links: u16) {
+        set_le16(&mut self.raw, 0x1a, links);
+    }
+
+    /// Space taken by the inode, in 512-byte sectors.
+    pub fn sectors(&self, block_size: usize) -> u64 {
+        let count = le32(&self.raw, 0x1c) as u64 | (le16(&self.raw, 0x74) as u64) << 32;
+
+        match self.flags() & FLAG_HUGE_FILE {
+            0 => count,
+            _ => count * (block_size / 512) as u64,
+        }
+    }
+
+    fn set_sectors(&mut self, sectors: u64, block_size: usize) {
+        let count = match self.flags() & FLAG_HUGE_FILE {
+            0 => sectors,
+            _ => sectors / (block_size / 512) as u64,
+        };
+
+        set_le32(&mut self.raw, 0x1c, count as u32);
+        set_le16(&mut self.raw, 0x74, (count >> 32) as u16);
+    }
+
+    /// Accounts for `count` blocks being added to or, if negative, removed from the inode.
+    pub fn add_blocks(&mut self, count: i64, block_size: usize) {
+        let sectors = self.sectors(block_size) as i64 + count * (block_size / 512) as i64;
+
+        self.set_sectors(sectors.max(0) as u64, block_size);
+    }
+
+    pub fn flags(&self) -> u32 {
+        le32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        set_le32(&mut self.raw, 0x20, flags);
+    }
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags() & flag != 0
+    }
+
+    /// The 60 bytes of `i_block`, a block map or a short symlink target.
+    pub fn block_map(&self) -> &[u8] {
+        &self.raw[0x28..0x28 + BLOCK_MAP_SIZE]
+    }
+
+    pub fn block_map_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[0x28..0x28 + BLOCK_MAP_SIZE]
+    }
+
+    pub fn generation(&self) -> u32 {
+        le32(&self.raw, 0x64)
+    }
+
+    pub fn set_generation(&mut self, generation: u32) {
+        set_le32(&mut self.raw, 0x64, generation);
+    }
+
+    /// The block holding extended attributes that do not fit in the inode, `i_file_acl`.
+    pub fn xattr_block(&self) -> u64 {
+        le32(&self.raw, 0x68) as u64 | (le16(&self.raw, 0x76) as u64) << 32
+    }
+
+    pub fn set_xattr_block(&mut self, block: u64) {
+        set_le32(&mut self.raw, 0x68, block as u32);
+        set_le16(&mut self.raw, 0x76, (block >> 32) as u16);
+    }
+
+    /// The space after the extra fields, which may hold extended attributes.
+    pub fn xattr_space(&self) -> &[u8] {
+        let start = (GOOD_OLD_INODE_SIZE + self.extra_isize() as usize).min(self.raw.len());
+
+        &self.raw[start..]
+    }
+
+    pub fn xattr_space_mut(&mut self) -> &mut [u8] {
+        let start = (GOOD_OLD_INODE_SIZE + self.extra_isize() as usize).min(self.raw.len());
+
+        &mut self.raw[start..]
+    }
+
+    pub fn extra_isize(&self) -> u16 {
+        match self.raw.len() > GOOD_OLD_INODE_SIZE {
+            true => le16(&self.raw, 0x80),
+            false => 0,
+        }
+    }
+
+    pub fn set_extra_isize(&mut self, size: u16) {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            set_le16(&mut self.raw, 0x80, size);
+        }
+    }
+
+    /// The seed of checksums over blocks owned by this inode.
+    pub fn checksum_seed(&self, filesystem_seed: u32) -> u32 {
+        let crc = crc32c(filesystem_seed, &self.number.to_le_bytes());
+
+        crc32c(crc, &self.generation().to_le_bytes())
+    }
+
+    fn checksum(&self, filesystem_seed: u32) -> u32 {
+        let has_high = self.has_extra(0x84);
+
+        let mut crc = crc32c(self.checksum_seed(filesystem_seed), &self.raw[..0x7c]);
+        crc = crc32c(crc, &[0, 0]);
+        crc = crc32c(crc, &self.raw[0x7e..GOOD_OLD_INODE_SIZE]);
+
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            crc = crc32c(crc, &self.raw[GOOD_OLD_INODE_SIZE..0x82]);
+
+            match has_high {
+                true => {
+                    crc = crc32c(crc, &[0, 0]);
+                    crc = crc32c(crc, &self.raw[0x84..]);
+                }
+                false => crc = crc32c(crc, &self.raw[0x82..]),
+            }
+        }
+
+        match has_high {
+            true => crc,
+            false => crc & 0xffff,
+        }
+    }
+
+    pub fn verify_checksum(&self, filesystem_seed: u32) -> bool {
+        let mut stored = le16(&self.raw, 0x7c) as u32;
+
+        if self.has_extra(0x84) {
+            stored |= (le16(&self.raw, 0x82) as u32) << 16;
+        }
+
+        stored == self.checksum(filesystem_seed)
+    }
+
+    pub fn update_checksum(&mut self, filesystem_seed: u32) {
+        let checksum = self.checksum(filesystem_seed);
+
+        set_le16(&mut self.raw, 0x7c, checksum as u16);
+
+        if self.has_extra(0x84) {
+            set_le16(&mut self.raw, 0x82, (checksum >> 16) as u16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn test_timestamps() {
+        let mut inode = Inode::new(12, vec![0; 256]);
+        inode.set_extra_isize(32);
+
+        let time = TimeSpec {
+            tv_sec: 0x1_2345_6789,
+            tv_nsec: 123_456_789,
+        };
+
+        inode.set_modify_time(time);
+        assert_eq!(inode.modify_time(), time);
+
+        // Without the extra fields only seconds within 32 bits survive
+        let mut small = Inode::new(12, vec![0; 128]);
+        small.set_modify_time(time);
+        assert_eq!(small.modify_time().tv_nsec, 0);
+    }
+
+    #[test]
+    fn test_checksum_roundtrip() {
+        let mut inode = Inode::new(12, vec![0; 256]);
+        inode.set_extra_isize(32);
+        inode.set_mode(MODE_FILE | 0o644);
+
+        inode.update_checksum(0x1234);
+        assert!(inode.verify_checksum(0x1234));
+
+        inode.set_size(5);
+        assert!(!inode.verify_checksum(0x1234));
+    }
+
+    #[test]
+    fn test_block_accounting() {
+        let mut inode = Inode::new(12, vec![0; 256]);
+
+        inode.add_blocks(3, 4096);
+        assert_eq!(inode.sectors(4096), 24);
+
+        inode.add_blocks(-1, 4096);
+        assert_eq!(inode.sectors(4096), 16);
+    }
+}
@@ -0,0 +1,578 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use timing::TimeSpec;
+
+use crate::{
+    crc::crc32c,
+    disk::Disk,
+    raw::{be16, be32, set_be16, set_be32, set_be64},
+};
+
+const MAGIC: u32 = 0xc03b_3998;
+
+const BLOCK_DESCRIPTOR: u32 = 1;
+const BLOCK_COMMIT: u32 = 2;
+const BLOCK_SUPERBLOCK_V1: u32 = 3;
+const BLOCK_SUPERBLOCK_V2: u32 = 4;
+const BLOCK_REVOKE: u32 = 5;
+
+const COMPAT_CHECKSUM: u32 = 0x1;
+
+const INCOMPAT_REVOKE: u32 = 0x1;
+const INCOMPAT_64BIT: u32 = 0x2;
+const INCOMPAT_ASYNC_COMMIT: u32 = 0x4;
+const INCOMPAT_CSUM_V2: u32 = 0x8;
+const INCOMPAT_CSUM_V3: u32 = 0x10;
+
+const INCOMPAT_SUPPORTED: u32 =
+    INCOMPAT_REVOKE | INCOMPAT_64BIT | INCOMPAT_ASYNC_COMMIT | INCOMPAT_CSUM_V2 | INCOMPAT_CSUM_V3;
+
+const CHECKSUM_TYPE_CRC32C: u8 = 4;
+
+const TAG_ESCAPE: u32 = 0x1;
+const TAG_SAME_UUID: u32 = 0x2;
+const TAG_LAST: u32 = 0x8;
+
+const HEADER_SIZE: usize = 12;
+const UUID_SIZE: usize = 16;
+
+/// A block of a transaction found in the log.
+struct LoggedBlock {
+    sequence: u32,
+    target: u64,
+    position: u32,
+    flags: u32,
+    checksum: u32,
+}
+
+/// What a scan of the log found.
+#[derive(Default)]
+struct Scan {
+    blocks: Vec<LoggedBlock>,
+    /// The latest transaction that revoked each block
+    revoked: BTreeMap<u64, u32>,
+    /// The first transaction that did not commit
+    end: u32,
+}
+
+/// A jbd2 journal living in the blocks of an inode.
+///
+/// Every transaction is checkpointed right after it commits, so the log is empty whenever no
+/// commit is running and transactions always start at the beginning of the log.
+pub(crate) struct Journal {
+    /// The device block of every journal block
+    blocks: Vec<u64>,
+    block_size: usize,
+    /// The first journal block, holding the journal superblock
+    superblock: Vec<u8>,
+}
+
+impl Journal {
+    pub fn open(disk: &Disk, blocks: Vec<u64>, block_size: usize) -> FileSystemResult<Journal> {
+        let Some(&first) = blocks.first() else {
+            return Err(FileSystemError::FileSystemCorrupted);
+        };
+
+        let journal = Journal {
+            superblock: disk.read_block(first)?,
+            blocks,
+            block_size,
+        };
+
+        let block_type = be32(&journal.superblock, 0x4);
+
+        if be32(&journal.superblock, 0x0) != MAGIC
+            || !matches!(block_type, BLOCK_SUPERBLOCK_V1 | BLOCK_SUPERBLOCK_V2)
+            || be32(&journal.superblock, 0xc) as usize != block_size
+            || journal.max_len() as usize > journal.blocks.len()
+            || journal.first() == 0
+            || journal.first() >= journal.max_len()
+        {
+            log::warn!("ext4: invalid journal superblock");
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        if journal.incompat() & !INCOMPAT_SUPPORTED != 0 {
+            log::warn!(
+                "ext4: unsupported journal features {:#x}",
+                journal.incompat()
+            );
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        if journal.has_checksums()
+            && be32(&journal.superblock, 0xfc) != journal.superblock_checksum()
+        {
+            log::warn!("ext4: journal superblock checksum mismatch");
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        Ok(journal)
+    }
+
+    fn max_len(&self) -> u32 {
+        be32(&self.superblock, 0x10)
+    }
+
+    fn first(&self) -> u32 {
+        be32(&self.superblock, 0x14)
+    }
+
+    fn sequence(&self) -> u32 {
+        be32(&self.superblock, 0x18)
+    }
+
+    fn start(&self) -> u32 {
+        be32(&self.superblock, 0x1c)
+    }
+
+    fn incompat(&self) -> u32 {
+        match be32(&self.superblock, 0x4) {
+            BLOCK_SUPERBLOCK_V2 => be32(&self.superblock, 0x28),
+            _ => 0,
+        }
+    }
+
+    fn has_incompat(&self, feature: u32) -> bool {
+        self.incompat() & feature != 0
+    }
+
+    fn has_checksums(&self) -> bool {
+        self.has_incompat(INCOMPAT_CSUM_V2 | INCOMPAT_CSUM_V3)
+    }
+
+    fn checksum_seed(&self) -> u32 {
+        crc32c(!0, &self.superblock[0x30..0x30 + UUID_SIZE])
+    }
+
+    fn superblock_checksum(&self) -> u32 {
+        let mut superblock = self.superblock[..1024].to_vec();
+        superblock[0xfc..0x100].fill(0);
+
+        crc32c(!0, &superblock)
+    }
+
+    fn tag_size(&self) -> usize {
+        match (
+            self.has_incompat(INCOMPAT_CSUM_V3),
+            self.has_incompat(INCOMPAT_64BIT),
+        ) {
+            (true, _) => 16,
+            (false, true) => 12,
+            (false, false) => 8,
+        }
+    }
+
+    /// Bytes at the end of descriptor and revoke blocks taken by their checksum.
+    fn tail_size(&self) -> usize {
+        match self.has_checksums() {
+            true => 4,
+            false => 0,
+        }
+    }
+
+    /// Whether the log holds transactions that have to be replayed.
+    pub fn needs_recovery(&self) -> bool {
+        self.start() != 0
+    }
+
+    fn write_superblock(&mut self, disk: &Disk) -> FileSystemResult<()> {
+        if self.has_checksums() {
+            let checksum = self.superblock_checksum();
+            set_be32(&mut self.superblock, 0xfc, checksum);
+        }
+
+        disk.write_block(self.blocks[0], &self.superblock)?;
+        disk.flush()
+    }
+
+    /// Turns on the features a filesystem with `metadata_csum` and `is_64bit` logs with, like
+    /// Linux does when mounting.
+    pub fn enable_features(
+        &mut self,
+        disk: &Disk,
+        metadata_csum: bool,
+        is_64bit: bool,
+    ) -> FileSystemResult<()> {
+        if be32(&self.superblock, 0x4) != BLOCK_SUPERBLOCK_V2 {
+            return Ok(());
+        }
+
+        let mut incompat = self.incompat();
+
+        if metadata_csum && !self.has_checksums() {
+            incompat |= INCOMPAT_CSUM_V3;
+
+            let compat = be32(&self.superblock, 0x24) & !COMPAT_CHECKSUM;
+            set_be32(&mut self.superblock, 0x24, compat);
+            self.superblock[0x50] = CHECKSUM_TYPE_CRC32C;
+        }
+
+        if is_64bit {
+            incompat |= INCOMPAT_64BIT;
+        }
+
+        if incompat == self.incompat() {
+            return Ok(());
+        }
+
+        set_be32(&mut self.superblock, 0x28, incompat);
+
+        self.write_superblock(disk)
+    }
+
+    fn next(&self, position: u32) -> u32 {
+        match position + 1 >= self.max_len() {
+            true => self.first(),
+            false => position + 1,
+        }
+    }
+
+    fn read(&self, disk: &Disk, position: u32) -> FileSystemResult<Vec<u8>> {
+        disk.read_block(self.blocks[position as usize])
+    }
+
+    fn block_checksum_matches(&self, block: &[u8]) -> bool {
+        if !self.has_checksums() {
+            return true;
+        }
+
+        let tail = block.len() - 4;
+
+        let mut copy = block.to_vec();
+        copy[tail..].fill(0);
+
+        be32(block, tail) == crc32c(self.checksum_seed(), &copy)
+    }
+
+    fn commit_checksum_matches(&self, block: &[u8]) -> bool {
+        if !self.has_checksums() {
+            return true;
+        }
+
+        let mut copy = block.to_vec();
+        copy[0x10..0x14].fill(0);
+
+        be32(block, 0x10) == crc32c(self.checksum_seed(), &copy)
+    }
+
+    fn data_checksum(&self, sequence: u32, data: &[u8]) -> u32 {
+        let crc = crc32c(self.checksum_seed(), &sequence.to_be_bytes());
+
+        crc32c(crc, data)
+    }
+
+    /// Walks the committed transactions in the log.
+    fn scan(&self, disk: &Disk) -> FileSystemResult<Scan> {
+        let mut scan = Scan::default();
+        let mut pending = Vec::new();
+        let mut pending_revokes = Vec::new();
+
+        let mut position = self.start();
+        let mut sequence = self.sequence();
+
+        loop {
+            let block = self.read(disk, position)?;
+
+            if be32(&block, 0x0) != MAGIC || be32(&block, 0x8) != sequence {
+                break;
+            }
+
+            match be32(&block, 0x4) {
+                BLOCK_DESCRIPTOR => {
+                    if !self.block_checksum_matches(&block) {
+                        break;
+                    }
+
+                    let mut offset = HEADER_SIZE;
+                    let end = self.block_size - self.tail_size();
+
+                    while offset + self.tag_size() <= end {
+                        let (target, flags, checksum) = self.parse_tag(&block[offset..]);
+
+                        offset += self.tag_size();
+
+                        if flags & TAG_SAME_UUID == 0 {
+                            offset += UUID_SIZE;
+                        }
+
+                        position = self.next(position);
+
+                        pending.push(LoggedBlock {
+                            sequence,
+                            target,
+                            position,
+                            flags,
+                            checksum,
+                        });
+
+                        if flags & TAG_LAST != 0 {
+                            break;
+                        }
+                    }
+                }
+                BLOCK_REVOKE => {
+                    if !self.block_checksum_matches(&block) {
+                        break;
+                    }
+
+                    let record_size = match self.has_incompat(INCOMPAT_64BIT) {
+                        true => 8,
+                        false => 4,
+                    };
+
+                    let used = (be32(&block, 0xc) as usize).min(self.block_size);
+                    let mut offset = 16;
+
+                    while offset + record_size <= used {
+                        let target = match record_size {
+                            8 => {
+                                (be32(&block, offset) as u64) << 32
+                                    | be32(&block, offset + 4) as u64
+                            }
+                            _ => be32(&block, offset) as u64,
+                        };
+
+                        pending_revokes.push(target);
+                        offset += record_size;
+                    }
+                }
+                BLOCK_COMMIT => {
+                    if !self.commit_checksum_matches(&block) {
+                        break;
+                    }
+
+                    scan.blocks.append(&mut pending);
+
+                    for target in pending_revokes.drain(..) {
+                        scan.revoked.insert(target, sequence);
+                    }
+
+                    sequence = sequence.wrapping_add(1);
+                }
+                _ => break,
+            }
+
+            position = self.next(position);
+        }
+
+        scan.end = sequence;
+
+        Ok(scan)
+    }
+
+    /// Returns the target block, the flags and the checksum of the tag at the start of `tag`.
+    fn parse_tag(&self, tag: &[u8]) -> (u64, u32, u32) {
+        let high = match self.has_incompat(INCOMPAT_64BIT) {
+            true => be32(tag, 8) as u64,
+            false => 0,
+        };
+
+        let target = high << 32 | be32(tag, 0) as u64;
+
+        match self.has_incompat(INCOMPAT_CSUM_V3) {
+            true => (target, be32(tag, 4), be32(tag, 12)),
+            false => (target, be16(tag, 6) as u32, be16(tag, 4) as u32),
+        }
+    }
+
+    /// Replays every committed transaction in the log to its place and empties the log.
+    pub fn recover(&mut self, disk: &Disk) -> FileSystemResult<()> {
+        let scan = self.scan(disk)?;
+
+        let mut replayed = 0;
+
+        for logged in scan.blocks.iter() {
+            // A later transaction revoked the block, what was logged before is stale
+            if matches!(scan.revoked.get(&logged.target), Some(&revoker) if revoker >= logged.sequence)
+            {
+                continue;
+            }
+
+            let mut data = self.read(disk, logged.position)?;
+
+            if self.has_checksums() {
+                let mut checksum = self.data_checksum(logged.sequence, &data);
+
+                if !self.has_incompat(INCOMPAT_CSUM_V3) {
+                    checksum &= 0xffff;
+                }
+
+                if checksum != logged.checksum {
+                    log::warn!(
+                        "ext4: skipping journaled block {} with a bad checksum",
+                        logged.target
+                    );
+                    continue;
+                }
+            }
+
+            if logged.flags & TAG_ESCAPE != 0 {
+                set_be32(&mut data, 0, MAGIC);
+            }
+
+            disk.write_block(logged.target, &data)?;
+            replayed += 1;
+        }
+
+        disk.flush()?;
+
+        log::info!(
+            "ext4: replayed {} blocks from transactions {}..{}",
+            replayed,
+            self.sequence(),
+            scan.end
+        );
+
+        set_be32(&mut self.superblock, 0x18, scan.end.wrapping_add(1));
+        set_be32(&mut self.superblock, 0x1c, 0);
+
+        self.write_superblock(disk)
+    }
+
+    fn tags_per_descriptor(&self) -> usize {
+        let space = self.block_size - HEADER_SIZE - self.tail_size() - UUID_SIZE;
+
+        space / self.tag_size()
+    }
+
+    fn descriptors_for(&self, count: usize) -> usize {
+        count.div_ceil(self.tags_per_descriptor())
+    }
+
+    /// The most metadata blocks a single transaction can log.
+    pub fn capacity(&self) -> usize {
+        let space = (self.max_len() - self.first()) as usize - 1;
+        let mut count = space * self.tags_per_descriptor() / (self.tags_per_descriptor() + 1);
+
+        while count > 0 && count + self.descriptors_for(count) > space {
+            count -= 1;
+        }
+
+        count
+    }
+
+    fn header(&self, block: &mut [u8], block_type: u32) {
+        set_be32(block, 0x0, MAGIC);
+        set_be32(block, 0x4, block_type);
+        set_be32(block, 0x8, self.sequence());
+    }
+
+    fn seal(&self, block: &mut [u8]) {
+        if self.has_checksums() {
+            let tail = block.len() - 4;
+            block[tail..].fill(0);
+
+            let checksum = crc32c(self.checksum_seed(), block);
+            set_be32(block, tail, checksum);
+        }
+    }
+
+    /// Logs `blocks` as a transaction and makes the log point at it, after this returns the
+    /// blocks survive a crash.
+    pub fn log(
+        &mut self,
+        disk: &Disk,
+        blocks: &[(u64, &[u8])],
+        now: TimeSpec,
+    ) -> FileSystemResult<()> {
+        debug_assert!(blocks.len() <= self.capacity());
+
+        let sequence = self.sequence();
+        let mut position = self.first();
+
+        for chunk in blocks.chunks(self.tags_per_descriptor()) {
+            let mut descriptor = vec![0; self.block_size];
+            self.header(&mut descriptor, BLOCK_DESCRIPTOR);
+
+            let mut offset = HEADER_SIZE;
+            let descriptor_position = position;
+
+            for (i, &(target, data)) in chunk.iter().enumerate() {
+                let mut logged = data.to_vec();
+                let mut flags = 0;
+
+                // The log must not contain anything that looks like a journal block header
+                if be32(&logged, 0) == MAGIC {
+                    logged[..4].fill(0);
+                    flags |= TAG_ESCAPE;
+                }
+
+                if i > 0 {
+                    flags |= TAG_SAME_UUID;
+                }
+
+                if i + 1 == chunk.len() {
+                    flags |= TAG_LAST;
+                }
+
+                let checksum = self.data_checksum(sequence, &logged);
+                let tag = &mut descriptor[offset..offset + self.tag_size()];
+
+                set_be32(tag, 0, target as u32);
+
+                if self.has_incompat(INCOMPAT_CSUM_V3) {
+                    set_be32(tag, 4, flags);
+                    set_be32(tag, 12, checksum);
+                } else {
+                    if self.has_incompat(INCOMPAT_CSUM_V2) {
+                        set_be16(tag, 4, checksum as u16);
+                    }
+
+                    set_be16(tag, 6, flags as u16);
+                }
+
+                if self.has_incompat(INCOMPAT_64BIT) {
+                    set_be32(tag, 8, (target >> 32) as u32);
+                }
+
+                offset += self.tag_size();
+
+                if i == 0 {
+                    descriptor[offset..offset + UUID_SIZE]
+                        .copy_from_slice(&self.superblock[0x30..0x30 + UUID_SIZE]);
+                    offset += UUID_SIZE;
+                }
+
+                position += 1;
+                disk.write_block(self.blocks[position as usize], &logged)?;
+            }
+
+            self.seal(&mut descriptor);
+            disk.write_block(self.blocks[descriptor_position as usize], &descriptor)?;
+
+            position += 1;
+        }
+
+        // Everything the commit block vouches for has to be on the device before it
+        disk.flush()?;
+
+        let mut commit = vec![0; self.block_size];
+        self.header(&mut commit, BLOCK_COMMIT);
+        set_be64(&mut commit, 0x30, now.tv_sec as u64);
+        set_be32(&mut commit, 0x38, now.tv_nsec as u32);
+
+        if self.has_checksums() {
+            let checksum = crc32c(self.checksum_seed(), &commit);
+            set_be32(&mut commit, 0x10, checksum);
+        }
+
+        disk.write_block(self.blocks[position as usize], &commit)?;
+        disk.flush()?;
+
+        let first = self.first();
+        set_be32(&mut self.superblock, 0x1c, first);
+
+        self.write_superblock(disk)
+    }
+
+    /// Empties the log once the last transaction reached its place.
+    pub fn checkpointed(&mut self, disk: &Disk) -> FileSystemResult<()> {
+        let sequence = self.sequence().wrapping_add(1);
+
+        set_be32(&mut self.superblock, 0x18, sequence);
+        set_be32(&mut self.superblock, 0x1c, 0);
+
+        self.write_superblock(disk)
+    }
+}
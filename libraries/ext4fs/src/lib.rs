@@ -0,0 +1,647 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use block_abstractions::{BlockDevices, IBlockDevice};
+use filesystem_abstractions::{
+    DirectoryEntryType, FileSystemResult, IFileSystem, IFileSystemType, IInode, MountFlags,
+};
+use hermit_sync::SpinMutex;
+use threading::IClock;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod bitmap;
+mod crc;
+mod dir;
+mod disk;
+mod extent;
+mod file;
+mod group;
+mod hash;
+mod htree;
+mod indirect;
+mod inode;
+mod journal;
+mod node;
+mod ops;
+mod raw;
+mod superblock;
+mod volume;
+mod xattr;
+
+pub use node::Ext4Inode;
+
+use inode::ROOT_INODE;
+use volume::Volume;
+
+/// An ext2, ext3 or ext4 filesystem on a block device.
+///
+/// Metadata changes are collected in memory and committed through the journal, if there is one,
+/// in ordered mode: file contents reach the device before the metadata pointing at them.
+pub struct Ext4FileSystem {
+    this: Weak<Ext4FileSystem>,
+    volume: SpinMutex<Volume>,
+    /// How many [`Ext4Inode`]s of each inode are alive
+    open: SpinMutex<BTreeMap<u32, usize>>,
+}
+
+impl Ext4FileSystem {
+    /// Mounts the filesystem on `device`, replaying the journal if the last mount did not
+    /// finish. The filesystem is read-only if the device is or if it uses features that this
+    /// driver can not write.
+    pub fn mount(
+        device: Arc<dyn IBlockDevice>,
+        clock: Arc<dyn IClock>,
+    ) -> FileSystemResult<Arc<Ext4FileSystem>> {
+        let volume = Volume::mount(device, clock)?;
+
+        Ok(Arc::new_cyclic(|this| Ext4FileSystem {
+            this: this.clone(),
+            volume: SpinMutex::new(volume),
+            open: SpinMutex::new(BTreeMap::new()),
+        }))
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.volume.lock().readonly
+    }
+
+    fn opened(&self, number: u32) {
+        *self.open.lock().entry(number).or_insert(0) += 1;
+    }
+
+    /// Returns whether the last user of the inode is gone.
+    fn closed(&self, number: u32) -> bool {
+        let mut open = self.open.lock();
+
+        let Some(count) = open.get_mut(&number) else {
+            return true;
+        };
+
+        *count -= 1;
+
+        match *count {
+            0 => open.remove(&number).is_some(),
+            _ => false,
+        }
+    }
+
+    fn is_open(&self, number: u32) -> bool {
+        self.open.lock().contains_key(&number)
+    }
+}
+
+impl IFileSystem for Ext4FileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        Ext4Inode::new(
+            self.this.upgrade().unwrap(),
+            ROOT_INODE,
+            "",
+            DirectoryEntryType::Directory,
+        )
+    }
+
+    fn name(&self) -> &str {
+        "ext4"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.volume.lock().commit()
+    }
+}
+
+impl Drop for Ext4FileSystem {
+    fn drop(&mut self) {
+        if let Err(e) = self.volume.get_mut().commit() {
+            log::warn!("ext4: failed to commit while unmounting: {:?}", e);
+        }
+    }
+}
+
+/// Lets `mount` open ext2, ext3 and ext4 filesystems on the registered block devices.
+pub struct Ext4FileSystemType {
+    devices: Arc<BlockDevices>,
+    clock: Arc<dyn IClock>,
+}
+
+impl Ext4FileSystemType {
+    pub fn new(devices: Arc<BlockDevices>, clock: Arc<dyn IClock>) -> Arc<Ext4FileSystemType> {
+        Arc::new(Ext4FileSystemType { devices, clock })
+    }
+}
+
+impl IFileSystemType for Ext4FileSystemType {
+    fn name(&self) -> &str {
+        "ext4"
+    }
+
+    fn create(&self, source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        let device = self.devices.get(source)?;
+
+        Ok(Ext4FileSystem::mount(device, self.clock.clone())?)
+    }
+}
+
+// The driver is checked against the e2fsprogs of the host, which make symlinks in the images
+// they are given directories for, so only unix hosts run the tests
+#[cfg(all(test, unix))]
+mod tests {
+    extern crate std;
+
+    use alloc::{format, string::String, vec, vec::Vec};
+    use std::{os::unix::fs::symlink, path::Path, process::Command};
+
+    use filesystem_abstractions::{
+        DirectoryTreeNode, FileMode, FileStatistics, FileSystemError, InodeAttributes, XattrFlags,
+    };
+    use test_utilities::{block::DiskImage, tempdir::TempDir};
+    use timing::TimeSpec;
+
+    use super::*;
+
+    struct FixedClock;
+
+    impl IClock for FixedClock {
+        fn now(&self) -> TimeSpec {
+            TimeSpec {
+                tv_sec: 1_700_000_000,
+                tv_nsec: 0,
+            }
+        }
+    }
+
+    /// A fresh 16MiB image formatted by the host's mke2fs with `options`, populated from `from`.
+    fn format(options: &[&str], from: Option<&Path>) -> Arc<DiskImage> {
+        let image = DiskImage::temporary(1024, 16 * 1024).unwrap();
+
+        let mut command = Command::new("mke2fs");
+        command.args(["-q", "-F"]).args(options);
+
+        if let Some(from) = from {
+            command.arg("-d").arg(from);
+        }
+
+        let status = command.arg(image.path().unwrap()).status().unwrap();
+        assert!(status.success(), "mke2fs failed");
+
+        image
+    }
+
+    fn run(program: &str, args: &[&str], image: &DiskImage) -> (bool, Vec<u8>) {
+        let output = Command::new(program)
+            .args(args)
+            .arg(image.path().unwrap())
+            .output()
+            .unwrap();
+
+        (output.status.success(), output.stdout)
+    }
+
+    /// Checks the image with the host's e2fsck without changing it.
+    fn check(image: &DiskImage) {
+        let (clean, output) = run("e2fsck", &["-fn"], image);
+
+        assert!(clean, "e2fsck: {}", String::from_utf8_lossy(&output));
+    }
+
+    /// Reads a file through the host's debugfs.
+    fn host_read(image: &DiskImage, path: &str) -> Vec<u8> {
+        run("debugfs", &["-R", &format!("cat {}", path)], image).1
+    }
+
+    fn mount(image: &Arc<DiskImage>) -> Arc<Ext4FileSystem> {
+        Ext4FileSystem::mount(image.clone(), Arc::new(FixedClock)).unwrap()
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+    }
+
+    fn read_all(inode: &Arc<dyn IInode>) -> Vec<u8> {
+        let mut buffer = vec![0; inode.metadata().size];
+        let read = inode.readat(0, &mut buffer).unwrap();
+        assert_eq!(read, buffer.len());
+
+        buffer
+    }
+
+    #[allow(deprecated)]
+    fn open(root: &Arc<dyn IInode>, path: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let mut current = root.clone();
+
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            current = current.lookup(part)?;
+        }
+
+        Ok(current)
+    }
+
+    #[test]
+    fn test_reads_host_files() {
+        let source = TempDir::new("ext4");
+        let big = pattern(300 * 1024);
+
+        std::fs::write(source.join("hello.txt"), b"Hello, ext4!").unwrap();
+        std::fs::create_dir_all(source.join("nested/deeper")).unwrap();
+        std::fs::write(source.join("nested/deeper/big.bin"), &big).unwrap();
+        symlink("nested/deeper/big.bin", source.join("short")).unwrap();
+        symlink("x".repeat(100), source.join("long")).unwrap();
+
+        let image = format(&["-t", "ext4"], Some(source.path()));
+        let fs = mount(&image);
+
+        let root = DirectoryTreeNode::from_filesystem(None, fs.clone(), None);
+
+        let hello = root.open("/hello.txt", Some(&root)).unwrap();
+        assert_eq!(hello.readall().unwrap(), b"Hello, ext4!");
+
+        let followed = root.open("/short", Some(&root)).unwrap();
+        assert_eq!(followed.readall().unwrap(), big);
+
+        let mut names: Vec<String> = root
+            .read_dir()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.filename)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [".", "hello.txt", "long", "lost+found", "nested", "short"]
+        );
+
+        let inode = fs.root_dir();
+        assert_eq!(
+            open(&inode, "long").unwrap().resolve_link(),
+            Some("x".repeat(100))
+        );
+
+        let file = open(&inode, "nested/deeper/big.bin").unwrap();
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        file.stat(&mut stat).unwrap();
+        assert_eq!(stat.size, big.len() as u64);
+        assert_eq!(stat.mode.bits() & 0o170000, 0o100000);
+        assert_eq!(stat.link_count, 1);
+
+        let mut middle = [0; 5000];
+        assert_eq!(file.readat(123_456, &mut middle).unwrap(), 5000);
+        assert_eq!(middle, big[123_456..128_456]);
+        assert_eq!(file.readat(big.len() - 10, &mut middle).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_writes_pass_fsck() {
+        for options in [
+            &["-t", "ext4"][..],
+            &["-t", "ext4", "-b", "4096"],
+            &["-t", "ext2"],
+        ] {
+            let image = format(options, None);
+
+            {
+                let fs = mount(&image);
+                let root = fs.root_dir();
+
+                let docs = root.mkdir("docs").unwrap();
+                assert_eq!(
+                    root.mkdir("docs").err(),
+                    Some(FileSystemError::AlreadyExists)
+                );
+
+                let data = pattern(200 * 1024);
+                let file = docs.touch("data.bin").unwrap();
+                assert_eq!(file.writeat(0, &data).unwrap(), data.len());
+
+                // A sparse file with a hole in the middle
+                let sparse = root.touch("sparse").unwrap();
+                sparse.writeat(1024 * 1024, b"tail").unwrap();
+                sparse.writeat(0, b"head").unwrap();
+
+                let shrunk = root.touch("shrunk").unwrap();
+                shrunk.writeat(0, &data).unwrap();
+                shrunk.resize(5000).unwrap();
+                shrunk.resize(9000).unwrap();
+
+                docs.hard_link("alias", &file).unwrap();
+                root.soft_link("link", "docs/data.bin").unwrap();
+                root.soft_link("far", &"y".repeat(200)).unwrap();
+
+                let doomed = root.mkdir("doomed").unwrap();
+                doomed.touch("inner").unwrap();
+                assert_eq!(
+                    root.rmdir("doomed").err(),
+                    Some(FileSystemError::DirectoryNotEmpty)
+                );
+                doomed.remove("inner").unwrap();
+                root.rmdir("doomed").unwrap();
+
+                root.touch("old").unwrap().writeat(0, b"old").unwrap();
+                root.touch("new").unwrap().writeat(0, b"replaced").unwrap();
+                root.rename("old", "new").unwrap();
+
+                fs.flush().unwrap();
+            }
+
+            check(&image);
+
+            let expected = pattern(200 * 1024);
+            assert_eq!(host_read(&image, "/docs/data.bin"), expected);
+            assert_eq!(host_read(&image, "/docs/alias"), expected);
+            assert_eq!(host_read(&image, "/new"), b"old");
+
+            let mut shrunk = expected[..5000].to_vec();
+            shrunk.resize(9000, 0);
+            assert_eq!(host_read(&image, "/shrunk"), shrunk);
+
+            let sparse = host_read(&image, "/sparse");
+            assert_eq!(sparse.len(), 1024 * 1024 + 4);
+            assert_eq!(&sparse[..4], b"head");
+            assert!(sparse[4..1024 * 1024].iter().all(|&b| b == 0));
+
+            let fs = mount(&image);
+            let root = fs.root_dir();
+
+            assert_eq!(open(&root, "doomed").err(), Some(FileSystemError::NotFound));
+            assert_eq!(open(&root, "old").err(), Some(FileSystemError::NotFound));
+            assert_eq!(
+                open(&root, "far").unwrap().resolve_link(),
+                Some("y".repeat(200))
+            );
+            assert_eq!(
+                open(&root, "link").unwrap().resolve_link().as_deref(),
+                Some("docs/data.bin")
+            );
+
+            let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+            open(&root, "docs/data.bin")
+                .unwrap()
+                .stat(&mut stat)
+                .unwrap();
+            assert_eq!(stat.link_count, 2);
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let image = format(&["-t", "ext4"], None);
+        let mtime = TimeSpec {
+            tv_sec: 1_000_000_000,
+            tv_nsec: 123,
+        };
+
+        {
+            let fs = mount(&image);
+            let file = fs.root_dir().touch("owned").unwrap();
+
+            file.set_attributes(&InodeAttributes {
+                mode: Some(FileMode::from_bits_truncate(0o4750)),
+                uid: Some(70_000),
+                gid: Some(100),
+                mtime: Some(mtime),
+                ..Default::default()
+            })
+            .unwrap();
+
+            fs.flush().unwrap();
+        }
+
+        check(&image);
+
+        let fs = mount(&image);
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        open(&fs.root_dir(), "owned")
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+
+        assert_eq!(stat.mode.bits(), 0o104750);
+        assert_eq!((stat.uid, stat.gid), (70_000, 100));
+        assert_eq!(stat.mtime, mtime);
+    }
+
+    #[test]
+    fn test_xattrs() {
+        let image = format(&["-t", "ext4"], None);
+
+        {
+            let fs = mount(&image);
+            let root = fs.root_dir();
+            let file = root.touch("tagged").unwrap();
+
+            file.set_xattr("user.mime", b"text/plain", XattrFlags::empty())
+                .unwrap();
+            file.set_xattr("trusted.big", &pattern(600), XattrFlags::empty())
+                .unwrap();
+            file.set_xattr("user.gone", b"x", XattrFlags::empty())
+                .unwrap();
+            file.remove_xattr("user.gone").unwrap();
+
+            // Removing the last one frees the block again
+            let other = root.touch("cleared").unwrap();
+            other
+                .set_xattr("user.a", b"1", XattrFlags::empty())
+                .unwrap();
+            other.remove_xattr("user.a").unwrap();
+
+            fs.flush().unwrap();
+        }
+
+        check(&image);
+
+        let (_, listed) = run("debugfs", &["-R", "ea_list /tagged"], &image);
+        assert!(String::from_utf8_lossy(&listed).contains("user.mime"));
+
+        let fs = mount(&image);
+        let file = open(&fs.root_dir(), "tagged").unwrap();
+
+        assert_eq!(file.get_xattr("user.mime").unwrap(), b"text/plain");
+        assert_eq!(file.get_xattr("trusted.big").unwrap(), pattern(600));
+        assert_eq!(
+            file.get_xattr("user.gone"),
+            Err(FileSystemError::NoAttribute)
+        );
+        assert_eq!(file.list_xattr().unwrap(), ["user.mime", "trusted.big"]);
+        assert!(open(&fs.root_dir(), "cleared")
+            .unwrap()
+            .list_xattr()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_removed_while_open() {
+        let image = format(&["-t", "ext4"], None);
+
+        {
+            let fs = mount(&image);
+            let root = fs.root_dir();
+
+            let file = root.touch("open").unwrap();
+            file.writeat(0, &pattern(50 * 1024)).unwrap();
+
+            root.remove("open").unwrap();
+            assert_eq!(open(&root, "open").err(), Some(FileSystemError::NotFound));
+
+            // The contents stay until the last user lets go
+            assert_eq!(read_all(&file), pattern(50 * 1024));
+
+            let number = file.downcast_ref::<Ext4Inode>().unwrap().number();
+            assert!(fs.volume.lock().inode_in_use(number).unwrap());
+
+            drop(file);
+            assert!(!fs.volume.lock().inode_in_use(number).unwrap());
+
+            fs.flush().unwrap();
+        }
+
+        check(&image);
+    }
+
+    #[test]
+    fn test_large_directories() {
+        let source = TempDir::new("ext4");
+        std::fs::create_dir(source.join("many")).unwrap();
+
+        for i in 0..2000 {
+            std::fs::write(source.join(format!("many/host-{}", i)), b"").unwrap();
+        }
+
+        let image = format(&["-t", "ext4", "-N", "8192"], Some(source.path()));
+
+        // Have the host index the directory
+        let (_, _) = run("e2fsck", &["-fyD"], &image);
+        check(&image);
+
+        {
+            let fs = mount(&image);
+            let many = open(&fs.root_dir(), "many").unwrap();
+
+            for i in (0..2000).step_by(97) {
+                open(&many, &format!("host-{}", i)).unwrap();
+            }
+
+            for i in 0..3000 {
+                many.touch(&format!("a-rather-long-name-to-fill-blocks-{}", i))
+                    .unwrap();
+            }
+
+            for i in (0..2000).step_by(3) {
+                many.remove(&format!("host-{}", i)).unwrap();
+            }
+
+            assert_eq!(
+                many.read_cache_dir(&mut BTreeMap::new()).unwrap().len(),
+                4333
+            );
+
+            // A plain directory that grows past one block
+            let plain = fs.root_dir().mkdir("plain").unwrap();
+
+            for i in 0..500 {
+                plain.touch(&format!("entry-{}", i)).unwrap();
+            }
+
+            fs.flush().unwrap();
+        }
+
+        check(&image);
+
+        let fs = mount(&image);
+        let many = open(&fs.root_dir(), "many").unwrap();
+
+        for i in (0..3000).step_by(101) {
+            open(&many, &format!("a-rather-long-name-to-fill-blocks-{}", i)).unwrap();
+        }
+
+        assert!(open(&many, "host-3").is_err());
+        assert!(open(&many, "host-4").is_ok());
+    }
+
+    #[test]
+    fn test_journal_replay() {
+        let image = format(&["-t", "ext4"], None);
+
+        {
+            let fs = mount(&image);
+            let root = fs.root_dir();
+
+            root.mkdir("committed").unwrap();
+            root.touch("file")
+                .unwrap()
+                .writeat(0, b"journaled")
+                .unwrap();
+
+            // Crash right after the transaction reached the log
+            fs.volume.lock().commit_without_checkpoint().unwrap();
+        }
+
+        // The host replays our log the same way
+        let copy = DiskImage::copy_of(image.path().unwrap(), 1024).unwrap();
+        run("e2fsck", &["-fy"], &copy);
+        check(&copy);
+        assert_eq!(host_read(&copy, "/file"), b"journaled");
+
+        {
+            let fs = mount(&image);
+            let root = fs.root_dir();
+
+            open(&root, "committed").unwrap();
+            assert_eq!(read_all(&open(&root, "file").unwrap()), b"journaled");
+        }
+
+        check(&image);
+    }
+
+    #[test]
+    fn test_running_out_of_space() {
+        let image = format(&["-t", "ext4"], None);
+
+        {
+            let fs = mount(&image);
+            let root = fs.root_dir();
+
+            let file = root.touch("huge").unwrap();
+            let chunk = pattern(1024 * 1024);
+            let mut offset = 0;
+
+            let error = loop {
+                match file.writeat(offset, &chunk) {
+                    Ok(written) => offset += written,
+                    Err(e) => break e,
+                }
+            };
+
+            assert_eq!(error, FileSystemError::SpaceNotEnough);
+
+            root.remove("huge").unwrap();
+            drop(file);
+
+            root.touch("after").unwrap().writeat(0, &chunk).unwrap();
+
+            fs.flush().unwrap();
+        }
+
+        check(&image);
+    }
+
+    #[test]
+    fn test_readonly_device() {
+        let image = format(&["-t", "ext4"], None);
+        let readonly = DiskImage::open(image.path().unwrap(), 1024, true).unwrap();
+
+        let fs = Ext4FileSystem::mount(readonly, Arc::new(FixedClock)).unwrap();
+        assert!(fs.is_readonly());
+
+        assert_eq!(
+            fs.root_dir().touch("nope").err(),
+            Some(FileSystemError::ReadOnly)
+        );
+        assert!(open(&fs.root_dir(), "lost+found").is_ok());
+    }
+}
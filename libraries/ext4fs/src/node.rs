@@ -0,0 +1,319 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IInode, InodeAttributes, InodeMetadata, XattrFlags,
+};
+
+use crate::{
+    dir::{entry_type_of, TYPE_UNKNOWN},
+    inode::{Inode, MODE_DIRECTORY, MODE_FILE},
+    volume::Volume,
+    Ext4FileSystem,
+};
+
+/// An inode of a mounted ext2/3/4 filesystem.
+pub struct Ext4Inode {
+    fs: Arc<Ext4FileSystem>,
+    number: u32,
+    name: String,
+    entry_type: DirectoryEntryType,
+}
+
+impl Ext4Inode {
+    pub(crate) fn new(
+        fs: Arc<Ext4FileSystem>,
+        number: u32,
+        name: &str,
+        entry_type: DirectoryEntryType,
+    ) -> Arc<Ext4Inode> {
+        fs.opened(number);
+
+        Arc::new(Ext4Inode {
+            fs,
+            number,
+            name: name.to_string(),
+            entry_type,
+        })
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    fn child(&self, inode: &Inode, name: &str) -> Arc<dyn IInode> {
+        Ext4Inode::new(self.fs.clone(), inode.number, name, inode.entry_type())
+    }
+
+    /// Runs `f` on this inode with the filesystem locked, then commits if enough changes piled up.
+    fn modify<T>(
+        &self,
+        f: impl FnOnce(&mut Volume, &mut Inode) -> FileSystemResult<T>,
+    ) -> FileSystemResult<T> {
+        let mut volume = self.fs.volume.lock();
+        let mut inode = volume.read_inode(self.number)?;
+
+        let result = f(&mut volume, &mut inode);
+
+        volume.maybe_commit()?;
+
+        result
+    }
+
+    /// Frees `inode` if it lost its last link and nobody has it open anymore.
+    fn release_unused(&self, volume: &mut Volume, mut inode: Inode) -> FileSystemResult<()> {
+        match inode.links() == 0 && !self.fs.is_open(inode.number) {
+            true => volume.release(&mut inode),
+            false => Ok(()),
+        }
+    }
+}
+
+impl IInode for Ext4Inode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        let size = match self.fs.volume.lock().read_inode(self.number) {
+            Ok(inode) => inode.size() as usize,
+            Err(_) => 0,
+        };
+
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry_type,
+            size,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        if self.entry_type != DirectoryEntryType::File {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        let volume = self.fs.volume.lock();
+        let inode = volume.read_inode(self.number)?;
+
+        volume.read_contents(&inode, offset as u64, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        if self.entry_type != DirectoryEntryType::File {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        self.modify(|volume, inode| volume.write_contents(inode, offset as u64, buffer))
+    }
+
+    fn mkdir(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.modify(|volume, dir| {
+            let made = volume.create(dir, name, MODE_DIRECTORY | 0o755)?;
+
+            Ok(self.child(&made, name))
+        })
+    }
+
+    fn rmdir(&self, name: &str) -> FileSystemResult<()> {
+        self.modify(|volume, dir| {
+            let removed = volume.rmdir(dir, name)?;
+
+            self.release_unused(volume, removed)
+        })
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        self.modify(|volume, dir| {
+            let removed = volume.unlink(dir, name)?;
+
+            self.release_unused(volume, removed)
+        })
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.modify(|volume, dir| {
+            let made = volume.create(dir, name, MODE_FILE | 0o644)?;
+
+            Ok(self.child(&made, name))
+        })
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        let volume = self.fs.volume.lock();
+        let dir = volume.read_inode(self.number)?;
+
+        if !dir.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        let mut entries = Vec::new();
+
+        for (filename, number, file_type) in volume.list(&dir)? {
+            // Filesystems without the filetype feature only keep the type in the inode
+            let entry_type = match file_type {
+                TYPE_UNKNOWN => volume.read_inode(number)?.entry_type(),
+                file_type => entry_type_of(file_type),
+            };
+
+            entries.push(DirectoryEntry {
+                filename,
+                entry_type,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let volume = self.fs.volume.lock();
+        let dir = volume.read_inode(self.number)?;
+
+        if !dir.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        let Some(found) = volume.lookup(&dir, name.as_bytes())? else {
+            return Err(FileSystemError::NotFound);
+        };
+
+        let entry_type = match found.file_type {
+            TYPE_UNKNOWN => volume.read_inode(found.inode)?.entry_type(),
+            file_type => entry_type_of(file_type),
+        };
+
+        Ok(Ext4Inode::new(
+            self.fs.clone(),
+            found.inode,
+            name,
+            entry_type,
+        ))
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.fs.volume.lock().commit()
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let volume = self.fs.volume.lock();
+        let inode = volume.read_inode(self.number)?;
+
+        stat.device_id = 0;
+        stat.inode_id = self.number as u64;
+        stat.mode = FileStatisticsMode::from_bits_truncate(inode.mode() as u32);
+        stat.link_count = inode.links() as u32;
+        stat.uid = inode.uid();
+        stat.gid = inode.gid();
+        stat.rdev = 0;
+        stat.size = inode.size();
+        stat.block_size = volume.block_size() as u32;
+        stat.block_count = inode.sectors(volume.block_size());
+        stat.atime = inode.access_time();
+        stat.mtime = inode.modify_time();
+        stat.ctime = inode.change_time();
+
+        Ok(())
+    }
+
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        self.modify(|volume, inode| volume.set_attributes(inode, attributes))
+    }
+
+    fn get_xattr(&self, name: &str) -> FileSystemResult<Vec<u8>> {
+        let volume = self.fs.volume.lock();
+        let inode = volume.read_inode(self.number)?;
+
+        volume.get_xattr(&inode, name)
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> FileSystemResult<()> {
+        self.modify(|volume, inode| volume.set_xattr(inode, name, value, flags))
+    }
+
+    fn list_xattr(&self) -> FileSystemResult<Vec<String>> {
+        let volume = self.fs.volume.lock();
+        let inode = volume.read_inode(self.number)?;
+
+        volume.list_xattrs(&inode)
+    }
+
+    fn remove_xattr(&self, name: &str) -> FileSystemResult<()> {
+        self.modify(|volume, inode| volume.remove_xattr(inode, name))
+    }
+
+    fn hard_link(&self, name: &str, inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let Some(source) = inode.downcast_ref::<Ext4Inode>() else {
+            return Err(FileSystemError::NotPermitted);
+        };
+
+        if !Arc::ptr_eq(&source.fs, &self.fs) {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        self.modify(|volume, dir| {
+            let mut target = volume.read_inode(source.number)?;
+
+            volume.link(dir, name, &mut target)
+        })
+    }
+
+    fn soft_link(&self, name: &str, point_to: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.modify(|volume, dir| {
+            let made = volume.symlink(dir, name, point_to)?;
+
+            Ok(self.child(&made, name))
+        })
+    }
+
+    fn resolve_link(&self) -> Option<String> {
+        if self.entry_type != DirectoryEntryType::Symlink {
+            return None;
+        }
+
+        let volume = self.fs.volume.lock();
+        let inode = volume.read_inode(self.number).ok()?;
+
+        volume.read_link(&inode).ok()
+    }
+
+    fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+        self.modify(|volume, inode| {
+            volume.resize(inode, new_size)?;
+
+            Ok(new_size)
+        })
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str) -> FileSystemResult<()> {
+        self.modify(
+            |volume, dir| match volume.rename(dir, old_name, new_name)? {
+                Some(replaced) => self.release_unused(volume, replaced),
+                None => Ok(()),
+            },
+        )
+    }
+}
+
+impl Drop for Ext4Inode {
+    fn drop(&mut self) {
+        if !self.fs.closed(self.number) {
+            return;
+        }
+
+        let mut volume = self.fs.volume.lock();
+
+        // Removed while it was open, the last user frees it
+        let released = match volume.read_inode(self.number) {
+            Ok(inode) if !volume.readonly => self
+                .release_unused(&mut volume, inode)
+                .and_then(|_| volume.maybe_commit()),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = released {
+            log::warn!("ext4: failed to release inode {}: {:?}", self.number, e);
+        }
+    }
+}
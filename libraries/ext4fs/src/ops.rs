@@ -0,0 +1,416 @@
+use alloc::{string::String, vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult, InodeAttributes};
+
+use crate::{
+    dir::{TYPE_DIRECTORY, TYPE_FILE, TYPE_SYMLINK, TYPE_UNKNOWN},
+    inode::{
+        Inode, BLOCK_MAP_SIZE, FLAG_EXTENTS, MODE_DIRECTORY, MODE_FILE, MODE_SYMLINK,
+        MODE_TYPE_MASK,
+    },
+    volume::Volume,
+};
+
+const NAME_MAX: usize = 255;
+
+/// Most links an inode can have, `nlink` is 16 bits on disk.
+const LINK_MAX: u16 = 65000;
+
+/// Extra inode space new inodes use when the superblock does not ask for any.
+const DEFAULT_EXTRA_ISIZE: u16 = 32;
+
+fn check_name(name: &str) -> FileSystemResult<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    if name.len() > NAME_MAX {
+        return Err(FileSystemError::PathNameLengthExceeded);
+    }
+
+    if name.contains(['/', '\0']) {
+        return Err(FileSystemError::PathContainsInvalidCharacter);
+    }
+
+    Ok(())
+}
+
+/// The directory entry type of an inode with `mode`.
+pub(crate) fn file_type_of(mode: u16) -> u8 {
+    match mode & MODE_TYPE_MASK {
+        MODE_FILE => TYPE_FILE,
+        MODE_DIRECTORY => TYPE_DIRECTORY,
+        MODE_SYMLINK => TYPE_SYMLINK,
+        _ => TYPE_UNKNOWN,
+    }
+}
+
+impl Volume {
+    fn new_inode(&self, number: u32, mode: u16) -> Inode {
+        let mut inode = Inode::new(number, vec![0; self.geometry.inode_size]);
+
+        match self.superblock.want_extra_isize() {
+            0 => inode.set_extra_isize(DEFAULT_EXTRA_ISIZE),
+            size => inode.set_extra_isize(size),
+        }
+
+        let now = self.now();
+
+        inode.set_mode(mode);
+        inode.set_access_time(now);
+        inode.set_change_time(now);
+        inode.set_modify_time(now);
+        inode.set_creation_time(now);
+        inode.set_generation(now.tv_sec as u32 ^ now.tv_nsec as u32 ^ number);
+
+        if self.has_extents() {
+            inode.set_flags(FLAG_EXTENTS);
+            self.extent_init(&mut inode);
+        }
+
+        inode
+    }
+
+    fn find_child(&self, dir: &Inode, name: &str) -> FileSystemResult<Inode> {
+        if !dir.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        match self.lookup(dir, name.as_bytes())? {
+            Some(found) => self.read_inode(found.inode),
+            None => Err(FileSystemError::NotFound),
+        }
+    }
+
+    fn ensure_absent(&self, dir: &Inode, name: &str) -> FileSystemResult<()> {
+        check_name(name)?;
+
+        if !dir.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        // The directory was removed while it was still open
+        if dir.links() == 0 {
+            return Err(FileSystemError::NotFound);
+        }
+
+        match self.lookup(dir, name.as_bytes())? {
+            Some(_) => Err(FileSystemError::AlreadyExists),
+            None => Ok(()),
+        }
+    }
+
+    /// Creates an inode of `mode` linked as `name` in `dir`.
+    pub fn create(&mut self, dir: &mut Inode, name: &str, mode: u16) -> FileSystemResult<Inode> {
+        self.ensure_writable()?;
+        self.ensure_absent(dir, name)?;
+
+        let directory = mode & MODE_TYPE_MASK == MODE_DIRECTORY;
+
+        if directory && dir.links() >= LINK_MAX {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        let number = self.allocate_inode(dir.number, directory)?;
+        let mut inode = self.new_inode(number, mode);
+
+        let linked = match directory {
+            true => {
+                inode.set_links(2);
+                self.init_dir(&mut inode, dir.number)
+            }
+            false => {
+                inode.set_links(1);
+                Ok(())
+            }
+        }
+        .and_then(|_| self.write_inode(&mut inode))
+        .and_then(|_| self.add_entry(dir, name.as_bytes(), number, file_type_of(mode)));
+
+        if let Err(e) = linked {
+            self.release(&mut inode)?;
+            return Err(e);
+        }
+
+        if directory {
+            dir.set_links(dir.links() + 1);
+        }
+
+        dir.touch(self.now());
+        self.write_inode(dir)?;
+
+        Ok(inode)
+    }
+
+    pub fn symlink(
+        &mut self,
+        dir: &mut Inode,
+        name: &str,
+        target: &str,
+    ) -> FileSystemResult<Inode> {
+        if target.is_empty() {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        if target.len() >= self.block_size() {
+            return Err(FileSystemError::PathNameLengthExceeded);
+        }
+
+        let mut inode = self.create(dir, name, MODE_SYMLINK | 0o777)?;
+
+        match target.len() < BLOCK_MAP_SIZE {
+            // Short targets live in the block map, which is not an extent tree then
+            true => {
+                inode.set_flags(inode.flags() & !FLAG_EXTENTS);
+
+                let map = inode.block_map_mut();
+                map.fill(0);
+                map[..target.len()].copy_from_slice(target.as_bytes());
+
+                inode.set_size(target.len() as u64);
+                self.write_inode(&mut inode)?;
+            }
+            false => {
+                self.write_contents(&mut inode, 0, target.as_bytes())?;
+            }
+        }
+
+        Ok(inode)
+    }
+
+    pub fn read_link(&self, inode: &Inode) -> FileSystemResult<String> {
+        let size = inode.size() as usize;
+
+        let target = match self.is_fast_symlink(inode) {
+            true => inode.block_map()[..size].to_vec(),
+            false => {
+                let mut target = vec![0; size];
+                let read = self.read_contents(inode, 0, &mut target)?;
+                target.truncate(read);
+                target
+            }
+        };
+
+        String::from_utf8(target).map_err(|_| FileSystemError::FileSystemCorrupted)
+    }
+
+    /// Removes the entry of a non-directory, returns its inode, which has to be released once its
+    /// links dropped to zero and nobody has it open.
+    pub fn unlink(&mut self, dir: &mut Inode, name: &str) -> FileSystemResult<Inode> {
+        self.ensure_writable()?;
+
+        let mut inode = self.find_child(dir, name)?;
+
+        if inode.is_dir() {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        self.remove_entry(dir, name.as_bytes())?;
+
+        inode.set_links(inode.links().saturating_sub(1));
+        inode.set_change_time(self.now());
+        self.write_inode(&mut inode)?;
+
+        dir.touch(self.now());
+        self.write_inode(dir)?;
+
+        Ok(inode)
+    }
+
+    /// Removes an empty directory, returns its inode like [`Volume::unlink`].
+    pub fn rmdir(&mut self, dir: &mut Inode, name: &str) -> FileSystemResult<Inode> {
+        self.ensure_writable()?;
+
+        let mut inode = self.find_child(dir, name)?;
+
+        if !inode.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        if !self.is_empty_dir(&inode)? {
+            return Err(FileSystemError::DirectoryNotEmpty);
+        }
+
+        self.remove_entry(dir, name.as_bytes())?;
+
+        inode.set_links(0);
+        inode.set_change_time(self.now());
+        self.write_inode(&mut inode)?;
+
+        dir.set_links(dir.links().saturating_sub(1));
+        dir.touch(self.now());
+        self.write_inode(dir)?;
+
+        Ok(inode)
+    }
+
+    pub fn link(&mut self, dir: &mut Inode, name: &str, inode: &mut Inode) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+        self.ensure_absent(dir, name)?;
+
+        if inode.is_dir() || inode.links() == 0 || inode.links() >= LINK_MAX {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        self.add_entry(
+            dir,
+            name.as_bytes(),
+            inode.number,
+            file_type_of(inode.mode()),
+        )?;
+
+        inode.set_links(inode.links() + 1);
+        inode.set_change_time(self.now());
+        self.write_inode(inode)?;
+
+        dir.touch(self.now());
+        self.write_inode(dir)
+    }
+
+    /// Renames an entry of `dir`, replacing whatever `new_name` was. Returns the replaced inode
+    /// like [`Volume::unlink`].
+    pub fn rename(
+        &mut self,
+        dir: &mut Inode,
+        old_name: &str,
+        new_name: &str,
+    ) -> FileSystemResult<Option<Inode>> {
+        self.ensure_writable()?;
+        check_name(new_name)?;
+
+        let mut source = self.find_child(dir, old_name)?;
+
+        if old_name == new_name {
+            return Ok(None);
+        }
+
+        let replaced = match self.lookup(dir, new_name.as_bytes())? {
+            // Both names are links of the same inode, nothing to do
+            Some(found) if found.inode == source.number => return Ok(None),
+            Some(found) => {
+                let mut target = self.read_inode(found.inode)?;
+
+                match (source.is_dir(), target.is_dir()) {
+                    (true, false) => return Err(FileSystemError::NotADirectory),
+                    (false, true) => return Err(FileSystemError::NotAFile),
+                    (true, true) if !self.is_empty_dir(&target)? => {
+                        return Err(FileSystemError::DirectoryNotEmpty)
+                    }
+                    _ => (),
+                }
+
+                self.remove_entry(dir, new_name.as_bytes())?;
+
+                match target.is_dir() {
+                    true => {
+                        target.set_links(0);
+                        dir.set_links(dir.links().saturating_sub(1));
+                    }
+                    false => target.set_links(target.links().saturating_sub(1)),
+                }
+
+                target.set_change_time(self.now());
+                self.write_inode(&mut target)?;
+
+                Some(target)
+            }
+            None => None,
+        };
+
+        self.remove_entry(dir, old_name.as_bytes())?;
+        self.add_entry(
+            dir,
+            new_name.as_bytes(),
+            source.number,
+            file_type_of(source.mode()),
+        )?;
+
+        source.set_change_time(self.now());
+        self.write_inode(&mut source)?;
+
+        dir.touch(self.now());
+        self.write_inode(dir)?;
+
+        Ok(replaced)
+    }
+
+    pub fn resize(&mut self, inode: &mut Inode, size: u64) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        if inode.file_type() != MODE_FILE {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        match size < inode.size() {
+            true => self.truncate_contents(inode, size)?,
+            false => inode.set_size(size),
+        }
+
+        inode.touch(self.now());
+        self.write_inode(inode)
+    }
+
+    pub fn set_attributes(
+        &mut self,
+        inode: &mut Inode,
+        attributes: &InodeAttributes,
+    ) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        if let Some(mode) = attributes.mode {
+            inode.set_mode(inode.file_type() | (mode.bits() & 0o7777) as u16);
+        }
+
+        if let Some(uid) = attributes.uid {
+            inode.set_uid(uid);
+        }
+
+        if let Some(gid) = attributes.gid {
+            inode.set_gid(gid);
+        }
+
+        if let Some(atime) = attributes.atime {
+            inode.set_access_time(atime);
+        }
+
+        if let Some(mtime) = attributes.mtime {
+            inode.set_modify_time(mtime);
+        }
+
+        inode.set_change_time(self.now());
+        self.write_inode(inode)
+    }
+
+    /// Frees an inode that lost its last link along with its blocks.
+    pub fn release(&mut self, inode: &mut Inode) -> FileSystemResult<()> {
+        let directory = inode.is_dir();
+
+        self.truncate_contents(inode, 0)?;
+        self.release_xattrs(inode)?;
+
+        inode.set_links(0);
+        inode.set_deletion_time(self.now().tv_sec as u32);
+        self.write_inode(inode)?;
+
+        self.free_inode(inode.number, directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("file.txt").is_ok());
+        assert_eq!(check_name(""), Err(FileSystemError::InvalidInput));
+        assert_eq!(check_name(".."), Err(FileSystemError::InvalidInput));
+        assert_eq!(
+            check_name("a/b"),
+            Err(FileSystemError::PathContainsInvalidCharacter)
+        );
+        assert_eq!(
+            check_name(&"x".repeat(256)),
+            Err(FileSystemError::PathNameLengthExceeded)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use alloc::vec::Vec;
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    crc::crc32c,
+    raw::{le16, le32, set_le32},
+};
+
+/// Where the primary superblock lives, in bytes from the start of the device.
+pub(crate) const SUPERBLOCK_OFFSET: u64 = 1024;
+pub(crate) const SUPERBLOCK_SIZE: usize = 1024;
+
+const MAGIC: u16 = 0xef53;
+
+pub(crate) const COMPAT_HAS_JOURNAL: u32 = 0x4;
+pub(crate) const COMPAT_EXT_ATTR: u32 = 0x8;
+
+pub(crate) const INCOMPAT_FILETYPE: u32 = 0x2;
+pub(crate) const INCOMPAT_RECOVER: u32 = 0x4;
+pub(crate) const INCOMPAT_META_BG: u32 = 0x10;
+pub(crate) const INCOMPAT_EXTENTS: u32 = 0x40;
+pub(crate) const INCOMPAT_64BIT: u32 = 0x80;
+pub(crate) const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub(crate) const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub(crate) const INCOMPAT_LARGEDIR: u32 = 0x4000;
+
+/// Incompatible features this driver understands, any other one refuses the mount.
+const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_META_BG
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+pub(crate) const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub(crate) const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub(crate) const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub(crate) const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub(crate) const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub(crate) const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+pub(crate) const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+
+/// Read-only compatible features that can be written without knowing more about them, any
+/// other one makes the mount read-only.
+const RO_COMPAT_WRITABLE: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM;
+
+/// Directory hashes of this filesystem treat names as unsigned chars.
+const FLAG_UNSIGNED_HASH: u32 = 0x2;
+
+pub(crate) struct Superblock {
+    raw: Vec<u8>,
+}
+
+impl Superblock {
+    pub fn parse(raw: Vec<u8>) -> FileSystemResult<Superblock> {
+        let superblock = Superblock { raw };
+
+        if superblock.raw.len() != SUPERBLOCK_SIZE || le16(&superblock.raw, 0x38) != MAGIC {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        if superblock.has_ro_compat(RO_COMPAT_METADATA_CSUM)
+            && le32(&superblock.raw, 0x3fc) != superblock.checksum()
+        {
+            log::warn!("ext4: superblock checksum mismatch");
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        let log_block_size = le32(&superblock.raw, 0x18);
+
+        if log_block_size > 6
+            || superblock.blocks_per_group() == 0
+            || superblock.inodes_per_group() == 0
+            || superblock.inode_size() < 128
+            || !superblock.inode_size().is_power_of_two()
+        {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        Ok(superblock)
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Incompatible features that prevent mounting, zero if there is none.
+    pub fn unsupported_features(&self) -> u32 {
+        self.incompat() & !INCOMPAT_SUPPORTED
+    }
+
+    /// Whether features that are unknown to this driver forbid writing.
+    pub fn forbids_writing(&self) -> bool {
+        le32(&self.raw, 0x64) & !RO_COMPAT_WRITABLE != 0
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        le32(&self.raw, 0x0)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        self.hi_lo(0x150, 0x4)
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        self.hi_lo(0x158, 0xc)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        set_le32(&mut self.raw, 0xc, count as u32);
+
+        if self.is_64bit() {
+            set_le32(&mut self.raw, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        le32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        set_le32(&mut self.raw, 0x10, count);
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        le32(&self.raw, 0x14)
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << le32(&self.raw, 0x18)
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        le32(&self.raw, 0x20)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        le32(&self.raw, 0x28)
+    }
+
+    pub fn set_write_time(&mut self, seconds: u32) {
+        set_le32(&mut self.raw, 0x30, seconds);
+    }
+
+    fn revision(&self) -> u32 {
+        le32(&self.raw, 0x4c)
+    }
+
+    pub fn first_inode(&self) -> u32 {
+        match self.revision() {
+            0 => 11,
+            _ => le32(&self.raw, 0x54),
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        match self.revision() {
+            0 => 128,
+            _ => le16(&self.raw, 0x58) as usize,
+        }
+    }
+
+    pub fn has_compat(&self, feature: u32) -> bool {
+        le32(&self.raw, 0x5c) & feature != 0
+    }
+
+    fn incompat(&self) -> u32 {
+        le32(&self.raw, 0x60)
+    }
+
+    pub fn has_incompat(&self, feature: u32) -> bool {
+        self.incompat() & feature != 0
+    }
+
+    pub fn set_incompat(&mut self, feature: u32, enabled: bool) {
+        let incompat = match enabled {
+            true => self.incompat() | feature,
+            false => self.incompat() & !feature,
+        };
+
+        set_le32(&mut self.raw, 0x60, incompat);
+    }
+
+    pub fn has_ro_compat(&self, feature: u32) -> bool {
+        le32(&self.raw, 0x64) & feature != 0
+    }
+
+    pub fn uuid(&self) -> &[u8] {
+        &self.raw[0x68..0x78]
+    }
+
+    pub fn reserved_gdt_blocks(&self) -> u32 {
+        le16(&self.raw, 0xce) as u32
+    }
+
+    pub fn journal_inode(&self) -> u32 {
+        le32(&self.raw, 0xe0)
+    }
+
+    pub fn hash_seed(&self) -> [u32; 4] {
+        core::array::from_fn(|i| le32(&self.raw, 0xec + i * 4))
+    }
+
+    pub fn is_64bit(&self) -> bool {
+        self.has_incompat(INCOMPAT_64BIT)
+    }
+
+    pub fn desc_size(&self) -> usize {
+        match self.is_64bit() {
+            true => (le16(&self.raw, 0xfe) as usize).max(64),
+            false => 32,
+        }
+    }
+
+    pub fn first_meta_bg(&self) -> u32 {
+        le32(&self.raw, 0x104)
+    }
+
+    pub fn want_extra_isize(&self) -> u16 {
+        le16(&self.raw, 0x15e)
+    }
+
+    pub fn hash_unsigned(&self) -> bool {
+        le32(&self.raw, 0x160) & FLAG_UNSIGNED_HASH != 0
+    }
+
+    /// The seed of every metadata checksum of this filesystem.
+    pub fn checksum_seed(&self) -> u32 {
+        match self.has_incompat(INCOMPAT_CSUM_SEED) {
+            true => le32(&self.raw, 0x270),
+            false => crc32c(!0, self.uuid()),
+        }
+    }
+
+    fn checksum(&self) -> u32 {
+        crc32c(!0, &self.raw[..0x3fc])
+    }
+
+    pub fn update_checksum(&mut self) {
+        if self.has_ro_compat(RO_COMPAT_METADATA_CSUM) {
+            let checksum = self.checksum();
+            set_le32(&mut self.raw, 0x3fc, checksum);
+        }
+    }
+
+    fn hi_lo(&self, hi: usize, lo: usize) -> u64 {
+        let high = match self.is_64bit() {
+            true => le32(&self.raw, hi) as u64,
+            false => 0,
+        };
+
+        (high << 32) | le32(&self.raw, lo) as u64
+    }
+}
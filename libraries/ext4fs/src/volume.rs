@@ -0,0 +1,403 @@
+use alloc::{collections::BTreeSet, sync::Arc, vec::Vec};
+use block_abstractions::IBlockDevice;
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use threading::IClock;
+use timing::TimeSpec;
+
+use crate::{
+    disk::Disk,
+    group::{Geometry, GroupDescriptor},
+    inode::Inode,
+    journal::Journal,
+    superblock::{
+        Superblock, COMPAT_HAS_JOURNAL, INCOMPAT_EXTENTS, INCOMPAT_FILETYPE, INCOMPAT_RECOVER,
+        RO_COMPAT_METADATA_CSUM, SUPERBLOCK_OFFSET, SUPERBLOCK_SIZE,
+    },
+};
+
+/// Metadata blocks collected before a commit is forced.
+const COMMIT_THRESHOLD: usize = 256;
+
+/// The state of a mounted filesystem, every operation runs with it locked.
+pub(crate) struct Volume {
+    pub disk: Disk,
+    pub superblock: Superblock,
+    pub geometry: Geometry,
+    pub groups: Vec<GroupDescriptor>,
+    pub checksum_seed: u32,
+    pub readonly: bool,
+    journal: Option<Journal>,
+    clock: Arc<dyn IClock>,
+    dirty_groups: BTreeSet<u32>,
+    superblock_dirty: bool,
+}
+
+impl Volume {
+    pub fn mount(
+        device: Arc<dyn IBlockDevice>,
+        clock: Arc<dyn IClock>,
+    ) -> FileSystemResult<Volume> {
+        let mut disk = Disk::new(device.clone(), SUPERBLOCK_SIZE);
+
+        let superblock = read_superblock(&disk)?;
+
+        if superblock.unsupported_features() != 0 {
+            log::warn!(
+                "ext4: unsupported incompatible features {:#x}",
+                superblock.unsupported_features()
+            );
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        let block_size = superblock.block_size();
+
+        if !block_size.is_multiple_of(device.block_size()) {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        disk.set_block_size(block_size);
+
+        let readonly = disk.is_readonly() || superblock.forbids_writing();
+
+        let mut volume = Volume {
+            geometry: Geometry::new(&superblock),
+            checksum_seed: superblock.checksum_seed(),
+            superblock,
+            groups: Vec::new(),
+            disk,
+            readonly,
+            journal: None,
+            clock,
+            dirty_groups: BTreeSet::new(),
+            superblock_dirty: false,
+        };
+
+        if volume.geometry.blocks_count
+            > device.block_count() * device.block_size() as u64 / block_size as u64
+        {
+            log::warn!("ext4: the filesystem is larger than the device");
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        volume.load_groups()?;
+        volume.open_journal()?;
+
+        Ok(volume)
+    }
+
+    fn load_groups(&mut self) -> FileSystemResult<()> {
+        let mut groups = Vec::with_capacity(self.geometry.group_count as usize);
+
+        for index in 0..self.geometry.desc_blocks() {
+            let block = self
+                .disk
+                .read_block(self.geometry.desc_block_location(index))?;
+
+            for raw in block.chunks_exact(self.geometry.desc_size) {
+                if groups.len() == self.geometry.group_count as usize {
+                    break;
+                }
+
+                let group = groups.len() as u32;
+                let descriptor = GroupDescriptor::new(raw.to_vec());
+
+                if !descriptor.verify_checksum(&self.superblock, self.checksum_seed, group) {
+                    log::warn!(
+                        "ext4: checksum mismatch in the descriptor of group {}",
+                        group
+                    );
+                    return Err(FileSystemError::FileSystemCorrupted);
+                }
+
+                groups.push(descriptor);
+            }
+        }
+
+        self.groups = groups;
+
+        Ok(())
+    }
+
+    fn open_journal(&mut self) -> FileSystemResult<()> {
+        let needs_recovery = self.superblock.has_incompat(INCOMPAT_RECOVER);
+
+        if !self.superblock.has_compat(COMPAT_HAS_JOURNAL) || self.superblock.journal_inode() == 0 {
+            if needs_recovery {
+                log::warn!("ext4: needs recovery without an internal journal");
+                return Err(FileSystemError::Unimplemented);
+            }
+
+            return Ok(());
+        }
+
+        let inode = self.read_inode(self.superblock.journal_inode())?;
+        let count = inode.size() / self.geometry.block_size as u64;
+        let blocks = self.block_list(&inode, count as u32)?;
+
+        let mut journal = Journal::open(&self.disk, blocks, self.geometry.block_size)?;
+
+        if needs_recovery || journal.needs_recovery() {
+            if self.readonly {
+                log::warn!("ext4: the journal needs recovery but the device is read-only");
+                return Err(FileSystemError::ReadOnly);
+            }
+
+            if journal.needs_recovery() {
+                journal.recover(&self.disk)?;
+
+                // The replayed metadata supersedes what was read before
+                self.superblock = read_superblock(&self.disk)?;
+                self.geometry = Geometry::new(&self.superblock);
+                self.load_groups()?;
+            }
+
+            self.superblock.set_incompat(INCOMPAT_RECOVER, false);
+            self.write_superblock_now(false)?;
+        }
+
+        if !self.readonly {
+            journal.enable_features(
+                &self.disk,
+                self.metadata_csum(),
+                self.superblock.is_64bit(),
+            )?;
+        }
+
+        self.journal = Some(journal);
+
+        Ok(())
+    }
+
+    pub fn metadata_csum(&self) -> bool {
+        self.superblock.has_ro_compat(RO_COMPAT_METADATA_CSUM)
+    }
+
+    pub fn has_extents(&self) -> bool {
+        self.superblock.has_incompat(INCOMPAT_EXTENTS)
+    }
+
+    pub fn has_filetype(&self) -> bool {
+        self.superblock.has_incompat(INCOMPAT_FILETYPE)
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.geometry.block_size
+    }
+
+    pub fn now(&self) -> TimeSpec {
+        self.clock.now()
+    }
+
+    pub fn ensure_writable(&self) -> FileSystemResult<()> {
+        match self.readonly {
+            true => Err(FileSystemError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    pub fn mark_group_dirty(&mut self, group: u32) {
+        self.dirty_groups.insert(group);
+        self.superblock_dirty = true;
+    }
+
+    fn inode_location(&self, number: u32) -> FileSystemResult<(u64, usize)> {
+        if number == 0 || number > self.superblock.inodes_count() {
+            return Err(FileSystemError::NotFound);
+        }
+
+        let group = self.geometry.group_of_inode(number);
+        let index = ((number - 1) % self.geometry.inodes_per_group) as usize;
+
+        let offset = index * self.geometry.inode_size;
+        let table = self.groups[group as usize].inode_table();
+
+        Ok((
+            table + (offset / self.geometry.block_size) as u64,
+            offset % self.geometry.block_size,
+        ))
+    }
+
+    pub fn read_inode(&self, number: u32) -> FileSystemResult<Inode> {
+        let (block, offset) = self.inode_location(number)?;
+
+        let data = self.disk.read_metadata(block)?;
+        let inode = Inode::new(
+            number,
+            data[offset..offset + self.geometry.inode_size].to_vec(),
+        );
+
+        if self.metadata_csum()
+            && inode.raw().iter().any(|&b| b != 0)
+            && !inode.verify_checksum(self.checksum_seed)
+        {
+            log::warn!("ext4: checksum mismatch in inode {}", number);
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        Ok(inode)
+    }
+
+    pub fn write_inode(&mut self, inode: &mut Inode) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        let (block, offset) = self.inode_location(inode.number)?;
+
+        if self.metadata_csum() {
+            inode.update_checksum(self.checksum_seed);
+        }
+
+        let mut data = self.disk.read_metadata(block)?;
+        data[offset..offset + self.geometry.inode_size].copy_from_slice(inode.raw());
+
+        self.disk.write_metadata(block, data);
+
+        Ok(())
+    }
+
+    /// Writes the superblock directly, outside of any transaction, with the recovery flag set as
+    /// asked.
+    fn write_superblock_now(&mut self, recovering: bool) -> FileSystemResult<()> {
+        let mut superblock = Superblock::parse(self.superblock.raw().to_vec())?;
+        superblock.set_incompat(INCOMPAT_RECOVER, recovering);
+        superblock.update_checksum();
+
+        self.disk.write_at(SUPERBLOCK_OFFSET, superblock.raw())?;
+        self.disk.flush()
+    }
+
+    /// Puts changed group descriptors and the superblock among the pending metadata.
+    fn stage(&mut self) -> FileSystemResult<()> {
+        for group in core::mem::take(&mut self.dirty_groups) {
+            let (block, offset) = self.geometry.desc_location(group);
+
+            let descriptor = &mut self.groups[group as usize];
+            descriptor.update_checksum(&self.superblock, self.checksum_seed, group);
+
+            let mut data = self.disk.read_metadata(block)?;
+            data[offset..offset + self.geometry.desc_size].copy_from_slice(descriptor.raw());
+
+            self.disk.write_metadata(block, data);
+        }
+
+        if core::mem::take(&mut self.superblock_dirty) {
+            self.superblock.set_write_time(self.now().tv_sec as u32);
+            self.superblock.update_checksum();
+
+            let block = SUPERBLOCK_OFFSET / self.geometry.block_size as u64;
+            let offset = (SUPERBLOCK_OFFSET % self.geometry.block_size as u64) as usize;
+
+            let mut data = self.disk.read_metadata(block)?;
+            data[offset..offset + SUPERBLOCK_SIZE].copy_from_slice(self.superblock.raw());
+
+            self.disk.write_metadata(block, data);
+        }
+
+        Ok(())
+    }
+
+    /// Commits once enough metadata piled up.
+    pub fn maybe_commit(&mut self) -> FileSystemResult<()> {
+        match self.disk.pending_count() + self.dirty_groups.len() >= COMMIT_THRESHOLD {
+            true => self.commit(),
+            false => Ok(()),
+        }
+    }
+
+    /// Makes every change durable. With a journal the file contents reach the device first, then
+    /// the metadata is logged and only then written to its place.
+    pub fn commit(&mut self) -> FileSystemResult<()> {
+        if self.readonly {
+            return Ok(());
+        }
+
+        self.stage()?;
+
+        let pending = self.disk.take_pending();
+
+        let Some(mut journal) = self.journal.take() else {
+            for (block, data) in pending.iter() {
+                self.disk.write_block(*block, data)?;
+            }
+
+            return self.disk.flush();
+        };
+
+        let result = self.commit_journaled(&mut journal, &pending);
+
+        self.journal = Some(journal);
+
+        result
+    }
+
+    fn commit_journaled(
+        &mut self,
+        journal: &mut Journal,
+        pending: &alloc::collections::BTreeMap<u64, Vec<u8>>,
+    ) -> FileSystemResult<()> {
+        // Ordered mode, the contents new metadata points at go first
+        self.disk.flush()?;
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let superblock_block = SUPERBLOCK_OFFSET / self.geometry.block_size as u64;
+
+        let blocks: Vec<(u64, &[u8])> = pending
+            .iter()
+            .map(|(&block, data)| (block, data.as_slice()))
+            .collect();
+
+        // Without the flag Linux would discard the log instead of replaying it
+        self.write_superblock_now(true)?;
+
+        for transaction in blocks.chunks(journal.capacity().max(1)) {
+            journal.log(&self.disk, transaction, self.now())?;
+
+            for &(block, data) in transaction {
+                if block != superblock_block {
+                    self.disk.write_block(block, data)?;
+                }
+            }
+
+            self.disk.flush()?;
+
+            journal.checkpointed(&self.disk)?;
+        }
+
+        self.write_superblock_now(false)
+    }
+
+    /// Logs the pending metadata without writing it to its place, as if the system crashed
+    /// right after the commit.
+    #[cfg(test)]
+    pub fn commit_without_checkpoint(&mut self) -> FileSystemResult<()> {
+        self.stage()?;
+
+        let pending = self.disk.take_pending();
+        let blocks: Vec<(u64, &[u8])> = pending
+            .iter()
+            .map(|(&block, data)| (block, data.as_slice()))
+            .collect();
+
+        self.disk.flush()?;
+        self.write_superblock_now(true)?;
+
+        let now = self.now();
+        self.journal
+            .as_mut()
+            .unwrap()
+            .log(&self.disk, &blocks, now)?;
+
+        self.readonly = true;
+
+        Ok(())
+    }
+}
+
+fn read_superblock(disk: &Disk) -> FileSystemResult<Superblock> {
+    let mut raw = alloc::vec![0; SUPERBLOCK_SIZE];
+    disk.read_at(SUPERBLOCK_OFFSET, &mut raw)?;
+
+    Superblock::parse(raw)
+}
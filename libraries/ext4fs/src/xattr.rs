@@ -0,0 +1,400 @@
+//! Extended attributes, kept in the space after the extra fields of an inode and in a block of
+//! their own that `i_file_acl` points to. Any change moves all of them into the block.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use filesystem_abstractions::{check_set, FileSystemError, FileSystemResult, XattrFlags};
+
+use crate::{
+    crc::crc32c,
+    inode::{Inode, FLAG_INLINE_DATA},
+    raw::{le16, le32, set_le16, set_le32},
+    superblock::COMPAT_EXT_ATTR,
+    volume::Volume,
+};
+
+const MAGIC: u32 = 0xea02_0000;
+
+/// Size of the header of an attribute block, the entries follow it.
+const BLOCK_HEADER_SIZE: usize = 32;
+
+/// Size of an entry without its name.
+const ENTRY_SIZE: usize = 16;
+
+/// Offset of the checksum in the header of an attribute block.
+const CHECKSUM_OFFSET: usize = 16;
+
+/// Name prefixes stored as an index, the ones that are whole names come first.
+const PREFIXES: [(u8, &str); 6] = [
+    (2, "system.posix_acl_access"),
+    (3, "system.posix_acl_default"),
+    (1, "user."),
+    (4, "trusted."),
+    (6, "security."),
+    (7, "system."),
+];
+
+struct Attribute {
+    index: u8,
+    suffix: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Attribute {
+    fn new(name: &str, value: &[u8]) -> Attribute {
+        let (index, suffix) = PREFIXES
+            .iter()
+            .find(|(index, prefix)| match index {
+                2 | 3 => name == *prefix,
+                _ => name.starts_with(prefix),
+            })
+            .map_or((0, name), |(index, prefix)| (*index, &name[prefix.len()..]));
+
+        Attribute {
+            index,
+            suffix: suffix.as_bytes().to_vec(),
+            value: value.to_vec(),
+        }
+    }
+
+    fn name(&self) -> String {
+        let prefix = PREFIXES
+            .iter()
+            .find(|(index, _)| *index == self.index)
+            .map_or("", |(_, prefix)| prefix);
+
+        format!("{}{}", prefix, String::from_utf8_lossy(&self.suffix))
+    }
+
+    /// The hash of the entry, over its name and its value padded with zeros.
+    fn hash(&self) -> u32 {
+        let mut hash = 0u32;
+
+        for &byte in &self.suffix {
+            hash = (hash << 5) ^ (hash >> 27) ^ byte as u32;
+        }
+
+        for word in self.value.chunks(4) {
+            let mut padded = [0; 4];
+            padded[..word.len()].copy_from_slice(word);
+
+            hash = (hash << 16) ^ (hash >> 16) ^ u32::from_le_bytes(padded);
+        }
+
+        hash
+    }
+}
+
+fn pad(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// The entries from `start` of `region` up to the terminating zero, with values at offsets from
+/// `base`.
+fn parse(region: &[u8], start: usize, base: usize) -> FileSystemResult<Vec<Attribute>> {
+    let mut attributes = Vec::new();
+    let mut offset = start;
+
+    loop {
+        if offset + 4 > region.len() {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        if le32(region, offset) == 0 {
+            return Ok(attributes);
+        }
+
+        let name_end = offset + ENTRY_SIZE + region[offset] as usize;
+        let value_start = base + le16(region, offset + 2) as usize;
+        let value_end = value_start + le32(region, offset + 8) as usize;
+
+        // Values in inodes of their own need a feature that is refused at mount
+        if name_end > region.len() || value_end > region.len() || le32(region, offset + 4) != 0 {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        attributes.push(Attribute {
+            index: region[offset + 1],
+            suffix: region[offset + ENTRY_SIZE..name_end].to_vec(),
+            value: region[value_start..value_end].to_vec(),
+        });
+
+        offset = pad(name_end);
+    }
+}
+
+/// Lays `attributes` out in an attribute block in the order ext4 keeps them, `None` if they do
+/// not fit. Entries grow from the header, values from the end.
+fn build_block(attributes: &mut [Attribute], block_size: usize) -> Option<Vec<u8>> {
+    attributes.sort_by(|a, b| {
+        (a.index, a.suffix.len(), &a.suffix).cmp(&(b.index, b.suffix.len(), &b.suffix))
+    });
+
+    let mut block = vec![0; block_size];
+    set_le32(&mut block, 0x0, MAGIC);
+    set_le32(&mut block, 0x4, 1); // references
+    set_le32(&mut block, 0x8, 1); // blocks
+
+    let mut entry = BLOCK_HEADER_SIZE;
+    let mut values = block_size;
+    let mut block_hash = 0u32;
+
+    for attribute in attributes.iter() {
+        let entry_len = pad(ENTRY_SIZE + attribute.suffix.len());
+        let value_len = pad(attribute.value.len());
+
+        // The list of entries ends with four zero bytes
+        if attribute.suffix.len() > u8::MAX as usize || entry + entry_len + 4 + value_len > values {
+            return None;
+        }
+
+        if !attribute.value.is_empty() {
+            values -= value_len;
+            block[values..values + attribute.value.len()].copy_from_slice(&attribute.value);
+            set_le16(&mut block, entry + 2, values as u16);
+        }
+
+        let hash = attribute.hash();
+
+        block[entry] = attribute.suffix.len() as u8;
+        block[entry + 1] = attribute.index;
+        set_le32(&mut block, entry + 8, attribute.value.len() as u32);
+        set_le32(&mut block, entry + 12, hash);
+        block[entry + ENTRY_SIZE..entry + ENTRY_SIZE + attribute.suffix.len()]
+            .copy_from_slice(&attribute.suffix);
+
+        block_hash = (block_hash << 16) ^ (block_hash >> 16) ^ hash;
+        entry += entry_len;
+    }
+
+    set_le32(&mut block, 0xc, block_hash);
+
+    Some(block)
+}
+
+impl Volume {
+    fn xattr_block_checksum(&self, block: u64, data: &[u8]) -> u32 {
+        let crc = crc32c(self.checksum_seed, &block.to_le_bytes());
+        let crc = crc32c(crc, &data[..CHECKSUM_OFFSET]);
+        let crc = crc32c(crc, &[0; 4]);
+
+        crc32c(crc, &data[CHECKSUM_OFFSET + 4..])
+    }
+
+    fn read_xattrs(&self, inode: &Inode) -> FileSystemResult<Vec<Attribute>> {
+        let mut attributes = Vec::new();
+
+        // Values in the inode are at offsets from the first entry
+        let space = inode.xattr_space();
+        if space.len() >= 4 && le32(space, 0) == MAGIC {
+            attributes.extend(parse(space, 4, 4)?);
+        }
+
+        let block = inode.xattr_block();
+        if block != 0 {
+            let data = self.disk.read_metadata(block)?;
+
+            if le32(&data, 0x0) != MAGIC || le32(&data, 0x8) != 1 {
+                return Err(FileSystemError::FileSystemCorrupted);
+            }
+
+            if self.metadata_csum()
+                && le32(&data, CHECKSUM_OFFSET) != self.xattr_block_checksum(block, &data)
+            {
+                log::warn!("ext4: checksum mismatch in attribute block {}", block);
+                return Err(FileSystemError::FileSystemCorrupted);
+            }
+
+            attributes.extend(parse(&data, BLOCK_HEADER_SIZE, 0)?);
+        }
+
+        Ok(attributes)
+    }
+
+    pub fn get_xattr(&self, inode: &Inode, name: &str) -> FileSystemResult<Vec<u8>> {
+        self.read_xattrs(inode)?
+            .into_iter()
+            .find(|attribute| attribute.name() == name)
+            .map(|attribute| attribute.value)
+            .ok_or(FileSystemError::NoAttribute)
+    }
+
+    pub fn list_xattrs(&self, inode: &Inode) -> FileSystemResult<Vec<String>> {
+        Ok(self
+            .read_xattrs(inode)?
+            .iter()
+            .map(Attribute::name)
+            .collect())
+    }
+
+    pub fn set_xattr(
+        &mut self,
+        inode: &mut Inode,
+        name: &str,
+        value: &[u8],
+        flags: XattrFlags,
+    ) -> FileSystemResult<()> {
+        self.change_xattrs(inode, |attributes| {
+            let position = attributes.iter().position(|a| a.name() == name);
+
+            check_set(position.is_some(), flags)?;
+
+            let attribute = Attribute::new(name, value);
+
+            match position {
+                Some(position) => attributes[position] = attribute,
+                None => attributes.push(attribute),
+            }
+
+            Ok(())
+        })
+    }
+
+    pub fn remove_xattr(&mut self, inode: &mut Inode, name: &str) -> FileSystemResult<()> {
+        self.change_xattrs(inode, |attributes| {
+            let position = attributes
+                .iter()
+                .position(|a| a.name() == name)
+                .ok_or(FileSystemError::NoAttribute)?;
+
+            attributes.remove(position);
+
+            Ok(())
+        })
+    }
+
+    /// Applies `change` to the attributes of the inode and writes all of them to its block.
+    fn change_xattrs(
+        &mut self,
+        inode: &mut Inode,
+        change: impl FnOnce(&mut Vec<Attribute>) -> FileSystemResult<()>,
+    ) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        // Inline data keeps part of the contents as an attribute in the inode
+        if !self.superblock.has_compat(COMPAT_EXT_ATTR) || inode.has_flag(FLAG_INLINE_DATA) {
+            return Err(FileSystemError::NotSupported);
+        }
+
+        let mut attributes = self.read_xattrs(inode)?;
+        change(&mut attributes)?;
+
+        let data = match attributes.is_empty() {
+            true => None,
+            false => Some(
+                build_block(&mut attributes, self.block_size())
+                    .ok_or(FileSystemError::SpaceNotEnough)?,
+            ),
+        };
+
+        let mut block = inode.xattr_block();
+
+        // A block shared with other inodes is left to them
+        if block != 0 && (data.is_none() || le32(&self.disk.read_metadata(block)?, 0x4) > 1) {
+            self.release_xattrs(inode)?;
+            block = 0;
+        }
+
+        if let Some(mut data) = data {
+            if block == 0 {
+                (block, _) = self.allocate_blocks(self.goal(inode), 1)?;
+
+                inode.set_xattr_block(block);
+                inode.add_blocks(1, self.block_size());
+            }
+
+            if self.metadata_csum() {
+                let checksum = self.xattr_block_checksum(block, &data);
+                set_le32(&mut data, CHECKSUM_OFFSET, checksum);
+            }
+
+            self.disk.write_metadata(block, data);
+        }
+
+        let space = inode.xattr_space_mut();
+        if space.len() >= 4 {
+            space.fill(0);
+        }
+
+        inode.set_change_time(self.now());
+        self.write_inode(inode)
+    }
+
+    /// Drops the reference of the inode to its attribute block, which is freed unless other
+    /// inodes share it.
+    pub fn release_xattrs(&mut self, inode: &mut Inode) -> FileSystemResult<()> {
+        let block = inode.xattr_block();
+
+        if block == 0 {
+            return Ok(());
+        }
+
+        let mut data = self.disk.read_metadata(block)?;
+        let references = le32(&data, 0x4);
+
+        match references > 1 {
+            true => {
+                set_le32(&mut data, 0x4, references - 1);
+
+                if self.metadata_csum() {
+                    let checksum = self.xattr_block_checksum(block, &data);
+                    set_le32(&mut data, CHECKSUM_OFFSET, checksum);
+                }
+
+                self.disk.write_metadata(block, data);
+            }
+            false => self.free_blocks(block, 1)?,
+        }
+
+        inode.set_xattr_block(0);
+        inode.add_blocks(-1, self.block_size());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixes() {
+        let attribute = Attribute::new("user.mime_type", b"");
+        assert_eq!(
+            (attribute.index, &attribute.suffix[..]),
+            (1, &b"mime_type"[..])
+        );
+
+        let acl = Attribute::new("system.posix_acl_access", b"");
+        assert_eq!((acl.index, acl.suffix.len()), (2, 0));
+        assert_eq!(acl.name(), "system.posix_acl_access");
+
+        assert_eq!(Attribute::new("system.data", b"").index, 7);
+    }
+
+    #[test]
+    fn test_block_roundtrip() {
+        let mut attributes = vec![
+            Attribute::new("user.b", b"second"),
+            Attribute::new("trusted.a", b"x"),
+            Attribute::new("user.a", b""),
+        ];
+
+        let block = build_block(&mut attributes, 1024).unwrap();
+        let parsed = parse(&block, BLOCK_HEADER_SIZE, 0).unwrap();
+
+        let names: Vec<String> = parsed.iter().map(Attribute::name).collect();
+        assert_eq!(names, ["user.a", "user.b", "trusted.a"]);
+        assert_eq!(parsed[1].value, b"second");
+        assert!(parsed[0].value.is_empty());
+        assert_eq!(le32(&block, BLOCK_HEADER_SIZE + 12), parsed[0].hash());
+    }
+
+    #[test]
+    fn test_block_full() {
+        let mut attributes = vec![Attribute::new("user.big", &[0; 950])];
+        assert!(build_block(&mut attributes, 1024).is_some());
+
+        attributes.push(Attribute::new("user.more", b"x"));
+        assert!(build_block(&mut attributes, 1024).is_none());
+    }
+}
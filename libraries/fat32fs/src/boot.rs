@@ -0,0 +1,264 @@
+//! The boot sector describing the layout of the volume, and the FSInfo sector keeping hints about
+//! free clusters.
+
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::raw::{le16, le32, set_le32};
+
+const BOOT_SIGNATURE: u16 = 0xaa55;
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FSINFO_TRAIL_SIGNATURE: u32 = 0xaa55_0000;
+const FSINFO_FREE_COUNT: usize = 488;
+const FSINFO_NEXT_FREE: usize = 492;
+
+/// Value of the FSInfo fields that are not known.
+pub(crate) const UNKNOWN: u32 = 0xffff_ffff;
+
+/// Bit of the extended flags telling that only one FAT is in use.
+const NO_MIRRORING: u16 = 0x80;
+
+/// Where everything is on the volume.
+pub(crate) struct Geometry {
+    pub bytes_per_sector: u32,
+    pub sectors_per_cluster: u32,
+    pub reserved_sectors: u32,
+    pub fat_count: u32,
+    pub fat_sectors: u32,
+    pub root_cluster: u32,
+    /// Clusters of the data area, numbered from 2
+    pub cluster_count: u32,
+    /// First sector of the data area
+    pub data_start: u32,
+    pub fsinfo_sector: Option<u32>,
+    /// The only FAT in use, `None` if all of them are kept the same
+    pub active_fat: Option<u32>,
+}
+
+impl Geometry {
+    /// Reads the BIOS parameter block of the first sector of a volume.
+    pub fn parse(sector: &[u8]) -> FileSystemResult<Geometry> {
+        if sector.len() < 512 || le16(sector, 510) != BOOT_SIGNATURE {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        let bytes_per_sector = le16(sector, 11) as u32;
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved_sectors = le16(sector, 14) as u32;
+        let fat_count = sector[16] as u32;
+        let root_entries = le16(sector, 17);
+        let fat16_sectors = le16(sector, 22);
+
+        if !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || fat_count == 0
+        {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        // FAT12 and FAT16 have a fixed root directory and keep the FAT size here
+        if root_entries != 0 || fat16_sectors != 0 {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        let total_sectors = match le16(sector, 19) {
+            0 => le32(sector, 32),
+            sectors => sectors as u32,
+        };
+
+        let fat_sectors = le32(sector, 36);
+        let extended_flags = le16(sector, 40);
+        let version = le16(sector, 42);
+        let root_cluster = le32(sector, 44);
+
+        if version != 0 {
+            return Err(FileSystemError::Unimplemented);
+        }
+
+        let data_start = fat_sectors
+            .checked_mul(fat_count)
+            .and_then(|fats| fats.checked_add(reserved_sectors))
+            .filter(|&start| start < total_sectors)
+            .ok_or(FileSystemError::FileSystemCorrupted)?;
+
+        let cluster_count = (total_sectors - data_start) / sectors_per_cluster;
+
+        // Entries past the end of the FAT would have nowhere to go
+        let fat_entries = fat_sectors as u64 * bytes_per_sector as u64 / 4;
+        let cluster_count = cluster_count.min(fat_entries.saturating_sub(2) as u32);
+
+        let geometry = Geometry {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors,
+            fat_count,
+            fat_sectors,
+            root_cluster,
+            cluster_count,
+            data_start,
+            fsinfo_sector: match le16(sector, 48) {
+                0 | 0xffff => None,
+                fsinfo => Some(fsinfo as u32).filter(|&s| s < reserved_sectors),
+            },
+            active_fat: match extended_flags & NO_MIRRORING {
+                0 => None,
+                _ => Some((extended_flags & 0xf) as u32).filter(|&fat| fat < fat_count),
+            },
+        };
+
+        if !geometry.is_cluster(root_cluster) {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        Ok(geometry)
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        (self.bytes_per_sector * self.sectors_per_cluster) as usize
+    }
+
+    /// Whether `cluster` is in the data area.
+    pub fn is_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count as u64 + 2).contains(&(cluster as u64))
+    }
+
+    /// Byte offset of a cluster on the device.
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        debug_assert!(self.is_cluster(cluster));
+
+        (self.data_start as u64 + (cluster - 2) as u64 * self.sectors_per_cluster as u64)
+            * self.bytes_per_sector as u64
+    }
+
+    /// Byte offset of the entry of `cluster` in the `fat`th FAT.
+    pub fn fat_offset(&self, fat: u32, cluster: u32) -> u64 {
+        (self.reserved_sectors as u64 + fat as u64 * self.fat_sectors as u64)
+            * self.bytes_per_sector as u64
+            + cluster as u64 * 4
+    }
+
+    /// The FATs changes have to go to.
+    pub fn fats(&self) -> impl Iterator<Item = u32> {
+        match self.active_fat {
+            Some(fat) => fat..fat + 1,
+            None => 0..self.fat_count,
+        }
+    }
+
+    pub fn fsinfo_offset(&self) -> Option<u64> {
+        self.fsinfo_sector
+            .map(|sector| sector as u64 * self.bytes_per_sector as u64)
+    }
+}
+
+/// The free cluster count and the cluster to start looking for free ones from, if the sector
+/// is an FSInfo sector.
+pub(crate) fn parse_fsinfo(sector: &[u8]) -> Option<(u32, u32)> {
+    let valid = le32(sector, 0) == FSINFO_LEAD_SIGNATURE
+        && le32(sector, 484) == FSINFO_STRUCT_SIGNATURE
+        && le32(sector, 508) == FSINFO_TRAIL_SIGNATURE;
+
+    valid.then(|| {
+        (
+            le32(sector, FSINFO_FREE_COUNT),
+            le32(sector, FSINFO_NEXT_FREE),
+        )
+    })
+}
+
+pub(crate) fn update_fsinfo(sector: &mut [u8], free_count: u32, next_free: u32) {
+    set_le32(sector, FSINFO_FREE_COUNT, free_count);
+    set_le32(sector, FSINFO_NEXT_FREE, next_free);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+    use crate::raw::set_le16;
+
+    /// A boot sector laid out like the ones mkfs.vfat writes for FAT32.
+    pub fn boot_sector(total_sectors: u32, sectors_per_cluster: u8, fat_sectors: u32) -> Vec<u8> {
+        let mut sector = vec![0; 512];
+
+        sector[..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        sector[3..11].copy_from_slice(b"mkfs.fat");
+        set_le16(&mut sector, 11, 512);
+        sector[13] = sectors_per_cluster;
+        set_le16(&mut sector, 14, 32);
+        sector[16] = 2;
+        sector[21] = 0xf8;
+        set_le16(&mut sector, 24, 32);
+        set_le16(&mut sector, 26, 8);
+        set_le32(&mut sector, 32, total_sectors);
+        set_le32(&mut sector, 36, fat_sectors);
+        set_le32(&mut sector, 44, 2);
+        set_le16(&mut sector, 48, 1);
+        set_le16(&mut sector, 50, 6);
+        sector[64] = 0x80;
+        sector[66] = 0x29;
+        sector[71..82].copy_from_slice(b"NO NAME    ");
+        sector[82..90].copy_from_slice(b"FAT32   ");
+        set_le16(&mut sector, 510, BOOT_SIGNATURE);
+
+        sector
+    }
+
+    /// An FSInfo sector with its signatures.
+    pub fn fsinfo_sector(free_count: u32, next_free: u32) -> Vec<u8> {
+        let mut sector = vec![0; 512];
+
+        set_le32(&mut sector, 0, FSINFO_LEAD_SIGNATURE);
+        set_le32(&mut sector, 484, FSINFO_STRUCT_SIGNATURE);
+        set_le32(&mut sector, 508, FSINFO_TRAIL_SIGNATURE);
+        update_fsinfo(&mut sector, free_count, next_free);
+
+        sector
+    }
+
+    #[test]
+    fn test_parse() {
+        let geometry = Geometry::parse(&boot_sector(131072, 1, 1009)).unwrap();
+
+        assert_eq!(geometry.cluster_size(), 512);
+        assert_eq!(geometry.data_start, 32 + 2 * 1009);
+        assert_eq!(geometry.cluster_count, 131072 - 2050);
+        assert_eq!(geometry.fsinfo_offset(), Some(512));
+        assert_eq!(geometry.fats().count(), 2);
+        assert_eq!(geometry.cluster_offset(2), 2050 * 512);
+        assert_eq!(geometry.fat_offset(1, 3), (32 + 1009) * 512 + 12);
+    }
+
+    #[test]
+    fn test_rejects_other_fats() {
+        let mut sector = boot_sector(131072, 1, 1009);
+        set_le16(&mut sector, 22, 256);
+        assert_eq!(
+            Geometry::parse(&sector).err(),
+            Some(FileSystemError::Unimplemented)
+        );
+
+        let mut sector = boot_sector(131072, 1, 1009);
+        sector[510] = 0;
+        assert_eq!(
+            Geometry::parse(&sector).err(),
+            Some(FileSystemError::FileSystemCorrupted)
+        );
+
+        assert!(Geometry::parse(&boot_sector(131072, 3, 1009)).is_err());
+    }
+
+    #[test]
+    fn test_fsinfo() {
+        assert_eq!(parse_fsinfo(&[0; 512]), None);
+
+        let mut sector = fsinfo_sector(UNKNOWN, UNKNOWN);
+        assert_eq!(parse_fsinfo(&sector), Some((UNKNOWN, UNKNOWN)));
+
+        update_fsinfo(&mut sector, 100, 7);
+        assert_eq!(parse_fsinfo(&sector), Some((100, 7)));
+    }
+}
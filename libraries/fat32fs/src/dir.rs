@@ -0,0 +1,579 @@
+use alloc::{string::String, vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use timing::TimeSpec;
+
+use crate::{
+    name::{
+        as_short_name, checksum, display_short_name, is_dot_entry, names_equal,
+        numbered_short_name, SHORT_NAME_LEN,
+    },
+    raw::{le16, le32, set_le16, set_le32},
+    time::{from_fat, to_fat},
+    volume::Volume,
+};
+
+pub(crate) const ENTRY_SIZE: usize = 32;
+
+pub(crate) const ATTR_READ_ONLY: u8 = 0x01;
+pub(crate) const ATTR_VOLUME_ID: u8 = 0x08;
+pub(crate) const ATTR_DIRECTORY: u8 = 0x10;
+pub(crate) const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// First name byte of deleted entries.
+const DELETED: u8 = 0xe5;
+/// Bit of the sequence number of the last long name entry, which comes first on disk.
+const LAST_LONG_ENTRY: u8 = 0x40;
+const LONG_CHARS: usize = 13;
+/// Offsets of the name characters in a long name entry.
+const LONG_CHAR_OFFSETS: [usize; LONG_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Entries a directory can have at most, their index has to fit 16 bits.
+const MAX_ENTRIES: usize = 65536;
+
+/// Largest numeric tail tried for a generated short name.
+const MAX_TAIL: u32 = 999_999;
+
+/// A short name entry, which holds everything about a file but its long name.
+#[derive(Clone)]
+pub(crate) struct ShortEntry {
+    raw: [u8; ENTRY_SIZE],
+}
+
+impl ShortEntry {
+    pub fn new(name: [u8; SHORT_NAME_LEN], case: u8, attributes: u8, now: TimeSpec) -> ShortEntry {
+        let mut entry = ShortEntry {
+            raw: [0; ENTRY_SIZE],
+        };
+
+        entry.set_name(name, case);
+        entry.raw[11] = attributes;
+
+        let (date, time, centiseconds) = to_fat(now);
+        entry.raw[13] = centiseconds;
+        set_le16(&mut entry.raw, 14, time);
+        set_le16(&mut entry.raw, 16, date);
+        entry.set_modified(now);
+
+        entry
+    }
+
+    fn parse(bytes: &[u8]) -> ShortEntry {
+        ShortEntry {
+            raw: bytes.try_into().unwrap(),
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8; ENTRY_SIZE] {
+        &self.raw
+    }
+
+    pub fn name(&self) -> [u8; SHORT_NAME_LEN] {
+        self.raw[..SHORT_NAME_LEN].try_into().unwrap()
+    }
+
+    pub fn case(&self) -> u8 {
+        self.raw[12]
+    }
+
+    pub fn set_name(&mut self, name: [u8; SHORT_NAME_LEN], case: u8) {
+        self.raw[..SHORT_NAME_LEN].copy_from_slice(&name);
+
+        if self.raw[0] == DELETED {
+            self.raw[0] = 0x05;
+        }
+
+        self.raw[12] = case;
+    }
+
+    pub fn attributes(&self) -> u8 {
+        self.raw[11]
+    }
+
+    pub fn set_attributes(&mut self, attributes: u8) {
+        self.raw[11] = attributes;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes() & ATTR_DIRECTORY != 0
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        (le16(&self.raw, 20) as u32) << 16 | le16(&self.raw, 26) as u32
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: u32) {
+        set_le16(&mut self.raw, 20, (cluster >> 16) as u16);
+        set_le16(&mut self.raw, 26, cluster as u16);
+    }
+
+    pub fn size(&self) -> u32 {
+        le32(&self.raw, 28)
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        set_le32(&mut self.raw, 28, size);
+    }
+
+    pub fn created(&self) -> TimeSpec {
+        from_fat(le16(&self.raw, 16), le16(&self.raw, 14), self.raw[13])
+    }
+
+    pub fn modified(&self) -> TimeSpec {
+        from_fat(le16(&self.raw, 24), le16(&self.raw, 22), 0)
+    }
+
+    /// Only the date of the last access is kept.
+    pub fn accessed(&self) -> TimeSpec {
+        from_fat(le16(&self.raw, 18), 0, 0)
+    }
+
+    /// Records a change of the contents, which is an access as well.
+    pub fn set_modified(&mut self, now: TimeSpec) {
+        self.set_modify_time(now);
+        self.set_access_time(now);
+    }
+
+    pub fn set_modify_time(&mut self, time: TimeSpec) {
+        let (date, time, _) = to_fat(time);
+
+        set_le16(&mut self.raw, 22, time);
+        set_le16(&mut self.raw, 24, date);
+    }
+
+    pub fn set_access_time(&mut self, time: TimeSpec) {
+        set_le16(&mut self.raw, 18, to_fat(time).0);
+    }
+}
+
+/// Where the short entry of a file is, as the first cluster of its directory and the index of
+/// the entry in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Location {
+    pub directory: u32,
+    pub slot: u32,
+}
+
+/// A file found in a directory.
+pub(crate) struct DirEntry {
+    pub name: String,
+    pub short: ShortEntry,
+    pub location: Location,
+    /// Index of the first long name entry, or of the short entry if there are none
+    pub first_slot: u32,
+}
+
+fn long_entry(name: &[u16], sequence: u8, checksum: u8) -> [u8; ENTRY_SIZE] {
+    let mut entry = [0; ENTRY_SIZE];
+
+    entry[0] = sequence;
+    entry[11] = ATTR_LONG_NAME;
+    entry[13] = checksum;
+
+    let first = ((sequence & !LAST_LONG_ENTRY) as usize - 1) * LONG_CHARS;
+
+    for (i, &offset) in LONG_CHAR_OFFSETS.iter().enumerate() {
+        // The name ends with a null if there is room for it and is padded with 0xffff after
+        let unit = match (first + i).cmp(&name.len()) {
+            core::cmp::Ordering::Less => name[first + i],
+            core::cmp::Ordering::Equal => 0,
+            core::cmp::Ordering::Greater => 0xffff,
+        };
+
+        set_le16(&mut entry, offset, unit);
+    }
+
+    entry
+}
+
+/// The entries of a directory, long name entries included, with the clusters holding them.
+pub(crate) struct Slots {
+    pub clusters: Vec<u32>,
+    pub data: Vec<u8>,
+}
+
+impl Slots {
+    pub fn count(&self) -> usize {
+        self.data.len() / ENTRY_SIZE
+    }
+
+    pub fn get(&self, slot: usize) -> &[u8] {
+        &self.data[slot * ENTRY_SIZE..(slot + 1) * ENTRY_SIZE]
+    }
+
+    /// Files of the directory in order, without `.`, `..` and volume labels.
+    pub fn entries(&self, directory: u32) -> Vec<DirEntry> {
+        let mut entries = Vec::new();
+
+        // The long name being put together: its units, the next sequence number expected,
+        // its checksum and where it started
+        let mut long: Option<(Vec<u16>, u8, u8, usize)> = None;
+
+        for slot in 0..self.count() {
+            let bytes = self.get(slot);
+
+            match bytes[0] {
+                0 => break,
+                DELETED => {
+                    long = None;
+                    continue;
+                }
+                _ => (),
+            }
+
+            if bytes[11] & 0x3f == ATTR_LONG_NAME {
+                let sequence = bytes[0] & !LAST_LONG_ENTRY;
+
+                if bytes[0] & LAST_LONG_ENTRY != 0 {
+                    long = match sequence {
+                        1..=20 => Some((
+                            vec![0; sequence as usize * LONG_CHARS],
+                            sequence,
+                            bytes[13],
+                            slot,
+                        )),
+                        _ => None,
+                    };
+                }
+
+                long =
+                    long.filter(|&(_, expected, sum, _)| sequence == expected && bytes[13] == sum);
+
+                if let Some((units, expected, _, _)) = &mut long {
+                    let first = (sequence as usize - 1) * LONG_CHARS;
+
+                    for (i, &offset) in LONG_CHAR_OFFSETS.iter().enumerate() {
+                        units[first + i] = le16(bytes, offset);
+                    }
+
+                    *expected -= 1;
+                }
+
+                continue;
+            }
+
+            let pending = long.take();
+
+            if bytes[11] & ATTR_VOLUME_ID != 0 {
+                continue;
+            }
+
+            let short = ShortEntry::parse(bytes);
+            let name = short.name();
+
+            if is_dot_entry(&name) {
+                continue;
+            }
+
+            let (name, first_slot) = match pending {
+                Some((units, 0, sum, start)) if sum == checksum(&name) => {
+                    let len = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+
+                    let long = char::decode_utf16(units[..len].iter().copied())
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect();
+
+                    (long, start)
+                }
+                _ => (display_short_name(&name, short.case()), slot),
+            };
+
+            entries.push(DirEntry {
+                name,
+                short,
+                location: Location {
+                    directory,
+                    slot: slot as u32,
+                },
+                first_slot: first_slot as u32,
+            });
+        }
+
+        entries
+    }
+
+    fn short_names(&self) -> Vec<[u8; SHORT_NAME_LEN]> {
+        (0..self.count())
+            .map(|slot| self.get(slot))
+            .take_while(|bytes| bytes[0] != 0)
+            .filter(|bytes| bytes[0] != DELETED && bytes[11] & 0x3f != ATTR_LONG_NAME)
+            .map(|bytes| bytes[..SHORT_NAME_LEN].try_into().unwrap())
+            .collect()
+    }
+
+    /// The first of `count` free slots in a row, may be past the end of the directory.
+    fn find_free(&self, count: usize) -> usize {
+        let mut run = 0;
+
+        for slot in 0..self.count() {
+            match self.get(slot)[0] {
+                // Everything past the end marker is free
+                0 => return slot - run,
+                DELETED => run += 1,
+                _ => run = 0,
+            }
+
+            if run == count {
+                return slot + 1 - run;
+            }
+        }
+
+        self.count() - run
+    }
+}
+
+impl Volume {
+    pub fn read_slots(&self, directory: u32) -> FileSystemResult<Slots> {
+        let clusters = self.chain(directory)?;
+
+        if clusters.is_empty() {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        let cluster_size = self.geometry.cluster_size();
+        let mut data = vec![0; clusters.len() * cluster_size];
+
+        for (i, &cluster) in clusters.iter().enumerate() {
+            self.read_cluster(
+                cluster,
+                0,
+                &mut data[i * cluster_size..(i + 1) * cluster_size],
+            )?;
+        }
+
+        Ok(Slots { clusters, data })
+    }
+
+    fn write_slot(&self, clusters: &[u32], slot: usize, bytes: &[u8]) -> FileSystemResult<()> {
+        let offset = slot * ENTRY_SIZE;
+        let cluster_size = self.geometry.cluster_size();
+
+        let cluster = *clusters
+            .get(offset / cluster_size)
+            .ok_or(FileSystemError::FileSystemCorrupted)?;
+
+        self.write_cluster(cluster, offset % cluster_size, bytes)
+    }
+
+    pub fn list(&self, directory: u32) -> FileSystemResult<Vec<DirEntry>> {
+        Ok(self.read_slots(directory)?.entries(directory))
+    }
+
+    /// Finds a file by its long or short name, ignoring case.
+    pub fn find(&self, directory: u32, name: &str) -> FileSystemResult<Option<DirEntry>> {
+        let slots = self.read_slots(directory)?;
+
+        let found = slots.entries(directory).into_iter().find(|entry| {
+            names_equal(&entry.name, name)
+                || names_equal(&display_short_name(&entry.short.name(), 0), name)
+        });
+
+        Ok(found)
+    }
+
+    pub fn is_empty_dir(&self, directory: u32) -> FileSystemResult<bool> {
+        Ok(self.list(directory)?.is_empty())
+    }
+
+    /// Rewrites the short entry at `location` after the file changed.
+    pub fn write_short(&self, location: Location, entry: &ShortEntry) -> FileSystemResult<()> {
+        let clusters = self.chain(location.directory)?;
+
+        self.write_slot(&clusters, location.slot as usize, entry.bytes())
+    }
+
+    /// Adds `entry` as `name` to a directory, along with a long name if the name needs one. The
+    /// name of `entry` is replaced.
+    pub fn insert(
+        &mut self,
+        directory: u32,
+        name: &str,
+        entry: &mut ShortEntry,
+    ) -> FileSystemResult<Location> {
+        let mut slots = self.read_slots(directory)?;
+        let taken = slots.short_names();
+
+        let mut long = Vec::new();
+
+        match as_short_name(name).filter(|(short, _)| !taken.contains(short)) {
+            Some((short, case)) => entry.set_name(short, case),
+            None => {
+                let short = (1..=MAX_TAIL)
+                    .map(|number| numbered_short_name(name, number))
+                    .find(|short| !taken.contains(short))
+                    .ok_or(FileSystemError::SpaceNotEnough)?;
+
+                entry.set_name(short, 0);
+
+                let units: Vec<u16> = name.encode_utf16().collect();
+                let count = units.len().div_ceil(LONG_CHARS);
+                let sum = checksum(&entry.name());
+
+                for sequence in (1..=count as u8).rev() {
+                    let flag = match sequence as usize == count {
+                        true => LAST_LONG_ENTRY,
+                        false => 0,
+                    };
+
+                    long.push(long_entry(&units, sequence | flag, sum));
+                }
+            }
+        }
+
+        let needed = long.len() + 1;
+        let first = slots.find_free(needed);
+
+        if first + needed > MAX_ENTRIES {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        if first + needed > slots.count() {
+            let cluster_size = self.geometry.cluster_size();
+            let missing =
+                ((first + needed) * ENTRY_SIZE).div_ceil(cluster_size) - slots.clusters.len();
+
+            let added = self.allocate(missing, slots.clusters.last().copied())?;
+
+            for &cluster in added.iter() {
+                self.write_cluster(cluster, 0, &vec![0; cluster_size])?;
+            }
+
+            slots.clusters.extend(added);
+        }
+
+        for (i, bytes) in long.iter().enumerate() {
+            self.write_slot(&slots.clusters, first + i, bytes)?;
+        }
+
+        let slot = first + long.len();
+        self.write_slot(&slots.clusters, slot, entry.bytes())?;
+
+        Ok(Location {
+            directory,
+            slot: slot as u32,
+        })
+    }
+
+    /// Marks the entries of a file deleted.
+    pub fn remove(&self, entry: &DirEntry) -> FileSystemResult<()> {
+        let clusters = self.chain(entry.location.directory)?;
+
+        for slot in entry.first_slot..=entry.location.slot {
+            self.write_slot(&clusters, slot as usize, &[DELETED])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the `.` and `..` entries into the first cluster of a new directory, which has to be
+    /// zeroed.
+    pub fn init_dir(&self, cluster: u32, parent: u32, now: TimeSpec) -> FileSystemResult<()> {
+        let mut this = ShortEntry::new(*b".          ", 0, ATTR_DIRECTORY, now);
+        this.set_first_cluster(cluster);
+
+        // The root directory is cluster 0 to its children
+        let mut up = ShortEntry::new(*b"..         ", 0, ATTR_DIRECTORY, now);
+        up.set_first_cluster(match parent == self.geometry.root_cluster {
+            true => 0,
+            false => parent,
+        });
+
+        self.write_cluster(cluster, 0, this.bytes())?;
+        self.write_cluster(cluster, ENTRY_SIZE, up.bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entries of a directory holding "Long File Name.txt", a deleted file and "SHORT.TXT",
+    /// encoded following the specification rather than by this driver.
+    const DIRECTORY: &str = concat!(
+        "4265002e007400780074000f00d40000ffffffffffffffffffff0000ffffffff",
+        "014c006f006e00670020000f00d4460069006c00650020004e00000061006d00",
+        "4c4f4e4746497e31545854200000000000000000000000000000000000000000",
+        "e54c442020202020545854200000000000000000000000000000000000000000",
+        "53484f5254202020545854200000000000000000000000000000000000000000",
+    );
+
+    fn decode(hex: &str) -> Vec<u8> {
+        let hex: Vec<u8> = hex.bytes().collect();
+
+        hex.chunks(2)
+            .map(|pair| u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_long_entries() {
+        let units: Vec<u16> = "Long File Name.txt".encode_utf16().collect();
+        let sum = checksum(b"LONGFI~1TXT");
+
+        assert_eq!(long_entry(&units, 0x42, sum)[..], decode(DIRECTORY)[..32]);
+        assert_eq!(long_entry(&units, 0x01, sum)[..], decode(DIRECTORY)[32..64]);
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let mut data = decode(DIRECTORY);
+        data.resize(512, 0);
+
+        let slots = Slots {
+            clusters: vec![2],
+            data,
+        };
+
+        let entries = slots.entries(2);
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].name, "Long File Name.txt");
+        assert_eq!(entries[0].first_slot, 0);
+        assert_eq!(entries[0].location.slot, 2);
+        assert_eq!(&entries[0].short.name(), b"LONGFI~1TXT");
+
+        assert_eq!(entries[1].name, "SHORT.TXT");
+        assert_eq!(entries[1].first_slot, 4);
+        assert_eq!(entries[1].short.case(), 0);
+
+        assert_eq!(slots.find_free(1), 3);
+        assert_eq!(slots.find_free(2), 5);
+    }
+
+    #[test]
+    fn test_orphaned_long_entries() {
+        let mut data = decode(DIRECTORY);
+        data.resize(512, 0);
+
+        // The short name no longer matches the checksum of its long name
+        data[64..75].copy_from_slice(b"RENAMED TXT");
+
+        let slots = Slots {
+            clusters: vec![2],
+            data,
+        };
+
+        let entries = slots.entries(2);
+        assert_eq!(entries[0].name, "RENAMED.TXT");
+        assert_eq!(entries[0].first_slot, 2);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let now = TimeSpec {
+            tv_sec: 1_700_000_001,
+            tv_nsec: 0,
+        };
+
+        let mut entry = ShortEntry::new(*b"FILE    TXT", 0, ATTR_ARCHIVE, now);
+        entry.set_first_cluster(0x0012_3456);
+        entry.set_size(1234);
+
+        assert_eq!(entry.created(), now);
+        assert_eq!(entry.modified().tv_sec, 1_700_000_000);
+        assert_eq!(entry.accessed().tv_sec, 1_699_920_000);
+        assert_eq!(entry.first_cluster(), 0x0012_3456);
+        assert_eq!(entry.size(), 1234);
+        assert!(!entry.is_dir());
+    }
+}
@@ -0,0 +1,167 @@
+use alloc::{vec, vec::Vec};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+use crate::{
+    dir::{ShortEntry, ATTR_ARCHIVE},
+    volume::Volume,
+};
+
+/// Largest file FAT can hold, the size is 32 bits.
+const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+impl Volume {
+    /// Walks the bytes from `offset` of the file made of `chain`, calling `f` with each cluster,
+    /// the offset in it and the range of the walked bytes it covers.
+    fn walk(
+        &self,
+        chain: &[u32],
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(u32, usize, core::ops::Range<usize>) -> FileSystemResult<()>,
+    ) -> FileSystemResult<()> {
+        let cluster_size = self.geometry.cluster_size();
+        let mut done = 0;
+
+        while done < len {
+            let position = offset + done as u64;
+
+            let cluster = *chain
+                .get((position / cluster_size as u64) as usize)
+                .ok_or(FileSystemError::FileSystemCorrupted)?;
+
+            let within = (position % cluster_size as u64) as usize;
+            let piece = (cluster_size - within).min(len - done);
+
+            f(cluster, within, done..done + piece)?;
+            done += piece;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_contents(
+        &self,
+        entry: &ShortEntry,
+        offset: u64,
+        buffer: &mut [u8],
+    ) -> FileSystemResult<usize> {
+        let size = entry.size() as u64;
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let len = buffer.len().min((size - offset) as usize);
+        let chain = self.chain(entry.first_cluster())?;
+
+        self.walk(&chain, offset, len, |cluster, within, range| {
+            self.read_cluster(cluster, within, &mut buffer[range])
+        })?;
+
+        Ok(len)
+    }
+
+    /// Fills the bytes from `from` to `to` of a file with zeros.
+    fn zero_contents(&self, chain: &[u32], from: u64, to: u64) -> FileSystemResult<()> {
+        let zeros = vec![0; self.geometry.cluster_size()];
+
+        self.walk(
+            chain,
+            from,
+            (to - from) as usize,
+            |cluster, within, range| self.write_cluster(cluster, within, &zeros[..range.len()]),
+        )
+    }
+
+    /// Makes the chain of a file at least `size` bytes long, returning the whole chain.
+    fn grow_chain(&mut self, entry: &mut ShortEntry, size: u64) -> FileSystemResult<Vec<u32>> {
+        let mut chain = self.chain(entry.first_cluster())?;
+        let needed = size.div_ceil(self.geometry.cluster_size() as u64) as usize;
+
+        if needed > chain.len() {
+            let added = self.allocate(needed - chain.len(), chain.last().copied())?;
+
+            if chain.is_empty() {
+                entry.set_first_cluster(added[0]);
+            }
+
+            chain.extend(added);
+        }
+
+        Ok(chain)
+    }
+
+    /// Writes `data` at `offset` of a file, the entry is not written back.
+    pub fn write_contents(
+        &mut self,
+        entry: &mut ShortEntry,
+        offset: u64,
+        data: &[u8],
+    ) -> FileSystemResult<usize> {
+        self.ensure_writable()?;
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let end = offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or(FileSystemError::InvalidInput)?;
+
+        let size = entry.size() as u64;
+        let chain = self.grow_chain(entry, end)?;
+
+        // There are no holes, whatever is skipped is zeroed
+        if offset > size {
+            self.zero_contents(&chain, size, offset)?;
+        }
+
+        self.walk(&chain, offset, data.len(), |cluster, within, range| {
+            self.write_cluster(cluster, within, &data[range])
+        })?;
+
+        if end > size {
+            entry.set_size(end as u32);
+        }
+
+        entry.set_attributes(entry.attributes() | ATTR_ARCHIVE);
+        entry.set_modified(self.now());
+
+        Ok(data.len())
+    }
+
+    /// Grows or shrinks a file, the entry is not written back.
+    pub fn resize_contents(&mut self, entry: &mut ShortEntry, size: u64) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        if size > MAX_FILE_SIZE {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        let old_size = entry.size() as u64;
+
+        if size > old_size {
+            let chain = self.grow_chain(entry, size)?;
+            self.zero_contents(&chain, old_size, size)?;
+        } else {
+            let chain = self.chain(entry.first_cluster())?;
+            let keep = size.div_ceil(self.geometry.cluster_size() as u64) as usize;
+
+            if keep < chain.len() {
+                match keep {
+                    0 => entry.set_first_cluster(0),
+                    _ => self.end_chain(chain[keep - 1])?,
+                }
+
+                self.free(&chain[keep..])?;
+            }
+        }
+
+        entry.set_size(size as u32);
+        entry.set_attributes(entry.attributes() | ATTR_ARCHIVE);
+        entry.set_modified(self.now());
+
+        Ok(())
+    }
+}
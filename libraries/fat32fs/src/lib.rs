@@ -0,0 +1,854 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use block_abstractions::{BlockDevices, IBlockDevice};
+use filesystem_abstractions::{FileSystemResult, IFileSystem, IFileSystemType, IInode, MountFlags};
+use hermit_sync::SpinMutex;
+use threading::IClock;
+use timing::TimeSpec;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod boot;
+mod dir;
+mod file;
+mod name;
+mod node;
+mod raw;
+mod time;
+mod volume;
+
+pub use node::Fat32Inode;
+
+use dir::{Location, ShortEntry, ATTR_DIRECTORY};
+use node::Entry;
+use volume::Volume;
+
+/// A FAT32 filesystem on a block device, with long file names.
+///
+/// Changes go straight to the cache of the device, there is no journal to keep them consistent.
+pub struct Fat32FileSystem {
+    this: Weak<Fat32FileSystem>,
+    volume: SpinMutex<Volume>,
+    /// Entries of open files by where they are, so all instances of a file share one
+    open: SpinMutex<BTreeMap<Location, Weak<SpinMutex<Entry>>>>,
+}
+
+impl Fat32FileSystem {
+    /// Mounts the filesystem on `device`, which is read-only if the device is.
+    pub fn mount(
+        device: Arc<dyn IBlockDevice>,
+        clock: Arc<dyn IClock>,
+    ) -> FileSystemResult<Arc<Fat32FileSystem>> {
+        let volume = Volume::mount(device, clock)?;
+
+        Ok(Arc::new_cyclic(|this| Fat32FileSystem {
+            this: this.clone(),
+            volume: SpinMutex::new(volume),
+            open: SpinMutex::new(BTreeMap::new()),
+        }))
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.volume.lock().readonly
+    }
+
+    /// The shared entry of the file at `location`, `short` is what is on disk for it.
+    fn entry(&self, location: Location, short: ShortEntry) -> Arc<SpinMutex<Entry>> {
+        let mut open = self.open.lock();
+
+        if let Some(entry) = open.get(&location).and_then(Weak::upgrade) {
+            return entry;
+        }
+
+        let entry = Arc::new(SpinMutex::new(Entry {
+            location: Some(location),
+            short,
+            removed: false,
+            number: Entry::number_of(location),
+        }));
+
+        open.insert(location, Arc::downgrade(&entry));
+
+        entry
+    }
+
+    /// Marks the file at `location` removed, returns whether it is still open.
+    fn detach(&self, location: Location) -> bool {
+        let Some(entry) = self.open.lock().remove(&location).and_then(|e| e.upgrade()) else {
+            return false;
+        };
+
+        let mut entry = entry.lock();
+        entry.location = None;
+        entry.removed = true;
+
+        true
+    }
+
+    /// Follows a file that was renamed from `from` to `to`.
+    fn moved(&self, from: Location, to: Location, short: &ShortEntry) {
+        let mut open = self.open.lock();
+
+        let Some(entry) = open.remove(&from).and_then(|e| e.upgrade()) else {
+            return;
+        };
+
+        {
+            let mut entry = entry.lock();
+            entry.location = Some(to);
+            entry.short = short.clone();
+        }
+
+        open.insert(to, Arc::downgrade(&entry));
+    }
+
+    /// Drops `entry` from the open files once its last user is gone.
+    fn forget(&self, location: Location, entry: &Arc<SpinMutex<Entry>>) {
+        let mut open = self.open.lock();
+
+        if open
+            .get(&location)
+            .is_some_and(|weak| weak.as_ptr() == Arc::as_ptr(entry))
+        {
+            open.remove(&location);
+        }
+    }
+}
+
+impl IFileSystem for Fat32FileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        let volume = self.volume.lock();
+
+        let mut short = ShortEntry::new([b' '; 11], 0, ATTR_DIRECTORY, TimeSpec::zero());
+        short.set_first_cluster(volume.geometry.root_cluster);
+
+        let entry = Arc::new(SpinMutex::new(Entry {
+            location: None,
+            short,
+            removed: false,
+            number: Entry::ROOT_NUMBER,
+        }));
+
+        Fat32Inode::new(self.this.upgrade().unwrap(), entry, "")
+    }
+
+    fn name(&self) -> &str {
+        "vfat"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.volume.lock().flush()
+    }
+}
+
+impl Drop for Fat32FileSystem {
+    fn drop(&mut self) {
+        if let Err(e) = self.volume.get_mut().flush() {
+            log::warn!("fat32: failed to flush while unmounting: {:?}", e);
+        }
+    }
+}
+
+/// Lets `mount` open FAT32 filesystems on the registered block devices.
+pub struct Fat32FileSystemType {
+    devices: Arc<BlockDevices>,
+    clock: Arc<dyn IClock>,
+}
+
+impl Fat32FileSystemType {
+    pub fn new(devices: Arc<BlockDevices>, clock: Arc<dyn IClock>) -> Arc<Fat32FileSystemType> {
+        Arc::new(Fat32FileSystemType { devices, clock })
+    }
+}
+
+impl IFileSystemType for Fat32FileSystemType {
+    fn name(&self) -> &str {
+        "vfat"
+    }
+
+    fn create(&self, source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        let device = self.devices.get(source)?;
+
+        Ok(Fat32FileSystem::mount(device, self.clock.clone())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+    use core::sync::atomic::{AtomicI64, Ordering};
+    use std::process::Command;
+
+    use block_abstractions::MemoryBlockDevice;
+    use filesystem_abstractions::{
+        DirectoryTreeNode, FileMode, FileStatistics, FileSystemError, InodeAttributes,
+    };
+    use test_utilities::block::DiskImage;
+    use timing::TimeSpec;
+
+    use super::*;
+    use crate::boot::{
+        tests::{boot_sector, fsinfo_sector},
+        UNKNOWN,
+    };
+
+    /// 64MiB, enough clusters of one sector for mkfs.vfat to agree to FAT32.
+    const SECTORS: u32 = 131072;
+
+    /// A clock that moves one second forward every time it is read.
+    struct TickingClock(AtomicI64);
+
+    impl TickingClock {
+        fn new() -> Arc<TickingClock> {
+            Arc::new(TickingClock(AtomicI64::new(1_700_000_000)))
+        }
+    }
+
+    impl IClock for TickingClock {
+        fn now(&self) -> TimeSpec {
+            TimeSpec {
+                tv_sec: self.0.fetch_add(1, Ordering::Relaxed),
+                tv_nsec: 0,
+            }
+        }
+    }
+
+    /// The reserved sectors, FATs and root directory of a fresh volume, laid out like
+    /// mkfs.vfat does with one sector per cluster.
+    fn formatted(sectors: u32) -> Vec<u8> {
+        let fat_sectors = (1..)
+            .find(|&fat: &u32| (sectors - 32 - 2 * fat + 2) * 4 <= fat * 512)
+            .unwrap();
+        let clusters = sectors - 32 - 2 * fat_sectors;
+
+        let mut image = vec![0; (32 + 2 * fat_sectors as usize + 1) * 512];
+
+        let boot = boot_sector(sectors, 1, fat_sectors);
+        image[..512].copy_from_slice(&boot);
+        image[6 * 512..7 * 512].copy_from_slice(&boot);
+        image[512..1024].copy_from_slice(&fsinfo_sector(clusters - 1, 3));
+
+        for fat in 0..2 {
+            let start = (32 + fat * fat_sectors as usize) * 512;
+
+            for (i, entry) in [0x0fff_fff8u32, 0x0fff_ffff, 0x0fff_ffff]
+                .iter()
+                .enumerate()
+            {
+                image[start + i * 4..start + i * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+            }
+        }
+
+        image
+    }
+
+    /// An image a scenario runs on.
+    struct Volume {
+        image: Arc<DiskImage>,
+        /// Whether fsck.fat checks the image along the way
+        host: bool,
+    }
+
+    impl Volume {
+        /// A fresh image laid out like mkfs.vfat does, without the host's help.
+        fn blank() -> Volume {
+            let image = DiskImage::temporary(512, SECTORS as u64).unwrap();
+            image.write_blocks(0, &formatted(SECTORS)).unwrap();
+
+            Volume { image, host: false }
+        }
+
+        /// A fresh image formatted by the host's mkfs.vfat.
+        fn mkfs() -> Volume {
+            let image = DiskImage::temporary(512, SECTORS as u64).unwrap();
+
+            let status = Command::new("mkfs.vfat")
+                .args(["-F", "32", "-s", "1"])
+                .arg(image.path().unwrap())
+                .output()
+                .expect("mkfs.vfat is missing, install dosfstools")
+                .status;
+
+            assert!(status.success(), "mkfs.vfat failed");
+
+            Volume { image, host: true }
+        }
+
+        /// Checks the image with the host's fsck.fat, if it came from mkfs.vfat.
+        fn check_host(&self) {
+            if !self.host {
+                return;
+            }
+
+            let output = Command::new("fsck.fat")
+                .arg("-n")
+                .arg(self.image.path().unwrap())
+                .output()
+                .expect("fsck.fat is missing, install dosfstools");
+
+            assert!(
+                output.status.success(),
+                "fsck.fat: {}",
+                String::from_utf8_lossy(&output.stdout)
+            );
+        }
+    }
+
+    /// Runs every scenario on an image made without the host's help, and once more on one
+    /// made and checked by the host's dosfstools, which are not installed everywhere.
+    macro_rules! scenarios {
+        ($($scenario:ident),* $(,)?) => {
+            mod blank {
+                $(
+                    #[test]
+                    fn $scenario() {
+                        super::$scenario(super::Volume::blank());
+                    }
+                )*
+            }
+
+            mod dosfstools {
+                $(
+                    #[test]
+                    #[ignore = "needs dosfstools"]
+                    fn $scenario() {
+                        super::$scenario(super::Volume::mkfs());
+                    }
+                )*
+            }
+        };
+    }
+
+    scenarios!(
+        files_roundtrip,
+        long_names,
+        rename_and_remove,
+        directory_growth,
+        timestamps,
+        attributes,
+        unsupported,
+    );
+
+    /// Checks that every used cluster belongs to exactly one file and that chains fit sizes.
+    fn check(fs: &Fat32FileSystem) {
+        let volume = fs.volume.lock();
+        let cluster_size = volume.geometry.cluster_size() as u64;
+
+        let mut owned = BTreeSet::new();
+        let mut claim = |chain: Vec<u32>| {
+            for cluster in chain {
+                assert!(owned.insert(cluster), "cluster {} is used twice", cluster);
+            }
+        };
+
+        let mut directories = vec![volume.geometry.root_cluster];
+
+        while let Some(directory) = directories.pop() {
+            claim(volume.chain(directory).unwrap());
+
+            for entry in volume.list(directory).unwrap() {
+                let first = entry.short.first_cluster();
+
+                match entry.short.is_dir() {
+                    true => directories.push(first),
+                    false => {
+                        let chain = volume.chain(first).unwrap();
+                        assert_eq!(
+                            chain.len() as u64,
+                            (entry.short.size() as u64).div_ceil(cluster_size),
+                            "{}",
+                            entry.name
+                        );
+                        claim(chain);
+                    }
+                }
+            }
+        }
+
+        let used = volume.used_clusters().unwrap();
+        assert_eq!(used, owned.into_iter().collect::<Vec<_>>());
+
+        if volume.free_count() != UNKNOWN {
+            assert_eq!(
+                volume.free_count() as usize,
+                volume.geometry.cluster_count as usize - used.len()
+            );
+        }
+    }
+
+    fn mount(device: Arc<dyn IBlockDevice>) -> Arc<Fat32FileSystem> {
+        Fat32FileSystem::mount(device, TickingClock::new()).unwrap()
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+    }
+
+    fn read_all(inode: &Arc<dyn IInode>) -> Vec<u8> {
+        let mut buffer = vec![0; inode.metadata().size];
+        assert_eq!(inode.readat(0, &mut buffer).unwrap(), buffer.len());
+
+        buffer
+    }
+
+    #[allow(deprecated)]
+    fn open(root: &Arc<dyn IInode>, path: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let mut current = root.clone();
+
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            current = current.lookup(part)?;
+        }
+
+        Ok(current)
+    }
+
+    fn names(dir: &Arc<dyn IInode>) -> Vec<String> {
+        let mut names: Vec<String> = dir
+            .read_cache_dir(&mut BTreeMap::new())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.filename)
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    fn files_roundtrip(volume: Volume) {
+        let image = &volume.image;
+        let data = pattern(100 * 1024);
+
+        {
+            let fs = mount(image.clone());
+            let root = fs.root_dir();
+
+            let docs = root.mkdir("docs").unwrap();
+            let nested = docs.mkdir("nested").unwrap();
+
+            let file = nested.touch("data.bin").unwrap();
+            assert_eq!(file.writeat(0, &data).unwrap(), data.len());
+
+            // Writing past the end zero fills the gap
+            let gap = root.touch("gap").unwrap();
+            gap.writeat(3000, b"tail").unwrap();
+            gap.writeat(0, b"head").unwrap();
+
+            let shrunk = root.touch("shrunk").unwrap();
+            shrunk.writeat(0, &data).unwrap();
+            shrunk.resize(1000).unwrap();
+            shrunk.resize(1500).unwrap();
+
+            assert_eq!(
+                root.mkdir("docs").err(),
+                Some(FileSystemError::AlreadyExists)
+            );
+            assert_eq!(
+                root.readat(0, &mut [0; 4]).err(),
+                Some(FileSystemError::NotAFile)
+            );
+            #[allow(deprecated)]
+            let lookup = file.lookup("x");
+            assert_eq!(lookup.err(), Some(FileSystemError::NotADirectory));
+
+            check(&fs);
+            fs.flush().unwrap();
+        }
+
+        volume.check_host();
+
+        let fs = mount(image.clone());
+        let root = fs.root_dir();
+
+        assert_eq!(
+            read_all(&open(&root, "docs/nested/data.bin").unwrap()),
+            data
+        );
+
+        let gap = read_all(&open(&root, "gap").unwrap());
+        assert_eq!(gap.len(), 3004);
+        assert_eq!(&gap[..4], b"head");
+        assert!(gap[4..3000].iter().all(|&b| b == 0));
+
+        let mut shrunk = data[..1000].to_vec();
+        shrunk.resize(1500, 0);
+        assert_eq!(read_all(&open(&root, "shrunk").unwrap()), shrunk);
+
+        assert_eq!(names(&root), ["docs", "gap", "shrunk"]);
+        assert_eq!(names(&open(&root, "docs").unwrap()), ["nested"]);
+
+        let tree = DirectoryTreeNode::from_filesystem(None, fs.clone(), None);
+        let node = tree.open("/docs/nested/data.bin", Some(&tree)).unwrap();
+        assert_eq!(node.readall().unwrap(), data);
+
+        check(&fs);
+    }
+
+    fn long_names(volume: Volume) {
+        let image = &volume.image;
+
+        let long = "x".repeat(200);
+        let created = [
+            "Long File Name.txt",
+            "readme.txt",
+            "UPPER.TXT",
+            "MixedCase",
+            "h\u{e9}llo w\u{f6}rld.txt",
+            "\u{4e2d}\u{6587}\u{540d}",
+            long.as_str(),
+        ];
+
+        {
+            let fs = mount(image.clone());
+            let root = fs.root_dir();
+
+            for name in created {
+                root.touch(name)
+                    .unwrap()
+                    .writeat(0, name.as_bytes())
+                    .unwrap();
+            }
+
+            // Names that only differ in case are the same
+            assert_eq!(
+                root.touch("README.TXT").err(),
+                Some(FileSystemError::AlreadyExists)
+            );
+            assert_eq!(
+                root.touch("LONG FILE NAME.TXT").err(),
+                Some(FileSystemError::AlreadyExists)
+            );
+
+            // Names sharing a prefix get distinct short names
+            for i in 0..20 {
+                root.touch(&format!("Document number {}.txt", i)).unwrap();
+            }
+
+            assert_eq!(
+                root.touch("bad?name").err(),
+                Some(FileSystemError::PathContainsInvalidCharacter)
+            );
+            assert_eq!(
+                root.touch(&"y".repeat(256)).err(),
+                Some(FileSystemError::PathNameLengthExceeded)
+            );
+
+            check(&fs);
+        }
+
+        volume.check_host();
+
+        let fs = mount(image.clone());
+        let root = fs.root_dir();
+
+        let listed = names(&root);
+        assert_eq!(listed.len(), created.len() + 20);
+
+        for name in created {
+            assert!(listed.iter().any(|l| l == name), "{} is missing", name);
+            assert_eq!(read_all(&open(&root, name).unwrap()), name.as_bytes());
+        }
+
+        assert_eq!(
+            read_all(&open(&root, "long file name.TXT").unwrap()),
+            b"Long File Name.txt"
+        );
+
+        // The generated short names work too
+        assert!(open(&root, "DOCUME~1.TXT").is_ok());
+        assert!(open(&root, "LONGFI~1.TXT").is_ok());
+    }
+
+    fn rename_and_remove(volume: Volume) {
+        let image = &volume.image;
+        let fs = mount(image.clone());
+        let root = fs.root_dir();
+
+        root.touch("a.txt").unwrap().writeat(0, b"a").unwrap();
+        root.touch("b.txt")
+            .unwrap()
+            .writeat(0, &pattern(5000))
+            .unwrap();
+
+        // Replacing frees the clusters of the file that was there
+        root.rename("a.txt", "b.txt").unwrap();
+        assert_eq!(read_all(&open(&root, "b.txt").unwrap()), b"a");
+        assert_eq!(open(&root, "a.txt").err(), Some(FileSystemError::NotFound));
+        check(&fs);
+
+        // Changing only the case
+        root.rename("b.txt", "B.TXT").unwrap();
+        assert_eq!(names(&root), ["B.TXT"]);
+
+        let dir = root.mkdir("dir").unwrap();
+        dir.touch("inside").unwrap().writeat(0, b"inside").unwrap();
+
+        assert_eq!(
+            root.rmdir("dir").err(),
+            Some(FileSystemError::DirectoryNotEmpty)
+        );
+        assert_eq!(root.remove("dir").err(), Some(FileSystemError::NotAFile));
+        assert_eq!(
+            root.rmdir("B.TXT").err(),
+            Some(FileSystemError::NotADirectory)
+        );
+        assert_eq!(
+            root.rename("B.TXT", "dir").err(),
+            Some(FileSystemError::NotAFile)
+        );
+
+        // Open instances follow a rename
+        let inside = open(&root, "dir/inside").unwrap();
+        root.rename("dir", "A much longer directory name").unwrap();
+        dir.rename("inside", "moved").unwrap();
+        inside.writeat(6, b" still").unwrap();
+
+        assert_eq!(
+            read_all(&open(&root, "a much longer directory name/moved").unwrap()),
+            b"inside still"
+        );
+
+        // A removed file stays readable until the last user lets go
+        let data = pattern(20 * 1024);
+        let doomed = root.touch("doomed").unwrap();
+        doomed.writeat(0, &data).unwrap();
+
+        root.remove("doomed").unwrap();
+        assert_eq!(open(&root, "doomed").err(), Some(FileSystemError::NotFound));
+        assert_eq!(read_all(&doomed), data);
+
+        let used = fs.volume.lock().used_clusters().unwrap().len();
+        drop(doomed);
+        assert_eq!(
+            fs.volume.lock().used_clusters().unwrap().len(),
+            used - data.len().div_ceil(512)
+        );
+
+        drop(inside);
+        dir.remove("moved").unwrap();
+        root.rmdir("A much longer directory name").unwrap();
+
+        assert_eq!(dir.touch("orphan").err(), Some(FileSystemError::NotFound));
+        drop(dir);
+
+        check(&fs);
+        drop(root);
+        drop(fs);
+
+        volume.check_host();
+    }
+
+    fn directory_growth(volume: Volume) {
+        let image = &volume.image;
+        let fs = mount(image.clone());
+        let dir = fs.root_dir().mkdir("many").unwrap();
+
+        // 16 entries fit a cluster, a long name takes a few of them
+        for i in 0..300 {
+            dir.touch(&format!("a rather long name {}", i)).unwrap();
+        }
+
+        for i in (0..300).step_by(2) {
+            dir.remove(&format!("a rather long name {}", i)).unwrap();
+        }
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        open(&fs.root_dir(), "many")
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+        let size = stat.size;
+
+        // Freed entries are reused before the directory grows
+        for i in 0..150 {
+            dir.touch(&format!("another long name {}", i)).unwrap();
+        }
+
+        open(&fs.root_dir(), "many")
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+        assert_eq!(stat.size, size);
+
+        assert_eq!(names(&dir).len(), 300);
+        assert!(open(&dir, "a rather long name 299").is_ok());
+        assert!(open(&dir, "a rather long name 298").is_err());
+
+        check(&fs);
+        drop(dir);
+        drop(fs);
+
+        volume.check_host();
+    }
+
+    fn timestamps(volume: Volume) {
+        let image = &volume.image;
+        let fs = mount(image.clone());
+        let root = fs.root_dir();
+
+        let file = root.touch("file").unwrap();
+
+        let mut created: FileStatistics = unsafe { core::mem::zeroed() };
+        file.stat(&mut created).unwrap();
+
+        assert!(created.ctime.tv_sec >= 1_700_000_000);
+        assert_eq!(created.mtime.tv_sec, created.ctime.tv_sec & !1);
+        assert_eq!(
+            created.atime.tv_sec,
+            created.ctime.tv_sec - created.ctime.tv_sec % 86400
+        );
+        assert_eq!(created.mode.bits(), 0o100755);
+        assert_eq!(created.size, 0);
+
+        for _ in 0..10 {
+            file.writeat(0, b"later").unwrap();
+        }
+
+        let mut written: FileStatistics = unsafe { core::mem::zeroed() };
+        file.stat(&mut written).unwrap();
+
+        assert_eq!(written.ctime, created.ctime);
+        assert!(written.mtime.tv_sec > created.mtime.tv_sec);
+        assert_eq!(written.size, 5);
+        assert_eq!(written.block_size, 512);
+        assert_eq!(written.block_count, 1);
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        root.stat(&mut stat).unwrap();
+        assert_eq!(stat.mode.bits(), 0o040755);
+        assert_eq!(stat.inode_id, 1);
+    }
+
+    fn attributes(volume: Volume) {
+        let image = &volume.image;
+        // 2023-11-14 22:13:20, FAT keeps times in steps of two seconds
+        let mtime = TimeSpec {
+            tv_sec: 1_700_000_000,
+            tv_nsec: 0,
+        };
+
+        {
+            let fs = mount(image.clone());
+            let file = fs.root_dir().touch("file").unwrap();
+
+            file.set_attributes(&InodeAttributes {
+                mode: Some(FileMode::from_bits_truncate(0o444)),
+                atime: Some(mtime),
+                mtime: Some(mtime),
+                ..Default::default()
+            })
+            .unwrap();
+
+            assert_eq!(
+                file.set_attributes(&InodeAttributes {
+                    uid: Some(1000),
+                    ..Default::default()
+                })
+                .err(),
+                Some(FileSystemError::NotPermitted)
+            );
+        }
+
+        volume.check_host();
+
+        let fs = mount(image.clone());
+        let file = open(&fs.root_dir(), "file").unwrap();
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        file.stat(&mut stat).unwrap();
+        assert_eq!(stat.mode.bits(), 0o100555);
+        assert_eq!(stat.mtime, mtime);
+        assert_eq!(stat.atime.tv_sec, mtime.tv_sec - mtime.tv_sec % 86400);
+
+        file.set_attributes(&InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o600)),
+            ..Default::default()
+        })
+        .unwrap();
+        file.stat(&mut stat).unwrap();
+        assert_eq!(stat.mode.bits(), 0o100755);
+    }
+
+    #[test]
+    fn test_running_out_of_space() {
+        // 2MiB, too small for mkfs.vfat to make FAT32 of
+        let mut image = formatted(4096);
+        image.resize(4096 * 512, 0);
+
+        let device = Arc::new(MemoryBlockDevice::from_image(512, image).unwrap());
+        let fs = mount(device);
+        let root = fs.root_dir();
+
+        let file = root.touch("huge").unwrap();
+        let chunk = pattern(64 * 1024);
+        let mut offset = 0;
+
+        let error = loop {
+            match file.writeat(offset, &chunk) {
+                Ok(written) => offset += written,
+                Err(e) => break e,
+            }
+        };
+
+        assert_eq!(error, FileSystemError::SpaceNotEnough);
+        assert_eq!(file.metadata().size, offset);
+        check(&fs);
+
+        drop(file);
+        root.remove("huge").unwrap();
+        root.touch("after").unwrap().writeat(0, &chunk).unwrap();
+
+        check(&fs);
+    }
+
+    fn unsupported(volume: Volume) {
+        let image = &volume.image;
+
+        {
+            let fs = mount(image.clone());
+            let root = fs.root_dir();
+            let file = root.touch("file").unwrap();
+
+            assert_eq!(
+                root.hard_link("link", &file).err(),
+                Some(FileSystemError::NotPermitted)
+            );
+            assert_eq!(
+                root.soft_link("link", "file").err(),
+                Some(FileSystemError::NotPermitted)
+            );
+            assert!(file.resolve_link().is_none());
+        }
+
+        let readonly = DiskImage::open(image.path().unwrap(), 512, true).unwrap();
+        let fs = Fat32FileSystem::mount(readonly, TickingClock::new()).unwrap();
+
+        assert!(fs.is_readonly());
+        assert_eq!(
+            fs.root_dir().touch("new").err(),
+            Some(FileSystemError::ReadOnly)
+        );
+        assert_eq!(
+            open(&fs.root_dir(), "file").unwrap().writeat(0, b"x").err(),
+            Some(FileSystemError::ReadOnly)
+        );
+
+        let mut sector = vec![0; 512];
+        image.read_blocks(0, &mut sector).unwrap();
+        sector[22] = 1;
+
+        let fat16 = MemoryBlockDevice::from_image(512, sector.repeat(64)).unwrap();
+        assert_eq!(
+            Fat32FileSystem::mount(Arc::new(fat16), TickingClock::new()).err(),
+            Some(FileSystemError::Unimplemented)
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Short 8.3 names and the long names (VFAT) kept in entries in front of them.
+
+use alloc::string::String;
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+pub(crate) const SHORT_NAME_LEN: usize = 11;
+/// UTF-16 units a long name can have.
+pub(crate) const LONG_NAME_MAX: usize = 255;
+
+/// Bits of the reserved byte of an entry telling its short name is displayed in lowercase.
+pub(crate) const CASE_LOWER_BASE: u8 = 0x08;
+pub(crate) const CASE_LOWER_EXTENSION: u8 = 0x10;
+
+/// First byte of names starting with 0xe5, which marks deleted entries otherwise.
+const ESCAPED_E5: u8 = 0x05;
+
+pub(crate) fn check_name(name: &str) -> FileSystemResult<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    if name.encode_utf16().count() > LONG_NAME_MAX {
+        return Err(FileSystemError::PathNameLengthExceeded);
+    }
+
+    if name
+        .chars()
+        .any(|c| c < ' ' || matches!(c, '"' | '*' | '/' | ':' | '<' | '>' | '?' | '\\' | '|'))
+    {
+        return Err(FileSystemError::PathContainsInvalidCharacter);
+    }
+
+    // Windows drops these, two names differing only by them would be the same file there
+    if name.ends_with(['.', ' ']) {
+        return Err(FileSystemError::PathContainsInvalidCharacter);
+    }
+
+    Ok(())
+}
+
+fn is_short_char(c: char) -> bool {
+    c.is_ascii_uppercase()
+        || c.is_ascii_digit()
+        || matches!(
+            c,
+            '$' | '%'
+                | '\''
+                | '-'
+                | '_'
+                | '@'
+                | '~'
+                | '`'
+                | '!'
+                | '('
+                | ')'
+                | '{'
+                | '}'
+                | '^'
+                | '#'
+                | '&'
+        )
+}
+
+/// Checks that a part of a name fits a short name, returning whether it is in lowercase.
+fn short_part(part: &str, max: usize) -> Option<bool> {
+    if part.len() > max {
+        return None;
+    }
+
+    let lower = part.chars().any(|c| c.is_ascii_lowercase());
+    let upper = part.chars().any(|c| c.is_ascii_uppercase());
+
+    let valid = part.chars().all(|c| is_short_char(c.to_ascii_uppercase()));
+
+    (valid && !(lower && upper)).then_some(lower)
+}
+
+/// The short name and case flags of `name` if it needs no long name.
+pub(crate) fn as_short_name(name: &str) -> Option<([u8; SHORT_NAME_LEN], u8)> {
+    let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+
+    if base.is_empty() || extension.contains('.') {
+        return None;
+    }
+
+    let lower_base = short_part(base, 8)?;
+    let lower_extension = short_part(extension, 3)?;
+
+    let mut raw = [b' '; SHORT_NAME_LEN];
+    raw[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    raw[8..8 + extension.len()].copy_from_slice(extension.to_ascii_uppercase().as_bytes());
+
+    let mut case = 0;
+
+    if lower_base {
+        case |= CASE_LOWER_BASE;
+    }
+
+    if lower_extension {
+        case |= CASE_LOWER_EXTENSION;
+    }
+
+    Some((raw, case))
+}
+
+/// Uppercases a character of a long name for its short name, replacing what does not fit.
+fn to_short_char(c: char) -> char {
+    match c.to_ascii_uppercase() {
+        c if is_short_char(c) => c,
+        _ => '_',
+    }
+}
+
+/// The short name made of `name` with the numeric tail `~number`, for names that need a long
+/// name.
+pub(crate) fn numbered_short_name(name: &str, number: u32) -> [u8; SHORT_NAME_LEN] {
+    let name = name.trim_start_matches('.');
+
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+
+    let keep = |part: &str| -> String {
+        part.chars()
+            .filter(|c| !matches!(c, ' ' | '.'))
+            .map(to_short_char)
+            .collect()
+    };
+
+    let base = keep(base);
+    let extension = keep(extension);
+
+    let mut tail = [0u8; 11];
+    let tail = format_tail(number, &mut tail);
+
+    let mut raw = [b' '; SHORT_NAME_LEN];
+    let room = 8 - tail.len();
+
+    let mut len = 0;
+    for c in base.chars().take(room) {
+        raw[len] = c as u8;
+        len += 1;
+    }
+
+    if len == 0 {
+        raw[0] = b'_';
+        len = 1;
+    }
+
+    raw[len..len + tail.len()].copy_from_slice(tail);
+
+    for (i, c) in extension.chars().take(3).enumerate() {
+        raw[8 + i] = c as u8;
+    }
+
+    raw
+}
+
+/// Writes `~number` into `buffer`, returning the written part.
+fn format_tail(number: u32, buffer: &mut [u8; 11]) -> &[u8] {
+    let mut digits = 1;
+    while 10u32.pow(digits) <= number {
+        digits += 1;
+    }
+
+    let digits = digits as usize;
+    buffer[0] = b'~';
+
+    let mut rest = number;
+    for i in (1..=digits).rev() {
+        buffer[i] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+    }
+
+    &buffer[..digits + 1]
+}
+
+/// Whether a short name is one of the `.` and `..` entries of subdirectories.
+pub(crate) fn is_dot_entry(raw: &[u8; SHORT_NAME_LEN]) -> bool {
+    raw == b".          " || raw == b"..         "
+}
+
+/// How a short name is shown when there is no long name for it.
+pub(crate) fn display_short_name(raw: &[u8; SHORT_NAME_LEN], case: u8) -> String {
+    let part = |bytes: &[u8], lower: bool| -> String {
+        let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+
+        bytes[..len]
+            .iter()
+            .map(|&b| match lower {
+                true => b.to_ascii_lowercase() as char,
+                // Bytes past ASCII are in an OEM code page, Latin-1 is close enough for display
+                false => b as char,
+            })
+            .collect()
+    };
+
+    let mut base = [0; 8];
+    base.copy_from_slice(&raw[..8]);
+
+    if base[0] == ESCAPED_E5 {
+        base[0] = 0xe5;
+    }
+
+    let mut name = part(&base, case & CASE_LOWER_BASE != 0);
+    let extension = part(&raw[8..], case & CASE_LOWER_EXTENSION != 0);
+
+    if !extension.is_empty() {
+        name.push('.');
+        name.push_str(&extension);
+    }
+
+    name
+}
+
+/// The checksum of a short name each of its long name entries carries.
+pub(crate) fn checksum(raw: &[u8; SHORT_NAME_LEN]) -> u8 {
+    raw.iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Whether two names refer to the same entry, names are case insensitive.
+pub(crate) fn names_equal(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("Long name with spaces.tar.gz").is_ok());
+        assert_eq!(check_name(".."), Err(FileSystemError::InvalidInput));
+        assert_eq!(
+            check_name("a:b"),
+            Err(FileSystemError::PathContainsInvalidCharacter)
+        );
+        assert_eq!(
+            check_name("trailing."),
+            Err(FileSystemError::PathContainsInvalidCharacter)
+        );
+        assert_eq!(
+            check_name(&"x".repeat(256)),
+            Err(FileSystemError::PathNameLengthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_short_names() {
+        assert_eq!(as_short_name("README.TXT"), Some((*b"README  TXT", 0)));
+        assert_eq!(
+            as_short_name("readme.txt"),
+            Some((*b"README  TXT", CASE_LOWER_BASE | CASE_LOWER_EXTENSION))
+        );
+        assert_eq!(
+            as_short_name("Makefile"),
+            None,
+            "mixed case needs a long name"
+        );
+        assert_eq!(as_short_name("LONGERNAME"), None);
+        assert_eq!(as_short_name("a.b.c"), None);
+        assert_eq!(as_short_name(".hidden"), None);
+        assert_eq!(as_short_name("a+b"), None);
+
+        assert_eq!(
+            display_short_name(b"README  TXT", CASE_LOWER_BASE),
+            "readme.TXT"
+        );
+        assert_eq!(display_short_name(b"NOEXT      ", 0), "NOEXT");
+        assert_eq!(display_short_name(b"\x05BC        ", 0), "\u{e5}BC");
+    }
+
+    #[test]
+    fn test_numbered_short_names() {
+        assert_eq!(&numbered_short_name("Makefile", 1), b"MAKEFI~1   ");
+        assert_eq!(&numbered_short_name("archive.tar.gz", 2), b"ARCHIV~2GZ ");
+        assert_eq!(&numbered_short_name(".bashrc", 1), b"BASHRC~1   ");
+        assert_eq!(&numbered_short_name("a b+c.txt", 12), b"AB_C~12 TXT");
+        assert_eq!(
+            &numbered_short_name("\u{4e2d}\u{6587}", 123456),
+            b"_~123456   "
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(b"MAKEFI~1   "), 0x62);
+        assert!(names_equal("Makefile", "MAKEFILE"));
+        assert!(!names_equal("Makefile", "Makefile2"));
+    }
+}
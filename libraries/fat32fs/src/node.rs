@@ -0,0 +1,442 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IInode, InodeAttributes, InodeMetadata,
+};
+use hermit_sync::SpinMutex;
+
+use crate::{
+    dir::{DirEntry, Location, ShortEntry, ATTR_ARCHIVE, ATTR_DIRECTORY, ATTR_READ_ONLY},
+    name::check_name,
+    volume::Volume,
+    Fat32FileSystem,
+};
+
+/// What all open instances of a file share, FAT keeps no inodes so this stands in for one.
+pub(crate) struct Entry {
+    /// Where the short entry is, `None` for the root directory and once removed
+    pub location: Option<Location>,
+    pub short: ShortEntry,
+    /// Removed while open, the clusters are freed by the last user
+    pub removed: bool,
+    /// Stays the same when the file is renamed
+    pub number: u64,
+}
+
+impl Entry {
+    /// Inode number of the root directory, like Linux has.
+    pub const ROOT_NUMBER: u64 = 1;
+
+    pub fn number_of(location: Location) -> u64 {
+        (location.directory as u64) << 16 | location.slot as u64
+    }
+
+    fn entry_type(&self) -> DirectoryEntryType {
+        match self.short.is_dir() {
+            true => DirectoryEntryType::Directory,
+            false => DirectoryEntryType::File,
+        }
+    }
+
+    /// The first cluster of the directory, for listing it or changing its entries.
+    fn directory(&self) -> FileSystemResult<u32> {
+        if !self.short.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        if self.removed {
+            return Err(FileSystemError::NotFound);
+        }
+
+        match self.short.first_cluster() {
+            0 => Err(FileSystemError::FileSystemCorrupted),
+            cluster => Ok(cluster),
+        }
+    }
+
+    /// Writes the short entry back after it changed.
+    fn write_back(&self, volume: &Volume) -> FileSystemResult<()> {
+        match self.location {
+            Some(location) => volume.write_short(location, &self.short),
+            None => Ok(()),
+        }
+    }
+
+    /// Records that an entry of the directory changed.
+    fn touch(&mut self, volume: &Volume) -> FileSystemResult<()> {
+        self.short.set_modified(volume.now());
+        self.write_back(volume)
+    }
+}
+
+/// A file or directory of a mounted FAT32 filesystem.
+pub struct Fat32Inode {
+    fs: Arc<Fat32FileSystem>,
+    entry: Arc<SpinMutex<Entry>>,
+    name: String,
+    entry_type: DirectoryEntryType,
+}
+
+impl Fat32Inode {
+    pub(crate) fn new(
+        fs: Arc<Fat32FileSystem>,
+        entry: Arc<SpinMutex<Entry>>,
+        name: &str,
+    ) -> Arc<Fat32Inode> {
+        let entry_type = entry.lock().entry_type();
+
+        Arc::new(Fat32Inode {
+            fs,
+            entry,
+            name: name.to_string(),
+            entry_type,
+        })
+    }
+
+    fn create(&self, name: &str, attributes: u8) -> FileSystemResult<Arc<dyn IInode>> {
+        let mut volume = self.fs.volume.lock();
+        volume.ensure_writable()?;
+        check_name(name)?;
+
+        let mut dir = self.entry.lock();
+        let directory = dir.directory()?;
+
+        if volume.find(directory, name)?.is_some() {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        let now = volume.now();
+        let mut short = ShortEntry::new([b' '; 11], 0, attributes, now);
+
+        let cluster = match attributes & ATTR_DIRECTORY != 0 {
+            true => {
+                let cluster = volume.allocate(1, None)?[0];
+
+                let initialized = volume
+                    .write_cluster(cluster, 0, &vec![0; volume.geometry.cluster_size()])
+                    .and_then(|_| volume.init_dir(cluster, directory, now));
+
+                if let Err(e) = initialized {
+                    volume.free(&[cluster])?;
+                    return Err(e);
+                }
+
+                short.set_first_cluster(cluster);
+                Some(cluster)
+            }
+            false => None,
+        };
+
+        let location = match volume.insert(directory, name, &mut short) {
+            Ok(location) => location,
+            Err(e) => {
+                if let Some(cluster) = cluster {
+                    volume.free(&[cluster])?;
+                }
+
+                return Err(e);
+            }
+        };
+
+        dir.touch(&volume)?;
+
+        let entry = self.fs.entry(location, short);
+
+        Ok(Fat32Inode::new(self.fs.clone(), entry, name))
+    }
+
+    /// Frees the clusters of a removed file, unless it is still open.
+    fn release(&self, volume: &mut Volume, found: &DirEntry) -> FileSystemResult<()> {
+        match self.fs.detach(found.location) {
+            true => Ok(()),
+            false => volume.free_chain(found.short.first_cluster()),
+        }
+    }
+
+    fn unlink(&self, name: &str, directory_wanted: bool) -> FileSystemResult<()> {
+        let mut volume = self.fs.volume.lock();
+        volume.ensure_writable()?;
+
+        let mut dir = self.entry.lock();
+        let directory = dir.directory()?;
+
+        let found = volume
+            .find(directory, name)?
+            .ok_or(FileSystemError::NotFound)?;
+
+        match (directory_wanted, found.short.is_dir()) {
+            (false, true) => return Err(FileSystemError::NotAFile),
+            (true, false) => return Err(FileSystemError::NotADirectory),
+            (true, true) if !volume.is_empty_dir(found.short.first_cluster())? => {
+                return Err(FileSystemError::DirectoryNotEmpty)
+            }
+            _ => (),
+        }
+
+        volume.remove(&found)?;
+        self.release(&mut volume, &found)?;
+
+        dir.touch(&volume)
+    }
+}
+
+impl IInode for Fat32Inode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry_type,
+            size: self.entry.lock().short.size() as usize,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let volume = self.fs.volume.lock();
+        let entry = self.entry.lock();
+
+        if entry.short.is_dir() {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        volume.read_contents(&entry.short, offset as u64, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        let mut volume = self.fs.volume.lock();
+        let mut entry = self.entry.lock();
+
+        if entry.short.is_dir() {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        let written = volume.write_contents(&mut entry.short, offset as u64, buffer)?;
+        entry.write_back(&volume)?;
+
+        Ok(written)
+    }
+
+    fn mkdir(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.create(name, ATTR_DIRECTORY)
+    }
+
+    fn rmdir(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, true)
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, false)
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.create(name, ATTR_ARCHIVE)
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        let volume = self.fs.volume.lock();
+        let directory = self.entry.lock().directory()?;
+
+        let entries = volume
+            .list(directory)?
+            .into_iter()
+            .map(|entry| DirectoryEntry {
+                filename: entry.name,
+                entry_type: match entry.short.is_dir() {
+                    true => DirectoryEntryType::Directory,
+                    false => DirectoryEntryType::File,
+                },
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let volume = self.fs.volume.lock();
+        let directory = self.entry.lock().directory()?;
+
+        let found = volume
+            .find(directory, name)?
+            .ok_or(FileSystemError::NotFound)?;
+
+        let entry = self.fs.entry(found.location, found.short);
+
+        Ok(Fat32Inode::new(self.fs.clone(), entry, name))
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.fs.volume.lock().flush()
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let volume = self.fs.volume.lock();
+        let entry = self.entry.lock();
+        let short = &entry.short;
+
+        let cluster_size = volume.geometry.cluster_size() as u64;
+        let clusters = volume.chain(short.first_cluster())?.len() as u64;
+
+        // Everything is rwxr-xr-x like Linux shows it with its default masks
+        let mut mode = FileStatisticsMode::from(self.entry_type).bits() | 0o755;
+
+        if short.attributes() & ATTR_READ_ONLY != 0 {
+            mode &= !0o222;
+        }
+
+        stat.device_id = 0;
+        stat.inode_id = entry.number;
+        stat.mode = FileStatisticsMode::from_bits_truncate(mode);
+        stat.link_count = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.rdev = 0;
+        stat.size = match short.is_dir() {
+            true => clusters * cluster_size,
+            false => short.size() as u64,
+        };
+        stat.block_size = cluster_size as u32;
+        stat.block_count = clusters * cluster_size / 512;
+        stat.atime = short.accessed();
+        stat.mtime = short.modified();
+        stat.ctime = short.created();
+
+        Ok(())
+    }
+
+    /// Only the read-only attribute and the times can be kept, a mode without any write
+    /// permission makes the entry read-only. There are no owners to change.
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        let volume = self.fs.volume.lock();
+        volume.ensure_writable()?;
+
+        let mut entry = self.entry.lock();
+
+        if attributes.uid.is_some_and(|uid| uid != 0) || attributes.gid.is_some_and(|gid| gid != 0)
+        {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if let Some(mode) = attributes.mode {
+            let attributes = match mode.bits() & 0o222 {
+                0 => entry.short.attributes() | ATTR_READ_ONLY,
+                _ => entry.short.attributes() & !ATTR_READ_ONLY,
+            };
+
+            entry.short.set_attributes(attributes);
+        }
+
+        if let Some(atime) = attributes.atime {
+            entry.short.set_access_time(atime);
+        }
+
+        if let Some(mtime) = attributes.mtime {
+            entry.short.set_modify_time(mtime);
+        }
+
+        entry.write_back(&volume)
+    }
+
+    fn hard_link(&self, _name: &str, _inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn soft_link(&self, _name: &str, _point_to: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+        let mut volume = self.fs.volume.lock();
+        let mut entry = self.entry.lock();
+
+        if entry.short.is_dir() {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        volume.resize_contents(&mut entry.short, new_size)?;
+        entry.write_back(&volume)?;
+
+        Ok(new_size)
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str) -> FileSystemResult<()> {
+        let mut volume = self.fs.volume.lock();
+        volume.ensure_writable()?;
+        check_name(new_name)?;
+
+        let mut dir = self.entry.lock();
+        let directory = dir.directory()?;
+
+        let source = volume
+            .find(directory, old_name)?
+            .ok_or(FileSystemError::NotFound)?;
+
+        if old_name == new_name {
+            return Ok(());
+        }
+
+        // Names differing only in case are the same entry
+        let target = volume
+            .find(directory, new_name)?
+            .filter(|target| target.location != source.location);
+
+        if let Some(target) = &target {
+            match (source.short.is_dir(), target.short.is_dir()) {
+                (true, false) => return Err(FileSystemError::NotADirectory),
+                (false, true) => return Err(FileSystemError::NotAFile),
+                (true, true) if !volume.is_empty_dir(target.short.first_cluster())? => {
+                    return Err(FileSystemError::DirectoryNotEmpty)
+                }
+                _ => (),
+            }
+        }
+
+        // The new entries go in first, so running out of room changes nothing
+        let mut short = source.short.clone();
+        let location = volume.insert(directory, new_name, &mut short)?;
+
+        if let Some(target) = &target {
+            volume.remove(target)?;
+            self.release(&mut volume, target)?;
+        }
+
+        volume.remove(&source)?;
+        self.fs.moved(source.location, location, &short);
+
+        dir.touch(&volume)
+    }
+}
+
+impl Drop for Fat32Inode {
+    fn drop(&mut self) {
+        let mut volume = self.fs.volume.lock();
+
+        // Others are still using the file
+        if Arc::strong_count(&self.entry) > 1 {
+            return;
+        }
+
+        let entry = self.entry.lock();
+
+        if let Some(location) = entry.location {
+            self.fs.forget(location, &self.entry);
+        }
+
+        if !entry.removed || volume.readonly {
+            return;
+        }
+
+        if let Err(e) = volume.free_chain(entry.short.first_cluster()) {
+            log::warn!(
+                "fat32: failed to free the clusters of a removed file: {:?}",
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Timestamps of directory entries. They are in local time with a two second resolution, this
+//! driver takes them as UTC like Linux does without a `tz` mount option.
+
+use timing::TimeSpec;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Earliest instant a FAT timestamp can hold, 1980-01-01 00:00:00.
+const FAT_MIN: i64 = 315_532_800;
+/// Latest instant a FAT timestamp can hold, 2107-12-31 23:59:58.
+const FAT_MAX: i64 = 4_354_819_198;
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = match shifted_month < 10 {
+        true => shifted_month + 3,
+        false => shifted_month - 9,
+    } as u32;
+
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (year, month, day)
+}
+
+/// Packs an instant into the date and time fields of an entry, along with the 10ms units the
+/// creation time carries on top of its two seconds.
+pub(crate) fn to_fat(time: TimeSpec) -> (u16, u16, u8) {
+    let (seconds, nanoseconds) = match time.tv_sec {
+        ..FAT_MIN => (FAT_MIN, 0),
+        FAT_MAX.. => (FAT_MAX, 0),
+        seconds => (seconds, time.tv_nsec),
+    };
+
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+
+    let date = ((year - 1980) as u16) << 9 | (month as u16) << 5 | day as u16;
+    let clock = ((of_day / 3600) as u16) << 11
+        | ((of_day / 60 % 60) as u16) << 5
+        | (of_day % 60 / 2) as u16;
+    let centiseconds = ((of_day % 2) * 100 + nanoseconds / 10_000_000) as u8;
+
+    (date, clock, centiseconds)
+}
+
+/// The instant of the date and time fields of an entry, zero if the date was never set.
+pub(crate) fn from_fat(date: u16, clock: u16, centiseconds: u8) -> TimeSpec {
+    if date == 0 {
+        return TimeSpec::zero();
+    }
+
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xf).clamp(1, 12) as u32;
+    let day = (date & 0x1f).max(1) as u32;
+
+    let of_day =
+        (clock >> 11) as i64 * 3600 + ((clock >> 5) & 0x3f) as i64 * 60 + (clock & 0x1f) as i64 * 2;
+    let centiseconds = centiseconds.min(199) as i64;
+
+    TimeSpec {
+        tv_sec: days_from_civil(year, month, day) * SECONDS_PER_DAY + of_day + centiseconds / 100,
+        tv_nsec: centiseconds % 100 * 10_000_000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(
+            civil_from_days(days_from_civil(2107, 12, 31)),
+            (2107, 12, 31)
+        );
+        assert_eq!(days_from_civil(1980, 1, 1) * SECONDS_PER_DAY, FAT_MIN);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        // 2023-11-14 22:13:21.250 UTC
+        let time = TimeSpec {
+            tv_sec: 1_700_000_001,
+            tv_nsec: 250_000_000,
+        };
+
+        let (date, clock, centiseconds) = to_fat(time);
+        assert_eq!(date, (43 << 9) | (11 << 5) | 14);
+        assert_eq!(clock, (22 << 11) | (13 << 5) | 10);
+        assert_eq!(centiseconds, 125);
+
+        assert_eq!(from_fat(date, clock, centiseconds), time);
+        assert_eq!(from_fat(date, clock, 0).tv_sec, 1_700_000_000);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(to_fat(TimeSpec::zero()), (1 << 5 | 1, 0, 0));
+        assert_eq!(from_fat(1 << 5 | 1, 0, 0).tv_sec, FAT_MIN);
+
+        let (date, clock, _) = to_fat(TimeSpec {
+            tv_sec: i64::MAX,
+            tv_nsec: 0,
+        });
+        assert_eq!(from_fat(date, clock, 0).tv_sec, FAT_MAX);
+
+        assert!(from_fat(0, 0, 0).is_zero());
+    }
+}
@@ -0,0 +1,304 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+use block_abstractions::{BlockCache, IBlockDevice};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use threading::IClock;
+use timing::TimeSpec;
+
+use crate::{
+    boot::{parse_fsinfo, update_fsinfo, Geometry, UNKNOWN},
+    raw::{le32, set_le32},
+};
+
+/// Device blocks kept by the cache under the filesystem.
+const CACHE_CAPACITY: usize = 2048;
+
+/// Bits of a FAT entry that are the cluster number, the others are reserved.
+const ENTRY_MASK: u32 = 0x0fff_ffff;
+/// Entries at or above this end a chain.
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+/// What this driver marks the end of a chain with.
+const END_MARK: u32 = 0x0fff_ffff;
+
+/// A mounted FAT32 volume.
+pub(crate) struct Volume {
+    cache: BlockCache,
+    pub geometry: Geometry,
+    pub readonly: bool,
+    clock: Arc<dyn IClock>,
+    /// Free clusters, [`UNKNOWN`] if the FSInfo sector did not know either
+    free_count: u32,
+    /// Where to start looking for a free cluster
+    next_free: u32,
+    fsinfo_dirty: bool,
+}
+
+impl Volume {
+    pub fn mount(
+        device: Arc<dyn IBlockDevice>,
+        clock: Arc<dyn IClock>,
+    ) -> FileSystemResult<Volume> {
+        let device_size = device.block_size() as u64 * device.block_count();
+        let cache = BlockCache::new(device, CACHE_CAPACITY);
+
+        let mut sector = vec![0; 512];
+        cache.read_at(0, &mut sector)?;
+
+        let geometry = Geometry::parse(&sector)?;
+
+        let end =
+            geometry.cluster_offset(geometry.cluster_count + 1) + geometry.cluster_size() as u64;
+
+        if end > device_size {
+            return Err(FileSystemError::FileSystemCorrupted);
+        }
+
+        let hints = match geometry.fsinfo_offset() {
+            Some(offset) => {
+                let mut sector = vec![0; geometry.bytes_per_sector as usize];
+                cache.read_at(offset, &mut sector)?;
+
+                parse_fsinfo(&sector)
+            }
+            None => None,
+        };
+
+        let (free_count, next_free) = hints.unwrap_or((UNKNOWN, UNKNOWN));
+
+        Ok(Volume {
+            readonly: cache.is_readonly(),
+            cache,
+            clock,
+            free_count: match free_count <= geometry.cluster_count {
+                true => free_count,
+                false => UNKNOWN,
+            },
+            next_free: match geometry.is_cluster(next_free) {
+                true => next_free,
+                false => 2,
+            },
+            geometry,
+            fsinfo_dirty: false,
+        })
+    }
+
+    pub fn now(&self) -> TimeSpec {
+        self.clock.now()
+    }
+
+    pub fn ensure_writable(&self) -> FileSystemResult<()> {
+        match self.readonly {
+            true => Err(FileSystemError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    pub fn read_cluster(
+        &self,
+        cluster: u32,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> FileSystemResult<()> {
+        debug_assert!(offset + buffer.len() <= self.geometry.cluster_size());
+
+        self.cache.read_at(
+            self.geometry.cluster_offset(cluster) + offset as u64,
+            buffer,
+        )
+    }
+
+    pub fn write_cluster(
+        &self,
+        cluster: u32,
+        offset: usize,
+        buffer: &[u8],
+    ) -> FileSystemResult<()> {
+        debug_assert!(offset + buffer.len() <= self.geometry.cluster_size());
+
+        self.cache.write_at(
+            self.geometry.cluster_offset(cluster) + offset as u64,
+            buffer,
+        )
+    }
+
+    fn fat_entry(&self, cluster: u32) -> FileSystemResult<u32> {
+        let fat = self.geometry.active_fat.unwrap_or(0);
+
+        let mut entry = [0; 4];
+        self.cache
+            .read_at(self.geometry.fat_offset(fat, cluster), &mut entry)?;
+
+        Ok(le32(&entry, 0) & ENTRY_MASK)
+    }
+
+    fn set_fat_entry(&self, cluster: u32, value: u32) -> FileSystemResult<()> {
+        for fat in self.geometry.fats() {
+            let offset = self.geometry.fat_offset(fat, cluster);
+
+            let mut entry = [0; 4];
+            self.cache.read_at(offset, &mut entry)?;
+
+            // The upper bits are reserved and kept as they are
+            let kept = le32(&entry, 0) & !ENTRY_MASK;
+            set_le32(&mut entry, 0, kept | value);
+
+            self.cache.write_at(offset, &entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// The clusters of the chain starting at `first`, empty if `first` is 0.
+    pub fn chain(&self, first: u32) -> FileSystemResult<Vec<u32>> {
+        let mut chain = Vec::new();
+
+        if first == 0 {
+            return Ok(chain);
+        }
+
+        let mut cluster = first;
+
+        loop {
+            // A chain longer than the volume has a loop
+            if !self.geometry.is_cluster(cluster)
+                || chain.len() >= self.geometry.cluster_count as usize
+            {
+                return Err(FileSystemError::FileSystemCorrupted);
+            }
+
+            chain.push(cluster);
+
+            match self.fat_entry(cluster)? {
+                next if next >= END_OF_CHAIN => return Ok(chain),
+                next => cluster = next,
+            }
+        }
+    }
+
+    /// Allocates `count` clusters and links them after `last`, the end of an existing chain. The
+    /// contents of the clusters are left as they are.
+    pub fn allocate(&mut self, count: usize, last: Option<u32>) -> FileSystemResult<Vec<u32>> {
+        self.ensure_writable()?;
+
+        if self.free_count != UNKNOWN && (self.free_count as usize) < count {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        let mut clusters = Vec::with_capacity(count);
+        let total = self.geometry.cluster_count;
+        let mut candidate = self.next_free;
+
+        for _ in 0..total {
+            if clusters.len() == count {
+                break;
+            }
+
+            if self.fat_entry(candidate)? == 0 {
+                clusters.push(candidate);
+            }
+
+            candidate = match candidate + 1 {
+                next if self.geometry.is_cluster(next) => next,
+                _ => 2,
+            };
+        }
+
+        if clusters.len() < count {
+            // The whole FAT was looked at, the hint can be corrected
+            self.free_count = clusters.len() as u32;
+            self.fsinfo_dirty = true;
+
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        for pair in clusters.windows(2) {
+            self.set_fat_entry(pair[0], pair[1])?;
+        }
+
+        if let Some(&tail) = clusters.last() {
+            self.set_fat_entry(tail, END_MARK)?;
+        }
+
+        if let (Some(last), Some(&first)) = (last, clusters.first()) {
+            self.set_fat_entry(last, first)?;
+        }
+
+        self.next_free = candidate;
+
+        if self.free_count != UNKNOWN {
+            self.free_count -= count as u32;
+        }
+
+        self.fsinfo_dirty = true;
+
+        Ok(clusters)
+    }
+
+    /// Frees `clusters`, which have to be the tail of a chain that was cut off already.
+    pub fn free(&mut self, clusters: &[u32]) -> FileSystemResult<()> {
+        self.ensure_writable()?;
+
+        for &cluster in clusters {
+            self.set_fat_entry(cluster, 0)?;
+        }
+
+        if self.free_count != UNKNOWN {
+            self.free_count += clusters.len() as u32;
+        }
+
+        self.fsinfo_dirty = true;
+
+        Ok(())
+    }
+
+    /// Ends a chain at `cluster`.
+    pub fn end_chain(&self, cluster: u32) -> FileSystemResult<()> {
+        self.set_fat_entry(cluster, END_MARK)
+    }
+
+    pub fn free_chain(&mut self, first: u32) -> FileSystemResult<()> {
+        let chain = self.chain(first)?;
+
+        self.free(&chain)
+    }
+
+    /// Clusters the FAT has as used.
+    #[cfg(test)]
+    pub fn used_clusters(&self) -> FileSystemResult<Vec<u32>> {
+        let mut used = Vec::new();
+
+        for cluster in 2..self.geometry.cluster_count + 2 {
+            if self.fat_entry(cluster)? != 0 {
+                used.push(cluster);
+            }
+        }
+
+        Ok(used)
+    }
+
+    #[cfg(test)]
+    pub fn free_count(&self) -> u32 {
+        self.free_count
+    }
+
+    pub fn flush(&mut self) -> FileSystemResult<()> {
+        if self.readonly {
+            return Ok(());
+        }
+
+        if self.fsinfo_dirty {
+            if let Some(offset) = self.geometry.fsinfo_offset() {
+                let mut sector = vec![0; self.geometry.bytes_per_sector as usize];
+                self.cache.read_at(offset, &mut sector)?;
+
+                if parse_fsinfo(&sector).is_some() {
+                    update_fsinfo(&mut sector, self.free_count, self.next_free);
+                    self.cache.write_at(offset, &sector)?;
+                }
+            }
+
+            self.fsinfo_dirty = false;
+        }
+
+        self.cache.flush()
+    }
+}
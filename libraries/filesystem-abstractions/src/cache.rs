@@ -0,0 +1,1001 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use constants::PAGE_SIZE;
+use core::{
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+use hermit_sync::SpinMutex;
+
+use crate::{FileSystemError, FileSystemResult, IInode};
+
+/// Where the page cache gets its pages from.
+///
+/// Pages are whole physical frames, so the same page that backs `read` and `write` can be mapped
+/// into a process by `mmap`.
+pub trait IPageAllocator: Send + Sync {
+    /// Allocates one page, returns its physical address and where the kernel can access it.
+    fn allocate(&self) -> Option<(usize, NonNull<u8>)>;
+
+    /// Gives back a page returned by [`IPageAllocator::allocate`].
+    ///
+    /// # Safety
+    ///
+    /// The page must not be accessed or mapped anywhere anymore.
+    unsafe fn deallocate(&self, physical: usize, page: NonNull<u8>);
+}
+
+/// Identifies a file in the page cache and the lock manager, the same for every path and hard
+/// link leading to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileKey {
+    /// Tells apart the filesystems the cache is shared by
+    pub mount: usize,
+    pub inode: u64,
+}
+
+/// A page of a file's contents.
+pub struct CachedPage {
+    physical: usize,
+    data: NonNull<u8>,
+    allocator: Arc<dyn IPageAllocator>,
+    // Serializes copies from the kernel, mappings in processes write to the page directly
+    lock: SpinMutex<()>,
+    dirty: AtomicBool,
+    writable_mappings: AtomicUsize,
+    last_used: AtomicU64,
+}
+
+unsafe impl Send for CachedPage {}
+unsafe impl Sync for CachedPage {}
+
+impl CachedPage {
+    fn allocate(allocator: &Arc<dyn IPageAllocator>) -> Option<Arc<CachedPage>> {
+        let (physical, data) = allocator.allocate()?;
+        unsafe { data.write_bytes(0, PAGE_SIZE) };
+
+        Some(Arc::new(CachedPage {
+            physical,
+            data,
+            allocator: allocator.clone(),
+            lock: SpinMutex::new(()),
+            dirty: AtomicBool::new(false),
+            writable_mappings: AtomicUsize::new(0),
+            last_used: AtomicU64::new(0),
+        }))
+    }
+
+    /// The physical address of the page, for mapping it.
+    pub fn physical(&self) -> usize {
+        self.physical
+    }
+
+    /// Whether the page may differ from what is on the backing store.
+    ///
+    /// A page mapped writable is always taken as dirty, as writes through a mapping go unnoticed.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire) || self.writable_mappings.load(Ordering::Acquire) > 0
+    }
+
+    /// Maps the page into a process, it stays in the cache until the mapping is dropped.
+    pub fn map(self: &Arc<Self>, writable: bool) -> PageMapping {
+        if writable {
+            self.writable_mappings.fetch_add(1, Ordering::AcqRel);
+        }
+
+        PageMapping {
+            page: self.clone(),
+            writable,
+        }
+    }
+
+    /// Copies out of the page, starting at `offset` in it.
+    pub fn read(&self, offset: usize, buffer: &mut [u8]) {
+        debug_assert!(offset + buffer.len() <= PAGE_SIZE);
+
+        let _guard = self.lock.lock();
+        buffer.copy_from_slice(unsafe {
+            core::slice::from_raw_parts(self.data.as_ptr().add(offset), buffer.len())
+        });
+    }
+
+    /// Lends `len` bytes of the page starting at `offset` to `f` instead of copying them out.
+    ///
+    /// `f` must not write to this page through the cache, that would wait for itself.
+    pub fn lend<R>(&self, offset: usize, len: usize, f: impl FnOnce(&[u8]) -> R) -> R {
+        debug_assert!(offset + len <= PAGE_SIZE);
+
+        let _guard = self.lock.lock();
+        f(unsafe { core::slice::from_raw_parts(self.data.as_ptr().add(offset), len) })
+    }
+
+    fn write(&self, offset: usize, buffer: &[u8]) {
+        debug_assert!(offset + buffer.len() <= PAGE_SIZE);
+
+        let _guard = self.lock.lock();
+        unsafe { core::slice::from_raw_parts_mut(self.data.as_ptr().add(offset), buffer.len()) }
+            .copy_from_slice(buffer);
+    }
+
+    fn fill_zero(&self, offset: usize) {
+        let _guard = self.lock.lock();
+        unsafe { self.data.add(offset).write_bytes(0, PAGE_SIZE - offset) };
+    }
+}
+
+impl Drop for CachedPage {
+    fn drop(&mut self) {
+        unsafe { self.allocator.deallocate(self.physical, self.data) };
+    }
+}
+
+/// A cached page mapped into a process.
+///
+/// Dropping a writable mapping leaves the page dirty, so what was written through it is written
+/// back later.
+pub struct PageMapping {
+    page: Arc<CachedPage>,
+    writable: bool,
+}
+
+impl PageMapping {
+    pub fn page(&self) -> &Arc<CachedPage> {
+        &self.page
+    }
+
+    pub fn physical(&self) -> usize {
+        self.page.physical
+    }
+}
+
+impl Drop for PageMapping {
+    fn drop(&mut self) {
+        if self.writable {
+            self.page.dirty.store(true, Ordering::Release);
+            self.page.writable_mappings.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+struct CachedFile {
+    // Any inode object of the file does, it is only used for writing back
+    inode: Arc<dyn IInode>,
+    pages: BTreeMap<usize, Arc<CachedPage>>,
+}
+
+struct PageCacheInner {
+    files: BTreeMap<FileKey, CachedFile>,
+    pages: usize,
+    clock: u64,
+}
+
+/// The pages of regular files, shared by `read`, `write` and file-backed `mmap`.
+///
+/// Writes stay in the cache until the file is synced, or until its pages are evicted when the
+/// cache is full or pages can't be allocated.
+pub struct PageCache {
+    allocator: Arc<dyn IPageAllocator>,
+    capacity: usize,
+    inner: SpinMutex<PageCacheInner>,
+}
+
+impl PageCache {
+    /// Creates a cache holding at most `capacity` pages, pages still mapped may go over it.
+    pub fn new(allocator: Arc<dyn IPageAllocator>, capacity: usize) -> Arc<PageCache> {
+        Arc::new(PageCache {
+            allocator,
+            capacity,
+            inner: SpinMutex::new(PageCacheInner {
+                files: BTreeMap::new(),
+                pages: 0,
+                clock: 0,
+            }),
+        })
+    }
+
+    /// How many pages are cached.
+    pub fn cached_pages(&self) -> usize {
+        self.inner.lock().pages
+    }
+
+    pub fn read(
+        &self,
+        key: FileKey,
+        inode: &Arc<dyn IInode>,
+        offset: usize,
+        buffer: &mut [u8],
+    ) -> FileSystemResult<usize> {
+        let size = inode.metadata().size;
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let end = Ord::min(offset + buffer.len(), size);
+        let mut inner = self.inner.lock();
+        let mut position = offset;
+
+        while position < end {
+            let index = position / PAGE_SIZE;
+            let within = position % PAGE_SIZE;
+            let len = Ord::min(PAGE_SIZE - within, end - position);
+
+            let page = self.get_page(&mut inner, key, inode, index, false)?;
+            page.read(
+                within,
+                &mut buffer[position - offset..position - offset + len],
+            );
+
+            position += len;
+        }
+
+        Ok(end - offset)
+    }
+
+    pub fn write(
+        &self,
+        key: FileKey,
+        inode: &Arc<dyn IInode>,
+        offset: usize,
+        buffer: &[u8],
+    ) -> FileSystemResult<usize> {
+        let end = offset + buffer.len();
+
+        if end > inode.metadata().size && inode.resize(end as u64).is_err() {
+            // The filesystem only grows files by writing, so this write can't wait in the cache
+            return self.write_through(key, inode, offset, buffer);
+        }
+
+        let mut inner = self.inner.lock();
+        let mut position = offset;
+
+        while position < end {
+            let index = position / PAGE_SIZE;
+            let within = position % PAGE_SIZE;
+            let len = Ord::min(PAGE_SIZE - within, end - position);
+
+            // Nothing has to be read for a page that is written all over
+            let page = self.get_page(&mut inner, key, inode, index, len == PAGE_SIZE)?;
+            page.write(within, &buffer[position - offset..position - offset + len]);
+            page.dirty.store(true, Ordering::Release);
+
+            position += len;
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn write_through(
+        &self,
+        key: FileKey,
+        inode: &Arc<dyn IInode>,
+        offset: usize,
+        buffer: &[u8],
+    ) -> FileSystemResult<usize> {
+        let mut inner = self.inner.lock();
+
+        // What is cached would be written back over this otherwise
+        Self::write_back(&mut inner, key)?;
+
+        let written = inode.writeat(offset, buffer)?;
+
+        if let Some(file) = inner.files.get(&key) {
+            let end = offset + written;
+
+            for (&index, page) in file
+                .pages
+                .range(offset / PAGE_SIZE..end.div_ceil(PAGE_SIZE))
+            {
+                let start = Ord::max(index * PAGE_SIZE, offset);
+                let stop = Ord::min((index + 1) * PAGE_SIZE, end);
+
+                page.write(start % PAGE_SIZE, &buffer[start - offset..stop - offset]);
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// The page at `index` of the file, for mapping it. Pages past the end of the file are zeros.
+    pub fn page(
+        &self,
+        key: FileKey,
+        inode: &Arc<dyn IInode>,
+        index: usize,
+    ) -> FileSystemResult<Arc<CachedPage>> {
+        let mut inner = self.inner.lock();
+
+        self.get_page(&mut inner, key, inode, index, false)
+    }
+
+    fn get_page(
+        &self,
+        inner: &mut PageCacheInner,
+        key: FileKey,
+        inode: &Arc<dyn IInode>,
+        index: usize,
+        overwritten: bool,
+    ) -> FileSystemResult<Arc<CachedPage>> {
+        inner.clock += 1;
+        let now = inner.clock;
+
+        if let Some(page) = inner
+            .files
+            .get(&key)
+            .and_then(|file| file.pages.get(&index))
+        {
+            page.last_used.store(now, Ordering::Relaxed);
+
+            return Ok(page.clone());
+        }
+
+        let page = self.allocate_page(inner)?;
+        page.last_used.store(now, Ordering::Relaxed);
+
+        if !overwritten {
+            Self::fill_page(&page, inode, index)?;
+        }
+
+        inner
+            .files
+            .entry(key)
+            .or_insert_with(|| CachedFile {
+                inode: inode.clone(),
+                pages: BTreeMap::new(),
+            })
+            .pages
+            .insert(index, page.clone());
+        inner.pages += 1;
+
+        if inner.pages > self.capacity {
+            let excess = inner.pages - self.capacity;
+            Self::evict(inner, excess);
+        }
+
+        Ok(page)
+    }
+
+    fn allocate_page(&self, inner: &mut PageCacheInner) -> FileSystemResult<Arc<CachedPage>> {
+        if let Some(page) = CachedPage::allocate(&self.allocator) {
+            return Ok(page);
+        }
+
+        // Out of memory, make room by giving back some of what is cached
+        if Self::evict(inner, 1) == 0 {
+            return Err(FileSystemError::SpaceNotEnough);
+        }
+
+        CachedPage::allocate(&self.allocator).ok_or(FileSystemError::SpaceNotEnough)
+    }
+
+    fn fill_page(page: &CachedPage, inode: &Arc<dyn IInode>, index: usize) -> FileSystemResult<()> {
+        let start = index * PAGE_SIZE;
+        let len = inode.metadata().size.saturating_sub(start).min(PAGE_SIZE);
+
+        // Not shared yet, so the lock is not needed
+        let data = unsafe { core::slice::from_raw_parts_mut(page.data.as_ptr(), len) };
+        let mut filled = 0;
+
+        while filled < len {
+            match inode.readat(start + filled, &mut data[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes back the dirty pages of a file.
+    pub fn sync(&self, key: FileKey) -> FileSystemResult<()> {
+        Self::write_back(&mut self.inner.lock(), key)
+    }
+
+    fn write_back(inner: &mut PageCacheInner, key: FileKey) -> FileSystemResult<()> {
+        let Some(file) = inner.files.get(&key) else {
+            return Ok(());
+        };
+
+        let size = file.inode.metadata().size;
+
+        for (&index, page) in file.pages.iter() {
+            Self::write_back_page(&file.inode, size, index, page)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_back_page(
+        inode: &Arc<dyn IInode>,
+        size: usize,
+        index: usize,
+        page: &CachedPage,
+    ) -> FileSystemResult<()> {
+        if !page.dirty.swap(false, Ordering::AcqRel) && !page.is_dirty() {
+            return Ok(());
+        }
+
+        let start = index * PAGE_SIZE;
+        let len = size.saturating_sub(start).min(PAGE_SIZE);
+        let mut buffer = vec![0; len];
+        page.read(0, &mut buffer);
+
+        let mut written = 0;
+
+        while written < len {
+            match inode.writeat(start + written, &buffer[written..]) {
+                Ok(0) | Err(_) => {
+                    page.dirty.store(true, Ordering::Release);
+
+                    return Err(FileSystemError::WriteZero);
+                }
+                Ok(n) => written += n,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops the cached pages past `new_size`, do this before the file itself is resized.
+    pub fn truncate(&self, key: FileKey, new_size: usize) {
+        let mut inner = self.inner.lock();
+
+        let Some(file) = inner.files.get_mut(&key) else {
+            return;
+        };
+
+        // Mapped pages are only taken out of the cache, what is written to them is lost
+        let cut = file.pages.split_off(&new_size.div_ceil(PAGE_SIZE)).len();
+
+        if let Some(page) = file.pages.get(&(new_size / PAGE_SIZE)) {
+            if !new_size.is_multiple_of(PAGE_SIZE) {
+                page.fill_zero(new_size % PAGE_SIZE);
+            }
+        }
+
+        inner.pages -= cut;
+    }
+
+    /// Whether a page of a file is mapped writable into a process.
+    pub fn is_mapped_writable(&self, key: FileKey) -> bool {
+        self.inner.lock().files.get(&key).is_some_and(|file| {
+            file.pages
+                .values()
+                .any(|page| page.writable_mappings.load(Ordering::Acquire) > 0)
+        })
+    }
+
+    /// Drops the pages of a file without writing them back, like when it is removed.
+    pub fn forget(&self, key: FileKey) {
+        let mut inner = self.inner.lock();
+
+        if let Some(file) = inner.files.remove(&key) {
+            inner.pages -= file.pages.len();
+        }
+    }
+
+    /// Writes back the dirty pages of every file in a filesystem.
+    pub fn sync_mount(&self, mount: usize) -> FileSystemResult<()> {
+        let mut inner = self.inner.lock();
+        let keys: Vec<_> = Self::keys_of(&inner, mount).collect();
+
+        keys.into_iter()
+            .try_for_each(|key| Self::write_back(&mut inner, key))
+    }
+
+    /// Drops the pages of every file in a filesystem, like when it is unmounted.
+    pub fn forget_mount(&self, mount: usize) {
+        let mut inner = self.inner.lock();
+        let keys: Vec<_> = Self::keys_of(&inner, mount).collect();
+
+        for key in keys {
+            if let Some(file) = inner.files.remove(&key) {
+                inner.pages -= file.pages.len();
+            }
+        }
+    }
+
+    fn keys_of(inner: &PageCacheInner, mount: usize) -> impl Iterator<Item = FileKey> + '_ {
+        let first = FileKey { mount, inode: 0 };
+
+        inner
+            .files
+            .range(first..)
+            .map(|(key, _)| *key)
+            .take_while(move |key| key.mount == mount)
+    }
+
+    /// Writes back every dirty page, returns the first error but keeps on with the other files.
+    pub fn sync_all(&self) -> FileSystemResult<()> {
+        let mut inner = self.inner.lock();
+        let keys: Vec<_> = inner.files.keys().copied().collect();
+        let mut result = Ok(());
+
+        for key in keys {
+            if let Err(e) = Self::write_back(&mut inner, key) {
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    /// Gives back up to `count` pages that are not mapped, least recently used first.
+    ///
+    /// Returns how many were given back.
+    pub fn shrink(&self, count: usize) -> usize {
+        Self::evict(&mut self.inner.lock(), count)
+    }
+
+    /// The same as [`PageCache::shrink`], but gives back nothing if the cache is busy.
+    ///
+    /// For memory reclaim, which may run while the cache itself is allocating.
+    pub fn try_shrink(&self, count: usize) -> usize {
+        self.inner
+            .try_lock()
+            .map_or(0, |mut inner| Self::evict(&mut inner, count))
+    }
+
+    fn evict(inner: &mut PageCacheInner, count: usize) -> usize {
+        // Pages held elsewhere are mapped or being copied right now
+        let mut candidates: Vec<_> = inner
+            .files
+            .iter()
+            .flat_map(|(key, file)| {
+                file.pages
+                    .iter()
+                    .filter(|(_, page)| Arc::strong_count(page) == 1)
+                    .map(move |(&index, page)| {
+                        (page.last_used.load(Ordering::Relaxed), *key, index)
+                    })
+            })
+            .collect();
+
+        candidates.sort_unstable();
+
+        let mut evicted = 0;
+
+        for (_, key, index) in candidates {
+            if evicted == count {
+                break;
+            }
+
+            let file = inner.files.get_mut(&key).unwrap();
+            let size = file.inode.metadata().size;
+
+            // Keep what could not be written back, it would be lost otherwise
+            if Self::write_back_page(&file.inode, size, index, &file.pages[&index]).is_err() {
+                continue;
+            }
+
+            file.pages.remove(&index);
+
+            if file.pages.is_empty() {
+                inner.files.remove(&key);
+            }
+
+            evicted += 1;
+        }
+
+        inner.pages -= evicted;
+
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DirectoryEntry, DirectoryEntryType, DirectoryTreeNode, FileStatistics, IFileSystem,
+        InodeMetadata,
+    };
+
+    use alloc::{
+        alloc::{alloc_zeroed, dealloc, Layout},
+        collections::BTreeMap,
+        string::{String, ToString},
+    };
+
+    struct HeapPageAllocator {
+        // Lets tests run out of pages
+        remaining: AtomicUsize,
+    }
+
+    impl HeapPageAllocator {
+        fn new(pages: usize) -> Arc<HeapPageAllocator> {
+            Arc::new(HeapPageAllocator {
+                remaining: AtomicUsize::new(pages),
+            })
+        }
+
+        fn layout() -> Layout {
+            Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap()
+        }
+    }
+
+    impl IPageAllocator for HeapPageAllocator {
+        fn allocate(&self) -> Option<(usize, NonNull<u8>)> {
+            self.remaining
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+                .ok()?;
+
+            let page = NonNull::new(unsafe { alloc_zeroed(Self::layout()) })?;
+
+            Some((page.as_ptr() as usize, page))
+        }
+
+        unsafe fn deallocate(&self, _physical: usize, page: NonNull<u8>) {
+            self.remaining.fetch_add(1, Ordering::AcqRel);
+
+            unsafe { dealloc(page.as_ptr(), Self::layout()) };
+        }
+    }
+
+    /// A file in memory that counts how often it is read and written.
+    struct MemoryInode {
+        name: String,
+        number: u64,
+        content: SpinMutex<Vec<u8>>,
+        reads: AtomicUsize,
+        writes: AtomicUsize,
+    }
+
+    impl MemoryInode {
+        fn new(name: &str, number: u64, content: &[u8]) -> Arc<MemoryInode> {
+            Arc::new(MemoryInode {
+                name: name.to_string(),
+                number,
+                content: SpinMutex::new(content.to_vec()),
+                reads: AtomicUsize::new(0),
+                writes: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl IInode for MemoryInode {
+        fn metadata(&self) -> InodeMetadata<'_> {
+            InodeMetadata {
+                filename: &self.name,
+                entry_type: DirectoryEntryType::File,
+                size: self.content.lock().len(),
+            }
+        }
+
+        fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+
+            let content = self.content.lock();
+            let len = content.len().saturating_sub(offset).min(buffer.len());
+            buffer[..len].copy_from_slice(&content[offset..offset + len]);
+
+            Ok(len)
+        }
+
+        fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+
+            let mut content = self.content.lock();
+            if content.len() < offset + buffer.len() {
+                content.resize(offset + buffer.len(), 0);
+            }
+            content[offset..offset + buffer.len()].copy_from_slice(buffer);
+
+            Ok(buffer.len())
+        }
+
+        fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+            self.content.lock().resize(new_size as usize, 0);
+
+            Ok(new_size)
+        }
+
+        fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+            stat.inode_id = self.number;
+            stat.link_count = 1;
+            stat.size = self.content.lock().len() as u64;
+
+            Ok(())
+        }
+    }
+
+    /// A directory with a fixed set of files.
+    struct MemoryDirectory {
+        files: BTreeMap<String, Arc<MemoryInode>>,
+    }
+
+    impl IInode for MemoryDirectory {
+        fn metadata(&self) -> InodeMetadata<'_> {
+            InodeMetadata {
+                filename: "",
+                entry_type: DirectoryEntryType::Directory,
+                size: 0,
+            }
+        }
+
+        fn read_cache_dir(
+            &self,
+            _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+        ) -> FileSystemResult<Vec<DirectoryEntry>> {
+            Ok(self
+                .files
+                .keys()
+                .map(|name| DirectoryEntry {
+                    filename: name.clone(),
+                    entry_type: DirectoryEntryType::File,
+                })
+                .collect())
+        }
+
+        fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+            match self.files.get(name) {
+                Some(file) => Ok(file.clone()),
+                None => Err(FileSystemError::NotFound),
+            }
+        }
+    }
+
+    struct MemoryFileSystem {
+        root: Arc<MemoryDirectory>,
+    }
+
+    impl IFileSystem for MemoryFileSystem {
+        fn root_dir(&self) -> Arc<dyn IInode> {
+            self.root.clone()
+        }
+
+        fn name(&self) -> &str {
+            "memfs"
+        }
+    }
+
+    const KEY: FileKey = FileKey { mount: 1, inode: 2 };
+
+    fn setup(content: &[u8], capacity: usize) -> (Arc<PageCache>, Arc<MemoryInode>) {
+        (
+            PageCache::new(HeapPageAllocator::new(usize::MAX), capacity),
+            MemoryInode::new("file", KEY.inode, content),
+        )
+    }
+
+    #[test]
+    fn test_repeated_reads_hit_the_cache() {
+        let (cache, file) = setup(&[7; PAGE_SIZE * 2], 16);
+        let inode: Arc<dyn IInode> = file.clone();
+        let mut buffer = [0; 100];
+
+        for _ in 0..3 {
+            assert_eq!(
+                cache.read(KEY, &inode, PAGE_SIZE - 50, &mut buffer),
+                Ok(100)
+            );
+        }
+
+        assert_eq!(buffer, [7; 100]);
+        assert_eq!(file.reads.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.cached_pages(), 2);
+    }
+
+    #[test]
+    fn test_read_stops_at_end_of_file() {
+        let (cache, file) = setup(b"hello", 16);
+        let inode: Arc<dyn IInode> = file;
+        let mut buffer = [0; 16];
+
+        assert_eq!(cache.read(KEY, &inode, 0, &mut buffer), Ok(5));
+        assert_eq!(&buffer[..5], b"hello");
+        assert_eq!(cache.read(KEY, &inode, 5, &mut buffer), Ok(0));
+    }
+
+    #[test]
+    fn test_writes_wait_for_sync() {
+        let (cache, file) = setup(b"hello world", 16);
+        let inode: Arc<dyn IInode> = file.clone();
+
+        assert_eq!(cache.write(KEY, &inode, 6, b"cache"), Ok(5));
+        assert_eq!(file.content.lock().as_slice(), b"hello world");
+
+        let mut buffer = [0; 11];
+        cache.read(KEY, &inode, 0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hello cache");
+
+        cache.sync(KEY).unwrap();
+        assert_eq!(file.content.lock().as_slice(), b"hello cache");
+
+        // Clean pages are not written again
+        let writes = file.writes.load(Ordering::Relaxed);
+        cache.sync(KEY).unwrap();
+        assert_eq!(file.writes.load(Ordering::Relaxed), writes);
+    }
+
+    #[test]
+    fn test_write_past_end_grows_file() {
+        let (cache, file) = setup(b"abc", 16);
+        let inode: Arc<dyn IInode> = file.clone();
+
+        cache.write(KEY, &inode, PAGE_SIZE + 1, b"xyz").unwrap();
+        assert_eq!(inode.metadata().size, PAGE_SIZE + 4);
+
+        cache.sync(KEY).unwrap();
+
+        let content = file.content.lock();
+        assert_eq!(&content[..3], b"abc");
+        assert!(content[3..PAGE_SIZE + 1].iter().all(|&b| b == 0));
+        assert_eq!(&content[PAGE_SIZE + 1..], b"xyz");
+    }
+
+    #[test]
+    fn test_full_cache_writes_back_what_it_evicts() {
+        let (cache, file) = setup(&[0; PAGE_SIZE * 4], 2);
+        let inode: Arc<dyn IInode> = file.clone();
+
+        for index in 0..4 {
+            cache
+                .write(KEY, &inode, index * PAGE_SIZE, &[index as u8 + 1])
+                .unwrap();
+        }
+
+        assert_eq!(cache.cached_pages(), 2);
+
+        let content = file.content.lock();
+        assert_eq!(content[0], 1);
+        assert_eq!(content[PAGE_SIZE], 2);
+        // Still only in the cache
+        assert_eq!(content[PAGE_SIZE * 2], 0);
+        assert_eq!(content[PAGE_SIZE * 3], 0);
+    }
+
+    #[test]
+    fn test_out_of_pages_evicts() {
+        let cache = PageCache::new(HeapPageAllocator::new(1), 16);
+        let inode: Arc<dyn IInode> = MemoryInode::new("file", KEY.inode, &[1; PAGE_SIZE * 2]);
+        let mut buffer = [0; 1];
+
+        cache.read(KEY, &inode, 0, &mut buffer).unwrap();
+        cache.read(KEY, &inode, PAGE_SIZE, &mut buffer).unwrap();
+        assert_eq!(cache.cached_pages(), 1);
+
+        // Nothing can be evicted while the only page is mapped
+        let page = cache.page(KEY, &inode, 1).unwrap();
+        assert_eq!(
+            cache.read(KEY, &inode, 0, &mut buffer),
+            Err(FileSystemError::SpaceNotEnough)
+        );
+
+        drop(page);
+        assert_eq!(cache.shrink(usize::MAX), 1);
+        assert_eq!(cache.cached_pages(), 0);
+    }
+
+    #[test]
+    fn test_mapping_shares_pages_with_read_and_write() {
+        let (cache, file) = setup(b"mapped", 16);
+        let inode: Arc<dyn IInode> = file.clone();
+
+        let page = cache.page(KEY, &inode, 0).unwrap();
+        let mapping = page.map(true);
+        assert!(page.is_dirty());
+
+        // A process writing through its mapping
+        unsafe { (mapping.physical() as *mut u8).write(b'M') };
+
+        let mut buffer = [0; 6];
+        cache.read(KEY, &inode, 0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"Mapped");
+
+        cache.write(KEY, &inode, 1, b"A").unwrap();
+        assert_eq!(
+            unsafe { (mapping.physical() as *const u8).add(1).read() },
+            b'A'
+        );
+
+        drop(mapping);
+        assert!(page.is_dirty());
+
+        cache.sync(KEY).unwrap();
+        assert!(!page.is_dirty());
+        assert_eq!(file.content.lock().as_slice(), b"MApped");
+    }
+
+    #[test]
+    fn test_truncate_drops_pages_past_the_end() {
+        let (cache, file) = setup(&[9; PAGE_SIZE * 2], 16);
+        let inode: Arc<dyn IInode> = file.clone();
+        let mut buffer = [0; PAGE_SIZE * 2];
+
+        cache.read(KEY, &inode, 0, &mut buffer).unwrap();
+        cache.truncate(KEY, 10);
+        inode.resize(10).unwrap();
+        assert_eq!(cache.cached_pages(), 1);
+
+        inode.resize(20).unwrap();
+        assert_eq!(cache.read(KEY, &inode, 0, &mut buffer), Ok(20));
+        assert_eq!(&buffer[..10], &[9; 10]);
+        assert_eq!(&buffer[10..20], &[0; 10]);
+    }
+
+    #[test]
+    fn test_mounts_are_synced_and_forgotten_separately() {
+        let (cache, file) = setup(b"one", 16);
+        let other = MemoryInode::new("other", 1, b"two");
+        let (inode, other_inode): (Arc<dyn IInode>, Arc<dyn IInode>) =
+            (file.clone(), other.clone());
+        let other_key = FileKey { mount: 2, inode: 1 };
+
+        cache.write(KEY, &inode, 0, b"ONE").unwrap();
+        cache.write(other_key, &other_inode, 0, b"TWO").unwrap();
+
+        cache.sync_mount(KEY.mount).unwrap();
+        cache.forget_mount(KEY.mount);
+
+        assert_eq!(file.content.lock().as_slice(), b"ONE");
+        assert_eq!(other.content.lock().as_slice(), b"two");
+        assert_eq!(cache.cached_pages(), 1);
+
+        cache.sync_all().unwrap();
+        assert_eq!(other.content.lock().as_slice(), b"TWO");
+    }
+
+    #[test]
+    fn test_tree_goes_through_the_cache() {
+        let file = MemoryInode::new("file", 3, b"content");
+        let fs = Arc::new(MemoryFileSystem {
+            root: Arc::new(MemoryDirectory {
+                files: BTreeMap::from([("file".to_string(), file.clone())]),
+            }),
+        });
+
+        let cache = PageCache::new(HeapPageAllocator::new(usize::MAX), 16);
+        let root = DirectoryTreeNode::from_empty(None, String::new());
+        root.mkdir("mnt").unwrap();
+
+        let mounted =
+            DirectoryTreeNode::from_cached_filesystem(Some(root.clone()), fs, cache.clone(), None);
+        root.mount_as(mounted, Some("mnt")).unwrap();
+
+        let node = root.open("/mnt/file", Some(&root)).unwrap();
+        let mut buffer = [0; 7];
+
+        node.readat(0, &mut buffer).unwrap();
+        node.readat(0, &mut buffer).unwrap();
+        assert_eq!(file.reads.load(Ordering::Relaxed), 1);
+
+        // Another path to the same file sees the same pages
+        node.writeat(0, b"C").unwrap();
+        root.open("/mnt/file", Some(&root))
+            .unwrap()
+            .readat(0, &mut buffer)
+            .unwrap();
+        assert_eq!(&buffer, b"Content");
+        assert_eq!(file.content.lock().as_slice(), b"content");
+
+        node.sync().unwrap();
+        assert_eq!(file.content.lock().as_slice(), b"Content");
+
+        node.writeat(1, b"O").unwrap();
+        drop(node);
+        root.umount_at("mnt").unwrap();
+
+        assert_eq!(file.content.lock().as_slice(), b"COntent");
+        assert_eq!(cache.cached_pages(), 0);
+    }
+
+    #[test]
+    fn test_uncached_filesystem_is_not_cached() {
+        let file = MemoryInode::new("file", 3, b"content");
+        let fs = Arc::new(MemoryFileSystem {
+            root: Arc::new(MemoryDirectory {
+                files: BTreeMap::from([("file".to_string(), file.clone())]),
+            }),
+        });
+
+        let root = DirectoryTreeNode::from_filesystem(None, fs, None);
+        let node = root.open("/file", Some(&root)).unwrap();
+
+        node.writeat(0, b"C").unwrap();
+        assert_eq!(file.content.lock().as_slice(), b"Content");
+        assert_eq!(
+            node.cached_page(0).err(),
+            Some(FileSystemError::Unimplemented)
+        );
+    }
+}
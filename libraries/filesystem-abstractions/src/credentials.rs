@@ -0,0 +1,795 @@
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+use crate::{
+    FileMode, FileStatistics, FileStatisticsMode, FileSystemError, FileSystemResult,
+    InodeAttributes, XattrNamespace,
+};
+
+bitflags! {
+    /// What a task wants to do with a file, the same bits as `R_OK`, `W_OK` and `X_OK`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AccessMode: u32 {
+        const EXECUTE = 1;
+        const WRITE = 2;
+        const READ = 4;
+    }
+}
+
+/// The users and groups a task acts as.
+///
+/// Files are checked against the filesystem ids, which follow the effective ones unless changed
+/// on their own. Changing ids needs an effective uid of root, there are no finer capabilities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub fsuid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    pub sgid: u32,
+    pub fsgid: u32,
+    /// The supplementary groups
+    pub groups: Vec<u32>,
+}
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+/// The real, effective, saved and filesystem ids of one kind.
+struct Ids<'a> {
+    real: &'a mut u32,
+    effective: &'a mut u32,
+    saved: &'a mut u32,
+    filesystem: &'a mut u32,
+}
+
+impl Ids<'_> {
+    fn is_current(&self, id: u32) -> bool {
+        id == *self.real || id == *self.effective || id == *self.saved
+    }
+
+    fn set(&mut self, id: u32, privileged: bool) -> FileSystemResult<()> {
+        if privileged {
+            *self.real = id;
+            *self.saved = id;
+        } else if id != *self.real && id != *self.saved {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        *self.effective = id;
+        *self.filesystem = id;
+
+        Ok(())
+    }
+
+    fn set_re(
+        &mut self,
+        real: Option<u32>,
+        effective: Option<u32>,
+        privileged: bool,
+    ) -> FileSystemResult<()> {
+        if !privileged {
+            let real_allowed = real.is_none_or(|id| id == *self.real || id == *self.effective);
+            let effective_allowed = effective.is_none_or(|id| self.is_current(id));
+
+            if !real_allowed || !effective_allowed {
+                return Err(FileSystemError::NotPermitted);
+            }
+        }
+
+        let previous_real = *self.real;
+
+        if let Some(id) = real {
+            *self.real = id;
+        }
+
+        if let Some(id) = effective {
+            *self.effective = id;
+        }
+
+        // The saved id follows, so the old effective id can not be taken back later
+        if real.is_some() || effective.is_some_and(|id| id != previous_real) {
+            *self.saved = *self.effective;
+        }
+
+        *self.filesystem = *self.effective;
+
+        Ok(())
+    }
+
+    fn set_res(
+        &mut self,
+        real: Option<u32>,
+        effective: Option<u32>,
+        saved: Option<u32>,
+        privileged: bool,
+    ) -> FileSystemResult<()> {
+        if !privileged
+            && ![real, effective, saved]
+                .iter()
+                .flatten()
+                .all(|&id| self.is_current(id))
+        {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if let Some(id) = real {
+            *self.real = id;
+        }
+
+        if let Some(id) = effective {
+            *self.effective = id;
+        }
+
+        if let Some(id) = saved {
+            *self.saved = id;
+        }
+
+        *self.filesystem = *self.effective;
+
+        Ok(())
+    }
+
+    /// Returns the previous filesystem id, whether it was changed or not.
+    fn set_filesystem(&mut self, id: u32, privileged: bool) -> u32 {
+        let previous = *self.filesystem;
+
+        if privileged || self.is_current(id) || id == previous {
+            *self.filesystem = id;
+        }
+
+        previous
+    }
+}
+
+impl Credentials {
+    /// Most supplementary groups a task can have, `NGROUPS_MAX`.
+    pub const MAX_GROUPS: usize = 65536;
+
+    pub fn root() -> Credentials {
+        Self::new(0, 0)
+    }
+
+    /// All ids of the user `uid` in the group `gid`, without supplementary groups.
+    pub fn new(uid: u32, gid: u32) -> Credentials {
+        Credentials {
+            uid,
+            euid: uid,
+            suid: uid,
+            fsuid: uid,
+            gid,
+            egid: gid,
+            sgid: gid,
+            fsgid: gid,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Whether file permissions are ignored.
+    pub fn is_superuser(&self) -> bool {
+        self.fsuid == 0
+    }
+
+    /// Whether ids can be changed at will.
+    fn is_privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// Whether system administration like mounting filesystems is allowed.
+    pub fn is_admin(&self) -> bool {
+        self.euid == 0
+    }
+
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.fsgid == gid || self.groups.contains(&gid)
+    }
+
+    fn owns(&self, stat: &FileStatistics) -> bool {
+        self.is_superuser() || self.fsuid == stat.uid
+    }
+
+    fn user_ids(&mut self) -> Ids<'_> {
+        Ids {
+            real: &mut self.uid,
+            effective: &mut self.euid,
+            saved: &mut self.suid,
+            filesystem: &mut self.fsuid,
+        }
+    }
+
+    fn group_ids(&mut self) -> Ids<'_> {
+        Ids {
+            real: &mut self.gid,
+            effective: &mut self.egid,
+            saved: &mut self.sgid,
+            filesystem: &mut self.fsgid,
+        }
+    }
+
+    /// Checks the permission bits of the file with `stat` grant all of `access`.
+    ///
+    /// Root may do anything, but only execute files someone can execute.
+    pub fn check_access(&self, stat: &FileStatistics, access: AccessMode) -> FileSystemResult<()> {
+        let mode = stat.mode.bits();
+
+        let granted = if self.is_superuser() {
+            let is_directory =
+                mode & FileStatisticsMode::TYPE_MASK.bits() == FileStatisticsMode::DIR.bits();
+
+            match is_directory || mode & 0o111 != 0 {
+                true => AccessMode::all().bits(),
+                false => (AccessMode::READ | AccessMode::WRITE).bits(),
+            }
+        } else if self.fsuid == stat.uid {
+            (mode >> 6) & 0o7
+        } else if self.in_group(stat.gid) {
+            (mode >> 3) & 0o7
+        } else {
+            mode & 0o7
+        };
+
+        match AccessMode::from_bits_truncate(granted).contains(access) {
+            true => Ok(()),
+            false => Err(FileSystemError::AccessDenied),
+        }
+    }
+
+    /// Checks `child` may be removed from or renamed out of the directory `dir`.
+    ///
+    /// In a sticky directory like `/tmp`, only the owner of a file or of the directory may do so.
+    pub fn check_delete(
+        &self,
+        dir: &FileStatistics,
+        child: &FileStatistics,
+    ) -> FileSystemResult<()> {
+        self.check_access(dir, AccessMode::WRITE | AccessMode::EXECUTE)?;
+
+        let sticky = dir.mode.bits() & FileMode::S_ISVTX.bits() != 0;
+
+        if sticky && !self.owns(dir) && !self.owns(child) {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the file with `stat` may be changed like `attributes` says, returning what to change.
+    ///
+    /// The mode and the times may only be set by the owner. Only root gives files away, owners may
+    /// only change the group to one they are in. Set-user-ID and set-group-ID are cleared where
+    /// they would grant something they should not anymore.
+    pub fn check_attributes(
+        &self,
+        stat: &FileStatistics,
+        attributes: &InodeAttributes,
+    ) -> FileSystemResult<InodeAttributes> {
+        let mut allowed = *attributes;
+
+        let uid_changes = attributes.uid.is_some_and(|uid| uid != stat.uid);
+        let gid_changes = attributes.gid.is_some_and(|gid| gid != stat.gid);
+        let times_change = attributes.atime.is_some() || attributes.mtime.is_some();
+
+        if (attributes.mode.is_some() || times_change) && !self.owns(stat) {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if uid_changes && !self.is_superuser() {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if gid_changes
+            && !self.is_superuser()
+            && !attributes
+                .gid
+                .is_some_and(|gid| self.fsuid == stat.uid && self.in_group(gid))
+        {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if let Some(mode) = attributes.mode {
+            let gid = attributes.gid.unwrap_or(stat.gid);
+
+            if !self.is_superuser() && !self.in_group(gid) {
+                allowed.mode = Some(mode - FileMode::S_ISGID);
+            }
+        }
+
+        let is_directory = stat.mode.bits() & FileStatisticsMode::TYPE_MASK.bits()
+            == FileStatisticsMode::DIR.bits();
+
+        // A file given away must not run as its previous owner anymore
+        if (uid_changes || gid_changes) && !is_directory {
+            let mode = allowed
+                .mode
+                .unwrap_or(FileMode::from_bits_truncate(stat.mode.bits()));
+
+            let mut cleared = mode - FileMode::S_ISUID;
+
+            // Without group execute, set-group-ID means mandatory locking instead
+            if mode.contains(FileMode::S_IXGRP) {
+                cleared -= FileMode::S_ISGID;
+            }
+
+            if cleared.bits() != mode.bits() {
+                allowed.mode = Some(cleared);
+            }
+        }
+
+        Ok(allowed)
+    }
+
+    /// Checks the times of the file with `stat` may be set to now, which writing to it does anyway.
+    pub fn check_touch(&self, stat: &FileStatistics) -> FileSystemResult<()> {
+        match self.owns(stat) {
+            true => Ok(()),
+            false => self.check_access(stat, AccessMode::WRITE),
+        }
+    }
+
+    /// Checks the extended attribute `name` of the file with `stat` may be read or, with
+    /// [`AccessMode::WRITE`], changed. An empty `access` checks it is visible at all.
+    ///
+    /// `user.` attributes follow the permission bits and only exist on regular files and
+    /// directories, `trusted.` ones are only for root. `security.` and `system.` ones can be read
+    /// by anyone and changed by the owner.
+    pub fn check_xattr(
+        &self,
+        stat: &FileStatistics,
+        name: &str,
+        access: AccessMode,
+    ) -> FileSystemResult<()> {
+        // Attributes someone may not see look like they do not exist
+        let hidden = match access.contains(AccessMode::WRITE) {
+            true => FileSystemError::NotPermitted,
+            false => FileSystemError::NoAttribute,
+        };
+
+        match XattrNamespace::of(name)? {
+            XattrNamespace::User => {
+                let file_type = stat.mode.bits() & FileStatisticsMode::TYPE_MASK.bits();
+                let is_directory = file_type == FileStatisticsMode::DIR.bits();
+
+                if !is_directory && file_type != FileStatisticsMode::FILE.bits() {
+                    return Err(hidden);
+                }
+
+                let sticky = stat.mode.bits() & FileMode::S_ISVTX.bits() != 0;
+
+                if is_directory && sticky && access.contains(AccessMode::WRITE) && !self.owns(stat)
+                {
+                    return Err(FileSystemError::NotPermitted);
+                }
+
+                self.check_access(stat, access)
+            }
+            XattrNamespace::Trusted => match self.is_privileged() {
+                true => Ok(()),
+                false => Err(hidden),
+            },
+            XattrNamespace::Security | XattrNamespace::System => {
+                match access.contains(AccessMode::WRITE) && !self.owns(stat) {
+                    true => Err(FileSystemError::NotPermitted),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Takes on the owner of a set-user-ID or set-group-ID executable with `stat`, which is then
+    /// kept as the saved ids.
+    pub fn exec(&mut self, stat: &FileStatistics) {
+        let mode = FileMode::from_bits_truncate(stat.mode.bits());
+
+        if mode.contains(FileMode::S_ISUID) {
+            self.euid = stat.uid;
+        }
+
+        if mode.contains(FileMode::S_ISGID | FileMode::S_IXGRP) {
+            self.egid = stat.gid;
+        }
+
+        self.suid = self.euid;
+        self.fsuid = self.euid;
+        self.sgid = self.egid;
+        self.fsgid = self.egid;
+    }
+
+    /// Like `setuid`, root sets all user ids, others only the effective one to the real or saved.
+    pub fn set_uid(&mut self, uid: u32) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.user_ids().set(uid, privileged)
+    }
+
+    pub fn set_gid(&mut self, gid: u32) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.group_ids().set(gid, privileged)
+    }
+
+    /// Like `setreuid`, `None` leaves an id as it is.
+    pub fn set_reuid(&mut self, uid: Option<u32>, euid: Option<u32>) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.user_ids().set_re(uid, euid, privileged)
+    }
+
+    pub fn set_regid(&mut self, gid: Option<u32>, egid: Option<u32>) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.group_ids().set_re(gid, egid, privileged)
+    }
+
+    /// Like `setresuid`, others than root may only use ids they already have.
+    pub fn set_resuid(
+        &mut self,
+        uid: Option<u32>,
+        euid: Option<u32>,
+        suid: Option<u32>,
+    ) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.user_ids().set_res(uid, euid, suid, privileged)
+    }
+
+    pub fn set_resgid(
+        &mut self,
+        gid: Option<u32>,
+        egid: Option<u32>,
+        sgid: Option<u32>,
+    ) -> FileSystemResult<()> {
+        let privileged = self.is_privileged();
+
+        self.group_ids().set_res(gid, egid, sgid, privileged)
+    }
+
+    /// Like `setfsuid`, returns the previous filesystem uid even if it was not changed.
+    pub fn set_fsuid(&mut self, fsuid: u32) -> u32 {
+        let privileged = self.is_privileged();
+
+        self.user_ids().set_filesystem(fsuid, privileged)
+    }
+
+    pub fn set_fsgid(&mut self, fsgid: u32) -> u32 {
+        let privileged = self.is_privileged();
+
+        self.group_ids().set_filesystem(fsgid, privileged)
+    }
+
+    pub fn set_groups(&mut self, groups: Vec<u32>) -> FileSystemResult<()> {
+        if !self.is_privileged() {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if groups.len() > Self::MAX_GROUPS {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        self.groups = groups;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use timing::TimeSpec;
+
+    use super::*;
+
+    const OWNER: u32 = 1000;
+    const GROUP: u32 = 100;
+
+    fn file(mode: u32) -> FileStatistics {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        stat.mode = FileStatisticsMode::from_bits_retain(mode);
+        stat.uid = OWNER;
+        stat.gid = GROUP;
+
+        stat
+    }
+
+    fn allowed(credentials: &Credentials, mode: u32, access: AccessMode) -> bool {
+        credentials.check_access(&file(mode), access).is_ok()
+    }
+
+    #[test]
+    fn test_access_matrix() {
+        let owner = Credentials::new(OWNER, 1);
+        let member = Credentials::new(2000, GROUP);
+        let mut supplementary = Credentials::new(2000, 1);
+        supplementary.groups = vec![GROUP];
+        let other = Credentials::new(3000, 3000);
+
+        let cases = [
+            (0o100700, [true, false, false, false]),
+            (0o100070, [false, true, true, false]),
+            (0o100007, [false, false, false, true]),
+            (0o100777, [true, true, true, true]),
+            (0o100000, [false, false, false, false]),
+        ];
+
+        for (mode, expected) in cases {
+            for (credentials, expected) in [&owner, &member, &supplementary, &other]
+                .into_iter()
+                .zip(expected)
+            {
+                for access in [AccessMode::READ, AccessMode::WRITE, AccessMode::EXECUTE] {
+                    assert_eq!(
+                        allowed(credentials, mode, access),
+                        expected,
+                        "{credentials:?} {mode:o} {access:?}"
+                    );
+                }
+            }
+        }
+
+        // Only the class that matches counts, the owner does not get what others have
+        assert!(!allowed(&owner, 0o100077, AccessMode::READ));
+        assert!(!allowed(&member, 0o100407, AccessMode::READ));
+
+        // Every bit asked for has to be granted
+        assert!(!allowed(
+            &owner,
+            0o100400,
+            AccessMode::READ | AccessMode::WRITE
+        ));
+        assert!(allowed(
+            &owner,
+            0o100600,
+            AccessMode::READ | AccessMode::WRITE
+        ));
+
+        // The filesystem ids count, not the effective ones
+        let mut switched = Credentials::new(3000, 3000);
+        switched.fsuid = OWNER;
+        assert!(allowed(&switched, 0o100600, AccessMode::WRITE));
+    }
+
+    #[test]
+    fn test_root_access() {
+        let root = Credentials::root();
+
+        assert!(allowed(
+            &root,
+            0o100000,
+            AccessMode::READ | AccessMode::WRITE
+        ));
+        assert!(!allowed(&root, 0o100600, AccessMode::EXECUTE));
+        assert!(allowed(&root, 0o100001, AccessMode::EXECUTE));
+        assert!(allowed(&root, 0o040000, AccessMode::EXECUTE));
+
+        assert_eq!(
+            Credentials::new(OWNER, GROUP).check_access(&file(0o100000), AccessMode::READ),
+            Err(FileSystemError::AccessDenied)
+        );
+    }
+
+    #[test]
+    fn test_sticky_directory() {
+        let tmp = {
+            let mut stat = file(0o041777);
+            stat.uid = 0;
+            stat
+        };
+        let owned = file(0o100600);
+
+        let owner = Credentials::new(OWNER, GROUP);
+        let other = Credentials::new(3000, 3000);
+
+        assert!(owner.check_delete(&tmp, &owned).is_ok());
+        assert!(Credentials::root().check_delete(&tmp, &owned).is_ok());
+        assert_eq!(
+            other.check_delete(&tmp, &owned),
+            Err(FileSystemError::NotPermitted)
+        );
+
+        // Without the sticky bit, write permission on the directory is enough
+        let mut shared = tmp;
+        shared.mode = FileStatisticsMode::from_bits_retain(0o040777);
+        assert!(other.check_delete(&shared, &owned).is_ok());
+
+        shared.mode = FileStatisticsMode::from_bits_retain(0o040755);
+        assert_eq!(
+            other.check_delete(&shared, &owned),
+            Err(FileSystemError::AccessDenied)
+        );
+    }
+
+    #[test]
+    fn test_xattr_namespaces() {
+        let owner = Credentials::new(OWNER, GROUP);
+        let other = Credentials::new(3000, 3000);
+        let readable = file(0o100644);
+
+        assert!(owner
+            .check_xattr(&readable, "user.tag", AccessMode::WRITE)
+            .is_ok());
+        assert!(other
+            .check_xattr(&readable, "user.tag", AccessMode::READ)
+            .is_ok());
+        assert_eq!(
+            other.check_xattr(&readable, "user.tag", AccessMode::WRITE),
+            Err(FileSystemError::AccessDenied)
+        );
+
+        // Nothing in the user namespace on symbolic links
+        assert_eq!(
+            owner.check_xattr(&file(0o120777), "user.tag", AccessMode::READ),
+            Err(FileSystemError::NoAttribute)
+        );
+
+        assert_eq!(
+            owner.check_xattr(&readable, "trusted.overlay.opaque", AccessMode::empty()),
+            Err(FileSystemError::NoAttribute)
+        );
+        assert_eq!(
+            owner.check_xattr(&readable, "trusted.overlay.opaque", AccessMode::WRITE),
+            Err(FileSystemError::NotPermitted)
+        );
+        assert!(Credentials::root()
+            .check_xattr(&readable, "trusted.overlay.opaque", AccessMode::WRITE)
+            .is_ok());
+
+        assert!(other
+            .check_xattr(&readable, "security.selinux", AccessMode::READ)
+            .is_ok());
+        assert_eq!(
+            other.check_xattr(&readable, "security.selinux", AccessMode::WRITE),
+            Err(FileSystemError::NotPermitted)
+        );
+        assert_eq!(
+            owner.check_xattr(&readable, "unknown.name", AccessMode::READ),
+            Err(FileSystemError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn test_attribute_changes() {
+        let owner = Credentials::new(OWNER, GROUP);
+        let other = Credentials::new(3000, 3000);
+        let stat = file(0o106755);
+
+        let chmod = InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o644)),
+            ..Default::default()
+        };
+        assert!(owner.check_attributes(&stat, &chmod).is_ok());
+        assert_eq!(
+            other.check_attributes(&stat, &chmod).err(),
+            Some(FileSystemError::NotPermitted)
+        );
+
+        let chown = InodeAttributes {
+            uid: Some(3000),
+            ..Default::default()
+        };
+        assert_eq!(
+            owner.check_attributes(&stat, &chown).err(),
+            Some(FileSystemError::NotPermitted)
+        );
+
+        // Root may give it away, but it does not run as the previous owner anymore
+        let given = Credentials::root().check_attributes(&stat, &chown).unwrap();
+        assert_eq!(given.mode.unwrap().bits(), 0o755);
+
+        let mut member = owner.clone();
+        member.groups = vec![200];
+        let chgrp = InodeAttributes {
+            gid: Some(200),
+            ..Default::default()
+        };
+        assert!(member.check_attributes(&stat, &chgrp).is_ok());
+        assert_eq!(
+            owner.check_attributes(&stat, &chgrp).err(),
+            Some(FileSystemError::NotPermitted)
+        );
+
+        // Set-group-ID of a group the owner is not in is dropped
+        let mut outsider = file(0o100644);
+        outsider.gid = 300;
+        let setgid = InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o2755)),
+            ..Default::default()
+        };
+        let allowed = owner.check_attributes(&outsider, &setgid).unwrap();
+        assert_eq!(allowed.mode.unwrap().bits(), 0o755);
+
+        let utime = InodeAttributes {
+            mtime: Some(TimeSpec::zero()),
+            ..Default::default()
+        };
+        assert_eq!(
+            other.check_attributes(&stat, &utime).err(),
+            Some(FileSystemError::NotPermitted)
+        );
+        assert!(other.check_touch(&file(0o100666)).is_ok());
+        assert_eq!(
+            other.check_touch(&file(0o100644)),
+            Err(FileSystemError::AccessDenied)
+        );
+    }
+
+    #[test]
+    fn test_exec_set_user_id() {
+        let mut credentials = Credentials::new(3000, 3000);
+        let mut stat = file(0o104755);
+        stat.uid = 0;
+
+        credentials.exec(&stat);
+
+        assert_eq!(credentials.uid, 3000);
+        assert_eq!(credentials.euid, 0);
+        assert_eq!(credentials.suid, 0);
+        assert_eq!(credentials.fsuid, 0);
+        assert_eq!(credentials.egid, 3000);
+
+        // Dropping to the real user and back again through the saved one
+        credentials.set_uid(3000).unwrap();
+        assert_eq!(credentials.euid, 3000);
+        assert_eq!(credentials.suid, 3000);
+        assert_eq!(credentials.set_uid(0), Err(FileSystemError::NotPermitted));
+    }
+
+    #[test]
+    fn test_set_ids() {
+        let mut credentials = Credentials::new(1000, 1000);
+        assert_eq!(
+            credentials.set_resuid(Some(1000), Some(1000), Some(2000)),
+            Err(FileSystemError::NotPermitted)
+        );
+        assert_eq!(
+            credentials.set_resuid(None, Some(2000), None),
+            Err(FileSystemError::NotPermitted)
+        );
+
+        let mut credentials = Credentials::root();
+        credentials
+            .set_resuid(Some(1000), Some(2000), Some(0))
+            .unwrap();
+        assert_eq!(
+            (credentials.uid, credentials.euid, credentials.suid),
+            (1000, 2000, 0)
+        );
+        assert_eq!(credentials.fsuid, 2000);
+
+        // Unprivileged now, but the saved root can be taken back
+        assert_eq!(
+            credentials.set_groups(vec![1]),
+            Err(FileSystemError::NotPermitted)
+        );
+        credentials.set_resuid(None, Some(0), None).unwrap();
+        credentials.set_groups(vec![1, 2]).unwrap();
+        assert_eq!(credentials.groups, [1, 2]);
+
+        // Swapping real and effective ids with setreuid
+        let mut credentials = Credentials::new(1000, 1000);
+        credentials.euid = 2000;
+        credentials.suid = 2000;
+        credentials.set_reuid(Some(2000), Some(1000)).unwrap();
+        assert_eq!(
+            (credentials.uid, credentials.euid, credentials.suid),
+            (2000, 1000, 1000)
+        );
+
+        // setfsuid tells the previous id, whether it was allowed or not
+        assert_eq!(credentials.set_fsuid(2000), 1000);
+        assert_eq!(credentials.set_fsuid(5000), 2000);
+        assert_eq!(credentials.fsuid, 2000);
+
+        let mut credentials = Credentials::root();
+        credentials.set_gid(50).unwrap();
+        assert_eq!(
+            (credentials.gid, credentials.egid, credentials.sgid),
+            (50, 50, 50)
+        );
+        credentials.set_regid(None, Some(60)).unwrap();
+        assert_eq!((credentials.egid, credentials.sgid), (60, 60));
+    }
+}
@@ -1,7 +1,10 @@
 use core::cell::UnsafeCell;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::{DirectoryEntry, DirectoryEntryType, DirectoryTreeNode, OpenFlags};
+use crate::{
+    DirectoryEntry, DirectoryEntryType, DirectoryTreeNode, FileLockManager, FileSystemResult,
+    InotifyMask, OpenFlags,
+};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use downcast_rs::{impl_downcast, Downcast, DowncastSend};
@@ -13,6 +16,8 @@ pub struct FileMetadata {
     open_flags: SpinMutex<OpenFlags>,
     inode: Arc<DirectoryTreeNode>,
     children_entries: UnsafeCell<Option<Vec<DirectoryEntry>>>,
+    /// Where this open file description holds OFD or `flock` locks, released once it is dropped
+    locks: SpinMutex<Option<Arc<FileLockManager>>>,
 }
 
 impl FileMetadata {
@@ -22,6 +27,7 @@ impl FileMetadata {
             open_flags: SpinMutex::new(flags),
             inode,
             children_entries: UnsafeCell::new(None),
+            locks: SpinMutex::new(None),
         }
     }
 
@@ -80,6 +86,17 @@ impl FileMetadata {
         self.inode.clone()
     }
 
+    /// Tells this open file description apart from others as the owner of OFD and `flock` locks.
+    pub fn lock_owner(&self) -> usize {
+        self as *const FileMetadata as usize
+    }
+
+    /// Notes that locks are taken in `manager` for this open file description, so that they are
+    /// released when it is closed for good.
+    pub fn hold_locks_in(&self, manager: &Arc<FileLockManager>) {
+        self.locks.lock().get_or_insert_with(|| manager.clone());
+    }
+
     pub fn read_dir(&self) -> Option<&[DirectoryEntry]> {
         let children_entries = unsafe { self.children_entries.get().as_mut().unwrap() };
         if let Some(ref children) = children_entries {
@@ -96,6 +113,21 @@ impl FileMetadata {
     }
 }
 
+impl Drop for FileMetadata {
+    fn drop(&mut self) {
+        if let Some(manager) = self.locks.get_mut().take() {
+            manager.release_file(self.lock_owner());
+        }
+
+        let flags = *self.open_flags.get_mut();
+
+        match flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR) {
+            true => self.inode.notify(InotifyMask::IN_CLOSE_WRITE),
+            false => self.inode.notify(InotifyMask::IN_CLOSE_NOWRITE),
+        }
+    }
+}
+
 unsafe impl Send for FileMetadata {}
 unsafe impl Sync for FileMetadata {}
 
@@ -169,6 +201,11 @@ pub trait IFile: Downcast + DowncastSend + Send + Sync {
         })
     }
 
+    /// Like `read`, but tells why nothing could be read, for files that refuse some reads.
+    fn try_read(&self, buf: &mut [u8]) -> FileSystemResult<usize> {
+        Ok(self.read(buf))
+    }
+
     fn pread(&self, buf: &mut [u8], offset: u64) -> usize {
         self.metadata().map_or(0, |metadata| {
             metadata.inode().readat(offset as usize, buf).unwrap_or(0)
@@ -290,6 +327,14 @@ impl FileDescriptorTable {
         Some(idx)
     }
 
+    /// The open file descriptors with their numbers, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Arc<dyn IFile>)> {
+        self.table
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| entry.as_ref().map(|file| (idx, file)))
+    }
+
     pub fn set_capacity(&mut self, new_capacity: usize) {
         self.capacity = new_capacity
     }
@@ -3,7 +3,10 @@ use alloc::string::String;
 use alloc::{sync::Arc, vec::Vec};
 use downcast_rs::{impl_downcast, Downcast, DowncastSend};
 
-use crate::{DirectoryEntry, FileStatistics, FileSystemError, FileSystemResult, InodeMetadata};
+use crate::{
+    DirectoryEntry, FileStatistics, FileSystemError, FileSystemResult, InodeAttributes,
+    InodeMetadata, XattrFlags,
+};
 
 pub trait IInode: Downcast + DowncastSend + Send + Sync {
     fn metadata(&self) -> InodeMetadata<'_>;
@@ -52,6 +55,29 @@ pub trait IInode: Downcast + DowncastSend + Send + Sync {
         Err(FileSystemError::Unimplemented)
     }
 
+    /// Changes the mode, owner or timestamps, the change time is updated by the filesystem.
+    fn set_attributes(&self, _attributes: &InodeAttributes) -> FileSystemResult<()> {
+        Err(FileSystemError::Unimplemented)
+    }
+
+    /// The value of the extended attribute `name`, permissions are checked by the caller.
+    fn get_xattr(&self, _name: &str) -> FileSystemResult<Vec<u8>> {
+        Err(FileSystemError::NotSupported)
+    }
+
+    fn set_xattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> FileSystemResult<()> {
+        Err(FileSystemError::NotSupported)
+    }
+
+    /// The names of all extended attributes, with their namespace prefix.
+    fn list_xattr(&self) -> FileSystemResult<Vec<String>> {
+        Err(FileSystemError::NotSupported)
+    }
+
+    fn remove_xattr(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotSupported)
+    }
+
     fn hard_link(&self, _name: &str, _inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
         Err(FileSystemError::Unimplemented)
     }
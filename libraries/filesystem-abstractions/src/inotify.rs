@@ -0,0 +1,544 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use bitflags::bitflags;
+use core::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
+use hermit_sync::SpinMutex;
+
+use crate::{DirectoryTreeNode, FileSystemError, FileSystemResult, IFile, OpenFlags};
+
+bitflags! {
+    /// The events of `inotify(7)` and the flags of `inotify_add_watch`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InotifyMask: u32 {
+        const IN_ACCESS        = 0x1;
+        const IN_MODIFY        = 0x2;
+        const IN_ATTRIB        = 0x4;
+        const IN_CLOSE_WRITE   = 0x8;
+        const IN_CLOSE_NOWRITE = 0x10;
+        const IN_OPEN          = 0x20;
+        const IN_MOVED_FROM    = 0x40;
+        const IN_MOVED_TO      = 0x80;
+        const IN_CREATE        = 0x100;
+        const IN_DELETE        = 0x200;
+        const IN_DELETE_SELF   = 0x400;
+        const IN_MOVE_SELF     = 0x800;
+
+        const IN_UNMOUNT    = 0x2000;
+        const IN_Q_OVERFLOW = 0x4000;
+        const IN_IGNORED    = 0x8000;
+
+        const IN_ONLYDIR     = 0x1000000;
+        const IN_DONT_FOLLOW = 0x2000000;
+        const IN_EXCL_UNLINK = 0x4000000;
+        const IN_MASK_CREATE = 0x10000000;
+        const IN_MASK_ADD    = 0x20000000;
+        const IN_ISDIR       = 0x40000000;
+        const IN_ONESHOT     = 0x80000000;
+    }
+}
+
+impl InotifyMask {
+    /// Everything a watch can ask for
+    pub const IN_ALL_EVENTS: InotifyMask = InotifyMask::from_bits_truncate(0xfff);
+
+    /// Events on a file that a watched directory holding it hears about as well
+    pub(crate) const CHILD_EVENTS: InotifyMask = InotifyMask::IN_ACCESS
+        .union(InotifyMask::IN_MODIFY)
+        .union(InotifyMask::IN_ATTRIB)
+        .union(InotifyMask::IN_CLOSE_WRITE)
+        .union(InotifyMask::IN_CLOSE_NOWRITE)
+        .union(InotifyMask::IN_OPEN);
+}
+
+/// Size of `struct inotify_event` without the name
+pub const INOTIFY_EVENT_SIZE: usize = 16;
+
+/// Events an instance queues before dropping the rest for a single `IN_Q_OVERFLOW`
+pub const INOTIFY_MAX_QUEUED_EVENTS: usize = 16384;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InotifyEvent {
+    /// The watch descriptor, -1 for `IN_Q_OVERFLOW`
+    pub wd: i32,
+    pub mask: InotifyMask,
+    /// Ties an `IN_MOVED_FROM` to its `IN_MOVED_TO`, 0 for other events
+    pub cookie: u32,
+    /// The entry in a watched directory the event is about
+    pub name: Option<String>,
+}
+
+impl InotifyEvent {
+    /// The name is nul-terminated and padded so the next event starts aligned.
+    fn name_len(&self) -> usize {
+        self.name.as_ref().map_or(0, |name| {
+            (name.len() + 1).div_ceil(INOTIFY_EVENT_SIZE) * INOTIFY_EVENT_SIZE
+        })
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        INOTIFY_EVENT_SIZE + self.name_len()
+    }
+
+    /// Writes the event as a `struct inotify_event`, `buf` has to be at least [`Self::encoded_len`] long.
+    fn encode(&self, buf: &mut [u8]) {
+        let name_len = self.name_len();
+
+        buf[0..4].copy_from_slice(&self.wd.to_ne_bytes());
+        buf[4..8].copy_from_slice(&self.mask.bits().to_ne_bytes());
+        buf[8..12].copy_from_slice(&self.cookie.to_ne_bytes());
+        buf[12..16].copy_from_slice(&(name_len as u32).to_ne_bytes());
+
+        let name = &mut buf[INOTIFY_EVENT_SIZE..INOTIFY_EVENT_SIZE + name_len];
+        name.fill(0);
+
+        if let Some(ref event_name) = self.name {
+            name[..event_name.len()].copy_from_slice(event_name.as_bytes());
+        }
+    }
+}
+
+struct Watch {
+    inotify: Weak<Inotify>,
+    wd: i32,
+    mask: InotifyMask,
+}
+
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// A fresh cookie for the two halves of a rename.
+pub(crate) fn next_cookie() -> u32 {
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The watches on the files of one filesystem, kept by the nodes it is mounted at and shared with
+/// its bind mounts.
+pub(crate) struct WatchTable {
+    /// Watches by the inode id of the file they watch
+    files: SpinMutex<BTreeMap<u64, Vec<Watch>>>,
+    /// Files in `files`, so operations skip looking up inodes while nothing is watched
+    watched: AtomicUsize,
+    /// Mounts showing the filesystem in the tree, the watches end with the last one
+    mounts: AtomicUsize,
+}
+
+impl WatchTable {
+    pub(crate) fn new() -> Arc<WatchTable> {
+        Arc::new(WatchTable {
+            files: SpinMutex::new(BTreeMap::new()),
+            watched: AtomicUsize::new(0),
+            mounts: AtomicUsize::new(0),
+        })
+    }
+
+    /// Counts another mount of the filesystem.
+    pub(crate) fn mount(&self) {
+        self.mounts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn update_watched(&self, files: &BTreeMap<u64, Vec<Watch>>) {
+        self.watched.store(files.len(), Ordering::Release);
+    }
+
+    /// Whether any file of the filesystem is watched at all.
+    pub(crate) fn is_watching(&self) -> bool {
+        self.watched.load(Ordering::Acquire) != 0
+    }
+
+    /// Queues `mask` on every watch of `inode` asking for it.
+    pub(crate) fn notify(&self, inode: u64, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+        let mut targets = Vec::new();
+        let mut finished = Vec::new();
+
+        {
+            let mut files = self.files.lock();
+
+            let Some(list) = files.get_mut(&inode) else {
+                return;
+            };
+
+            list.retain(|watch| {
+                if !watch.mask.intersects(mask & InotifyMask::IN_ALL_EVENTS) {
+                    return true;
+                }
+
+                let Some(inotify) = watch.inotify.upgrade() else {
+                    return false;
+                };
+
+                targets.push((inotify.clone(), watch.wd));
+
+                match watch.mask.contains(InotifyMask::IN_ONESHOT) {
+                    true => {
+                        finished.push((inotify, watch.wd));
+                        false
+                    }
+                    false => true,
+                }
+            });
+
+            if list.is_empty() {
+                files.remove(&inode);
+                self.update_watched(&files);
+            }
+        }
+
+        for (inotify, wd) in targets {
+            inotify.push(InotifyEvent {
+                wd,
+                mask,
+                cookie,
+                name: name.map(|name| name.to_string()),
+            });
+        }
+
+        for (inotify, wd) in finished {
+            inotify.ignored(wd);
+        }
+    }
+
+    /// Drops every watch of `inode` as the file is gone.
+    pub(crate) fn forget(&self, inode: u64) {
+        let removed = {
+            let mut files = self.files.lock();
+            let removed = files.remove(&inode);
+            self.update_watched(&files);
+
+            removed
+        };
+
+        for watch in removed.into_iter().flatten() {
+            if let Some(inotify) = watch.inotify.upgrade() {
+                inotify.ignored(watch.wd);
+            }
+        }
+    }
+
+    /// Takes one mount of the filesystem away. Once it was the last, every watch hears
+    /// `IN_UNMOUNT` and is dropped.
+    pub(crate) fn unmount(&self) {
+        if self.mounts.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        let removed = {
+            let mut files = self.files.lock();
+            let removed = core::mem::take(&mut *files);
+            self.update_watched(&files);
+
+            removed
+        };
+
+        for watch in removed.into_values().flatten() {
+            if let Some(inotify) = watch.inotify.upgrade() {
+                inotify.push(InotifyEvent {
+                    wd: watch.wd,
+                    mask: InotifyMask::IN_UNMOUNT,
+                    cookie: 0,
+                    name: None,
+                });
+                inotify.ignored(watch.wd);
+            }
+        }
+    }
+}
+
+struct InotifyInner {
+    /// Watched files by their watch descriptor, as the table of their filesystem knows them.
+    /// The tables are weak so watches never keep a filesystem busy.
+    watches: BTreeMap<i32, (Weak<WatchTable>, u64)>,
+    events: VecDeque<InotifyEvent>,
+}
+
+/// An inotify instance, reading it returns the queued events as `struct inotify_event`s.
+pub struct Inotify {
+    this: Weak<Inotify>,
+    inner: SpinMutex<InotifyInner>,
+    next_wd: AtomicI32,
+    flags: SpinMutex<OpenFlags>,
+}
+
+impl Inotify {
+    pub fn new(flags: OpenFlags) -> Arc<Inotify> {
+        Arc::new_cyclic(|this| Inotify {
+            this: this.clone(),
+            inner: SpinMutex::new(InotifyInner {
+                watches: BTreeMap::new(),
+                events: VecDeque::new(),
+            }),
+            next_wd: AtomicI32::new(1),
+            flags: SpinMutex::new(flags | OpenFlags::O_RDONLY),
+        })
+    }
+
+    /// Watches `node` for the events in `mask`, watching it again changes the mask of the same
+    /// watch descriptor.
+    ///
+    /// Fails with [`FileSystemError::AlreadyExists`] if it is watched already and `mask` has `IN_MASK_CREATE`.
+    pub fn add_watch(
+        &self,
+        node: &Arc<DirectoryTreeNode>,
+        mask: InotifyMask,
+    ) -> FileSystemResult<i32> {
+        let (table, inode) = node.watch_key()?;
+
+        let mut files = table.files.lock();
+        let list = files.entry(inode).or_default();
+
+        if let Some(watch) = list
+            .iter_mut()
+            .find(|watch| Weak::ptr_eq(&watch.inotify, &self.this))
+        {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(FileSystemError::AlreadyExists);
+            }
+
+            watch.mask = match mask.contains(InotifyMask::IN_MASK_ADD) {
+                true => watch.mask | mask,
+                false => mask,
+            };
+
+            return Ok(watch.wd);
+        }
+
+        let wd = self.next_wd.fetch_add(1, Ordering::Relaxed);
+
+        list.push(Watch {
+            inotify: self.this.clone(),
+            wd,
+            mask,
+        });
+        table.update_watched(&files);
+        drop(files);
+
+        self.inner
+            .lock()
+            .watches
+            .insert(wd, (Arc::downgrade(&table), inode));
+
+        Ok(wd)
+    }
+
+    /// Stops the watch `wd`, queuing its `IN_IGNORED`. Returns false if there is no such watch.
+    pub fn remove_watch(&self, wd: i32) -> bool {
+        let Some((table, inode)) = self.inner.lock().watches.get(&wd).cloned() else {
+            return false;
+        };
+
+        self.unregister(&table, inode, wd);
+        self.ignored(wd);
+
+        true
+    }
+
+    fn unregister(&self, table: &Weak<WatchTable>, inode: u64, wd: i32) {
+        let Some(table) = table.upgrade() else {
+            return;
+        };
+
+        let mut files = table.files.lock();
+
+        if let Some(list) = files.get_mut(&inode) {
+            list.retain(|watch| !(watch.wd == wd && Weak::ptr_eq(&watch.inotify, &self.this)));
+
+            if list.is_empty() {
+                files.remove(&inode);
+            }
+        }
+
+        table.update_watched(&files);
+    }
+
+    /// Queues the `IN_IGNORED` that ends a watch.
+    fn ignored(&self, wd: i32) {
+        self.inner.lock().watches.remove(&wd);
+
+        self.push(InotifyEvent {
+            wd,
+            mask: InotifyMask::IN_IGNORED,
+            cookie: 0,
+            name: None,
+        });
+    }
+
+    fn push(&self, event: InotifyEvent) {
+        let mut inner = self.inner.lock();
+
+        // The same event twice in a row is only reported once
+        if inner.events.back() == Some(&event) {
+            return;
+        }
+
+        if inner.events.len() >= INOTIFY_MAX_QUEUED_EVENTS {
+            return;
+        }
+
+        let event = match inner.events.len() + 1 == INOTIFY_MAX_QUEUED_EVENTS {
+            true => InotifyEvent {
+                wd: -1,
+                mask: InotifyMask::IN_Q_OVERFLOW,
+                cookie: 0,
+                name: None,
+            },
+            false => event,
+        };
+
+        inner.events.push_back(event);
+    }
+
+    /// Bytes of the queued events, what `FIONREAD` reports.
+    pub fn pending_bytes(&self) -> usize {
+        self.inner
+            .lock()
+            .events
+            .iter()
+            .map(|e| e.encoded_len())
+            .sum()
+    }
+
+    /// Takes as many whole events as fit into `buf`, returning the bytes written.
+    ///
+    /// Returns 0 if no event is queued, and `InvalidInput` if the first one does not fit.
+    pub fn read_events(&self, buf: &mut [u8]) -> FileSystemResult<usize> {
+        let mut inner = self.inner.lock();
+        let mut written = 0;
+
+        while let Some(event) = inner.events.front() {
+            let len = event.encoded_len();
+
+            if written + len > buf.len() {
+                if written == 0 {
+                    return Err(FileSystemError::InvalidInput);
+                }
+
+                break;
+            }
+
+            event.encode(&mut buf[written..written + len]);
+            written += len;
+
+            inner.events.pop_front();
+        }
+
+        Ok(written)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        let watches = core::mem::take(&mut self.inner.get_mut().watches);
+
+        for (wd, (table, inode)) in watches {
+            self.unregister(&table, inode, wd);
+        }
+    }
+}
+
+impl IFile for Inotify {
+    fn can_read(&self) -> bool {
+        true
+    }
+
+    fn can_write(&self) -> bool {
+        false
+    }
+
+    fn read_avaliable(&self) -> bool {
+        !self.inner.lock().events.is_empty()
+    }
+
+    fn write_avaliable(&self) -> bool {
+        false
+    }
+
+    fn flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+
+    fn set_flags(&self, new_flags: OpenFlags) -> bool {
+        *self.flags.lock() = new_flags;
+        true
+    }
+
+    fn is_dir(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> usize {
+        self.read_events(buf).unwrap_or(0)
+    }
+
+    fn try_read(&self, buf: &mut [u8]) -> FileSystemResult<usize> {
+        self.read_events(buf)
+    }
+
+    fn write(&self, _buf: &[u8]) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(wd: i32, mask: InotifyMask, name: Option<&str>) -> InotifyEvent {
+        InotifyEvent {
+            wd,
+            mask,
+            cookie: 0,
+            name: name.map(|name| name.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_encodes_padded_names() {
+        let inotify = Inotify::new(OpenFlags::NONE);
+
+        inotify.push(event(1, InotifyMask::IN_CREATE, Some("file")));
+        inotify.push(event(2, InotifyMask::IN_MODIFY, None));
+        assert!(inotify.read_avaliable());
+        assert_eq!(inotify.pending_bytes(), 48);
+
+        // Only whole events are returned
+        let mut buf = [0xffu8; 40];
+        assert_eq!(inotify.read(&mut buf), 32);
+        assert_eq!(i32::from_ne_bytes(buf[0..4].try_into().unwrap()), 1);
+        assert_eq!(u32::from_ne_bytes(buf[4..8].try_into().unwrap()), 0x100);
+        assert_eq!(u32::from_ne_bytes(buf[12..16].try_into().unwrap()), 16);
+        assert_eq!(&buf[16..32], b"file\0\0\0\0\0\0\0\0\0\0\0\0");
+
+        assert_eq!(inotify.read(&mut buf), 16);
+        assert_eq!(u32::from_ne_bytes(buf[12..16].try_into().unwrap()), 0);
+        assert!(!inotify.read_avaliable());
+    }
+
+    #[test]
+    fn test_rejects_buffer_smaller_than_an_event() {
+        let inotify = Inotify::new(OpenFlags::NONE);
+
+        assert_eq!(inotify.try_read(&mut [0u8; 8]), Ok(0));
+
+        inotify.push(event(1, InotifyMask::IN_CREATE, Some("file")));
+
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            inotify.try_read(&mut buf),
+            Err(FileSystemError::InvalidInput)
+        );
+        assert_eq!(inotify.pending_bytes(), 32);
+    }
+
+    #[test]
+    fn test_merges_repeated_events() {
+        let inotify = Inotify::new(OpenFlags::NONE);
+
+        inotify.push(event(1, InotifyMask::IN_MODIFY, Some("file")));
+        inotify.push(event(1, InotifyMask::IN_MODIFY, Some("file")));
+        inotify.push(event(1, InotifyMask::IN_MODIFY, Some("other")));
+
+        assert_eq!(inotify.pending_bytes(), 64);
+    }
+}
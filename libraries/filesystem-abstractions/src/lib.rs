@@ -12,13 +12,33 @@ extern crate std;
 
 extern crate alloc;
 
+mod cache;
+mod credentials;
 mod file;
 mod inode;
+mod inotify;
+mod lock;
+mod mount;
+mod pipe;
+mod seal;
+mod transfer;
 mod tree;
+mod xattr;
 
+pub use cache::*;
+pub use credentials::*;
 pub use file::*;
 pub use inode::*;
+pub use inotify::{
+    Inotify, InotifyEvent, InotifyMask, INOTIFY_EVENT_SIZE, INOTIFY_MAX_QUEUED_EVENTS,
+};
+pub use lock::*;
+pub use mount::*;
+pub use pipe::*;
+pub use seal::*;
+pub use transfer::*;
 pub use tree::{DirectoryTreeNode, MountError};
+pub use xattr::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileSystemError {
@@ -41,6 +61,17 @@ pub enum FileSystemError {
     LinkTooDepth,
     NotPermitted,
     ReadOnly,
+    AccessDenied,
+    /// The filesystem or the namespace of an extended attribute does not support it
+    NotSupported,
+    /// The extended attribute does not exist
+    NoAttribute,
+    /// Nothing reads from the pipe anymore
+    BrokenPipe,
+    /// In use in a way the operation can't go along with
+    Busy,
+    /// Can only be done by copying, like moving something to another filesystem
+    CrossDevice,
 }
 
 impl FileSystemError {
@@ -61,6 +92,12 @@ impl FileSystemError {
             FileSystemError::LinkTooDepth => ErrNo::TooManyLevelsOfSymbolicLinks,
             FileSystemError::NotPermitted => ErrNo::OperationNotPermitted,
             FileSystemError::ReadOnly => ErrNo::ReadOnlyFileSystem,
+            FileSystemError::AccessDenied => ErrNo::PermissionDenied,
+            FileSystemError::NotSupported => ErrNo::OperationNotSupported,
+            FileSystemError::NoAttribute => ErrNo::NoDataAvailable,
+            FileSystemError::BrokenPipe => ErrNo::BrokenPipe,
+            FileSystemError::Busy => ErrNo::DeviceOrResourceBusy,
+            FileSystemError::CrossDevice => ErrNo::InvalidCrossDeviceLink,
             _ => ErrNo::InvalidArgument,
         }
     }
@@ -85,7 +122,7 @@ pub enum DirectoryEntryType {
     BlockDevice = 6,
     File = 8,
     Symlink = 10,
-    // Socket = 12,
+    Socket = 12,
 }
 
 impl From<DirectoryEntryType> for FileStatisticsMode {
@@ -98,7 +135,7 @@ impl From<DirectoryEntryType> for FileStatisticsMode {
             DirectoryEntryType::CharDevice => FileStatisticsMode::CHAR,
             DirectoryEntryType::NamedPipe => FileStatisticsMode::FIFO,
             DirectoryEntryType::Symlink => FileStatisticsMode::LINK,
-            // DirectoryEntryType::Socket => FileStatisticsMode::SOCKET,
+            DirectoryEntryType::Socket => FileStatisticsMode::SOCKET,
         }
     }
 }
@@ -116,6 +153,17 @@ pub struct InodeMetadata<'a> {
     pub size: usize,
 }
 
+/// Attributes to change with [`IInode::set_attributes`], `None` leaves one as it is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InodeAttributes {
+    /// The permission bits, including set-user-ID, set-group-ID and sticky
+    pub mode: Option<FileMode>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub atime: Option<TimeSpec>,
+    pub mtime: Option<TimeSpec>,
+}
+
 #[repr(C)]
 pub struct FileStatistics {
     pub device_id: u64,
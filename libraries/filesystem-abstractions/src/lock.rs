@@ -0,0 +1,586 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use constants::ErrNo;
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+
+use crate::FileKey;
+
+/// Who a lock belongs to, locks of the same owner never conflict with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockOwner {
+    /// POSIX record locks belong to the process with this id
+    Process(u32),
+    /// OFD record locks and `flock` locks belong to an open file description, see
+    /// [`crate::FileMetadata::lock_owner`]
+    File(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+/// A lock on the bytes `start..end` of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    pub owner: LockOwner,
+    pub kind: LockKind,
+    pub start: u64,
+    /// One past the last byte, `u64::MAX` for up to the end of the file however large it grows
+    pub end: u64,
+    /// The process that placed the lock, reported by `F_GETLK`
+    pub pid: u32,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts_with(&self, other: &RecordLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.kind == LockKind::Exclusive || other.kind == LockKind::Exclusive)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+    /// A conflicting lock is held by someone else
+    WouldBlock,
+    /// Waiting would never end as the holder waits for the caller
+    Deadlock,
+}
+
+impl LockError {
+    pub fn to_errno(self) -> ErrNo {
+        match self {
+            LockError::WouldBlock => ErrNo::ResourceTemporarilyUnavailable,
+            LockError::Deadlock => ErrNo::ResourceDeadlockAvoided,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FileLocks {
+    /// `flock` locks by the open file description holding them
+    flocks: Vec<(usize, LockKind)>,
+    /// POSIX and OFD record locks, the ones of an owner never overlap
+    records: Vec<RecordLock>,
+}
+
+impl FileLocks {
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty()
+    }
+
+    fn conflicting(&self, lock: &RecordLock) -> Option<RecordLock> {
+        self.records
+            .iter()
+            .find(|r| r.conflicts_with(lock))
+            .copied()
+    }
+
+    /// Takes the range `start..end` out of the locks of `owner`, returns whether any were hit.
+    fn cut(&mut self, owner: LockOwner, start: u64, end: u64) -> bool {
+        let mut cut = false;
+        let mut kept = Vec::with_capacity(self.records.len());
+
+        for record in self.records.drain(..) {
+            if record.owner != owner || !record.overlaps(start, end) {
+                kept.push(record);
+                continue;
+            }
+
+            cut = true;
+
+            if record.start < start {
+                kept.push(RecordLock {
+                    end: start,
+                    ..record
+                });
+            }
+
+            if record.end > end {
+                kept.push(RecordLock {
+                    start: end,
+                    ..record
+                });
+            }
+        }
+
+        self.records = kept;
+
+        cut
+    }
+
+    /// Places `lock` over whatever its owner held in its range, merging it with adjacent locks of
+    /// the same kind.
+    fn insert(&mut self, lock: RecordLock) {
+        self.cut(lock.owner, lock.start, lock.end);
+
+        let mut merged = lock;
+
+        self.records.retain(|record| {
+            let touches = record.start <= merged.end && merged.start <= record.end;
+
+            if record.owner != lock.owner || record.kind != lock.kind || !touches {
+                return true;
+            }
+
+            merged.start = merged.start.min(record.start);
+            merged.end = merged.end.max(record.end);
+
+            false
+        });
+
+        self.records.push(merged);
+    }
+}
+
+#[derive(Default)]
+struct LockManagerInner {
+    files: BTreeMap<FileKey, FileLocks>,
+    /// The lock each blocked process waits for, to find deadlocks
+    blocked: BTreeMap<u32, (FileKey, RecordLock)>,
+}
+
+impl LockManagerInner {
+    /// Whether `pid` waiting for a lock held by `holder` closes a cycle of waiting processes.
+    fn would_deadlock(&self, pid: u32, mut holder: LockOwner) -> bool {
+        // Every step goes to another blocked process, more steps than that means a cycle
+        for _ in 0..=self.blocked.len() {
+            let LockOwner::Process(holder_pid) = holder else {
+                return false;
+            };
+
+            if holder_pid == pid {
+                return true;
+            }
+
+            let Some((key, wanted)) = self.blocked.get(&holder_pid) else {
+                return false;
+            };
+
+            match self.files.get(key).and_then(|f| f.conflicting(wanted)) {
+                Some(next) => holder = next.owner,
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    fn remove_empty(&mut self, key: FileKey) {
+        if self.files.get(&key).is_some_and(FileLocks::is_empty) {
+            self.files.remove(&key);
+        }
+    }
+}
+
+/// The advisory locks on all files, keyed by the inode they are on.
+///
+/// `flock` locks cover whole files and are independent of record locks. POSIX and OFD record locks
+/// share the byte ranges and differ only in their owner.
+#[derive(Default)]
+pub struct FileLockManager {
+    inner: SpinMutex<LockManagerInner>,
+    released: WaitQueue,
+}
+
+impl FileLockManager {
+    pub fn new() -> FileLockManager {
+        FileLockManager::default()
+    }
+
+    /// The first lock held by someone else that conflicts with `lock`, for `F_GETLK`.
+    pub fn conflicting(&self, key: FileKey, lock: &RecordLock) -> Option<RecordLock> {
+        self.inner.lock().files.get(&key)?.conflicting(lock)
+    }
+
+    /// Places `lock` unless it conflicts with a lock held by someone else.
+    pub fn try_lock_records(&self, key: FileKey, lock: RecordLock) -> Result<(), LockError> {
+        let mut inner = self.inner.lock();
+        let file = inner.files.entry(key).or_default();
+
+        if file.conflicting(&lock).is_some() {
+            inner.remove_empty(key);
+            return Err(LockError::WouldBlock);
+        }
+
+        file.insert(lock);
+        drop(inner);
+
+        // Turning an exclusive lock into a shared one lets readers in
+        self.released.notify_all();
+
+        Ok(())
+    }
+
+    /// Places `lock`, waiting until conflicting locks are released. Processes waiting for each
+    /// other fail with [`LockError::Deadlock`] instead.
+    pub async fn lock_records(&self, key: FileKey, lock: RecordLock) -> Result<(), LockError> {
+        let _blocked = BlockedGuard {
+            manager: self,
+            owner: lock.owner,
+        };
+
+        let result = self
+            .released
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+                let file = inner.files.entry(key).or_default();
+
+                let Some(holder) = file.conflicting(&lock) else {
+                    file.insert(lock);
+                    return Some(Ok(()));
+                };
+
+                if let LockOwner::Process(pid) = lock.owner {
+                    if inner.would_deadlock(pid, holder.owner) {
+                        return Some(Err(LockError::Deadlock));
+                    }
+
+                    inner.blocked.insert(pid, (key, lock));
+                }
+
+                None
+            })
+            .await;
+
+        self.released.notify_all();
+
+        result
+    }
+
+    /// Releases the range `start..end` of the locks of `owner`.
+    pub fn unlock_records(&self, key: FileKey, owner: LockOwner, start: u64, end: u64) {
+        let mut inner = self.inner.lock();
+
+        let cut = inner
+            .files
+            .get_mut(&key)
+            .is_some_and(|file| file.cut(owner, start, end));
+
+        inner.remove_empty(key);
+        drop(inner);
+
+        if cut {
+            self.released.notify_all();
+        }
+    }
+
+    /// Takes a `flock` lock for the open file description `owner`, replacing the one it had.
+    ///
+    /// Like on Linux, converting a lock first releases the old one, so it is lost if the new one
+    /// can not be taken.
+    pub fn try_flock(&self, key: FileKey, owner: usize, kind: LockKind) -> Result<(), LockError> {
+        let mut inner = self.inner.lock();
+        let result = Self::flock_internal(inner.files.entry(key).or_default(), owner, kind);
+
+        inner.remove_empty(key);
+        drop(inner);
+
+        self.released.notify_all();
+
+        result
+    }
+
+    /// Takes a `flock` lock like [`FileLockManager::try_flock`], waiting until it can.
+    pub async fn flock(&self, key: FileKey, owner: usize, kind: LockKind) {
+        self.unflock(key, owner);
+
+        self.released
+            .wait_until(|| {
+                let mut inner = self.inner.lock();
+                Self::flock_internal(inner.files.entry(key).or_default(), owner, kind).is_ok()
+            })
+            .await;
+
+        self.released.notify_all();
+    }
+
+    fn flock_internal(file: &mut FileLocks, owner: usize, kind: LockKind) -> Result<(), LockError> {
+        file.flocks.retain(|(holder, _)| *holder != owner);
+
+        let conflict = file
+            .flocks
+            .iter()
+            .any(|(_, held)| *held == LockKind::Exclusive || kind == LockKind::Exclusive);
+
+        match conflict {
+            true => Err(LockError::WouldBlock),
+            false => {
+                file.flocks.push((owner, kind));
+                Ok(())
+            }
+        }
+    }
+
+    /// Releases the `flock` lock of the open file description `owner`.
+    pub fn unflock(&self, key: FileKey, owner: usize) {
+        let mut inner = self.inner.lock();
+
+        if let Some(file) = inner.files.get_mut(&key) {
+            file.flocks.retain(|(holder, _)| *holder != owner);
+        }
+
+        inner.remove_empty(key);
+        drop(inner);
+
+        self.released.notify_all();
+    }
+
+    /// Releases the POSIX locks of the process on the file, which happens when it closes any
+    /// descriptor of it.
+    pub fn release_process_file(&self, key: FileKey, pid: u32) {
+        self.unlock_records(key, LockOwner::Process(pid), 0, u64::MAX);
+    }
+
+    /// Releases all POSIX locks of an exiting process.
+    pub fn release_process(&self, pid: u32) {
+        self.release(|owner| owner == LockOwner::Process(pid));
+    }
+
+    /// Releases the OFD and `flock` locks of an open file description that was closed for good.
+    pub fn release_file(&self, file: usize) {
+        self.release(|owner| owner == LockOwner::File(file));
+    }
+
+    fn release(&self, matches: impl Fn(LockOwner) -> bool) {
+        let mut inner = self.inner.lock();
+
+        for file in inner.files.values_mut() {
+            file.records.retain(|record| !matches(record.owner));
+            file.flocks
+                .retain(|(holder, _)| !matches(LockOwner::File(*holder)));
+        }
+
+        inner.files.retain(|_, file| !file.is_empty());
+        drop(inner);
+
+        self.released.notify_all();
+    }
+}
+
+/// Forgets what a process waits for once it stops waiting, however that happens.
+struct BlockedGuard<'a> {
+    manager: &'a FileLockManager,
+    owner: LockOwner,
+}
+
+impl Drop for BlockedGuard<'_> {
+    fn drop(&mut self) {
+        if let LockOwner::Process(pid) = self.owner {
+            self.manager.inner.lock().blocked.remove(&pid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::*;
+
+    const KEY: FileKey = FileKey { mount: 1, inode: 2 };
+
+    fn posix(pid: u32, kind: LockKind, start: u64, end: u64) -> RecordLock {
+        RecordLock {
+            owner: LockOwner::Process(pid),
+            kind,
+            start,
+            end,
+            pid,
+        }
+    }
+
+    fn poll<F: Future>(future: core::pin::Pin<&mut F>) -> Poll<F::Output> {
+        future.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    fn ranges(manager: &FileLockManager, owner: LockOwner) -> Vec<(u64, u64, LockKind)> {
+        let mut ranges: Vec<_> = manager.inner.lock().files[&KEY]
+            .records
+            .iter()
+            .filter(|r| r.owner == owner)
+            .map(|r| (r.start, r.end, r.kind))
+            .collect();
+        ranges.sort_by_key(|r| r.0);
+
+        ranges
+    }
+
+    #[test]
+    fn test_shared_and_exclusive() {
+        let manager = FileLockManager::new();
+
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Shared, 0, 100))
+            .unwrap();
+        manager
+            .try_lock_records(KEY, posix(2, LockKind::Shared, 50, 150))
+            .unwrap();
+
+        assert_eq!(
+            manager.try_lock_records(KEY, posix(3, LockKind::Exclusive, 120, 130)),
+            Err(LockError::WouldBlock)
+        );
+        assert_eq!(
+            manager
+                .conflicting(KEY, &posix(3, LockKind::Exclusive, 0, 10))
+                .map(|l| l.pid),
+            Some(1)
+        );
+        assert!(manager
+            .conflicting(KEY, &posix(3, LockKind::Exclusive, 150, u64::MAX))
+            .is_none());
+
+        // The own locks of a process never stand in its way
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Exclusive, 0, 40))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let manager = FileLockManager::new();
+        let owner = LockOwner::Process(1);
+
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Exclusive, 0, 100))
+            .unwrap();
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Shared, 40, 60))
+            .unwrap();
+        assert_eq!(
+            ranges(&manager, owner),
+            [
+                (0, 40, LockKind::Exclusive),
+                (40, 60, LockKind::Shared),
+                (60, 100, LockKind::Exclusive)
+            ]
+        );
+
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Exclusive, 40, 60))
+            .unwrap();
+        assert_eq!(ranges(&manager, owner), [(0, 100, LockKind::Exclusive)]);
+
+        manager.unlock_records(KEY, owner, 10, 20);
+        assert_eq!(
+            ranges(&manager, owner),
+            [(0, 10, LockKind::Exclusive), (20, 100, LockKind::Exclusive)]
+        );
+
+        manager.release_process_file(KEY, 1);
+        assert!(manager.inner.lock().files.is_empty());
+    }
+
+    #[test]
+    fn test_ofd_and_posix_conflict() {
+        let manager = FileLockManager::new();
+        let ofd = RecordLock {
+            owner: LockOwner::File(0x1000),
+            ..posix(1, LockKind::Exclusive, 0, u64::MAX)
+        };
+
+        manager.try_lock_records(KEY, ofd).unwrap();
+
+        // Another description of the same process is someone else for OFD locks
+        assert_eq!(
+            manager.try_lock_records(
+                KEY,
+                RecordLock {
+                    owner: LockOwner::File(0x2000),
+                    ..ofd
+                }
+            ),
+            Err(LockError::WouldBlock)
+        );
+        assert_eq!(
+            manager.try_lock_records(KEY, posix(1, LockKind::Shared, 0, 1)),
+            Err(LockError::WouldBlock)
+        );
+
+        manager.release_process(1);
+        assert!(manager
+            .conflicting(KEY, &posix(1, LockKind::Shared, 0, 1))
+            .is_some());
+
+        manager.release_file(0x1000);
+        assert!(manager
+            .conflicting(KEY, &posix(1, LockKind::Shared, 0, 1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_waiter_wakes_on_release() {
+        let manager = Arc::new(FileLockManager::new());
+
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Exclusive, 0, 10))
+            .unwrap();
+
+        let mut waiter = pin!(manager.lock_records(KEY, posix(2, LockKind::Shared, 5, 6)));
+        assert!(poll(waiter.as_mut()).is_pending());
+
+        manager.release_process(1);
+        assert_eq!(poll(waiter.as_mut()), Poll::Ready(Ok(())));
+        assert!(manager.inner.lock().blocked.is_empty());
+    }
+
+    #[test]
+    fn test_deadlock_detected() {
+        let manager = FileLockManager::new();
+
+        manager
+            .try_lock_records(KEY, posix(1, LockKind::Exclusive, 0, 10))
+            .unwrap();
+        manager
+            .try_lock_records(KEY, posix(2, LockKind::Exclusive, 10, 20))
+            .unwrap();
+
+        let mut first = pin!(manager.lock_records(KEY, posix(1, LockKind::Exclusive, 10, 20)));
+        assert!(poll(first.as_mut()).is_pending());
+
+        let mut second = pin!(manager.lock_records(KEY, posix(2, LockKind::Exclusive, 0, 10)));
+        assert_eq!(poll(second.as_mut()), Poll::Ready(Err(LockError::Deadlock)));
+    }
+
+    #[test]
+    fn test_flock() {
+        let manager = FileLockManager::new();
+
+        manager.try_flock(KEY, 1, LockKind::Shared).unwrap();
+        manager.try_flock(KEY, 2, LockKind::Shared).unwrap();
+        assert_eq!(
+            manager.try_flock(KEY, 3, LockKind::Exclusive),
+            Err(LockError::WouldBlock)
+        );
+
+        // Independent of record locks
+        manager
+            .try_lock_records(KEY, posix(9, LockKind::Exclusive, 0, u64::MAX))
+            .unwrap();
+
+        let mut waiter = pin!(manager.flock(KEY, 3, LockKind::Exclusive));
+        assert!(poll(waiter.as_mut()).is_pending());
+
+        manager.unflock(KEY, 1);
+        assert!(poll(waiter.as_mut()).is_pending());
+
+        manager.release_file(2);
+        assert_eq!(poll(waiter.as_mut()), Poll::Ready(()));
+        assert_eq!(
+            manager.try_flock(KEY, 1, LockKind::Shared),
+            Err(LockError::WouldBlock)
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use bitflags::bitflags;
+use hermit_sync::SpinMutex;
+
+use crate::{FileSystemResult, IFileSystem, PageCache};
+
+bitflags! {
+    /// Flags of `mount(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MountFlags: usize {
+        const MS_RDONLY      = 1;
+        const MS_NOSUID      = 2;
+        const MS_NODEV       = 4;
+        const MS_NOEXEC      = 8;
+        const MS_SYNCHRONOUS = 16;
+        const MS_REMOUNT     = 32;
+        const MS_MANDLOCK    = 64;
+        const MS_DIRSYNC     = 128;
+        const MS_NOSYMFOLLOW = 256;
+        const MS_NOATIME     = 1024;
+        const MS_NODIRATIME  = 2048;
+        const MS_BIND        = 4096;
+        const MS_MOVE        = 8192;
+        const MS_REC         = 16384;
+        const MS_SILENT      = 32768;
+        const MS_RELATIME    = 1 << 21;
+        const MS_STRICTATIME = 1 << 24;
+        const MS_LAZYTIME    = 1 << 25;
+    }
+}
+
+impl MountFlags {
+    /// Flags that belong to a mount and show up in its options, the others only say what to do
+    pub const PER_MOUNT: MountFlags = MountFlags::MS_RDONLY
+        .union(MountFlags::MS_NOSUID)
+        .union(MountFlags::MS_NODEV)
+        .union(MountFlags::MS_NOEXEC)
+        .union(MountFlags::MS_NOATIME)
+        .union(MountFlags::MS_NODIRATIME)
+        .union(MountFlags::MS_RELATIME);
+}
+
+bitflags! {
+    /// Flags of `umount2(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UnmountFlags: usize {
+        const MNT_FORCE       = 1;
+        /// Take the mount out of the tree now, it goes away once it is no longer used
+        const MNT_DETACH      = 2;
+        const MNT_EXPIRE      = 4;
+        const UMOUNT_NOFOLLOW = 8;
+    }
+}
+
+/// A kind of filesystem `mount(2)` can create, by the name it is given as the type.
+pub trait IFileSystemType: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Creates the filesystem to mount from `source`, which names the device for filesystems on
+    /// disks and is ignored by the others.
+    fn create(&self, source: &str, flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>>;
+
+    /// Whether regular files of this type go through the page cache, which is not the case for
+    /// files generated each time they are read.
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// A line of `/proc/mounts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    /// What was mounted, the device or the path of a bind mount
+    pub source: String,
+    /// Full path of the mount point
+    pub target: String,
+    pub fs_type: String,
+    /// Only [`MountFlags::PER_MOUNT`] ones
+    pub flags: MountFlags,
+}
+
+impl MountEntry {
+    /// The flags as the options of `/proc/mounts`.
+    pub fn options(&self) -> String {
+        let mut options = String::from(match self.flags.contains(MountFlags::MS_RDONLY) {
+            true => "ro",
+            false => "rw",
+        });
+
+        for (flag, option) in [
+            (MountFlags::MS_NOSUID, ",nosuid"),
+            (MountFlags::MS_NODEV, ",nodev"),
+            (MountFlags::MS_NOEXEC, ",noexec"),
+            (MountFlags::MS_NOATIME, ",noatime"),
+            (MountFlags::MS_NODIRATIME, ",nodiratime"),
+            (MountFlags::MS_RELATIME, ",relatime"),
+        ] {
+            if self.flags.contains(flag) {
+                options.push_str(option);
+            }
+        }
+
+        options
+    }
+}
+
+/// The filesystems of a kernel: the types it can mount and what is mounted where.
+pub struct MountTable {
+    types: SpinMutex<BTreeMap<String, Arc<dyn IFileSystemType>>>,
+    /// In the order they were mounted, so the last one at a path is the one that is visible
+    mounts: SpinMutex<Vec<MountEntry>>,
+    page_cache: Option<Arc<PageCache>>,
+}
+
+impl MountTable {
+    pub fn new(page_cache: Option<Arc<PageCache>>) -> MountTable {
+        MountTable {
+            types: SpinMutex::new(BTreeMap::new()),
+            mounts: SpinMutex::new(Vec::new()),
+            page_cache,
+        }
+    }
+
+    /// Where the regular files of cacheable filesystems go through.
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.page_cache.clone()
+    }
+
+    /// Makes `fs_type` available to `mount(2)`, replacing any type of the same name.
+    pub fn register(&self, fs_type: Arc<dyn IFileSystemType>) {
+        self.types
+            .lock()
+            .insert(String::from(fs_type.name()), fs_type);
+    }
+
+    pub fn file_system_type(&self, name: &str) -> Option<Arc<dyn IFileSystemType>> {
+        self.types.lock().get(name).cloned()
+    }
+
+    pub fn add(&self, entry: MountEntry) {
+        self.mounts.lock().push(entry);
+    }
+
+    /// The visible mount at `target`.
+    pub fn get(&self, target: &str) -> Option<MountEntry> {
+        self.mounts
+            .lock()
+            .iter()
+            .rev()
+            .find(|entry| entry.target == target)
+            .cloned()
+    }
+
+    /// The mount holding the file at `path`, the one at its longest prefix.
+    pub fn containing(&self, path: &str) -> Option<MountEntry> {
+        self.mounts
+            .lock()
+            .iter()
+            .rev()
+            .filter(|entry| is_below(path, &entry.target))
+            .max_by_key(|entry| entry.target.len())
+            .cloned()
+    }
+
+    /// Mounts below `target`, not counting the ones at it.
+    pub fn submounts(&self, target: &str) -> Vec<MountEntry> {
+        self.mounts
+            .lock()
+            .iter()
+            .filter(|entry| entry.target != target && is_below(&entry.target, target))
+            .cloned()
+            .collect()
+    }
+
+    /// Changes the flags of the visible mount at `target`, returns false if there is none.
+    pub fn set_flags(&self, target: &str, flags: MountFlags) -> bool {
+        let mut mounts = self.mounts.lock();
+
+        match mounts.iter_mut().rev().find(|entry| entry.target == target) {
+            Some(entry) => {
+                entry.flags = flags & MountFlags::PER_MOUNT;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets the visible mount at `target` and, if `recursive`, everything mounted below it.
+    pub fn remove(&self, target: &str, recursive: bool) -> Option<MountEntry> {
+        let mut mounts = self.mounts.lock();
+
+        let index = mounts.iter().rposition(|entry| entry.target == target)?;
+        let removed = mounts.remove(index);
+
+        if recursive {
+            mounts.retain(|entry| entry.target == target || !is_below(&entry.target, target));
+        }
+
+        Some(removed)
+    }
+
+    /// Everything mounted, in the order it was mounted.
+    pub fn entries(&self) -> Vec<MountEntry> {
+        self.mounts.lock().clone()
+    }
+}
+
+/// Whether `path` is `dir` or something below it, both being full paths.
+fn is_below(path: &str, dir: &str) -> bool {
+    match path.strip_prefix(dir) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || dir.ends_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn entry(target: &str, fs_type: &str) -> MountEntry {
+        MountEntry {
+            source: fs_type.to_string(),
+            target: target.to_string(),
+            fs_type: fs_type.to_string(),
+            flags: MountFlags::empty(),
+        }
+    }
+
+    #[test]
+    fn test_stacked_and_nested_mounts() {
+        let table = MountTable::new(None);
+
+        table.add(entry("/", "tmpfs"));
+        table.add(entry("/mnt", "ext4"));
+        table.add(entry("/mnt/data", "vfat"));
+        table.add(entry("/mnt", "proc"));
+        table.add(entry("/mntx", "tmpfs"));
+
+        assert_eq!(table.get("/mnt").unwrap().fs_type, "proc");
+        assert_eq!(table.containing("/mnt/data/file").unwrap().fs_type, "vfat");
+        assert_eq!(table.containing("/mntx/file").unwrap().target, "/mntx");
+        assert_eq!(table.containing("/etc").unwrap().target, "/");
+        assert_eq!(table.submounts("/mnt"), [entry("/mnt/data", "vfat")]);
+
+        assert!(table.set_flags("/mnt", MountFlags::MS_RDONLY | MountFlags::MS_REMOUNT));
+        assert_eq!(table.get("/mnt").unwrap().options(), "ro");
+
+        // The one mounted first shows up again
+        assert_eq!(table.remove("/mnt", false).unwrap().fs_type, "proc");
+        assert_eq!(table.get("/mnt").unwrap().fs_type, "ext4");
+
+        table.remove("/mnt", true);
+        assert_eq!(
+            table.entries(),
+            [entry("/", "tmpfs"), entry("/mntx", "tmpfs")]
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use alloc::{boxed::Box, sync::Arc, vec};
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+
+use crate::{FileSystemError, FileSystemResult, IFile, OpenFlags};
+
+/// Bytes a pipe holds before writers have to wait, 16 pages like Linux
+pub const PIPE_CAPACITY: usize = 65536;
+
+/// The bytes written into a pipe and not read yet, in a ring.
+struct PipeBuffer {
+    data: Box<[u8]>,
+    head: usize,
+    len: usize,
+    readers: usize,
+    writers: usize,
+}
+
+impl PipeBuffer {
+    /// The queued bytes from the oldest on, `skip` bytes in, as one contiguous part of the ring.
+    fn filled(&self, skip: usize) -> &[u8] {
+        let start = (self.head + skip) % self.data.len();
+        let len = Ord::min(self.len - skip, self.data.len() - start);
+
+        &self.data[start..start + len]
+    }
+
+    /// The free space after the queued bytes, as one contiguous part of the ring.
+    fn free(&mut self) -> &mut [u8] {
+        let capacity = self.data.len();
+        let start = (self.head + self.len) % capacity;
+        let len = Ord::min(capacity - self.len, capacity - start);
+
+        &mut self.data[start..start + len]
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.head = (self.head + len) % self.data.len();
+        self.len -= len;
+
+        // Keeps the free space in one piece as long as possible
+        if self.len == 0 {
+            self.head = 0;
+        }
+    }
+}
+
+/// What the two ends of a pipe share.
+struct PipeShared {
+    buffer: SpinMutex<PipeBuffer>,
+    // Notified when something is queued or the write end closes
+    readable: WaitQueue,
+    // Notified when something is taken out or the read end closes
+    writable: WaitQueue,
+}
+
+/// One end of a pipe, made in pairs by [`Pipe::new`].
+///
+/// Besides `read` and `write`, the buffer is lent out by [`Pipe::read_with`] and
+/// [`Pipe::write_with`], so `splice` moves data between a pipe and a file with a single copy.
+pub struct Pipe {
+    shared: Arc<PipeShared>,
+    writable: bool,
+    flags: SpinMutex<OpenFlags>,
+}
+
+impl Pipe {
+    /// Creates a pipe, returning its read end and its write end.
+    pub fn new(flags: OpenFlags) -> (Arc<Pipe>, Arc<Pipe>) {
+        let shared = Arc::new(PipeShared {
+            buffer: SpinMutex::new(PipeBuffer {
+                data: vec![0; PIPE_CAPACITY].into_boxed_slice(),
+                head: 0,
+                len: 0,
+                readers: 1,
+                writers: 1,
+            }),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+        });
+
+        let end = |writable: bool, access: OpenFlags| {
+            Arc::new(Pipe {
+                shared: shared.clone(),
+                writable,
+                flags: SpinMutex::new(flags | access),
+            })
+        };
+
+        (
+            end(false, OpenFlags::O_RDONLY),
+            end(true, OpenFlags::O_WRONLY),
+        )
+    }
+
+    /// Whether this is the write end.
+    pub fn is_write_end(&self) -> bool {
+        self.writable
+    }
+
+    /// Whether both are ends of the same pipe.
+    pub fn is_same_pipe(&self, other: &Pipe) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// How many bytes are queued.
+    pub fn len(&self) -> usize {
+        self.shared.buffer.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the write end is still open, reading an empty pipe without it is the end of file.
+    pub fn has_writers(&self) -> bool {
+        self.shared.buffer.lock().writers > 0
+    }
+
+    /// Whether the read end is still open, writing without it is a broken pipe.
+    pub fn has_readers(&self) -> bool {
+        self.shared.buffer.lock().readers > 0
+    }
+
+    /// Suspends until reading would not block, see [`IFile::read_avaliable`].
+    pub async fn wait_readable(&self) {
+        self.shared
+            .readable
+            .wait_until(|| self.read_avaliable())
+            .await
+    }
+
+    /// Suspends until writing would not block, see [`IFile::write_avaliable`].
+    pub async fn wait_writable(&self) {
+        self.shared
+            .writable
+            .wait_until(|| self.write_avaliable())
+            .await
+    }
+
+    /// Lends up to `max` queued bytes to `f`, in as many parts as the ring has them, and takes out
+    /// what it reports to have used. Stops at the first part `f` does not use completely.
+    ///
+    /// Returns how many bytes were taken, an error of `f` only if nothing was.
+    pub fn read_with(
+        &self,
+        max: usize,
+        mut f: impl FnMut(&[u8]) -> FileSystemResult<usize>,
+    ) -> FileSystemResult<usize> {
+        let mut buffer = self.shared.buffer.lock();
+        let mut taken = 0;
+
+        while taken < max && buffer.len > 0 {
+            let part = buffer.filled(0);
+            let len = Ord::min(part.len(), max - taken);
+
+            let used = match f(&part[..len]) {
+                Ok(used) => used,
+                Err(e) if taken == 0 => return Err(e),
+                Err(_) => break,
+            };
+
+            buffer.consume(used);
+            taken += used;
+
+            if used < len {
+                break;
+            }
+        }
+
+        drop(buffer);
+
+        if taken > 0 {
+            self.shared.writable.notify_all();
+        }
+
+        Ok(taken)
+    }
+
+    /// Like [`Pipe::read_with`], but leaves the bytes queued, which is how `tee` copies them.
+    pub fn peek_with(
+        &self,
+        max: usize,
+        mut f: impl FnMut(&[u8]) -> FileSystemResult<usize>,
+    ) -> FileSystemResult<usize> {
+        let buffer = self.shared.buffer.lock();
+        let mut seen = 0;
+
+        while seen < Ord::min(max, buffer.len) {
+            let part = buffer.filled(seen);
+            let len = Ord::min(part.len(), max - seen);
+
+            let used = match f(&part[..len]) {
+                Ok(used) => used,
+                Err(e) if seen == 0 => return Err(e),
+                Err(_) => break,
+            };
+
+            seen += used;
+
+            if used < len {
+                break;
+            }
+        }
+
+        Ok(seen)
+    }
+
+    /// Lends up to `max` bytes of free space to `f`, in as many parts as the ring has it, and
+    /// queues what it reports to have filled. Stops at the first part `f` does not fill.
+    ///
+    /// Returns how many bytes were queued, an error of `f` only if nothing was. Fails with
+    /// `BrokenPipe` if nothing reads from the pipe anymore.
+    pub fn write_with(
+        &self,
+        max: usize,
+        mut f: impl FnMut(&mut [u8]) -> FileSystemResult<usize>,
+    ) -> FileSystemResult<usize> {
+        let mut buffer = self.shared.buffer.lock();
+
+        if buffer.readers == 0 {
+            return Err(FileSystemError::BrokenPipe);
+        }
+
+        let mut queued = 0;
+
+        while queued < max {
+            let part = buffer.free();
+            let len = Ord::min(part.len(), max - queued);
+
+            if len == 0 {
+                break;
+            }
+
+            let filled = match f(&mut part[..len]) {
+                Ok(filled) => filled,
+                Err(e) if queued == 0 => return Err(e),
+                Err(_) => break,
+            };
+
+            buffer.len += filled;
+            queued += filled;
+
+            if filled < len {
+                break;
+            }
+        }
+
+        drop(buffer);
+
+        if queued > 0 {
+            self.shared.readable.notify_all();
+        }
+
+        Ok(queued)
+    }
+
+    /// Copies up to `max` queued bytes into `other` without taking them out, for `tee`. The two
+    /// must not be the same pipe.
+    pub fn tee_into(&self, other: &Pipe, max: usize) -> FileSystemResult<usize> {
+        debug_assert!(!self.is_same_pipe(other));
+
+        self.peek_with(max, |data| other.push(data))
+    }
+
+    /// Queues as much of `data` as there is room for.
+    pub(crate) fn push(&self, data: &[u8]) -> FileSystemResult<usize> {
+        let mut pushed = 0;
+
+        self.write_with(data.len(), |part| {
+            part.copy_from_slice(&data[pushed..pushed + part.len()]);
+            pushed += part.len();
+
+            Ok(part.len())
+        })
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock();
+
+        // Whoever waits on the other end finds the end of file or a broken pipe
+        let waiters = match self.writable {
+            true => {
+                buffer.writers -= 1;
+                &self.shared.readable
+            }
+            false => {
+                buffer.readers -= 1;
+                &self.shared.writable
+            }
+        };
+
+        drop(buffer);
+        waiters.notify_all();
+    }
+}
+
+impl IFile for Pipe {
+    fn can_read(&self) -> bool {
+        !self.writable
+    }
+
+    fn can_write(&self) -> bool {
+        self.writable
+    }
+
+    /// Something is queued, or nothing will be anymore and reading gives the end of file.
+    fn read_avaliable(&self) -> bool {
+        let buffer = self.shared.buffer.lock();
+
+        buffer.len > 0 || buffer.writers == 0
+    }
+
+    /// There is room, or nothing reads anymore and writing fails right away.
+    fn write_avaliable(&self) -> bool {
+        let buffer = self.shared.buffer.lock();
+
+        buffer.len < buffer.data.len() || buffer.readers == 0
+    }
+
+    fn flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+
+    fn set_flags(&self, new_flags: OpenFlags) -> bool {
+        *self.flags.lock() = new_flags;
+        true
+    }
+
+    fn is_dir(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> usize {
+        if self.writable {
+            return 0;
+        }
+
+        let mut read = 0;
+
+        self.read_with(buf.len(), |part| {
+            buf[read..read + part.len()].copy_from_slice(part);
+            read += part.len();
+
+            Ok(part.len())
+        })
+        .unwrap_or(0)
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        if !self.writable {
+            return 0;
+        }
+
+        self.push(buf).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{task::Wake, vec::Vec};
+    use core::{
+        future::Future,
+        pin::pin,
+        sync::atomic::{AtomicBool, Ordering},
+        task::{Context, Waker},
+    };
+
+    use super::*;
+
+    struct WokenFlag(AtomicBool);
+
+    impl Wake for WokenFlag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_wraps_around_the_ring() {
+        let (reader, writer) = Pipe::new(OpenFlags::NONE);
+
+        let first = vec![1u8; PIPE_CAPACITY - 10];
+        assert_eq!(writer.write(&first), first.len());
+        assert_eq!(
+            reader.read(&mut vec![0; PIPE_CAPACITY - 20]),
+            PIPE_CAPACITY - 20
+        );
+
+        // 10 bytes are left near the end, these go past it
+        let second: Vec<u8> = (0..100).collect();
+        assert_eq!(writer.write(&second), 100);
+        assert_eq!(reader.len(), 110);
+
+        let mut parts = Vec::new();
+        assert_eq!(
+            reader.peek_with(usize::MAX, |part| {
+                parts.push(part.len());
+                Ok(part.len())
+            }),
+            Ok(110)
+        );
+        assert_eq!(parts, [20, 90]);
+
+        let mut buf = [0; 110];
+        assert_eq!(reader.read(&mut buf), 110);
+        assert_eq!(&buf[..10], &[1; 10]);
+        assert_eq!(&buf[10..], &second[..]);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_closed_ends() {
+        let (reader, writer) = Pipe::new(OpenFlags::NONE);
+
+        assert!(!reader.read_avaliable());
+        assert_eq!(writer.write(b"data"), 4);
+        drop(writer);
+
+        // What was written is still read before the end of file
+        let mut buf = [0; 8];
+        assert!(reader.read_avaliable());
+        assert_eq!(reader.read(&mut buf), 4);
+        assert!(reader.read_avaliable());
+        assert_eq!(reader.read(&mut buf), 0);
+
+        let (reader, writer) = Pipe::new(OpenFlags::NONE);
+        drop(reader);
+
+        assert!(writer.write_avaliable());
+        assert_eq!(
+            writer.write_with(4, |_| Ok(4)),
+            Err(FileSystemError::BrokenPipe)
+        );
+    }
+
+    #[test]
+    fn test_waiters_are_woken() {
+        let (reader, writer) = Pipe::new(OpenFlags::NONE);
+        let woken = Arc::new(WokenFlag(AtomicBool::new(false)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        {
+            let mut readable = pin!(reader.wait_readable());
+            assert!(readable.as_mut().poll(&mut cx).is_pending());
+
+            assert_eq!(writer.write(b"data"), 4);
+            assert!(woken.0.swap(false, Ordering::Relaxed));
+            assert!(readable.as_mut().poll(&mut cx).is_ready());
+        }
+
+        assert_eq!(writer.write(&vec![0; PIPE_CAPACITY]), PIPE_CAPACITY - 4);
+
+        {
+            let mut writable = pin!(writer.wait_writable());
+            assert!(writable.as_mut().poll(&mut cx).is_pending());
+
+            assert_eq!(reader.read(&mut [0; 1]), 1);
+            assert!(woken.0.swap(false, Ordering::Relaxed));
+            assert!(writable.as_mut().poll(&mut cx).is_ready());
+        }
+
+        assert_eq!(writer.write(&[0]), 1);
+
+        // Closing the read end breaks the pipe, which wakes the writers too
+        let mut writable = pin!(writer.wait_writable());
+        assert!(writable.as_mut().poll(&mut cx).is_pending());
+
+        drop(reader);
+        assert!(woken.0.swap(false, Ordering::Relaxed));
+        assert!(writable.as_mut().poll(&mut cx).is_ready());
+    }
+}
@@ -0,0 +1,33 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Seals of a memfd, set by `fcntl(F_ADD_SEALS)`. Once set, a seal can't be taken off.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FileSeals: u32 {
+        /// No more seals can be added
+        const F_SEAL_SEAL         = 0x1;
+        const F_SEAL_SHRINK       = 0x2;
+        const F_SEAL_GROW         = 0x4;
+        /// Nothing can write the contents, there must be no writable shared mapping when sealing
+        const F_SEAL_WRITE        = 0x8;
+        /// Like `F_SEAL_WRITE`, but writable mappings made before keep working
+        const F_SEAL_FUTURE_WRITE = 0x10;
+    }
+}
+
+impl FileSeals {
+    /// Seals that stop writes to the contents.
+    pub const WRITES: FileSeals = FileSeals::F_SEAL_WRITE.union(FileSeals::F_SEAL_FUTURE_WRITE);
+
+    /// Whether a write of `len` bytes at `offset` to a file of `size` bytes is allowed.
+    pub fn allows_write(self, offset: usize, len: usize, size: usize) -> bool {
+        !self.intersects(FileSeals::WRITES)
+            && (!self.contains(FileSeals::F_SEAL_GROW) || offset.saturating_add(len) <= size)
+    }
+
+    /// Whether a file of `size` bytes may be resized to `new_size`.
+    pub fn allows_resize(self, size: usize, new_size: usize) -> bool {
+        !(new_size < size && self.contains(FileSeals::F_SEAL_SHRINK)
+            || new_size > size && self.contains(FileSeals::F_SEAL_GROW))
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::{sync::Arc, vec};
+use constants::PAGE_SIZE;
+
+use crate::{
+    DirectoryEntryType, DirectoryTreeNode, FileMetadata, FileSystemError, FileSystemResult, IFile,
+    Pipe,
+};
+
+/// Where a regular file is read or written.
+enum Position {
+    At(u64),
+    /// The file position of the open file, which is moved along
+    Cursor(Arc<FileMetadata>),
+}
+
+/// A file data is moved out of or into by [`transfer`].
+pub struct TransferEnd {
+    file: Arc<dyn IFile>,
+    /// Regular files are read and written where they are positioned, others as a stream
+    regular: Option<(Arc<DirectoryTreeNode>, Position)>,
+}
+
+impl TransferEnd {
+    /// `file` at `offset`, or at its file position without one. Offsets only apply to regular
+    /// files and block devices, which other files are read and written as a stream.
+    pub fn new(file: Arc<dyn IFile>, offset: Option<u64>) -> TransferEnd {
+        let regular = file.metadata().and_then(|metadata| {
+            let node = metadata.inode();
+
+            match node.metadata().entry_type {
+                DirectoryEntryType::File | DirectoryEntryType::BlockDevice => {
+                    let position = match offset {
+                        Some(offset) => Position::At(offset),
+                        None => Position::Cursor(metadata),
+                    };
+
+                    Some((node, position))
+                }
+                _ => None,
+            }
+        });
+
+        TransferEnd { file, regular }
+    }
+
+    pub fn file(&self) -> &Arc<dyn IFile> {
+        &self.file
+    }
+
+    /// Where the given offset has moved to, `None` if the file position was used.
+    pub fn offset(&self) -> Option<u64> {
+        match &self.regular {
+            Some((_, Position::At(offset))) => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Where the file is read or written next, `None` for a stream.
+    pub fn position(&self) -> Option<usize> {
+        self.regular.as_ref().map(|(_, position)| match position {
+            Position::At(offset) => *offset as usize,
+            Position::Cursor(metadata) => metadata.offset(),
+        })
+    }
+
+    /// Moves the position `len` bytes forward, or backward if negative.
+    fn advance(&mut self, len: isize) {
+        match &mut self.regular {
+            Some((_, Position::At(offset))) => *offset = offset.wrapping_add_signed(len as i64),
+            Some((_, Position::Cursor(metadata))) => {
+                metadata.seek(len as i64, 1 /* SEEK_CURRENT */);
+            }
+            None => (),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> FileSystemResult<usize> {
+        let read = match (&self.regular, self.position()) {
+            (Some((node, _)), Some(position)) => node.readat(position, buf)?,
+            _ => return Ok(self.file.read(buf)),
+        };
+
+        self.advance(read as isize);
+
+        Ok(read)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> FileSystemResult<usize> {
+        let written = match (&self.regular, self.position()) {
+            (Some((node, _)), Some(position)) => node.writeat(position, buf)?,
+            _ => {
+                // Tells a broken pipe apart from a full one
+                if let Some(pipe) = self.file.downcast_ref::<Pipe>() {
+                    return pipe.push(buf);
+                }
+
+                return Ok(self.file.write(buf));
+            }
+        };
+
+        self.advance(written as isize);
+
+        Ok(written)
+    }
+
+    fn is_same_file(&self, other: &TransferEnd) -> bool {
+        match (&self.regular, &other.regular) {
+            (Some((node, _)), Some((other, _))) => {
+                matches!((node.file_key(), other.file_key()), (Ok(a), Ok(b)) if a == b)
+            }
+            _ => false,
+        }
+    }
+
+    /// Lends the pages of a regular file in the page cache to `sink` one after another. Returns
+    /// `None` if the file does not go through the page cache.
+    fn transfer_cached(
+        &mut self,
+        sink: &mut TransferEnd,
+        len: usize,
+    ) -> FileSystemResult<Option<usize>> {
+        let (Some((node, _)), Some(start)) = (&self.regular, self.position()) else {
+            return Ok(None);
+        };
+
+        let node = node.clone();
+        let end = Ord::min(start.saturating_add(len), node.metadata().size);
+        let mut position = start;
+
+        while position < end {
+            let within = position % PAGE_SIZE;
+            let len = Ord::min(PAGE_SIZE - within, end - position);
+
+            let written = node
+                .cached_page(position / PAGE_SIZE)
+                .and_then(|page| page.lend(within, len, |bytes| sink.write(bytes)));
+
+            let written = match written {
+                Ok(written) => written,
+                Err(FileSystemError::Unimplemented) if position == start => return Ok(None),
+                Err(e) if position == start => return Err(e),
+                Err(_) => break,
+            };
+
+            position += written;
+
+            if written < len {
+                break;
+            }
+        }
+
+        self.advance((position - start) as isize);
+
+        Ok(Some(position - start))
+    }
+}
+
+/// Moves up to `len` bytes from `source` to `sink` inside the kernel, advancing both, and returns
+/// how many were moved.
+///
+/// Pipes lend their buffer and regular files their cached pages, so the data is copied once. Other
+/// files, and a file copied into itself, go through a kernel buffer. What a stream `source` gave
+/// but `sink` did not take then is lost, which only happens when `sink` takes less than it could.
+pub fn transfer(
+    source: &mut TransferEnd,
+    sink: &mut TransferEnd,
+    len: usize,
+) -> FileSystemResult<usize> {
+    let file = source.file.clone();
+    if let Some(pipe) = file.downcast_ref::<Pipe>() {
+        return pipe.read_with(len, |data| sink.write(data));
+    }
+
+    if !source.is_same_file(sink) {
+        if let Some(moved) = source.transfer_cached(sink, len)? {
+            return Ok(moved);
+        }
+    }
+
+    let file = sink.file.clone();
+    if let Some(pipe) = file.downcast_ref::<Pipe>() {
+        return pipe.write_with(len, |free| source.read(free));
+    }
+
+    let mut buffer = vec![0; Ord::min(len, PAGE_SIZE)];
+    let mut moved = 0;
+
+    while moved < len {
+        let chunk = Ord::min(buffer.len(), len - moved);
+
+        let read = match source.read(&mut buffer[..chunk]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if moved == 0 => return Err(e),
+            Err(_) => break,
+        };
+
+        let written = match sink.write(&buffer[..read]) {
+            Ok(written) => written,
+            Err(e) if moved == 0 => {
+                source.advance(-(read as isize));
+                return Err(e);
+            }
+            Err(_) => 0,
+        };
+
+        moved += written;
+
+        if written < read {
+            source.advance(-((read - written) as isize));
+            break;
+        }
+    }
+
+    Ok(moved)
+}
@@ -3,6 +3,7 @@ use alloc::{
     collections::{BTreeMap, BTreeSet},
     string::{String, ToString},
     sync::{Arc, Weak},
+    vec,
     vec::Vec,
 };
 use constants::{ErrNo, SyscallError};
@@ -11,9 +12,11 @@ use hermit_sync::SpinMutex;
 use timing::TimeSpec;
 
 use crate::{
-    CachelessInodeFile, DirectoryEntry, DirectoryEntryType, FileMetadata, FileStatistics,
-    FileStatisticsMode, FileSystemError, FileSystemResult, IFileSystem, IInode, InodeMetadata,
-    OpenFlags,
+    inotify::{self, WatchTable},
+    AccessMode, CachedPage, CachelessInodeFile, Credentials, DirectoryEntry, DirectoryEntryType,
+    FileKey, FileMetadata, FileSeals, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IFileSystem, IInode, InodeAttributes, InodeMetadata, InotifyMask, OpenFlags,
+    PageCache, XattrFlags,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -49,9 +52,18 @@ impl MountError {
 
 #[derive(Clone)]
 enum DirectoryTreeNodeMetadata {
-    Inode { inode: Arc<dyn IInode> },
-    FileSystem { fs: Arc<dyn IFileSystem> },
-    Link { target: String },
+    Inode {
+        inode: Arc<dyn IInode>,
+    },
+    /// The root of a mount, bind mounts give the inode they start at
+    FileSystem {
+        fs: Arc<dyn IFileSystem>,
+        root: Option<Arc<dyn IInode>>,
+        watches: Arc<WatchTable>,
+    },
+    Link {
+        target: String,
+    },
     Empty,
 }
 
@@ -59,20 +71,48 @@ impl DirectoryTreeNodeMetadata {
     fn as_inode(&self) -> Option<Arc<dyn IInode>> {
         match self {
             DirectoryTreeNodeMetadata::Inode { inode } => Some(inode.clone()),
-            DirectoryTreeNodeMetadata::FileSystem { fs } => Some(fs.root_dir()),
+            DirectoryTreeNodeMetadata::FileSystem { fs, root, .. } => {
+                Some(root.clone().unwrap_or_else(|| fs.root_dir()))
+            }
             DirectoryTreeNodeMetadata::Link { target: _ } => None,
             DirectoryTreeNodeMetadata::Empty => None,
         }
     }
 }
 
+/// How a node goes through the page cache.
+#[derive(Clone)]
+enum PageCacheState {
+    /// Not worked out yet, a file finds out on its first read or write
+    Unknown,
+    Uncached,
+    /// A filesystem whose regular files are cached
+    Mount(Arc<PageCache>),
+    /// A cached filesystem no path leads to, its pages are forgotten once its root drops
+    Anonymous(Arc<PageCache>),
+    File(Arc<PageCache>, FileKey),
+}
+
+/// A removed file as its watches need it: the watch table, the inode id in it, the inode and
+/// whether it is a directory
+type WatchedChild = (Arc<WatchTable>, u64, Arc<dyn IInode>, bool);
+
+fn mount_id(fs: &Arc<dyn IFileSystem>) -> usize {
+    Arc::as_ptr(fs) as *const () as usize
+}
+
 struct DirectoryTreeNodeInner {
     meta: DirectoryTreeNodeMetadata,
+    page_cache: PageCacheState,
     name: String,
     mounted: BTreeMap<String, Arc<DirectoryTreeNode>>,
     opened: BTreeMap<String, Weak<DirectoryTreeNode>>,
     children_cache: BTreeMap<String, Arc<dyn IInode>>,
     shadowed: Option<UnsafeCell<Box<DirectoryTreeNodeInner>>>,
+    /// Only on the root of a mount, for everything below it
+    readonly: bool,
+    /// Only files made by `memfd_create` can be sealed
+    seals: Option<FileSeals>,
 }
 
 impl DirectoryTreeNodeInner {
@@ -104,11 +144,14 @@ impl DirectoryTreeNode {
         // This is only used for `new` to drop. Still, doesn't involve any allocation.
         let mut new_inner = DirectoryTreeNodeInner {
             meta: DirectoryTreeNodeMetadata::Empty,
+            page_cache: PageCacheState::Unknown,
             name: String::new(),
             mounted: BTreeMap::new(),
             opened: BTreeMap::new(),
             children_cache: BTreeMap::new(),
             shadowed: None,
+            readonly: false,
+            seals: None,
         };
 
         core::mem::swap(&mut new_inner, new.inner.lock().deref_mut());
@@ -130,27 +173,20 @@ impl DirectoryTreeNode {
     pub fn restore_shadow(self: &Arc<DirectoryTreeNode>) -> Option<Arc<DirectoryTreeNode>> {
         let mut inner = self.inner.lock();
 
-        if let Some(ref shadowed) = inner.shadowed {
-            // We have to use a temporary value to make borrow checker happy
-            let shadowed_inner = unsafe { shadowed.get().as_mut().unwrap() };
-
-            core::mem::swap(inner.deref_mut(), shadowed_inner);
-
-            // # SAFETY: This assume that the previous name is always correct
-            if shadowed_inner.name != inner.name {
-                core::mem::swap(&mut shadowed_inner.name, &mut inner.name);
-            }
+        let mut restored = *inner.shadowed.take()?.into_inner();
 
-            // prevent memory leak
-            let previous_inner = shadowed_inner.shadowed.take().unwrap();
+        core::mem::swap(inner.deref_mut(), &mut restored);
 
-            return Some(Arc::new(DirectoryTreeNode {
-                parent: self.parent.clone(),
-                inner: SpinMutex::new(*previous_inner.into_inner()),
-            }));
+        // # SAFETY: This assume that the previous name is always correct
+        if restored.name != inner.name {
+            core::mem::swap(&mut restored.name, &mut inner.name);
         }
 
-        None
+        // What was shadowing this node
+        Some(Arc::new(DirectoryTreeNode {
+            parent: self.parent.clone(),
+            inner: SpinMutex::new(restored),
+        }))
     }
 }
 
@@ -173,11 +209,14 @@ impl DirectoryTreeNode {
             parent,
             inner: SpinMutex::new(DirectoryTreeNodeInner {
                 meta: DirectoryTreeNodeMetadata::Empty,
+                page_cache: PageCacheState::Unknown,
                 name,
                 mounted: BTreeMap::new(),
                 opened: BTreeMap::new(),
                 children_cache: BTreeMap::new(),
                 shadowed: None,
+                readonly: false,
+                seals: None,
             }),
         })
     }
@@ -193,11 +232,14 @@ impl DirectoryTreeNode {
                 meta: DirectoryTreeNodeMetadata::Inode {
                     inode: inode.clone(),
                 },
+                page_cache: PageCacheState::Unknown,
                 name: name.unwrap_or(inode.metadata().filename).to_string(),
                 mounted: BTreeMap::new(),
                 opened: BTreeMap::new(),
                 children_cache: BTreeMap::new(),
                 shadowed: None,
+                readonly: false,
+                seals: None,
             }),
         })
     }
@@ -206,20 +248,102 @@ impl DirectoryTreeNode {
         parent: Option<Arc<DirectoryTreeNode>>,
         fs: Arc<dyn IFileSystem>,
         name: Option<&str>,
+    ) -> Arc<DirectoryTreeNode> {
+        Self::from_filesystem_with(parent, fs, name, PageCacheState::Uncached)
+    }
+
+    /// Like [`DirectoryTreeNode::from_filesystem`], but reads and writes of regular files below
+    /// go through `cache`.
+    pub fn from_cached_filesystem(
+        parent: Option<Arc<DirectoryTreeNode>>,
+        fs: Arc<dyn IFileSystem>,
+        cache: Arc<PageCache>,
+        name: Option<&str>,
+    ) -> Arc<DirectoryTreeNode> {
+        Self::from_filesystem_with(parent, fs, name, PageCacheState::Mount(cache))
+    }
+
+    /// The root of a cached filesystem that is never mounted, like the one behind a memfd or a
+    /// shared memory segment. Nothing can reach its files once this node drops, so their pages
+    /// are dropped from `cache` without being written back.
+    pub fn from_anonymous_filesystem(
+        fs: Arc<dyn IFileSystem>,
+        cache: Arc<PageCache>,
+    ) -> Arc<DirectoryTreeNode> {
+        Self::from_filesystem_with(None, fs, Some(""), PageCacheState::Anonymous(cache))
+    }
+
+    fn from_filesystem_with(
+        parent: Option<Arc<DirectoryTreeNode>>,
+        fs: Arc<dyn IFileSystem>,
+        name: Option<&str>,
+        page_cache: PageCacheState,
     ) -> Arc<DirectoryTreeNode> {
         Arc::new(DirectoryTreeNode {
             parent,
             inner: SpinMutex::new(DirectoryTreeNodeInner {
                 name: name.unwrap_or(fs.name()).to_string(),
-                meta: DirectoryTreeNodeMetadata::FileSystem { fs },
+                meta: DirectoryTreeNodeMetadata::FileSystem {
+                    fs,
+                    root: None,
+                    watches: WatchTable::new(),
+                },
+                page_cache,
                 mounted: BTreeMap::new(),
                 opened: BTreeMap::new(),
                 children_cache: BTreeMap::new(),
                 shadowed: None,
+                readonly: false,
+                seals: None,
             }),
         })
     }
 
+    /// A bind mount of `source`, which shows the same files through another path. It belongs to
+    /// the filesystem `source` is in, so files keep their [`FileKey`] and cached pages.
+    pub fn from_bind(
+        parent: Option<Arc<DirectoryTreeNode>>,
+        source: &Arc<DirectoryTreeNode>,
+        name: Option<&str>,
+    ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        let root = source.inode().ok_or(FileSystemError::InvalidInput)?;
+
+        let mut current = Some(source.as_ref());
+
+        while let Some(node) = current {
+            let inner = node.inner.lock();
+
+            if let DirectoryTreeNodeMetadata::FileSystem { fs, watches, .. } = &inner.meta {
+                let page_cache = match &inner.page_cache {
+                    PageCacheState::Mount(cache) | PageCacheState::Anonymous(cache) => {
+                        PageCacheState::Mount(cache.clone())
+                    }
+                    _ => PageCacheState::Uncached,
+                };
+
+                let node = Self::from_filesystem_with(
+                    parent,
+                    fs.clone(),
+                    Some(name.unwrap_or(source.name())),
+                    page_cache,
+                );
+                node.inner.lock().meta = DirectoryTreeNodeMetadata::FileSystem {
+                    fs: fs.clone(),
+                    root: Some(root),
+                    watches: watches.clone(),
+                };
+
+                return Ok(node);
+            }
+
+            drop(inner);
+
+            current = node.parent.as_deref();
+        }
+
+        Err(FileSystemError::InvalidInput)
+    }
+
     pub fn from_symlink(
         parent: Option<Arc<DirectoryTreeNode>>,
         name: &str,
@@ -232,10 +356,13 @@ impl DirectoryTreeNode {
                 meta: DirectoryTreeNodeMetadata::Link {
                     target: String::from(target),
                 },
+                page_cache: PageCacheState::Unknown,
                 mounted: BTreeMap::new(),
                 opened: BTreeMap::new(),
                 children_cache: BTreeMap::new(),
                 shadowed: None,
+                readonly: false,
+                seals: None,
             }),
         })
     }
@@ -250,7 +377,7 @@ impl DirectoryTreeNode {
             fn get_raw_name(node: &Arc<DirectoryTreeNode>) -> &str {
                 match unsafe { &node.inner.data_ptr().as_ref().unwrap().meta } {
                     DirectoryTreeNodeMetadata::Inode { inode } => inode.metadata().filename,
-                    DirectoryTreeNodeMetadata::FileSystem { fs } => fs.name(),
+                    DirectoryTreeNodeMetadata::FileSystem { fs, .. } => fs.name(),
                     _ => node.metadata().filename,
                 }
             }
@@ -271,6 +398,10 @@ impl DirectoryTreeNode {
             }
         }
 
+        if let DirectoryTreeNodeMetadata::FileSystem { watches, .. } = &node.inner.lock().meta {
+            watches.mount();
+        }
+
         let mut inner = self.inner.lock();
 
         if let Some(mounted) = inner.mounted.get(name).cloned() {
@@ -320,6 +451,9 @@ impl DirectoryTreeNode {
             .remove_entry(name)
             .ok_or(MountError::FileNotExists)?;
 
+        umounted.release_page_cache();
+        umounted.unmount_watches();
+
         if umounted.inner.lock().shadowed.is_some() {
             umounted.restore_shadow();
 
@@ -338,6 +472,87 @@ impl DirectoryTreeNode {
         self.name_internal()
     }
 
+    /// The directory this node was opened or mounted in, `None` for the root.
+    pub fn parent(&self) -> Option<&Arc<DirectoryTreeNode>> {
+        self.parent.as_ref()
+    }
+
+    /// Makes the mount this node is the root of read-only or writable again.
+    pub fn set_readonly(&self, readonly: bool) {
+        self.inner.lock().readonly = readonly;
+    }
+
+    /// Whether the mount this node is in is read-only.
+    pub fn is_readonly(&self) -> bool {
+        let mut current = Some(self);
+
+        while let Some(node) = current {
+            let inner = node.inner.lock();
+
+            if inner.readonly {
+                return true;
+            }
+
+            if let DirectoryTreeNodeMetadata::FileSystem { .. } = &inner.meta {
+                return false;
+            }
+
+            drop(inner);
+
+            current = node.parent.as_deref();
+        }
+
+        false
+    }
+
+    /// Lets this file be sealed, starting with `seals`.
+    pub fn make_sealable(&self, seals: FileSeals) {
+        self.inner.lock().seals = Some(seals);
+    }
+
+    /// The seals of this file, `None` if it can't be sealed.
+    pub fn seals(&self) -> Option<FileSeals> {
+        self.inner.lock().seals
+    }
+
+    /// Adds `seals` to the ones this file has.
+    ///
+    /// Fails with `NotPermitted` once `F_SEAL_SEAL` is set, and with `Busy` for `F_SEAL_WRITE`
+    /// while the file is mapped writable somewhere.
+    pub fn add_seals(&self, seals: FileSeals) -> FileSystemResult<()> {
+        let current = self.seals().ok_or(FileSystemError::InvalidInput)?;
+
+        if current.contains(FileSeals::F_SEAL_SEAL) {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        if seals.contains(FileSeals::F_SEAL_WRITE) {
+            if let Some((cache, key, _)) = self.page_cache() {
+                if cache.is_mapped_writable(key) {
+                    return Err(FileSystemError::Busy);
+                }
+            }
+        }
+
+        let mut inner = self.inner.lock();
+
+        // Checked again, as someone may have sealed it meanwhile
+        match &mut inner.seals {
+            Some(current) if !current.contains(FileSeals::F_SEAL_SEAL) => {
+                current.insert(seals);
+                Ok(())
+            }
+            _ => Err(FileSystemError::NotPermitted),
+        }
+    }
+
+    fn check_writable(&self) -> FileSystemResult<()> {
+        match self.is_readonly() {
+            true => Err(FileSystemError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
     fn name_internal(&self) -> &'static str {
         unsafe { &self.inner.data_ptr().as_ref().unwrap().name }
     }
@@ -365,6 +580,21 @@ impl DirectoryTreeNode {
         Ok((closed.is_some(), unmounted.is_some()))
     }
 
+    /// Forgets `node` as the opened child called `name`. Something mounted at `name` since it was
+    /// opened stays, as does a child opened again under the same name.
+    fn forget_opened(&self, name: &str, node: *const DirectoryTreeNode) {
+        let mut inner = self.inner.lock();
+
+        if inner
+            .opened
+            .get(name)
+            .is_some_and(|weak| core::ptr::eq(weak.as_ptr(), node))
+        {
+            inner.opened.remove(name);
+            inner.children_cache.remove(name);
+        }
+    }
+
     pub fn open_path(
         path: &str,
         root: &Arc<DirectoryTreeNode>,
@@ -380,6 +610,17 @@ impl DirectoryTreeNode {
         self: &Arc<DirectoryTreeNode>,
         path: &str,
         root: Option<&Arc<DirectoryTreeNode>>,
+    ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.open_raw_as(path, root, None)
+    }
+
+    /// Like [`DirectoryTreeNode::open_raw`], but every directory passed through has to be
+    /// searchable with `credentials`, including those on the way to link targets.
+    pub fn open_raw_as(
+        self: &Arc<DirectoryTreeNode>,
+        path: &str,
+        root: Option<&Arc<DirectoryTreeNode>>,
+        credentials: Option<&Credentials>,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
         let mut current = match path::is_path_fully_qualified(path) {
             false => self.clone(),
@@ -388,7 +629,13 @@ impl DirectoryTreeNode {
 
         let parts = path.split(path::SEPARATOR).skip_while(|d| d.is_empty());
         for part in parts {
-            current = current.resolve_all_link(root)?.open_child(part)?;
+            current = current.resolve_all_link_as(root, credentials)?;
+
+            if let Some(credentials) = credentials {
+                current.check_access(credentials, AccessMode::EXECUTE)?;
+            }
+
+            current = current.open_child(part)?;
         }
 
         Ok(current)
@@ -399,8 +646,92 @@ impl DirectoryTreeNode {
         path: &str,
         root: Option<&Arc<DirectoryTreeNode>>,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
-        self.open_raw(path, root)
-            .and_then(|n| n.resolve_all_link(root))
+        self.open_as(path, root, None)
+    }
+
+    /// Like [`DirectoryTreeNode::open`], checking search permission like
+    /// [`DirectoryTreeNode::open_raw_as`].
+    pub fn open_as(
+        self: &Arc<DirectoryTreeNode>,
+        path: &str,
+        root: Option<&Arc<DirectoryTreeNode>>,
+        credentials: Option<&Credentials>,
+    ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.open_raw_as(path, root, credentials)
+            .and_then(|n| n.resolve_all_link_as(root, credentials))
+    }
+
+    /// Checks the permission bits of this node grant all of `access` to `credentials`.
+    pub fn check_access(
+        self: &Arc<DirectoryTreeNode>,
+        credentials: &Credentials,
+        access: AccessMode,
+    ) -> FileSystemResult<()> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+        self.stat(&mut stat)?;
+
+        credentials.check_access(&stat, access)
+    }
+
+    /// Checks this node may be opened with `flags` by `credentials`, truncating needs write
+    /// permission as well. `O_PATH` does not access the file at all.
+    pub fn check_open(
+        self: &Arc<DirectoryTreeNode>,
+        flags: OpenFlags,
+        credentials: &Credentials,
+    ) -> FileSystemResult<()> {
+        if flags.contains(OpenFlags::O_PATH) {
+            return Ok(());
+        }
+
+        let mut access = match flags.bits() & OpenFlags::O_ACCMODE.bits() {
+            0 => AccessMode::READ,
+            1 => AccessMode::WRITE,
+            _ => AccessMode::READ | AccessMode::WRITE,
+        };
+
+        if flags.contains(OpenFlags::O_TRUNC) {
+            access |= AccessMode::WRITE;
+        }
+
+        if access.contains(AccessMode::WRITE) {
+            self.check_writable()?;
+        }
+
+        self.check_access(credentials, access)
+    }
+
+    /// Checks this node is a regular file `credentials` may execute.
+    pub fn check_exec(
+        self: &Arc<DirectoryTreeNode>,
+        credentials: &Credentials,
+    ) -> FileSystemResult<()> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+        self.stat(&mut stat)?;
+
+        if stat.mode.bits() & FileStatisticsMode::TYPE_MASK.bits()
+            != FileStatisticsMode::FILE.bits()
+        {
+            return Err(FileSystemError::AccessDenied);
+        }
+
+        credentials.check_access(&stat, AccessMode::EXECUTE)
+    }
+
+    /// Checks the child `name` may be removed or renamed by `credentials`, see
+    /// [`Credentials::check_delete`].
+    pub fn check_delete(
+        self: &Arc<DirectoryTreeNode>,
+        name: &str,
+        credentials: &Credentials,
+    ) -> FileSystemResult<()> {
+        let mut dir = unsafe { core::mem::zeroed::<FileStatistics>() };
+        let mut child = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+        self.stat(&mut dir)?;
+        self.open_child(name)?.stat(&mut child)?;
+
+        credentials.check_delete(&dir, &child)
     }
 
     pub fn open_child(
@@ -488,6 +819,36 @@ impl DirectoryTreeNode {
         path
     }
 
+    /// The filesystems mounted at or below this node, as their full paths and filesystem names.
+    pub fn mounts(self: &Arc<DirectoryTreeNode>) -> Vec<(String, String)> {
+        let mut mounts = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![self.clone()];
+
+        while let Some(node) = pending.pop() {
+            // Hard links made in the tree mount the same node a second time
+            if !visited.insert(Arc::as_ptr(&node)) {
+                continue;
+            }
+
+            let inner = node.inner.lock();
+
+            if let DirectoryTreeNodeMetadata::FileSystem { fs, .. } = &inner.meta {
+                mounts.push((node.fullpath(), fs.name().to_string()));
+            }
+
+            // Mounts can be below opened directories too, those are looked at after the mounted
+            let opened = inner.opened.values().filter_map(|weak| weak.upgrade());
+            let children: Vec<_> = inner.mounted.values().cloned().chain(opened).collect();
+
+            drop(inner);
+
+            pending.extend(children.into_iter().rev());
+        }
+
+        mounts
+    }
+
     // Calculate the closest node that's ancestor of both the input node
     pub fn get_common_parent(
         lhs: &Arc<DirectoryTreeNode>,
@@ -518,7 +879,7 @@ impl DirectoryTreeNode {
     pub fn get_containing_filesystem(self: &Arc<DirectoryTreeNode>) -> Arc<DirectoryTreeNode> {
         fn as_filesystem(this: &Arc<DirectoryTreeNode>) -> Option<Arc<DirectoryTreeNode>> {
             match unsafe { &this.inner.data_ptr().as_ref().unwrap().meta } {
-                DirectoryTreeNodeMetadata::FileSystem { fs: _ } => Some(this.clone()),
+                DirectoryTreeNodeMetadata::FileSystem { .. } => Some(this.clone()),
                 _ => None,
             }
         }
@@ -561,7 +922,10 @@ impl DirectoryTreeNode {
 
         // Cast &mut [MaybeUninit<u8>] to &mut [u8] to shut up the clippy
         let slice = unsafe { core::mem::transmute::<&mut [MaybeUninit<u8>], &mut [u8]>(&mut buf) };
-        self.readat(offset, slice)?;
+        let read = self.readat(offset, slice)?;
+
+        // Files whose contents are generated may have become shorter since the size was taken
+        buf.truncate(read);
 
         // Cast back to Vec<u8>
         Ok(unsafe { core::mem::transmute::<Vec<MaybeUninit<u8>>, Vec<u8>>(buf) })
@@ -571,9 +935,17 @@ impl DirectoryTreeNode {
 impl Drop for DirectoryTreeNode {
     fn drop(&mut self) {
         if let Some(ref parent) = self.parent {
-            if let Err(e) = parent.close(self.name()) {
-                log::warn!("Failed to close directory node: {}, {:?}", self.name(), e);
-            }
+            parent.forget_opened(self.name(), self);
+        }
+
+        let inner = self.inner.get_mut();
+
+        if let (
+            DirectoryTreeNodeMetadata::FileSystem { fs, .. },
+            PageCacheState::Anonymous(cache),
+        ) = (&inner.meta, &inner.page_cache)
+        {
+            cache.forget_mount(mount_id(fs));
         }
     }
 }
@@ -601,11 +973,20 @@ impl DirectoryTreeNode {
         }
     }
 
+    /// The inode backing this node, `None` for symlinks and empty directories created in the tree.
+    pub fn inode(&self) -> Option<Arc<dyn IInode>> {
+        self.inner.lock().meta.as_inode()
+    }
+
     pub fn readat(
         self: &Arc<DirectoryTreeNode>,
         offset: usize,
         buffer: &mut [u8],
     ) -> FileSystemResult<usize> {
+        if let Some((cache, key, inode)) = self.page_cache() {
+            return cache.read(key, &inode, offset, buffer);
+        }
+
         match self.inner.lock().meta.as_inode() {
             Some(inode) => inode.readat(offset, buffer),
             None => Err(FileSystemError::NotAFile),
@@ -613,9 +994,172 @@ impl DirectoryTreeNode {
     }
 
     pub fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        self.check_writable()?;
+
+        let (seals, inode) = {
+            let inner = self.inner.lock();
+            (inner.seals, inner.meta.as_inode())
+        };
+
+        if let (Some(seals), Some(inode)) = (seals, inode) {
+            if !seals.allows_write(offset, buffer.len(), inode.metadata().size) {
+                return Err(FileSystemError::NotPermitted);
+            }
+        }
+
+        let written = match self.page_cache() {
+            Some((cache, key, inode)) => cache.write(key, &inode, offset, buffer),
+            None => match self.inner.lock().meta.as_inode() {
+                Some(inode) => inode.writeat(offset, buffer),
+                None => Err(FileSystemError::NotAFile),
+            },
+        };
+
+        if written.is_ok() {
+            self.notify(InotifyMask::IN_MODIFY);
+        }
+
+        written
+    }
+
+    /// Writes back what the page cache holds for this file, then flushes the inode.
+    pub fn sync(&self) -> FileSystemResult<()> {
+        if let Some((cache, key, _)) = self.page_cache() {
+            cache.sync(key)?;
+        }
+
         match self.inner.lock().meta.as_inode() {
-            Some(inode) => inode.writeat(offset, buffer),
-            None => Err(FileSystemError::NotAFile),
+            Some(inode) => match inode.flush() {
+                // Nothing is buffered below then
+                Err(FileSystemError::Unimplemented) => Ok(()),
+                result => result,
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// The page of this file at `index` from the page cache, for mapping it.
+    ///
+    /// Fails with [`FileSystemError::Unimplemented`] if the file is not on a cached filesystem.
+    pub fn cached_page(&self, index: usize) -> FileSystemResult<Arc<CachedPage>> {
+        let (cache, key, inode) = self.page_cache().ok_or(FileSystemError::Unimplemented)?;
+
+        cache.page(key, &inode, index)
+    }
+
+    /// The page cache a regular file goes through, its key there and its inode.
+    fn page_cache(&self) -> Option<(Arc<PageCache>, FileKey, Arc<dyn IInode>)> {
+        let inode = {
+            let inner = self.inner.lock();
+
+            match (&inner.page_cache, &inner.meta) {
+                (PageCacheState::File(cache, key), DirectoryTreeNodeMetadata::Inode { inode }) => {
+                    return Some((cache.clone(), *key, inode.clone()));
+                }
+                (PageCacheState::Unknown, DirectoryTreeNodeMetadata::Inode { inode }) => {
+                    inode.clone()
+                }
+                _ => return None,
+            }
+        };
+
+        // Worked out outside of the lock, the parent may be locked by whoever created this node
+        let state = match inode.metadata().entry_type {
+            DirectoryEntryType::File => {
+                let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+                match (
+                    self.parent.as_ref().and_then(|p| p.cache_mount()),
+                    inode.stat(&mut stat),
+                ) {
+                    (Some((cache, mount)), Ok(())) => PageCacheState::File(
+                        cache,
+                        FileKey {
+                            mount,
+                            inode: stat.inode_id,
+                        },
+                    ),
+                    _ => PageCacheState::Uncached,
+                }
+            }
+            _ => PageCacheState::Uncached,
+        };
+
+        self.inner.lock().page_cache = state.clone();
+
+        match state {
+            PageCacheState::File(cache, key) => Some((cache, key, inode)),
+            _ => None,
+        }
+    }
+
+    /// The page cache of the filesystem this node is in, and the filesystem's id in it.
+    fn cache_mount(&self) -> Option<(Arc<PageCache>, usize)> {
+        let mut current = Some(self);
+
+        while let Some(node) = current {
+            let inner = node.inner.lock();
+
+            if let DirectoryTreeNodeMetadata::FileSystem { fs, .. } = &inner.meta {
+                return match &inner.page_cache {
+                    PageCacheState::Mount(cache) | PageCacheState::Anonymous(cache) => {
+                        Some((cache.clone(), mount_id(fs)))
+                    }
+                    _ => None,
+                };
+            }
+
+            drop(inner);
+
+            current = node.parent.as_deref();
+        }
+
+        None
+    }
+
+    /// Identifies the file behind this node, the same for every path and hard link to it.
+    pub fn file_key(&self) -> FileSystemResult<FileKey> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+        match self.inode() {
+            Some(inode) => inode.stat(&mut stat)?,
+            None => return Err(FileSystemError::InvalidInput),
+        }
+
+        let mut current = Some(self);
+
+        while let Some(node) = current {
+            if let DirectoryTreeNodeMetadata::FileSystem { fs, .. } = &node.inner.lock().meta {
+                return Ok(FileKey {
+                    mount: mount_id(fs),
+                    inode: stat.inode_id,
+                });
+            }
+
+            current = node.parent.as_deref();
+        }
+
+        Err(FileSystemError::InvalidInput)
+    }
+
+    /// Writes back and drops the pages cached for the filesystem at this node.
+    fn release_page_cache(&self) {
+        let inner = self.inner.lock();
+
+        if let (DirectoryTreeNodeMetadata::FileSystem { fs, .. }, PageCacheState::Mount(cache)) =
+            (&inner.meta, &inner.page_cache)
+        {
+            let mount = mount_id(fs);
+
+            if let Err(e) = cache.sync_mount(mount) {
+                log::warn!(
+                    "Failed to write back {} while unmounting: {:?}",
+                    fs.name(),
+                    e
+                );
+            }
+
+            cache.forget_mount(mount);
         }
     }
 
@@ -623,6 +1167,8 @@ impl DirectoryTreeNode {
         self: &Arc<DirectoryTreeNode>,
         name: &str,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.check_writable()?;
+
         let mut inner = self.inner.lock();
 
         if inner.is_mounted(name) {
@@ -637,6 +1183,9 @@ impl DirectoryTreeNode {
                     inner
                         .opened
                         .insert(wrapped.name().to_string(), Arc::downgrade(&wrapped));
+                    drop(inner);
+
+                    self.notify_entry(InotifyMask::IN_CREATE | InotifyMask::IN_ISDIR, 0, name);
 
                     return Ok(wrapped);
                 }
@@ -651,35 +1200,73 @@ impl DirectoryTreeNode {
     }
 
     pub fn rmdir(self: &Arc<DirectoryTreeNode>, name: &str) -> FileSystemResult<()> {
-        match self.close(name) {
-            Ok((closed, unmounted)) if closed || unmounted => return Ok(()),
-            Err(e) => return Err(e),
-            _ => (),
-        }
+        self.check_writable()?;
 
-        match self.inner.lock().meta.as_inode() {
-            Some(inode) => inode.rmdir(name),
-            None => Ok(()), // same as below
+        let watched = self.watched_child(name);
+
+        // Only mounted nodes live in the tree alone, opened ones still have to be removed below
+        if !self.close(name)?.1 {
+            // Without an inode it was already removed in close method
+            if let Some(inode) = self.inner.lock().meta.as_inode() {
+                inode.rmdir(name)?;
+            }
         }
+
+        self.notify_removed(name, watched);
+
+        Ok(())
     }
 
     pub fn remove(self: &Arc<DirectoryTreeNode>, name: &str) -> FileSystemResult<()> {
-        match self.close(name) {
-            Ok((closed, unmounted)) if closed || unmounted => return Ok(()),
-            Err(e) => return Err(e),
-            _ => (),
+        self.check_writable()?;
+
+        // The inode number may be given to a new file later, which must not see these pages
+        let cached = match self.cache_mount() {
+            Some(_) => self
+                .open_child(name)
+                .ok()
+                .and_then(|child| child.page_cache()),
+            None => None,
+        };
+
+        let watched = self.watched_child(name);
+
+        // Only mounted nodes live in the tree alone, opened ones still have to be removed below
+        if self.close(name)?.1 {
+            self.notify_removed(name, watched);
+
+            return Ok(());
         }
 
-        match self.inner.lock().meta.as_inode() {
-            Some(inode) => inode.remove(name),
-            None => Ok(()), // already removed in close method
+        let inode = match self.inner.lock().meta.as_inode() {
+            Some(inode) => inode,
+            None => return Ok(()), // already removed in close method
+        };
+
+        inode.remove(name)?;
+
+        self.notify_removed(name, watched);
+
+        if let Some((cache, key, inode)) = cached {
+            let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+            // Still reachable through other hard links
+            if inode.stat(&mut stat).is_ok_and(|_| stat.link_count > 0) {
+                cache.sync(key)?;
+            }
+
+            cache.forget(key);
         }
+
+        Ok(())
     }
 
     pub fn touch(
         self: &Arc<DirectoryTreeNode>,
         name: &str,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.check_writable()?;
+
         let mut inner = self.inner.lock();
 
         if let Some(inode) = inner.meta.as_inode() {
@@ -690,6 +1277,9 @@ impl DirectoryTreeNode {
                     inner
                         .opened
                         .insert(wrapped.name().to_string(), Arc::downgrade(&wrapped));
+                    drop(inner);
+
+                    self.notify_entry(InotifyMask::IN_CREATE, 0, name);
 
                     return Ok(wrapped);
                 }
@@ -788,6 +1378,22 @@ impl DirectoryTreeNode {
         self: &Arc<DirectoryTreeNode>,
         name: &str,
         source: &Arc<DirectoryTreeNode>,
+    ) -> FileSystemResult<()> {
+        self.check_writable()?;
+
+        self.link_internal(name, source)?;
+
+        // The link count of the source changed
+        source.notify(InotifyMask::IN_ATTRIB);
+        self.notify_entry(InotifyMask::IN_CREATE, 0, name);
+
+        Ok(())
+    }
+
+    fn link_internal(
+        self: &Arc<DirectoryTreeNode>,
+        name: &str,
+        source: &Arc<DirectoryTreeNode>,
     ) -> FileSystemResult<()> {
         if Arc::ptr_eq(self, source) {
             if let Some(ref inode) = self.inner.lock().meta.as_inode() {
@@ -838,11 +1444,16 @@ impl DirectoryTreeNode {
         name: &str,
         point_to: &str,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.check_writable()?;
+
         let self_inner = self.inner.lock();
 
         match self_inner.meta.as_inode() {
             Some(ref self_inode) => {
                 let inode = self_inode.soft_link(name, point_to)?;
+                drop(self_inner);
+
+                self.notify_entry(InotifyMask::IN_CREATE, 0, name);
 
                 Ok(Self::from_inode(Some(self.clone()), &inode, Some(name)))
             }
@@ -868,6 +1479,16 @@ impl DirectoryTreeNode {
     pub fn resolve_all_link(
         self: &Arc<DirectoryTreeNode>,
         root: Option<&Arc<DirectoryTreeNode>>,
+    ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+        self.resolve_all_link_as(root, None)
+    }
+
+    /// Like [`DirectoryTreeNode::resolve_all_link`], checking search permission like
+    /// [`DirectoryTreeNode::open_raw_as`].
+    pub fn resolve_all_link_as(
+        self: &Arc<DirectoryTreeNode>,
+        root: Option<&Arc<DirectoryTreeNode>>,
+        credentials: Option<&Credentials>,
     ) -> FileSystemResult<Arc<DirectoryTreeNode>> {
         const RESOLUTION_LIMIT: usize = 40;
 
@@ -875,11 +1496,16 @@ impl DirectoryTreeNode {
         for _ in 0..RESOLUTION_LIMIT {
             match current.resolve_link() {
                 None => return Ok(current),
-                Some(target) => match root
-                    .unwrap_or(&current)
-                    .open_raw(&target, current.parent.as_ref())
-                {
+                // Relative targets start from the directory holding the link
+                Some(target) => match current.parent.as_ref().unwrap_or(&current).open_raw_as(
+                    &target,
+                    root.or(current.parent.as_ref()),
+                    credentials,
+                ) {
                     Ok(node) => current = node,
+                    Err(FileSystemError::AccessDenied) => {
+                        return Err(FileSystemError::AccessDenied)
+                    }
                     Err(_) => return Err(FileSystemError::NotFound),
                 },
             }
@@ -889,18 +1515,181 @@ impl DirectoryTreeNode {
     }
 
     pub fn resize_inode(self: &Arc<DirectoryTreeNode>, new_size: u64) -> FileSystemResult<u64> {
-        let inner = self.inner.lock();
+        self.check_writable()?;
 
-        match inner.meta.as_inode() {
-            Some(inode) => inode.resize(new_size),
-            None => Err(FileSystemError::NotAFile),
+        if let Some(seals) = self.seals() {
+            if !seals.allows_resize(self.metadata().size, new_size as usize) {
+                return Err(FileSystemError::NotPermitted);
+            }
+        }
+
+        let inode = self
+            .inner
+            .lock()
+            .meta
+            .as_inode()
+            .ok_or(FileSystemError::NotAFile)?;
+
+        if let Some((cache, key, _)) = self.page_cache() {
+            cache.truncate(key, new_size as usize);
         }
+
+        let resized = inode.resize(new_size)?;
+        self.notify(InotifyMask::IN_MODIFY);
+
+        Ok(resized)
+    }
+
+    pub fn set_attributes(
+        self: &Arc<DirectoryTreeNode>,
+        attributes: &InodeAttributes,
+    ) -> FileSystemResult<()> {
+        self.check_writable()?;
+
+        let inode = self
+            .inner
+            .lock()
+            .meta
+            .as_inode()
+            .ok_or(FileSystemError::Unimplemented)?;
+
+        inode.set_attributes(attributes)?;
+        self.notify(InotifyMask::IN_ATTRIB);
+
+        Ok(())
+    }
+
+    /// Changes attributes as `credentials`, with the checks and adjustments of
+    /// [`Credentials::check_attributes`].
+    pub fn set_attributes_as(
+        self: &Arc<DirectoryTreeNode>,
+        attributes: &InodeAttributes,
+        credentials: &Credentials,
+    ) -> FileSystemResult<()> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+        self.stat(&mut stat)?;
+
+        let allowed = credentials.check_attributes(&stat, attributes)?;
+
+        self.set_attributes(&allowed)
+    }
+
+    /// Checks the extended attribute `name` may be accessed by `credentials`, see
+    /// [`Credentials::check_xattr`].
+    pub fn check_xattr(
+        self: &Arc<DirectoryTreeNode>,
+        name: &str,
+        credentials: &Credentials,
+        access: AccessMode,
+    ) -> FileSystemResult<()> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+        self.stat(&mut stat)?;
+
+        credentials.check_xattr(&stat, name, access)
+    }
+
+    pub fn get_xattr(self: &Arc<DirectoryTreeNode>, name: &str) -> FileSystemResult<Vec<u8>> {
+        match self.inner.lock().meta.as_inode() {
+            Some(inode) => inode.get_xattr(name),
+            None => Err(FileSystemError::NotSupported),
+        }
+    }
+
+    pub fn set_xattr(
+        self: &Arc<DirectoryTreeNode>,
+        name: &str,
+        value: &[u8],
+        flags: XattrFlags,
+    ) -> FileSystemResult<()> {
+        self.check_writable()?;
+
+        let inode = self
+            .inner
+            .lock()
+            .meta
+            .as_inode()
+            .ok_or(FileSystemError::NotSupported)?;
+
+        inode.set_xattr(name, value, flags)?;
+        self.notify(InotifyMask::IN_ATTRIB);
+
+        Ok(())
+    }
+
+    pub fn list_xattr(self: &Arc<DirectoryTreeNode>) -> FileSystemResult<Vec<String>> {
+        match self.inner.lock().meta.as_inode() {
+            Some(inode) => inode.list_xattr(),
+            None => Err(FileSystemError::NotSupported),
+        }
+    }
+
+    /// Like [`DirectoryTreeNode::list_xattr`], leaving out what `credentials` may not see.
+    pub fn list_xattr_as(
+        self: &Arc<DirectoryTreeNode>,
+        credentials: &Credentials,
+    ) -> FileSystemResult<Vec<String>> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+        self.stat(&mut stat)?;
+
+        let mut names = self.list_xattr()?;
+        names.retain(|name| {
+            credentials
+                .check_xattr(&stat, name, AccessMode::empty())
+                .is_ok()
+        });
+
+        Ok(names)
+    }
+
+    pub fn remove_xattr(self: &Arc<DirectoryTreeNode>, name: &str) -> FileSystemResult<()> {
+        self.check_writable()?;
+
+        let inode = self
+            .inner
+            .lock()
+            .meta
+            .as_inode()
+            .ok_or(FileSystemError::NotSupported)?;
+
+        inode.remove_xattr(name)?;
+        self.notify(InotifyMask::IN_ATTRIB);
+
+        Ok(())
     }
 
     pub fn rename(
         self: &Arc<DirectoryTreeNode>,
         old_name: &str,
         new_name: &str,
+    ) -> FileSystemResult<()> {
+        self.check_writable()?;
+
+        self.rename_internal(old_name, new_name)?;
+
+        if self.is_watched() {
+            if let Ok(moved) = self.open_child(new_name) {
+                let is_dir = match moved.is_dir() {
+                    true => InotifyMask::IN_ISDIR,
+                    false => InotifyMask::empty(),
+                };
+                let cookie = inotify::next_cookie();
+
+                self.notify_entry(InotifyMask::IN_MOVED_FROM | is_dir, cookie, old_name);
+                self.notify_entry(InotifyMask::IN_MOVED_TO | is_dir, cookie, new_name);
+
+                if let Some((table, inode)) = moved.watched_key() {
+                    table.notify(inode, InotifyMask::IN_MOVE_SELF | is_dir, 0, None);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rename_internal(
+        self: &Arc<DirectoryTreeNode>,
+        old_name: &str,
+        new_name: &str,
     ) -> FileSystemResult<()> {
         let mut inner = self.inner.lock();
 
@@ -958,6 +1747,153 @@ impl DirectoryTreeNode {
     }
 }
 
+/// Reporting changes to inotify watches.
+impl DirectoryTreeNode {
+    fn is_dir(&self) -> bool {
+        match &self.inner.lock().meta {
+            DirectoryTreeNodeMetadata::Inode { inode } => {
+                inode.metadata().entry_type == DirectoryEntryType::Directory
+            }
+            DirectoryTreeNodeMetadata::Link { target: _ } => false,
+            _ => true,
+        }
+    }
+
+    /// The watch table of the filesystem this node is in.
+    fn watch_table(&self) -> Option<Arc<WatchTable>> {
+        let mut current = Some(self);
+
+        while let Some(node) = current {
+            if let DirectoryTreeNodeMetadata::FileSystem { watches, .. } = &node.inner.lock().meta {
+                return Some(watches.clone());
+            }
+
+            current = node.parent.as_deref();
+        }
+
+        None
+    }
+
+    /// Whether anything in the filesystem this node is in is watched.
+    fn is_watched(&self) -> bool {
+        self.watch_table().is_some_and(|table| table.is_watching())
+    }
+
+    /// The watch table of the filesystem this node is in and the inode id it knows the file by.
+    pub(crate) fn watch_key(&self) -> FileSystemResult<(Arc<WatchTable>, u64)> {
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+        match self.inode() {
+            Some(inode) => inode.stat(&mut stat)?,
+            None => return Err(FileSystemError::InvalidInput),
+        }
+
+        let table = self.watch_table().ok_or(FileSystemError::InvalidInput)?;
+
+        Ok((table, stat.inode_id))
+    }
+
+    /// Like [`Self::watch_key`], but only if something in the filesystem is watched.
+    fn watched_key(&self) -> Option<(Arc<WatchTable>, u64)> {
+        match self.is_watched() {
+            true => self.watch_key().ok(),
+            false => None,
+        }
+    }
+
+    /// Reports `mask` on this file to its watches, and to those of the directory holding it if
+    /// the event is one a directory hears about its entries.
+    pub(crate) fn notify(&self, mut mask: InotifyMask) {
+        if !self.is_watched() {
+            return;
+        }
+
+        if self.is_dir() {
+            mask |= InotifyMask::IN_ISDIR;
+        }
+
+        if let Some((table, inode)) = self.watched_key() {
+            table.notify(inode, mask, 0, None);
+        }
+
+        if let (true, Some(parent)) = (mask.intersects(InotifyMask::CHILD_EVENTS), &self.parent) {
+            if let Some((table, inode)) = parent.watched_key() {
+                table.notify(inode, mask, 0, Some(self.name()));
+            }
+        }
+    }
+
+    /// Reports `mask` on the entry `name` of this directory to its watches.
+    fn notify_entry(&self, mask: InotifyMask, cookie: u32, name: &str) {
+        if let Some((table, inode)) = self.watched_key() {
+            table.notify(inode, mask, cookie, Some(name));
+        }
+    }
+
+    /// What the watches need to hear once the entry `name` is removed, worked out before it is.
+    fn watched_child(self: &Arc<DirectoryTreeNode>, name: &str) -> Option<WatchedChild> {
+        if !self.is_watched() {
+            return None;
+        }
+
+        let child = self.open_child(name).ok()?;
+        let (table, id) = child.watch_key().ok()?;
+
+        Some((table, id, child.inode()?, child.is_dir()))
+    }
+
+    /// Reports the removal of the entry `name`, and ends the watches of the file if this was its
+    /// last link.
+    fn notify_removed(&self, name: &str, watched: Option<WatchedChild>) {
+        let Some((table, id, inode, is_dir)) = watched else {
+            return;
+        };
+
+        let is_dir_mask = match is_dir {
+            true => InotifyMask::IN_ISDIR,
+            false => InotifyMask::empty(),
+        };
+
+        self.notify_entry(InotifyMask::IN_DELETE | is_dir_mask, 0, name);
+
+        let mut stat = unsafe { core::mem::zeroed::<FileStatistics>() };
+
+        if is_dir || inode.stat(&mut stat).map_or(true, |_| stat.link_count == 0) {
+            table.notify(id, InotifyMask::IN_DELETE_SELF | is_dir_mask, 0, None);
+            table.forget(id);
+        }
+    }
+
+    /// Ends the watches of the filesystem mounted at this node and of those mounted below it, as
+    /// they are being unmounted.
+    fn unmount_watches(&self) {
+        let (watches, below) = {
+            let inner = self.inner.lock();
+
+            let watches = match &inner.meta {
+                DirectoryTreeNodeMetadata::FileSystem { watches, .. } => Some(watches.clone()),
+                _ => None,
+            };
+            let below = inner
+                .mounted
+                .values()
+                .cloned()
+                .chain(inner.opened.values().filter_map(Weak::upgrade))
+                .collect::<Vec<_>>();
+
+            (watches, below)
+        };
+
+        for node in below {
+            node.unmount_watches();
+        }
+
+        if let Some(watches) = watches {
+            watches.unmount();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1319,6 +2255,58 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_directory_tree_node_readvec_at_shorter_than_size() {
+        // The mock says it has 10 bytes but reads none, like generated contents that shrank
+        let inode: Arc<dyn IInode> = Arc::new(MockInode::new("test_file", 10));
+        let node = DirectoryTreeNode::from_inode(None, &inode, Some("test_file"));
+        assert_eq!(node.readall().unwrap(), Vec::<u8>::new());
+    }
+
+    struct MockFileSystem(&'static str);
+
+    impl IFileSystem for MockFileSystem {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn root_dir(&self) -> Arc<dyn IInode> {
+            Arc::new(MockInode::new("", 0))
+        }
+    }
+
+    #[test]
+    fn test_directory_tree_node_mounts() {
+        let root =
+            DirectoryTreeNode::from_filesystem(None, Arc::new(MockFileSystem("rootfs")), Some(""));
+        let tmp = DirectoryTreeNode::from_filesystem(
+            Some(root.clone()),
+            Arc::new(MockFileSystem("tmpfs")),
+            Some("tmp"),
+        );
+        root.mount_as(tmp.clone(), Some("tmp")).unwrap();
+
+        let empty = root.mount_empty("mnt").unwrap();
+        let disk = DirectoryTreeNode::from_filesystem(
+            Some(empty.clone()),
+            Arc::new(MockFileSystem("ext4")),
+            Some("disk"),
+        );
+        empty.mount_as(disk, Some("disk")).unwrap();
+
+        // A hard link made in the tree mounts the same node again, it comes first by its name
+        root.hard_link("alias", &tmp).unwrap();
+
+        assert_eq!(
+            root.mounts(),
+            [
+                ("/".to_string(), "rootfs".to_string()),
+                ("/tmp".to_string(), "tmpfs".to_string()),
+                ("/mnt/disk".to_string(), "ext4".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_directory_tree_node_metadata() {
         let parent = None;
@@ -0,0 +1,159 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use bitflags::bitflags;
+
+use crate::{FileSystemError, FileSystemResult};
+
+/// Longest name of an extended attribute, including its namespace prefix.
+pub const XATTR_NAME_MAX: usize = 255;
+/// Largest value of an extended attribute.
+pub const XATTR_SIZE_MAX: usize = 65536;
+
+bitflags! {
+    /// How [`crate::IInode::set_xattr`] treats an existing attribute, none means either way.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct XattrFlags: u32 {
+        /// Fail if the attribute exists
+        const XATTR_CREATE = 1;
+        /// Fail if the attribute does not exist
+        const XATTR_REPLACE = 2;
+    }
+}
+
+/// The namespace an attribute name belongs to, which decides who may access it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XattrNamespace {
+    User,
+    Trusted,
+    Security,
+    System,
+}
+
+impl XattrNamespace {
+    /// The namespace of `name`, names without a known prefix are not supported.
+    pub fn of(name: &str) -> FileSystemResult<XattrNamespace> {
+        const PREFIXES: [(&str, XattrNamespace); 4] = [
+            ("user.", XattrNamespace::User),
+            ("trusted.", XattrNamespace::Trusted),
+            ("security.", XattrNamespace::Security),
+            ("system.", XattrNamespace::System),
+        ];
+
+        PREFIXES
+            .iter()
+            .find(|(prefix, _)| name.len() > prefix.len() && name.starts_with(prefix))
+            .map(|(_, namespace)| *namespace)
+            .ok_or(FileSystemError::NotSupported)
+    }
+}
+
+/// Extended attributes kept in memory, for filesystems without a place on disk for them.
+#[derive(Debug, Clone, Default)]
+pub struct ExtendedAttributes {
+    attributes: BTreeMap<String, Vec<u8>>,
+}
+
+impl ExtendedAttributes {
+    pub fn new() -> ExtendedAttributes {
+        ExtendedAttributes::default()
+    }
+
+    pub fn get(&self, name: &str) -> FileSystemResult<Vec<u8>> {
+        self.attributes
+            .get(name)
+            .cloned()
+            .ok_or(FileSystemError::NoAttribute)
+    }
+
+    pub fn set(&mut self, name: &str, value: &[u8], flags: XattrFlags) -> FileSystemResult<()> {
+        check_set(self.attributes.contains_key(name), flags)?;
+
+        self.attributes.insert(name.to_string(), value.to_vec());
+
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.attributes.keys().cloned().collect()
+    }
+
+    pub fn remove(&mut self, name: &str) -> FileSystemResult<()> {
+        self.attributes
+            .remove(name)
+            .map(|_| ())
+            .ok_or(FileSystemError::NoAttribute)
+    }
+}
+
+/// Checks an attribute that `exists` or not may be set with `flags`.
+pub fn check_set(exists: bool, flags: XattrFlags) -> FileSystemResult<()> {
+    if flags.contains(XattrFlags::XATTR_CREATE) && exists {
+        return Err(FileSystemError::AlreadyExists);
+    }
+
+    if flags.contains(XattrFlags::XATTR_REPLACE) && !exists {
+        return Err(FileSystemError::NoAttribute);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespaces() {
+        assert_eq!(XattrNamespace::of("user.mime"), Ok(XattrNamespace::User));
+        assert_eq!(
+            XattrNamespace::of("trusted.overlay.opaque"),
+            Ok(XattrNamespace::Trusted)
+        );
+        assert_eq!(
+            XattrNamespace::of("system.posix_acl_access"),
+            Ok(XattrNamespace::System)
+        );
+        assert_eq!(
+            XattrNamespace::of("user."),
+            Err(FileSystemError::NotSupported)
+        );
+        assert_eq!(
+            XattrNamespace::of("other.name"),
+            Err(FileSystemError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn test_create_and_replace() {
+        let mut attributes = ExtendedAttributes::new();
+
+        assert_eq!(
+            attributes.set("user.a", b"1", XattrFlags::XATTR_REPLACE),
+            Err(FileSystemError::NoAttribute)
+        );
+        attributes
+            .set("user.a", b"1", XattrFlags::XATTR_CREATE)
+            .unwrap();
+        assert_eq!(
+            attributes.set("user.a", b"2", XattrFlags::XATTR_CREATE),
+            Err(FileSystemError::AlreadyExists)
+        );
+        attributes
+            .set("user.a", b"2", XattrFlags::XATTR_REPLACE)
+            .unwrap();
+        attributes.set("user.b", b"", XattrFlags::empty()).unwrap();
+
+        assert_eq!(attributes.get("user.a").unwrap(), b"2");
+        assert_eq!(attributes.list(), ["user.a", "user.b"]);
+
+        attributes.remove("user.a").unwrap();
+        assert_eq!(attributes.get("user.a"), Err(FileSystemError::NoAttribute));
+        assert_eq!(
+            attributes.remove("user.a"),
+            Err(FileSystemError::NoAttribute)
+        );
+    }
+}
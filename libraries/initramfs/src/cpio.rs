@@ -0,0 +1,333 @@
+use alloc::{collections::BTreeMap, string::String, string::ToString};
+
+use crate::{normalize, ArchiveError, Entry, EntryKind};
+
+const MAGIC: &[u8] = b"070701";
+/// The same layout, but the header has a checksum of the contents
+const MAGIC_CRC: &[u8] = b"070702";
+
+const HEADER_SIZE: usize = 110;
+const FIELD_SIZE: usize = 8;
+
+/// Ends an archive, another one may follow it.
+const TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+pub(crate) fn is_cpio(data: &[u8]) -> bool {
+    data.starts_with(MAGIC) || data.starts_with(MAGIC_CRC)
+}
+
+/// Reader of "newc" cpio archives, what Linux takes as initramfs.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// The first name of files with more than one, by device and inode number
+    links: BTreeMap<(u32, u32, u32), String>,
+    failed: bool,
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+fn field(header: &[u8], index: usize) -> Result<u32, ArchiveError> {
+    let start = MAGIC.len() + index * FIELD_SIZE;
+    let digits = core::str::from_utf8(&header[start..start + FIELD_SIZE])
+        .map_err(|_| ArchiveError::Malformed)?;
+
+    u32::from_str_radix(digits, 16).map_err(|_| ArchiveError::Malformed)
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data,
+            offset: 0,
+            links: BTreeMap::new(),
+            failed: false,
+        }
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Entry<'a>>, ArchiveError> {
+        loop {
+            // Concatenated archives may be padded with zeros in between
+            while self.data.get(self.offset) == Some(&0) {
+                self.offset += 1;
+            }
+
+            if self.offset >= self.data.len() {
+                return Ok(None);
+            }
+
+            let header = self
+                .data
+                .get(self.offset..self.offset + HEADER_SIZE)
+                .ok_or(ArchiveError::Truncated)?;
+
+            let magic = &header[..MAGIC.len()];
+
+            if magic != MAGIC && magic != MAGIC_CRC {
+                return Err(ArchiveError::Malformed);
+            }
+
+            let inode = field(header, 0)?;
+            let mode = field(header, 1)?;
+            let uid = field(header, 2)?;
+            let gid = field(header, 3)?;
+            let link_count = field(header, 4)?;
+            let mtime = field(header, 5)?;
+            let size = field(header, 6)? as usize;
+            let device = (field(header, 7)?, field(header, 8)?);
+            let name_size = field(header, 11)? as usize;
+            let checksum = field(header, 12)?;
+
+            let name_start = self.offset + HEADER_SIZE;
+            let name = self
+                .data
+                .get(name_start..name_start + name_size)
+                .ok_or(ArchiveError::Truncated)?
+                .strip_suffix(&[0])
+                .ok_or(ArchiveError::Malformed)?;
+            let name = core::str::from_utf8(name).map_err(|_| ArchiveError::InvalidPath)?;
+
+            let contents_start = align(name_start + name_size);
+            let contents = self
+                .data
+                .get(contents_start..contents_start + size)
+                .ok_or(ArchiveError::Truncated)?;
+
+            self.offset = align(contents_start + size);
+
+            if magic == MAGIC_CRC && mode & MODE_TYPE_MASK == MODE_FILE {
+                let sum = contents
+                    .iter()
+                    .fold(0u32, |sum, &b| sum.wrapping_add(b as u32));
+
+                if sum != checksum {
+                    return Err(ArchiveError::BadChecksum);
+                }
+            }
+
+            if name == TRAILER {
+                // Inode numbers start over in the next archive
+                self.links.clear();
+                continue;
+            }
+
+            let path = normalize(name);
+
+            let kind = match mode & MODE_TYPE_MASK {
+                MODE_DIRECTORY => EntryKind::Directory,
+                MODE_SYMLINK => EntryKind::Symlink(
+                    core::str::from_utf8(contents)
+                        .map_err(|_| ArchiveError::InvalidPath)?
+                        .to_string(),
+                ),
+                MODE_FILE if link_count >= 2 => {
+                    match self.links.get(&(device.0, device.1, inode)) {
+                        Some(target) => EntryKind::HardLink {
+                            target: target.clone(),
+                            contents,
+                        },
+                        None => {
+                            self.links.insert((device.0, device.1, inode), path.clone());
+
+                            EntryKind::File(contents)
+                        }
+                    }
+                }
+                MODE_FILE => EntryKind::File(contents),
+                _ => EntryKind::Special,
+            };
+
+            return Ok(Some(Entry {
+                path,
+                kind,
+                mode: mode & 0o7777,
+                uid,
+                gid,
+                mtime: mtime as i64,
+            }));
+        }
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Entry<'a>, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let entry = self.read_entry();
+        self.failed = entry.is_err();
+
+        entry.transpose()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{format, vec::Vec};
+
+    use super::*;
+    use crate::Archive;
+
+    /// Appends a newc entry to `archive`.
+    pub fn push(archive: &mut Vec<u8>, name: &str, mode: u32, inode: u32, links: u32, data: &[u8]) {
+        let fields = [
+            inode,
+            mode,
+            1000,
+            100,
+            links,
+            1_700_000_000,
+            data.len() as u32,
+            8,
+            1,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+
+        archive.extend_from_slice(MAGIC);
+
+        for value in fields {
+            archive.extend_from_slice(format!("{:08X}", value).as_bytes());
+        }
+
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align(archive.len()), 0);
+    }
+
+    pub fn trailer(archive: &mut Vec<u8>) {
+        push(archive, TRAILER, 0, 0, 1, &[]);
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut archive = Vec::new();
+        push(&mut archive, ".", 0o040755, 1, 2, &[]);
+        push(&mut archive, "bin", 0o040755, 2, 2, &[]);
+        push(&mut archive, "bin/busybox", 0o104755, 3, 1, b"\x7fELF");
+        push(&mut archive, "bin/sh", 0o120777, 4, 1, b"busybox");
+        push(&mut archive, "dev/console", 0o020600, 5, 1, &[]);
+        trailer(&mut archive);
+
+        let entries: Vec<_> = Archive::new(&archive)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].path, "");
+        assert_eq!(entries[1].kind, EntryKind::Directory);
+        assert_eq!(
+            entries[2],
+            Entry {
+                path: "bin/busybox".into(),
+                kind: EntryKind::File(b"\x7fELF"),
+                mode: 0o4755,
+                uid: 1000,
+                gid: 100,
+                mtime: 1_700_000_000,
+            }
+        );
+        assert_eq!(entries[3].kind, EntryKind::Symlink("busybox".into()));
+        assert_eq!(entries[4].kind, EntryKind::Special);
+    }
+
+    #[test]
+    fn test_hard_links() {
+        let mut archive = Vec::new();
+
+        // Like GNU cpio, the contents come with the last name
+        push(&mut archive, "a", 0o100644, 7, 3, &[]);
+        push(&mut archive, "b", 0o100644, 7, 3, &[]);
+        push(&mut archive, "c", 0o100644, 7, 3, b"shared");
+        push(&mut archive, "other", 0o100644, 8, 1, b"single");
+        trailer(&mut archive);
+
+        // Another archive, whose inode numbers mean other files
+        push(&mut archive, "d", 0o100644, 7, 2, b"new");
+        trailer(&mut archive);
+
+        let kinds: Vec<_> = Archive::new(&archive)
+            .unwrap()
+            .map(|e| e.unwrap().kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [
+                EntryKind::File(&[]),
+                EntryKind::HardLink {
+                    target: "a".into(),
+                    contents: &[],
+                },
+                EntryKind::HardLink {
+                    target: "a".into(),
+                    contents: b"shared",
+                },
+                EntryKind::File(b"single"),
+                EntryKind::File(b"new"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checksums() {
+        let mut archive = Vec::new();
+        push(&mut archive, "file", 0o100644, 1, 1, b"abc");
+
+        archive[..MAGIC_CRC.len()].copy_from_slice(MAGIC_CRC);
+        archive[6 + 12 * 8..6 + 13 * 8]
+            .copy_from_slice(format!("{:08X}", 0x61 + 0x62 + 0x63).as_bytes());
+
+        let entry = Archive::new(&archive).unwrap().next().unwrap();
+        assert_eq!(entry.unwrap().kind, EntryKind::File(b"abc"));
+
+        archive[6 + 12 * 8..6 + 13 * 8].copy_from_slice(b"00000000");
+
+        let entry = Archive::new(&archive).unwrap().next().unwrap();
+        assert_eq!(entry.err(), Some(ArchiveError::BadChecksum));
+    }
+
+    #[test]
+    fn test_broken_archives() {
+        let mut archive = Vec::new();
+        push(&mut archive, "file", 0o100644, 1, 1, &[1; 100]);
+
+        let truncated = &archive[..archive.len() - 10];
+        let mut entries = Archive::new(truncated).unwrap();
+        assert_eq!(entries.next(), Some(Err(ArchiveError::Truncated)));
+        assert_eq!(entries.next(), None);
+
+        let mut garbage = archive.clone();
+        garbage.extend_from_slice(b"070701 not hex");
+        garbage.resize(garbage.len() + HEADER_SIZE, b'x');
+
+        let results: Vec<_> = Archive::new(&garbage).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1], Err(ArchiveError::Malformed));
+
+        let mut invalid = Vec::new();
+        push(&mut invalid, "\u{fffd}", 0o100644, 1, 1, &[]);
+        let at = invalid.iter().position(|&b| b == 0xef).unwrap();
+        invalid[at] = 0xff;
+
+        assert_eq!(
+            Archive::new(&invalid).unwrap().next(),
+            Some(Err(ArchiveError::InvalidPath))
+        );
+    }
+}
@@ -0,0 +1,128 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Readers of the archives an initramfs comes in, newc cpio and ustar, and unpacking one into
+//! the directory tree.
+
+use alloc::string::{String, ToString};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod cpio;
+mod tar;
+mod unpack;
+
+pub use unpack::{unpack, UnpackError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// Neither a newc cpio nor a ustar archive
+    UnknownFormat,
+    Truncated,
+    Malformed,
+    /// A name or a link target that is not UTF-8
+    InvalidPath,
+    BadChecksum,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind<'a> {
+    File(&'a [u8]),
+    Directory,
+    Symlink(String),
+    /// Another name of the file at `target`, a path that came earlier in the archive. cpio
+    /// stores the contents with the last name of a file, they are in `contents` then.
+    HardLink {
+        target: String,
+        contents: &'a [u8],
+    },
+    /// Devices, pipes and sockets
+    Special,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// Relative to the root of the archive, empty for the root itself
+    pub path: String,
+    pub kind: EntryKind<'a>,
+    /// The permission bits, including set-user-ID, set-group-ID and sticky
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Seconds since the epoch
+    pub mtime: i64,
+}
+
+/// The entries of an archive, in the order they were stored.
+pub struct Archive<'a>(Format<'a>);
+
+enum Format<'a> {
+    Cpio(cpio::Reader<'a>),
+    Tar(tar::Reader<'a>),
+}
+
+impl<'a> Archive<'a> {
+    /// Reads a newc cpio or a ustar archive, telling them apart by their magic.
+    pub fn new(data: &'a [u8]) -> Result<Archive<'a>, ArchiveError> {
+        if cpio::is_cpio(data) {
+            return Ok(Archive(Format::Cpio(cpio::Reader::new(data))));
+        }
+
+        if tar::is_tar(data) {
+            return Ok(Archive(Format::Tar(tar::Reader::new(data))));
+        }
+
+        Err(ArchiveError::UnknownFormat)
+    }
+}
+
+impl<'a> Iterator for Archive<'a> {
+    type Item = Result<Entry<'a>, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Format::Cpio(reader) => reader.next(),
+            Format::Tar(reader) => reader.next(),
+        }
+    }
+}
+
+/// Strips what archivers put in front of and after paths, `./foo/` becomes `foo`.
+fn normalize(path: &str) -> String {
+    let mut path = path.trim_end_matches('/');
+
+    while let Some(rest) = path.strip_prefix("./").or_else(|| path.strip_prefix('/')) {
+        path = rest;
+    }
+
+    match path {
+        "." => String::new(),
+        path => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("./bin/sh"), "bin/sh");
+        assert_eq!(normalize("/etc/"), "etc");
+        assert_eq!(normalize(".//./usr"), "usr");
+        assert_eq!(normalize("."), "");
+        assert_eq!(normalize("./"), "");
+        assert_eq!(normalize(".hidden"), ".hidden");
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert_eq!(
+            Archive::new(b"\x7fELF not an archive").err(),
+            Some(ArchiveError::UnknownFormat)
+        );
+        assert_eq!(Archive::new(&[]).err(), Some(ArchiveError::UnknownFormat));
+    }
+}
@@ -0,0 +1,451 @@
+use alloc::string::{String, ToString};
+
+use crate::{normalize, ArchiveError, Entry, EntryKind};
+
+const BLOCK_SIZE: usize = 512;
+
+const MAGIC_OFFSET: usize = 257;
+/// POSIX ustar, followed by a version
+const MAGIC_USTAR: &[u8] = b"ustar\0";
+/// GNU tar, which has no prefix for long names
+const MAGIC_GNU: &[u8] = b"ustar ";
+
+const TYPE_FILE: u8 = b'0';
+/// Files of old archives, before there were types
+const TYPE_OLD_FILE: u8 = 0;
+const TYPE_HARD_LINK: u8 = b'1';
+const TYPE_SYMLINK: u8 = b'2';
+const TYPE_DIRECTORY: u8 = b'5';
+const TYPE_CONTIGUOUS: u8 = b'7';
+/// pax attributes of the next entry
+const TYPE_PAX: u8 = b'x';
+/// pax attributes of all entries, which are not used here
+const TYPE_PAX_GLOBAL: u8 = b'g';
+/// GNU long name and long link target of the next entry
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+const TYPE_GNU_LONG_LINK: u8 = b'K';
+
+pub(crate) fn is_tar(data: &[u8]) -> bool {
+    data.len() >= BLOCK_SIZE
+        && (data[MAGIC_OFFSET..].starts_with(MAGIC_USTAR)
+            || data[MAGIC_OFFSET..].starts_with(MAGIC_GNU))
+}
+
+/// What pax and GNU extension headers say about the next entry.
+#[derive(Default)]
+struct Overrides {
+    path: Option<String>,
+    link: Option<String>,
+    size: Option<usize>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mtime: Option<i64>,
+}
+
+/// Reader of ustar archives, with the pax and GNU extensions for long names.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    overrides: Overrides,
+    failed: bool,
+}
+
+/// Parses a numeric field, octal or GNU base-256 for values that do not fit.
+fn number(field: &[u8]) -> Result<u64, ArchiveError> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return field[1..].iter().try_fold(0u64, |value, &b| {
+            value
+                .checked_mul(256)
+                .map(|value| value + b as u64)
+                .ok_or(ArchiveError::Malformed)
+        });
+    }
+
+    let digits = core::str::from_utf8(field).map_err(|_| ArchiveError::Malformed)?;
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+
+    match digits {
+        "" => Ok(0),
+        digits => u64::from_str_radix(digits, 8).map_err(|_| ArchiveError::Malformed),
+    }
+}
+
+/// A NUL padded string field.
+fn text(field: &[u8]) -> Result<&str, ArchiveError> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+
+    core::str::from_utf8(&field[..len]).map_err(|_| ArchiveError::InvalidPath)
+}
+
+fn checksum_matches(header: &[u8]) -> Result<bool, ArchiveError> {
+    let expected = number(&header[148..156])?;
+
+    // The checksum is taken with its own field filled with spaces
+    let sum = |signed: bool| {
+        header.iter().enumerate().fold(0i64, |sum, (i, &b)| {
+            let b = match i {
+                148..156 => b' ',
+                _ => b,
+            };
+
+            match signed {
+                true => sum + b as i8 as i64,
+                false => sum + b as i64,
+            }
+        })
+    };
+
+    // Some old archivers summed signed bytes
+    Ok(sum(false) == expected as i64 || sum(true) == expected as i64)
+}
+
+impl Overrides {
+    /// Parses pax records, `<length> <key>=<value>\n` each.
+    fn parse_pax(&mut self, mut records: &[u8]) -> Result<(), ArchiveError> {
+        while !records.is_empty() {
+            let space = records
+                .iter()
+                .position(|&b| b == b' ')
+                .ok_or(ArchiveError::Malformed)?;
+
+            let len: usize = core::str::from_utf8(&records[..space])
+                .ok()
+                .and_then(|len| len.parse().ok())
+                .filter(|&len| len > space && len <= records.len())
+                .ok_or(ArchiveError::Malformed)?;
+
+            let record = records[space + 1..len]
+                .strip_suffix(b"\n")
+                .ok_or(ArchiveError::Malformed)?;
+            records = &records[len..];
+
+            let equals = record
+                .iter()
+                .position(|&b| b == b'=')
+                .ok_or(ArchiveError::Malformed)?;
+
+            let key = &record[..equals];
+            let value = core::str::from_utf8(&record[equals + 1..])
+                .map_err(|_| ArchiveError::InvalidPath)?;
+
+            let decimal = |value: &str| -> Result<u64, ArchiveError> {
+                // Times may have a fraction, which is dropped
+                let whole = value.split('.').next().unwrap_or_default();
+                whole.parse().map_err(|_| ArchiveError::Malformed)
+            };
+
+            match key {
+                b"path" => self.path = Some(value.to_string()),
+                b"linkpath" => self.link = Some(value.to_string()),
+                b"size" => self.size = Some(decimal(value)? as usize),
+                b"uid" => self.uid = Some(decimal(value)? as u32),
+                b"gid" => self.gid = Some(decimal(value)? as u32),
+                b"mtime" => self.mtime = Some(decimal(value)? as i64),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data,
+            offset: 0,
+            overrides: Overrides::default(),
+            failed: false,
+        }
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Entry<'a>>, ArchiveError> {
+        loop {
+            if self.offset >= self.data.len() {
+                return Ok(None);
+            }
+
+            let header = self
+                .data
+                .get(self.offset..self.offset + BLOCK_SIZE)
+                .ok_or(ArchiveError::Truncated)?;
+
+            // The archive ends with zeroed blocks
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+
+            if !checksum_matches(header)? {
+                return Err(ArchiveError::BadChecksum);
+            }
+
+            let kind = header[156];
+            let size = match self.overrides.size {
+                Some(size) => size,
+                None => number(&header[124..136])? as usize,
+            };
+
+            // Links have no contents, whatever size they say
+            let stored = match kind {
+                TYPE_HARD_LINK | TYPE_SYMLINK => 0,
+                _ => size,
+            };
+
+            let contents_start = self.offset + BLOCK_SIZE;
+            let contents = self
+                .data
+                .get(contents_start..contents_start + stored)
+                .ok_or(ArchiveError::Truncated)?;
+
+            self.offset = contents_start + stored.next_multiple_of(BLOCK_SIZE);
+
+            match kind {
+                TYPE_PAX => {
+                    self.overrides.parse_pax(contents)?;
+                    continue;
+                }
+                TYPE_PAX_GLOBAL => continue,
+                TYPE_GNU_LONG_NAME => {
+                    self.overrides.path = Some(text(contents)?.to_string());
+                    continue;
+                }
+                TYPE_GNU_LONG_LINK => {
+                    self.overrides.link = Some(text(contents)?.to_string());
+                    continue;
+                }
+                _ => (),
+            }
+
+            let overrides = core::mem::take(&mut self.overrides);
+
+            let path = match overrides.path {
+                Some(path) => path,
+                None => {
+                    let name = text(&header[0..100])?;
+                    let prefix = match header[MAGIC_OFFSET..].starts_with(MAGIC_USTAR) {
+                        true => text(&header[345..500])?,
+                        false => "",
+                    };
+
+                    match prefix {
+                        "" => name.to_string(),
+                        prefix => alloc::format!("{}/{}", prefix, name),
+                    }
+                }
+            };
+
+            let link = match overrides.link {
+                Some(link) => link,
+                None => text(&header[157..257])?.to_string(),
+            };
+
+            let kind = match kind {
+                TYPE_FILE | TYPE_OLD_FILE | TYPE_CONTIGUOUS => EntryKind::File(contents),
+                TYPE_HARD_LINK => EntryKind::HardLink {
+                    target: normalize(&link),
+                    contents: &[],
+                },
+                TYPE_SYMLINK => EntryKind::Symlink(link),
+                TYPE_DIRECTORY => EntryKind::Directory,
+                _ => EntryKind::Special,
+            };
+
+            return Ok(Some(Entry {
+                path: normalize(&path),
+                kind,
+                mode: number(&header[100..108])? as u32 & 0o7777,
+                uid: match overrides.uid {
+                    Some(uid) => uid,
+                    None => number(&header[108..116])? as u32,
+                },
+                gid: match overrides.gid {
+                    Some(gid) => gid,
+                    None => number(&header[116..124])? as u32,
+                },
+                mtime: match overrides.mtime {
+                    Some(mtime) => mtime,
+                    None => number(&header[136..148])? as i64,
+                },
+            }));
+        }
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Entry<'a>, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let entry = self.read_entry();
+        self.failed = entry.is_err();
+
+        entry.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec, vec::Vec};
+
+    use super::*;
+    use crate::Archive;
+
+    /// Appends a ustar entry to `archive`.
+    fn push(archive: &mut Vec<u8>, name: &str, kind: u8, link: &str, data: &[u8]) {
+        let mut header = vec![0; BLOCK_SIZE];
+
+        let (prefix, name) = match name.len() > 100 {
+            true => name.rsplit_once('/').unwrap(),
+            false => ("", name),
+        };
+
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000755\0");
+        header[108..116].copy_from_slice(b"0001750\0");
+        header[116..124].copy_from_slice(b"0000144\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(format!("{:011o}\0", 1_700_000_000).as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..265].copy_from_slice(b"ustar\x0000");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(BLOCK_SIZE), 0);
+    }
+
+    fn pax_record(key: &str, value: &str) -> Vec<u8> {
+        // The length counts its own digits
+        let rest = key.len() + value.len() + 3;
+        let len = (1..)
+            .map(|digits| rest + digits)
+            .find(|len| len.to_string().len() + rest == *len)
+            .unwrap();
+
+        format!("{} {}={}\n", len, key, value).into_bytes()
+    }
+
+    #[test]
+    fn test_entries() {
+        let long = format!("{}/{}", "d".repeat(120), "file");
+
+        let mut archive = Vec::new();
+        push(&mut archive, "./", TYPE_DIRECTORY, "", &[]);
+        push(&mut archive, "./etc/", TYPE_DIRECTORY, "", &[]);
+        push(&mut archive, "./etc/hostname", TYPE_FILE, "", b"bakaos\n");
+        push(
+            &mut archive,
+            "./etc/alias",
+            TYPE_HARD_LINK,
+            "./etc/hostname",
+            &[],
+        );
+        push(&mut archive, "./init", TYPE_SYMLINK, "/bin/sh", &[]);
+        push(&mut archive, &long, TYPE_FILE, "", &[7; 600]);
+        push(&mut archive, "./fifo", b'6', "", &[]);
+        archive.resize(archive.len() + 2 * BLOCK_SIZE, 0);
+
+        let entries: Vec<_> = Archive::new(&archive)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 7);
+        assert_eq!(entries[0].path, "");
+        assert_eq!(
+            entries[2],
+            Entry {
+                path: "etc/hostname".into(),
+                kind: EntryKind::File(b"bakaos\n"),
+                mode: 0o755,
+                uid: 1000,
+                gid: 100,
+                mtime: 1_700_000_000,
+            }
+        );
+        assert_eq!(
+            entries[3].kind,
+            EntryKind::HardLink {
+                target: "etc/hostname".into(),
+                contents: &[],
+            }
+        );
+        assert_eq!(entries[4].kind, EntryKind::Symlink("/bin/sh".into()));
+        assert_eq!(entries[5].path, long);
+        assert_eq!(entries[5].kind, EntryKind::File(&[7; 600]));
+        assert_eq!(entries[6].kind, EntryKind::Special);
+    }
+
+    #[test]
+    fn test_extensions() {
+        let long = "n".repeat(300);
+
+        let mut pax = pax_record("path", &long);
+        pax.extend(pax_record("linkpath", "target"));
+        pax.extend(pax_record("mtime", "1234.5678"));
+        pax.extend(pax_record("comment", "ignored"));
+
+        let mut archive = Vec::new();
+        push(&mut archive, "PaxHeaders/x", TYPE_PAX, "", &pax);
+        push(&mut archive, "short", TYPE_SYMLINK, "short", &[]);
+        push(
+            &mut archive,
+            "././@LongLink",
+            TYPE_GNU_LONG_NAME,
+            "",
+            format!("{}\0", long).as_bytes(),
+        );
+        push(&mut archive, "short", TYPE_FILE, "", b"gnu");
+        push(&mut archive, "after", TYPE_FILE, "", b"plain");
+
+        let entries: Vec<_> = Archive::new(&archive)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, long);
+        assert_eq!(entries[0].kind, EntryKind::Symlink("target".into()));
+        assert_eq!(entries[0].mtime, 1234);
+        assert_eq!(entries[1].path, long);
+        assert_eq!(entries[1].kind, EntryKind::File(b"gnu"));
+        assert_eq!(entries[2].path, "after");
+        assert_eq!(entries[2].mtime, 1_700_000_000);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(number(b"0000755\0").unwrap(), 0o755);
+        assert_eq!(number(b"  755 \0").unwrap(), 0o755);
+        assert_eq!(number(b"\0\0\0\0").unwrap(), 0);
+        assert_eq!(
+            number(&[0x80, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0]).unwrap(),
+            2 << 32
+        );
+        assert_eq!(number(b"0009"), Err(ArchiveError::Malformed));
+    }
+
+    #[test]
+    fn test_broken_archives() {
+        let mut archive = Vec::new();
+        push(&mut archive, "file", TYPE_FILE, "", &[1; 1000]);
+
+        let mut entries = Archive::new(&archive[..700]).unwrap();
+        assert_eq!(entries.next(), Some(Err(ArchiveError::Truncated)));
+        assert_eq!(entries.next(), None);
+
+        archive[0] = b'F';
+        assert_eq!(
+            Archive::new(&archive).unwrap().next(),
+            Some(Err(ArchiveError::BadChecksum))
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntryType, DirectoryTreeNode, FileMode, FileSystemError, FileSystemResult,
+    InodeAttributes,
+};
+use timing::TimeSpec;
+
+use crate::{Archive, ArchiveError, Entry, EntryKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnpackError {
+    Archive(ArchiveError),
+    /// Creating the entry at `path` failed
+    FileSystem {
+        path: String,
+        error: FileSystemError,
+    },
+}
+
+impl From<ArchiveError> for UnpackError {
+    fn from(error: ArchiveError) -> Self {
+        UnpackError::Archive(error)
+    }
+}
+
+/// Unpacks a newc cpio or a ustar archive into `root`, keeping modes, owners, modification times,
+/// symbolic and hard links. Like Linux does for its initramfs, entries replace what is there
+/// already, except directories, which are merged.
+pub fn unpack(archive: &[u8], root: &Arc<DirectoryTreeNode>) -> Result<(), UnpackError> {
+    // Creating entries inside a directory changes its modification time, so they are set last
+    let mut directories = Vec::new();
+
+    for entry in Archive::new(archive)? {
+        let entry = entry?;
+
+        if entry.path.split('/').any(|part| part == "..") {
+            log::warn!("initramfs: skipping {}, which leaves the root", entry.path);
+            continue;
+        }
+
+        let node = match create(&entry, root) {
+            Ok(Some(node)) => node,
+            Ok(None) => continue,
+            Err(error) => {
+                return Err(UnpackError::FileSystem {
+                    path: entry.path,
+                    error,
+                })
+            }
+        };
+
+        let mtime = TimeSpec::new(entry.mtime, 0);
+        let is_directory = entry.kind == EntryKind::Directory;
+
+        let attributes = InodeAttributes {
+            // Symbolic links have no permissions of their own
+            mode: match entry.kind {
+                EntryKind::Symlink(_) => None,
+                _ => Some(FileMode::from_bits_retain(entry.mode)),
+            },
+            uid: Some(entry.uid),
+            gid: Some(entry.gid),
+            atime: (!is_directory).then_some(mtime),
+            mtime: (!is_directory).then_some(mtime),
+        };
+
+        set_attributes(&node, &attributes, &entry.path)?;
+
+        if is_directory {
+            directories.push((entry.path, node, mtime));
+        }
+    }
+
+    for (path, node, mtime) in directories {
+        let attributes = InodeAttributes {
+            atime: Some(mtime),
+            mtime: Some(mtime),
+            ..Default::default()
+        };
+
+        set_attributes(&node, &attributes, &path)?;
+    }
+
+    Ok(())
+}
+
+fn set_attributes(
+    node: &Arc<DirectoryTreeNode>,
+    attributes: &InodeAttributes,
+    path: &str,
+) -> Result<(), UnpackError> {
+    match node.set_attributes(attributes) {
+        // Not every filesystem keeps them, the files are still usable
+        Ok(()) | Err(FileSystemError::Unimplemented) => Ok(()),
+        Err(error) => Err(UnpackError::FileSystem {
+            path: path.to_string(),
+            error,
+        }),
+    }
+}
+
+/// Creates the node of `entry`, `None` if it is skipped.
+fn create(
+    entry: &Entry,
+    root: &Arc<DirectoryTreeNode>,
+) -> FileSystemResult<Option<Arc<DirectoryTreeNode>>> {
+    let Some((parent, name)) = split(&entry.path) else {
+        // The root itself, only its attributes are taken
+        return Ok(Some(root.clone()));
+    };
+
+    let parent = make_parents(root, parent)?;
+
+    if let Ok(existing) = parent.open_child(name) {
+        let is_directory = existing.metadata().entry_type == DirectoryEntryType::Directory;
+
+        match (is_directory, &entry.kind) {
+            (true, EntryKind::Directory) => return Ok(Some(existing)),
+            (true, _) => parent.rmdir(name)?,
+            (false, _) => parent.remove(name)?,
+        }
+    }
+
+    let node = match &entry.kind {
+        EntryKind::Directory => parent.mkdir(name)?,
+        EntryKind::File(contents) => {
+            let node = parent.touch(name)?;
+            write_all(&node, contents)?;
+            node
+        }
+        EntryKind::Symlink(target) => parent.soft_link(name, target)?,
+        EntryKind::HardLink { target, contents } => {
+            let source = root.open_raw(target, Some(root))?;
+            parent.hard_link(name, &source)?;
+
+            // cpio may bring the contents with the last name only
+            if !contents.is_empty() {
+                source.resize_inode(0)?;
+                write_all(&source, contents)?;
+            }
+
+            parent.open_child(name)?
+        }
+        EntryKind::Special => {
+            log::warn!(
+                "initramfs: skipping {}, special files are not supported",
+                entry.path
+            );
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(node))
+}
+
+/// Splits a path into its parent and its name, `None` for the root.
+fn split(path: &str) -> Option<(&str, &str)> {
+    match path.rsplit_once('/') {
+        Some((parent, name)) => Some((parent, name)),
+        None if path.is_empty() => None,
+        None => Some(("", path)),
+    }
+}
+
+/// Opens the directory at `path`, creating what is missing of it like `mkdir -p`.
+fn make_parents(
+    root: &Arc<DirectoryTreeNode>,
+    path: &str,
+) -> FileSystemResult<Arc<DirectoryTreeNode>> {
+    let mut current = root.clone();
+
+    for part in path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+    {
+        current = match current.open_child(part) {
+            Ok(child) => child.resolve_all_link(Some(root))?,
+            Err(FileSystemError::NotFound) => current.mkdir(part)?,
+            Err(e) => return Err(e),
+        };
+    }
+
+    Ok(current)
+}
+
+fn write_all(node: &Arc<DirectoryTreeNode>, mut contents: &[u8]) -> FileSystemResult<()> {
+    let mut offset = 0;
+
+    while !contents.is_empty() {
+        match node.writeat(offset, contents)? {
+            0 => return Err(FileSystemError::WriteZero),
+            written => {
+                offset += written;
+                contents = &contents[written..];
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use alloc::{format, vec::Vec};
+    use filesystem_abstractions::FileStatistics;
+    use threading::IClock;
+    use tmpfs::TmpFileSystem;
+
+    use super::*;
+    use crate::cpio::tests::{push, trailer};
+
+    struct FixedClock;
+
+    impl IClock for FixedClock {
+        fn now(&self) -> TimeSpec {
+            TimeSpec::new(42, 0)
+        }
+    }
+
+    fn tmpfs() -> Arc<DirectoryTreeNode> {
+        DirectoryTreeNode::from_filesystem(None, TmpFileSystem::new(Arc::new(FixedClock)), Some(""))
+    }
+
+    fn stat(root: &Arc<DirectoryTreeNode>, path: &str) -> FileStatistics {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        root.open_raw(path, Some(root))
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+
+        stat
+    }
+
+    fn read(root: &Arc<DirectoryTreeNode>, path: &str) -> Vec<u8> {
+        root.open(path, Some(root)).unwrap().readall().unwrap()
+    }
+
+    /// Packs a small tree with a host archiver, `None` if it is not installed.
+    ///
+    /// The tree has symlinks and a setuid file, which only unix hosts can make.
+    #[cfg(unix)]
+    fn host_archive(program: &str, format: &[&str]) -> Option<Vec<u8>> {
+        use std::{
+            os::unix::fs::{symlink, PermissionsExt},
+            process::Command,
+        };
+        use test_utilities::tempdir::TempDir;
+
+        let dir = TempDir::new("initramfs");
+        let tree = dir.join("tree");
+
+        std::fs::create_dir_all(tree.join("bin")).unwrap();
+        std::fs::create_dir_all(tree.join(format!("deep/{}", "long".repeat(20)))).unwrap();
+        std::fs::write(tree.join("bin/busybox"), b"\x7fELF busybox").unwrap();
+        std::fs::set_permissions(tree.join("bin/busybox"), PermissionsExt::from_mode(0o4755))
+            .unwrap();
+        std::fs::hard_link(tree.join("bin/busybox"), tree.join("bin/ls")).unwrap();
+        symlink("busybox", tree.join("bin/sh")).unwrap();
+        symlink("/bin/busybox", tree.join("init")).unwrap();
+        std::fs::write(
+            tree.join(format!("deep/{}/file", "long".repeat(20))),
+            b"deep",
+        )
+        .unwrap();
+
+        let output = dir.join("archive");
+        let status = Command::new(program)
+            .args(format)
+            .arg("-cf")
+            .arg(&output)
+            .arg("-C")
+            .arg(&tree)
+            .arg(".")
+            .status()
+            .ok()?;
+
+        assert!(status.success());
+
+        Some(std::fs::read(output).unwrap())
+    }
+
+    #[cfg(unix)]
+    fn check_host_tree(root: &Arc<DirectoryTreeNode>) {
+        assert_eq!(read(root, "/init"), b"\x7fELF busybox");
+        assert_eq!(read(root, "/bin/sh"), b"\x7fELF busybox");
+        assert_eq!(read(root, "/bin/ls"), b"\x7fELF busybox");
+        assert_eq!(
+            read(root, &format!("/deep/{}/file", "long".repeat(20))),
+            b"deep"
+        );
+
+        let busybox = stat(root, "/bin/busybox");
+        assert_eq!(busybox.mode.bits() & 0o7777, 0o4755);
+        assert_eq!(busybox.link_count, 2);
+        assert_eq!(busybox.inode_id, stat(root, "/bin/ls").inode_id);
+
+        assert_eq!(
+            root.open_raw("/bin/sh", Some(root)).unwrap().resolve_link(),
+            Some("busybox".into())
+        );
+    }
+
+    #[test]
+    fn test_unpack() {
+        let mut archive = Vec::new();
+        push(&mut archive, ".", 0o040700, 1, 2, &[]);
+        push(&mut archive, "bin", 0o040755, 2, 2, &[]);
+        push(&mut archive, "bin/busybox", 0o104755, 3, 2, &[]);
+        push(&mut archive, "bin/ls", 0o104755, 3, 2, b"\x7fELF");
+        push(&mut archive, "bin/sh", 0o120777, 4, 1, b"busybox");
+        push(&mut archive, "etc/motd", 0o100600, 5, 1, b"hello");
+        push(&mut archive, "init", 0o120777, 6, 1, b"/bin/sh");
+        push(&mut archive, "dev/console", 0o020600, 7, 1, &[]);
+        trailer(&mut archive);
+
+        let root = tmpfs();
+        unpack(&archive, &root).unwrap();
+
+        assert_eq!(read(&root, "/init"), b"\x7fELF");
+        assert_eq!(read(&root, "/bin/busybox"), b"\x7fELF");
+        assert_eq!(read(&root, "/etc/motd"), b"hello");
+        assert!(root.open("/dev/console", Some(&root)).is_err());
+
+        let busybox = stat(&root, "/bin/busybox");
+        assert_eq!(busybox.mode.bits(), 0o104755);
+        assert_eq!((busybox.uid, busybox.gid), (1000, 100));
+        assert_eq!(busybox.link_count, 2);
+        assert_eq!(busybox.inode_id, stat(&root, "/bin/ls").inode_id);
+        assert_eq!(busybox.mtime, TimeSpec::new(1_700_000_000, 0));
+
+        let bin = stat(&root, "/bin");
+        assert_eq!(bin.mode.bits() & 0o7777, 0o755);
+        assert_eq!(bin.mtime, TimeSpec::new(1_700_000_000, 0));
+
+        // Made for the file in it, which came first
+        assert_eq!(stat(&root, "/etc").mode.bits() & 0o7777, 0o755);
+        assert_eq!(stat(&root, "/").mode.bits() & 0o7777, 0o700);
+        assert_eq!(stat(&root, "/bin/sh").mode.bits() & 0o7777, 0o777);
+    }
+
+    #[test]
+    fn test_replace() {
+        let root = tmpfs();
+        root.mkdir("etc").unwrap().touch("kept").unwrap();
+        root.touch("init").unwrap();
+        root.mkdir("empty").unwrap();
+
+        let mut archive = Vec::new();
+        push(&mut archive, "etc", 0o040750, 1, 2, &[]);
+        push(&mut archive, "etc/new", 0o100644, 2, 1, b"new");
+        push(&mut archive, "init", 0o120777, 3, 1, b"/etc/new");
+        push(&mut archive, "empty", 0o100644, 4, 1, b"file");
+        trailer(&mut archive);
+
+        unpack(&archive, &root).unwrap();
+
+        assert!(root.open("/etc/kept", Some(&root)).is_ok());
+        assert_eq!(stat(&root, "/etc").mode.bits() & 0o7777, 0o750);
+        assert_eq!(read(&root, "/init"), b"new");
+        assert_eq!(read(&root, "/empty"), b"file");
+
+        // A directory that is not empty stays
+        let mut archive = Vec::new();
+        push(&mut archive, "etc", 0o100644, 5, 1, b"file");
+        trailer(&mut archive);
+
+        assert_eq!(
+            unpack(&archive, &root),
+            Err(UnpackError::FileSystem {
+                path: "etc".into(),
+                error: FileSystemError::DirectoryNotEmpty,
+            })
+        );
+    }
+
+    #[test]
+    fn test_escaping_paths() {
+        let mut archive = Vec::new();
+        push(&mut archive, "../outside", 0o100644, 1, 1, b"no");
+        push(&mut archive, "a/../../outside", 0o100644, 2, 1, b"no");
+        push(&mut archive, "inside", 0o100644, 3, 1, b"yes");
+        trailer(&mut archive);
+
+        let parent = tmpfs();
+        let root = parent.mkdir("root").unwrap();
+
+        unpack(&archive, &root).unwrap();
+
+        assert!(parent.open_child("outside").is_err());
+        assert!(root.open_child("a").is_err());
+        assert_eq!(
+            root.open_child("inside").unwrap().readall().unwrap(),
+            b"yes"
+        );
+    }
+
+    #[test]
+    fn test_broken_archive() {
+        assert_eq!(
+            unpack(b"garbage", &tmpfs()),
+            Err(UnpackError::Archive(ArchiveError::UnknownFormat))
+        );
+
+        let mut archive = Vec::new();
+        push(&mut archive, "file", 0o100644, 1, 1, b"data");
+        archive.truncate(archive.len() - 4);
+
+        assert_eq!(
+            unpack(&archive, &tmpfs()),
+            Err(UnpackError::Archive(ArchiveError::Truncated))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_host_cpio() {
+        let Some(archive) = host_archive("bsdtar", &["--format", "newc"]) else {
+            return;
+        };
+
+        let root = tmpfs();
+        unpack(&archive, &root).unwrap();
+
+        check_host_tree(&root);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_host_tar() {
+        for format in ["--format=ustar", "--format=pax", "--format=gnu"] {
+            let Some(archive) = host_archive("tar", &[format]) else {
+                return;
+            };
+
+            let root = tmpfs();
+            unpack(&archive, &root).unwrap();
+
+            check_host_tree(&root);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#![feature(future_join)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod mqueue;
+mod msg;
+mod permissions;
+mod sem;
+mod shm;
+mod table;
+
+pub use mqueue::*;
+pub use msg::*;
+pub use permissions::*;
+pub use sem::*;
+pub use shm::*;
+pub use table::*;
@@ -0,0 +1,650 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use constants::ErrNo;
+use core::{
+    cmp::Reverse,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, DirectoryTreeNode, FileStatistics, FileStatisticsMode,
+    FileSystemError, FileSystemResult, IFileSystem, IFileSystemType, IInode, InodeAttributes,
+    InodeMetadata, MountFlags,
+};
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+use timing::TimeSpec;
+
+/// Priorities are below this, `MQ_PRIO_MAX`.
+pub const MQ_PRIO_MAX: u32 = 32768;
+
+/// Most messages of a queue, `HARD_MSGMAX`.
+pub const MQ_MAXMSG_MAX: usize = 65536;
+
+/// Largest message of a queue, `HARD_MSGSIZEMAX`.
+pub const MQ_MSGSIZE_MAX: usize = 16 * 1024 * 1024;
+
+/// How many messages of which size a POSIX message queue holds, fixed once it is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageQueueAttributes {
+    pub max_messages: usize,
+    pub message_size: usize,
+}
+
+impl MessageQueueAttributes {
+    /// Whether a process that is not an admin may create a queue this large, as the
+    /// `msg_max` and `msgsize_max` sysctls of Linux allow by default.
+    pub fn within_defaults(&self) -> bool {
+        let defaults = MessageQueueAttributes::default();
+
+        self.max_messages <= defaults.max_messages && self.message_size <= defaults.message_size
+    }
+}
+
+impl Default for MessageQueueAttributes {
+    /// What a queue created without attributes gets.
+    fn default() -> Self {
+        MessageQueueAttributes {
+            max_messages: 10,
+            message_size: 8192,
+        }
+    }
+}
+
+/// How the process `pid` asked with `mq_notify` to learn about a message arriving in the empty
+/// queue, the fields of `struct sigevent` that matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Notification {
+    pub pid: u32,
+    /// `sigev_notify`
+    pub method: i32,
+    pub signo: i32,
+    pub value: u64,
+}
+
+struct PosixMessageQueueInner {
+    /// The highest priority first, in the order they were sent within one
+    messages: BTreeMap<Reverse<u32>, VecDeque<Vec<u8>>>,
+    count: usize,
+    bytes: usize,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    notification: Option<Notification>,
+}
+
+/// A POSIX message queue, which is a file of the mqueue filesystem.
+///
+/// Reading the file gives a line about the state of the queue, like on Linux.
+pub struct PosixMessageQueue {
+    name: String,
+    inode_id: u64,
+    attributes: MessageQueueAttributes,
+    inner: SpinMutex<PosixMessageQueueInner>,
+    readable: WaitQueue,
+    writable: WaitQueue,
+}
+
+impl PosixMessageQueue {
+    pub fn attributes(&self) -> MessageQueueAttributes {
+        self.attributes
+    }
+
+    /// How many messages are queued.
+    pub fn len(&self) -> usize {
+        self.inner.lock().count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn notification(&self) -> Option<Notification> {
+        self.inner.lock().notification
+    }
+
+    /// Registers `notification`, which fails with `EBUSY` if a process already is. `None` takes
+    /// the registration of `pid` back.
+    pub fn set_notification(
+        &self,
+        notification: Option<Notification>,
+        pid: u32,
+    ) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+
+        match notification {
+            Some(_) if inner.notification.is_some() => Err(ErrNo::DeviceOrResourceBusy),
+            Some(_) => {
+                inner.notification = notification;
+                Ok(())
+            }
+            None => {
+                if inner.notification.is_some_and(|n| n.pid == pid) {
+                    inner.notification = None;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Queues `data` with `priority`, waiting until there is room unless `nonblocking` is set.
+    ///
+    /// Returns the notification the message sets off, which happens once for a message arriving
+    /// in the empty queue while no one waits to receive.
+    pub async fn send(
+        &self,
+        data: Vec<u8>,
+        priority: u32,
+        nonblocking: bool,
+    ) -> Result<Option<Notification>, ErrNo> {
+        if data.len() > self.attributes.message_size {
+            return Err(ErrNo::MessageTooLong);
+        }
+
+        let mut data = Some(data);
+
+        let notification = self
+            .writable
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+
+                if inner.count >= self.attributes.max_messages {
+                    return nonblocking.then_some(Err(ErrNo::ResourceTemporarilyUnavailable));
+                }
+
+                let data = data.take()?;
+                let was_empty = inner.count == 0;
+
+                inner.count += 1;
+                inner.bytes += data.len();
+                inner
+                    .messages
+                    .entry(Reverse(priority))
+                    .or_default()
+                    .push_back(data);
+
+                match was_empty && self.readable.is_empty() {
+                    true => Some(Ok(inner.notification.take())),
+                    false => Some(Ok(None)),
+                }
+            })
+            .await?;
+
+        self.readable.notify_one();
+
+        Ok(notification)
+    }
+
+    /// Takes the oldest message of the highest priority along with its priority, waiting until
+    /// there is one unless `nonblocking` is set.
+    ///
+    /// Like on Linux, `max_len` has to hold the largest message the queue can have.
+    pub async fn receive(
+        &self,
+        max_len: usize,
+        nonblocking: bool,
+    ) -> Result<(Vec<u8>, u32), ErrNo> {
+        if max_len < self.attributes.message_size {
+            return Err(ErrNo::MessageTooLong);
+        }
+
+        let received = self
+            .readable
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+
+                let Some(mut entry) = inner.messages.first_entry() else {
+                    return nonblocking.then_some(Err(ErrNo::ResourceTemporarilyUnavailable));
+                };
+
+                let Reverse(priority) = *entry.key();
+                let data = entry.get_mut().pop_front()?;
+
+                if entry.get().is_empty() {
+                    entry.remove();
+                }
+
+                inner.count -= 1;
+                inner.bytes -= data.len();
+
+                Some(Ok((data, priority)))
+            })
+            .await?;
+
+        self.writable.notify_one();
+
+        Ok(received)
+    }
+
+    /// The line reading the file gives.
+    fn status_line(&self) -> String {
+        let inner = self.inner.lock();
+        let notification = inner.notification;
+
+        format!(
+            "QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}\n",
+            inner.bytes,
+            notification.map_or(0, |n| n.method),
+            notification.map_or(0, |n| n.signo),
+            notification.map_or(0, |n| n.pid),
+        )
+    }
+}
+
+impl IInode for PosixMessageQueue {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: DirectoryEntryType::File,
+            size: 0,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let line = self.status_line();
+        let line = line.as_bytes().get(offset..).unwrap_or_default();
+        let len = line.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&line[..len]);
+
+        Ok(len)
+    }
+
+    fn writeat(&self, _offset: usize, _buffer: &[u8]) -> FileSystemResult<usize> {
+        Err(FileSystemError::InvalidInput)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let inner = self.inner.lock();
+
+        fill_stat(stat, self.inode_id, FileStatisticsMode::FILE, inner.mode);
+        stat.uid = inner.uid;
+        stat.gid = inner.gid;
+        stat.size = inner.bytes as u64;
+
+        Ok(())
+    }
+
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        let mut inner = self.inner.lock();
+
+        if let Some(mode) = attributes.mode {
+            inner.mode = mode.bits() & 0o777;
+        }
+
+        inner.uid = attributes.uid.unwrap_or(inner.uid);
+        inner.gid = attributes.gid.unwrap_or(inner.gid);
+
+        Ok(())
+    }
+}
+
+/// The mqueue filesystem, a flat directory of POSIX message queues.
+///
+/// `mq_open` and `mq_unlink` name queues in the instance of the kernel, every mount of the type
+/// shows that same instance.
+pub struct MqueueFileSystem {
+    this: Weak<MqueueFileSystem>,
+    queues: SpinMutex<BTreeMap<String, Arc<PosixMessageQueue>>>,
+    next_inode: AtomicU64,
+}
+
+impl MqueueFileSystem {
+    const ROOT_INODE: u64 = 1;
+
+    pub fn new() -> Arc<MqueueFileSystem> {
+        Arc::new_cyclic(|this| MqueueFileSystem {
+            this: this.clone(),
+            queues: SpinMutex::new(BTreeMap::new()),
+            next_inode: AtomicU64::new(Self::ROOT_INODE + 1),
+        })
+    }
+
+    /// The filesystem `root` is the root directory of, if it is an mqueue filesystem.
+    pub fn of(root: &DirectoryTreeNode) -> Option<Arc<MqueueFileSystem>> {
+        let inode = root.inode()?;
+        let root = inode.downcast_ref::<MqueueRootInode>()?;
+
+        Some(root.fs.clone())
+    }
+
+    /// Creates the empty queue `name` owned by `uid` and `gid`, which fails with
+    /// `AlreadyExists` if there is one.
+    pub fn create(
+        &self,
+        name: &str,
+        attributes: MessageQueueAttributes,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    ) -> FileSystemResult<Arc<PosixMessageQueue>> {
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        if name.contains('/') || name.contains('\0') {
+            return Err(FileSystemError::PathContainsInvalidCharacter);
+        }
+
+        let mut queues = self.queues.lock();
+
+        if queues.contains_key(name) {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        let queue = Arc::new(PosixMessageQueue {
+            name: name.to_string(),
+            inode_id: self.next_inode.fetch_add(1, Ordering::Relaxed),
+            attributes,
+            inner: SpinMutex::new(PosixMessageQueueInner {
+                messages: BTreeMap::new(),
+                count: 0,
+                bytes: 0,
+                mode: mode & 0o777,
+                uid,
+                gid,
+                notification: None,
+            }),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+        });
+
+        queues.insert(name.to_string(), queue.clone());
+
+        Ok(queue)
+    }
+}
+
+impl IFileSystem for MqueueFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        Arc::new(MqueueRootInode {
+            fs: self.this.upgrade().unwrap(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "mqueue"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+}
+
+/// The directory holding all queues. Creating a file in it creates a queue with the default
+/// attributes, removing one removes the queue once it is closed everywhere.
+struct MqueueRootInode {
+    fs: Arc<MqueueFileSystem>,
+}
+
+impl IInode for MqueueRootInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: "",
+            entry_type: DirectoryEntryType::Directory,
+            size: 0,
+        }
+    }
+
+    fn mkdir(&self, _name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn rmdir(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        match self.fs.queues.lock().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(FileSystemError::NotFound),
+        }
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let queue = self
+            .fs
+            .create(name, MessageQueueAttributes::default(), 0o644, 0, 0)?;
+
+        Ok(queue)
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        Ok(self
+            .fs
+            .queues
+            .lock()
+            .keys()
+            .map(|name| DirectoryEntry {
+                filename: name.clone(),
+                entry_type: DirectoryEntryType::File,
+            })
+            .collect())
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        match self.fs.queues.lock().get(name) {
+            Some(queue) => Ok(queue.clone()),
+            None => Err(FileSystemError::NotFound),
+        }
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        // Anyone can create queues, but only remove their own
+        fill_stat(
+            stat,
+            MqueueFileSystem::ROOT_INODE,
+            FileStatisticsMode::DIR,
+            0o1777,
+        );
+        stat.link_count = 2;
+
+        Ok(())
+    }
+}
+
+/// Lets `mount` show the queues of the kernel, every mount is the same instance.
+pub struct MqueueFileSystemType {
+    fs: Arc<MqueueFileSystem>,
+}
+
+impl MqueueFileSystemType {
+    pub fn new(fs: Arc<MqueueFileSystem>) -> Arc<MqueueFileSystemType> {
+        Arc::new(MqueueFileSystemType { fs })
+    }
+}
+
+impl IFileSystemType for MqueueFileSystemType {
+    fn name(&self) -> &str {
+        "mqueue"
+    }
+
+    fn create(&self, _source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        Ok(self.fs.clone())
+    }
+
+    fn is_cacheable(&self) -> bool {
+        false
+    }
+}
+
+fn fill_stat(
+    stat: &mut FileStatistics,
+    inode_id: u64,
+    file_type: FileStatisticsMode,
+    permissions: u32,
+) {
+    stat.device_id = 0;
+    stat.inode_id = inode_id;
+    stat.mode = FileStatisticsMode::from_bits_retain(file_type.bits() | permissions);
+    stat.link_count = 1;
+    stat.uid = 0;
+    stat.gid = 0;
+    stat.rdev = 0;
+    stat.size = 0;
+    stat.block_size = 4096;
+    stat.block_count = 0;
+    stat.atime = TimeSpec::zero();
+    stat.mtime = TimeSpec::zero();
+    stat.ctime = TimeSpec::zero();
+}
+
+#[cfg(test)]
+mod tests {
+    use threading::block_on;
+
+    use super::*;
+
+    fn setup() -> (Arc<MqueueFileSystem>, Arc<DirectoryTreeNode>) {
+        let fs = MqueueFileSystem::new();
+        let root = DirectoryTreeNode::from_filesystem(None, fs.clone(), Some(""));
+
+        (fs, root)
+    }
+
+    fn attributes(max_messages: usize, message_size: usize) -> MessageQueueAttributes {
+        MessageQueueAttributes {
+            max_messages,
+            message_size,
+        }
+    }
+
+    #[test]
+    fn test_by_priority() {
+        let (fs, _) = setup();
+        let queue = fs.create("queue", attributes(4, 8), 0o600, 0, 0).unwrap();
+
+        for (data, priority) in [(b"low", 1), (b"top", 9), (b"mid", 5), (b"one", 9)] {
+            block_on!(queue.send(data.to_vec(), priority, true)).unwrap();
+        }
+
+        assert_eq!(
+            block_on!(queue.send(b"full".to_vec(), 0, true)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+        assert_eq!(
+            block_on!(queue.receive(4, true)),
+            Err(ErrNo::MessageTooLong)
+        );
+
+        let received: Vec<_> = (0..4)
+            .map(|_| block_on!(queue.receive(8, true)).unwrap())
+            .collect();
+
+        assert_eq!(
+            received,
+            [
+                (b"top".to_vec(), 9),
+                (b"one".to_vec(), 9),
+                (b"mid".to_vec(), 5),
+                (b"low".to_vec(), 1)
+            ]
+        );
+        assert_eq!(
+            block_on!(queue.receive(8, true)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_waits_for_a_message() {
+        let (fs, _) = setup();
+        let queue = fs.create("queue", attributes(1, 8), 0o600, 0, 0).unwrap();
+
+        let receiver = queue.receive(8, false);
+        let sender = async {
+            threading::yield_now().await;
+            queue.send(b"message".to_vec(), 0, false).await
+        };
+
+        assert_eq!(
+            block_on!(receiver, sender),
+            (Ok((b"message".to_vec(), 0)), Ok(None))
+        );
+    }
+
+    #[test]
+    fn test_notification() {
+        let (fs, _) = setup();
+        let queue = fs.create("queue", attributes(4, 8), 0o600, 0, 0).unwrap();
+        let notification = Notification {
+            pid: 2,
+            method: 0,
+            signo: 10,
+            value: 42,
+        };
+
+        queue.set_notification(Some(notification), 2).unwrap();
+        assert_eq!(
+            queue.set_notification(Some(notification), 3),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+
+        // Only someone else's registration stays
+        queue.set_notification(None, 3).unwrap();
+        assert_eq!(queue.notification(), Some(notification));
+
+        let mut line = [0u8; 64];
+        let len = queue.readat(0, &mut line).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&line[..len]).unwrap(),
+            "QSIZE:0          NOTIFY:0     SIGNO:10    NOTIFY_PID:2     \n"
+        );
+
+        // Goes off once, for the message arriving in the empty queue
+        assert_eq!(
+            block_on!(queue.send(b"first".to_vec(), 0, true)),
+            Ok(Some(notification))
+        );
+        assert_eq!(block_on!(queue.send(b"second".to_vec(), 0, true)), Ok(None));
+        assert_eq!(queue.notification(), None);
+    }
+
+    #[test]
+    fn test_as_files() {
+        let (fs, root) = setup();
+        assert!(Arc::ptr_eq(&MqueueFileSystem::of(&root).unwrap(), &fs));
+
+        fs.create("queue", attributes(4, 8), 0o640, 1000, 100)
+            .unwrap();
+
+        let node = root.open_child("queue").unwrap();
+        let inode = node.inode().unwrap();
+        let queue = inode.downcast_ref::<PosixMessageQueue>().unwrap();
+        assert_eq!(queue.attributes(), attributes(4, 8));
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        node.stat(&mut stat).unwrap();
+        assert_eq!(stat.mode.bits(), 0o100640);
+        assert_eq!((stat.uid, stat.gid), (1000, 100));
+
+        assert_eq!(
+            fs.create("queue", attributes(4, 8), 0o600, 0, 0).err(),
+            Some(FileSystemError::AlreadyExists)
+        );
+        assert!(root.touch("other").is_ok());
+        // The two queues and "."
+        assert_eq!(root.read_dir().unwrap().len(), 3);
+
+        // Still usable by those who have it open
+        root.remove("queue").unwrap();
+        assert!(root.open_child("queue").is_err());
+        assert_eq!(block_on!(queue.send(b"kept".to_vec(), 0, true)), Ok(None));
+    }
+}
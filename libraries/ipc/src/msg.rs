@@ -0,0 +1,362 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use constants::ErrNo;
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+
+use crate::{IIpcObject, IpcPermissions};
+
+/// Most message queues a kernel can have, `MSGMNI`.
+pub const MSGMNI: usize = 32000;
+
+/// Largest message, `MSGMAX`.
+pub const MSGMAX: usize = 8192;
+
+/// How many bytes a queue holds by default, and at most unless raised by an admin, `MSGMNB`.
+pub const MSGMNB: usize = 16384;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The positive type the sender gave it, which receivers can pick messages by
+    pub kind: i64,
+    pub data: Vec<u8>,
+}
+
+/// Which message `msgrcv` takes, by the type it asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageSelector {
+    /// The first message
+    Any,
+    /// The first message of the type
+    Kind(i64),
+    /// The first message of any other type, with `MSG_EXCEPT`
+    NotKind(i64),
+    /// The first message of the lowest type up to the given one
+    AtMost(i64),
+}
+
+impl MessageSelector {
+    /// The selector of `msgtyp`, with or without `MSG_EXCEPT`.
+    pub fn new(msgtyp: i64, except: bool) -> MessageSelector {
+        match msgtyp {
+            0 => MessageSelector::Any,
+            kind if kind < 0 => MessageSelector::AtMost(-kind),
+            kind if except => MessageSelector::NotKind(kind),
+            kind => MessageSelector::Kind(kind),
+        }
+    }
+
+    fn select(self, messages: &VecDeque<Message>) -> Option<usize> {
+        let mut messages = messages.iter().enumerate();
+
+        match self {
+            MessageSelector::Any => messages.next().map(|(index, _)| index),
+            MessageSelector::Kind(kind) => messages.find(|(_, m)| m.kind == kind).map(|m| m.0),
+            MessageSelector::NotKind(kind) => messages.find(|(_, m)| m.kind != kind).map(|m| m.0),
+            MessageSelector::AtMost(kind) => messages
+                .filter(|(_, m)| m.kind <= kind)
+                .min_by_key(|(index, m)| (m.kind, *index))
+                .map(|(index, _)| index),
+        }
+    }
+}
+
+/// What `msgctl` reports about a queue besides the messages in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageQueueStatus {
+    pub permissions: IpcPermissions,
+    /// When a message was last sent and received and when the queue was last changed otherwise,
+    /// in seconds since the epoch
+    pub send_time: i64,
+    pub receive_time: i64,
+    pub change_time: i64,
+    pub last_sender: u32,
+    pub last_receiver: u32,
+    /// How many bytes of messages the queue holds, which also bounds the number of messages
+    pub max_bytes: usize,
+}
+
+struct MessageQueueInner {
+    messages: VecDeque<Message>,
+    bytes: usize,
+    removed: bool,
+}
+
+/// A System V message queue.
+pub struct MessageQueue {
+    status: SpinMutex<MessageQueueStatus>,
+    inner: SpinMutex<MessageQueueInner>,
+    readable: WaitQueue,
+    writable: WaitQueue,
+}
+
+impl MessageQueue {
+    pub fn new(permissions: IpcPermissions, now: i64) -> MessageQueue {
+        MessageQueue {
+            status: SpinMutex::new(MessageQueueStatus {
+                permissions,
+                send_time: 0,
+                receive_time: 0,
+                change_time: now,
+                last_sender: 0,
+                last_receiver: 0,
+                max_bytes: MSGMNB,
+            }),
+            inner: SpinMutex::new(MessageQueueInner {
+                messages: VecDeque::new(),
+                bytes: 0,
+                removed: false,
+            }),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+        }
+    }
+
+    pub fn status(&self) -> &SpinMutex<MessageQueueStatus> {
+        &self.status
+    }
+
+    /// How many messages are queued.
+    pub fn len(&self) -> usize {
+        self.inner.lock().messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many bytes the queued messages have.
+    pub fn bytes(&self) -> usize {
+        self.inner.lock().bytes
+    }
+
+    /// Lets the queue hold `max_bytes`, waking senders that may fit now.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        self.status.lock().max_bytes = max_bytes;
+        self.writable.notify_all();
+    }
+
+    /// Queues `message`, waiting until there is room for it unless `nowait` is set.
+    pub async fn send(
+        &self,
+        message: Message,
+        nowait: bool,
+        pid: u32,
+        now: i64,
+    ) -> Result<(), ErrNo> {
+        let mut message = Some(message);
+
+        self.writable
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+
+                if inner.removed {
+                    return Some(Err(ErrNo::IdentifierRemoved));
+                }
+
+                let len = message.as_ref().map_or(0, |m| m.data.len());
+                let max_bytes = self.status.lock().max_bytes;
+
+                if inner.bytes + len > max_bytes || inner.messages.len() + 1 > max_bytes {
+                    return nowait.then_some(Err(ErrNo::ResourceTemporarilyUnavailable));
+                }
+
+                inner.bytes += len;
+                inner.messages.extend(message.take());
+
+                Some(Ok(()))
+            })
+            .await?;
+
+        let mut status = self.status.lock();
+        status.send_time = now;
+        status.last_sender = pid;
+        drop(status);
+
+        self.readable.notify_all();
+
+        Ok(())
+    }
+
+    /// Takes the message `selector` picks, waiting until there is one unless `nowait` is set.
+    ///
+    /// A message longer than `max_len` stays queued and fails with `E2BIG`, unless `truncate`
+    /// lets the rest of it go.
+    pub async fn receive(
+        &self,
+        selector: MessageSelector,
+        max_len: usize,
+        truncate: bool,
+        nowait: bool,
+        pid: u32,
+        now: i64,
+    ) -> Result<Message, ErrNo> {
+        let mut message = self
+            .readable
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+
+                if inner.removed {
+                    return Some(Err(ErrNo::IdentifierRemoved));
+                }
+
+                let Some(index) = selector.select(&inner.messages) else {
+                    return nowait.then_some(Err(ErrNo::NoMessageOfDesiredType));
+                };
+
+                if inner.messages[index].data.len() > max_len && !truncate {
+                    return Some(Err(ErrNo::ArgumentListTooLong));
+                }
+
+                let message = inner.messages.remove(index)?;
+                inner.bytes -= message.data.len();
+
+                Some(Ok(message))
+            })
+            .await?;
+
+        message.data.truncate(max_len);
+
+        let mut status = self.status.lock();
+        status.receive_time = now;
+        status.last_receiver = pid;
+        drop(status);
+
+        self.writable.notify_all();
+
+        Ok(message)
+    }
+
+    /// Fails every send and receive waiting on the queue, and the ones to come.
+    pub fn remove(&self) {
+        self.inner.lock().removed = true;
+
+        self.readable.notify_all();
+        self.writable.notify_all();
+    }
+}
+
+impl IIpcObject for MessageQueue {
+    fn permissions(&self) -> IpcPermissions {
+        self.status.lock().permissions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+    use threading::block_on;
+
+    use super::*;
+
+    fn message(kind: i64, data: &[u8]) -> Message {
+        Message {
+            kind,
+            data: data.to_vec(),
+        }
+    }
+
+    fn queue_with(kinds: &[i64]) -> MessageQueue {
+        let permissions = IpcPermissions::new(0, 0o600, &Credentials::root());
+        let queue = MessageQueue::new(permissions, 0);
+
+        for kind in kinds {
+            let data = kind.to_ne_bytes();
+            block_on!(queue.send(message(*kind, &data), true, 1, 0)).unwrap();
+        }
+
+        queue
+    }
+
+    fn kinds_received(queue: &MessageQueue, selector: MessageSelector) -> Vec<i64> {
+        let mut kinds = Vec::new();
+
+        while let Ok(m) = block_on!(queue.receive(selector, 8, false, true, 2, 0)) {
+            kinds.push(m.kind);
+        }
+
+        kinds
+    }
+
+    #[test]
+    fn test_selectors() {
+        let queue = queue_with(&[3, 1, 2, 1]);
+        assert_eq!(kinds_received(&queue, MessageSelector::Kind(1)), [1, 1]);
+        assert_eq!(kinds_received(&queue, MessageSelector::Any), [3, 2]);
+
+        let queue = queue_with(&[3, 1, 2, 1]);
+        assert_eq!(
+            kinds_received(&queue, MessageSelector::NotKind(3)),
+            [1, 2, 1]
+        );
+
+        let queue = queue_with(&[3, 2, 4, 1, 2]);
+        assert_eq!(
+            kinds_received(&queue, MessageSelector::AtMost(2)),
+            [1, 2, 2]
+        );
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(MessageSelector::new(-2, false), MessageSelector::AtMost(2));
+        assert_eq!(MessageSelector::new(2, true), MessageSelector::NotKind(2));
+    }
+
+    #[test]
+    fn test_too_long() {
+        let queue = queue_with(&[]);
+        block_on!(queue.send(message(1, b"message"), true, 1, 0)).unwrap();
+
+        let receive = |truncate| queue.receive(MessageSelector::Any, 4, truncate, true, 2, 0);
+
+        assert_eq!(block_on!(receive(false)), Err(ErrNo::ArgumentListTooLong));
+        assert_eq!(queue.bytes(), 7);
+        assert_eq!(block_on!(receive(true)), Ok(message(1, b"mess")));
+        assert_eq!(queue.bytes(), 0);
+    }
+
+    #[test]
+    fn test_waits_for_room() {
+        let queue = queue_with(&[]);
+        queue.set_max_bytes(8);
+
+        block_on!(queue.send(message(1, &[1; 6]), true, 1, 0)).unwrap();
+        assert_eq!(
+            block_on!(queue.send(message(1, &[2; 6]), true, 1, 0)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let sender = queue.send(message(2, &[2; 6]), false, 1, 42);
+        let receiver = async {
+            threading::yield_now().await;
+            queue
+                .receive(MessageSelector::Any, 8, false, false, 2, 0)
+                .await
+        };
+
+        assert_eq!(
+            block_on!(sender, receiver),
+            (Ok(()), Ok(message(1, &[1; 6])))
+        );
+        assert_eq!(queue.status().lock().send_time, 42);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_removed_while_waiting() {
+        let queue = queue_with(&[]);
+
+        let receiver = queue.receive(MessageSelector::Kind(1), 8, false, false, 2, 0);
+        let remover = async {
+            threading::yield_now().await;
+            queue.remove();
+        };
+
+        assert_eq!(
+            block_on!(receiver, remover),
+            (Err(ErrNo::IdentifierRemoved), ())
+        );
+        assert_eq!(
+            block_on!(queue.send(message(1, &[0; 4]), true, 1, 0)),
+            Err(ErrNo::IdentifierRemoved)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use filesystem_abstractions::Credentials;
+
+/// The key `*get` always creates a new object for, which no one else can look up.
+pub const IPC_PRIVATE: i32 = 0;
+
+bitflags::bitflags! {
+    /// The flags of `shmget`, `semget` and `msgget` above the permission bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IpcGetFlags: u32 {
+        /// Creates the object if there is none with the key
+        const IPC_CREAT = 0o1000;
+        /// Fails if there already is an object with the key
+        const IPC_EXCL = 0o2000;
+    }
+}
+
+/// The owner and permissions of an IPC object, like `struct ipc_perm`.
+///
+/// Access is checked against the effective ids, unlike files which go by the filesystem ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpcPermissions {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    /// The creator, who keeps the rights of the owner
+    pub cuid: u32,
+    pub cgid: u32,
+    /// The `rwxrwxrwx` bits, objects keep flags of their own above them
+    pub mode: u32,
+}
+
+impl IpcPermissions {
+    pub fn new(key: i32, mode: u32, credentials: &Credentials) -> IpcPermissions {
+        IpcPermissions {
+            key,
+            uid: credentials.euid,
+            gid: credentials.egid,
+            cuid: credentials.euid,
+            cgid: credentials.egid,
+            mode: mode & 0o777,
+        }
+    }
+
+    /// Whether `credentials` are granted every access in `requested`, where any of the user,
+    /// group and other bits ask for that access.
+    pub fn allows(&self, credentials: &Credentials, requested: u32) -> bool {
+        let requested = (requested >> 6 | requested >> 3 | requested) & 0o7;
+
+        let granted = match credentials.euid {
+            euid if euid == self.uid || euid == self.cuid => self.mode >> 6,
+            _ if credentials.in_group(self.gid) || credentials.in_group(self.cgid) => {
+                self.mode >> 3
+            }
+            _ => self.mode,
+        };
+
+        requested & !granted & 0o7 == 0 || credentials.is_admin()
+    }
+
+    /// Whether `credentials` may change the owner and permissions or remove the object.
+    pub fn can_change(&self, credentials: &Credentials) -> bool {
+        credentials.is_admin() || credentials.euid == self.uid || credentials.euid == self.cuid
+    }
+
+    /// Hands the object over to `uid` and `gid` with the `rwxrwxrwx` bits of `mode`, for
+    /// `IPC_SET`.
+    pub fn set(&mut self, uid: u32, gid: u32, mode: u32) {
+        self.uid = uid;
+        self.gid = gid;
+        self.mode = (self.mode & !0o777) | (mode & 0o777);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows() {
+        let owner = Credentials::new(1000, 100);
+        let permissions = IpcPermissions::new(42, 0o640 | 0o1000, &owner);
+
+        assert_eq!(permissions.mode, 0o640);
+        assert!(permissions.allows(&owner, 0o600));
+        assert!(!permissions.allows(&owner, 0o100));
+
+        let member = Credentials::new(1001, 100);
+        assert!(permissions.allows(&member, 0o444));
+        assert!(!permissions.allows(&member, 0o222));
+
+        let other = Credentials::new(1002, 200);
+        assert!(permissions.allows(&other, 0));
+        assert!(!permissions.allows(&other, 0o004));
+        assert!(permissions.allows(&Credentials::root(), 0o666));
+    }
+
+    #[test]
+    fn test_set_keeps_the_creator() {
+        let creator = Credentials::new(1000, 100);
+        let mut permissions = IpcPermissions::new(42, 0o600, &creator);
+        permissions.mode |= 0o1000;
+
+        permissions.set(1001, 101, 0o7644);
+
+        assert_eq!(permissions.mode, 0o1644);
+        assert!(permissions.can_change(&creator));
+        assert!(permissions.can_change(&Credentials::new(1001, 0)));
+        assert!(!permissions.can_change(&Credentials::new(1002, 101)));
+    }
+}
@@ -0,0 +1,620 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use constants::ErrNo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+
+use crate::{IIpcObject, IpcPermissions, IpcTable};
+
+/// Most semaphore sets a kernel can have, `SEMMNI`.
+pub const SEMMNI: usize = 32000;
+
+/// Most semaphores in a set, `SEMMSL`.
+pub const SEMMSL: usize = 32000;
+
+/// Most operations a single `semop` can do, `SEMOPM`.
+pub const SEMOPM: usize = 500;
+
+/// Largest value of a semaphore, and of what undoing can add to or take from it.
+pub const SEMVMX: i32 = 32767;
+
+/// One operation of `semop`, laid out like `struct sembuf`.
+///
+/// A positive `op` adds to the semaphore, a negative one waits until it can take that much from
+/// it and zero waits until the semaphore is zero.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SemaphoreOperation {
+    pub num: u16,
+    pub op: i16,
+    pub flags: i16,
+}
+
+impl SemaphoreOperation {
+    /// Fails with `EAGAIN` instead of waiting
+    pub const IPC_NOWAIT: i16 = 0o4000;
+    /// Reverts the operation when the process exits
+    pub const SEM_UNDO: i16 = 0x1000;
+
+    /// Whether the operation changes the semaphore, rather than waiting for it to be zero.
+    pub fn alters(&self) -> bool {
+        self.op != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Semaphore {
+    pub value: i32,
+    /// The last process that operated on it
+    pub pid: u32,
+}
+
+/// What `semctl` reports about a set besides its semaphores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemaphoreSetStatus {
+    pub permissions: IpcPermissions,
+    /// When `semop` last succeeded and when the set was last changed otherwise, in seconds
+    /// since the epoch
+    pub operation_time: i64,
+    pub change_time: i64,
+}
+
+/// Why a `semop` could not go through as a whole.
+enum Refused {
+    /// The operation at the index has to wait for the semaphore to grow, or to be zero
+    Blocked(usize, usize, bool),
+    Failed(ErrNo),
+}
+
+struct SemaphoreSetInner {
+    semaphores: Vec<Semaphore>,
+    /// The semaphore each blocked `semop` waits on, and whether it waits for it to be zero
+    waiting: BTreeMap<u64, (usize, bool)>,
+    next_waiter: u64,
+    removed: bool,
+}
+
+impl SemaphoreSetInner {
+    /// Does every operation or none of them.
+    fn try_operate(
+        &mut self,
+        id: usize,
+        operations: &[SemaphoreOperation],
+        pid: u32,
+        undo: Option<&SemaphoreUndo>,
+    ) -> Result<(), Refused> {
+        let mut values = BTreeMap::new();
+        let mut adjustments = BTreeMap::new();
+
+        for (index, operation) in operations.iter().enumerate() {
+            let num = operation.num as usize;
+            let value = values.entry(num).or_insert(self.semaphores[num].value);
+            let op = operation.op as i32;
+
+            match op {
+                0 if *value != 0 => return Err(Refused::Blocked(index, num, true)),
+                _ if *value + op < 0 => return Err(Refused::Blocked(index, num, false)),
+                _ if *value + op > SEMVMX => {
+                    return Err(Refused::Failed(ErrNo::NumericalResultOutOfRange))
+                }
+                _ => *value += op,
+            }
+
+            if operation.alters() && operation.flags & SemaphoreOperation::SEM_UNDO != 0 {
+                let adjustment = adjustments
+                    .entry(num)
+                    .or_insert_with(|| undo.map_or(0, |undo| undo.adjustment(id, num)));
+
+                *adjustment -= op;
+
+                if !(-SEMVMX - 1..=SEMVMX).contains(adjustment) {
+                    return Err(Refused::Failed(ErrNo::NumericalResultOutOfRange));
+                }
+            }
+        }
+
+        for (num, value) in values {
+            self.semaphores[num] = Semaphore { value, pid };
+        }
+
+        if let Some(undo) = undo {
+            let mut undo = undo.adjustments.lock();
+
+            for (num, adjustment) in adjustments {
+                match adjustment {
+                    0 => undo.remove(&(id, num)),
+                    _ => undo.insert((id, num), adjustment),
+                };
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A System V semaphore set.
+pub struct SemaphoreSet {
+    status: SpinMutex<SemaphoreSetStatus>,
+    inner: SpinMutex<SemaphoreSetInner>,
+    changed: WaitQueue,
+}
+
+impl SemaphoreSet {
+    /// Creates a set of `count` semaphores that are all zero.
+    pub fn new(permissions: IpcPermissions, count: usize, now: i64) -> SemaphoreSet {
+        SemaphoreSet {
+            status: SpinMutex::new(SemaphoreSetStatus {
+                permissions,
+                operation_time: 0,
+                change_time: now,
+            }),
+            inner: SpinMutex::new(SemaphoreSetInner {
+                semaphores: alloc::vec![Semaphore::default(); count],
+                waiting: BTreeMap::new(),
+                next_waiter: 0,
+                removed: false,
+            }),
+            changed: WaitQueue::new(),
+        }
+    }
+
+    /// How many semaphores the set has.
+    pub fn len(&self) -> usize {
+        self.inner.lock().semaphores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn status(&self) -> &SpinMutex<SemaphoreSetStatus> {
+        &self.status
+    }
+
+    pub fn semaphore(&self, num: usize) -> Option<Semaphore> {
+        self.inner.lock().semaphores.get(num).copied()
+    }
+
+    /// The values of all semaphores, for `GETALL`.
+    pub fn values(&self) -> Vec<u16> {
+        let inner = self.inner.lock();
+
+        inner.semaphores.iter().map(|s| s.value as u16).collect()
+    }
+
+    /// How many `semop`s wait for the semaphore `num` to grow and how many wait for it to be
+    /// zero, for `GETNCNT` and `GETZCNT`.
+    pub fn waiting(&self, num: usize) -> (usize, usize) {
+        let inner = self.inner.lock();
+        let waiting = inner.waiting.values().filter(|(on, _)| *on == num);
+
+        waiting.fold((0, 0), |(grow, zero), (_, for_zero)| match for_zero {
+            true => (grow, zero + 1),
+            false => (grow + 1, zero),
+        })
+    }
+
+    /// Sets the semaphore `num` to `value` for `SETVAL`, waking those waiting on it.
+    pub fn set_value(&self, num: usize, value: i32, pid: u32, now: i64) -> Result<(), ErrNo> {
+        if !(0..=SEMVMX).contains(&value) {
+            return Err(ErrNo::NumericalResultOutOfRange);
+        }
+
+        let mut inner = self.inner.lock();
+        let semaphore = inner
+            .semaphores
+            .get_mut(num)
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        *semaphore = Semaphore { value, pid };
+        drop(inner);
+
+        self.status.lock().change_time = now;
+        self.changed.notify_all();
+
+        Ok(())
+    }
+
+    /// Sets every semaphore at once for `SETALL`, `values` has one for each of them.
+    pub fn set_values(&self, values: &[u16], pid: u32, now: i64) -> Result<(), ErrNo> {
+        if values.iter().any(|value| *value as i32 > SEMVMX) {
+            return Err(ErrNo::NumericalResultOutOfRange);
+        }
+
+        let mut inner = self.inner.lock();
+
+        if values.len() != inner.semaphores.len() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        for (semaphore, value) in inner.semaphores.iter_mut().zip(values) {
+            *semaphore = Semaphore {
+                value: *value as i32,
+                pid,
+            };
+        }
+
+        drop(inner);
+
+        self.status.lock().change_time = now;
+        self.changed.notify_all();
+
+        Ok(())
+    }
+
+    /// Does all `operations` at once, waiting until they can be done unless the one that would
+    /// wait has `IPC_NOWAIT`.
+    ///
+    /// Adjustments of operations with `SEM_UNDO` go to `undo`, for the set with `id`. A set
+    /// removed in the meantime fails with `EIDRM`.
+    pub async fn operate(
+        &self,
+        id: usize,
+        operations: &[SemaphoreOperation],
+        pid: u32,
+        undo: Option<&SemaphoreUndo>,
+        now: i64,
+    ) -> Result<(), ErrNo> {
+        let waiter = {
+            let mut inner = self.inner.lock();
+            inner.next_waiter += 1;
+            inner.next_waiter
+        };
+
+        let _waiting = WaitingGuard { set: self, waiter };
+
+        self.changed
+            .wait_for(|| {
+                let mut inner = self.inner.lock();
+
+                if inner.removed {
+                    return Some(Err(ErrNo::IdentifierRemoved));
+                }
+
+                match inner.try_operate(id, operations, pid, undo) {
+                    Ok(()) => Some(Ok(())),
+                    Err(Refused::Failed(e)) => Some(Err(e)),
+                    Err(Refused::Blocked(index, num, for_zero)) => {
+                        if operations[index].flags & SemaphoreOperation::IPC_NOWAIT != 0 {
+                            return Some(Err(ErrNo::ResourceTemporarilyUnavailable));
+                        }
+
+                        inner.waiting.insert(waiter, (num, for_zero));
+
+                        None
+                    }
+                }
+            })
+            .await?;
+
+        self.status.lock().operation_time = now;
+
+        if operations.iter().any(SemaphoreOperation::alters) {
+            self.changed.notify_all();
+        }
+
+        Ok(())
+    }
+
+    /// Adds `adjustment` to the semaphore `num` as far as it can go, undoing the operations
+    /// of an exited process.
+    fn adjust(&self, num: usize, adjustment: i32, pid: u32) {
+        let mut inner = self.inner.lock();
+
+        if let Some(semaphore) = inner.semaphores.get_mut(num) {
+            *semaphore = Semaphore {
+                value: (semaphore.value + adjustment).clamp(0, SEMVMX),
+                pid,
+            };
+        }
+
+        drop(inner);
+
+        self.changed.notify_all();
+    }
+
+    /// Fails every `semop` waiting on the set, and the ones to come.
+    fn remove(&self) {
+        self.inner.lock().removed = true;
+        self.changed.notify_all();
+    }
+}
+
+impl IIpcObject for SemaphoreSet {
+    fn permissions(&self) -> IpcPermissions {
+        self.status.lock().permissions
+    }
+}
+
+/// Stops counting a `semop` as waiting once it is done or given up.
+struct WaitingGuard<'a> {
+    set: &'a SemaphoreSet,
+    waiter: u64,
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.set.inner.lock().waiting.remove(&self.waiter);
+    }
+}
+
+/// What has to be added back to semaphores when a process exits, by set id and semaphore, for
+/// the operations it did with `SEM_UNDO`.
+pub struct SemaphoreUndo {
+    adjustments: SpinMutex<BTreeMap<(usize, usize), i32>>,
+    /// Processes sharing the adjustments, whoever else holds on to them does not count
+    processes: AtomicUsize,
+}
+
+impl Default for SemaphoreUndo {
+    fn default() -> Self {
+        SemaphoreUndo {
+            adjustments: SpinMutex::new(BTreeMap::new()),
+            processes: AtomicUsize::new(1),
+        }
+    }
+}
+
+impl SemaphoreUndo {
+    pub fn adjustment(&self, id: usize, num: usize) -> i32 {
+        self.adjustments
+            .lock()
+            .get(&(id, num))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// The System V semaphore sets of a kernel, along with the undo adjustments of its processes.
+///
+/// Processes cloned with `CLONE_SYSVSEM` share their adjustments, which are applied once the
+/// last of them exits.
+pub struct SemaphoreSets {
+    sets: IpcTable<SemaphoreSet>,
+    undo: SpinMutex<BTreeMap<u32, Arc<SemaphoreUndo>>>,
+}
+
+impl Default for SemaphoreSets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SemaphoreSets {
+    pub fn new() -> SemaphoreSets {
+        SemaphoreSets {
+            sets: IpcTable::new(SEMMNI),
+            undo: SpinMutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn sets(&self) -> &IpcTable<SemaphoreSet> {
+        &self.sets
+    }
+
+    /// Removes the set with `id`, waking everyone waiting on it.
+    pub fn remove_set(&self, id: usize) -> bool {
+        let Some(set) = self.sets.remove(id) else {
+            return false;
+        };
+
+        set.remove();
+        self.forget_adjustments(id, None);
+
+        true
+    }
+
+    /// Drops what processes would add back to the semaphore `num` of the set `id`, or to all of
+    /// its semaphores, as its value was set directly or it was removed.
+    pub fn forget_adjustments(&self, id: usize, num: Option<usize>) {
+        for undo in self.undo.lock().values() {
+            undo.adjustments
+                .lock()
+                .retain(|(set, semaphore), _| *set != id || num.is_some_and(|n| n != *semaphore));
+        }
+    }
+
+    /// The adjustments of the process `pid`.
+    pub fn undo_of(&self, pid: u32) -> Arc<SemaphoreUndo> {
+        self.undo.lock().entry(pid).or_default().clone()
+    }
+
+    /// Makes the process `child` share the adjustments of `parent`, like `clone` does with
+    /// `CLONE_SYSVSEM`. Without it the child starts with none.
+    pub fn share_undo(&self, parent: u32, child: u32) {
+        let mut undo = self.undo.lock();
+        let shared = undo.entry(parent).or_default().clone();
+
+        if let Some(previous) = undo.insert(child, shared.clone()) {
+            previous.processes.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        shared.processes.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Applies the adjustments of the exited process `pid`, unless another process shares them.
+    pub fn release_process(&self, pid: u32) {
+        let Some(undo) = self.undo.lock().remove(&pid) else {
+            return;
+        };
+
+        if undo.processes.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        let adjustments = core::mem::take(&mut *undo.adjustments.lock());
+
+        for ((id, num), adjustment) in adjustments {
+            if let Some(set) = self.sets.find(id) {
+                set.adjust(num, adjustment, pid);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+    use threading::block_on;
+
+    use super::*;
+    use crate::{IpcGetFlags, IPC_PRIVATE};
+
+    fn create(sets: &SemaphoreSets, count: usize) -> (usize, Arc<SemaphoreSet>) {
+        let id = sets
+            .sets()
+            .get(
+                IPC_PRIVATE,
+                IpcGetFlags::IPC_CREAT,
+                0o600,
+                &Credentials::root(),
+                |_| Ok(()),
+                |permissions| Ok(SemaphoreSet::new(permissions, count, 0)),
+            )
+            .unwrap();
+
+        (id, sets.sets().find(id).unwrap())
+    }
+
+    fn op(num: u16, op: i16, flags: i16) -> SemaphoreOperation {
+        SemaphoreOperation { num, op, flags }
+    }
+
+    fn operate(
+        set: &SemaphoreSet,
+        id: usize,
+        operations: &[SemaphoreOperation],
+        undo: Option<&SemaphoreUndo>,
+    ) -> Result<(), ErrNo> {
+        block_on!(set.operate(id, operations, 2, undo, 42))
+    }
+
+    #[test]
+    fn test_operations_are_atomic() {
+        let sets = SemaphoreSets::new();
+        let (id, set) = create(&sets, 2);
+        let nowait = SemaphoreOperation::IPC_NOWAIT;
+
+        set.set_value(0, 1, 1, 0).unwrap();
+
+        // The second one can't go through, so neither does the first
+        assert_eq!(
+            operate(&set, id, &[op(0, -1, 0), op(1, -1, nowait)], None),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+        assert_eq!(set.values(), [1, 0]);
+
+        assert_eq!(
+            operate(&set, id, &[op(0, -1, 0), op(1, 2, 0)], None),
+            Ok(())
+        );
+        assert_eq!(set.values(), [0, 2]);
+        assert_eq!(set.semaphore(1).unwrap().pid, 2);
+        assert_eq!(set.status().lock().operation_time, 42);
+
+        assert_eq!(
+            operate(&set, id, &[op(1, SEMVMX as i16, 0)], None),
+            Err(ErrNo::NumericalResultOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_waits_until_it_can() {
+        let sets = SemaphoreSets::new();
+        let (id, set) = create(&sets, 1);
+
+        let take = [op(0, -2, 0)];
+        let post = [op(0, 1, 0)];
+
+        let waiter = set.operate(id, &take, 2, None, 0);
+        let poster = async {
+            threading::yield_now().await;
+            assert_eq!(set.waiting(0), (1, 0));
+
+            set.operate(id, &post, 3, None, 0).await.unwrap();
+            threading::yield_now().await;
+            assert_eq!(set.values(), [1]);
+
+            set.operate(id, &post, 3, None, 0).await.unwrap();
+        };
+
+        assert_eq!(block_on!(waiter, poster), (Ok(()), ()));
+        assert_eq!(set.values(), [0]);
+        assert_eq!(set.waiting(0), (0, 0));
+    }
+
+    #[test]
+    fn test_removed_while_waiting() {
+        let sets = SemaphoreSets::new();
+        let (id, set) = create(&sets, 1);
+        set.set_value(0, 1, 1, 0).unwrap();
+
+        let wait_for_zero = [op(0, 0, 0)];
+
+        let waiter = set.operate(id, &wait_for_zero, 2, None, 0);
+        let remover = async {
+            threading::yield_now().await;
+            assert_eq!(set.waiting(0), (0, 1));
+
+            assert!(sets.remove_set(id));
+        };
+
+        assert_eq!(
+            block_on!(waiter, remover),
+            (Err(ErrNo::IdentifierRemoved), ())
+        );
+        assert!(sets.sets().find(id).is_none());
+    }
+
+    #[test]
+    fn test_undone_on_exit() {
+        let sets = SemaphoreSets::new();
+        let (id, set) = create(&sets, 2);
+        let undo = SemaphoreOperation::SEM_UNDO;
+
+        set.set_value(0, 5, 1, 0).unwrap();
+
+        let adjustments = sets.undo_of(2);
+        operate(
+            &set,
+            id,
+            &[op(0, -3, undo), op(1, 2, undo)],
+            Some(&adjustments),
+        )
+        .unwrap();
+        assert_eq!(adjustments.adjustment(id, 0), 3);
+        assert_eq!(adjustments.adjustment(id, 1), -2);
+
+        // Shared with a child, which outlives the parent
+        sets.share_undo(2, 3);
+        drop(adjustments);
+        sets.release_process(2);
+        assert_eq!(set.values(), [2, 2]);
+
+        // Undoing can't take more than there is, and a `semop` still holding the adjustments
+        // does not keep them from being applied
+        let in_use = sets.undo_of(3);
+        set.set_value(1, 1, 1, 0).unwrap();
+        sets.release_process(3);
+        assert_eq!(set.values(), [5, 0]);
+        assert_eq!(in_use.adjustment(id, 0), 0);
+        assert_eq!(set.semaphore(0).unwrap().pid, 3);
+    }
+
+    #[test]
+    fn test_set_value_forgets_adjustments() {
+        let sets = SemaphoreSets::new();
+        let (id, set) = create(&sets, 1);
+
+        let adjustments = sets.undo_of(2);
+        let operation = op(0, 1, SemaphoreOperation::SEM_UNDO);
+        operate(&set, id, &[operation], Some(&adjustments)).unwrap();
+
+        set.set_value(0, 3, 1, 0).unwrap();
+        sets.forget_adjustments(id, Some(0));
+        assert_eq!(adjustments.adjustment(id, 0), 0);
+
+        sets.release_process(2);
+        assert_eq!(set.values(), [3]);
+    }
+}
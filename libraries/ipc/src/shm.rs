@@ -0,0 +1,204 @@
+use abstractions::IUsizeAlias;
+use address::PhysicalAddress;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use constants::ErrNo;
+use filesystem_abstractions::{DirectoryTreeNode, PageMapping};
+use hermit_sync::SpinMutex;
+use memory_space::ISharedPage;
+
+use crate::{IIpcObject, IpcPermissions, IpcTable, IPC_PRIVATE};
+
+/// Most segments a kernel can have, `SHMMNI`.
+pub const SHMMNI: usize = 4096;
+
+/// Largest segment, as large as a mapping can be.
+pub const SHMMAX: usize = 1 << 36;
+
+/// Set in the mode of a segment removed with `IPC_RMID` while it is still attached.
+pub const SHM_DEST: u32 = 0o1000;
+
+/// What `shmctl` reports about a segment besides its size and how often it is attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentStatus {
+    pub permissions: IpcPermissions,
+    /// When it was last attached, detached and changed, in seconds since the epoch
+    pub attach_time: i64,
+    pub detach_time: i64,
+    pub change_time: i64,
+    pub creator_pid: u32,
+    /// The last process that attached or detached it
+    pub last_pid: u32,
+}
+
+/// A System V shared memory segment.
+///
+/// Its pages are those of a tmpfs file, so they start zeroed. Every attach maps the same pages,
+/// and starts with the same `AttachedPage`, which is how attaches are counted.
+pub struct SharedMemorySegment {
+    size: usize,
+    pages: Vec<Arc<dyn ISharedPage>>,
+    /// The first page of the mappings there are, if any
+    attached: SpinMutex<Weak<AttachedPage>>,
+    _file: Arc<DirectoryTreeNode>,
+    status: SpinMutex<SegmentStatus>,
+}
+
+impl SharedMemorySegment {
+    /// Creates a segment of `size` bytes out of `file`, an empty file on a cached tmpfs.
+    pub fn new(
+        file: Arc<DirectoryTreeNode>,
+        size: usize,
+        permissions: IpcPermissions,
+        pid: u32,
+        now: i64,
+    ) -> Result<SharedMemorySegment, ErrNo> {
+        debug_assert!(size > 0);
+
+        file.resize_inode(size as u64).map_err(|e| e.to_errno())?;
+
+        let pages = (0..size.div_ceil(constants::PAGE_SIZE))
+            .map(|index| {
+                file.cached_page(index)
+                    .map(|page| Arc::new(SegmentPage(page.map(true))) as Arc<dyn ISharedPage>)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_errno())?;
+
+        Ok(SharedMemorySegment {
+            size,
+            pages,
+            attached: SpinMutex::new(Weak::new()),
+            _file: file,
+            status: SpinMutex::new(SegmentStatus {
+                permissions,
+                attach_time: 0,
+                detach_time: 0,
+                change_time: now,
+                creator_pid: pid,
+                last_pid: 0,
+            }),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The pages to map for an attach of the segment, which is `id` in `table`, in order.
+    pub fn attach(
+        &self,
+        table: &Arc<IpcTable<SharedMemorySegment>>,
+        id: usize,
+    ) -> Vec<Arc<dyn ISharedPage>> {
+        let mut attached = self.attached.lock();
+
+        let first = attached.upgrade().unwrap_or_else(|| {
+            let first = Arc::new(AttachedPage {
+                page: self.pages[0].clone(),
+                table: Arc::downgrade(table),
+                id,
+            });
+            *attached = Arc::downgrade(&first);
+
+            first
+        });
+
+        core::iter::once(first as Arc<dyn ISharedPage>)
+            .chain(self.pages[1..].iter().cloned())
+            .collect()
+    }
+
+    /// How many mappings of the segment there are, in any process.
+    pub fn attaches(&self) -> usize {
+        self.attached.lock().strong_count()
+    }
+
+    /// Whether `page` is the first page of the segment, which is what an attach starts with.
+    pub fn starts_with(&self, page: &dyn ISharedPage) -> bool {
+        self.pages[0].physical() == page.physical()
+    }
+
+    pub fn status(&self) -> &SpinMutex<SegmentStatus> {
+        &self.status
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.status.lock().permissions.mode & SHM_DEST != 0
+    }
+}
+
+impl IIpcObject for SharedMemorySegment {
+    fn permissions(&self) -> IpcPermissions {
+        self.status.lock().permissions
+    }
+}
+
+impl IpcTable<SharedMemorySegment> {
+    /// Removes the segment with `id` as soon as nothing is attached to it anymore, it can't be
+    /// looked up by its key in the meantime.
+    pub fn remove_segment(&self, id: usize) -> bool {
+        let Some(segment) = self.find(id) else {
+            return false;
+        };
+
+        let mut status = segment.status.lock();
+        status.permissions.key = IPC_PRIVATE;
+        status.permissions.mode |= SHM_DEST;
+        drop(status);
+
+        if segment.attaches() == 0 {
+            self.remove(id);
+        }
+
+        true
+    }
+
+    /// The segment an attach starting with `page` is of.
+    pub fn segment_of(&self, page: &dyn ISharedPage) -> Option<(usize, Arc<SharedMemorySegment>)> {
+        self.objects()
+            .into_iter()
+            .find(|(_, segment)| segment.starts_with(page))
+    }
+}
+
+/// A page of a segment, mapped writable for as long as the segment exists.
+struct SegmentPage(PageMapping);
+
+impl ISharedPage for SegmentPage {
+    fn physical(&self) -> PhysicalAddress {
+        PhysicalAddress::from_usize(self.0.physical())
+    }
+}
+
+/// The first page of a segment as its mappings have it. Once the last mapping goes away, whether
+/// with `shmdt` or with the memory space of its process, a segment removed with `IPC_RMID` is
+/// dropped.
+struct AttachedPage {
+    page: Arc<dyn ISharedPage>,
+    table: Weak<IpcTable<SharedMemorySegment>>,
+    id: usize,
+}
+
+impl ISharedPage for AttachedPage {
+    fn physical(&self) -> PhysicalAddress {
+        self.page.physical()
+    }
+}
+
+impl Drop for AttachedPage {
+    fn drop(&mut self) {
+        let Some(table) = self.table.upgrade() else {
+            return;
+        };
+
+        if table
+            .find(self.id)
+            .is_some_and(|segment| segment.is_removed())
+        {
+            table.remove(self.id);
+        }
+    }
+}
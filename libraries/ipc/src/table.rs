@@ -0,0 +1,228 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use constants::ErrNo;
+use filesystem_abstractions::Credentials;
+use hermit_sync::SpinMutex;
+
+use crate::{IpcGetFlags, IpcPermissions, IPC_PRIVATE};
+
+/// An object kept in an [`IpcTable`].
+pub trait IIpcObject: Send + Sync {
+    fn permissions(&self) -> IpcPermissions;
+}
+
+struct IpcTableInner<T> {
+    objects: BTreeMap<usize, Arc<T>>,
+    next_id: usize,
+}
+
+/// The IPC objects of one kind a kernel has, by their id.
+///
+/// Ids are never reused, so a removed object can't be mistaken for one created after it.
+pub struct IpcTable<T> {
+    inner: SpinMutex<IpcTableInner<T>>,
+    limit: usize,
+}
+
+impl<T: IIpcObject> IpcTable<T> {
+    /// Creates a table holding at most `limit` objects.
+    pub fn new(limit: usize) -> IpcTable<T> {
+        IpcTable {
+            inner: SpinMutex::new(IpcTableInner {
+                objects: BTreeMap::new(),
+                next_id: 0,
+            }),
+            limit,
+        }
+    }
+
+    /// Returns the id of the object with `key`, creating it with `create` as `flags` ask.
+    ///
+    /// `mode` is the access asked for an existing object, which `check` may refuse for reasons
+    /// of its kind, or the permissions of a new one.
+    pub fn get(
+        &self,
+        key: i32,
+        flags: IpcGetFlags,
+        mode: u32,
+        credentials: &Credentials,
+        check: impl FnOnce(&T) -> Result<(), ErrNo>,
+        create: impl FnOnce(IpcPermissions) -> Result<T, ErrNo>,
+    ) -> Result<usize, ErrNo> {
+        let mut inner = self.inner.lock();
+
+        if key != IPC_PRIVATE {
+            let existing = inner
+                .objects
+                .iter()
+                .find(|(_, object)| object.permissions().key == key);
+
+            if let Some((&id, object)) = existing {
+                if flags.contains(IpcGetFlags::IPC_CREAT | IpcGetFlags::IPC_EXCL) {
+                    return Err(ErrNo::FileExists);
+                }
+
+                if !object.permissions().allows(credentials, mode) {
+                    return Err(ErrNo::PermissionDenied);
+                }
+
+                check(object)?;
+
+                return Ok(id);
+            }
+
+            if !flags.contains(IpcGetFlags::IPC_CREAT) {
+                return Err(ErrNo::NoSuchFileOrDirectory);
+            }
+        }
+
+        if inner.objects.len() >= self.limit {
+            return Err(ErrNo::NoSpaceLeftOnDevice);
+        }
+
+        let object = create(IpcPermissions::new(key, mode, credentials))?;
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.objects.insert(id, Arc::new(object));
+
+        Ok(id)
+    }
+
+    pub fn find(&self, id: usize) -> Option<Arc<T>> {
+        self.inner.lock().objects.get(&id).cloned()
+    }
+
+    pub fn remove(&self, id: usize) -> Option<Arc<T>> {
+        self.inner.lock().objects.remove(&id)
+    }
+
+    /// Keeps only the objects `f` returns true for.
+    pub fn retain(&self, mut f: impl FnMut(usize, &T) -> bool) {
+        self.inner
+            .lock()
+            .objects
+            .retain(|id, object| f(*id, object));
+    }
+
+    /// Every object along with its id, in the order of their ids.
+    pub fn objects(&self) -> Vec<(usize, Arc<T>)> {
+        let inner = self.inner.lock();
+
+        inner
+            .objects
+            .iter()
+            .map(|(id, object)| (*id, object.clone()))
+            .collect()
+    }
+
+    /// The largest id in use, what `IPC_INFO` returns.
+    pub fn highest_id(&self) -> Option<usize> {
+        self.inner.lock().objects.keys().next_back().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Object(IpcPermissions, usize);
+
+    impl IIpcObject for Object {
+        fn permissions(&self) -> IpcPermissions {
+            self.0
+        }
+    }
+
+    fn get(
+        table: &IpcTable<Object>,
+        key: i32,
+        flags: IpcGetFlags,
+        mode: u32,
+        size: usize,
+    ) -> Result<usize, ErrNo> {
+        table.get(
+            key,
+            flags,
+            mode,
+            &Credentials::new(1000, 100),
+            |object| match object.1 < size {
+                true => Err(ErrNo::InvalidArgument),
+                false => Ok(()),
+            },
+            |permissions| Ok(Object(permissions, size)),
+        )
+    }
+
+    #[test]
+    fn test_get_by_key() {
+        let table = IpcTable::new(3);
+        let create = IpcGetFlags::IPC_CREAT;
+
+        assert_eq!(
+            get(&table, 42, IpcGetFlags::empty(), 0o600, 8),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(get(&table, 42, create, 0o600, 8), Ok(0));
+        assert_eq!(get(&table, 42, create, 0o600, 4), Ok(0));
+        assert_eq!(get(&table, 42, IpcGetFlags::empty(), 0, 8), Ok(0));
+        assert_eq!(
+            get(&table, 42, create | IpcGetFlags::IPC_EXCL, 0o600, 8),
+            Err(ErrNo::FileExists)
+        );
+        assert_eq!(
+            get(&table, 42, create, 0o600, 16),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        // Private keys always get a new object
+        assert_eq!(get(&table, IPC_PRIVATE, create, 0o600, 8), Ok(1));
+        assert_eq!(
+            get(&table, IPC_PRIVATE, IpcGetFlags::empty(), 0o600, 8),
+            Ok(2)
+        );
+        assert_eq!(
+            get(&table, IPC_PRIVATE, create, 0o600, 8),
+            Err(ErrNo::NoSpaceLeftOnDevice)
+        );
+
+        // Ids are not reused
+        assert!(table.remove(0).is_some());
+        assert_eq!(get(&table, 42, create, 0o600, 8), Ok(3));
+        assert_eq!(table.highest_id(), Some(3));
+    }
+
+    #[test]
+    fn test_get_checks_permissions() {
+        let table = IpcTable::new(16);
+        let owner = Credentials::new(1001, 101);
+
+        let id = table
+            .get(
+                42,
+                IpcGetFlags::IPC_CREAT,
+                0o640,
+                &owner,
+                |_| Ok(()),
+                |permissions| Ok(Object(permissions, 0)),
+            )
+            .unwrap();
+
+        assert_eq!(table.find(id).unwrap().permissions().uid, 1001);
+        assert_eq!(
+            get(&table, 42, IpcGetFlags::empty(), 0o400, 0),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+}
@@ -5,10 +5,14 @@
 use alloc::sync::Arc;
 use allocation_abstractions::IFrameAllocator;
 use downcast_rs::{impl_downcast, Downcast};
-use filesystem_abstractions::DirectoryTreeNode;
+use filesystem_abstractions::{DirectoryTreeNode, FileLockManager, MountTable};
 use hermit_sync::SpinMutex;
+use ipc::{IpcTable, MessageQueue, SemaphoreSets, SharedMemorySegment};
 use mmu_abstractions::IMMU;
+use network_stack::NetworkStack;
+use threading::TimerQueue;
 use timing::TimeSpec;
+use unix_socket::UnixNamespace;
 
 #[cfg(feature = "std")]
 extern crate std;
@@ -25,6 +29,31 @@ pub trait IKernel: Downcast {
     fn activate_mmu(&self, pt: &dyn IMMU);
 
     fn time(&self) -> TimeSpec;
+
+    fn timer(&self) -> Arc<TimerQueue>;
+
+    fn network(&self) -> Arc<NetworkStack>;
+
+    /// Where the `AF_UNIX` sockets of this kernel find each other.
+    fn unix_sockets(&self) -> Arc<UnixNamespace>;
+
+    /// The advisory `flock` and `fcntl` locks on the files of this kernel.
+    fn locks(&self) -> Arc<FileLockManager>;
+
+    /// The filesystem types `mount` knows and what is mounted, shown in `/proc/mounts`.
+    fn mounts(&self) -> Arc<MountTable>;
+
+    /// The System V shared memory segments, only seen by the processes of this kernel.
+    fn shared_memory(&self) -> Arc<IpcTable<SharedMemorySegment>>;
+
+    /// The System V semaphore sets and the adjustments processes made with `SEM_UNDO`.
+    fn semaphores(&self) -> Arc<SemaphoreSets>;
+
+    /// The System V message queues.
+    fn message_queues(&self) -> Arc<IpcTable<MessageQueue>>;
+
+    /// The root of the mqueue filesystem holding the POSIX message queues `mq_open` names.
+    fn mqueue(&self) -> Arc<DirectoryTreeNode>;
 }
 
 impl_downcast!(IKernel);
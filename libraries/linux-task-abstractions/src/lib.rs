@@ -6,10 +6,10 @@ use core::ops::{Deref, DerefMut};
 
 use alloc::sync::Arc;
 use memory_space::MemorySpace;
-use task_abstractions::{IProcess, ITask};
+use task_abstractions::{IProcess, ITask, ProcessImage};
 
 pub trait ILinuxProcess: IProcess {
-    fn execve(&self, mem: MemorySpace, calling: u32);
+    fn execve(&self, mem: MemorySpace, calling: u32, image: ProcessImage);
 }
 
 impl Deref for dyn ILinuxTask {
@@ -1,21 +1,21 @@
 use core::cell::RefCell;
 
 use alloc::{
-    string::String,
+    string::{String, ToString},
     sync::{Arc, Weak},
     vec,
     vec::Vec,
 };
 
 use abstractions::operations::IUsizeAlias;
-use filesystem_abstractions::FileDescriptorTable;
+use filesystem_abstractions::{Credentials, FileDescriptorTable};
 use hermit_sync::SpinMutex;
 use linux_loader::LinuxLoader;
 use linux_task_abstractions::ILinuxProcess;
 use memory_space::MemorySpace;
 use mmu_abstractions::IMMU;
 use platform_specific::{ITaskContext, TaskTrapContext};
-use task_abstractions::{IProcess, ITask, ITaskIdAllocator, TaskId};
+use task_abstractions::{IProcess, ITask, ITaskIdAllocator, ProcessImage, TaskId};
 
 use crate::{id_allocator::TaskIdAllocator, LinuxTask};
 
@@ -30,6 +30,8 @@ pub struct LinuxProcess {
     mmu: RefCell<Arc<SpinMutex<dyn IMMU>>>,
     fd_table: SpinMutex<FileDescriptorTable>,
     working_directory: SpinMutex<String>,
+    credentials: SpinMutex<Credentials>,
+    image: SpinMutex<ProcessImage>,
     exit_code: SpinMutex<Option<u8>>,
 }
 
@@ -70,6 +72,11 @@ impl LinuxProcess {
         Self::register_kernel_area_for_pt(&builder.memory_space);
 
         let mmu = builder.memory_space.mmu().clone();
+        let image = ProcessImage {
+            executable: builder.executable.clone(),
+            argv: builder.ctx.argv.iter().map(|arg| arg.to_string()).collect(),
+            envp: builder.ctx.envp.iter().map(|env| env.to_string()).collect(),
+        };
 
         let process = Arc::new(Self {
             pgid: *pid,
@@ -82,6 +89,8 @@ impl LinuxProcess {
             memory_space: SpinMutex::new(builder.memory_space),
             fd_table: SpinMutex::new(FileDescriptorTable::new()),
             working_directory: SpinMutex::new(String::new()),
+            credentials: SpinMutex::new(Credentials::root()),
+            image: SpinMutex::new(image),
             exit_code: SpinMutex::new(None),
         });
 
@@ -135,6 +144,14 @@ impl IProcess for LinuxProcess {
         self.working_directory.lock().clone()
     }
 
+    fn credentials(&self) -> &SpinMutex<Credentials> {
+        &self.credentials
+    }
+
+    fn image(&self) -> ProcessImage {
+        self.image.lock().clone()
+    }
+
     fn exit_code(&self) -> &SpinMutex<Option<u8>> {
         &self.exit_code
     }
@@ -152,11 +169,13 @@ impl ILinuxProcess for LinuxProcess {
     /// Replace the process address space with `mem` and constrain execution to the calling thread.
     ///
     /// Replaces the process MMU and memory space with those from `mem`, removes all threads except the
-    /// thread whose `tid` equals `calling`, and clears the file-descriptor table's exec state.
+    /// thread whose `tid` equals `calling`, clears the file-descriptor table's exec state and
+    /// records `image` as what the process runs now.
     /// Panics if no thread with id `calling` exists.
-    fn execve(&self, mem: MemorySpace, calling: u32) {
+    fn execve(&self, mem: MemorySpace, calling: u32, image: ProcessImage) {
         *self.mmu.borrow_mut() = mem.mmu().clone();
         *self.memory_space.lock() = mem;
+        *self.image.lock() = image;
 
         let mut threads = self.threads.lock();
 
@@ -1,4 +1,4 @@
-use address::{VirtualPageNum, VirtualPageNumRange};
+use address::{PhysicalAddress, VirtualPageNum, VirtualPageNumRange};
 use alloc::{collections::btree_map::BTreeMap, sync::Arc};
 use allocation_abstractions::{FrameDesc, IFrameAllocator};
 use hermit_sync::SpinMutex;
@@ -70,24 +70,70 @@ impl alloc::fmt::Debug for MappingArea {
     }
 }
 
-pub struct MappingAreaAllocation {
-    pub allocator: Arc<SpinMutex<dyn IFrameAllocator>>,
-    pub frames: BTreeMap<VirtualPageNum, FrameDesc>,
+/// A page memory spaces map, shared by all of them and by whoever else holds it, like a page of
+/// a file.
+pub trait ISharedPage: Send + Sync {
+    fn physical(&self) -> PhysicalAddress;
 }
 
-impl MappingAreaAllocation {
-    pub fn empty(allocator: Arc<SpinMutex<dyn IFrameAllocator>>) -> Self {
+/// A frame allocated for an area, given back once nothing maps it anymore.
+pub struct AllocatedFrame {
+    frame: Option<FrameDesc>,
+    allocator: Arc<SpinMutex<dyn IFrameAllocator>>,
+}
+
+impl AllocatedFrame {
+    pub fn new(frame: FrameDesc, allocator: Arc<SpinMutex<dyn IFrameAllocator>>) -> Self {
         Self {
+            frame: Some(frame),
             allocator,
-            frames: BTreeMap::new(),
         }
     }
 }
 
-impl Drop for MappingAreaAllocation {
+// The allocator is only used behind its lock
+unsafe impl Send for AllocatedFrame {}
+unsafe impl Sync for AllocatedFrame {}
+
+impl ISharedPage for AllocatedFrame {
+    fn physical(&self) -> PhysicalAddress {
+        self.frame.as_ref().unwrap().0
+    }
+}
+
+impl Drop for AllocatedFrame {
     fn drop(&mut self) {
-        while let Some((_, frame)) = self.frames.pop_first() {
+        if let Some(frame) = self.frame.take() {
             self.allocator.lock().dealloc(frame);
         }
     }
 }
+
+pub struct MappingAreaAllocation {
+    pub allocator: Arc<SpinMutex<dyn IFrameAllocator>>,
+    /// The pages mapped in the area, they are released as the last area mapping them is dropped
+    pub pages: BTreeMap<VirtualPageNum, Arc<dyn ISharedPage>>,
+    /// Whether a cloned memory space maps the same pages instead of copies of them
+    pub shared: bool,
+}
+
+impl MappingAreaAllocation {
+    pub fn empty(allocator: Arc<SpinMutex<dyn IFrameAllocator>>) -> Self {
+        Self {
+            allocator,
+            pages: BTreeMap::new(),
+            shared: false,
+        }
+    }
+
+    /// Allocates a frame for `vpn` and returns its physical address.
+    pub fn allocate(&mut self, vpn: VirtualPageNum) -> Option<PhysicalAddress> {
+        let frame = self.allocator.lock().alloc_frame()?;
+        let frame = AllocatedFrame::new(frame, self.allocator.clone());
+        let paddr = frame.physical();
+
+        self.pages.insert(vpn, Arc::new(frame));
+
+        Some(paddr)
+    }
+}
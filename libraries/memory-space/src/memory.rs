@@ -1,9 +1,9 @@
 use core::cell::OnceCell;
 
 use abstractions::IUsizeAlias;
-use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{sync::Arc, vec::Vec};
 
-use crate::{AreaType, MapType, MappingArea, MappingAreaAllocation};
+use crate::{AreaType, ISharedPage, MapType, MappingArea, MappingAreaAllocation};
 use address::{
     IAddressBase, IPageNum, IToPageNum, PhysicalAddress, VirtualAddress, VirtualAddressRange,
     VirtualPageNum, VirtualPageNumRange,
@@ -87,10 +87,7 @@ impl MemorySpace {
 
         {
             for vpn in area.range().iter() {
-                let frame = alloc.allocator.lock().alloc_frame().unwrap();
-                let paddr = frame.0;
-
-                alloc.frames.insert(vpn, frame);
+                let paddr = alloc.allocate(vpn).unwrap();
 
                 self.mmu
                     .lock()
@@ -103,6 +100,38 @@ impl MemorySpace {
         self.mapping_areas.push(area);
     }
 
+    /// Maps `pages` one after another over the area, they are shared with whoever else has them.
+    pub fn map_shared_area(
+        &mut self,
+        mut area: MappingArea,
+        pages: impl IntoIterator<Item = Arc<dyn ISharedPage>>,
+    ) {
+        debug_assert!(area.allocation.is_none());
+
+        let mut alloc = self.create_empty_area_allocation();
+
+        for (vpn, page) in area.range().iter().zip(pages) {
+            self.mmu
+                .lock()
+                .map_single(
+                    vpn.start_addr(),
+                    page.physical(),
+                    PageSize::_4K,
+                    area.permissions(),
+                )
+                .unwrap();
+
+            alloc.pages.insert(vpn, page);
+        }
+
+        debug_assert_eq!(alloc.pages.len(), area.range().page_count());
+
+        alloc.shared = true;
+
+        area.allocation = Some(alloc);
+        self.mapping_areas.push(area);
+    }
+
     pub fn map_area(&mut self, area: MappingArea) {
         debug_assert!(area.allocation.is_some());
         debug_assert!(Arc::ptr_eq(
@@ -180,12 +209,9 @@ impl MemorySpace {
             VirtualPageNumRange::from_start_count(old_end_vpn, page_count as usize);
 
         for vpn in increased_range.iter() {
-            let frame = self.allocator.lock().alloc_frame().unwrap();
-            let paddr = frame.0;
-
             let area = &mut self.mapping_areas[brk_idx];
 
-            area.allocation.as_mut().unwrap().frames.insert(vpn, frame);
+            let paddr = area.allocation.as_mut().unwrap().allocate(vpn).unwrap();
 
             self.mmu
                 .lock()
@@ -223,10 +249,7 @@ impl MemorySpace {
     }
 
     pub(crate) fn create_empty_area_allocation(&self) -> MappingAreaAllocation {
-        MappingAreaAllocation {
-            allocator: self.allocator.clone(),
-            frames: BTreeMap::new(),
-        }
+        MappingAreaAllocation::empty(self.allocator.clone())
     }
 
     /// Initialize the memory space's attribute value
@@ -252,6 +275,17 @@ impl MemorySpace {
 
         for area in them.mapping_areas.iter() {
             let my_area = MappingArea::clone_from(area);
+
+            // Shared pages stay shared, the child maps the very same ones
+            if let Some(shared) = area
+                .allocation
+                .as_ref()
+                .filter(|allocation| allocation.shared)
+            {
+                this.map_shared_area(my_area, shared.pages.values().cloned());
+                continue;
+            }
+
             this.alloc_and_map_area(my_area);
 
             // Copy datas through high half address
@@ -269,7 +303,7 @@ impl MemorySpace {
             }
         }
 
-        *this.attr.get_mut().unwrap() = *them.attr();
+        this.attr = them.attr.clone();
 
         this
     }
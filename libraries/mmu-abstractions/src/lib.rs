@@ -88,7 +88,7 @@ impl dyn IMMU {
     }
 
     #[cfg(not(target_os = "none"))]
-    pub fn register<T>(&mut self, val: &T, mutable: bool) -> VirtualAddress {
+    pub fn register<T: ?Sized>(&mut self, val: &T, mutable: bool) -> VirtualAddress {
         self.register_internal(
             VirtualAddress::from_ref(val),
             core::mem::size_of_val(val),
@@ -99,7 +99,7 @@ impl dyn IMMU {
     }
 
     #[cfg(not(target_os = "none"))]
-    pub fn unregister<T>(&mut self, val: &T) {
+    pub fn unregister<T: ?Sized>(&mut self, val: &T) {
         self.unregister_internal(VirtualAddress::from_ref(val));
     }
 }
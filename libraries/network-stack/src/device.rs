@@ -0,0 +1,217 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+/// Size of an ethernet frame carrying a full 1500 bytes MTU packet, without the checksum
+pub const ETHERNET_FRAME_SIZE: usize = 1514;
+
+/// A network card that sends and receives ethernet frames.
+///
+/// Implementations never block, the stack polls them whenever it makes progress.
+pub trait INetworkDevice: Send {
+    fn mac_address(&self) -> [u8; 6];
+
+    /// Takes the next received frame, if any.
+    fn receive(&mut self) -> Option<Vec<u8>>;
+
+    /// Whether [`INetworkDevice::transmit`] would accept a frame now.
+    fn can_transmit(&mut self) -> bool;
+
+    /// Queues `frame` for sending, dropping it silently if there is no room, like a full wire.
+    fn transmit(&mut self, frame: &[u8]);
+}
+
+/// Lets smoltcp drive an [`INetworkDevice`].
+pub(crate) struct EthernetDevice(pub Box<dyn INetworkDevice>);
+
+pub(crate) struct EthernetRxToken(Vec<u8>);
+
+pub(crate) struct EthernetTxToken<'a>(&'a mut dyn INetworkDevice);
+
+impl phy::RxToken for EthernetRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+impl phy::TxToken for EthernetTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+
+        self.0.transmit(&frame);
+
+        result
+    }
+}
+
+impl phy::Device for EthernetDevice {
+    type RxToken<'a> = EthernetRxToken;
+    type TxToken<'a> = EthernetTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.0.receive()?;
+
+        Some((EthernetRxToken(frame), EthernetTxToken(self.0.as_mut())))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        match self.0.can_transmit() {
+            true => Some(EthernetTxToken(self.0.as_mut())),
+            false => None,
+        }
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = ETHERNET_FRAME_SIZE;
+        capabilities.max_burst_size = Some(1);
+
+        capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::VecDeque, sync::Arc};
+    use core::net::{Ipv4Addr, SocketAddrV4};
+    use hermit_sync::SpinMutex;
+    use socket_abstractions::{ISocket, MessageFlags, SocketAddress};
+
+    use super::*;
+    use crate::{tests::setup_stack, NetworkStack, TcpSocket, UdpSocket};
+
+    type Queue = Arc<SpinMutex<VecDeque<Vec<u8>>>>;
+
+    /// One end of a crossover cable.
+    struct WireEnd {
+        mac: [u8; 6],
+        inbox: Queue,
+        outbox: Queue,
+    }
+
+    impl INetworkDevice for WireEnd {
+        fn mac_address(&self) -> [u8; 6] {
+            self.mac
+        }
+
+        fn receive(&mut self) -> Option<Vec<u8>> {
+            self.inbox.lock().pop_front()
+        }
+
+        fn can_transmit(&mut self) -> bool {
+            true
+        }
+
+        fn transmit(&mut self, frame: &[u8]) {
+            self.outbox.lock().push_back(frame.to_vec());
+        }
+    }
+
+    fn connected_stacks() -> (Arc<NetworkStack>, Arc<NetworkStack>) {
+        let first_inbox = Queue::default();
+        let second_inbox = Queue::default();
+
+        let first = setup_stack();
+        first.add_interface(
+            Box::new(WireEnd {
+                mac: [0x52, 0x54, 0, 0, 0, 1],
+                inbox: first_inbox.clone(),
+                outbox: second_inbox.clone(),
+            }),
+            Ipv4Addr::new(10, 0, 2, 15),
+            24,
+            Some(Ipv4Addr::new(10, 0, 2, 2)),
+        );
+
+        let second = setup_stack();
+        second.add_interface(
+            Box::new(WireEnd {
+                mac: [0x52, 0x54, 0, 0, 0, 2],
+                inbox: second_inbox,
+                outbox: first_inbox,
+            }),
+            Ipv4Addr::new(10, 0, 2, 2),
+            24,
+            None,
+        );
+
+        (first, second)
+    }
+
+    fn address(ip: [u8; 4], port: u16) -> SocketAddress {
+        SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::from(ip), port))
+    }
+
+    #[test]
+    fn test_tcp_over_ethernet() {
+        let (guest, host) = connected_stacks();
+
+        let server = TcpSocket::new(host.clone());
+        server.bind(&address([0, 0, 0, 0], 80)).unwrap();
+        server.listen(1).unwrap();
+
+        let client = TcpSocket::new(guest.clone());
+
+        let connected = (0..100).any(|_| {
+            host.poll();
+
+            matches!(client.connect(&address([10, 0, 2, 2], 80)), Ok(()))
+        });
+        assert!(connected);
+
+        host.poll();
+        let accepted = server.accept().unwrap();
+
+        assert_eq!(
+            accepted.peer_address().unwrap(),
+            address([10, 0, 2, 15], client_port(&client))
+        );
+
+        client.send(b"GET /", Vec::new(), None).unwrap();
+        host.poll();
+
+        let mut buf = [0u8; 8];
+        let received = accepted.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf[..received.length], b"GET /");
+    }
+
+    fn client_port(socket: &TcpSocket) -> u16 {
+        match socket.local_address() {
+            SocketAddress::Inet(address) => address.port(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_udp_resolves_neighbor() {
+        let (guest, host) = connected_stacks();
+
+        let server = UdpSocket::new(host.clone());
+        server.bind(&address([10, 0, 2, 2], 53)).unwrap();
+
+        let client = UdpSocket::new(guest.clone());
+        client
+            .send(b"query", Vec::new(), Some(&address([10, 0, 2, 2], 53)))
+            .unwrap();
+
+        // The datagram waits in the socket until the ARP reply is back
+        host.poll();
+        guest.poll();
+        host.poll();
+
+        let mut buf = [0u8; 8];
+        let received = server.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf[..received.length], b"query");
+    }
+}
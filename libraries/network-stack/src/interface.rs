@@ -0,0 +1,107 @@
+use alloc::{boxed::Box, vec};
+use core::net::Ipv4Addr;
+use smoltcp::{
+    iface::{Config, Interface, PollResult, SocketSet},
+    phy::{Loopback, Medium},
+    time::Instant,
+    wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr},
+};
+
+use crate::device::{EthernetDevice, INetworkDevice};
+
+pub(crate) enum InterfaceDevice {
+    Loopback(Loopback),
+    Ethernet(EthernetDevice),
+}
+
+/// One interface of the stack with the sockets that send through it.
+///
+/// Every interface owns its own socket set so a socket only ever egresses on the interface it
+/// was routed to.
+pub(crate) struct NetworkInterface {
+    pub iface: Interface,
+    pub device: InterfaceDevice,
+    pub sockets: SocketSet<'static>,
+}
+
+impl NetworkInterface {
+    pub fn loopback(now: Instant, seed: u64) -> NetworkInterface {
+        let mut device = Loopback::new(Medium::Ip);
+
+        let mut config = Config::new(HardwareAddress::Ip);
+        config.random_seed = seed;
+
+        let mut iface = Interface::new(config, &mut device, now);
+        iface.update_ip_addrs(|addresses| {
+            addresses
+                .push(IpCidr::new(IpAddress::Ipv4(Ipv4Addr::LOCALHOST), 8))
+                .unwrap();
+        });
+
+        NetworkInterface {
+            iface,
+            device: InterfaceDevice::Loopback(device),
+            sockets: SocketSet::new(vec![]),
+        }
+    }
+
+    /// An ethernet interface with a static address, `gateway` becomes the default route.
+    pub fn ethernet(
+        device: Box<dyn INetworkDevice>,
+        now: Instant,
+        seed: u64,
+        address: Ipv4Addr,
+        prefix_len: u8,
+        gateway: Option<Ipv4Addr>,
+    ) -> NetworkInterface {
+        let mut device = EthernetDevice(device);
+
+        let mut config = Config::new(HardwareAddress::Ethernet(EthernetAddress(
+            device.0.mac_address(),
+        )));
+        config.random_seed = seed;
+
+        let mut iface = Interface::new(config, &mut device, now);
+        iface.update_ip_addrs(|addresses| {
+            addresses
+                .push(IpCidr::new(IpAddress::Ipv4(address), prefix_len))
+                .unwrap();
+        });
+
+        if let Some(gateway) = gateway {
+            iface.routes_mut().add_default_ipv4_route(gateway).unwrap();
+        }
+
+        NetworkInterface {
+            iface,
+            device: InterfaceDevice::Ethernet(device),
+            sockets: SocketSet::new(vec![]),
+        }
+    }
+
+    /// Processes pending packets, returns whether any socket may have changed.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let result = match &mut self.device {
+            InterfaceDevice::Loopback(device) => self.iface.poll(now, device, &mut self.sockets),
+            InterfaceDevice::Ethernet(device) => self.iface.poll(now, device, &mut self.sockets),
+        };
+
+        result == PollResult::SocketStateChanged
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        matches!(self.device, InterfaceDevice::Loopback(_))
+    }
+
+    pub fn has_address(&self, address: Ipv4Addr) -> bool {
+        self.iface.has_ip_addr(IpAddress::Ipv4(address))
+    }
+
+    /// Whether `address` is on a network directly attached to this interface.
+    pub fn is_attached(&self, address: Ipv4Addr) -> bool {
+        self.iface
+            .ip_addrs()
+            .iter()
+            .any(|cidr| cidr.contains_addr(&IpAddress::Ipv4(address)))
+    }
+}
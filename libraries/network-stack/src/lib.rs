@@ -0,0 +1,328 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use constants::ErrNo;
+use core::{
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use hermit_sync::SpinMutex;
+use smoltcp::{
+    iface::SocketHandle,
+    socket::{tcp, udp},
+    time::Instant,
+    wire::{IpAddress, IpEndpoint, IpListenEndpoint},
+};
+use socket_abstractions::{ISocket, SocketType};
+use threading::{sync::WaitQueue, IClock};
+
+use crate::{
+    interface::NetworkInterface,
+    ports::{PortTable, Protocol},
+};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod device;
+mod interface;
+mod ports;
+mod tcp_socket;
+mod udp_socket;
+
+pub use device::{INetworkDevice, ETHERNET_FRAME_SIZE};
+pub use tcp_socket::TcpSocket;
+pub use udp_socket::UdpSocket;
+
+/// Upper bound of poll rounds in one [`NetworkStack::poll`], a loopback round trip takes two
+const MAX_POLL_ROUNDS: usize = 64;
+
+/// A socket living in the socket set of one interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SocketRef {
+    pub interface: usize,
+    pub handle: SocketHandle,
+}
+
+/// A TCP connection whose owner is gone, kept around until it is fully closed.
+struct ClosingSocket {
+    socket: SocketRef,
+    /// The port reservation released once the connection is closed
+    port: Option<(u16, usize)>,
+}
+
+pub(crate) struct StackInner {
+    pub interfaces: Vec<NetworkInterface>,
+    pub ports: PortTable,
+    closing: Vec<ClosingSocket>,
+}
+
+/// The in-kernel TCP/IP stack.
+///
+/// Socket operations drive the stack themselves, the kernel additionally calls
+/// [`NetworkStack::poll`] periodically so that timers and incoming packets make progress.
+pub struct NetworkStack {
+    clock: Arc<dyn IClock>,
+    next_id: AtomicUsize,
+    inner: SpinMutex<StackInner>,
+    readiness: WaitQueue,
+}
+
+impl NetworkStack {
+    /// Creates a stack with a loopback interface answering `127.0.0.1`.
+    pub fn new(clock: Arc<dyn IClock>) -> Arc<NetworkStack> {
+        let now = clock.now();
+        let now_instant = Self::instant_of(&clock);
+
+        let seed = (now.tv_sec as u64) << 32 ^ now.tv_nsec as u64;
+
+        Arc::new(NetworkStack {
+            clock,
+            next_id: AtomicUsize::new(1),
+            inner: SpinMutex::new(StackInner {
+                interfaces: Vec::from([NetworkInterface::loopback(now_instant, seed)]),
+                ports: PortTable::new(),
+                closing: Vec::new(),
+            }),
+            readiness: WaitQueue::new(),
+        })
+    }
+
+    /// Attaches an ethernet interface with a static IPv4 configuration.
+    pub fn add_interface(
+        &self,
+        device: Box<dyn INetworkDevice>,
+        address: Ipv4Addr,
+        prefix_len: u8,
+        gateway: Option<Ipv4Addr>,
+    ) {
+        let now = self.now();
+        let seed = now.total_micros() as u64 ^ self.allocate_id() as u64;
+
+        let interface = NetworkInterface::ethernet(device, now, seed, address, prefix_len, gateway);
+
+        self.inner.lock().interfaces.push(interface);
+    }
+
+    /// Creates an `AF_INET` socket of the given type.
+    pub fn create_socket(self: &Arc<NetworkStack>, socket_type: SocketType) -> Arc<dyn ISocket> {
+        match socket_type {
+            SocketType::Stream => TcpSocket::new(self.clone()),
+            SocketType::Datagram => UdpSocket::new(self.clone()),
+        }
+    }
+
+    /// Processes pending packets and timers on all interfaces.
+    pub fn poll(&self) {
+        self.poll_locked(&mut self.inner.lock());
+    }
+
+    /// Notified whenever a poll changes the state of some socket, shared by all of them as
+    /// packets are only ever processed for the whole stack.
+    pub fn readiness(&self) -> &WaitQueue {
+        &self.readiness
+    }
+
+    fn instant_of(clock: &Arc<dyn IClock>) -> Instant {
+        let now = clock.now();
+
+        Instant::from_micros(now.tv_sec * 1_000_000 + now.tv_nsec / 1_000)
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        Self::instant_of(&self.clock)
+    }
+
+    pub(crate) fn allocate_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Locks the stack after bringing it up to date.
+    pub(crate) fn lock(&self) -> hermit_sync::SpinMutexGuard<'_, StackInner> {
+        let mut inner = self.inner.lock();
+
+        self.poll_locked(&mut inner);
+
+        inner
+    }
+
+    /// Polls the stack locked as `inner`, waking whoever waits for the sockets it changed.
+    pub(crate) fn poll_locked(&self, inner: &mut StackInner) {
+        if inner.poll(self.now()) {
+            self.readiness.notify_all();
+        }
+    }
+
+    pub(crate) fn lock_without_poll(&self) -> hermit_sync::SpinMutexGuard<'_, StackInner> {
+        self.inner.lock()
+    }
+}
+
+impl StackInner {
+    /// Returns whether the state of any socket changed.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let mut any_changed = false;
+
+        for _ in 0..MAX_POLL_ROUNDS {
+            let mut changed = false;
+
+            for interface in self.interfaces.iter_mut() {
+                changed |= interface.poll(now);
+            }
+
+            if !changed {
+                break;
+            }
+
+            any_changed = true;
+        }
+
+        self.reap_closed();
+
+        any_changed
+    }
+
+    fn reap_closed(&mut self) {
+        let mut index = 0;
+
+        while index < self.closing.len() {
+            let socket = self.closing[index].socket;
+
+            if self.tcp(socket).state() != tcp::State::Closed {
+                index += 1;
+                continue;
+            }
+
+            let closed = self.closing.swap_remove(index);
+
+            self.remove(closed.socket);
+
+            if let Some((port, id)) = closed.port {
+                self.ports.release(Protocol::Tcp, port, id);
+            }
+        }
+    }
+
+    /// Picks the interface packets to `destination` go out of.
+    pub fn route(&self, destination: Ipv4Addr) -> Result<usize, ErrNo> {
+        if destination.is_loopback() {
+            return self
+                .interfaces
+                .iter()
+                .position(|i| i.is_loopback())
+                .ok_or(ErrNo::NetworkIsUnreachable);
+        }
+
+        self.interfaces
+            .iter()
+            .position(|i| i.is_attached(destination))
+            .or_else(|| self.interfaces.iter().position(|i| !i.is_loopback()))
+            .ok_or(ErrNo::NetworkIsUnreachable)
+    }
+
+    /// The interfaces a socket bound to `address` receives on.
+    pub fn interfaces_of(&self, address: Ipv4Addr) -> Result<Vec<usize>, ErrNo> {
+        let interfaces = (0..self.interfaces.len())
+            .filter(|i| address.is_unspecified() || self.interfaces[*i].has_address(address))
+            .collect::<Vec<_>>();
+
+        match interfaces.is_empty() {
+            true => Err(ErrNo::CannotAssignRequestedAddress),
+            false => Ok(interfaces),
+        }
+    }
+
+    pub fn add_tcp(&mut self, interface: usize, socket: tcp::Socket<'static>) -> SocketRef {
+        SocketRef {
+            interface,
+            handle: self.interfaces[interface].sockets.add(socket),
+        }
+    }
+
+    pub fn add_udp(&mut self, interface: usize, socket: udp::Socket<'static>) -> SocketRef {
+        SocketRef {
+            interface,
+            handle: self.interfaces[interface].sockets.add(socket),
+        }
+    }
+
+    pub fn tcp(&mut self, socket: SocketRef) -> &mut tcp::Socket<'static> {
+        self.interfaces[socket.interface]
+            .sockets
+            .get_mut::<tcp::Socket>(socket.handle)
+    }
+
+    pub fn udp(&mut self, socket: SocketRef) -> &mut udp::Socket<'static> {
+        self.interfaces[socket.interface]
+            .sockets
+            .get_mut::<udp::Socket>(socket.handle)
+    }
+
+    pub fn remove(&mut self, socket: SocketRef) {
+        self.interfaces[socket.interface]
+            .sockets
+            .remove(socket.handle);
+    }
+
+    /// Gracefully closes a TCP connection in the background, releasing the port reservation
+    /// `(port, id)` once it is gone.
+    pub fn linger(&mut self, socket: SocketRef, port: Option<(u16, usize)>) {
+        self.tcp(socket).close();
+
+        if let Some((port, id)) = port {
+            self.ports.set_lingering(Protocol::Tcp, port, id);
+        }
+
+        self.closing.push(ClosingSocket { socket, port });
+    }
+}
+
+pub(crate) fn endpoint_of(address: SocketAddrV4) -> IpEndpoint {
+    IpEndpoint::from(address)
+}
+
+pub(crate) fn listen_endpoint_of(address: SocketAddrV4) -> IpListenEndpoint {
+    IpListenEndpoint {
+        addr: match address.ip().is_unspecified() {
+            true => None,
+            false => Some(IpAddress::Ipv4(*address.ip())),
+        },
+        port: address.port(),
+    }
+}
+
+pub(crate) fn address_of(endpoint: IpEndpoint) -> SocketAddrV4 {
+    let IpAddress::Ipv4(address) = endpoint.addr;
+
+    SocketAddrV4::new(address, endpoint.port)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicI64, Ordering};
+    use threading::IClock;
+    use timing::TimeSpec;
+
+    use super::NetworkStack;
+
+    /// Moves forward by a millisecond every time it is read
+    pub struct TickingClock(AtomicI64);
+
+    impl IClock for TickingClock {
+        fn now(&self) -> TimeSpec {
+            let millis = self.0.fetch_add(1, Ordering::Relaxed);
+
+            TimeSpec {
+                tv_sec: millis / 1000,
+                tv_nsec: millis % 1000 * 1_000_000,
+            }
+        }
+    }
+
+    pub fn setup_stack() -> Arc<NetworkStack> {
+        NetworkStack::new(Arc::new(TickingClock(AtomicI64::new(0))))
+    }
+}
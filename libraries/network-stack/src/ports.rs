@@ -0,0 +1,233 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use constants::ErrNo;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+/// Ports handed out when binding to port 0, same as Linux's `ip_local_port_range`
+const EPHEMERAL_PORT_START: u16 = 32768;
+const EPHEMERAL_PORT_END: u16 = 60999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug)]
+struct PortUser {
+    id: usize,
+    address: Ipv4Addr,
+    reuse_address: bool,
+    listening: bool,
+    /// The owner is gone but the connection is still shutting down (`TIME_WAIT` and friends)
+    lingering: bool,
+}
+
+impl PortUser {
+    fn overlaps(&self, address: Ipv4Addr) -> bool {
+        self.address.is_unspecified() || address.is_unspecified() || self.address == address
+    }
+
+    /// Whether a new socket may bind next to this one, following the `SO_REUSEADDR` rules of
+    /// Linux: both sides must set it and a listening socket is never shared.
+    fn allows(&self, reuse_address: bool) -> bool {
+        reuse_address && (self.lingering || (self.reuse_address && !self.listening))
+    }
+}
+
+/// Keeps track of which socket uses which local port.
+pub(crate) struct PortTable {
+    users: BTreeMap<(Protocol, u16), Vec<PortUser>>,
+    next_ephemeral: u16,
+}
+
+impl PortTable {
+    pub fn new() -> PortTable {
+        PortTable {
+            users: BTreeMap::new(),
+            next_ephemeral: EPHEMERAL_PORT_START,
+        }
+    }
+
+    /// Reserves `address` for socket `id`, port 0 picks a free ephemeral port.
+    pub fn reserve(
+        &mut self,
+        protocol: Protocol,
+        address: SocketAddrV4,
+        reuse_address: bool,
+        id: usize,
+    ) -> Result<u16, ErrNo> {
+        let port = match address.port() {
+            0 => self.find_ephemeral(protocol)?,
+            port => {
+                let in_use = self.users.get(&(protocol, port)).is_some_and(|users| {
+                    users
+                        .iter()
+                        .any(|u| u.overlaps(*address.ip()) && !u.allows(reuse_address))
+                });
+
+                if in_use {
+                    return Err(ErrNo::AddressAlreadyInUse);
+                }
+
+                port
+            }
+        };
+
+        self.users
+            .entry((protocol, port))
+            .or_default()
+            .push(PortUser {
+                id,
+                address: *address.ip(),
+                reuse_address,
+                listening: false,
+                lingering: false,
+            });
+
+        Ok(port)
+    }
+
+    /// Records that socket `id` uses `address` as well, without checking for conflicts.
+    ///
+    /// Accepted connections use the port of their listener this way.
+    pub fn share(
+        &mut self,
+        protocol: Protocol,
+        address: SocketAddrV4,
+        reuse_address: bool,
+        id: usize,
+    ) {
+        self.users
+            .entry((protocol, address.port()))
+            .or_default()
+            .push(PortUser {
+                id,
+                address: *address.ip(),
+                reuse_address,
+                listening: false,
+                lingering: false,
+            });
+    }
+
+    fn find_ephemeral(&mut self, protocol: Protocol) -> Result<u16, ErrNo> {
+        let count = EPHEMERAL_PORT_END - EPHEMERAL_PORT_START + 1;
+
+        for _ in 0..count {
+            let port = self.next_ephemeral;
+
+            self.next_ephemeral = match port {
+                EPHEMERAL_PORT_END => EPHEMERAL_PORT_START,
+                port => port + 1,
+            };
+
+            if !self.users.contains_key(&(protocol, port)) {
+                return Ok(port);
+            }
+        }
+
+        Err(ErrNo::CannotAssignRequestedAddress)
+    }
+
+    pub fn release(&mut self, protocol: Protocol, port: u16, id: usize) {
+        if let Some(users) = self.users.get_mut(&(protocol, port)) {
+            users.retain(|u| u.id != id);
+
+            if users.is_empty() {
+                self.users.remove(&(protocol, port));
+            }
+        }
+    }
+
+    pub fn set_listening(&mut self, protocol: Protocol, port: u16, id: usize) {
+        self.update(protocol, port, id, |u| u.listening = true);
+    }
+
+    pub fn set_lingering(&mut self, protocol: Protocol, port: u16, id: usize) {
+        self.update(protocol, port, id, |u| {
+            u.listening = false;
+            u.lingering = true;
+        });
+    }
+
+    fn update(&mut self, protocol: Protocol, port: u16, id: usize, f: impl FnOnce(&mut PortUser)) {
+        if let Some(user) = self
+            .users
+            .get_mut(&(protocol, port))
+            .and_then(|users| users.iter_mut().find(|u| u.id == id))
+        {
+            f(user)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn any(port: u16) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)
+    }
+
+    #[test]
+    fn test_conflict() {
+        let mut ports = PortTable::new();
+
+        assert_eq!(ports.reserve(Protocol::Tcp, any(80), false, 1), Ok(80));
+        assert_eq!(
+            ports.reserve(
+                Protocol::Tcp,
+                SocketAddrV4::new(Ipv4Addr::LOCALHOST, 80),
+                false,
+                2
+            ),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+
+        // protocols have their own port space
+        assert_eq!(ports.reserve(Protocol::Udp, any(80), false, 3), Ok(80));
+
+        ports.release(Protocol::Tcp, 80, 1);
+        assert_eq!(ports.reserve(Protocol::Tcp, any(80), false, 2), Ok(80));
+    }
+
+    #[test]
+    fn test_reuse_address() {
+        let mut ports = PortTable::new();
+
+        ports.reserve(Protocol::Tcp, any(8080), true, 1).unwrap();
+        assert_eq!(ports.reserve(Protocol::Tcp, any(8080), true, 2), Ok(8080));
+
+        // listening sockets are never shared
+        ports.set_listening(Protocol::Tcp, 8080, 1);
+        assert_eq!(
+            ports.reserve(Protocol::Tcp, any(8080), true, 3),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+    }
+
+    #[test]
+    fn test_lingering_needs_reuse_address() {
+        let mut ports = PortTable::new();
+
+        ports.reserve(Protocol::Tcp, any(9000), false, 1).unwrap();
+        ports.set_listening(Protocol::Tcp, 9000, 1);
+        ports.set_lingering(Protocol::Tcp, 9000, 1);
+
+        assert_eq!(
+            ports.reserve(Protocol::Tcp, any(9000), false, 2),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+        assert_eq!(ports.reserve(Protocol::Tcp, any(9000), true, 2), Ok(9000));
+    }
+
+    #[test]
+    fn test_ephemeral_ports_are_unique() {
+        let mut ports = PortTable::new();
+
+        let first = ports.reserve(Protocol::Udp, any(0), false, 1).unwrap();
+        let second = ports.reserve(Protocol::Udp, any(0), false, 2).unwrap();
+
+        assert_ne!(first, second);
+        assert!((EPHEMERAL_PORT_START..=EPHEMERAL_PORT_END).contains(&first));
+    }
+}
@@ -0,0 +1,838 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+use constants::ErrNo;
+use core::net::{Ipv4Addr, SocketAddrV4};
+use filesystem_abstractions::IFile;
+use hermit_sync::SpinMutex;
+use smoltcp::{
+    socket::tcp::{self, ConnectError, RecvError, State},
+    time::Duration,
+};
+use socket_abstractions::{
+    ISocket, MessageFlags, PollEvents, ReceivedMessage, Shutdown, SocketAddress, SocketType,
+    AF_INET, IPPROTO_TCP, SOL_SOCKET, SO_ACCEPTCONN, SO_DOMAIN, SO_ERROR, SO_KEEPALIVE, SO_RCVBUF,
+    SO_REUSEADDR, SO_SNDBUF, SO_TYPE, TCP_NODELAY,
+};
+use threading::sync::WaitQueue;
+
+use crate::{
+    address_of, endpoint_of, listen_endpoint_of, ports::Protocol, NetworkStack, SocketRef,
+    StackInner,
+};
+
+/// Size of the send and the receive buffer of every connection
+const TCP_BUFFER_SIZE: usize = 32 * 1024;
+
+/// Connections a listening socket holds per interface before further handshakes are refused
+const MAX_LISTENERS: usize = 8;
+
+/// Interval of keep-alive probes once `SO_KEEPALIVE` is set, same as Linux's `tcp_keepalive_intvl`
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(75);
+
+enum TcpState {
+    Closed {
+        local: Option<SocketAddrV4>,
+    },
+    /// smoltcp sockets accept exactly one connection, so a listening socket keeps a few of
+    /// them listening on every interface it is bound to.
+    Listening {
+        local: SocketAddrV4,
+        listeners: Vec<SocketRef>,
+    },
+    Connecting {
+        socket: SocketRef,
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+    },
+    Connected {
+        socket: SocketRef,
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+    },
+}
+
+struct TcpInner {
+    state: TcpState,
+    /// The port reserved in the port table, accepted sockets share the one of their listener
+    reservation: Option<u16>,
+    reuse_address: bool,
+    keep_alive: bool,
+    no_delay: bool,
+    send_buffer: usize,
+    receive_buffer: usize,
+    read_shutdown: bool,
+    write_shutdown: bool,
+    /// Pending error of a failed non-blocking connect, reported by `SO_ERROR`
+    error: Option<ErrNo>,
+}
+
+/// An `AF_INET` socket of `SOCK_STREAM` type.
+pub struct TcpSocket {
+    id: usize,
+    stack: Arc<NetworkStack>,
+    inner: SpinMutex<TcpInner>,
+}
+
+impl TcpSocket {
+    pub fn new(stack: Arc<NetworkStack>) -> Arc<TcpSocket> {
+        Self::with_state(stack, TcpState::Closed { local: None }, false, false)
+    }
+
+    fn with_state(
+        stack: Arc<NetworkStack>,
+        state: TcpState,
+        keep_alive: bool,
+        no_delay: bool,
+    ) -> Arc<TcpSocket> {
+        Arc::new(TcpSocket {
+            id: stack.allocate_id(),
+            stack,
+            inner: SpinMutex::new(TcpInner {
+                state,
+                reservation: None,
+                reuse_address: false,
+                keep_alive,
+                no_delay,
+                send_buffer: TCP_BUFFER_SIZE,
+                receive_buffer: TCP_BUFFER_SIZE,
+                read_shutdown: false,
+                write_shutdown: false,
+                error: None,
+            }),
+        })
+    }
+
+    fn create_socket(inner: &TcpInner) -> tcp::Socket<'static> {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+            tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        );
+
+        socket.set_nagle_enabled(!inner.no_delay);
+        socket.set_keep_alive(inner.keep_alive.then_some(KEEP_ALIVE_INTERVAL));
+
+        socket
+    }
+
+    fn add_listener(
+        inner: &TcpInner,
+        stack: &mut StackInner,
+        interface: usize,
+        local: SocketAddrV4,
+    ) -> SocketRef {
+        let mut socket = Self::create_socket(inner);
+
+        // The endpoint has a port, listening can not fail
+        socket.listen(listen_endpoint_of(local)).unwrap();
+
+        stack.add_tcp(interface, socket)
+    }
+
+    fn reserve(
+        &self,
+        inner: &mut TcpInner,
+        stack: &mut StackInner,
+        address: SocketAddrV4,
+    ) -> Result<SocketAddrV4, ErrNo> {
+        let port = stack
+            .ports
+            .reserve(Protocol::Tcp, address, inner.reuse_address, self.id)?;
+
+        inner.reservation = Some(port);
+
+        Ok(SocketAddrV4::new(*address.ip(), port))
+    }
+
+    /// Moves a pending connection forward once the handshake is done or has failed.
+    fn update_connecting(inner: &mut TcpInner, stack: &mut StackInner) {
+        if let TcpState::Connecting {
+            socket,
+            local,
+            remote,
+        } = inner.state
+        {
+            match stack.tcp(socket).state() {
+                State::SynSent | State::SynReceived => (),
+                State::Closed => {
+                    stack.remove(socket);
+
+                    inner.state = TcpState::Closed { local: Some(local) };
+                    inner.error = Some(ErrNo::ConnectionRefused);
+                }
+                _ => {
+                    inner.state = TcpState::Connected {
+                        socket,
+                        local,
+                        remote,
+                    }
+                }
+            }
+        }
+    }
+
+    fn local_of(state: &TcpState) -> SocketAddrV4 {
+        match state {
+            TcpState::Closed { local } => {
+                local.unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))
+            }
+            TcpState::Listening { local, .. }
+            | TcpState::Connecting { local, .. }
+            | TcpState::Connected { local, .. } => *local,
+        }
+    }
+}
+
+fn inet_address_of(address: &SocketAddress) -> Result<SocketAddrV4, ErrNo> {
+    match address {
+        SocketAddress::Inet(address) => Ok(*address),
+        _ => Err(ErrNo::AddressFamilyNotSupportedByProtocol),
+    }
+}
+
+impl ISocket for TcpSocket {
+    fn family(&self) -> u16 {
+        AF_INET
+    }
+
+    fn socket_type(&self) -> SocketType {
+        SocketType::Stream
+    }
+
+    fn bind(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        let address = inet_address_of(address)?;
+
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock_without_poll();
+
+        if !matches!(inner.state, TcpState::Closed { local: None }) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        stack.interfaces_of(*address.ip())?;
+
+        let local = self.reserve(&mut inner, &mut stack, address)?;
+
+        inner.state = TcpState::Closed { local: Some(local) };
+
+        Ok(())
+    }
+
+    fn listen(&self, backlog: usize) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock_without_poll();
+
+        let local = match inner.state {
+            TcpState::Listening { .. } => return Ok(()),
+            TcpState::Closed { local: Some(local) } => local,
+            TcpState::Closed { local: None } => self.reserve(
+                &mut inner,
+                &mut stack,
+                SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+            )?,
+            _ => return Err(ErrNo::InvalidArgument),
+        };
+
+        let count = backlog.clamp(1, MAX_LISTENERS);
+
+        let listeners = stack
+            .interfaces_of(*local.ip())?
+            .into_iter()
+            .flat_map(|interface| (0..count).map(move |_| interface))
+            .collect::<Vec<_>>();
+
+        let listeners = listeners
+            .into_iter()
+            .map(|interface| Self::add_listener(&inner, &mut stack, interface, local))
+            .collect();
+
+        stack
+            .ports
+            .set_listening(Protocol::Tcp, local.port(), self.id);
+
+        inner.state = TcpState::Listening { local, listeners };
+
+        Ok(())
+    }
+
+    fn accept(&self) -> Result<Arc<dyn ISocket>, ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        let TcpState::Listening { local, listeners } = &inner.state else {
+            return Err(ErrNo::InvalidArgument);
+        };
+
+        let (local, listeners) = (*local, listeners.clone());
+
+        for index in 0..listeners.len() {
+            let listener = listeners[index];
+            let socket = stack.tcp(listener);
+
+            match socket.state() {
+                State::Listen | State::SynReceived => continue,
+                // The connection was reset before anyone accepted it
+                State::Closed => {
+                    socket.listen(listen_endpoint_of(local)).unwrap();
+                    continue;
+                }
+                _ => (),
+            }
+
+            let connection_local = address_of(socket.local_endpoint().unwrap());
+            let remote = address_of(socket.remote_endpoint().unwrap());
+
+            let replacement = Self::add_listener(&inner, &mut stack, listener.interface, local);
+
+            if let TcpState::Listening { listeners, .. } = &mut inner.state {
+                listeners[index] = replacement;
+            }
+
+            let accepted = Self::with_state(
+                self.stack.clone(),
+                TcpState::Connected {
+                    socket: listener,
+                    local: connection_local,
+                    remote,
+                },
+                inner.keep_alive,
+                inner.no_delay,
+            );
+
+            // The connection keeps the port of the listener in use, even after it is closed
+            stack
+                .ports
+                .share(Protocol::Tcp, local, inner.reuse_address, accepted.id);
+
+            {
+                let mut accepted_inner = accepted.inner.lock();
+
+                accepted_inner.reservation = Some(local.port());
+                accepted_inner.reuse_address = inner.reuse_address;
+            }
+
+            return Ok(accepted);
+        }
+
+        Err(ErrNo::ResourceTemporarilyUnavailable)
+    }
+
+    fn connect(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        let mut remote = inet_address_of(address)?;
+
+        // Linux connects to the local host when the address is INADDR_ANY
+        if remote.ip().is_unspecified() {
+            remote.set_ip(Ipv4Addr::LOCALHOST);
+        }
+
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        let local = match inner.state {
+            TcpState::Closed { local } => local,
+            TcpState::Listening { .. } => return Err(ErrNo::InvalidArgument),
+            TcpState::Connected { .. } => return Err(ErrNo::TransportEndpointIsAlreadyConnected),
+            TcpState::Connecting { .. } => {
+                Self::update_connecting(&mut inner, &mut stack);
+
+                return match inner.state {
+                    TcpState::Connecting { .. } => Err(ErrNo::OperationAlreadyInProgress),
+                    TcpState::Connected { .. } => Ok(()),
+                    _ => Err(inner.error.take().unwrap_or(ErrNo::ConnectionRefused)),
+                };
+            }
+        };
+
+        let interface = stack.route(*remote.ip())?;
+
+        let local = match local {
+            Some(local) => local,
+            None => self.reserve(
+                &mut inner,
+                &mut stack,
+                SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
+            )?,
+        };
+
+        let socket = stack.add_tcp(interface, Self::create_socket(&inner));
+
+        let result = {
+            let interface = &mut stack.interfaces[interface];
+
+            interface
+                .sockets
+                .get_mut::<tcp::Socket>(socket.handle)
+                .connect(
+                    interface.iface.context(),
+                    endpoint_of(remote),
+                    listen_endpoint_of(local),
+                )
+        };
+
+        if let Err(e) = result {
+            stack.remove(socket);
+
+            return Err(match e {
+                ConnectError::InvalidState => ErrNo::OperationAlreadyInProgress,
+                ConnectError::Unaddressable => ErrNo::NetworkIsUnreachable,
+            });
+        }
+
+        let local = address_of(stack.tcp(socket).local_endpoint().unwrap());
+
+        inner.state = TcpState::Connecting {
+            socket,
+            local,
+            remote,
+        };
+
+        self.stack.poll_locked(&mut stack);
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        match inner.state {
+            TcpState::Connecting { .. } => Err(ErrNo::OperationNowInProgress),
+            TcpState::Connected { .. } => Ok(()),
+            _ => Err(inner.error.take().unwrap_or(ErrNo::ConnectionRefused)),
+        }
+    }
+
+    fn send(
+        &self,
+        data: &[u8],
+        _rights: Vec<Arc<dyn IFile>>,
+        _destination: Option<&SocketAddress>,
+    ) -> Result<usize, ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        let socket = match inner.state {
+            TcpState::Connected { socket, .. } => socket,
+            TcpState::Connecting { .. } => return Err(ErrNo::ResourceTemporarilyUnavailable),
+            _ => return Err(ErrNo::TransportEndpointIsNotConnected),
+        };
+
+        if inner.write_shutdown {
+            return Err(ErrNo::BrokenPipe);
+        }
+
+        let connection = stack.tcp(socket);
+
+        if !connection.may_send() {
+            return match connection.state() {
+                State::Closed => Err(ErrNo::ConnectionResetByPeer),
+                _ => Err(ErrNo::BrokenPipe),
+            };
+        }
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let sent = connection.send_slice(data).map_err(|_| ErrNo::BrokenPipe)?;
+
+        if sent == 0 {
+            return Err(ErrNo::ResourceTemporarilyUnavailable);
+        }
+
+        self.stack.poll_locked(&mut stack);
+
+        Ok(sent)
+    }
+
+    fn receive(&self, buf: &mut [u8], flags: MessageFlags) -> Result<ReceivedMessage, ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        let (socket, remote) = match inner.state {
+            TcpState::Connected { socket, remote, .. } => (socket, remote),
+            TcpState::Connecting { .. } => return Err(ErrNo::ResourceTemporarilyUnavailable),
+            _ => return Err(ErrNo::TransportEndpointIsNotConnected),
+        };
+
+        let message = |length| ReceivedMessage {
+            length,
+            full_length: length,
+            source: SocketAddress::Inet(remote),
+            rights: Vec::new(),
+        };
+
+        if inner.read_shutdown {
+            return Ok(message(0));
+        }
+
+        let connection = stack.tcp(socket);
+
+        let result = match flags.contains(MessageFlags::MSG_PEEK) {
+            true => connection.peek_slice(buf),
+            false => connection.recv_slice(buf),
+        };
+
+        let length = match result {
+            Ok(0) if !buf.is_empty() => return Err(ErrNo::ResourceTemporarilyUnavailable),
+            Ok(length) => length,
+            Err(RecvError::Finished) => 0,
+            Err(RecvError::InvalidState) => match connection.state() {
+                State::Closed => return Err(ErrNo::ConnectionResetByPeer),
+                _ => 0,
+            },
+        };
+
+        // Let the peer know about the freed window
+        self.stack.poll_locked(&mut stack);
+
+        Ok(message(length))
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        let TcpState::Connected { socket, .. } = inner.state else {
+            return Err(ErrNo::TransportEndpointIsNotConnected);
+        };
+
+        if how.read() {
+            inner.read_shutdown = true;
+        }
+
+        if how.write() && !inner.write_shutdown {
+            inner.write_shutdown = true;
+
+            stack.tcp(socket).close();
+            self.stack.poll_locked(&mut stack);
+        }
+
+        Ok(())
+    }
+
+    fn local_address(&self) -> SocketAddress {
+        SocketAddress::Inet(Self::local_of(&self.inner.lock().state))
+    }
+
+    fn peer_address(&self) -> Result<SocketAddress, ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        match inner.state {
+            TcpState::Connected { remote, .. } => Ok(SocketAddress::Inet(remote)),
+            _ => Err(ErrNo::TransportEndpointIsNotConnected),
+        }
+    }
+
+    fn poll(&self) -> PollEvents {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        Self::update_connecting(&mut inner, &mut stack);
+
+        match &inner.state {
+            TcpState::Closed { .. } => match inner.error {
+                Some(_) => PollEvents::POLLOUT | PollEvents::POLLERR | PollEvents::POLLHUP,
+                None => PollEvents::POLLOUT | PollEvents::POLLHUP,
+            },
+            TcpState::Listening { listeners, .. } => {
+                let pending = listeners.iter().any(|listener| {
+                    !matches!(
+                        stack.tcp(*listener).state(),
+                        State::Listen | State::SynReceived | State::Closed
+                    )
+                });
+
+                match pending {
+                    true => PollEvents::POLLIN,
+                    false => PollEvents::empty(),
+                }
+            }
+            TcpState::Connecting { .. } => PollEvents::empty(),
+            TcpState::Connected { socket, .. } => {
+                let connection = stack.tcp(*socket);
+                let mut events = PollEvents::empty();
+
+                if connection.can_recv() || !connection.may_recv() || inner.read_shutdown {
+                    events |= PollEvents::POLLIN;
+                }
+
+                if !connection.may_recv() {
+                    events |= PollEvents::POLLRDHUP;
+                }
+
+                if connection.can_send() || inner.write_shutdown {
+                    events |= PollEvents::POLLOUT;
+                }
+
+                if !connection.may_recv() && !connection.may_send() {
+                    events |= PollEvents::POLLHUP;
+                }
+
+                events
+            }
+        }
+    }
+
+    fn readiness(&self) -> &WaitQueue {
+        self.stack.readiness()
+    }
+
+    fn get_option(&self, level: usize, name: usize) -> Result<usize, ErrNo> {
+        let mut inner = self.inner.lock();
+
+        match (level, name) {
+            (SOL_SOCKET, SO_TYPE) => Ok(SocketType::Stream as usize),
+            (SOL_SOCKET, SO_DOMAIN) => Ok(AF_INET as usize),
+            (SOL_SOCKET, SO_ERROR) => Ok(inner.error.take().map_or(0, |e| -(e as isize) as usize)),
+            (SOL_SOCKET, SO_ACCEPTCONN) => {
+                Ok(matches!(inner.state, TcpState::Listening { .. }) as usize)
+            }
+            (SOL_SOCKET, SO_SNDBUF) => Ok(inner.send_buffer),
+            (SOL_SOCKET, SO_RCVBUF) => Ok(inner.receive_buffer),
+            (SOL_SOCKET, SO_REUSEADDR) => Ok(inner.reuse_address as usize),
+            (SOL_SOCKET, SO_KEEPALIVE) => Ok(inner.keep_alive as usize),
+            (IPPROTO_TCP, TCP_NODELAY) => Ok(inner.no_delay as usize),
+            _ => Err(ErrNo::ProtocolNotAvailable),
+        }
+    }
+
+    fn set_option(&self, level: usize, name: usize, value: usize) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+
+        match (level, name) {
+            (SOL_SOCKET, SO_SNDBUF) => inner.send_buffer = value,
+            (SOL_SOCKET, SO_RCVBUF) => inner.receive_buffer = value,
+            (SOL_SOCKET, SO_REUSEADDR) => inner.reuse_address = value != 0,
+            (SOL_SOCKET, SO_KEEPALIVE) => inner.keep_alive = value != 0,
+            (IPPROTO_TCP, TCP_NODELAY) => inner.no_delay = value != 0,
+            _ => return Err(ErrNo::ProtocolNotAvailable),
+        }
+
+        if let TcpState::Connecting { socket, .. } | TcpState::Connected { socket, .. } =
+            inner.state
+        {
+            let mut stack = self.stack.lock_without_poll();
+            let connection = stack.tcp(socket);
+
+            connection.set_nagle_enabled(!inner.no_delay);
+            connection.set_keep_alive(inner.keep_alive.then_some(KEEP_ALIVE_INTERVAL));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        let mut stack = self.stack.lock_without_poll();
+
+        let reservation = inner.reservation.map(|port| (port, self.id));
+
+        match &inner.state {
+            TcpState::Closed { .. } => {
+                if let Some((port, id)) = reservation {
+                    stack.ports.release(Protocol::Tcp, port, id);
+                }
+            }
+            TcpState::Listening { listeners, .. } => {
+                for listener in listeners.iter() {
+                    // Connections nobody accepted are reset, as Linux does
+                    stack.tcp(*listener).abort();
+                }
+
+                // Let the resets go out before the sockets are gone
+                self.stack.poll_locked(&mut stack);
+
+                for listener in listeners.iter() {
+                    stack.remove(*listener);
+                }
+
+                if let Some((port, id)) = reservation {
+                    stack.ports.release(Protocol::Tcp, port, id);
+                }
+            }
+            TcpState::Connecting { socket, .. } | TcpState::Connected { socket, .. } => {
+                stack.linger(*socket, reservation);
+                self.stack.poll_locked(&mut stack);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use super::*;
+    use crate::tests::setup_stack;
+
+    fn localhost(port: u16) -> SocketAddress {
+        SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+    }
+
+    fn setup_server(stack: &Arc<NetworkStack>, port: u16) -> Arc<TcpSocket> {
+        let server = TcpSocket::new(stack.clone());
+
+        server.bind(&localhost(port)).unwrap();
+        server.listen(4).unwrap();
+
+        server
+    }
+
+    fn connect(client: &TcpSocket, address: &SocketAddress) -> Result<(), ErrNo> {
+        loop {
+            match client.connect(address) {
+                Err(ErrNo::OperationNowInProgress) | Err(ErrNo::OperationAlreadyInProgress) => (),
+                result => return result,
+            }
+        }
+    }
+
+    #[test]
+    fn test_connect_accept_echo() {
+        let stack = setup_stack();
+        let server = setup_server(&stack, 8000);
+
+        let client = TcpSocket::new(stack.clone());
+        assert_eq!(connect(&client, &localhost(8000)), Ok(()));
+
+        let accepted = server.accept().unwrap();
+        assert_eq!(accepted.peer_address().unwrap(), client.local_address());
+        assert_eq!(client.peer_address().unwrap(), localhost(8000));
+
+        assert_eq!(client.send(b"ping", Vec::new(), None), Ok(4));
+
+        let mut buf = [0u8; 16];
+        let received = accepted.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf[..received.length], b"ping");
+
+        accepted.send(b"pong", Vec::new(), None).unwrap();
+
+        let received = client.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf[..received.length], b"pong");
+
+        assert_eq!(
+            server.accept().err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_polls_wake_waiters() {
+        let stack = setup_stack();
+        let server = setup_server(&stack, 8002);
+
+        let mut waiting = pin!(server.readiness().wait());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Pending);
+
+        // Nothing happens without packets
+        stack.poll();
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Pending);
+
+        let client = TcpSocket::new(stack.clone());
+        assert_eq!(connect(&client, &localhost(8002)), Ok(()));
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let stack = setup_stack();
+        let client = TcpSocket::new(stack);
+
+        assert_eq!(
+            connect(&client, &localhost(8001)),
+            Err(ErrNo::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn test_end_of_file_after_shutdown() {
+        let stack = setup_stack();
+        let server = setup_server(&stack, 8002);
+
+        let client = TcpSocket::new(stack.clone());
+        connect(&client, &localhost(8002)).unwrap();
+
+        let accepted = server.accept().unwrap();
+
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(
+            client.send(b"late", Vec::new(), None),
+            Err(ErrNo::BrokenPipe)
+        );
+
+        assert!(accepted.poll().contains(PollEvents::POLLRDHUP));
+        assert_eq!(
+            accepted
+                .receive(&mut [0u8; 4], MessageFlags::empty())
+                .unwrap()
+                .length,
+            0
+        );
+    }
+
+    #[test]
+    fn test_nonblocking_receive() {
+        let stack = setup_stack();
+        let server = setup_server(&stack, 8003);
+
+        let client = TcpSocket::new(stack.clone());
+        connect(&client, &localhost(8003)).unwrap();
+
+        let accepted = server.accept().unwrap();
+
+        assert!(!accepted.poll().contains(PollEvents::POLLIN));
+        assert_eq!(
+            accepted.receive(&mut [0u8; 4], MessageFlags::empty()).err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_reuse_address() {
+        let stack = setup_stack();
+        let server = setup_server(&stack, 8004);
+
+        let client = TcpSocket::new(stack.clone());
+        connect(&client, &localhost(8004)).unwrap();
+
+        let accepted = server.accept().unwrap();
+
+        // The server side closes first and keeps the port while the connection shuts down
+        drop(accepted);
+        drop(server);
+
+        let again = TcpSocket::new(stack.clone());
+        assert_eq!(
+            again.bind(&localhost(8004)),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+
+        again.set_option(SOL_SOCKET, SO_REUSEADDR, 1).unwrap();
+        assert_eq!(again.bind(&localhost(8004)), Ok(()));
+        assert_eq!(again.listen(1), Ok(()));
+    }
+
+    #[test]
+    fn test_bind_foreign_address() {
+        let stack = setup_stack();
+        let socket = TcpSocket::new(stack);
+
+        assert_eq!(
+            socket.bind(&SocketAddress::Inet(SocketAddrV4::new(
+                Ipv4Addr::new(10, 0, 0, 1),
+                80
+            ))),
+            Err(ErrNo::CannotAssignRequestedAddress)
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+use constants::ErrNo;
+use core::net::{Ipv4Addr, SocketAddrV4};
+use filesystem_abstractions::IFile;
+use hermit_sync::SpinMutex;
+use smoltcp::socket::udp::{self, SendError};
+use socket_abstractions::{
+    ISocket, MessageFlags, PollEvents, ReceivedMessage, Shutdown, SocketAddress, SocketType,
+    AF_INET, SOL_SOCKET, SO_ACCEPTCONN, SO_DOMAIN, SO_ERROR, SO_RCVBUF, SO_REUSEADDR, SO_SNDBUF,
+    SO_TYPE,
+};
+use threading::sync::WaitQueue;
+
+use crate::{
+    address_of, endpoint_of, listen_endpoint_of, ports::Protocol, NetworkStack, SocketRef,
+    StackInner,
+};
+
+/// Datagrams queued in either direction
+const UDP_QUEUE_LENGTH: usize = 64;
+
+/// Bytes of payload queued in either direction, also the largest datagram
+const UDP_BUFFER_SIZE: usize = 64 * 1024;
+
+struct UdpInner {
+    local: Option<SocketAddrV4>,
+    /// One socket for every interface the local address is reachable on
+    sockets: Vec<SocketRef>,
+    /// Default destination and the only accepted source once connected
+    peer: Option<SocketAddrV4>,
+    reuse_address: bool,
+    send_buffer: usize,
+    receive_buffer: usize,
+    read_shutdown: bool,
+    write_shutdown: bool,
+}
+
+/// An `AF_INET` socket of `SOCK_DGRAM` type.
+pub struct UdpSocket {
+    id: usize,
+    stack: Arc<NetworkStack>,
+    inner: SpinMutex<UdpInner>,
+}
+
+impl UdpSocket {
+    pub fn new(stack: Arc<NetworkStack>) -> Arc<UdpSocket> {
+        Arc::new(UdpSocket {
+            id: stack.allocate_id(),
+            stack,
+            inner: SpinMutex::new(UdpInner {
+                local: None,
+                sockets: Vec::new(),
+                peer: None,
+                reuse_address: false,
+                send_buffer: UDP_BUFFER_SIZE,
+                receive_buffer: UDP_BUFFER_SIZE,
+                read_shutdown: false,
+                write_shutdown: false,
+            }),
+        })
+    }
+
+    fn bind_to(
+        &self,
+        inner: &mut UdpInner,
+        stack: &mut StackInner,
+        address: SocketAddrV4,
+    ) -> Result<(), ErrNo> {
+        let interfaces = stack.interfaces_of(*address.ip())?;
+
+        let port = stack
+            .ports
+            .reserve(Protocol::Udp, address, inner.reuse_address, self.id)?;
+
+        let local = SocketAddrV4::new(*address.ip(), port);
+
+        inner.sockets = interfaces
+            .into_iter()
+            .map(|interface| {
+                let mut socket = udp::Socket::new(
+                    udp::PacketBuffer::new(
+                        vec![udp::PacketMetadata::EMPTY; UDP_QUEUE_LENGTH],
+                        vec![0; UDP_BUFFER_SIZE],
+                    ),
+                    udp::PacketBuffer::new(
+                        vec![udp::PacketMetadata::EMPTY; UDP_QUEUE_LENGTH],
+                        vec![0; UDP_BUFFER_SIZE],
+                    ),
+                );
+
+                // The port is never 0, binding can not fail
+                socket.bind(listen_endpoint_of(local)).unwrap();
+
+                stack.add_udp(interface, socket)
+            })
+            .collect();
+
+        inner.local = Some(local);
+
+        Ok(())
+    }
+
+    fn autobind(&self, inner: &mut UdpInner, stack: &mut StackInner) -> Result<(), ErrNo> {
+        match inner.local {
+            Some(_) => Ok(()),
+            None => self.bind_to(inner, stack, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)),
+        }
+    }
+}
+
+fn inet_address_of(address: &SocketAddress) -> Result<SocketAddrV4, ErrNo> {
+    match address {
+        // Linux sends to the local host when the address is INADDR_ANY
+        SocketAddress::Inet(address) if address.ip().is_unspecified() => {
+            Ok(SocketAddrV4::new(Ipv4Addr::LOCALHOST, address.port()))
+        }
+        SocketAddress::Inet(address) => Ok(*address),
+        _ => Err(ErrNo::AddressFamilyNotSupportedByProtocol),
+    }
+}
+
+impl ISocket for UdpSocket {
+    fn family(&self) -> u16 {
+        AF_INET
+    }
+
+    fn socket_type(&self) -> SocketType {
+        SocketType::Datagram
+    }
+
+    fn bind(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        let SocketAddress::Inet(address) = address else {
+            return Err(ErrNo::AddressFamilyNotSupportedByProtocol);
+        };
+
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock_without_poll();
+
+        if inner.local.is_some() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        self.bind_to(&mut inner, &mut stack, *address)
+    }
+
+    fn listen(&self, _backlog: usize) -> Result<(), ErrNo> {
+        Err(ErrNo::OperationNotSupported)
+    }
+
+    fn accept(&self) -> Result<Arc<dyn ISocket>, ErrNo> {
+        Err(ErrNo::OperationNotSupported)
+    }
+
+    fn connect(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+
+        // Connecting to AF_UNSPEC dissolves the association
+        if let SocketAddress::Unspecified = address {
+            inner.peer = None;
+            return Ok(());
+        }
+
+        let peer = inet_address_of(address)?;
+
+        let mut stack = self.stack.lock_without_poll();
+
+        stack.route(*peer.ip())?;
+        self.autobind(&mut inner, &mut stack)?;
+
+        inner.peer = Some(peer);
+
+        Ok(())
+    }
+
+    fn send(
+        &self,
+        data: &[u8],
+        _rights: Vec<Arc<dyn IFile>>,
+        destination: Option<&SocketAddress>,
+    ) -> Result<usize, ErrNo> {
+        let mut inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        if inner.write_shutdown {
+            return Err(ErrNo::BrokenPipe);
+        }
+
+        let destination = match destination {
+            Some(destination) => inet_address_of(destination)?,
+            None => inner.peer.ok_or(ErrNo::DestinationAddressRequired)?,
+        };
+
+        let interface = stack.route(*destination.ip())?;
+
+        self.autobind(&mut inner, &mut stack)?;
+
+        let socket = *inner
+            .sockets
+            .iter()
+            .find(|socket| socket.interface == interface)
+            .ok_or(ErrNo::NetworkIsUnreachable)?;
+
+        let udp = stack.udp(socket);
+
+        if data.len() > udp.payload_send_capacity() {
+            return Err(ErrNo::MessageTooLong);
+        }
+
+        udp.send_slice(data, endpoint_of(destination))
+            .map_err(|e| match e {
+                SendError::BufferFull => ErrNo::ResourceTemporarilyUnavailable,
+                SendError::Unaddressable => ErrNo::NetworkIsUnreachable,
+            })?;
+
+        self.stack.poll_locked(&mut stack);
+
+        Ok(data.len())
+    }
+
+    fn receive(&self, buf: &mut [u8], flags: MessageFlags) -> Result<ReceivedMessage, ErrNo> {
+        let inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        for socket in inner.sockets.iter() {
+            let udp = stack.udp(*socket);
+
+            while let Ok((_, metadata)) = udp.peek() {
+                let source = address_of(metadata.endpoint);
+
+                // A connected socket drops datagrams from anyone but its peer
+                if inner.peer.is_some_and(|peer| peer != source) {
+                    udp.recv().unwrap();
+                    continue;
+                }
+
+                let data = match flags.contains(MessageFlags::MSG_PEEK) {
+                    true => udp.peek().unwrap().0,
+                    false => udp.recv().unwrap().0,
+                };
+
+                let length = data.len().min(buf.len());
+                buf[..length].copy_from_slice(&data[..length]);
+
+                return Ok(ReceivedMessage {
+                    length,
+                    full_length: data.len(),
+                    source: SocketAddress::Inet(source),
+                    rights: Vec::new(),
+                });
+            }
+        }
+
+        match inner.read_shutdown {
+            true => Ok(ReceivedMessage {
+                length: 0,
+                full_length: 0,
+                source: SocketAddress::Unspecified,
+                rights: Vec::new(),
+            }),
+            false => Err(ErrNo::ResourceTemporarilyUnavailable),
+        }
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+
+        if inner.peer.is_none() {
+            return Err(ErrNo::TransportEndpointIsNotConnected);
+        }
+
+        inner.read_shutdown |= how.read();
+        inner.write_shutdown |= how.write();
+
+        Ok(())
+    }
+
+    fn local_address(&self) -> SocketAddress {
+        let local = self.inner.lock().local;
+
+        SocketAddress::Inet(local.unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)))
+    }
+
+    fn peer_address(&self) -> Result<SocketAddress, ErrNo> {
+        self.inner
+            .lock()
+            .peer
+            .map(SocketAddress::Inet)
+            .ok_or(ErrNo::TransportEndpointIsNotConnected)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let inner = self.inner.lock();
+        let mut stack = self.stack.lock();
+
+        let mut events = PollEvents::POLLOUT;
+
+        let readable = inner.read_shutdown
+            || inner
+                .sockets
+                .iter()
+                .any(|socket| stack.udp(*socket).can_recv());
+
+        if readable {
+            events |= PollEvents::POLLIN;
+        }
+
+        events
+    }
+
+    fn readiness(&self) -> &WaitQueue {
+        self.stack.readiness()
+    }
+
+    fn get_option(&self, level: usize, name: usize) -> Result<usize, ErrNo> {
+        if level != SOL_SOCKET {
+            return Err(ErrNo::ProtocolNotAvailable);
+        }
+
+        let inner = self.inner.lock();
+
+        match name {
+            SO_TYPE => Ok(SocketType::Datagram as usize),
+            SO_DOMAIN => Ok(AF_INET as usize),
+            SO_ERROR => Ok(0),
+            SO_ACCEPTCONN => Ok(0),
+            SO_SNDBUF => Ok(inner.send_buffer),
+            SO_RCVBUF => Ok(inner.receive_buffer),
+            SO_REUSEADDR => Ok(inner.reuse_address as usize),
+            _ => Err(ErrNo::ProtocolNotAvailable),
+        }
+    }
+
+    fn set_option(&self, level: usize, name: usize, value: usize) -> Result<(), ErrNo> {
+        if level != SOL_SOCKET {
+            return Err(ErrNo::ProtocolNotAvailable);
+        }
+
+        let mut inner = self.inner.lock();
+
+        match name {
+            SO_SNDBUF => inner.send_buffer = value,
+            SO_RCVBUF => inner.receive_buffer = value,
+            SO_REUSEADDR => inner.reuse_address = value != 0,
+            _ => return Err(ErrNo::ProtocolNotAvailable),
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        let mut stack = self.stack.lock_without_poll();
+
+        for socket in inner.sockets.iter() {
+            stack.remove(*socket);
+        }
+
+        if let Some(local) = inner.local {
+            stack.ports.release(Protocol::Udp, local.port(), self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::setup_stack;
+
+    fn localhost(port: u16) -> SocketAddress {
+        SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+    }
+
+    #[test]
+    fn test_send_receive() {
+        let stack = setup_stack();
+
+        let server = UdpSocket::new(stack.clone());
+        server.bind(&localhost(5353)).unwrap();
+
+        let client = UdpSocket::new(stack);
+        assert_eq!(
+            client.send(b"query", Vec::new(), Some(&localhost(5353))),
+            Ok(5)
+        );
+
+        assert!(server.poll().contains(PollEvents::POLLIN));
+
+        let mut buf = [0u8; 16];
+        let received = server.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        assert_eq!(&buf[..received.length], b"query");
+        let SocketAddress::Inet(local) = client.local_address() else {
+            unreachable!()
+        };
+        assert_eq!(
+            received.source,
+            SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, local.port()))
+        );
+
+        assert_eq!(
+            server.receive(&mut buf, MessageFlags::empty()).err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_truncated_datagram() {
+        let stack = setup_stack();
+
+        let server = UdpSocket::new(stack.clone());
+        server.bind(&localhost(5354)).unwrap();
+
+        let client = UdpSocket::new(stack);
+        client.connect(&localhost(5354)).unwrap();
+        client.send(b"long datagram", Vec::new(), None).unwrap();
+
+        let mut buf = [0u8; 4];
+
+        let peeked = server.receive(&mut buf, MessageFlags::MSG_PEEK).unwrap();
+        assert_eq!(peeked.full_length, 13);
+
+        let received = server.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!((received.length, received.full_length), (4, 13));
+        assert_eq!(&buf, b"long");
+    }
+
+    #[test]
+    fn test_connected_filters_sources() {
+        let stack = setup_stack();
+
+        let receiver = UdpSocket::new(stack.clone());
+        receiver.bind(&localhost(5355)).unwrap();
+        receiver.connect(&localhost(5356)).unwrap();
+
+        let stranger = UdpSocket::new(stack);
+        stranger
+            .send(b"hi", Vec::new(), Some(&localhost(5355)))
+            .unwrap();
+
+        assert_eq!(
+            receiver.receive(&mut [0u8; 4], MessageFlags::empty()).err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_destination_required() {
+        let stack = setup_stack();
+        let socket = UdpSocket::new(stack);
+
+        assert_eq!(
+            socket.send(b"lost", Vec::new(), None),
+            Err(ErrNo::DestinationAddressRequired)
+        );
+    }
+}
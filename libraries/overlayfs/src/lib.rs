@@ -0,0 +1,341 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::sync::Arc;
+use filesystem_abstractions::{
+    DirectoryTreeNode, FileSystemError, FileSystemResult, IFileSystem, IInode,
+};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod node;
+
+pub use node::{OverlayInode, OPAQUE_MARKER, WHITEOUT_PREFIX};
+
+use node::Origins;
+
+/// A writable view of a read-only lower tree, with all changes kept in an upper directory.
+///
+/// Files are copied up with their parents the first time they are changed. Removing a lower file
+/// leaves a `.wh.<name>` whiteout next to where it would be in the upper layer, and a directory
+/// made over a removed one gets an opaque marker so the lower directory stays hidden. Renaming
+/// lower directories is not supported.
+pub struct OverlayFileSystem {
+    root: Arc<OverlayInode>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay of the `lower` directory, which is only ever read, and the `upper` one.
+    pub fn new(lower: Arc<dyn IInode>, upper: Arc<dyn IInode>) -> Arc<OverlayFileSystem> {
+        let origins = Arc::new(Origins::new(Default::default()));
+
+        Arc::new(OverlayFileSystem {
+            root: OverlayInode::root(lower, upper, origins),
+        })
+    }
+
+    /// Shadows what is at `mount_point` with an overlay of it and `upper`.
+    ///
+    /// What was opened or mounted below the mount point is hidden along with it, until
+    /// [`DirectoryTreeNode::restore_shadow`] puts it back.
+    pub fn mount_over(
+        mount_point: &Arc<DirectoryTreeNode>,
+        upper: Arc<dyn IInode>,
+    ) -> FileSystemResult<Arc<OverlayFileSystem>> {
+        let lower = mount_point.inode().ok_or(FileSystemError::NotADirectory)?;
+        let fs = Self::new(lower, upper);
+
+        let node = DirectoryTreeNode::from_filesystem(None, fs.clone(), Some(mount_point.name()));
+
+        match mount_point.shadow_with(node) {
+            true => Ok(fs),
+            false => Err(FileSystemError::InternalError),
+        }
+    }
+}
+
+impl IFileSystem for OverlayFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        self.root.clone()
+    }
+
+    fn name(&self) -> &str {
+        "overlay"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.root.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::sync::atomic::{AtomicI64, Ordering};
+    use filesystem_abstractions::{FileStatistics, FileSystemError, XattrFlags};
+    use threading::IClock;
+    use timing::TimeSpec;
+    use tmpfs::TmpFileSystem;
+
+    use super::*;
+
+    struct TickingClock(AtomicI64);
+
+    impl IClock for TickingClock {
+        fn now(&self) -> TimeSpec {
+            TimeSpec {
+                tv_sec: self.0.fetch_add(1, Ordering::Relaxed),
+                tv_nsec: 0,
+            }
+        }
+    }
+
+    fn tmpfs() -> Arc<DirectoryTreeNode> {
+        let clock = Arc::new(TickingClock(AtomicI64::new(1_700_000_000)));
+
+        DirectoryTreeNode::from_filesystem(None, TmpFileSystem::new(clock), Some(""))
+    }
+
+    /// A lower tree with `/etc/hosts`, `/etc/passwd`, `/bin/sh` and `/readme`, an empty upper
+    /// tree and the overlay of both.
+    fn create() -> (
+        Arc<DirectoryTreeNode>,
+        Arc<DirectoryTreeNode>,
+        Arc<DirectoryTreeNode>,
+    ) {
+        let lower = tmpfs();
+        let etc = lower.mkdir("etc").unwrap();
+        etc.touch("hosts")
+            .unwrap()
+            .writeat(0, b"localhost")
+            .unwrap();
+        etc.touch("passwd").unwrap().writeat(0, b"root").unwrap();
+        lower.mkdir("bin").unwrap().touch("sh").unwrap();
+        lower.touch("readme").unwrap().writeat(0, b"lower").unwrap();
+
+        let upper = tmpfs();
+
+        let fs = OverlayFileSystem::new(lower.inode().unwrap(), upper.inode().unwrap());
+        let root = DirectoryTreeNode::from_filesystem(None, fs, Some(""));
+
+        (lower, upper, root)
+    }
+
+    fn names(node: &Arc<DirectoryTreeNode>) -> Vec<String> {
+        let mut names: Vec<String> = node
+            .read_dir()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.filename)
+            .filter(|name| name != "." && name != "..")
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    fn read(root: &Arc<DirectoryTreeNode>, path: &str) -> FileSystemResult<Vec<u8>> {
+        root.open(path, Some(root))?.readall()
+    }
+
+    fn inode_id(node: &Arc<DirectoryTreeNode>) -> u64 {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        node.stat(&mut stat).unwrap();
+
+        stat.inode_id
+    }
+
+    #[test]
+    fn test_copy_up_on_write() {
+        let (lower, upper, root) = create();
+
+        assert_eq!(names(&root), ["bin", "etc", "readme"]);
+        assert_eq!(read(&root, "/etc/hosts").unwrap(), b"localhost");
+
+        let hosts = root.open("/etc/hosts", Some(&root)).unwrap();
+        let id = inode_id(&hosts);
+        hosts.writeat(9, b" router").unwrap();
+
+        assert_eq!(read(&root, "/etc/hosts").unwrap(), b"localhost router");
+        assert_eq!(read(&lower, "/etc/hosts").unwrap(), b"localhost");
+        assert_eq!(read(&upper, "/etc/hosts").unwrap(), b"localhost router");
+        assert_eq!(inode_id(&hosts), id);
+
+        // Only what was written to is copied, the rest of its directory stays below
+        assert_eq!(names(&upper), ["etc"]);
+        assert_eq!(names(&upper.open("/etc", Some(&upper)).unwrap()), ["hosts"]);
+        assert_eq!(
+            names(&root.open("/etc", Some(&root)).unwrap()),
+            ["hosts", "passwd"]
+        );
+
+        root.open("/readme", Some(&root))
+            .unwrap()
+            .resize_inode(2)
+            .unwrap();
+        assert_eq!(read(&root, "/readme").unwrap(), b"lo");
+        assert_eq!(read(&lower, "/readme").unwrap(), b"lower");
+    }
+
+    #[test]
+    fn test_xattrs_copied_up() {
+        let (lower, upper, root) = create();
+        let open = |root: &Arc<DirectoryTreeNode>| root.open("/etc/passwd", Some(root)).unwrap();
+
+        open(&lower)
+            .set_xattr("user.origin", b"lower", XattrFlags::empty())
+            .unwrap();
+        assert_eq!(open(&root).get_xattr("user.origin").unwrap(), b"lower");
+
+        open(&root)
+            .set_xattr("user.added", b"upper", XattrFlags::empty())
+            .unwrap();
+
+        assert_eq!(
+            open(&upper).list_xattr().unwrap(),
+            ["user.added", "user.origin"]
+        );
+        assert_eq!(open(&lower).list_xattr().unwrap(), ["user.origin"]);
+
+        open(&root).remove_xattr("user.origin").unwrap();
+        assert_eq!(open(&root).list_xattr().unwrap(), ["user.added"]);
+        assert_eq!(open(&lower).get_xattr("user.origin").unwrap(), b"lower");
+    }
+
+    #[test]
+    fn test_whiteouts() {
+        let (lower, upper, root) = create();
+
+        root.remove("readme").unwrap();
+        assert_eq!(names(&root), ["bin", "etc"]);
+        assert_eq!(
+            read(&root, "/readme").err(),
+            Some(FileSystemError::NotFound)
+        );
+        assert_eq!(names(&upper), [".wh.readme"]);
+        assert_eq!(read(&lower, "/readme").unwrap(), b"lower");
+
+        let bin = root.open("/bin", Some(&root)).unwrap();
+        assert_eq!(
+            root.rmdir("bin").err(),
+            Some(FileSystemError::DirectoryNotEmpty)
+        );
+        bin.remove("sh").unwrap();
+        drop(bin);
+        root.rmdir("bin").unwrap();
+        assert_eq!(names(&root), ["etc"]);
+        assert_eq!(names(&lower), ["bin", "etc", "readme"]);
+
+        // A new file takes the place of the whiteout
+        root.touch("readme").unwrap().writeat(0, b"upper").unwrap();
+        assert_eq!(read(&root, "/readme").unwrap(), b"upper");
+        assert_eq!(names(&upper), [".wh.bin", "readme"]);
+
+        assert_eq!(
+            root.inode().unwrap().touch(".wh.etc").err(),
+            Some(FileSystemError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_opaque_directories() {
+        let (lower, upper, root) = create();
+
+        // Both layers are merged in a directory that is in both
+        root.open("/etc", Some(&root))
+            .unwrap()
+            .touch("fstab")
+            .unwrap();
+        assert_eq!(
+            names(&root.open("/etc", Some(&root)).unwrap()),
+            ["fstab", "hosts", "passwd"]
+        );
+
+        let etc = root.open("/etc", Some(&root)).unwrap();
+        for name in ["fstab", "hosts", "passwd"] {
+            etc.remove(name).unwrap();
+        }
+        drop(etc);
+        root.rmdir("etc").unwrap();
+
+        let etc = root.mkdir("etc").unwrap();
+        assert!(names(&etc).is_empty());
+        etc.touch("hosts").unwrap();
+        assert_eq!(read(&root, "/etc/hosts").unwrap(), b"");
+
+        assert_eq!(
+            names(&upper.open("/etc", Some(&upper)).unwrap()),
+            [".wh..wh..opq", "hosts"]
+        );
+        assert_eq!(
+            names(&lower.open("/etc", Some(&lower)).unwrap()),
+            ["hosts", "passwd"]
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let (lower, upper, root) = create();
+
+        let passwd = root.open("/etc/passwd", Some(&root)).unwrap();
+        let id = inode_id(&passwd);
+        drop(passwd);
+
+        let etc = root.open("/etc", Some(&root)).unwrap();
+        etc.rename("passwd", "users").unwrap();
+        assert_eq!(names(&etc), ["hosts", "users"]);
+        assert_eq!(read(&root, "/etc/users").unwrap(), b"root");
+        assert_eq!(inode_id(&root.open("/etc/users", Some(&root)).unwrap()), id);
+        assert_eq!(
+            names(&upper.open("/etc", Some(&upper)).unwrap()),
+            [".wh.passwd", "users"]
+        );
+
+        // Over a lower file, which is hidden by the one renamed
+        etc.rename("users", "hosts").unwrap();
+        assert_eq!(names(&etc), ["hosts"]);
+        assert_eq!(read(&root, "/etc/hosts").unwrap(), b"root");
+
+        assert_eq!(
+            root.rename("bin", "sbin").err(),
+            Some(FileSystemError::CrossDevice)
+        );
+
+        root.mkdir("var").unwrap();
+        root.rename("var", "tmp").unwrap();
+        assert_eq!(names(&root), ["bin", "etc", "readme", "tmp"]);
+        assert_eq!(names(&lower), ["bin", "etc", "readme"]);
+    }
+
+    #[test]
+    fn test_mount_over() {
+        let tree = tmpfs();
+        let lower = tmpfs();
+        lower.touch("file").unwrap().writeat(0, b"lower").unwrap();
+        tree.mkdir("data").unwrap();
+        tree.mount_as(lower.clone(), Some("data")).unwrap();
+
+        let data = tree.open("/data", Some(&tree)).unwrap();
+        OverlayFileSystem::mount_over(&data, tmpfs().inode().unwrap()).unwrap();
+
+        tree.open("/data/file", Some(&tree))
+            .unwrap()
+            .writeat(0, b"upper")
+            .unwrap();
+        tree.open("/data", Some(&tree))
+            .unwrap()
+            .touch("new")
+            .unwrap();
+
+        assert_eq!(read(&tree, "/data/file").unwrap(), b"upper");
+        assert_eq!(names(&data), ["file", "new"]);
+        assert_eq!(tree.mounts()[1].1, "overlay");
+
+        data.restore_shadow().unwrap();
+
+        assert_eq!(read(&tree, "/data/file").unwrap(), b"lower");
+        assert_eq!(names(&data), ["file"]);
+    }
+}
@@ -0,0 +1,620 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileMode, FileStatistics, FileSystemError,
+    FileSystemResult, IInode, InodeAttributes, InodeMetadata, XattrFlags,
+};
+use hermit_sync::SpinMutex;
+
+/// Names in the upper layer starting with this are markers, never shown or created by users.
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// A file in an upper directory that hides everything the lower layer has in that directory.
+pub const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// Inode numbers of files only ever in the upper layer, kept apart from the lower ones.
+const UPPER_INODE_BIT: u64 = 1 << 63;
+
+const COPY_CHUNK: usize = 4096;
+
+/// Lower inode numbers of copied up files, by their upper inode number.
+///
+/// A copied up file keeps its number this way, even after it is renamed away from its lower file.
+pub(crate) type Origins = SpinMutex<BTreeMap<u64, u64>>;
+
+/// A file in the overlay, made of its upper file, its lower file or both for merged directories.
+pub struct OverlayInode {
+    this: Weak<OverlayInode>,
+    name: String,
+    parent: Option<Arc<OverlayInode>>,
+    origins: Arc<Origins>,
+    entry_type: DirectoryEntryType,
+    upper: SpinMutex<Option<Arc<dyn IInode>>>,
+    lower: Option<Arc<dyn IInode>>,
+}
+
+fn whiteout(name: &str) -> String {
+    format!("{WHITEOUT_PREFIX}{name}")
+}
+
+fn check_name(name: &str) -> FileSystemResult<()> {
+    if name.is_empty() || name == "." || name == ".." || name.starts_with(WHITEOUT_PREFIX) {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    Ok(())
+}
+
+fn ignore_missing(result: FileSystemResult<()>) -> FileSystemResult<()> {
+    match result {
+        Err(FileSystemError::NotFound) => Ok(()),
+        result => result,
+    }
+}
+
+#[allow(deprecated)]
+fn find(dir: &Arc<dyn IInode>, name: &str) -> FileSystemResult<Option<Arc<dyn IInode>>> {
+    match dir.lookup(name) {
+        Ok(inode) => Ok(Some(inode)),
+        Err(FileSystemError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_directory(inode: &Arc<dyn IInode>) -> bool {
+    inode.metadata().entry_type == DirectoryEntryType::Directory
+}
+
+fn inode_id(inode: &Arc<dyn IInode>) -> FileSystemResult<u64> {
+    let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+    inode.stat(&mut stat)?;
+
+    Ok(stat.inode_id)
+}
+
+/// Creates a marker file, which may be there already.
+fn mark(dir: &Arc<dyn IInode>, name: &str) -> FileSystemResult<()> {
+    match dir.touch(name) {
+        Ok(_) | Err(FileSystemError::AlreadyExists) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes the markers in an upper directory, so the directory itself can be removed.
+fn clear_markers(dir: &Arc<dyn IInode>) -> FileSystemResult<()> {
+    for entry in dir.read_cache_dir(&mut BTreeMap::new())? {
+        if entry.filename.starts_with(WHITEOUT_PREFIX) {
+            dir.remove(&entry.filename)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl OverlayInode {
+    pub(crate) fn root(
+        lower: Arc<dyn IInode>,
+        upper: Arc<dyn IInode>,
+        origins: Arc<Origins>,
+    ) -> Arc<OverlayInode> {
+        Self::new(
+            String::new(),
+            None,
+            origins,
+            DirectoryEntryType::Directory,
+            Some(upper),
+            Some(lower),
+        )
+    }
+
+    fn new(
+        name: String,
+        parent: Option<Arc<OverlayInode>>,
+        origins: Arc<Origins>,
+        entry_type: DirectoryEntryType,
+        upper: Option<Arc<dyn IInode>>,
+        lower: Option<Arc<dyn IInode>>,
+    ) -> Arc<OverlayInode> {
+        Arc::new_cyclic(|this| OverlayInode {
+            this: this.clone(),
+            name,
+            parent,
+            origins,
+            entry_type,
+            upper: SpinMutex::new(upper),
+            lower,
+        })
+    }
+
+    fn child(
+        &self,
+        name: &str,
+        upper: Option<Arc<dyn IInode>>,
+        lower: Option<Arc<dyn IInode>>,
+    ) -> Arc<OverlayInode> {
+        let entry_type = match upper.as_ref().or(lower.as_ref()) {
+            Some(inode) => inode.metadata().entry_type,
+            None => DirectoryEntryType::Unknown,
+        };
+
+        Self::new(
+            name.to_string(),
+            self.this.upgrade(),
+            self.origins.clone(),
+            entry_type,
+            upper,
+            lower,
+        )
+    }
+
+    /// The upper file, if this was created or copied up already.
+    pub fn upper(&self) -> Option<Arc<dyn IInode>> {
+        self.upper.lock().clone()
+    }
+
+    /// The lower file this shows, it is never written to.
+    pub fn lower(&self) -> Option<&Arc<dyn IInode>> {
+        self.lower.as_ref()
+    }
+
+    fn backing(&self) -> Arc<dyn IInode> {
+        match self.upper() {
+            Some(upper) => upper,
+            // One of both is always there
+            None => self.lower.clone().unwrap(),
+        }
+    }
+
+    fn is_directory(&self) -> bool {
+        self.entry_type == DirectoryEntryType::Directory
+    }
+
+    /// The lower directory whose entries show through, none once the upper one is opaque.
+    fn lower_dir(
+        &self,
+        upper: Option<&Arc<dyn IInode>>,
+    ) -> FileSystemResult<Option<&Arc<dyn IInode>>> {
+        match upper {
+            Some(upper) if find(upper, OPAQUE_MARKER)?.is_some() => Ok(None),
+            _ => Ok(self.lower.as_ref()),
+        }
+    }
+
+    fn lookup_child(&self, name: &str) -> FileSystemResult<Arc<OverlayInode>> {
+        if !self.is_directory() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(FileSystemError::NotFound);
+        }
+
+        let upper_dir = self.upper();
+
+        let (upper, whited_out) = match &upper_dir {
+            Some(dir) => (find(dir, name)?, find(dir, &whiteout(name))?.is_some()),
+            None => (None, false),
+        };
+
+        let lower = match self.lower_dir(upper_dir.as_ref())? {
+            Some(dir) if !whited_out => find(dir, name)?,
+            _ => None,
+        };
+
+        // Only directories are merged, anything else in the upper layer hides the lower file
+        let lower = match (&upper, lower) {
+            (Some(upper), Some(lower)) if is_directory(upper) && is_directory(&lower) => {
+                Some(lower)
+            }
+            (Some(_), _) => None,
+            (None, lower) => lower,
+        };
+
+        if upper.is_none() && lower.is_none() {
+            return Err(FileSystemError::NotFound);
+        }
+
+        Ok(self.child(name, upper, lower))
+    }
+
+    fn entries(&self) -> FileSystemResult<BTreeMap<String, DirectoryEntryType>> {
+        let upper_dir = self.upper();
+
+        let mut entries = BTreeMap::new();
+        let mut whiteouts = BTreeSet::new();
+
+        if let Some(dir) = &upper_dir {
+            for entry in dir.read_cache_dir(&mut BTreeMap::new())? {
+                match entry.filename.strip_prefix(WHITEOUT_PREFIX) {
+                    Some(_) if entry.filename == OPAQUE_MARKER => (),
+                    Some(hidden) => {
+                        whiteouts.insert(hidden.to_string());
+                    }
+                    None => {
+                        entries.insert(entry.filename, entry.entry_type);
+                    }
+                }
+            }
+        }
+
+        if let Some(dir) = self.lower_dir(upper_dir.as_ref())? {
+            for entry in dir.read_cache_dir(&mut BTreeMap::new())? {
+                if !whiteouts.contains(&entry.filename) {
+                    entries.entry(entry.filename).or_insert(entry.entry_type);
+                }
+            }
+        }
+
+        entries.remove(".");
+        entries.remove("..");
+
+        Ok(entries)
+    }
+
+    /// Makes sure this has an upper file, copying the lower file and its parents up if not.
+    pub fn copy_up(&self) -> FileSystemResult<Arc<dyn IInode>> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+
+        // The root always has its upper directory
+        let parent_dir = self
+            .parent
+            .as_ref()
+            .ok_or(FileSystemError::InternalError)?
+            .copy_up()?;
+
+        let mut upper = self.upper.lock();
+
+        if let Some(upper) = upper.as_ref() {
+            return Ok(upper.clone());
+        }
+
+        let lower = self.lower.as_ref().ok_or(FileSystemError::InternalError)?;
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        lower.stat(&mut stat)?;
+
+        let copied = match self.entry_type {
+            DirectoryEntryType::Directory => parent_dir.mkdir(&self.name)?,
+            DirectoryEntryType::Symlink => {
+                let target = lower.resolve_link().ok_or(FileSystemError::NotALink)?;
+
+                parent_dir.soft_link(&self.name, &target)?
+            }
+            DirectoryEntryType::File => {
+                let file = parent_dir.touch(&self.name)?;
+
+                if let Err(e) = Self::copy_content(lower, &file) {
+                    let _ = parent_dir.remove(&self.name);
+
+                    return Err(e);
+                }
+
+                file
+            }
+            // Device nodes and the like cannot be made through inodes
+            _ => return Err(FileSystemError::NotPermitted),
+        };
+
+        let attributes = InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(stat.mode.bits())),
+            uid: Some(stat.uid),
+            gid: Some(stat.gid),
+            atime: Some(stat.atime),
+            mtime: Some(stat.mtime),
+        };
+
+        match copied.set_attributes(&attributes) {
+            Ok(()) | Err(FileSystemError::Unimplemented) => (),
+            Err(e) => return Err(e),
+        }
+
+        Self::copy_xattrs(lower, &copied)?;
+
+        if let Ok(upper_id) = inode_id(&copied) {
+            self.origins.lock().insert(upper_id, stat.inode_id);
+        }
+
+        *upper = Some(copied.clone());
+
+        Ok(copied)
+    }
+
+    fn copy_content(from: &Arc<dyn IInode>, to: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let mut buffer = vec![0; COPY_CHUNK];
+        let mut offset = 0;
+
+        loop {
+            let read = from.readat(offset, &mut buffer)?;
+
+            if read == 0 {
+                return Ok(());
+            }
+
+            let mut written = 0;
+
+            while written < read {
+                match to.writeat(offset + written, &buffer[written..read])? {
+                    0 => return Err(FileSystemError::WriteZero),
+                    n => written += n,
+                }
+            }
+
+            offset += read;
+        }
+    }
+
+    /// Copies the extended attributes over, as far as both layers support them.
+    fn copy_xattrs(from: &Arc<dyn IInode>, to: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let names = match from.list_xattr() {
+            Ok(names) => names,
+            Err(FileSystemError::NotSupported) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for name in names {
+            match to.set_xattr(&name, &from.get_xattr(&name)?, XattrFlags::empty()) {
+                Ok(()) | Err(FileSystemError::NotSupported) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies this directory up and checks `name` is free to create something at.
+    fn prepare_create(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        check_name(name)?;
+
+        match self.lookup_child(name) {
+            Ok(_) => return Err(FileSystemError::AlreadyExists),
+            Err(FileSystemError::NotFound) => (),
+            Err(e) => return Err(e),
+        }
+
+        self.copy_up()
+    }
+
+    /// Removes `name` from the upper directory and hides the lower file, if any.
+    fn unlink(&self, name: &str, directory_wanted: bool) -> FileSystemResult<()> {
+        let child = self.lookup_child(name)?;
+
+        match (directory_wanted, child.is_directory()) {
+            (false, true) => return Err(FileSystemError::NotAFile),
+            (true, false) => return Err(FileSystemError::NotADirectory),
+            (true, true) if !child.entries()?.is_empty() => {
+                return Err(FileSystemError::DirectoryNotEmpty)
+            }
+            _ => (),
+        }
+
+        let upper_dir = self.copy_up()?;
+
+        if child.lower.is_some() {
+            mark(&upper_dir, &whiteout(name))?;
+        }
+
+        match child.upper() {
+            Some(upper) if directory_wanted => {
+                clear_markers(&upper)?;
+                upper_dir.rmdir(name)
+            }
+            Some(_) => upper_dir.remove(name),
+            None => Ok(()),
+        }
+    }
+}
+
+impl IInode for OverlayInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry_type,
+            size: self.backing().metadata().size,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        self.backing().readat(offset, buffer)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        if self.entry_type != DirectoryEntryType::File {
+            return self.backing().writeat(offset, buffer);
+        }
+
+        self.copy_up()?.writeat(offset, buffer)
+    }
+
+    fn mkdir(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let upper_dir = self.prepare_create(name)?;
+        let made = upper_dir.mkdir(name)?;
+
+        // Something was removed here, the lower directory it may have been must stay hidden
+        if find(&upper_dir, &whiteout(name))?.is_some() {
+            mark(&made, OPAQUE_MARKER)?;
+            ignore_missing(upper_dir.remove(&whiteout(name)))?;
+        }
+
+        Ok(self.child(name, Some(made), None))
+    }
+
+    fn rmdir(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, true)
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, false)
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let upper_dir = self.prepare_create(name)?;
+        let made = upper_dir.touch(name)?;
+
+        ignore_missing(upper_dir.remove(&whiteout(name)))?;
+
+        Ok(self.child(name, Some(made), None))
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        if !self.is_directory() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        Ok(self
+            .entries()?
+            .into_iter()
+            .map(|(filename, entry_type)| DirectoryEntry {
+                filename,
+                entry_type,
+            })
+            .collect())
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Ok(self.lookup_child(name)?)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        match self.upper() {
+            Some(upper) => upper.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        self.backing().stat(stat)?;
+
+        // Keep the number of the lower file, so it stays the same across a copy up
+        stat.inode_id = match &self.lower {
+            Some(lower) => inode_id(lower)?,
+            None => match self.origins.lock().get(&stat.inode_id) {
+                Some(&origin) => origin,
+                None => stat.inode_id | UPPER_INODE_BIT,
+            },
+        };
+
+        Ok(())
+    }
+
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        self.copy_up()?.set_attributes(attributes)
+    }
+
+    fn get_xattr(&self, name: &str) -> FileSystemResult<Vec<u8>> {
+        self.backing().get_xattr(name)
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> FileSystemResult<()> {
+        self.copy_up()?.set_xattr(name, value, flags)
+    }
+
+    fn list_xattr(&self) -> FileSystemResult<Vec<String>> {
+        self.backing().list_xattr()
+    }
+
+    fn remove_xattr(&self, name: &str) -> FileSystemResult<()> {
+        self.copy_up()?.remove_xattr(name)
+    }
+
+    fn hard_link(&self, name: &str, inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let Some(source) = inode.downcast_ref::<OverlayInode>() else {
+            return Err(FileSystemError::NotPermitted);
+        };
+
+        if source.is_directory() {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        let upper_dir = self.prepare_create(name)?;
+
+        upper_dir.hard_link(name, &source.copy_up()?)?;
+
+        ignore_missing(upper_dir.remove(&whiteout(name)))
+    }
+
+    fn soft_link(&self, name: &str, point_to: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let upper_dir = self.prepare_create(name)?;
+        let made = upper_dir.soft_link(name, point_to)?;
+
+        ignore_missing(upper_dir.remove(&whiteout(name)))?;
+
+        Ok(self.child(name, Some(made), None))
+    }
+
+    fn resolve_link(&self) -> Option<String> {
+        self.backing().resolve_link()
+    }
+
+    fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+        if self.entry_type != DirectoryEntryType::File {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        self.copy_up()?.resize(new_size)
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str) -> FileSystemResult<()> {
+        check_name(new_name)?;
+
+        let source = self.lookup_child(old_name)?;
+
+        // The lower directory would have to be found under another name, which is not tracked.
+        // Like Linux without redirect_dir, callers fall back to copying on EXDEV
+        if source.is_directory() && source.lower.is_some() {
+            return Err(FileSystemError::CrossDevice);
+        }
+
+        let target = match self.lookup_child(new_name) {
+            Ok(target) => Some(target),
+            Err(FileSystemError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(target) = &target {
+            match (source.is_directory(), target.is_directory()) {
+                (true, true) if !target.entries()?.is_empty() => {
+                    return Err(FileSystemError::DirectoryNotEmpty)
+                }
+                (true, false) => return Err(FileSystemError::NotADirectory),
+                (false, true) => return Err(FileSystemError::NotAFile),
+                _ => (),
+            }
+        }
+
+        let upper_dir = self.copy_up()?;
+        let source_upper = source.copy_up()?;
+
+        let target_whiteout = whiteout(new_name);
+        let hides_lower = target.as_ref().is_some_and(|t| t.lower.is_some())
+            || find(&upper_dir, &target_whiteout)?.is_some();
+
+        if let Some(target_upper) = target.as_ref().and_then(|t| t.upper()) {
+            if target.as_ref().is_some_and(|t| t.is_directory()) {
+                clear_markers(&target_upper)?;
+            }
+        }
+
+        if source.is_directory() && hides_lower {
+            mark(&source_upper, OPAQUE_MARKER)?;
+        }
+
+        upper_dir.rename(old_name, new_name)?;
+
+        if source.lower.is_some() {
+            mark(&upper_dir, &whiteout(old_name))?;
+        }
+
+        ignore_missing(upper_dir.remove(&target_whiteout))
+    }
+}
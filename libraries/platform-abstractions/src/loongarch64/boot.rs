@@ -8,6 +8,8 @@ use loongArch64::{
     },
 };
 
+use platform_specific::{phys_to_virt, virt_to_phys};
+
 use crate::{clear_bss, loongarch64::context::init_thread_info};
 
 #[unsafe(naked)]
@@ -44,6 +46,10 @@ pub unsafe extern "C" fn _start() -> ! {
             li.d        $t0, (0x9000000000000000 | 1 | 1 << 4)
             csrwr       $t0, 0x181
 
+            # Keep the EFI system table the firmware passed, bss is cleared later so it lives in .data
+            la.global   $t0, {system_table}
+            st.d        $a2, $t0, 0
+
             # Setup stack for main thread
             la.global   $sp, __tmp_stack_top
 
@@ -96,9 +102,68 @@ pub unsafe extern "C" fn _start() -> ! {
             jirl        $zero, $t0, 0
             ",
         main_processor_init = sym main_processor_init,
+        system_table = sym EFI_SYSTEM_TABLE,
     )
 }
 
+#[link_section = ".data"]
+static mut EFI_SYSTEM_TABLE: usize = 0;
+
+/// "IBI SYST"
+const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+
+/// b1b621d5-f19c-41a5-830b-d9152c69aae0 in its in-memory layout
+const EFI_DEVICE_TREE_GUID: [u8; 16] = [
+    0xd5, 0x21, 0xb6, 0xb1, 0x9c, 0xf1, 0xa5, 0x41, 0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0,
+];
+
+/// Where QEMU's `virt` machine loads the device tree when it does not pass a system table
+const QEMU_DEVICE_TREE: usize = 0x10_0000;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// The physical address of the flattened device tree from the firmware.
+///
+/// QEMU passes an EFI system table in a2 whose configuration table points to the device tree,
+/// older versions only load it at a fixed address.
+pub fn device_tree_address() -> Option<usize> {
+    // Written by `_start` behind the compiler's back
+    let system_table = unsafe { ::core::ptr::addr_of!(EFI_SYSTEM_TABLE).read_volatile() };
+
+    let address = find_efi_device_tree(virt_to_phys(system_table)).unwrap_or(QEMU_DEVICE_TREE);
+    let magic = unsafe { (phys_to_virt(address) as *const u32).read_volatile() };
+
+    (u32::from_be(magic) == FDT_MAGIC).then_some(address)
+}
+
+fn find_efi_device_tree(system_table: usize) -> Option<usize> {
+    // Only look inside the boot mapping of the RAM
+    if !(0x1000..0xc000_0000).contains(&system_table) || system_table % 8 != 0 {
+        return None;
+    }
+
+    let table = phys_to_virt(system_table) as *const u64;
+
+    unsafe {
+        if table.read_volatile() != EFI_SYSTEM_TABLE_SIGNATURE {
+            return None;
+        }
+
+        // NumberOfTableEntries and ConfigurationTable follow the header and the 10 pointers after it
+        let entries = table.add(13).read_volatile() as usize;
+        let configurations = virt_to_phys(table.add(14).read_volatile() as usize);
+
+        (0..entries).find_map(|index| {
+            // Each entry is a GUID followed by a pointer
+            let entry = phys_to_virt(configurations + index * 24) as *const u8;
+            let guid = (entry as *const [u8; 16]).read_unaligned();
+
+            (guid == EFI_DEVICE_TREE_GUID)
+                .then(|| virt_to_phys((entry.add(16) as *const usize).read_unaligned()))
+        })
+    }
+}
+
 global_asm!(
     "
 .section .text
@@ -0,0 +1,31 @@
+use loongArch64::{
+    register::{
+        ecfg::{self, LineBasedInterrupt},
+        tcfg, ticlr,
+    },
+    time::get_timer_freq,
+};
+
+#[inline]
+pub fn time_counter() -> u64 {
+    platform_specific::stable_counter() as u64
+}
+
+/// The stable counter runs at the frequency reported by `CPUCFG` words 4 and 5
+#[inline]
+pub fn time_counter_frequency() -> u64 {
+    get_timer_freq() as u64
+}
+
+/// Raises a timer interrupt once the stable counter reaches `deadline`
+pub fn set_timer(deadline: u64) {
+    // The timer counts down from a relative value whose lowest two bits are reserved
+    let ticks = deadline.saturating_sub(time_counter()).max(4);
+
+    ticlr::clear_timer_interrupt();
+    tcfg::set_init_val((ticks as usize + 3) & !3);
+    tcfg::set_periodic(false);
+    tcfg::set_en(true);
+
+    ecfg::set_lie(ecfg::read().lie() | LineBasedInterrupt::TIMER);
+}
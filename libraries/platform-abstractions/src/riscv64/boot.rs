@@ -19,6 +19,9 @@ pub unsafe extern "C" fn _start() -> ! {
         // "mv tp, a0",
         // // Read the device tree address
         // "mv gp, a1",
+        // Keep the device tree address OpenSBI passed, bss is cleared later so it lives in .data
+        "la t0, {device_tree}",
+        "sd a1, 0(t0)",
         // Setup virtual memory
         // See comments below for details
         "la t0, {page_table}",
@@ -34,6 +37,7 @@ pub unsafe extern "C" fn _start() -> ! {
         // Do not save the return address to ra
         "jr t0",
         page_table = sym PAGE_TABLE,
+        device_tree = sym DEVICE_TREE,
         virt_addr_offset = const platform_specific::VIRT_ADDR_OFFSET,
         entry = sym _start_virtualized,
     )
@@ -91,6 +95,23 @@ static mut PAGE_TABLE: [usize; 512] = {
     arr
 };
 
+#[link_section = ".data"]
+static mut DEVICE_TREE: usize = 0;
+
+/// The physical address of the flattened device tree from the bootloader.
+///
+/// Only addresses inside the boot mapping of the RAM are reported, as booting with `go` on the
+/// Vision Five 2 leaves whatever was in a1.
+pub fn device_tree_address() -> Option<usize> {
+    // Written by `_start` behind the compiler's back
+    let address = unsafe { ::core::ptr::addr_of!(DEVICE_TREE).read_volatile() };
+
+    match address {
+        0x8000_0000..0xc000_0000 if address % 8 == 0 => Some(address),
+        _ => None,
+    }
+}
+
 unsafe extern "C" fn pre_boot_init() {
     unsafe { clear_bss() };
 
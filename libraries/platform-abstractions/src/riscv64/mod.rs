@@ -1,9 +1,11 @@
 mod boot;
 mod context;
 mod system;
+mod timer;
 mod trap;
 
-pub use boot::_start;
+pub use boot::{_start, device_tree_address};
 pub use system::{machine_shutdown, print_bootloader_info};
+pub use timer::{set_timer, time_counter, time_counter_frequency};
 pub use trap::init as init_trap;
 pub use trap::{return_to_user, translate_current_trap};
@@ -0,0 +1,19 @@
+/// Frequency of the `time` CSR, QEMU's `virt` machine reports 10MHz as its `timebase-frequency`
+const TIMEBASE_FREQUENCY: u64 = 10_000_000;
+
+#[inline]
+pub fn time_counter() -> u64 {
+    platform_specific::time() as u64
+}
+
+#[inline]
+pub fn time_counter_frequency() -> u64 {
+    TIMEBASE_FREQUENCY
+}
+
+/// Raises a supervisor timer interrupt once the time counter reaches `deadline`
+pub fn set_timer(deadline: u64) {
+    sbi_rt::set_timer(deadline);
+
+    unsafe { riscv::register::sie::set_stimer() };
+}
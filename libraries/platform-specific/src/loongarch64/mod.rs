@@ -23,6 +23,7 @@ pub const PLATFORM_STRING: &CStr = c"LoongArch64";
 
 pub const PHYS_ADDR_MASK: usize = 0x0000_7FFF_FFFF_FFFF; // keep to lower half
 pub const VIRT_ADDR_OFFSET: usize = 0x9000_0000_0000_0000; // to higher half
+pub const DEVICE_ADDR_OFFSET: usize = 0x8000_0000_0000_0000; // strongly ordered uncached
 
 pub use registers::*;
 
@@ -38,6 +39,13 @@ pub const fn phys_to_virt(paddr: usize) -> usize {
     (paddr & PHYS_ADDR_MASK) | VIRT_ADDR_OFFSET
 }
 
+// IMPORTANT: Must provide for every platform
+/// Where the registers of a device at `paddr` are reached, through the uncached window
+#[inline(always)]
+pub const fn device_phys_to_virt(paddr: usize) -> usize {
+    (paddr & PHYS_ADDR_MASK) | DEVICE_ADDR_OFFSET
+}
+
 #[inline(always)]
 pub fn current_processor_index() -> usize {
     r21()
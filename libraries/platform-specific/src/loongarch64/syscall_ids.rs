@@ -1,8 +1,24 @@
+pub const SYSCALL_ID_SETXATTR: usize = 5;
+pub const SYSCALL_ID_LSETXATTR: usize = 6;
+pub const SYSCALL_ID_FSETXATTR: usize = 7;
+pub const SYSCALL_ID_GETXATTR: usize = 8;
+pub const SYSCALL_ID_LGETXATTR: usize = 9;
+pub const SYSCALL_ID_FGETXATTR: usize = 10;
+pub const SYSCALL_ID_LISTXATTR: usize = 11;
+pub const SYSCALL_ID_LLISTXATTR: usize = 12;
+pub const SYSCALL_ID_FLISTXATTR: usize = 13;
+pub const SYSCALL_ID_REMOVEXATTR: usize = 14;
+pub const SYSCALL_ID_LREMOVEXATTR: usize = 15;
+pub const SYSCALL_ID_FREMOVEXATTR: usize = 16;
 pub const SYSCALL_ID_GETCWD: usize = 17;
 pub const SYSCALL_ID_DUP: usize = 23;
 pub const SYSCALL_ID_DUP3: usize = 24;
 pub const SYSCALL_ID_FCNTL64: usize = 25;
+pub const SYSCALL_ID_INOTIFY_INIT1: usize = 26;
+pub const SYSCALL_ID_INOTIFY_ADD_WATCH: usize = 27;
+pub const SYSCALL_ID_INOTIFY_RM_WATCH: usize = 28;
 pub const SYSCALL_ID_IOCTL: usize = 29;
+pub const SYSCALL_ID_FLOCK: usize = 32;
 pub const SYSCALL_ID_MKDIRAT: usize = 34;
 pub const SYSCALL_ID_UNLINKAT: usize = 35;
 pub const SYSCALL_ID_SYMLINKAT: usize = 36;
@@ -10,7 +26,11 @@ pub const SYSCALL_ID_LINKAT: usize = 37;
 pub const SYSCALL_ID_UMOUNT: usize = 39;
 pub const SYSCALL_ID_MOUNT: usize = 40;
 pub const SYSCALL_ID_FTRUNCATE64: usize = 46;
+pub const SYSCALL_ID_FALLOCATE: usize = 47;
+pub const SYSCALL_ID_FACCESSAT: usize = 48;
 pub const SYSCALL_ID_CHDIR: usize = 49;
+pub const SYSCALL_ID_FCHMODAT: usize = 53;
+pub const SYSCALL_ID_FCHOWNAT: usize = 54;
 pub const SYSCALL_ID_OPENAT: usize = 56;
 pub const SYSCALL_ID_CLOSE: usize = 57;
 pub const SYSCALL_ID_PIPE2: usize = 59;
@@ -26,7 +46,12 @@ pub const SYSCALL_ID_SENDFILE: usize = 71;
 pub const SYSCALL_ID_PSELECT6: usize = 72;
 pub const SYSCALL_ID_PPOLL: usize = 73;
 pub const SYSCALL_ID_SPLICE: usize = 76;
+pub const SYSCALL_ID_TEE: usize = 77;
 pub const SYSCALL_ID_READLINKAT: usize = 78;
+pub const SYSCALL_ID_SYNC: usize = 81;
+pub const SYSCALL_ID_FSYNC: usize = 82;
+pub const SYSCALL_ID_FDATASYNC: usize = 83;
+pub const SYSCALL_ID_UTIMENSAT: usize = 88;
 pub const SYSCALL_ID_EXIT: usize = 93;
 pub const SYSCALL_ID_EXIT_GROUP: usize = 94;
 pub const SYSCALL_ID_SET_TID_ADDRESS: usize = 96;
@@ -34,7 +59,19 @@ pub const SYSCALL_ID_FUTEX: usize = 98;
 pub const SYSCALL_ID_NANOSLEEP: usize = 101;
 pub const SYSCALL_ID_SYSLOG: usize = 116;
 pub const SYSCALL_ID_SCHED_YIELD: usize = 124;
+pub const SYSCALL_ID_SETREGID: usize = 143;
+pub const SYSCALL_ID_SETGID: usize = 144;
+pub const SYSCALL_ID_SETREUID: usize = 145;
+pub const SYSCALL_ID_SETUID: usize = 146;
+pub const SYSCALL_ID_SETRESUID: usize = 147;
+pub const SYSCALL_ID_GETRESUID: usize = 148;
+pub const SYSCALL_ID_SETRESGID: usize = 149;
+pub const SYSCALL_ID_GETRESGID: usize = 150;
+pub const SYSCALL_ID_SETFSUID: usize = 151;
+pub const SYSCALL_ID_SETFSGID: usize = 152;
 pub const SYSCALL_ID_TIMES: usize = 153;
+pub const SYSCALL_ID_GETGROUPS: usize = 158;
+pub const SYSCALL_ID_SETGROUPS: usize = 159;
 pub const SYSCALL_ID_UNAME: usize = 160;
 pub const SYSCALL_ID_GETRUSAGE: usize = 165;
 pub const SYSCALL_ID_GETTIMEOFDAY: usize = 169;
@@ -42,25 +79,59 @@ pub const SYSCALL_ID_GETPID: usize = 172;
 pub const SYSCALL_ID_GETPPID: usize = 173;
 pub const SYSCALL_ID_GETUID: usize = 174;
 pub const SYSCALL_ID_GETEUID: usize = 175;
+pub const SYSCALL_ID_GETGID: usize = 176;
+pub const SYSCALL_ID_GETEGID: usize = 177;
 pub const SYSCALL_ID_GETTID: usize = 178;
 pub const SYSCALL_ID_SYSINFO: usize = 179;
+pub const SYSCALL_ID_MQ_OPEN: usize = 180;
+pub const SYSCALL_ID_MQ_UNLINK: usize = 181;
+pub const SYSCALL_ID_MQ_TIMEDSEND: usize = 182;
+pub const SYSCALL_ID_MQ_TIMEDRECEIVE: usize = 183;
+pub const SYSCALL_ID_MQ_NOTIFY: usize = 184;
+pub const SYSCALL_ID_MQ_GETSETATTR: usize = 185;
+pub const SYSCALL_ID_MSGGET: usize = 186;
+pub const SYSCALL_ID_MSGCTL: usize = 187;
+pub const SYSCALL_ID_MSGRCV: usize = 188;
+pub const SYSCALL_ID_MSGSND: usize = 189;
+pub const SYSCALL_ID_SEMGET: usize = 190;
+pub const SYSCALL_ID_SEMCTL: usize = 191;
+pub const SYSCALL_ID_SEMTIMEDOP: usize = 192;
+pub const SYSCALL_ID_SEMOP: usize = 193;
 pub const SYSCALL_ID_SHMGET: usize = 194;
+pub const SYSCALL_ID_SHMCTL: usize = 195;
 pub const SYSCALL_ID_SHMAT: usize = 196;
+pub const SYSCALL_ID_SHMDT: usize = 197;
 pub const SYSCALL_ID_SOCKET: usize = 198;
+pub const SYSCALL_ID_SOCKETPAIR: usize = 199;
+pub const SYSCALL_ID_BIND: usize = 200;
+pub const SYSCALL_ID_LISTEN: usize = 201;
+pub const SYSCALL_ID_ACCEPT: usize = 202;
+pub const SYSCALL_ID_CONNECT: usize = 203;
+pub const SYSCALL_ID_GETSOCKNAME: usize = 204;
+pub const SYSCALL_ID_GETPEERNAME: usize = 205;
+pub const SYSCALL_ID_SENDTO: usize = 206;
+pub const SYSCALL_ID_RECVFROM: usize = 207;
+pub const SYSCALL_ID_SETSOCKOPT: usize = 208;
+pub const SYSCALL_ID_GETSOCKOPT: usize = 209;
 pub const SYSCALL_ID_SHUTDOWN: usize = 210;
+pub const SYSCALL_ID_SENDMSG: usize = 211;
+pub const SYSCALL_ID_RECVMSG: usize = 212;
 pub const SYSCALL_ID_BRK: usize = 214;
 pub const SYSCALL_ID_MUNMAP: usize = 215;
 pub const SYSCALL_ID_CLONE: usize = 220;
 pub const SYSCALL_ID_EXECVE: usize = 221;
 pub const SYSCALL_ID_MMAP: usize = 222;
 pub const SYSCALL_ID_MPROTECT: usize = 226;
+pub const SYSCALL_ID_ACCEPT4: usize = 242;
 pub const SYSCALL_ID_WAIT4: usize = 260;
 pub const SYSCALL_ID_PRLIMIT64: usize = 261;
 pub const SYSCALL_ID_RENAMEAT2: usize = 276;
 pub const SYSCALL_ID_GETRANDOM: usize = 278;
+pub const SYSCALL_ID_MEMFD_CREATE: usize = 279;
 pub const SYSCALL_ID_COPY_FILE_RANGE: usize = 285;
 pub const SYSCALL_ID_STATX: usize = 291;
 pub const SYSCALL_ID_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_ID_FACCESSAT2: usize = 439;
 
 // Unavaliable syscalls, use different values to prevent code lint issues
 pub const SYSCALL_ID_NEWFSTATAT: usize = usize::MAX; // Not avaliable for LA64, use sys_statx
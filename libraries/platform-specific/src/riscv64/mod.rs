@@ -35,6 +35,13 @@ pub const fn phys_to_virt(paddr: usize) -> usize {
     paddr | VIRT_ADDR_OFFSET
 }
 
+// IMPORTANT: Must provide for every platform
+/// Where the registers of a device at `paddr` are reached, the page table maps them like memory
+#[inline(always)]
+pub const fn device_phys_to_virt(paddr: usize) -> usize {
+    phys_to_virt(paddr)
+}
+
 /// # Safety
 /// May not support all instructions or not work correctly if the pc does not point to the start of an instruction
 /// Returns the size of the instruction at the given address
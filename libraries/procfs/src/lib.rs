@@ -0,0 +1,467 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The `/proc` filesystem, whose files are generated from the state of the kernel each time they
+//! are read.
+
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    FileSystemError, FileSystemResult, IFileSystem, IFileSystemType, IInode, MountEntry, MountFlags,
+};
+use task_abstractions::IProcess;
+use timing::TimeSpec;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod node;
+mod process;
+mod system;
+
+pub use node::ProcInode;
+
+use node::Entry;
+
+/// Where procfs takes what it shows from, implemented by the kernel.
+pub trait IProcSource: Send + Sync {
+    /// The process making the current system call, which `/proc/self` points to
+    fn current(&self) -> Option<Arc<dyn IProcess>>;
+
+    /// All processes that have not been reaped, in any order
+    fn processes(&self) -> Vec<Arc<dyn IProcess>>;
+
+    fn memory(&self) -> MemoryStatistics;
+
+    /// Time since the kernel started
+    fn uptime(&self) -> TimeSpec;
+
+    /// What `/proc/cpuinfo` shows, which is up to the platform
+    fn cpuinfo(&self) -> String;
+
+    /// The mounted filesystems in the order they were mounted, see
+    /// [`filesystem_abstractions::MountTable`]
+    fn mounts(&self) -> Vec<MountEntry>;
+}
+
+/// Memory in use and free, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryStatistics {
+    /// Memory that can be given to processes
+    pub total: usize,
+    pub free: usize,
+    /// Allocated from the kernel heap
+    pub kernel_heap: usize,
+}
+
+pub struct ProcFileSystem {
+    this: Weak<ProcFileSystem>,
+    source: Arc<dyn IProcSource>,
+}
+
+impl ProcFileSystem {
+    pub fn new(source: Arc<dyn IProcSource>) -> Arc<ProcFileSystem> {
+        Arc::new_cyclic(|this| ProcFileSystem {
+            this: this.clone(),
+            source,
+        })
+    }
+
+    fn process(&self, pid: u32) -> FileSystemResult<Arc<dyn IProcess>> {
+        self.source
+            .processes()
+            .into_iter()
+            .find(|process| process.pid() == pid)
+            .ok_or(FileSystemError::NotFound)
+    }
+}
+
+impl IFileSystem for ProcFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        ProcInode::new(self.this.upgrade().unwrap(), Entry::Root, "")
+    }
+
+    fn name(&self) -> &str {
+        "proc"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+}
+
+/// Lets `mount` create procfs instances showing the same source.
+pub struct ProcFileSystemType {
+    source: Arc<dyn IProcSource>,
+}
+
+impl ProcFileSystemType {
+    pub fn new(source: Arc<dyn IProcSource>) -> Arc<ProcFileSystemType> {
+        Arc::new(ProcFileSystemType { source })
+    }
+}
+
+impl IFileSystemType for ProcFileSystemType {
+    fn name(&self) -> &str {
+        "proc"
+    }
+
+    fn create(&self, _source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        Ok(ProcFileSystem::new(self.source.clone()))
+    }
+
+    fn is_cacheable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use abstractions::IUsizeAlias;
+    use address::{VirtualPageNum, VirtualPageNumRange};
+    use alloc::{
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+    use filesystem_abstractions::{
+        DirectoryEntryType, DirectoryTreeNode, FileDescriptorTable, FileStatistics, IFile,
+        OpenFlags,
+    };
+    use hermit_sync::SpinMutex;
+    use memory_space::{AreaType, MapType, MappingArea, MemorySpace};
+    use mmu_abstractions::GenericMappingFlags;
+    use task_abstractions::{status::TaskStatus, ProcessImage, UserTaskStatistics};
+    use test_utilities::{allocation::contiguous::TestFrameAllocator, task::TestProcess};
+
+    use super::*;
+
+    struct TestSource {
+        processes: SpinMutex<Vec<Arc<dyn IProcess>>>,
+        current: SpinMutex<Option<Arc<dyn IProcess>>>,
+    }
+
+    impl IProcSource for TestSource {
+        fn current(&self) -> Option<Arc<dyn IProcess>> {
+            self.current.lock().clone()
+        }
+
+        fn processes(&self) -> Vec<Arc<dyn IProcess>> {
+            self.processes.lock().clone()
+        }
+
+        fn memory(&self) -> MemoryStatistics {
+            MemoryStatistics {
+                total: 64 << 20,
+                free: 48 << 20,
+                kernel_heap: 3 << 20,
+            }
+        }
+
+        fn uptime(&self) -> TimeSpec {
+            TimeSpec::new(1234, 560_000_000)
+        }
+
+        fn cpuinfo(&self) -> String {
+            "processor\t: 0\n\n".to_string()
+        }
+
+        fn mounts(&self) -> Vec<MountEntry> {
+            vec![
+                MountEntry {
+                    source: "tmpfs".into(),
+                    target: "/".into(),
+                    fs_type: "tmpfs".into(),
+                    flags: MountFlags::empty(),
+                },
+                MountEntry {
+                    source: "/dev/vda".into(),
+                    target: "/mnt/my disk".into(),
+                    fs_type: "ext4".into(),
+                    flags: MountFlags::MS_RDONLY | MountFlags::MS_NOATIME,
+                },
+            ]
+        }
+    }
+
+    /// A file that is not in the tree, like a pipe.
+    struct AnonymousFile;
+
+    impl IFile for AnonymousFile {}
+
+    fn process(pid: u32, source: &TestSource) -> Arc<dyn IProcess> {
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu(1 << 20);
+
+        let mut memory_space = MemorySpace::new(mmu, alloc);
+        let area = |start: usize, pages: usize, area_type, permissions| {
+            MappingArea::new(
+                VirtualPageNumRange::from_start_count(VirtualPageNum::from_usize(start), pages),
+                area_type,
+                MapType::Framed,
+                permissions,
+                None,
+            )
+        };
+
+        let user = GenericMappingFlags::User | GenericMappingFlags::Readable;
+
+        memory_space.alloc_and_map_area(area(
+            0x10,
+            2,
+            AreaType::UserElf,
+            user | GenericMappingFlags::Executable,
+        ));
+        memory_space.alloc_and_map_area(area(
+            0x20,
+            4,
+            AreaType::UserStack,
+            user | GenericMappingFlags::Writable,
+        ));
+
+        let files = DirectoryTreeNode::from_empty(None, String::new());
+        let log = files
+            .mount_empty("var")
+            .unwrap()
+            .mount_empty("log")
+            .unwrap();
+
+        let mut fd_table = FileDescriptorTable::new();
+        fd_table.allocate_at(Arc::new(AnonymousFile), 0).unwrap();
+        fd_table
+            .allocate_at(log.open_as_file(OpenFlags::O_RDONLY, 0), 3)
+            .unwrap();
+
+        let mut process = TestProcess::new()
+            .with_pid(pid)
+            .with_pgid(pid)
+            .with_parent(source.processes().first().cloned())
+            .with_memory_space(Some(memory_space))
+            .with_fd_table(Some(fd_table))
+            .with_cwd("/home".to_string())
+            .with_image(ProcessImage {
+                executable: "/usr/bin/a-very-long-program-name".into(),
+                argv: vec!["prog".into(), "--flag".into()],
+                envp: vec!["HOME=/".into()],
+            });
+
+        process.configure_main_thread(|thread| {
+            *thread = core::mem::take(thread).with_stats(UserTaskStatistics {
+                syscalls: 7,
+                timer_interrupts: 2,
+                ..Default::default()
+            });
+        });
+
+        let (process, _) = process.build();
+        let process: Arc<dyn IProcess> = process;
+
+        source.processes.lock().push(process.clone());
+
+        process
+    }
+
+    fn setup() -> (Arc<TestSource>, Arc<DirectoryTreeNode>) {
+        let source = Arc::new(TestSource {
+            processes: SpinMutex::new(Vec::new()),
+            current: SpinMutex::new(None),
+        });
+
+        let root = DirectoryTreeNode::from_empty(None, String::new());
+        let proc = DirectoryTreeNode::from_filesystem(
+            Some(root.clone()),
+            ProcFileSystem::new(source.clone()),
+            Some("proc"),
+        );
+        root.mount_as(proc, Some("proc")).unwrap();
+
+        (source, root)
+    }
+
+    fn read(root: &Arc<DirectoryTreeNode>, path: &str) -> String {
+        let node = root.open(path, Some(root)).unwrap();
+
+        String::from_utf8(node.readall().unwrap()).unwrap()
+    }
+
+    fn link(root: &Arc<DirectoryTreeNode>, path: &str) -> Option<String> {
+        root.open_raw(path, Some(root)).unwrap().resolve_link()
+    }
+
+    fn names(root: &Arc<DirectoryTreeNode>, path: &str) -> Vec<String> {
+        let mut names: Vec<_> = root
+            .open(path, Some(root))
+            .unwrap()
+            .read_dir()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.filename)
+            .filter(|name| name != "." && name != "..")
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_directories() {
+        let (source, root) = setup();
+        process(1, &source);
+        process(42, &source);
+
+        assert_eq!(
+            names(&root, "/proc"),
+            ["1", "42", "cpuinfo", "meminfo", "mounts", "uptime"]
+        );
+        assert_eq!(
+            names(&root, "/proc/42"),
+            ["cmdline", "cwd", "environ", "exe", "fd", "maps", "stat", "status"]
+        );
+        assert_eq!(names(&root, "/proc/42/fd"), ["0", "3"]);
+
+        assert_eq!(
+            root.open("/proc/7", Some(&root)).err(),
+            Some(FileSystemError::NotFound)
+        );
+        assert_eq!(
+            root.open("/proc/42/nothing", Some(&root)).err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        let entry = root.open_raw("/proc/42/exe", Some(&root)).unwrap();
+        assert_eq!(entry.metadata().entry_type, DirectoryEntryType::Symlink);
+
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        root.open("/proc/42", Some(&root))
+            .unwrap()
+            .stat(&mut stat)
+            .unwrap();
+        assert_eq!(stat.mode.bits(), 0o040555);
+    }
+
+    #[test]
+    fn test_self() {
+        let (source, root) = setup();
+        process(1, &source);
+        let current = process(42, &source);
+
+        assert!(root.open("/proc/self", Some(&root)).is_err());
+
+        *source.current.lock() = Some(current);
+
+        assert_eq!(link(&root, "/proc/self"), Some("42".into()));
+        assert_eq!(read(&root, "/proc/self/cmdline"), "prog\0--flag\0");
+    }
+
+    #[test]
+    fn test_process_files() {
+        let (source, root) = setup();
+        process(1, &source);
+        process(42, &source);
+
+        assert_eq!(read(&root, "/proc/42/cmdline"), "prog\0--flag\0");
+        assert_eq!(read(&root, "/proc/42/environ"), "HOME=/\0");
+        assert_eq!(
+            link(&root, "/proc/42/exe"),
+            Some("/usr/bin/a-very-long-program-name".into())
+        );
+        assert_eq!(link(&root, "/proc/42/cwd"), Some("/home".into()));
+        assert_eq!(
+            link(&root, "/proc/42/fd/0"),
+            Some("anon_inode:[file]".into())
+        );
+        assert_eq!(link(&root, "/proc/42/fd/3"), Some("/var/log".into()));
+
+        assert_eq!(
+            read(&root, "/proc/42/maps"),
+            "00010000-00012000 r-xp 00000000 00:00 0          /usr/bin/a-very-long-program-name\n\
+             00020000-00024000 rw-p 00000000 00:00 0          [stack]\n"
+        );
+
+        let status = read(&root, "/proc/42/status");
+        assert!(status.starts_with("Name:\ta-very-long-pro\nState:\tR (running)\n"));
+        assert!(status.contains("\nPid:\t42\nPPid:\t1\n"));
+        assert!(status.contains("\nVmSize:\t      24 kB\n"));
+        assert!(status.contains("\nVmStk:\t      16 kB\n"));
+        assert!(status.contains("\nThreads:\t1\n"));
+        assert!(status.contains("\nvoluntary_ctxt_switches:\t7\n"));
+        assert!(status.contains("\nnonvoluntary_ctxt_switches:\t2\n"));
+
+        let stat = read(&root, "/proc/42/stat");
+        let fields: Vec<_> = stat.trim_end().split(' ').collect();
+        assert_eq!(fields.len(), 52);
+        assert_eq!(fields[..5], ["42", "(a-very-long-pro)", "R", "1", "42"]);
+        assert_eq!(fields[19], "1");
+        assert_eq!(fields[22], (24 * 1024).to_string());
+        assert_eq!(fields[23], "6");
+    }
+
+    #[test]
+    fn test_exited_process() {
+        let (source, root) = setup();
+        let process = process(1, &source);
+
+        process.threads()[0].update_status(TaskStatus::Exited);
+
+        assert!(read(&root, "/proc/1/status").contains("State:\tZ (zombie)\n"));
+        assert_eq!(read(&root, "/proc/1/stat").split(' ').nth(2), Some("Z"));
+
+        // Opened nodes of a process that went away no longer read
+        let status = root.open("/proc/1/status", Some(&root)).unwrap();
+        source.processes.lock().clear();
+
+        assert_eq!(
+            status.readat(0, &mut [0; 16]),
+            Err(FileSystemError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_system_files() {
+        let (_, root) = setup();
+
+        assert_eq!(
+            read(&root, "/proc/meminfo"),
+            "MemTotal:          65536 kB\n\
+             MemFree:           49152 kB\n\
+             MemAvailable:      49152 kB\n\
+             Slab:               3072 kB\n"
+        );
+        assert_eq!(read(&root, "/proc/uptime"), "1234.56 0.00\n");
+        assert_eq!(read(&root, "/proc/cpuinfo"), "processor\t: 0\n\n");
+        assert_eq!(
+            read(&root, "/proc/mounts"),
+            "tmpfs / tmpfs rw 0 0\n/dev/vda /mnt/my\\040disk ext4 ro,noatime 0 0\n"
+        );
+
+        let uptime = root.open("/proc/uptime", Some(&root)).unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(uptime.readat(5, &mut buffer), Ok(4));
+        assert_eq!(&buffer, b"56 0");
+        assert_eq!(uptime.readat(100, &mut buffer), Ok(0));
+    }
+
+    #[test]
+    fn test_read_only() {
+        let (source, root) = setup();
+        process(1, &source);
+
+        let status = root.open("/proc/1/status", Some(&root)).unwrap();
+        assert_eq!(status.writeat(0, b"x"), Err(FileSystemError::NotPermitted));
+
+        let proc = root.open("/proc", Some(&root)).unwrap();
+        assert_eq!(
+            proc.inode().unwrap().touch("file").err(),
+            Some(FileSystemError::NotPermitted)
+        );
+        assert_eq!(
+            proc.inode().unwrap().remove("1").err(),
+            Some(FileSystemError::NotPermitted)
+        );
+    }
+}
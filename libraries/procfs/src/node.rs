@@ -0,0 +1,273 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IInode, InodeMetadata,
+};
+use timing::TimeSpec;
+
+use crate::{
+    process::{self, ProcessFile},
+    system::{self, SystemFile},
+    ProcFileSystem,
+};
+
+/// What an inode of procfs stands for. Processes are only referred to by their ids, so inodes of
+/// a process that went away fail with `NotFound`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Entry {
+    Root,
+    /// `/proc/self`, a link to the directory of the calling process
+    SelfLink,
+    System(SystemFile),
+    Process(u32),
+    ProcessFile(u32, ProcessFile),
+    /// `/proc/<pid>/fd`
+    Descriptors(u32),
+    Descriptor(u32, usize),
+}
+
+impl Entry {
+    fn entry_type(self) -> DirectoryEntryType {
+        match self {
+            Entry::Root | Entry::Process(_) | Entry::Descriptors(_) => {
+                DirectoryEntryType::Directory
+            }
+            Entry::SelfLink | Entry::Descriptor(..) => DirectoryEntryType::Symlink,
+            Entry::ProcessFile(_, file) if file.is_link() => DirectoryEntryType::Symlink,
+            Entry::System(_) | Entry::ProcessFile(..) => DirectoryEntryType::File,
+        }
+    }
+
+    /// Numbers that stay the same for the same entry. Those of a process are above 2^32, with its
+    /// id in the upper half.
+    fn number(self) -> u64 {
+        let process = |pid: u32| (pid as u64 + 1) << 32;
+
+        match self {
+            Entry::Root => 1,
+            Entry::SelfLink => 2,
+            Entry::System(file) => 3 + file as u64,
+            Entry::Process(pid) => process(pid),
+            Entry::ProcessFile(pid, file) => process(pid) + 1 + file as u64,
+            Entry::Descriptors(pid) => process(pid) + 0x100,
+            Entry::Descriptor(pid, fd) => process(pid) + 0x1000 + fd as u64,
+        }
+    }
+}
+
+pub struct ProcInode {
+    fs: Arc<ProcFileSystem>,
+    entry: Entry,
+    name: String,
+}
+
+impl ProcInode {
+    pub(crate) fn new(fs: Arc<ProcFileSystem>, entry: Entry, name: &str) -> Arc<ProcInode> {
+        Arc::new(ProcInode {
+            fs,
+            entry,
+            name: name.to_string(),
+        })
+    }
+
+    /// The contents of a file, generated again for every call.
+    fn contents(&self) -> FileSystemResult<Vec<u8>> {
+        match self.entry {
+            Entry::System(file) => Ok(system::generate(file, self.fs.source.as_ref())),
+            Entry::ProcessFile(pid, file) if !file.is_link() => {
+                Ok(process::generate(file, &self.fs.process(pid)?))
+            }
+            _ => Err(FileSystemError::NotAFile),
+        }
+    }
+
+    fn target(&self) -> FileSystemResult<String> {
+        match self.entry {
+            Entry::SelfLink => match self.fs.source.current() {
+                Some(process) => Ok(process.pid().to_string()),
+                None => Err(FileSystemError::NotFound),
+            },
+            Entry::ProcessFile(pid, file) => process::link(file, &self.fs.process(pid)?),
+            Entry::Descriptor(pid, fd) => process::descriptor(&self.fs.process(pid)?, fd),
+            _ => Err(FileSystemError::NotALink),
+        }
+    }
+
+    fn entries(&self) -> FileSystemResult<Vec<(String, Entry)>> {
+        let entries = match self.entry {
+            Entry::Root => {
+                let mut processes = self.fs.source.processes();
+                processes.sort_by_key(|process| process.pid());
+
+                let processes = processes
+                    .iter()
+                    .map(|process| (process.pid().to_string(), Entry::Process(process.pid())));
+
+                let system = SystemFile::ALL
+                    .iter()
+                    .map(|&file| (file.name().to_string(), Entry::System(file)));
+
+                // There is no calling process while the kernel itself looks
+                let this = self
+                    .fs
+                    .source
+                    .current()
+                    .map(|_| (String::from("self"), Entry::SelfLink));
+
+                processes.chain(this).chain(system).collect()
+            }
+            Entry::Process(pid) => {
+                // Fails if the process went away
+                self.fs.process(pid)?;
+
+                ProcessFile::ALL
+                    .iter()
+                    .map(|&file| (file.name().to_string(), Entry::ProcessFile(pid, file)))
+                    .chain([(String::from("fd"), Entry::Descriptors(pid))])
+                    .collect()
+            }
+            Entry::Descriptors(pid) => self
+                .fs
+                .process(pid)?
+                .fd_table()
+                .lock()
+                .iter()
+                .map(|(fd, _)| (fd.to_string(), Entry::Descriptor(pid, fd)))
+                .collect(),
+            _ => return Err(FileSystemError::NotADirectory),
+        };
+
+        Ok(entries)
+    }
+
+    fn size(&self) -> usize {
+        match self.entry.entry_type() {
+            DirectoryEntryType::File => self.contents().map_or(0, |contents| contents.len()),
+            DirectoryEntryType::Symlink => self.target().map_or(0, |target| target.len()),
+            _ => 0,
+        }
+    }
+}
+
+impl IInode for ProcInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry.entry_type(),
+            size: self.size(),
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let contents = self.contents()?;
+
+        let rest = contents.get(offset..).unwrap_or_default();
+        let len = rest.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&rest[..len]);
+
+        Ok(len)
+    }
+
+    fn writeat(&self, _offset: usize, _buffer: &[u8]) -> FileSystemResult<usize> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn mkdir(&self, _name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn rmdir(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn remove(&self, _name: &str) -> FileSystemResult<()> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn touch(&self, _name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Err(FileSystemError::NotPermitted)
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .map(|(filename, entry)| DirectoryEntry {
+                filename,
+                entry_type: entry.entry_type(),
+            })
+            .collect())
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let entry = match (self.entry, name) {
+            (Entry::Root, "self") => Entry::SelfLink,
+            (Entry::Root, name) => match SystemFile::from_name(name) {
+                Some(file) => Entry::System(file),
+                None => Entry::Process(name.parse().map_err(|_| FileSystemError::NotFound)?),
+            },
+            (Entry::Process(pid), "fd") => Entry::Descriptors(pid),
+            (Entry::Process(pid), name) => Entry::ProcessFile(
+                pid,
+                ProcessFile::from_name(name).ok_or(FileSystemError::NotFound)?,
+            ),
+            (Entry::Descriptors(pid), name) => {
+                Entry::Descriptor(pid, name.parse().map_err(|_| FileSystemError::NotFound)?)
+            }
+            _ => return Err(FileSystemError::NotADirectory),
+        };
+
+        // Only what is listed can be looked up, a number is not always a process
+        let exists = self.entries()?.iter().any(|(_, listed)| *listed == entry);
+
+        match exists {
+            true => Ok(ProcInode::new(self.fs.clone(), entry, name)),
+            false => Err(FileSystemError::NotFound),
+        }
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let (permissions, link_count) = match self.entry {
+            Entry::Descriptors(_) => (0o500, 2),
+            _ if self.entry.entry_type() == DirectoryEntryType::Directory => (0o555, 2),
+            _ if self.entry.entry_type() == DirectoryEntryType::Symlink => (0o777, 1),
+            _ => (0o444, 1),
+        };
+
+        stat.device_id = 0;
+        stat.inode_id = self.entry.number();
+        stat.mode = FileStatisticsMode::from_bits_retain(
+            FileStatisticsMode::from(self.entry.entry_type()).bits() | permissions,
+        );
+        stat.link_count = link_count;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.rdev = 0;
+        // Like Linux, files have no size as their contents are made when they are read
+        stat.size = 0;
+        stat.block_size = 1024;
+        stat.block_count = 0;
+        stat.atime = TimeSpec::zero();
+        stat.mtime = TimeSpec::zero();
+        stat.ctime = TimeSpec::zero();
+
+        Ok(())
+    }
+
+    fn resolve_link(&self) -> Option<String> {
+        self.target().ok()
+    }
+}
@@ -0,0 +1,321 @@
+use abstractions::IUsizeAlias;
+use address::IPageNum;
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use constants::PAGE_SIZE;
+use core::fmt::Write;
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use memory_space::AreaType;
+use mmu_abstractions::GenericMappingFlags;
+use task_abstractions::{status::TaskStatus, IProcess, UserTaskStatistics};
+
+/// Longest name Linux keeps of the executable of a process, without the terminating NUL.
+const COMMAND_NAME_MAX: usize = 15;
+
+/// The files in the directory of a process, besides `fd`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProcessFile {
+    Maps,
+    Status,
+    Stat,
+    Cmdline,
+    Environ,
+    Exe,
+    Cwd,
+}
+
+impl ProcessFile {
+    pub const ALL: [ProcessFile; 7] = [
+        ProcessFile::Maps,
+        ProcessFile::Status,
+        ProcessFile::Stat,
+        ProcessFile::Cmdline,
+        ProcessFile::Environ,
+        ProcessFile::Exe,
+        ProcessFile::Cwd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProcessFile::Maps => "maps",
+            ProcessFile::Status => "status",
+            ProcessFile::Stat => "stat",
+            ProcessFile::Cmdline => "cmdline",
+            ProcessFile::Environ => "environ",
+            ProcessFile::Exe => "exe",
+            ProcessFile::Cwd => "cwd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ProcessFile> {
+        Self::ALL.into_iter().find(|file| file.name() == name)
+    }
+
+    pub fn is_link(self) -> bool {
+        matches!(self, ProcessFile::Exe | ProcessFile::Cwd)
+    }
+}
+
+pub(crate) fn generate(file: ProcessFile, process: &Arc<dyn IProcess>) -> Vec<u8> {
+    match file {
+        ProcessFile::Maps => maps(process).into_bytes(),
+        ProcessFile::Status => status(process).into_bytes(),
+        ProcessFile::Stat => stat(process).into_bytes(),
+        ProcessFile::Cmdline => null_terminated(&process.image().argv),
+        ProcessFile::Environ => null_terminated(&process.image().envp),
+        ProcessFile::Exe | ProcessFile::Cwd => unreachable!("{:?} is a link", file),
+    }
+}
+
+pub(crate) fn link(file: ProcessFile, process: &Arc<dyn IProcess>) -> FileSystemResult<String> {
+    match file {
+        ProcessFile::Exe => Ok(process.image().executable),
+        ProcessFile::Cwd => match process.working_directory() {
+            cwd if cwd.is_empty() => Ok(String::from("/")),
+            cwd => Ok(cwd),
+        },
+        _ => Err(FileSystemError::NotALink),
+    }
+}
+
+/// Where the file descriptor `fd` points to, its path if it is in the tree.
+pub(crate) fn descriptor(process: &Arc<dyn IProcess>, fd: usize) -> FileSystemResult<String> {
+    let file = process
+        .fd_table()
+        .lock()
+        .get(fd)
+        .cloned()
+        .ok_or(FileSystemError::NotFound)?;
+
+    match file.inode() {
+        Some(node) => Ok(node.fullpath()),
+        None => Ok(String::from("anon_inode:[file]")),
+    }
+}
+
+fn null_terminated(strings: &[String]) -> Vec<u8> {
+    strings.iter().flat_map(|s| s.bytes().chain([0])).collect()
+}
+
+/// The name of the executable, cut like Linux does.
+fn command_name(process: &Arc<dyn IProcess>) -> String {
+    let executable = process.image().executable;
+    let name = executable.rsplit('/').next().unwrap_or_default();
+
+    let mut end = name.len().min(COMMAND_NAME_MAX);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    name[..end].to_string()
+}
+
+/// The state letter and its description, a process whose threads all exited is a zombie.
+fn state(process: &Arc<dyn IProcess>) -> (char, &'static str) {
+    let exited = process
+        .threads()
+        .iter()
+        .all(|thread| thread.status() == TaskStatus::Exited);
+
+    match exited {
+        true => ('Z', "zombie"),
+        false => ('R', "running"),
+    }
+}
+
+fn parent_pid(process: &Arc<dyn IProcess>) -> u32 {
+    process.parent().map_or(0, |parent| parent.pid())
+}
+
+fn user_mappings(
+    process: &Arc<dyn IProcess>,
+) -> Vec<(AreaType, usize, usize, GenericMappingFlags, usize)> {
+    process
+        .memory_space()
+        .lock()
+        .mappings()
+        .iter()
+        .filter(|area| area.area_type != AreaType::Kernel)
+        .map(|area| {
+            let range = area.range();
+
+            (
+                area.area_type,
+                range.start().start_addr().as_usize(),
+                range.end().start_addr().as_usize(),
+                area.permissions(),
+                area.allocation.as_ref().map_or(0, |a| a.pages.len()),
+            )
+        })
+        .collect()
+}
+
+fn maps(process: &Arc<dyn IProcess>) -> String {
+    let executable = process.image().executable;
+    let mut maps = String::new();
+
+    for (area_type, start, end, permissions, _) in user_mappings(process) {
+        let flag = |flag, c| match permissions.contains(flag) {
+            true => c,
+            false => '-',
+        };
+
+        let name = match area_type {
+            AreaType::UserElf => executable.as_str(),
+            AreaType::UserStack => "[stack]",
+            AreaType::UserBrk => "[heap]",
+            AreaType::SignalTrampoline => "[vdso]",
+            _ => "",
+        };
+
+        let line = format!(
+            "{:08x}-{:08x} {}{}{}p 00000000 00:00 0",
+            start,
+            end,
+            flag(GenericMappingFlags::Readable, 'r'),
+            flag(GenericMappingFlags::Writable, 'w'),
+            flag(GenericMappingFlags::Executable, 'x'),
+        );
+
+        // Names start at the same column, like Linux pads them
+        match name {
+            "" => writeln!(maps, "{}", line),
+            name => writeln!(maps, "{:<48} {}", line, name),
+        }
+        .unwrap();
+    }
+
+    maps
+}
+
+/// Sizes in KiB of all mappings, the resident ones, data, stack and executable.
+struct MemoryUsage {
+    size: usize,
+    resident: usize,
+    data: usize,
+    stack: usize,
+    executable: usize,
+}
+
+fn memory_usage(process: &Arc<dyn IProcess>) -> MemoryUsage {
+    let mut usage = MemoryUsage {
+        size: 0,
+        resident: 0,
+        data: 0,
+        stack: 0,
+        executable: 0,
+    };
+
+    for (area_type, start, end, _, frames) in user_mappings(process) {
+        let size = (end - start) / 1024;
+
+        usage.size += size;
+        usage.resident += frames * PAGE_SIZE / 1024;
+
+        match area_type {
+            AreaType::UserBrk | AreaType::VMA => usage.data += size,
+            AreaType::UserStack => usage.stack += size,
+            AreaType::UserElf => usage.executable += size,
+            _ => (),
+        }
+    }
+
+    usage
+}
+
+fn statistics(process: &Arc<dyn IProcess>) -> UserTaskStatistics {
+    process.threads().iter().map(|thread| thread.stats()).fold(
+        UserTaskStatistics::default(),
+        |sum, stats| UserTaskStatistics {
+            external_interrupts: sum.external_interrupts + stats.external_interrupts,
+            timer_interrupts: sum.timer_interrupts + stats.timer_interrupts,
+            software_interrupts: sum.software_interrupts + stats.software_interrupts,
+            exceptions: sum.exceptions + stats.exceptions,
+            syscalls: sum.syscalls + stats.syscalls,
+        },
+    )
+}
+
+fn status(process: &Arc<dyn IProcess>) -> String {
+    let (state, description) = state(process);
+    let memory = memory_usage(process);
+    let statistics = statistics(process);
+
+    // Entering the kernel by a system call is a voluntary switch, an interrupt is not
+    let involuntary = statistics.external_interrupts
+        + statistics.timer_interrupts
+        + statistics.software_interrupts
+        + statistics.exceptions;
+
+    format!(
+        "Name:\t{}\n\
+         State:\t{} ({})\n\
+         Tgid:\t{pid}\n\
+         Pid:\t{pid}\n\
+         PPid:\t{}\n\
+         Uid:\t0\t0\t0\t0\n\
+         Gid:\t0\t0\t0\t0\n\
+         VmSize:\t{:>8} kB\n\
+         VmRSS:\t{:>8} kB\n\
+         VmData:\t{:>8} kB\n\
+         VmStk:\t{:>8} kB\n\
+         VmExe:\t{:>8} kB\n\
+         Threads:\t{}\n\
+         voluntary_ctxt_switches:\t{}\n\
+         nonvoluntary_ctxt_switches:\t{}\n",
+        command_name(process),
+        state,
+        description,
+        parent_pid(process),
+        memory.size,
+        memory.resident,
+        memory.data,
+        memory.stack,
+        memory.executable,
+        process.threads().len(),
+        statistics.syscalls,
+        involuntary,
+        pid = process.pid(),
+    )
+}
+
+fn stat(process: &Arc<dyn IProcess>) -> String {
+    let (state, _) = state(process);
+    let memory = memory_usage(process);
+
+    let mut fields = [0u64; 52 - 5];
+
+    // Numbered like proc(5), starting at the 6th field, what is not tracked stays zero
+    let mut set = |number: usize, value: u64| fields[number - 6] = value;
+    set(6, process.pgid() as u64); // session
+    set(8, u64::MAX); // tpgid, -1 without a terminal
+    set(18, 20); // priority
+    set(20, process.threads().len() as u64);
+    set(23, memory.size as u64 * 1024);
+    set(24, (memory.resident * 1024 / PAGE_SIZE) as u64);
+    set(25, u64::MAX); // rsslim
+    set(38, 17); // exit_signal, SIGCHLD
+
+    let mut stat = format!(
+        "{} ({}) {} {} {}",
+        process.pid(),
+        command_name(process),
+        state,
+        parent_pid(process),
+        process.pgid()
+    );
+
+    for (index, value) in fields.iter().enumerate() {
+        match index + 6 {
+            8 => stat.push_str(" -1"),
+            _ => write!(stat, " {}", value).unwrap(),
+        }
+    }
+
+    stat.push('\n');
+    stat
+}
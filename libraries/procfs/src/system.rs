@@ -0,0 +1,104 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::IProcSource;
+
+/// The files directly in `/proc` that are not about a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SystemFile {
+    Meminfo,
+    Uptime,
+    Cpuinfo,
+    Mounts,
+}
+
+impl SystemFile {
+    pub const ALL: [SystemFile; 4] = [
+        SystemFile::Cpuinfo,
+        SystemFile::Meminfo,
+        SystemFile::Mounts,
+        SystemFile::Uptime,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SystemFile::Meminfo => "meminfo",
+            SystemFile::Uptime => "uptime",
+            SystemFile::Cpuinfo => "cpuinfo",
+            SystemFile::Mounts => "mounts",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SystemFile> {
+        Self::ALL.into_iter().find(|file| file.name() == name)
+    }
+}
+
+pub(crate) fn generate(file: SystemFile, source: &dyn IProcSource) -> Vec<u8> {
+    match file {
+        SystemFile::Meminfo => meminfo(source).into_bytes(),
+        SystemFile::Uptime => uptime(source).into_bytes(),
+        SystemFile::Cpuinfo => source.cpuinfo().into_bytes(),
+        SystemFile::Mounts => mounts(source).into_bytes(),
+    }
+}
+
+fn meminfo(source: &dyn IProcSource) -> String {
+    let memory = source.memory();
+    let mut meminfo = String::new();
+
+    // Nothing is reclaimable, so all free memory is available
+    for (name, bytes) in [
+        ("MemTotal:", memory.total),
+        ("MemFree:", memory.free),
+        ("MemAvailable:", memory.free),
+        ("Slab:", memory.kernel_heap),
+    ] {
+        writeln!(meminfo, "{:<15}{:>9} kB", name, bytes / 1024).unwrap();
+    }
+
+    meminfo
+}
+
+fn uptime(source: &dyn IProcSource) -> String {
+    let uptime = source.uptime();
+
+    // The second number is the idle time, which is not tracked
+    format!(
+        "{}.{:02} 0.00\n",
+        uptime.tv_sec,
+        uptime.tv_nsec / 10_000_000
+    )
+}
+
+fn mounts(source: &dyn IProcSource) -> String {
+    let mut mounts = String::new();
+
+    for mount in source.mounts() {
+        writeln!(
+            mounts,
+            "{} {} {} {} 0 0",
+            escape(&mount.source),
+            escape(&mount.target),
+            mount.fs_type,
+            mount.options()
+        )
+        .unwrap();
+    }
+
+    mounts
+}
+
+/// Escapes what would break the fields of a line, as octal like Linux does.
+fn escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+
+    for c in path.chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => write!(escaped, "\\{:03o}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
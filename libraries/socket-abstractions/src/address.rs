@@ -0,0 +1,196 @@
+use alloc::{string::String, vec::Vec};
+use constants::ErrNo;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::{AF_INET, AF_UNIX, AF_UNSPEC};
+
+/// Maximum length of `sun_path` in `struct sockaddr_un`
+pub const UNIX_PATH_MAX: usize = 108;
+
+const FAMILY_SIZE: usize = core::mem::size_of::<u16>();
+
+/// Size of `struct sockaddr_in`, including the trailing padding
+const SOCKADDR_IN_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnixAddress {
+    /// A socket that was never bound, e.g. one end of a `socketpair`
+    Unnamed,
+    /// A socket bound to a path in the filesystem
+    Pathname(String),
+    /// A socket bound in the abstract namespace, the name does not include the leading NUL
+    Abstract(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddress {
+    Unspecified,
+    Unix(UnixAddress),
+    Inet(SocketAddrV4),
+}
+
+impl SocketAddress {
+    pub fn family(&self) -> u16 {
+        match self {
+            SocketAddress::Unspecified => AF_UNSPEC,
+            SocketAddress::Unix(_) => AF_UNIX,
+            SocketAddress::Inet(_) => AF_INET,
+        }
+    }
+
+    /// Parses the raw `struct sockaddr` passed by user space.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SocketAddress, ErrNo> {
+        if bytes.len() < FAMILY_SIZE {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let family = u16::from_ne_bytes([bytes[0], bytes[1]]);
+        let payload = &bytes[FAMILY_SIZE..];
+
+        match family {
+            AF_UNSPEC => Ok(SocketAddress::Unspecified),
+            AF_UNIX => Self::parse_unix(payload).map(SocketAddress::Unix),
+            AF_INET => Self::parse_inet(bytes).map(SocketAddress::Inet),
+            _ => Err(ErrNo::AddressFamilyNotSupportedByProtocol),
+        }
+    }
+
+    fn parse_unix(path: &[u8]) -> Result<UnixAddress, ErrNo> {
+        if path.len() > UNIX_PATH_MAX {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        match path.first() {
+            None => Ok(UnixAddress::Unnamed),
+            Some(0) => Ok(UnixAddress::Abstract(path[1..].to_vec())),
+            Some(_) => {
+                let len = path.iter().position(|b| *b == 0).unwrap_or(path.len());
+
+                core::str::from_utf8(&path[..len])
+                    .map(|p| UnixAddress::Pathname(String::from(p)))
+                    .map_err(|_| ErrNo::InvalidArgument)
+            }
+        }
+    }
+
+    fn parse_inet(bytes: &[u8]) -> Result<SocketAddrV4, ErrNo> {
+        if bytes.len() < SOCKADDR_IN_SIZE {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        // sin_port and sin_addr are in network byte order
+        let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+        let address = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+
+        Ok(SocketAddrV4::new(address, port))
+    }
+
+    /// Serializes the address as a `struct sockaddr` of the matching family.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(self.family().to_ne_bytes());
+
+        match self {
+            SocketAddress::Unspecified => (),
+            SocketAddress::Unix(UnixAddress::Unnamed) => (),
+            SocketAddress::Unix(UnixAddress::Pathname(path)) => {
+                bytes.extend_from_slice(path.as_bytes());
+                bytes.push(0);
+            }
+            SocketAddress::Unix(UnixAddress::Abstract(name)) => {
+                bytes.push(0);
+                bytes.extend_from_slice(name);
+            }
+            SocketAddress::Inet(address) => {
+                bytes.extend_from_slice(&address.port().to_be_bytes());
+                bytes.extend_from_slice(&address.ip().octets());
+                bytes.resize(SOCKADDR_IN_SIZE, 0);
+            }
+        }
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_unix(path: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::from(AF_UNIX.to_ne_bytes());
+        bytes.extend_from_slice(path);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_pathname() {
+        let address = SocketAddress::from_bytes(&raw_unix(b"/tmp/socket\0garbage")).unwrap();
+
+        assert_eq!(
+            address,
+            SocketAddress::Unix(UnixAddress::Pathname(String::from("/tmp/socket")))
+        );
+    }
+
+    #[test]
+    fn test_parse_abstract() {
+        let address = SocketAddress::from_bytes(&raw_unix(b"\0name")).unwrap();
+
+        assert_eq!(
+            address,
+            SocketAddress::Unix(UnixAddress::Abstract(Vec::from(b"name")))
+        );
+    }
+
+    #[test]
+    fn test_parse_unnamed() {
+        let address = SocketAddress::from_bytes(&raw_unix(b"")).unwrap();
+
+        assert_eq!(address, SocketAddress::Unix(UnixAddress::Unnamed));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(SocketAddress::from_bytes(&[1]), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            SocketAddress::from_bytes(&raw_unix(&[b'a'; UNIX_PATH_MAX + 1])),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            SocketAddress::from_bytes(&42u16.to_ne_bytes()),
+            Err(ErrNo::AddressFamilyNotSupportedByProtocol)
+        );
+    }
+
+    #[test]
+    fn test_parse_inet() {
+        let mut bytes = Vec::from(AF_INET.to_ne_bytes());
+        bytes.extend_from_slice(&[0x1f, 0x90, 127, 0, 0, 1]);
+        bytes.resize(SOCKADDR_IN_SIZE, 0);
+
+        assert_eq!(
+            SocketAddress::from_bytes(&bytes),
+            Ok(SocketAddress::Inet(SocketAddrV4::new(
+                Ipv4Addr::LOCALHOST,
+                8080
+            )))
+        );
+        assert_eq!(
+            SocketAddress::from_bytes(&bytes[..8]),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let addresses = [
+            SocketAddress::Unix(UnixAddress::Unnamed),
+            SocketAddress::Unix(UnixAddress::Pathname(String::from("a/b"))),
+            SocketAddress::Unix(UnixAddress::Abstract(Vec::from(b"\0x"))),
+            SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 15), 80)),
+        ];
+
+        for address in addresses {
+            assert_eq!(SocketAddress::from_bytes(&address.to_bytes()), Ok(address));
+        }
+    }
+}
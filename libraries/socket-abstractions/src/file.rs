@@ -0,0 +1,78 @@
+use alloc::{sync::Arc, vec::Vec};
+use filesystem_abstractions::{IFile, OpenFlags};
+use hermit_sync::SpinMutex;
+
+use crate::{ISocket, MessageFlags, PollEvents};
+
+/// Wraps a socket so it can live in a `FileDescriptorTable`.
+pub struct SocketFile {
+    socket: Arc<dyn ISocket>,
+    flags: SpinMutex<OpenFlags>,
+}
+
+impl SocketFile {
+    pub fn new(socket: Arc<dyn ISocket>, flags: OpenFlags) -> Arc<SocketFile> {
+        Arc::new(SocketFile {
+            socket,
+            flags: SpinMutex::new(flags | OpenFlags::O_RDWR),
+        })
+    }
+
+    pub fn socket(&self) -> Arc<dyn ISocket> {
+        self.socket.clone()
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::O_NONBLOCK)
+    }
+
+    /// Gets the socket behind a file descriptor, if the file is a socket at all.
+    pub fn socket_of(file: &Arc<dyn IFile>) -> Option<Arc<dyn ISocket>> {
+        file.downcast_ref::<SocketFile>().map(|f| f.socket())
+    }
+}
+
+impl IFile for SocketFile {
+    fn can_read(&self) -> bool {
+        true
+    }
+
+    fn can_write(&self) -> bool {
+        true
+    }
+
+    fn read_avaliable(&self) -> bool {
+        self.socket
+            .poll()
+            .intersects(PollEvents::POLLIN | PollEvents::POLLHUP | PollEvents::POLLERR)
+    }
+
+    fn write_avaliable(&self) -> bool {
+        self.socket
+            .poll()
+            .intersects(PollEvents::POLLOUT | PollEvents::POLLHUP | PollEvents::POLLERR)
+    }
+
+    fn flags(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+
+    fn set_flags(&self, new_flags: OpenFlags) -> bool {
+        *self.flags.lock() = new_flags;
+        true
+    }
+
+    fn is_dir(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> usize {
+        self.socket
+            .receive(buf, MessageFlags::empty())
+            .map_or(0, |message| message.length)
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        self.socket.send(buf, Vec::new(), None).unwrap_or(0)
+    }
+}
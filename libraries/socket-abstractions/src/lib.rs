@@ -0,0 +1,182 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{sync::Arc, vec::Vec};
+use bitflags::bitflags;
+use constants::ErrNo;
+use downcast_rs::{impl_downcast, Downcast, DowncastSend};
+use filesystem_abstractions::IFile;
+use threading::sync::WaitQueue;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod address;
+mod file;
+
+pub use address::*;
+pub use file::*;
+
+pub const AF_UNSPEC: u16 = 0;
+pub const AF_UNIX: u16 = 1;
+pub const AF_INET: u16 = 2;
+
+pub const IPPROTO_IP: usize = 0;
+pub const IPPROTO_TCP: usize = 6;
+pub const IPPROTO_UDP: usize = 17;
+
+pub const SOL_SOCKET: usize = 1;
+
+pub const SO_REUSEADDR: usize = 2;
+pub const SO_TYPE: usize = 3;
+pub const SO_ERROR: usize = 4;
+pub const SO_SNDBUF: usize = 7;
+pub const SO_RCVBUF: usize = 8;
+pub const SO_KEEPALIVE: usize = 9;
+pub const SO_PASSCRED: usize = 16;
+pub const SO_ACCEPTCONN: usize = 30;
+pub const SO_DOMAIN: usize = 39;
+
+pub const TCP_NODELAY: usize = 1;
+
+pub const SCM_RIGHTS: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Stream = 1,
+    Datagram = 2,
+}
+
+impl SocketType {
+    /// The bits of the `type` argument of `socket(2)` holding the socket type
+    pub const MASK: usize = 0xf;
+
+    pub fn from_raw(raw: usize) -> Option<SocketType> {
+        match raw & Self::MASK {
+            1 => Some(SocketType::Stream),
+            2 => Some(SocketType::Datagram),
+            _ => None,
+        }
+    }
+}
+
+bitflags! {
+    /// Flags that can be or-ed into the `type` argument of `socket(2)` and `socketpair(2)`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SocketFlags: usize {
+        const SOCK_NONBLOCK = 0o4000;
+        const SOCK_CLOEXEC  = 0o2000000;
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MessageFlags: usize {
+        const MSG_OOB          = 0x1;
+        const MSG_PEEK         = 0x2;
+        const MSG_CTRUNC       = 0x8;
+        const MSG_TRUNC        = 0x20;
+        const MSG_DONTWAIT     = 0x40;
+        const MSG_EOR          = 0x80;
+        const MSG_WAITALL      = 0x100;
+        const MSG_NOSIGNAL     = 0x4000;
+        const MSG_CMSG_CLOEXEC = 0x40000000;
+    }
+}
+
+bitflags! {
+    /// Readiness of a socket, with the same layout as `struct pollfd::events`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        const POLLIN    = 0x1;
+        const POLLPRI   = 0x2;
+        const POLLOUT   = 0x4;
+        const POLLERR   = 0x8;
+        const POLLHUP   = 0x10;
+        const POLLRDHUP = 0x2000;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    Read = 0,
+    Write = 1,
+    Both = 2,
+}
+
+impl Shutdown {
+    pub fn from_raw(raw: usize) -> Option<Shutdown> {
+        match raw {
+            0 => Some(Shutdown::Read),
+            1 => Some(Shutdown::Write),
+            2 => Some(Shutdown::Both),
+            _ => None,
+        }
+    }
+
+    pub fn read(self) -> bool {
+        self != Shutdown::Write
+    }
+
+    pub fn write(self) -> bool {
+        self != Shutdown::Read
+    }
+}
+
+pub struct ReceivedMessage {
+    /// Bytes copied into the buffer
+    pub length: usize,
+    /// Size of the whole datagram, larger than `length` if it was truncated
+    pub full_length: usize,
+    pub source: SocketAddress,
+    /// Files passed along with the data by `SCM_RIGHTS`
+    pub rights: Vec<Arc<dyn IFile>>,
+}
+
+/// A socket endpoint. Operations never block, they fail with
+/// [`ErrNo::ResourceTemporarilyUnavailable`] and the caller decides whether to wait.
+pub trait ISocket: Downcast + DowncastSend + Send + Sync {
+    fn family(&self) -> u16;
+
+    fn socket_type(&self) -> SocketType;
+
+    fn bind(&self, address: &SocketAddress) -> Result<(), ErrNo>;
+
+    fn listen(&self, backlog: usize) -> Result<(), ErrNo>;
+
+    fn accept(&self) -> Result<Arc<dyn ISocket>, ErrNo>;
+
+    fn connect(&self, address: &SocketAddress) -> Result<(), ErrNo>;
+
+    fn send(
+        &self,
+        data: &[u8],
+        rights: Vec<Arc<dyn IFile>>,
+        destination: Option<&SocketAddress>,
+    ) -> Result<usize, ErrNo>;
+
+    fn receive(&self, buf: &mut [u8], flags: MessageFlags) -> Result<ReceivedMessage, ErrNo>;
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), ErrNo>;
+
+    fn local_address(&self) -> SocketAddress;
+
+    fn peer_address(&self) -> Result<SocketAddress, ErrNo>;
+
+    fn poll(&self) -> PollEvents;
+
+    /// Notified whenever the socket may have become ready, callers that got
+    /// [`ErrNo::ResourceTemporarilyUnavailable`] wait here before trying again.
+    fn readiness(&self) -> &WaitQueue;
+
+    fn get_option(&self, _level: usize, _name: usize) -> Result<usize, ErrNo> {
+        Err(ErrNo::ProtocolNotAvailable)
+    }
+
+    fn set_option(&self, _level: usize, _name: usize, _value: usize) -> Result<(), ErrNo> {
+        Err(ErrNo::ProtocolNotAvailable)
+    }
+}
+
+impl_downcast!(ISocket);
@@ -12,7 +12,7 @@ mod task_id;
 
 use alloc::{string::String, sync::Arc, vec::Vec};
 use downcast_rs::{impl_downcast, Downcast, DowncastSync};
-use filesystem_abstractions::FileDescriptorTable;
+use filesystem_abstractions::{Credentials, FileDescriptorTable};
 use hermit_sync::SpinMutex;
 pub use id::*;
 use memory_space::MemorySpace;
@@ -39,6 +39,12 @@ pub trait IProcess: Downcast + DowncastSync {
 
     fn working_directory(&self) -> String;
 
+    /// Who the process acts as, shared by all of its threads
+    fn credentials(&self) -> &SpinMutex<Credentials>;
+
+    /// What the process is running, as given to execve
+    fn image(&self) -> ProcessImage;
+
     fn exit_code(&self) -> &SpinMutex<Option<u8>>;
 
     fn alloc_id(&self) -> TaskId;
@@ -85,6 +91,14 @@ pub trait ITask: Downcast + DowncastSync {
 
 impl_downcast!(sync ITask);
 
+/// The executable a process runs and what it was started with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessImage {
+    pub executable: String,
+    pub argv: Vec<String>,
+    pub envp: Vec<String>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct UserTaskStatistics {
     pub external_interrupts: usize,
@@ -7,8 +7,12 @@ extern crate std;
 extern crate alloc;
 
 mod futures;
+mod timer;
+
+pub mod sync;
 
 pub use futures::*;
+pub use timer::*;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,72 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::WaitQueue;
+
+/// A manual-reset event: once set, every waiter completes until the event is reset.
+pub struct Event {
+    set: AtomicBool,
+    waiters: WaitQueue,
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Event {
+    pub const fn new() -> Event {
+        Event {
+            set: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    pub fn set(&self) {
+        self.set.store(true, Ordering::Release);
+        self.waiters.notify_all();
+    }
+
+    pub fn reset(&self) {
+        self.set.store(false, Ordering::Release);
+    }
+
+    pub async fn wait(&self) {
+        self.waiters.wait_until(|| self.is_set()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_on, yield_now};
+
+    #[test]
+    fn test_wait_after_set() {
+        let event = Event::new();
+
+        event.set();
+        block_on!(event.wait());
+
+        assert!(event.is_set());
+    }
+
+    #[test]
+    fn test_set_wakes_all_waiters() {
+        let event = Event::new();
+
+        let setter = async {
+            yield_now().await;
+            event.set();
+        };
+
+        block_on!(event.wait(), event.wait(), setter);
+
+        event.reset();
+        assert!(!event.is_set());
+    }
+}
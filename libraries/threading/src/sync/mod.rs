@@ -0,0 +1,19 @@
+//! Synchronization primitives for async code.
+//!
+//! Unlike `hermit_sync`'s spin locks, waiting on these suspends the future and registers its
+//! waker, so they are safe to hold across `.await` points.
+
+mod event;
+mod mutex;
+mod rwlock;
+mod semaphore;
+mod wait_queue;
+
+pub mod mpsc;
+pub mod oneshot;
+
+pub use event::*;
+pub use mutex::*;
+pub use rwlock::*;
+pub use semaphore::*;
+pub use wait_queue::*;
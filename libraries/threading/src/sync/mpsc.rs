@@ -0,0 +1,270 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use hermit_sync::SpinMutex;
+
+use super::WaitQueue;
+
+struct ChannelState<T> {
+    buffer: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+struct Shared<T> {
+    state: SpinMutex<ChannelState<T>>,
+    capacity: usize,
+    // Receiver waits here for data or for the last sender to go away
+    readable: WaitQueue,
+    // Senders wait here for buffer space or for the receiver to go away
+    writable: WaitQueue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Closed,
+}
+
+/// Creates a multi-producer single-consumer channel buffering at most `capacity` values.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must not be zero");
+
+    let shared = Arc::new(Shared {
+        state: SpinMutex::new(ChannelState {
+            buffer: VecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        capacity,
+        readable: WaitQueue::new(),
+        writable: WaitQueue::new(),
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value, suspending while the buffer is full.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+
+        self.shared
+            .writable
+            .wait_for(|| match self.try_send(value.take().unwrap()) {
+                Ok(()) => Some(Ok(())),
+                Err(TrySendError::Closed(v)) => Some(Err(SendError(v))),
+                Err(TrySendError::Full(v)) => {
+                    value = Some(v);
+                    None
+                }
+            })
+            .await
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        {
+            let mut state = self.shared.state.lock();
+
+            if !state.receiver_alive {
+                return Err(TrySendError::Closed(value));
+            }
+
+            if state.buffer.len() >= self.shared.capacity {
+                return Err(TrySendError::Full(value));
+            }
+
+            state.buffer.push_back(value);
+        }
+
+        self.shared.readable.notify_one();
+
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.shared.state.lock().receiver_alive
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().senders += 1;
+
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.shared.state.lock();
+            state.senders -= 1;
+            state.senders == 0
+        };
+
+        if last {
+            self.shared.readable.notify_all();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value, or `None` once the buffer is drained and all senders are gone.
+    pub async fn recv(&mut self) -> Option<T> {
+        let this = &*self;
+
+        this.shared
+            .readable
+            .wait_for(|| match this.try_take() {
+                Ok(value) => Some(Some(value)),
+                Err(TryRecvError::Closed) => Some(None),
+                Err(TryRecvError::Empty) => None,
+            })
+            .await
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.try_take()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn try_take(&self) -> Result<T, TryRecvError> {
+        let value = {
+            let mut state = self.shared.state.lock();
+
+            match state.buffer.pop_front() {
+                Some(value) => value,
+                None if state.senders == 0 => return Err(TryRecvError::Closed),
+                None => return Err(TryRecvError::Empty),
+            }
+        };
+
+        self.shared.writable.notify_one();
+
+        Ok(value)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().receiver_alive = false;
+        self.shared.writable.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Waker},
+    };
+
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn test_values_arrive_in_order() {
+        let (tx, mut rx) = channel(4);
+
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+
+        assert_eq!(block_on!(rx.recv()), Some(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_full_channel_suspends_sender() {
+        let (tx, mut rx) = channel(1);
+
+        tx.try_send(1).unwrap();
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+
+        let mut send = pin!(tx.send(2));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(send.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(rx.try_recv(), Ok(1));
+
+        assert!(send.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(rx.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn test_multiple_producers() {
+        let (tx, mut rx) = channel(2);
+        let tx2 = tx.clone();
+
+        let producer1 = async move {
+            for i in 0..5 {
+                tx.send(i).await.unwrap();
+            }
+        };
+
+        let producer2 = async move {
+            for i in 5..10 {
+                tx2.send(i).await.unwrap();
+            }
+        };
+
+        let consumer = async {
+            let mut received = Vec::new();
+
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+
+            received
+        };
+
+        let (_, _, mut received) = block_on!(producer1, producer2, consumer);
+        received.sort();
+
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (tx, rx) = channel(1);
+
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(block_on!(tx.send(1)), Err(SendError(1)));
+    }
+}
@@ -0,0 +1,157 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::WaitQueue;
+
+/// A mutual exclusion lock whose `lock` suspends the calling future instead of spinning.
+///
+/// The guard may be held across `.await` points.
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex {
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        self.waiters.wait_for(|| self.try_lock()).await
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+        self.mutex.waiters.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Waker},
+    };
+
+    use super::*;
+    use crate::{block_on, yield_now};
+
+    #[test]
+    fn test_lock_and_modify() {
+        let mutex = Mutex::new(0);
+
+        *block_on!(mutex.lock()) += 1;
+
+        assert_eq!(mutex.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_try_lock_while_locked() {
+        let mutex = Mutex::new(());
+
+        let guard = mutex.try_lock().unwrap();
+        assert!(mutex.try_lock().is_none());
+        assert!(mutex.is_locked());
+
+        drop(guard);
+        assert!(mutex.try_lock().is_some());
+    }
+
+    #[test]
+    fn test_contended_lock_suspends() {
+        let mutex = Mutex::new(());
+        let guard = mutex.try_lock().unwrap();
+
+        let mut lock = pin!(mutex.lock());
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(lock.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(mutex.waiters.len(), 1);
+
+        drop(guard);
+
+        assert!(mutex.waiters.is_empty());
+        assert!(lock.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_guard_held_across_await() {
+        let mutex = Mutex::new(alloc::vec::Vec::new());
+
+        let first = async {
+            let mut guard = mutex.lock().await;
+            guard.push(1);
+            yield_now().await;
+            guard.push(2);
+        };
+
+        let second = async {
+            mutex.lock().await.push(3);
+        };
+
+        block_on!(first, second);
+
+        assert_eq!(mutex.into_inner(), [1, 2, 3]);
+    }
+}
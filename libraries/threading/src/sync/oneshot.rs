@@ -0,0 +1,165 @@
+use alloc::sync::Arc;
+use hermit_sync::SpinMutex;
+
+use super::WaitQueue;
+
+struct OneshotState<T> {
+    value: Option<T>,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+struct Shared<T> {
+    state: SpinMutex<OneshotState<T>>,
+    waiters: WaitQueue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Closed,
+}
+
+/// Creates a channel carrying exactly one value.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: SpinMutex::new(OneshotState {
+            value: None,
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        waiters: WaitQueue::new(),
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the value, handing it back if the receiver is gone.
+    pub fn send(self, value: T) -> Result<(), T> {
+        {
+            let mut state = self.shared.state.lock();
+
+            if !state.receiver_alive {
+                return Err(value);
+            }
+
+            state.value = Some(value);
+        }
+
+        // Drop notifies the receiver
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.shared.state.lock().receiver_alive
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().sender_alive = false;
+        self.shared.waiters.notify_all();
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the value, fails if the sender was dropped without sending.
+    pub async fn recv(self) -> Result<T, RecvError> {
+        self.shared
+            .waiters
+            .wait_for(|| match self.try_take() {
+                Err(TryRecvError::Empty) => None,
+                Ok(value) => Some(Ok(value)),
+                Err(TryRecvError::Closed) => Some(Err(RecvError)),
+            })
+            .await
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.try_take()
+    }
+
+    fn try_take(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock();
+
+        match state.value.take() {
+            Some(value) => Ok(value),
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Closed),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().receiver_alive = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_on, yield_now};
+
+    #[test]
+    fn test_send_then_recv() {
+        let (tx, rx) = channel();
+
+        tx.send(42).unwrap();
+
+        assert_eq!(block_on!(rx.recv()), Ok(42));
+    }
+
+    #[test]
+    fn test_recv_waits_for_sender() {
+        let (tx, rx) = channel();
+
+        let sender = async move {
+            yield_now().await;
+            tx.send("hello").unwrap();
+        };
+
+        let (received, _) = block_on!(rx.recv(), sender);
+
+        assert_eq!(received, Ok("hello"));
+    }
+
+    #[test]
+    fn test_sender_dropped() {
+        let (tx, mut rx) = channel::<()>();
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx);
+
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+        assert_eq!(block_on!(rx.recv()), Err(RecvError));
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (tx, rx) = channel();
+
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1), Err(1));
+    }
+}
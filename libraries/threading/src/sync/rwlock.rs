@@ -0,0 +1,192 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::WaitQueue;
+
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// A reader-writer lock whose acquisitions suspend the calling future.
+///
+/// Every release wakes all waiters and lets them race for the lock, so neither readers nor
+/// writers are given priority.
+pub struct RwLock<T: ?Sized> {
+    // WRITER bit when write-locked, otherwise the number of readers
+    state: AtomicUsize,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> RwLock<T> {
+        RwLock {
+            state: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.waiters.wait_for(|| self.try_read()).await
+    }
+
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.waiters.wait_for(|| self.try_write()).await
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+
+        loop {
+            if state & WRITER != 0 {
+                return None;
+            }
+
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(RwLockReadGuard { lock: self }),
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockWriteGuard { lock: self })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        // Only a writer can be waiting while readers hold the lock
+        if self.lock.state.fetch_sub(1, Ordering::Release) == 1 {
+            self.lock.waiters.notify_all();
+        }
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+        self.lock.waiters.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Waker},
+    };
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn test_multiple_readers() {
+        let lock = RwLock::new(1);
+
+        let first = lock.try_read().unwrap();
+        let second = lock.try_read().unwrap();
+
+        assert_eq!(*first + *second, 2);
+        assert!(lock.try_write().is_none());
+    }
+
+    #[test]
+    fn test_writer_excludes_readers() {
+        let lock = RwLock::new(1);
+
+        let mut writer = lock.try_write().unwrap();
+        *writer = 2;
+
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+
+        drop(writer);
+
+        assert_eq!(*lock.try_read().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_writer_waits_for_readers() {
+        let lock = RwLock::new(0);
+        let reader = lock.try_read().unwrap();
+
+        let mut write = pin!(lock.write());
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(write.as_mut().poll(&mut cx).is_pending());
+
+        drop(reader);
+
+        match write.as_mut().poll(&mut cx) {
+            core::task::Poll::Ready(mut guard) => *guard = 1,
+            core::task::Poll::Pending => panic!("writer should acquire the lock"),
+        }
+
+        assert_eq!(*block_on!(lock.read()), 1);
+    }
+}
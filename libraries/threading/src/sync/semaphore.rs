@@ -0,0 +1,129 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::WaitQueue;
+
+/// A counting semaphore, acquisitions suspend until enough permits are available.
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.acquire_many(1).await
+    }
+
+    pub async fn acquire_many(&self, count: usize) -> SemaphorePermit<'_> {
+        self.waiters.wait_for(|| self.try_acquire_many(count)).await
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, count: usize) -> Option<SemaphorePermit<'_>> {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |permits| {
+                permits.checked_sub(count)
+            })
+            .ok()
+            .map(|_| SemaphorePermit {
+                semaphore: self,
+                count,
+            })
+    }
+
+    /// Adds permits that are not tied to any [`SemaphorePermit`].
+    pub fn release(&self, count: usize) {
+        self.permits.fetch_add(count, Ordering::Release);
+
+        // Waiters may ask for different amounts, let all of them retry
+        self.waiters.notify_all();
+    }
+}
+
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+impl SemaphorePermit<'_> {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Consumes the permits without giving them back to the semaphore.
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.count != 0 {
+            self.semaphore.release(self.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Waker},
+    };
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn test_permits_are_counted() {
+        let semaphore = Semaphore::new(2);
+
+        let first = semaphore.try_acquire().unwrap();
+        let second = semaphore.try_acquire().unwrap();
+
+        assert!(semaphore.try_acquire().is_none());
+
+        drop(first);
+        assert_eq!(semaphore.available_permits(), 1);
+
+        drop(second);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn test_acquire_many_waits_for_release() {
+        let semaphore = Semaphore::new(1);
+
+        let mut acquire = pin!(semaphore.acquire_many(2));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(acquire.as_mut().poll(&mut cx).is_pending());
+
+        semaphore.release(1);
+
+        assert!(acquire.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_forget_keeps_permits() {
+        let semaphore = Semaphore::new(3);
+
+        block_on!(semaphore.acquire_many(2)).forget();
+
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
@@ -0,0 +1,378 @@
+use alloc::collections::VecDeque;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use hermit_sync::SpinMutex;
+
+struct WaitQueueInner {
+    waiters: VecDeque<(u64, Waker)>,
+    next_id: u64,
+}
+
+impl WaitQueueInner {
+    fn position(&self, id: u64) -> Option<usize> {
+        self.waiters.iter().position(|(waiter, _)| *waiter == id)
+    }
+}
+
+/// A FIFO list of suspended futures waiting to be notified.
+///
+/// A waiter that has been notified is removed from the queue, so a registration that is no
+/// longer present means the waiter was picked by `notify_*`.
+pub struct WaitQueue {
+    inner: SpinMutex<WaitQueueInner>,
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WaitQueue {
+    pub const fn new() -> WaitQueue {
+        WaitQueue {
+            inner: SpinMutex::new(WaitQueueInner {
+                waiters: VecDeque::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().waiters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Suspends until the next `notify_*` call picks this waiter.
+    pub fn wait(&self) -> Wait<'_> {
+        Wait {
+            queue: self,
+            state: WaitState::Idle,
+        }
+    }
+
+    /// Suspends until `condition` yields a value.
+    ///
+    /// The condition is evaluated again after the waker is registered, so a notification sent
+    /// between the first check and the registration can not be lost. Notifiers must update the
+    /// state the condition observes *before* calling `notify_*`.
+    pub fn wait_for<R, F>(&self, condition: F) -> WaitFor<'_, F>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        WaitFor {
+            queue: self,
+            condition,
+            registration: None,
+        }
+    }
+
+    /// Suspends until `condition` returns true, see [`WaitQueue::wait_for`].
+    pub async fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        self.wait_for(|| condition().then_some(())).await
+    }
+
+    /// Wakes the longest waiting future, returns whether there was one.
+    pub fn notify_one(&self) -> bool {
+        self.notify(1) == 1
+    }
+
+    /// Wakes every waiting future and returns how many were woken.
+    pub fn notify_all(&self) -> usize {
+        self.notify(usize::MAX)
+    }
+
+    /// Wakes at most `count` waiting futures and returns how many were woken.
+    pub fn notify(&self, count: usize) -> usize {
+        let mut woken = 0;
+
+        while woken < count {
+            // Pop one at a time so that no waker is invoked with the lock held
+            let waiter = self.inner.lock().waiters.pop_front();
+
+            match waiter {
+                Some((_, waker)) => waker.wake(),
+                None => break,
+            }
+
+            woken += 1;
+        }
+
+        woken
+    }
+
+    fn register(&self, registration: Option<u64>, waker: &Waker) -> u64 {
+        let mut inner = self.inner.lock();
+
+        if let Some(id) = registration {
+            if let Some(index) = inner.position(id) {
+                let (_, registered) = &mut inner.waiters[index];
+
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+
+                return id;
+            }
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.waiters.push_back((id, waker.clone()));
+
+        id
+    }
+
+    /// Removes the registration, returns false if the waiter had already been notified.
+    fn unregister(&self, id: u64) -> bool {
+        let mut inner = self.inner.lock();
+
+        match inner.position(id) {
+            Some(index) => {
+                inner.waiters.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+enum WaitState {
+    Idle,
+    Waiting(u64),
+    Done,
+}
+
+pub struct Wait<'a> {
+    queue: &'a WaitQueue,
+    state: WaitState,
+}
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.state {
+            WaitState::Idle => {
+                let id = self.queue.register(None, cx.waker());
+                self.state = WaitState::Waiting(id);
+
+                Poll::Pending
+            }
+            WaitState::Waiting(id) => {
+                let notified = self.queue.inner.lock().position(id).is_none();
+
+                if notified {
+                    self.state = WaitState::Done;
+                    return Poll::Ready(());
+                }
+
+                self.queue.register(Some(id), cx.waker());
+
+                Poll::Pending
+            }
+            WaitState::Done => Poll::Ready(()),
+        }
+    }
+}
+
+impl Drop for Wait<'_> {
+    fn drop(&mut self) {
+        if let WaitState::Waiting(id) = self.state {
+            // The notification was aimed at us but nobody will observe it, hand it over
+            if !self.queue.unregister(id) {
+                self.queue.notify_one();
+            }
+        }
+    }
+}
+
+pub struct WaitFor<'a, F> {
+    queue: &'a WaitQueue,
+    condition: F,
+    registration: Option<u64>,
+}
+
+// The condition is never pinned, so moving it around is fine
+impl<F> Unpin for WaitFor<'_, F> {}
+
+impl<R, F> Future for WaitFor<'_, F>
+where
+    F: FnMut() -> Option<R>,
+{
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        if let Some(value) = (this.condition)() {
+            if let Some(id) = this.registration.take() {
+                this.queue.unregister(id);
+            }
+
+            return Poll::Ready(value);
+        }
+
+        let id = this.queue.register(this.registration, cx.waker());
+        this.registration = Some(id);
+
+        if let Some(value) = (this.condition)() {
+            this.registration = None;
+            this.queue.unregister(id);
+
+            return Poll::Ready(value);
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<F> Drop for WaitFor<'_, F> {
+    fn drop(&mut self) {
+        if let Some(id) = self.registration.take() {
+            if !self.queue.unregister(id) {
+                self.queue.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        cell::Cell,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use alloc::{sync::Arc, task::Wake};
+
+    use super::*;
+    use crate::block_on;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F, waker: &Waker) -> Poll<F::Output> {
+        Pin::new(future).poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn test_wait_registers_waker() {
+        let queue = WaitQueue::new();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+
+        let mut wait = queue.wait();
+
+        assert!(poll_once(&mut wait, &waker).is_pending());
+        assert!(poll_once(&mut wait, &waker).is_pending());
+        assert_eq!(queue.len(), 1);
+
+        assert!(queue.notify_one());
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert!(queue.is_empty());
+
+        assert!(poll_once(&mut wait, &waker).is_ready());
+    }
+
+    #[test]
+    fn test_notify_is_fifo() {
+        let queue = WaitQueue::new();
+
+        let mut first = queue.wait();
+        let mut second = queue.wait();
+
+        assert!(poll_once(&mut first, Waker::noop()).is_pending());
+        assert!(poll_once(&mut second, Waker::noop()).is_pending());
+
+        queue.notify_one();
+
+        assert!(poll_once(&mut first, Waker::noop()).is_ready());
+        assert!(poll_once(&mut second, Waker::noop()).is_pending());
+    }
+
+    #[test]
+    fn test_notify_all() {
+        let queue = WaitQueue::new();
+
+        let mut first = queue.wait();
+        let mut second = queue.wait();
+
+        assert!(poll_once(&mut first, Waker::noop()).is_pending());
+        assert!(poll_once(&mut second, Waker::noop()).is_pending());
+
+        assert_eq!(queue.notify_all(), 2);
+        assert_eq!(queue.notify_all(), 0);
+    }
+
+    #[test]
+    fn test_dropped_waiter_unregisters() {
+        let queue = WaitQueue::new();
+
+        let mut wait = queue.wait();
+        assert!(poll_once(&mut wait, Waker::noop()).is_pending());
+
+        drop(wait);
+
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_dropped_notified_waiter_passes_notification_on() {
+        let queue = WaitQueue::new();
+
+        let mut first = queue.wait();
+        let mut second = queue.wait();
+
+        assert!(poll_once(&mut first, Waker::noop()).is_pending());
+        assert!(poll_once(&mut second, Waker::noop()).is_pending());
+
+        queue.notify_one();
+        drop(first);
+
+        assert!(poll_once(&mut second, Waker::noop()).is_ready());
+    }
+
+    #[test]
+    fn test_wait_for_rechecks_after_registration() {
+        let queue = WaitQueue::new();
+        let checks = Cell::new(0);
+
+        // Becomes true on the second evaluation, i.e. right after the waker is registered
+        let mut wait = queue.wait_for(|| {
+            checks.set(checks.get() + 1);
+            (checks.get() == 2).then_some(42)
+        });
+
+        assert_eq!(poll_once(&mut wait, Waker::noop()), Poll::Ready(42));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_wait_until_with_notifier() {
+        let queue = WaitQueue::new();
+        let flag = Cell::new(false);
+
+        let waiter = queue.wait_until(|| flag.get());
+        let notifier = async {
+            crate::yield_now().await;
+            flag.set(true);
+            queue.notify_all();
+        };
+
+        block_on!(waiter, notifier);
+
+        assert!(queue.is_empty());
+    }
+}
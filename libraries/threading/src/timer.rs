@@ -0,0 +1,449 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use hermit_sync::SpinMutex;
+use timing::TimeSpec;
+
+/// A monotonic time source used by [`TimerQueue`] to decide whether a deadline has passed.
+pub trait IClock: Send + Sync {
+    fn now(&self) -> TimeSpec;
+
+    /// Asks the time source to interrupt once `deadline` passes, or to stop when it's `None`.
+    ///
+    /// Called by [`TimerQueue`] whenever its earliest deadline may have changed.
+    fn arm(&self, _deadline: Option<TimeSpec>) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TimerKey {
+    deadline: TimeSpec,
+    // Disambiguates timers sharing the same deadline
+    id: u64,
+}
+
+struct TimerQueueInner {
+    timers: BTreeMap<TimerKey, Waker>,
+    next_id: u64,
+}
+
+/// A queue of pending deadlines ordered by expiry time.
+///
+/// Futures waiting for a deadline register their waker here instead of yielding in a loop.
+/// Whoever owns the time source (usually the timer interrupt handler) calls
+/// [`TimerQueue::process_expired`] to wake every timer whose deadline has passed,
+/// and the queue arms the clock for the next deadline through [`IClock::arm`].
+pub struct TimerQueue {
+    clock: Arc<dyn IClock>,
+    inner: SpinMutex<TimerQueueInner>,
+}
+
+impl TimerQueue {
+    pub fn new(clock: Arc<dyn IClock>) -> Arc<TimerQueue> {
+        Arc::new(TimerQueue {
+            clock,
+            inner: SpinMutex::new(TimerQueueInner {
+                timers: BTreeMap::new(),
+                next_id: 0,
+            }),
+        })
+    }
+
+    pub fn now(&self) -> TimeSpec {
+        self.clock.now()
+    }
+
+    /// The earliest registered deadline, can be used to program the next timer interrupt.
+    pub fn next_deadline(&self) -> Option<TimeSpec> {
+        self.inner
+            .lock()
+            .timers
+            .first_key_value()
+            .map(|(key, _)| key.deadline)
+    }
+
+    /// Number of timers currently waiting for their deadline.
+    pub fn pending(&self) -> usize {
+        self.inner.lock().timers.len()
+    }
+
+    /// Wakes all the timers whose deadline has passed and returns how many were woken.
+    pub fn process_expired(&self) -> usize {
+        let now = self.now();
+        let mut expired = Vec::new();
+
+        {
+            let mut inner = self.inner.lock();
+
+            while let Some(entry) = inner.timers.first_entry() {
+                if entry.key().deadline > now {
+                    break;
+                }
+
+                expired.push(entry.remove());
+            }
+        }
+
+        self.clock.arm(self.next_deadline());
+
+        // Wake outside the lock, as a waker may poll the future synchronously
+        let count = expired.len();
+        for waker in expired {
+            waker.wake();
+        }
+
+        count
+    }
+
+    pub fn sleep_until(self: &Arc<TimerQueue>, deadline: TimeSpec) -> Sleep {
+        Sleep {
+            queue: self.clone(),
+            deadline,
+            registration: None,
+        }
+    }
+
+    pub fn sleep(self: &Arc<TimerQueue>, duration: TimeSpec) -> Sleep {
+        self.sleep_until(self.now() + duration)
+    }
+
+    /// Runs `future` until it completes or `deadline` passes, whichever comes first.
+    pub fn timeout<F: Future>(self: &Arc<TimerQueue>, deadline: TimeSpec, future: F) -> Timeout<F> {
+        Timeout {
+            future,
+            sleep: self.sleep_until(deadline),
+        }
+    }
+
+    fn register(&self, previous: Option<TimerKey>, deadline: TimeSpec, waker: &Waker) -> TimerKey {
+        let (key, earliest) = self.insert(previous, deadline, waker);
+
+        if earliest {
+            self.clock.arm(Some(deadline));
+        }
+
+        key
+    }
+
+    /// Returns the key of the timer and whether it's a new timer that became the earliest one.
+    fn insert(
+        &self,
+        previous: Option<TimerKey>,
+        deadline: TimeSpec,
+        waker: &Waker,
+    ) -> (TimerKey, bool) {
+        let mut inner = self.inner.lock();
+
+        if let Some(key) = previous {
+            if let Some(registered) = inner.timers.get_mut(&key) {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+
+                return (key, false);
+            }
+        }
+
+        let key = TimerKey {
+            deadline,
+            id: inner.next_id,
+        };
+
+        inner.next_id += 1;
+        inner.timers.insert(key, waker.clone());
+
+        let earliest = inner.timers.first_key_value().map(|(first, _)| *first) == Some(key);
+
+        (key, earliest)
+    }
+
+    fn cancel(&self, key: TimerKey) {
+        self.inner.lock().timers.remove(&key);
+    }
+}
+
+/// A future that completes once the clock of its [`TimerQueue`] reaches the deadline.
+pub struct Sleep {
+    queue: Arc<TimerQueue>,
+    deadline: TimeSpec,
+    registration: Option<TimerKey>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> TimeSpec {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.queue.now() >= self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_elapsed() {
+            if let Some(key) = self.registration.take() {
+                self.queue.cancel(key);
+            }
+
+            return Poll::Ready(());
+        }
+
+        let previous = self.registration.take();
+        let key = self.queue.register(previous, self.deadline, cx.waker());
+        self.registration = Some(key);
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.registration.take() {
+            self.queue.cancel(key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut;
+
+/// A future that races the inner future against a deadline, see [`TimerQueue::timeout`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    pub fn deadline(&self) -> TimeSpec {
+        self.sleep.deadline()
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, TimedOut>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`, and `sleep` is Unpin
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if let Poll::Ready(value) = future.poll(cx) {
+            return Poll::Ready(Ok(value));
+        }
+
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(TimedOut)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+    use alloc::task::Wake;
+
+    use super::*;
+    use crate::block_on;
+
+    struct ManualClock {
+        nanos: AtomicI64,
+        armed: SpinMutex<Option<TimeSpec>>,
+    }
+
+    impl ManualClock {
+        fn new() -> Arc<ManualClock> {
+            Arc::new(ManualClock {
+                nanos: AtomicI64::new(0),
+                armed: SpinMutex::new(None),
+            })
+        }
+
+        fn advance(&self, nanos: i64) {
+            self.nanos.fetch_add(nanos, Ordering::Relaxed);
+        }
+
+        fn armed(&self) -> Option<TimeSpec> {
+            *self.armed.lock()
+        }
+    }
+
+    impl IClock for ManualClock {
+        fn now(&self) -> TimeSpec {
+            let nanos = self.nanos.load(Ordering::Relaxed);
+            TimeSpec::new(0, nanos)
+        }
+
+        fn arm(&self, deadline: Option<TimeSpec>) {
+            *self.armed.lock() = deadline;
+        }
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn setup() -> (Arc<ManualClock>, Arc<TimerQueue>) {
+        let clock = ManualClock::new();
+        let queue = TimerQueue::new(clock.clone());
+
+        (clock, queue)
+    }
+
+    fn poll_once<F: Future>(future: &mut F, waker: &Waker) -> Poll<F::Output> {
+        let mut cx = Context::from_waker(waker);
+        unsafe { Pin::new_unchecked(future) }.poll(&mut cx)
+    }
+
+    #[test]
+    fn test_sleep_registers_instead_of_yielding() {
+        let (_, queue) = setup();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+
+        let mut sleep = queue.sleep(TimeSpec::new(1, 0));
+
+        assert_eq!(poll_once(&mut sleep, &waker), Poll::Pending);
+        assert_eq!(poll_once(&mut sleep, &waker), Poll::Pending);
+
+        assert_eq!(queue.pending(), 1);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_process_expired_wakes_due_timers_only() {
+        let (clock, queue) = setup();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+
+        let mut early = queue.sleep(TimeSpec::new(0, 100));
+        let mut late = queue.sleep(TimeSpec::new(0, 300));
+
+        assert!(poll_once(&mut early, &waker).is_pending());
+        assert!(poll_once(&mut late, &waker).is_pending());
+        assert_eq!(queue.next_deadline(), Some(TimeSpec::new(0, 100)));
+
+        clock.advance(200);
+
+        assert_eq!(queue.process_expired(), 1);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(queue.next_deadline(), Some(TimeSpec::new(0, 300)));
+
+        assert_eq!(poll_once(&mut early, &waker), Poll::Ready(()));
+        assert!(poll_once(&mut late, &waker).is_pending());
+    }
+
+    #[test]
+    fn test_clock_armed_for_earliest_deadline() {
+        let (clock, queue) = setup();
+
+        let mut late = queue.sleep(TimeSpec::new(0, 300));
+        assert!(poll_once(&mut late, Waker::noop()).is_pending());
+        assert_eq!(clock.armed(), Some(TimeSpec::new(0, 300)));
+
+        let mut early = queue.sleep(TimeSpec::new(0, 100));
+        assert!(poll_once(&mut early, Waker::noop()).is_pending());
+        assert_eq!(clock.armed(), Some(TimeSpec::new(0, 100)));
+
+        // A later timer must not push the interrupt back
+        let mut later = queue.sleep(TimeSpec::new(0, 500));
+        assert!(poll_once(&mut later, Waker::noop()).is_pending());
+        assert_eq!(clock.armed(), Some(TimeSpec::new(0, 100)));
+
+        clock.advance(100);
+        queue.process_expired();
+        assert_eq!(clock.armed(), Some(TimeSpec::new(0, 300)));
+
+        drop(late);
+        drop(later);
+        queue.process_expired();
+        assert_eq!(clock.armed(), None);
+    }
+
+    #[test]
+    fn test_dropped_sleep_cancels_timer() {
+        let (_, queue) = setup();
+
+        let mut sleep = queue.sleep(TimeSpec::new(1, 0));
+        assert!(poll_once(&mut sleep, Waker::noop()).is_pending());
+        assert_eq!(queue.pending(), 1);
+
+        drop(sleep);
+
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_elapsed_sleep_completes_immediately() {
+        let (_, queue) = setup();
+
+        let mut sleep = queue.sleep_until(TimeSpec::zero());
+
+        assert_eq!(poll_once(&mut sleep, Waker::noop()), Poll::Ready(()));
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[test]
+    fn test_same_deadline_timers_are_distinct() {
+        let (clock, queue) = setup();
+
+        let mut first = queue.sleep(TimeSpec::new(0, 10));
+        let mut second = queue.sleep(TimeSpec::new(0, 10));
+
+        assert!(poll_once(&mut first, Waker::noop()).is_pending());
+        assert!(poll_once(&mut second, Waker::noop()).is_pending());
+        assert_eq!(queue.pending(), 2);
+
+        clock.advance(10);
+
+        assert_eq!(queue.process_expired(), 2);
+    }
+
+    #[test]
+    fn test_timeout_expires() {
+        let (clock, queue) = setup();
+
+        let mut timeout = queue.timeout(TimeSpec::new(0, 50), core::future::pending::<()>());
+
+        assert!(poll_once(&mut timeout, Waker::noop()).is_pending());
+
+        clock.advance(50);
+
+        assert_eq!(
+            poll_once(&mut timeout, Waker::noop()),
+            Poll::Ready(Err(TimedOut))
+        );
+    }
+
+    #[test]
+    fn test_timeout_inner_completes_first() {
+        let (_, queue) = setup();
+
+        let mut timeout = queue.timeout(TimeSpec::new(1, 0), async { 42 });
+
+        assert_eq!(poll_once(&mut timeout, Waker::noop()), Poll::Ready(Ok(42)));
+    }
+
+    #[test]
+    fn test_block_on_sleep() {
+        let (clock, queue) = setup();
+
+        let sleep = queue.sleep(TimeSpec::new(0, 1));
+        clock.advance(1);
+
+        block_on!(sleep);
+
+        assert_eq!(queue.pending(), 0);
+    }
+}
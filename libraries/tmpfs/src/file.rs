@@ -0,0 +1,240 @@
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::Entry, BTreeMap},
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+
+/// Files are kept in pages of this size, which is also the block size `stat` reports.
+pub(crate) const PAGE_SIZE: usize = 4096;
+
+/// Pages used by all files of a filesystem, shared with the files so they can give them back
+/// when they go away.
+pub(crate) struct Space {
+    /// Most pages the files may use, `None` if there is no limit
+    limit: Option<usize>,
+    used: AtomicUsize,
+}
+
+impl Space {
+    pub fn new(limit: Option<usize>) -> Space {
+        Space {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn reserve(&self, pages: usize) -> FileSystemResult<()> {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(pages)
+                    .filter(|&total| self.limit.is_none_or(|limit| total <= limit))
+            })
+            .map(|_| ())
+            .map_err(|_| FileSystemError::SpaceNotEnough)
+    }
+
+    fn release(&self, pages: usize) {
+        self.used.fetch_sub(pages, Ordering::Relaxed);
+    }
+}
+
+/// The contents of a file. Pages that were never written to are holes and read as zeros.
+#[derive(Default)]
+pub(crate) struct Pages {
+    pages: BTreeMap<u64, Box<[u8; PAGE_SIZE]>>,
+    size: u64,
+}
+
+impl Pages {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// How many pages are allocated, holes not included.
+    pub fn count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn read(&self, offset: u64, buffer: &mut [u8]) -> usize {
+        if offset >= self.size {
+            return 0;
+        }
+
+        let len = buffer.len().min((self.size - offset) as usize);
+        let mut done = 0;
+
+        while done < len {
+            let position = offset + done as u64;
+            let within = (position % PAGE_SIZE as u64) as usize;
+            let piece = (PAGE_SIZE - within).min(len - done);
+            let target = &mut buffer[done..done + piece];
+
+            match self.pages.get(&(position / PAGE_SIZE as u64)) {
+                Some(page) => target.copy_from_slice(&page[within..within + piece]),
+                None => target.fill(0),
+            }
+
+            done += piece;
+        }
+
+        len
+    }
+
+    /// Writes `data` at `offset`, stopping early if the pages it needs run out. Skipping past the
+    /// end leaves a hole.
+    pub fn write(&mut self, space: &Space, offset: u64, data: &[u8]) -> FileSystemResult<usize> {
+        offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= i64::MAX as u64)
+            .ok_or(FileSystemError::InvalidInput)?;
+
+        let mut done = 0;
+
+        while done < data.len() {
+            let position = offset + done as u64;
+            let within = (position % PAGE_SIZE as u64) as usize;
+            let piece = (PAGE_SIZE - within).min(data.len() - done);
+            let index = position / PAGE_SIZE as u64;
+
+            let page = match self.pages.entry(index) {
+                Entry::Occupied(page) => page.into_mut(),
+                Entry::Vacant(vacant) => {
+                    if let Err(e) = space.reserve(1) {
+                        // Like Linux, what fits is written
+                        match done {
+                            0 => return Err(e),
+                            _ => break,
+                        }
+                    }
+
+                    vacant.insert(Box::new([0; PAGE_SIZE]))
+                }
+            };
+
+            page[within..within + piece].copy_from_slice(&data[done..done + piece]);
+
+            done += piece;
+        }
+
+        self.size = self.size.max(offset + done as u64);
+
+        Ok(done)
+    }
+
+    /// Grows the file with a hole or cuts it at `size`.
+    pub fn resize(&mut self, space: &Space, size: u64) -> FileSystemResult<()> {
+        if size > i64::MAX as u64 {
+            return Err(FileSystemError::InvalidInput);
+        }
+
+        if size < self.size {
+            let kept = size.div_ceil(PAGE_SIZE as u64);
+            let cut = self.pages.split_off(&kept).len();
+            space.release(cut);
+
+            // What is left of the last page is read back as zeros if the file grows again
+            let within = (size % PAGE_SIZE as u64) as usize;
+
+            if within != 0 {
+                if let Some(page) = self.pages.get_mut(&(size / PAGE_SIZE as u64)) {
+                    page[within..].fill(0);
+                }
+            }
+        }
+
+        self.size = size;
+
+        Ok(())
+    }
+
+    pub fn release(&mut self, space: &Space) {
+        space.release(self.pages.len());
+        self.pages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_holes() {
+        let space = Space::new(None);
+        let mut pages = Pages::default();
+
+        assert_eq!(
+            pages
+                .write(&space, 3 * PAGE_SIZE as u64 + 10, b"far")
+                .unwrap(),
+            3
+        );
+        assert_eq!(pages.size(), 3 * PAGE_SIZE as u64 + 13);
+        assert_eq!(pages.count(), 1);
+        assert_eq!(space.used(), 1);
+
+        let mut buffer = vec![0xff; PAGE_SIZE * 4];
+        assert_eq!(pages.read(0, &mut buffer), 3 * PAGE_SIZE + 13);
+        assert!(buffer[..3 * PAGE_SIZE + 10].iter().all(|&b| b == 0));
+        assert_eq!(&buffer[3 * PAGE_SIZE + 10..3 * PAGE_SIZE + 13], b"far");
+
+        pages.release(&space);
+        assert_eq!(space.used(), 0);
+    }
+
+    #[test]
+    fn test_resize() {
+        let space = Space::new(None);
+        let mut pages = Pages::default();
+
+        let data: Vec<u8> = (0..3 * PAGE_SIZE).map(|i| i as u8 | 1).collect();
+        pages.write(&space, 0, &data).unwrap();
+
+        pages.resize(&space, 100).unwrap();
+        assert_eq!(pages.count(), 1);
+        assert_eq!(space.used(), 1);
+
+        pages.resize(&space, 2 * PAGE_SIZE as u64).unwrap();
+        assert_eq!(pages.count(), 1);
+
+        let mut buffer = vec![0xff; 2 * PAGE_SIZE];
+        assert_eq!(pages.read(0, &mut buffer), 2 * PAGE_SIZE);
+        assert_eq!(buffer[..100], data[..100]);
+        assert!(buffer[100..].iter().all(|&b| b == 0));
+
+        pages.resize(&space, 0).unwrap();
+        assert_eq!(space.used(), 0);
+    }
+
+    #[test]
+    fn test_limit() {
+        let space = Space::new(Some(2));
+        let mut pages = Pages::default();
+
+        assert_eq!(
+            pages.write(&space, 10, &[1; 3 * PAGE_SIZE]).unwrap(),
+            2 * PAGE_SIZE - 10
+        );
+        assert_eq!(
+            pages.write(&space, 5 * PAGE_SIZE as u64, b"x").err(),
+            Some(FileSystemError::SpaceNotEnough)
+        );
+
+        // Pages that are there already can still be written
+        assert_eq!(pages.write(&space, 0, b"again").unwrap(), 5);
+        assert_eq!(
+            pages.write(&space, u64::MAX - 1, b"xx").err(),
+            Some(FileSystemError::InvalidInput)
+        );
+    }
+}
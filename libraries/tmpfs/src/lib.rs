@@ -0,0 +1,629 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicU64, Ordering};
+use filesystem_abstractions::{FileSystemResult, IFileSystem, IFileSystemType, IInode, MountFlags};
+use threading::IClock;
+use timing::TimeSpec;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod file;
+mod node;
+
+pub use node::TmpInode;
+
+use file::{Space, PAGE_SIZE};
+use node::{Content, Node};
+
+/// A filesystem kept entirely in kernel memory, gone once it is dropped.
+///
+/// File contents are allocated a page at a time as they are written, so files can have holes.
+/// Only these pages count against the size limit.
+pub struct TmpFileSystem {
+    this: Weak<TmpFileSystem>,
+    root: Arc<Node>,
+    space: Arc<Space>,
+    clock: Arc<dyn IClock>,
+    next_number: AtomicU64,
+}
+
+impl TmpFileSystem {
+    /// Creates an empty filesystem that can grow as long as there is memory.
+    pub fn new(clock: Arc<dyn IClock>) -> Arc<TmpFileSystem> {
+        Self::create(clock, None)
+    }
+
+    /// Creates an empty filesystem whose files can hold at most `limit` bytes, rounded down to
+    /// whole pages. Writing more fails with `SpaceNotEnough`.
+    pub fn with_limit(clock: Arc<dyn IClock>, limit: usize) -> Arc<TmpFileSystem> {
+        Self::create(clock, Some(limit / PAGE_SIZE))
+    }
+
+    fn create(clock: Arc<dyn IClock>, limit: Option<usize>) -> Arc<TmpFileSystem> {
+        let space = Arc::new(Space::new(limit));
+        let root = Node::new(
+            Node::ROOT_NUMBER,
+            space.clone(),
+            Content::Directory(BTreeMap::new()),
+            clock.now(),
+        );
+
+        Arc::new_cyclic(|this| TmpFileSystem {
+            this: this.clone(),
+            root,
+            space,
+            clock,
+            next_number: AtomicU64::new(Node::ROOT_NUMBER + 1),
+        })
+    }
+
+    /// Bytes the contents of all files take up.
+    pub fn used(&self) -> usize {
+        self.space.used() * PAGE_SIZE
+    }
+
+    /// Most bytes the contents of all files can take up, if there is a limit.
+    pub fn limit(&self) -> Option<usize> {
+        self.space.limit().map(|pages| pages * PAGE_SIZE)
+    }
+
+    fn now(&self) -> TimeSpec {
+        self.clock.now()
+    }
+
+    fn node(&self, content: Content, now: TimeSpec) -> Arc<Node> {
+        let number = self.next_number.fetch_add(1, Ordering::Relaxed);
+
+        Node::new(number, self.space.clone(), content, now)
+    }
+}
+
+impl IFileSystem for TmpFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        TmpInode::new(self.this.upgrade().unwrap(), self.root.clone(), "")
+    }
+
+    fn name(&self) -> &str {
+        "tmpfs"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+}
+
+/// Lets `mount` create a new, empty tmpfs each time.
+pub struct TmpFileSystemType {
+    clock: Arc<dyn IClock>,
+}
+
+impl TmpFileSystemType {
+    pub fn new(clock: Arc<dyn IClock>) -> Arc<TmpFileSystemType> {
+        Arc::new(TmpFileSystemType { clock })
+    }
+}
+
+impl IFileSystemType for TmpFileSystemType {
+    fn name(&self) -> &str {
+        "tmpfs"
+    }
+
+    fn create(&self, _source: &str, _flags: MountFlags) -> FileSystemResult<Arc<dyn IFileSystem>> {
+        Ok(TmpFileSystem::new(self.clock.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+    use core::sync::atomic::AtomicI64;
+    use filesystem_abstractions::{
+        Credentials, DirectoryTreeNode, FileMode, FileStatistics, FileStatisticsMode,
+        FileSystemError, InodeAttributes, OpenFlags, XattrFlags,
+    };
+
+    use super::*;
+
+    /// A clock that moves one second forward every time it is read.
+    struct TickingClock(AtomicI64);
+
+    impl IClock for TickingClock {
+        fn now(&self) -> TimeSpec {
+            TimeSpec {
+                tv_sec: self.0.fetch_add(1, Ordering::Relaxed),
+                tv_nsec: 0,
+            }
+        }
+    }
+
+    fn create(limit: Option<usize>) -> (Arc<TmpFileSystem>, Arc<DirectoryTreeNode>) {
+        let clock = Arc::new(TickingClock(AtomicI64::new(1_700_000_000)));
+
+        let fs = match limit {
+            Some(limit) => TmpFileSystem::with_limit(clock, limit),
+            None => TmpFileSystem::new(clock),
+        };
+
+        let root = DirectoryTreeNode::from_filesystem(None, fs.clone(), Some(""));
+
+        (fs, root)
+    }
+
+    fn stat(node: &Arc<DirectoryTreeNode>) -> FileStatistics {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        node.stat(&mut stat).unwrap();
+
+        stat
+    }
+
+    fn names(node: &Arc<DirectoryTreeNode>) -> Vec<String> {
+        let mut names: Vec<String> = node
+            .read_dir()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.filename)
+            .filter(|name| name != "." && name != "..")
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_files_and_directories() {
+        let (_fs, root) = create(None);
+
+        let etc = root.mkdir("etc").unwrap();
+        let nested = etc.mkdir("nested").unwrap();
+        let file = nested.touch("hosts").unwrap();
+
+        let data: Vec<u8> = (0..10000).map(|i| (i % 251) as u8).collect();
+        assert_eq!(file.writeat(0, &data).unwrap(), data.len());
+        drop(file);
+
+        let opened = root.open("/etc/nested/hosts", Some(&root)).unwrap();
+        assert_eq!(opened.readall().unwrap(), data);
+
+        assert_eq!(names(&root), ["etc"]);
+        assert_eq!(names(&etc), ["nested"]);
+        assert_eq!(
+            etc.touch("nested").err(),
+            Some(FileSystemError::AlreadyExists)
+        );
+
+        // `.`, the entry in `/` and the `..` of nested
+        assert_eq!(stat(&etc).link_count, 3);
+        assert_eq!(stat(&etc).size, 3 * 20);
+        assert_eq!(stat(&root).inode_id, 1);
+
+        let file_stat = stat(&opened);
+        assert_eq!(
+            file_stat.mode,
+            FileStatisticsMode::FILE | FileStatisticsMode::from_bits_truncate(0o644)
+        );
+        assert_eq!(file_stat.size, 10000);
+        assert_eq!(file_stat.block_size, 4096);
+        assert_eq!(file_stat.block_count, 3 * 8);
+        assert_eq!(file_stat.link_count, 1);
+
+        assert_eq!(
+            etc.rmdir("nested").err(),
+            Some(FileSystemError::DirectoryNotEmpty)
+        );
+
+        opened.resize_inode(5).unwrap();
+        assert_eq!(opened.readall().unwrap(), &data[..5]);
+
+        drop(opened);
+        nested.remove("hosts").unwrap();
+        etc.rmdir("nested").unwrap();
+
+        assert_eq!(stat(&etc).link_count, 2);
+        assert_eq!(
+            nested.touch("orphan").err(),
+            Some(FileSystemError::NotADirectory)
+        );
+        assert!(names(&etc).is_empty());
+    }
+
+    #[test]
+    fn test_sparse_files() {
+        let (fs, root) = create(None);
+        let file = root.touch("sparse").unwrap();
+
+        file.writeat(1 << 30, b"end").unwrap();
+
+        let stat = stat(&file);
+        assert_eq!(stat.size, (1 << 30) + 3);
+        assert_eq!(stat.block_count, 8);
+        assert_eq!(fs.used(), 4096);
+
+        let mut buffer = vec![0xff; 8192];
+        assert_eq!(file.readat(4096, &mut buffer).unwrap(), 8192);
+        assert!(buffer.iter().all(|&b| b == 0));
+
+        assert_eq!(file.readrest_at(1 << 30).unwrap(), b"end");
+
+        file.resize_inode(1 << 40).unwrap();
+        assert_eq!(fs.used(), 4096);
+    }
+
+    #[test]
+    fn test_links() {
+        let (fs, root) = create(None);
+        let dir = root.mkdir("dir").unwrap();
+
+        let file = dir.touch("file").unwrap();
+        file.writeat(0, b"shared").unwrap();
+
+        root.hard_link("link", &file).unwrap();
+        assert_eq!(stat(&file).link_count, 2);
+
+        let link = root.open("/link", Some(&root)).unwrap();
+        assert_eq!(stat(&link).inode_id, stat(&file).inode_id);
+
+        link.writeat(6, b" contents").unwrap();
+        assert_eq!(file.readall().unwrap(), b"shared contents");
+
+        drop(file);
+        dir.remove("file").unwrap();
+        assert_eq!(stat(&link).link_count, 1);
+        assert_eq!(link.readall().unwrap(), b"shared contents");
+
+        assert_eq!(
+            root.inode()
+                .unwrap()
+                .hard_link("again", &dir.inode().unwrap())
+                .err(),
+            Some(FileSystemError::NotPermitted)
+        );
+
+        let symlink = root.soft_link("symlink", "/dir/../link").unwrap();
+        assert_eq!(symlink.resolve_link().as_deref(), Some("/dir/../link"));
+        assert_eq!(
+            stat(&symlink).mode,
+            FileStatisticsMode::LINK | FileStatisticsMode::from_bits_truncate(0o777)
+        );
+        assert_eq!(stat(&symlink).size, 12);
+
+        let resolved = root.open("/symlink", Some(&root)).unwrap();
+        assert_eq!(resolved.readall().unwrap(), b"shared contents");
+
+        drop((link, resolved));
+        root.remove("link").unwrap();
+        assert_eq!(fs.used(), 0);
+        assert_eq!(
+            root.open("/symlink", Some(&root)).err(),
+            Some(FileSystemError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let (fs, root) = create(None);
+        let inode = fs.root_dir();
+
+        inode.touch("a").unwrap().writeat(0, b"a").unwrap();
+        inode.touch("b").unwrap().writeat(0, b"b").unwrap();
+        inode.mkdir("empty").unwrap();
+        inode.mkdir("full").unwrap().touch("inside").unwrap();
+
+        inode.rename("a", "b").unwrap();
+        assert_eq!(names(&root), ["b", "empty", "full"]);
+        assert_eq!(fs.used(), 4096);
+
+        assert_eq!(
+            inode.rename("b", "empty").err(),
+            Some(FileSystemError::NotAFile)
+        );
+        assert_eq!(
+            inode.rename("empty", "b").err(),
+            Some(FileSystemError::NotADirectory)
+        );
+        assert_eq!(
+            inode.rename("empty", "full").err(),
+            Some(FileSystemError::DirectoryNotEmpty)
+        );
+        assert_eq!(
+            inode.rename("missing", "c").err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        inode.rename("full", "empty").unwrap();
+        assert_eq!(names(&root), ["b", "empty"]);
+        assert_eq!(stat(&root).link_count, 3);
+
+        root.rename("b", "c").unwrap();
+        assert_eq!(
+            root.open("/c", Some(&root)).unwrap().readall().unwrap(),
+            b"a"
+        );
+        assert!(root.open("/empty/inside", Some(&root)).is_ok());
+    }
+
+    #[test]
+    fn test_removed_while_open() {
+        let (fs, root) = create(None);
+        let inode = fs.root_dir();
+
+        let file = inode.touch("file").unwrap();
+        file.writeat(0, &[1; 20000]).unwrap();
+        assert_eq!(fs.used(), 5 * 4096);
+
+        inode.remove("file").unwrap();
+        assert_eq!(
+            root.open("/file", Some(&root)).err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        file.writeat(20000, b"more").unwrap();
+        assert_eq!(file.metadata().size, 20004);
+        assert_eq!(fs.used(), 5 * 4096);
+
+        drop(file);
+        assert_eq!(fs.used(), 0);
+    }
+
+    #[test]
+    fn test_limit() {
+        let (fs, root) = create(Some(64 * 1024 + 100));
+        assert_eq!(fs.limit(), Some(64 * 1024));
+
+        let file = root.touch("big").unwrap();
+
+        assert_eq!(file.writeat(100, &[1; 80 * 1024]).unwrap(), 64 * 1024 - 100);
+        assert_eq!(
+            file.writeat(64 * 1024, b"x").err(),
+            Some(FileSystemError::SpaceNotEnough)
+        );
+
+        let other = root.touch("other").unwrap();
+        assert_eq!(
+            other.writeat(0, b"x").err(),
+            Some(FileSystemError::SpaceNotEnough)
+        );
+
+        // Holes take no room
+        other.resize_inode(1 << 20).unwrap();
+
+        file.resize_inode(4096).unwrap();
+        assert_eq!(other.writeat(0, &[2; 8192]).unwrap(), 8192);
+        assert_eq!(fs.used(), 3 * 4096);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let (_fs, root) = create(None);
+        let dir = root.mkdir("dir").unwrap();
+        let before = stat(&dir);
+
+        let file = dir.touch("file").unwrap();
+        let created = stat(&file);
+        let after = stat(&dir);
+
+        assert!(after.mtime.tv_sec > before.mtime.tv_sec);
+        assert_eq!(after.mtime, created.mtime);
+        assert_eq!(created.atime, created.ctime);
+
+        file.writeat(0, b"data").unwrap();
+        let written = stat(&file);
+        assert!(written.mtime.tv_sec > created.mtime.tv_sec);
+        assert_eq!(written.ctime, written.mtime);
+        assert_eq!(written.atime, created.atime);
+
+        file.readall().unwrap();
+        let read = stat(&file);
+        assert!(read.atime.tv_sec > written.mtime.tv_sec);
+        assert_eq!(read.mtime, written.mtime);
+
+        root.hard_link("link", &file).unwrap();
+        let linked = stat(&file);
+        assert!(linked.ctime.tv_sec > read.atime.tv_sec);
+        assert_eq!(linked.mtime, written.mtime);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let (_fs, root) = create(None);
+        let file = root.touch("file").unwrap();
+        let before = stat(&file);
+
+        let mtime = TimeSpec {
+            tv_sec: 1000,
+            tv_nsec: 5,
+        };
+
+        file.set_attributes(&InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o4750)),
+            uid: Some(1000),
+            gid: Some(100),
+            mtime: Some(mtime),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let after = stat(&file);
+        assert_eq!(after.mode.bits(), 0o104750);
+        assert_eq!((after.uid, after.gid), (1000, 100));
+        assert_eq!(after.mtime, mtime);
+        assert_eq!(after.atime, before.atime);
+        assert!(after.ctime.tv_sec > before.ctime.tv_sec);
+
+        let dir = root.mkdir("sticky").unwrap();
+        dir.set_attributes(&InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o1777)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(stat(&dir).mode.bits(), 0o041777);
+    }
+
+    #[test]
+    fn test_xattrs() {
+        let (_fs, root) = create(None);
+        let file = root.touch("file").unwrap();
+        root.hard_link("link", &file).unwrap();
+        let link = root.open("link", None).unwrap();
+        let before = stat(&file);
+
+        file.set_xattr("user.mime", b"text/plain", XattrFlags::empty())
+            .unwrap();
+        file.set_xattr("trusted.note", b"", XattrFlags::XATTR_CREATE)
+            .unwrap();
+
+        // Attributes belong to the inode, not to one of its names
+        assert_eq!(link.get_xattr("user.mime").unwrap(), b"text/plain");
+        assert_eq!(link.list_xattr().unwrap(), ["trusted.note", "user.mime"]);
+        assert!(stat(&file).ctime.tv_sec > before.ctime.tv_sec);
+
+        assert_eq!(
+            file.set_xattr("trusted.note", b"x", XattrFlags::XATTR_CREATE),
+            Err(FileSystemError::AlreadyExists)
+        );
+
+        file.remove_xattr("user.mime").unwrap();
+        assert_eq!(
+            file.get_xattr("user.mime"),
+            Err(FileSystemError::NoAttribute)
+        );
+        assert_eq!(file.list_xattr().unwrap(), ["trusted.note"]);
+    }
+
+    fn own(node: &Arc<DirectoryTreeNode>, uid: u32, mode: u32) -> &Arc<DirectoryTreeNode> {
+        node.set_attributes(&InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(mode)),
+            uid: Some(uid),
+            gid: Some(uid),
+            ..Default::default()
+        })
+        .unwrap();
+
+        node
+    }
+
+    #[test]
+    fn test_permission_checks() {
+        let (_fs, root) = create(None);
+
+        let home = root.mkdir("home").unwrap();
+        let alice_home = own(&home.mkdir("alice").unwrap(), 1000, 0o700).clone();
+        own(&alice_home.touch("notes").unwrap(), 1000, 0o600);
+        root.soft_link("notes", "/home/alice/notes").unwrap();
+
+        let tmp = own(&root.mkdir("tmp").unwrap(), 0, 0o1777).clone();
+        own(&tmp.touch("shared").unwrap(), 1000, 0o644);
+
+        let bin = root.mkdir("bin").unwrap();
+        own(&bin.touch("sh").unwrap(), 0, 0o755);
+        own(&bin.touch("data").unwrap(), 0, 0o644);
+
+        let root_user = Credentials::root();
+        let alice = Credentials::new(1000, 1000);
+        let bob = Credentials::new(2000, 2000);
+
+        // Lookup needs search permission on every directory, also on the way to link targets
+        let open = |path: &str, credentials: &Credentials| {
+            root.open_as(path, Some(&root), Some(credentials))
+        };
+        assert!(open("/home/alice/notes", &alice).is_ok());
+        assert!(open("/home/alice/notes", &root_user).is_ok());
+        assert_eq!(
+            open("/home/alice/notes", &bob).err(),
+            Some(FileSystemError::AccessDenied)
+        );
+        assert_eq!(
+            open("/notes", &bob).err(),
+            Some(FileSystemError::AccessDenied)
+        );
+        assert!(open("/notes", &alice).is_ok());
+
+        let shared = open("/tmp/shared", &bob).unwrap();
+        assert!(shared.check_open(OpenFlags::O_RDONLY, &bob).is_ok());
+        assert!(shared.check_open(OpenFlags::O_PATH, &bob).is_ok());
+        assert!(shared.check_open(OpenFlags::O_RDWR, &alice).is_ok());
+        for flags in [
+            OpenFlags::O_WRONLY,
+            OpenFlags::O_RDWR,
+            OpenFlags::O_RDONLY | OpenFlags::O_TRUNC,
+        ] {
+            assert_eq!(
+                shared.check_open(flags, &bob),
+                Err(FileSystemError::AccessDenied)
+            );
+        }
+
+        // Only regular files someone may execute can be run, even by root
+        let sh = open("/bin/sh", &bob).unwrap();
+        let data = open("/bin/data", &bob).unwrap();
+        assert!(sh.check_exec(&bob).is_ok());
+        assert_eq!(
+            data.check_exec(&root_user),
+            Err(FileSystemError::AccessDenied)
+        );
+        assert_eq!(
+            bin.check_exec(&root_user),
+            Err(FileSystemError::AccessDenied)
+        );
+
+        // Anyone may create in /tmp, but only remove their own files
+        assert_eq!(
+            tmp.check_delete("shared", &bob),
+            Err(FileSystemError::NotPermitted)
+        );
+        assert!(tmp.check_delete("shared", &root_user).is_ok());
+        assert!(tmp.check_delete("shared", &alice).is_ok());
+        assert_eq!(
+            home.check_delete("alice", &alice),
+            Err(FileSystemError::AccessDenied)
+        );
+
+        let notes = open("/home/alice/notes", &alice).unwrap();
+        let chmod = InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(0o644)),
+            ..Default::default()
+        };
+        assert_eq!(
+            notes.set_attributes_as(&chmod, &bob),
+            Err(FileSystemError::NotPermitted)
+        );
+        notes.set_attributes_as(&chmod, &alice).unwrap();
+        assert_eq!(stat(&notes).mode.bits(), 0o100644);
+
+        let chown = InodeAttributes {
+            uid: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(
+            notes.set_attributes_as(&chown, &alice),
+            Err(FileSystemError::NotPermitted)
+        );
+        notes.set_attributes_as(&chown, &root_user).unwrap();
+        assert_eq!(stat(&notes).uid, 2000);
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let (fs, _root) = create(None);
+        let inode = fs.root_dir();
+
+        assert_eq!(inode.touch("..").err(), Some(FileSystemError::InvalidInput));
+        assert_eq!(inode.mkdir("").err(), Some(FileSystemError::InvalidInput));
+        assert_eq!(
+            inode.touch("a/b").err(),
+            Some(FileSystemError::PathContainsInvalidCharacter)
+        );
+        assert_eq!(
+            inode.touch(&"x".repeat(256)).err(),
+            Some(FileSystemError::PathNameLengthExceeded)
+        );
+        assert!(inode.touch(&"x".repeat(255)).is_ok());
+    }
+}
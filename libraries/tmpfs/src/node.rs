@@ -0,0 +1,501 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, ExtendedAttributes, FileStatistics, FileStatisticsMode,
+    FileSystemError, FileSystemResult, IInode, InodeAttributes, InodeMetadata, XattrFlags,
+};
+use hermit_sync::SpinMutex;
+use timing::TimeSpec;
+
+use crate::{
+    file::{Pages, Space, PAGE_SIZE},
+    TmpFileSystem,
+};
+
+/// Longest name of a directory entry.
+const NAME_MAX: usize = 255;
+
+/// What Linux counts every directory entry as in the size of a directory.
+const DIRECTORY_ENTRY_SIZE: u64 = 20;
+
+pub(crate) enum Content {
+    File(Pages),
+    Directory(BTreeMap<String, Arc<Node>>),
+    Symlink(String),
+}
+
+/// A file, directory or symlink, shared by all its names.
+pub(crate) struct Node {
+    pub number: u64,
+    space: Arc<Space>,
+    pub inner: SpinMutex<NodeInner>,
+}
+
+pub(crate) struct NodeInner {
+    pub content: Content,
+    /// Directories count their own entry, `.` and the `..` of each subdirectory. A removed
+    /// directory has none left.
+    pub link_count: u32,
+    /// The permission bits, the type comes from the content
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub atime: TimeSpec,
+    pub mtime: TimeSpec,
+    pub ctime: TimeSpec,
+    pub xattrs: ExtendedAttributes,
+}
+
+impl Node {
+    /// Inode number of the root directory, like Linux has.
+    pub const ROOT_NUMBER: u64 = 1;
+
+    pub fn new(number: u64, space: Arc<Space>, content: Content, now: TimeSpec) -> Arc<Node> {
+        let (link_count, mode) = match content {
+            Content::File(_) => (1, 0o644),
+            Content::Directory(_) => (2, 0o755),
+            Content::Symlink(_) => (1, 0o777),
+        };
+
+        Arc::new(Node {
+            number,
+            space,
+            inner: SpinMutex::new(NodeInner {
+                content,
+                link_count,
+                mode,
+                uid: 0,
+                gid: 0,
+                atime: now,
+                mtime: now,
+                ctime: now,
+                xattrs: ExtendedAttributes::new(),
+            }),
+        })
+    }
+}
+
+impl NodeInner {
+    pub fn entry_type(&self) -> DirectoryEntryType {
+        match self.content {
+            Content::File(_) => DirectoryEntryType::File,
+            Content::Directory(_) => DirectoryEntryType::Directory,
+            Content::Symlink(_) => DirectoryEntryType::Symlink,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match &self.content {
+            Content::File(pages) => pages.size(),
+            Content::Directory(entries) => (entries.len() as u64 + 2) * DIRECTORY_ENTRY_SIZE,
+            Content::Symlink(target) => target.len() as u64,
+        }
+    }
+
+    fn entries(&mut self) -> FileSystemResult<&mut BTreeMap<String, Arc<Node>>> {
+        match &mut self.content {
+            Content::Directory(_) if self.link_count == 0 => Err(FileSystemError::NotFound),
+            Content::Directory(entries) => Ok(entries),
+            _ => Err(FileSystemError::NotADirectory),
+        }
+    }
+
+    fn pages(&mut self) -> FileSystemResult<&mut Pages> {
+        match &mut self.content {
+            Content::File(pages) => Ok(pages),
+            _ => Err(FileSystemError::NotAFile),
+        }
+    }
+
+    fn is_empty_dir(&self) -> bool {
+        matches!(&self.content, Content::Directory(entries) if entries.is_empty())
+    }
+
+    /// Records a change of the contents.
+    fn modified(&mut self, now: TimeSpec) {
+        self.mtime = now;
+        self.ctime = now;
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        if let Content::File(pages) = &mut self.inner.get_mut().content {
+            pages.release(&self.space);
+        }
+    }
+}
+
+fn check_name(name: &str) -> FileSystemResult<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    if name.len() > NAME_MAX {
+        return Err(FileSystemError::PathNameLengthExceeded);
+    }
+
+    if name.contains(['/', '\0']) {
+        return Err(FileSystemError::PathContainsInvalidCharacter);
+    }
+
+    Ok(())
+}
+
+/// A name of a [`Node`] handed out to the directory tree.
+pub struct TmpInode {
+    fs: Arc<TmpFileSystem>,
+    node: Arc<Node>,
+    name: String,
+    entry_type: DirectoryEntryType,
+}
+
+impl TmpInode {
+    pub(crate) fn new(fs: Arc<TmpFileSystem>, node: Arc<Node>, name: &str) -> Arc<TmpInode> {
+        let entry_type = node.inner.lock().entry_type();
+
+        Arc::new(TmpInode {
+            fs,
+            node,
+            name: name.to_string(),
+            entry_type,
+        })
+    }
+
+    /// Adds a new node named `name` to this directory.
+    fn create(&self, name: &str, content: Content) -> FileSystemResult<Arc<dyn IInode>> {
+        check_name(name)?;
+
+        let mut dir = self.node.inner.lock();
+        let is_dir = matches!(content, Content::Directory(_));
+
+        if dir.entries()?.contains_key(name) {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        let now = self.fs.now();
+        let node = self.fs.node(content, now);
+
+        dir.entries()?.insert(name.to_string(), node.clone());
+        dir.modified(now);
+
+        if is_dir {
+            dir.link_count += 1;
+        }
+
+        Ok(TmpInode::new(self.fs.clone(), node, name))
+    }
+
+    fn unlink(&self, name: &str, directory_wanted: bool) -> FileSystemResult<()> {
+        let mut dir = self.node.inner.lock();
+        let now = self.fs.now();
+
+        let node = dir
+            .entries()?
+            .get(name)
+            .cloned()
+            .ok_or(FileSystemError::NotFound)?;
+
+        let mut inner = node.inner.lock();
+
+        match (directory_wanted, &inner.content) {
+            (false, Content::Directory(_)) => return Err(FileSystemError::NotAFile),
+            (true, Content::Directory(_)) if !inner.is_empty_dir() => {
+                return Err(FileSystemError::DirectoryNotEmpty)
+            }
+            (true, Content::Directory(_)) => {
+                inner.link_count = 0;
+                dir.link_count -= 1;
+            }
+            (true, _) => return Err(FileSystemError::NotADirectory),
+            (false, _) => inner.link_count -= 1,
+        }
+
+        inner.ctime = now;
+        drop(inner);
+
+        dir.entries()?.remove(name);
+        dir.modified(now);
+
+        Ok(())
+    }
+}
+
+impl IInode for TmpInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry_type,
+            size: self.node.inner.lock().size() as usize,
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        let mut inner = self.node.inner.lock();
+        let read = inner.pages()?.read(offset as u64, buffer);
+
+        inner.atime = self.fs.now();
+
+        Ok(read)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        let mut inner = self.node.inner.lock();
+        let written = inner
+            .pages()?
+            .write(&self.fs.space, offset as u64, buffer)?;
+
+        inner.modified(self.fs.now());
+
+        Ok(written)
+    }
+
+    fn mkdir(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.create(name, Content::Directory(BTreeMap::new()))
+    }
+
+    fn rmdir(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, true)
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        self.unlink(name, false)
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        self.create(name, Content::File(Pages::default()))
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        let mut dir = self.node.inner.lock();
+
+        let entries = dir
+            .entries()?
+            .iter()
+            .map(|(name, node)| DirectoryEntry {
+                filename: name.clone(),
+                entry_type: node.inner.lock().entry_type(),
+            })
+            .collect();
+
+        dir.atime = self.fs.now();
+
+        Ok(entries)
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let node = self
+            .node
+            .inner
+            .lock()
+            .entries()?
+            .get(name)
+            .cloned()
+            .ok_or(FileSystemError::NotFound)?;
+
+        Ok(TmpInode::new(self.fs.clone(), node, name))
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let inner = self.node.inner.lock();
+
+        let pages = match &inner.content {
+            Content::File(pages) => pages.count() as u64,
+            _ => 0,
+        };
+
+        stat.device_id = 0;
+        stat.inode_id = self.node.number;
+        stat.mode = FileStatisticsMode::from_bits_retain(
+            FileStatisticsMode::from(self.entry_type).bits() | inner.mode,
+        );
+        stat.link_count = inner.link_count;
+        stat.uid = inner.uid;
+        stat.gid = inner.gid;
+        stat.rdev = 0;
+        stat.size = inner.size();
+        stat.block_size = PAGE_SIZE as u32;
+        stat.block_count = pages * (PAGE_SIZE as u64 / 512);
+        stat.atime = inner.atime;
+        stat.mtime = inner.mtime;
+        stat.ctime = inner.ctime;
+
+        Ok(())
+    }
+
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        let mut inner = self.node.inner.lock();
+
+        if let Some(mode) = attributes.mode {
+            inner.mode = mode.bits() & 0o7777;
+        }
+
+        if let Some(uid) = attributes.uid {
+            inner.uid = uid;
+        }
+
+        if let Some(gid) = attributes.gid {
+            inner.gid = gid;
+        }
+
+        if let Some(atime) = attributes.atime {
+            inner.atime = atime;
+        }
+
+        if let Some(mtime) = attributes.mtime {
+            inner.mtime = mtime;
+        }
+
+        inner.ctime = self.fs.now();
+
+        Ok(())
+    }
+
+    fn get_xattr(&self, name: &str) -> FileSystemResult<Vec<u8>> {
+        self.node.inner.lock().xattrs.get(name)
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> FileSystemResult<()> {
+        let mut inner = self.node.inner.lock();
+
+        inner.xattrs.set(name, value, flags)?;
+        inner.ctime = self.fs.now();
+
+        Ok(())
+    }
+
+    fn list_xattr(&self) -> FileSystemResult<Vec<String>> {
+        Ok(self.node.inner.lock().xattrs.list())
+    }
+
+    fn remove_xattr(&self, name: &str) -> FileSystemResult<()> {
+        let mut inner = self.node.inner.lock();
+
+        inner.xattrs.remove(name)?;
+        inner.ctime = self.fs.now();
+
+        Ok(())
+    }
+
+    fn hard_link(&self, name: &str, inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let Some(source) = inode.downcast_ref::<TmpInode>() else {
+            return Err(FileSystemError::NotPermitted);
+        };
+
+        if !Arc::ptr_eq(&source.fs, &self.fs) || source.entry_type == DirectoryEntryType::Directory
+        {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        check_name(name)?;
+
+        let mut dir = self.node.inner.lock();
+
+        if dir.entries()?.contains_key(name) {
+            return Err(FileSystemError::AlreadyExists);
+        }
+
+        let now = self.fs.now();
+
+        {
+            let mut inner = source.node.inner.lock();
+
+            // Removed everywhere already, only still open
+            if inner.link_count == 0 {
+                return Err(FileSystemError::NotFound);
+            }
+
+            inner.link_count += 1;
+            inner.ctime = now;
+        }
+
+        dir.entries()?.insert(name.to_string(), source.node.clone());
+        dir.modified(now);
+
+        Ok(())
+    }
+
+    fn soft_link(&self, name: &str, point_to: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        if point_to.is_empty() {
+            return Err(FileSystemError::NotFound);
+        }
+
+        self.create(name, Content::Symlink(point_to.to_string()))
+    }
+
+    fn resolve_link(&self) -> Option<String> {
+        match &self.node.inner.lock().content {
+            Content::Symlink(target) => Some(target.clone()),
+            _ => None,
+        }
+    }
+
+    fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+        let mut inner = self.node.inner.lock();
+
+        inner.pages()?.resize(&self.fs.space, new_size)?;
+        inner.modified(self.fs.now());
+
+        Ok(new_size)
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str) -> FileSystemResult<()> {
+        check_name(new_name)?;
+
+        let mut dir = self.node.inner.lock();
+        let now = self.fs.now();
+
+        let source = dir
+            .entries()?
+            .get(old_name)
+            .cloned()
+            .ok_or(FileSystemError::NotFound)?;
+
+        let target = dir.entries()?.get(new_name).cloned();
+
+        if let Some(target) = &target {
+            // Two names of the same file, nothing to do
+            if Arc::ptr_eq(&source, target) {
+                return Ok(());
+            }
+
+            let source_is_dir = matches!(source.inner.lock().content, Content::Directory(_));
+            let mut target_inner = target.inner.lock();
+
+            match (source_is_dir, &target_inner.content) {
+                (true, Content::Directory(_)) if !target_inner.is_empty_dir() => {
+                    return Err(FileSystemError::DirectoryNotEmpty)
+                }
+                (true, Content::Directory(_)) => {
+                    target_inner.link_count = 0;
+                    dir.link_count -= 1;
+                }
+                (true, _) => return Err(FileSystemError::NotADirectory),
+                (false, Content::Directory(_)) => return Err(FileSystemError::NotAFile),
+                (false, _) => target_inner.link_count -= 1,
+            }
+
+            target_inner.ctime = now;
+        }
+
+        let entries = dir.entries()?;
+        entries.remove(old_name);
+        entries.insert(new_name.to_string(), source.clone());
+
+        source.inner.lock().ctime = now;
+        dir.modified(now);
+
+        Ok(())
+    }
+}
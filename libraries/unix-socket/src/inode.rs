@@ -0,0 +1,61 @@
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+};
+use filesystem_abstractions::{
+    DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemResult, IInode, InodeMetadata,
+};
+use timing::TimeSpec;
+
+use crate::UnixSocket;
+
+/// The filesystem entry created by binding a socket to a path.
+///
+/// It only refers to the socket weakly, the entry outlives the socket until it is unlinked,
+/// and connecting to a stale entry is refused.
+pub struct SocketInode {
+    name: String,
+    socket: Weak<UnixSocket>,
+}
+
+impl SocketInode {
+    pub fn new(name: &str, socket: Weak<UnixSocket>) -> SocketInode {
+        SocketInode {
+            name: name.to_string(),
+            socket,
+        }
+    }
+
+    pub fn socket(&self) -> Option<Arc<UnixSocket>> {
+        self.socket.upgrade()
+    }
+}
+
+impl IInode for SocketInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: DirectoryEntryType::Socket,
+            size: 0,
+        }
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        stat.device_id = 0;
+        stat.inode_id = 0;
+        stat.mode = FileStatisticsMode::SOCKET | FileStatisticsMode::from_bits_retain(0o777);
+        stat.link_count = 1;
+        stat.uid = 0;
+        stat.gid = 0;
+        stat.size = 0;
+        stat.block_size = 512;
+        stat.block_count = 0;
+        stat.rdev = 0;
+
+        stat.ctime = TimeSpec::zero();
+        stat.mtime = TimeSpec::zero();
+        stat.atime = TimeSpec::zero();
+
+        Ok(())
+    }
+}
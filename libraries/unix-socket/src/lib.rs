@@ -0,0 +1,1016 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use constants::ErrNo;
+use filesystem_abstractions::{DirectoryTreeNode, FileSystemError, IFile, IInode};
+use hermit_sync::SpinMutex;
+use socket_abstractions::{
+    ISocket, MessageFlags, PollEvents, ReceivedMessage, Shutdown, SocketAddress, SocketType,
+    UnixAddress, AF_UNIX, SOL_SOCKET, SO_ACCEPTCONN, SO_DOMAIN, SO_ERROR, SO_KEEPALIVE,
+    SO_PASSCRED, SO_RCVBUF, SO_REUSEADDR, SO_SNDBUF, SO_TYPE,
+};
+use threading::sync::WaitQueue;
+
+use crate::stream::{StreamBuffer, STREAM_BUFFER_SIZE};
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod inode;
+mod namespace;
+mod stream;
+
+pub use inode::SocketInode;
+pub use namespace::UnixNamespace;
+
+/// Datagrams waiting in a receive queue before senders get `EAGAIN`
+const DATAGRAM_QUEUE_LIMIT: usize = 64;
+
+struct Datagram {
+    data: Vec<u8>,
+    source: UnixAddress,
+    rights: Vec<Arc<dyn IFile>>,
+}
+
+enum SocketState {
+    Unconnected,
+    Listening {
+        backlog: usize,
+        pending: VecDeque<Arc<UnixSocket>>,
+    },
+    Connected {
+        peer: UnixAddress,
+        rx: Arc<SpinMutex<StreamBuffer>>,
+        tx: Arc<SpinMutex<StreamBuffer>>,
+    },
+}
+
+struct SocketOptions {
+    send_buffer: usize,
+    receive_buffer: usize,
+    reuse_address: bool,
+    keep_alive: bool,
+    pass_credentials: bool,
+}
+
+struct UnixSocketInner {
+    local: UnixAddress,
+    state: SocketState,
+    // Datagram sockets only
+    datagrams: VecDeque<Datagram>,
+    default_destination: Option<(UnixAddress, Weak<UnixSocket>)>,
+    read_shutdown: bool,
+    write_shutdown: bool,
+    options: SocketOptions,
+}
+
+/// An `AF_UNIX` socket of either `SOCK_STREAM` or `SOCK_DGRAM` type.
+pub struct UnixSocket {
+    socket_type: SocketType,
+    namespace: Arc<UnixNamespace>,
+    this: Weak<UnixSocket>,
+    inner: SpinMutex<UnixSocketInner>,
+}
+
+impl UnixSocket {
+    /// Creates an unbound socket, finding others in `namespace`.
+    pub fn new(socket_type: SocketType, namespace: Arc<UnixNamespace>) -> Arc<UnixSocket> {
+        Self::with_state(
+            socket_type,
+            namespace,
+            UnixAddress::Unnamed,
+            SocketState::Unconnected,
+        )
+    }
+
+    /// Creates two connected, unnamed sockets as `socketpair(2)` does.
+    pub fn new_pair(
+        socket_type: SocketType,
+        namespace: Arc<UnixNamespace>,
+    ) -> (Arc<UnixSocket>, Arc<UnixSocket>) {
+        match socket_type {
+            SocketType::Stream => {
+                let (first, second) =
+                    Self::connected_states(UnixAddress::Unnamed, UnixAddress::Unnamed);
+
+                (
+                    Self::with_state(socket_type, namespace.clone(), UnixAddress::Unnamed, first),
+                    Self::with_state(socket_type, namespace, UnixAddress::Unnamed, second),
+                )
+            }
+            SocketType::Datagram => {
+                let first = Self::new(socket_type, namespace.clone());
+                let second = Self::new(socket_type, namespace);
+
+                first.inner.lock().default_destination =
+                    Some((UnixAddress::Unnamed, Arc::downgrade(&second)));
+                second.inner.lock().default_destination =
+                    Some((UnixAddress::Unnamed, Arc::downgrade(&first)));
+
+                (first, second)
+            }
+        }
+    }
+
+    fn with_state(
+        socket_type: SocketType,
+        namespace: Arc<UnixNamespace>,
+        local: UnixAddress,
+        state: SocketState,
+    ) -> Arc<UnixSocket> {
+        Arc::new_cyclic(|this| UnixSocket {
+            socket_type,
+            namespace,
+            this: this.clone(),
+            inner: SpinMutex::new(UnixSocketInner {
+                local,
+                state,
+                datagrams: VecDeque::new(),
+                default_destination: None,
+                read_shutdown: false,
+                write_shutdown: false,
+                options: SocketOptions {
+                    send_buffer: STREAM_BUFFER_SIZE,
+                    receive_buffer: STREAM_BUFFER_SIZE,
+                    reuse_address: false,
+                    keep_alive: false,
+                    pass_credentials: false,
+                },
+            }),
+        })
+    }
+
+    /// Returns the states of the two ends of a new stream connection.
+    fn connected_states(first: UnixAddress, second: UnixAddress) -> (SocketState, SocketState) {
+        let a_to_b = Arc::new(SpinMutex::new(StreamBuffer::new()));
+        let b_to_a = Arc::new(SpinMutex::new(StreamBuffer::new()));
+
+        (
+            SocketState::Connected {
+                peer: second,
+                rx: b_to_a.clone(),
+                tx: a_to_b.clone(),
+            },
+            SocketState::Connected {
+                peer: first,
+                rx: a_to_b,
+                tx: b_to_a,
+            },
+        )
+    }
+
+    /// Wakes whoever waits on a socket, after this one did something that may make it ready.
+    fn changed(&self) {
+        self.namespace.readiness.notify_all();
+    }
+
+    fn unix_address(address: &SocketAddress) -> Result<&UnixAddress, ErrNo> {
+        match address {
+            SocketAddress::Unix(unix) => Ok(unix),
+            _ => Err(ErrNo::InvalidArgument),
+        }
+    }
+
+    fn bind_pathname(&self, path: &str) -> Result<(), ErrNo> {
+        if !path::is_path_fully_qualified(path) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let root = self.namespace.root();
+
+        let directory = path::get_directory_name(path).unwrap_or(path::ROOT_STR);
+        let name = path::get_filename(path);
+
+        if name.is_empty() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let parent = root
+            .open(directory, Some(&root))
+            .map_err(FileSystemError::to_errno)?;
+
+        if parent.open_child(name).is_ok() {
+            return Err(ErrNo::AddressAlreadyInUse);
+        }
+
+        let inode: Arc<dyn IInode> = Arc::new(SocketInode::new(name, self.this.clone()));
+        let node = DirectoryTreeNode::from_inode(Some(parent.clone()), &inode, Some(name));
+
+        parent
+            .mount_as(node, Some(name))
+            .map(|_| ())
+            .map_err(|_| ErrNo::AddressAlreadyInUse)
+    }
+
+    fn lookup(&self, address: &UnixAddress) -> Result<Arc<UnixSocket>, ErrNo> {
+        let target = match address {
+            UnixAddress::Unnamed => return Err(ErrNo::InvalidArgument),
+            UnixAddress::Abstract(name) => self.namespace.lookup_abstract(name),
+            UnixAddress::Pathname(path) => {
+                let root = self.namespace.root();
+
+                let node = root
+                    .open(path, Some(&root))
+                    .map_err(FileSystemError::to_errno)?;
+
+                let inode = node.inode().ok_or(ErrNo::ConnectionRefused)?;
+
+                inode
+                    .downcast_ref::<SocketInode>()
+                    .ok_or(ErrNo::ConnectionRefused)?
+                    .socket()
+            }
+        };
+
+        let target = target.ok_or(ErrNo::ConnectionRefused)?;
+
+        if target.socket_type != self.socket_type {
+            return Err(ErrNo::ProtocolWrongTypeForSocket);
+        }
+
+        Ok(target)
+    }
+
+    fn connect_stream(&self, address: &UnixAddress) -> Result<(), ErrNo> {
+        let local = {
+            let inner = self.inner.lock();
+
+            match inner.state {
+                SocketState::Unconnected => inner.local.clone(),
+                SocketState::Connected { .. } => {
+                    return Err(ErrNo::TransportEndpointIsAlreadyConnected)
+                }
+                SocketState::Listening { .. } => return Err(ErrNo::InvalidArgument),
+            }
+        };
+
+        let listener = self.lookup(address)?;
+
+        let (ours, theirs) = Self::connected_states(local, address.clone());
+
+        {
+            let mut listener_inner = listener.inner.lock();
+            let listener_local = listener_inner.local.clone();
+
+            match &mut listener_inner.state {
+                SocketState::Listening { backlog, pending } => {
+                    if pending.len() >= *backlog {
+                        return Err(ErrNo::ResourceTemporarilyUnavailable);
+                    }
+
+                    let accepted = Self::with_state(
+                        self.socket_type,
+                        self.namespace.clone(),
+                        listener_local,
+                        theirs,
+                    );
+
+                    pending.push_back(accepted);
+                }
+                _ => return Err(ErrNo::ConnectionRefused),
+            }
+        }
+
+        self.inner.lock().state = ours;
+        self.changed();
+
+        Ok(())
+    }
+
+    fn send_datagram(
+        &self,
+        data: &[u8],
+        rights: Vec<Arc<dyn IFile>>,
+        destination: Option<&SocketAddress>,
+    ) -> Result<usize, ErrNo> {
+        let (source, connected) = {
+            let inner = self.inner.lock();
+
+            if inner.write_shutdown {
+                return Err(ErrNo::BrokenPipe);
+            }
+
+            (inner.local.clone(), inner.default_destination.clone())
+        };
+
+        let target = match destination {
+            Some(address) => self.lookup(Self::unix_address(address)?)?,
+            None => connected
+                .ok_or(ErrNo::DestinationAddressRequired)?
+                .1
+                .upgrade()
+                .ok_or(ErrNo::ConnectionRefused)?,
+        };
+
+        let mut target_inner = target.inner.lock();
+
+        if data.len() > target_inner.options.receive_buffer {
+            return Err(ErrNo::MessageTooLong);
+        }
+
+        if target_inner.read_shutdown {
+            return Err(ErrNo::ConnectionRefused);
+        }
+
+        if target_inner.datagrams.len() >= DATAGRAM_QUEUE_LIMIT {
+            return Err(ErrNo::ResourceTemporarilyUnavailable);
+        }
+
+        target_inner.datagrams.push_back(Datagram {
+            data: data.to_vec(),
+            source,
+            rights,
+        });
+
+        drop(target_inner);
+        self.changed();
+
+        Ok(data.len())
+    }
+
+    fn receive_datagram(
+        &self,
+        buf: &mut [u8],
+        flags: MessageFlags,
+    ) -> Result<ReceivedMessage, ErrNo> {
+        let mut inner = self.inner.lock();
+
+        let datagram = match flags.contains(MessageFlags::MSG_PEEK) {
+            true => inner.datagrams.front().map(|d| Datagram {
+                data: d.data.clone(),
+                source: d.source.clone(),
+                rights: d.rights.clone(),
+            }),
+            false => inner.datagrams.pop_front(),
+        };
+
+        match datagram {
+            Some(datagram) => {
+                let length = datagram.data.len().min(buf.len());
+                buf[..length].copy_from_slice(&datagram.data[..length]);
+
+                if !flags.contains(MessageFlags::MSG_PEEK) {
+                    self.changed();
+                }
+
+                Ok(ReceivedMessage {
+                    length,
+                    full_length: datagram.data.len(),
+                    source: SocketAddress::Unix(datagram.source),
+                    rights: datagram.rights,
+                })
+            }
+            None if inner.read_shutdown => Ok(ReceivedMessage {
+                length: 0,
+                full_length: 0,
+                source: SocketAddress::Unix(UnixAddress::Unnamed),
+                rights: Vec::new(),
+            }),
+            None => Err(ErrNo::ResourceTemporarilyUnavailable),
+        }
+    }
+}
+
+impl ISocket for UnixSocket {
+    fn family(&self) -> u16 {
+        AF_UNIX
+    }
+
+    fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    fn bind(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        let address = Self::unix_address(address)?;
+
+        // Held throughout, so that a socket is never bound twice by binds racing each other
+        let mut inner = self.inner.lock();
+
+        if inner.local != UnixAddress::Unnamed {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        inner.local = match address {
+            UnixAddress::Unnamed => UnixAddress::Abstract(self.namespace.autobind(&self.this)?),
+            UnixAddress::Abstract(name) => {
+                self.namespace.bind_abstract(name, self.this.clone())?;
+                address.clone()
+            }
+            UnixAddress::Pathname(path) => {
+                self.bind_pathname(path)?;
+                address.clone()
+            }
+        };
+
+        Ok(())
+    }
+
+    fn listen(&self, backlog: usize) -> Result<(), ErrNo> {
+        if self.socket_type != SocketType::Stream {
+            return Err(ErrNo::OperationNotSupported);
+        }
+
+        let mut inner = self.inner.lock();
+
+        if inner.local == UnixAddress::Unnamed {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let backlog = backlog.max(1);
+
+        match &mut inner.state {
+            SocketState::Unconnected => {
+                inner.state = SocketState::Listening {
+                    backlog,
+                    pending: VecDeque::new(),
+                }
+            }
+            SocketState::Listening { backlog: limit, .. } => *limit = backlog,
+            SocketState::Connected { .. } => return Err(ErrNo::InvalidArgument),
+        }
+
+        Ok(())
+    }
+
+    fn accept(&self) -> Result<Arc<dyn ISocket>, ErrNo> {
+        if self.socket_type != SocketType::Stream {
+            return Err(ErrNo::OperationNotSupported);
+        }
+
+        let accepted = match &mut self.inner.lock().state {
+            SocketState::Listening { pending, .. } => pending
+                .pop_front()
+                .ok_or(ErrNo::ResourceTemporarilyUnavailable)?,
+            _ => return Err(ErrNo::InvalidArgument),
+        };
+
+        // The backlog has room again
+        self.changed();
+
+        Ok(accepted)
+    }
+
+    fn connect(&self, address: &SocketAddress) -> Result<(), ErrNo> {
+        match self.socket_type {
+            SocketType::Stream => self.connect_stream(Self::unix_address(address)?),
+            SocketType::Datagram => {
+                let destination = match address {
+                    SocketAddress::Unspecified => None,
+                    address => {
+                        let unix = Self::unix_address(address)?;
+                        let target = self.lookup(unix)?;
+
+                        Some((unix.clone(), Arc::downgrade(&target)))
+                    }
+                };
+
+                self.inner.lock().default_destination = destination;
+
+                Ok(())
+            }
+        }
+    }
+
+    fn send(
+        &self,
+        data: &[u8],
+        rights: Vec<Arc<dyn IFile>>,
+        destination: Option<&SocketAddress>,
+    ) -> Result<usize, ErrNo> {
+        if self.socket_type == SocketType::Datagram {
+            return self.send_datagram(data, rights, destination);
+        }
+
+        let tx = match &self.inner.lock().state {
+            SocketState::Connected { tx, .. } if destination.is_none() => tx.clone(),
+            SocketState::Connected { .. } => {
+                return Err(ErrNo::TransportEndpointIsAlreadyConnected)
+            }
+            _ => return Err(ErrNo::TransportEndpointIsNotConnected),
+        };
+
+        let written = tx.lock().write(data, rights)?;
+        self.changed();
+
+        Ok(written)
+    }
+
+    fn receive(&self, buf: &mut [u8], flags: MessageFlags) -> Result<ReceivedMessage, ErrNo> {
+        if self.socket_type == SocketType::Datagram {
+            return self.receive_datagram(buf, flags);
+        }
+
+        let (peer, rx) = match &self.inner.lock().state {
+            SocketState::Connected { peer, rx, .. } => (peer.clone(), rx.clone()),
+            _ => return Err(ErrNo::TransportEndpointIsNotConnected),
+        };
+
+        let peek = flags.contains(MessageFlags::MSG_PEEK);
+        let (length, rights) = rx.lock().read(buf, peek)?;
+
+        if !peek {
+            self.changed();
+        }
+
+        Ok(ReceivedMessage {
+            length,
+            full_length: length,
+            source: SocketAddress::Unix(peer),
+            rights,
+        })
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), ErrNo> {
+        let mut inner = self.inner.lock();
+
+        if self.socket_type == SocketType::Datagram {
+            inner.read_shutdown |= how.read();
+            inner.write_shutdown |= how.write();
+        } else {
+            match &inner.state {
+                SocketState::Connected { rx, tx, .. } => {
+                    if how.read() {
+                        rx.lock().reader_closed = true;
+                    }
+
+                    if how.write() {
+                        tx.lock().writer_closed = true;
+                    }
+                }
+                _ => return Err(ErrNo::TransportEndpointIsNotConnected),
+            }
+        }
+
+        drop(inner);
+        self.changed();
+
+        Ok(())
+    }
+
+    fn local_address(&self) -> SocketAddress {
+        SocketAddress::Unix(self.inner.lock().local.clone())
+    }
+
+    fn peer_address(&self) -> Result<SocketAddress, ErrNo> {
+        let inner = self.inner.lock();
+
+        match (&inner.state, &inner.default_destination) {
+            (SocketState::Connected { peer, .. }, _) => Ok(SocketAddress::Unix(peer.clone())),
+            (_, Some((peer, _))) => Ok(SocketAddress::Unix(peer.clone())),
+            _ => Err(ErrNo::TransportEndpointIsNotConnected),
+        }
+    }
+
+    fn poll(&self) -> PollEvents {
+        let inner = self.inner.lock();
+
+        if self.socket_type == SocketType::Datagram {
+            let mut events = PollEvents::POLLOUT;
+
+            if !inner.datagrams.is_empty() || inner.read_shutdown {
+                events |= PollEvents::POLLIN;
+            }
+
+            return events;
+        }
+
+        match &inner.state {
+            SocketState::Unconnected => PollEvents::POLLOUT | PollEvents::POLLHUP,
+            SocketState::Listening { pending, .. } => match pending.is_empty() {
+                true => PollEvents::empty(),
+                false => PollEvents::POLLIN,
+            },
+            SocketState::Connected { rx, tx, .. } => {
+                let rx = rx.lock();
+                let tx = tx.lock();
+
+                let mut events = PollEvents::empty();
+
+                if rx.len() != 0 || rx.writer_closed || rx.reader_closed {
+                    events |= PollEvents::POLLIN;
+                }
+
+                if rx.writer_closed {
+                    events |= PollEvents::POLLRDHUP;
+                }
+
+                if tx.reader_closed {
+                    events |= PollEvents::POLLOUT | PollEvents::POLLERR;
+                } else if tx.space() != 0 && !tx.writer_closed {
+                    events |= PollEvents::POLLOUT;
+                }
+
+                if rx.writer_closed && tx.reader_closed {
+                    events |= PollEvents::POLLHUP;
+                }
+
+                events
+            }
+        }
+    }
+
+    fn readiness(&self) -> &WaitQueue {
+        &self.namespace.readiness
+    }
+
+    fn get_option(&self, level: usize, name: usize) -> Result<usize, ErrNo> {
+        if level != SOL_SOCKET {
+            return Err(ErrNo::ProtocolNotAvailable);
+        }
+
+        let inner = self.inner.lock();
+
+        match name {
+            SO_TYPE => Ok(self.socket_type as usize),
+            SO_DOMAIN => Ok(AF_UNIX as usize),
+            SO_ERROR => Ok(0),
+            SO_ACCEPTCONN => Ok(matches!(inner.state, SocketState::Listening { .. }) as usize),
+            SO_SNDBUF => Ok(inner.options.send_buffer),
+            SO_RCVBUF => Ok(inner.options.receive_buffer),
+            SO_REUSEADDR => Ok(inner.options.reuse_address as usize),
+            SO_KEEPALIVE => Ok(inner.options.keep_alive as usize),
+            SO_PASSCRED => Ok(inner.options.pass_credentials as usize),
+            _ => Err(ErrNo::ProtocolNotAvailable),
+        }
+    }
+
+    fn set_option(&self, level: usize, name: usize, value: usize) -> Result<(), ErrNo> {
+        if level != SOL_SOCKET {
+            return Err(ErrNo::ProtocolNotAvailable);
+        }
+
+        let options = &mut self.inner.lock().options;
+
+        match name {
+            SO_SNDBUF => options.send_buffer = value,
+            SO_RCVBUF => options.receive_buffer = value,
+            SO_REUSEADDR => options.reuse_address = value != 0,
+            SO_KEEPALIVE => options.keep_alive = value != 0,
+            SO_PASSCRED => options.pass_credentials = value != 0,
+            _ => return Err(ErrNo::ProtocolNotAvailable),
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+
+        if let SocketState::Connected { rx, tx, .. } = &inner.state {
+            rx.lock().reader_closed = true;
+            tx.lock().writer_closed = true;
+
+            // The peer sees the end of file or a broken pipe
+            self.namespace.readiness.notify_all();
+        }
+
+        if let UnixAddress::Abstract(name) = &inner.local {
+            self.namespace.release_abstract(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use filesystem_abstractions::DirectoryEntryType;
+
+    use super::*;
+
+    fn setup_namespace() -> Arc<UnixNamespace> {
+        let root = DirectoryTreeNode::from_empty(None, String::new());
+        root.mount_empty("tmp").unwrap();
+
+        UnixNamespace::new(Arc::new(SpinMutex::new(root)))
+    }
+
+    fn pathname(path: &str) -> SocketAddress {
+        SocketAddress::Unix(UnixAddress::Pathname(String::from(path)))
+    }
+
+    fn abstract_name(name: &str) -> SocketAddress {
+        SocketAddress::Unix(UnixAddress::Abstract(Vec::from(name.as_bytes())))
+    }
+
+    struct DummyFile;
+
+    impl IFile for DummyFile {}
+
+    #[test]
+    fn test_stream_pair() {
+        let (a, b) = UnixSocket::new_pair(SocketType::Stream, setup_namespace());
+
+        assert_eq!(a.send(b"ping", Vec::new(), None), Ok(4));
+
+        let mut buf = [0u8; 16];
+        let message = b.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        assert_eq!(&buf[..message.length], b"ping");
+        assert_eq!(
+            b.receive(&mut buf, MessageFlags::empty()).err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_changes_wake_waiters() {
+        let (a, b) = UnixSocket::new_pair(SocketType::Stream, setup_namespace());
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut waiting = pin!(b.readiness().wait());
+            assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Pending);
+
+            a.send(b"ping", Vec::new(), None).unwrap();
+            assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(()));
+        }
+
+        // Peeking leaves everything as it was
+        let mut waiting = pin!(a.readiness().wait());
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Pending);
+
+        b.receive(&mut [0u8; 4], MessageFlags::MSG_PEEK).unwrap();
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Pending);
+
+        drop(b);
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn test_bind_creates_socket_inode() {
+        let namespace = setup_namespace();
+        let socket = UnixSocket::new(SocketType::Stream, namespace.clone());
+
+        socket.bind(&pathname("/tmp/server")).unwrap();
+
+        let root = namespace.root();
+        let node = root.open("/tmp/server", Some(&root)).unwrap();
+
+        assert_eq!(node.metadata().entry_type, DirectoryEntryType::Socket);
+        assert_eq!(socket.local_address(), pathname("/tmp/server"));
+    }
+
+    #[test]
+    fn test_bind_twice_in_use() {
+        let namespace = setup_namespace();
+
+        let first = UnixSocket::new(SocketType::Stream, namespace.clone());
+        let second = UnixSocket::new(SocketType::Stream, namespace);
+
+        first.bind(&pathname("/tmp/in_use")).unwrap();
+
+        assert_eq!(
+            second.bind(&pathname("/tmp/in_use")),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+        assert_eq!(
+            first.bind(&pathname("/tmp/other")),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_listen_connect_accept() {
+        let namespace = setup_namespace();
+
+        let server = UnixSocket::new(SocketType::Stream, namespace.clone());
+        server.bind(&pathname("/tmp/listener")).unwrap();
+        server.listen(1).unwrap();
+
+        assert_eq!(server.poll(), PollEvents::empty());
+
+        let client = UnixSocket::new(SocketType::Stream, namespace.clone());
+        client.connect(&pathname("/tmp/listener")).unwrap();
+
+        assert_eq!(server.poll(), PollEvents::POLLIN);
+
+        // backlog is full
+        let another = UnixSocket::new(SocketType::Stream, namespace);
+        assert_eq!(
+            another.connect(&pathname("/tmp/listener")),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let accepted = server.accept().unwrap();
+
+        client.send(b"hello", Vec::new(), None).unwrap();
+
+        let mut buf = [0u8; 5];
+        accepted.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        assert_eq!(accepted.local_address(), pathname("/tmp/listener"));
+        assert_eq!(client.peer_address(), Ok(pathname("/tmp/listener")));
+    }
+
+    #[test]
+    fn test_connect_refused() {
+        let namespace = setup_namespace();
+
+        let not_listening = UnixSocket::new(SocketType::Stream, namespace.clone());
+        not_listening.bind(&pathname("/tmp/idle")).unwrap();
+
+        let client = UnixSocket::new(SocketType::Stream, namespace.clone());
+
+        assert_eq!(
+            client.connect(&pathname("/tmp/idle")),
+            Err(ErrNo::ConnectionRefused)
+        );
+        assert_eq!(
+            client.connect(&pathname("/tmp/missing")),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+
+        // The entry stays after the socket is gone
+        drop(not_listening);
+
+        assert_eq!(
+            client.connect(&pathname("/tmp/idle")),
+            Err(ErrNo::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn test_peer_closed() {
+        let (a, b) = UnixSocket::new_pair(SocketType::Stream, setup_namespace());
+
+        a.send(b"bye", Vec::new(), None).unwrap();
+        drop(a);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            b.receive(&mut buf, MessageFlags::empty()).unwrap().length,
+            3
+        );
+        assert_eq!(
+            b.receive(&mut buf, MessageFlags::empty()).unwrap().length,
+            0
+        );
+
+        assert_eq!(b.send(b"x", Vec::new(), None), Err(ErrNo::BrokenPipe));
+        assert!(b.poll().contains(PollEvents::POLLHUP));
+    }
+
+    #[test]
+    fn test_shutdown_write() {
+        let (a, b) = UnixSocket::new_pair(SocketType::Stream, setup_namespace());
+
+        a.shutdown(Shutdown::Write).unwrap();
+
+        assert_eq!(a.send(b"x", Vec::new(), None), Err(ErrNo::BrokenPipe));
+        assert_eq!(
+            b.receive(&mut [0u8; 1], MessageFlags::empty())
+                .unwrap()
+                .length,
+            0
+        );
+        assert!(b.poll().contains(PollEvents::POLLRDHUP));
+
+        // the other direction still works
+        b.send(b"y", Vec::new(), None).unwrap();
+        assert_eq!(
+            a.receive(&mut [0u8; 1], MessageFlags::empty())
+                .unwrap()
+                .length,
+            1
+        );
+    }
+
+    #[test]
+    fn test_datagram_abstract_address() {
+        let namespace = setup_namespace();
+
+        let receiver = UnixSocket::new(SocketType::Datagram, namespace.clone());
+        receiver
+            .bind(&abstract_name("unix-socket-test-dgram"))
+            .unwrap();
+
+        let sender = UnixSocket::new(SocketType::Datagram, namespace);
+        sender
+            .bind(&SocketAddress::Unix(UnixAddress::Unnamed))
+            .unwrap();
+
+        sender
+            .send(
+                b"one",
+                Vec::new(),
+                Some(&abstract_name("unix-socket-test-dgram")),
+            )
+            .unwrap();
+        sender
+            .send(
+                b"two",
+                Vec::new(),
+                Some(&abstract_name("unix-socket-test-dgram")),
+            )
+            .unwrap();
+
+        let mut buf = [0u8; 2];
+        let message = receiver.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        // datagram boundaries are kept, the tail is discarded
+        assert_eq!((message.length, message.full_length), (2, 3));
+        assert_eq!(message.source, sender.local_address());
+
+        let mut buf = [0u8; 8];
+        let message = receiver.receive(&mut buf, MessageFlags::empty()).unwrap();
+        assert_eq!(&buf[..message.length], b"two");
+    }
+
+    #[test]
+    fn test_abstract_names_per_namespace() {
+        let first = UnixSocket::new(SocketType::Datagram, setup_namespace());
+        let second = UnixSocket::new(SocketType::Datagram, setup_namespace());
+
+        // Both get the first name their namespace hands out, and the same name is free in each
+        first
+            .bind(&SocketAddress::Unix(UnixAddress::Unnamed))
+            .unwrap();
+        second
+            .bind(&SocketAddress::Unix(UnixAddress::Unnamed))
+            .unwrap();
+        assert_eq!(first.local_address(), abstract_name("00000"));
+        assert_eq!(second.local_address(), abstract_name("00000"));
+
+        let peer = UnixSocket::new(SocketType::Datagram, second.namespace.clone());
+        assert_eq!(
+            peer.bind(&abstract_name("00000")),
+            Err(ErrNo::AddressAlreadyInUse)
+        );
+
+        peer.bind(&abstract_name("peer")).unwrap();
+        assert_eq!(
+            peer.bind(&abstract_name("other")),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_datagram_connect() {
+        let namespace = setup_namespace();
+
+        let receiver = UnixSocket::new(SocketType::Datagram, namespace.clone());
+        receiver.bind(&pathname("/tmp/dgram")).unwrap();
+
+        let sender = UnixSocket::new(SocketType::Datagram, namespace.clone());
+
+        assert_eq!(
+            sender.send(b"x", Vec::new(), None),
+            Err(ErrNo::DestinationAddressRequired)
+        );
+
+        sender.connect(&pathname("/tmp/dgram")).unwrap();
+        sender.send(b"x", Vec::new(), None).unwrap();
+
+        assert!(receiver.poll().contains(PollEvents::POLLIN));
+
+        let stream = UnixSocket::new(SocketType::Stream, namespace);
+        assert_eq!(
+            stream.connect(&pathname("/tmp/dgram")),
+            Err(ErrNo::ProtocolWrongTypeForSocket)
+        );
+    }
+
+    #[test]
+    fn test_rights_are_passed() {
+        let (a, b) = UnixSocket::new_pair(SocketType::Stream, setup_namespace());
+
+        a.send(b"fd", vec![Arc::new(DummyFile)], None).unwrap();
+
+        let message = b.receive(&mut [0u8; 2], MessageFlags::empty()).unwrap();
+
+        assert_eq!(message.rights.len(), 1);
+    }
+
+    #[test]
+    fn test_options() {
+        let socket = UnixSocket::new(SocketType::Datagram, setup_namespace());
+
+        assert_eq!(socket.get_option(SOL_SOCKET, SO_TYPE), Ok(2));
+        assert_eq!(
+            socket.get_option(SOL_SOCKET, SO_DOMAIN),
+            Ok(AF_UNIX as usize)
+        );
+
+        socket.set_option(SOL_SOCKET, SO_RCVBUF, 4096).unwrap();
+        assert_eq!(socket.get_option(SOL_SOCKET, SO_RCVBUF), Ok(4096));
+
+        assert_eq!(
+            socket.get_option(0, SO_TYPE),
+            Err(ErrNo::ProtocolNotAvailable)
+        );
+    }
+}
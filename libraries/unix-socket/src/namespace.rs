@@ -0,0 +1,89 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use constants::ErrNo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use filesystem_abstractions::DirectoryTreeNode;
+use hermit_sync::SpinMutex;
+use threading::sync::WaitQueue;
+
+use crate::UnixSocket;
+
+/// Where the unix sockets of one kernel find each other, by pathname in its filesystem or by
+/// abstract name.
+pub struct UnixNamespace {
+    fs: Arc<SpinMutex<Arc<DirectoryTreeNode>>>,
+    abstract_names: SpinMutex<BTreeMap<Vec<u8>, Weak<UnixSocket>>>,
+    autobind_counter: AtomicUsize,
+    // Shared by all sockets, connecting and sending reach sockets a waiter knows nothing about
+    pub(crate) readiness: WaitQueue,
+}
+
+impl UnixNamespace {
+    /// Creates a namespace without abstract names, pathnames are resolved against the root of
+    /// `fs`.
+    pub fn new(fs: Arc<SpinMutex<Arc<DirectoryTreeNode>>>) -> Arc<UnixNamespace> {
+        Arc::new(UnixNamespace {
+            fs,
+            abstract_names: SpinMutex::new(BTreeMap::new()),
+            autobind_counter: AtomicUsize::new(0),
+            readiness: WaitQueue::new(),
+        })
+    }
+
+    pub(crate) fn root(&self) -> Arc<DirectoryTreeNode> {
+        self.fs.lock().clone()
+    }
+
+    /// Gives `name` to `socket`, unless a socket still alive has it.
+    pub(crate) fn bind_abstract(&self, name: &[u8], socket: Weak<UnixSocket>) -> Result<(), ErrNo> {
+        let mut names = self.abstract_names.lock();
+
+        if names
+            .get(name)
+            .is_some_and(|bound| bound.strong_count() != 0)
+        {
+            return Err(ErrNo::AddressAlreadyInUse);
+        }
+
+        names.insert(name.to_vec(), socket);
+
+        Ok(())
+    }
+
+    /// Gives `socket` an unused name of five hex digits, as Linux does for an unnamed bind.
+    pub(crate) fn autobind(&self, socket: &Weak<UnixSocket>) -> Result<Vec<u8>, ErrNo> {
+        loop {
+            let id = self.autobind_counter.fetch_add(1, Ordering::Relaxed) & 0xfffff;
+            let name = format!("{:05x}", id).into_bytes();
+
+            match self.bind_abstract(&name, socket.clone()) {
+                Ok(()) => return Ok(name),
+                Err(ErrNo::AddressAlreadyInUse) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub(crate) fn lookup_abstract(&self, name: &[u8]) -> Option<Arc<UnixSocket>> {
+        self.abstract_names
+            .lock()
+            .get(name)
+            .and_then(|bound| bound.upgrade())
+    }
+
+    /// Frees `name` once the socket that had it is gone.
+    pub(crate) fn release_abstract(&self, name: &[u8]) {
+        let mut names = self.abstract_names.lock();
+
+        if names
+            .get(name)
+            .is_some_and(|bound| bound.strong_count() == 0)
+        {
+            names.remove(name);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use constants::ErrNo;
+use filesystem_abstractions::IFile;
+
+pub(crate) const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+struct Segment {
+    data: Vec<u8>,
+    offset: usize,
+    // Delivered together with the first byte of the segment
+    rights: Vec<Arc<dyn IFile>>,
+}
+
+impl Segment {
+    fn remaining(&self) -> &[u8] {
+        &self.data[self.offset..]
+    }
+}
+
+/// One direction of a connected stream socket pair.
+pub(crate) struct StreamBuffer {
+    segments: VecDeque<Segment>,
+    len: usize,
+    capacity: usize,
+    /// No more data will be written, readers see end of file once drained
+    pub(crate) writer_closed: bool,
+    /// Nobody reads anymore, writers get `EPIPE`
+    pub(crate) reader_closed: bool,
+}
+
+impl StreamBuffer {
+    pub fn new() -> StreamBuffer {
+        StreamBuffer {
+            segments: VecDeque::new(),
+            len: 0,
+            capacity: STREAM_BUFFER_SIZE,
+            writer_closed: false,
+            reader_closed: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn space(&self) -> usize {
+        self.capacity - self.len
+    }
+
+    pub fn write(&mut self, data: &[u8], rights: Vec<Arc<dyn IFile>>) -> Result<usize, ErrNo> {
+        if self.writer_closed || self.reader_closed {
+            return Err(ErrNo::BrokenPipe);
+        }
+
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let len = data.len().min(self.space());
+
+        if len == 0 {
+            return Err(ErrNo::ResourceTemporarilyUnavailable);
+        }
+
+        self.segments.push_back(Segment {
+            data: data[..len].to_vec(),
+            offset: 0,
+            rights,
+        });
+        self.len += len;
+
+        Ok(len)
+    }
+
+    /// Reads as much as possible, but never past a segment carrying rights so that each batch of
+    /// files is delivered with the data it was sent with.
+    pub fn read(
+        &mut self,
+        buf: &mut [u8],
+        peek: bool,
+    ) -> Result<(usize, Vec<Arc<dyn IFile>>), ErrNo> {
+        if self.len == 0 {
+            return match self.writer_closed || self.reader_closed {
+                true => Ok((0, Vec::new())),
+                false => Err(ErrNo::ResourceTemporarilyUnavailable),
+            };
+        }
+
+        let mut copied = 0;
+        let mut rights = Vec::new();
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            if copied == buf.len() {
+                break;
+            }
+
+            let carries_rights = segment.offset == 0 && !segment.rights.is_empty();
+
+            if carries_rights {
+                if index != 0 {
+                    break;
+                }
+
+                rights = segment.rights.clone();
+            }
+
+            let remaining = segment.remaining();
+            let len = remaining.len().min(buf.len() - copied);
+
+            buf[copied..copied + len].copy_from_slice(&remaining[..len]);
+            copied += len;
+        }
+
+        if !peek {
+            self.consume(copied);
+        }
+
+        Ok((copied, rights))
+    }
+
+    fn consume(&mut self, mut len: usize) {
+        self.len -= len;
+
+        while len != 0 {
+            let segment = self.segments.front_mut().unwrap();
+            let available = segment.data.len() - segment.offset;
+
+            segment.rights.clear();
+
+            if len < available {
+                segment.offset += len;
+                return;
+            }
+
+            len -= available;
+            self.segments.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    struct DummyFile;
+
+    impl IFile for DummyFile {}
+
+    #[test]
+    fn test_read_write() {
+        let mut buffer = StreamBuffer::new();
+
+        assert_eq!(buffer.write(b"hello ", Vec::new()), Ok(6));
+        assert_eq!(buffer.write(b"world", Vec::new()), Ok(5));
+
+        let mut buf = [0u8; 8];
+        assert_eq!(buffer.read(&mut buf, false).unwrap().0, 8);
+        assert_eq!(&buf, b"hello wo");
+
+        assert_eq!(buffer.read(&mut buf, false).unwrap().0, 3);
+        assert_eq!(&buf[..3], b"rld");
+
+        assert_eq!(
+            buffer.read(&mut buf, false).err(),
+            Some(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_peek_keeps_data() {
+        let mut buffer = StreamBuffer::new();
+        buffer.write(b"abc", Vec::new()).unwrap();
+
+        let mut buf = [0u8; 3];
+        assert_eq!(buffer.read(&mut buf, true).unwrap().0, 3);
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[test]
+    fn test_rights_split_reads() {
+        let mut buffer = StreamBuffer::new();
+
+        buffer.write(b"abc", Vec::new()).unwrap();
+        buffer.write(b"def", vec![Arc::new(DummyFile)]).unwrap();
+
+        let mut buf = [0u8; 6];
+
+        let (len, rights) = buffer.read(&mut buf, false).unwrap();
+        assert_eq!(len, 3);
+        assert!(rights.is_empty());
+
+        let (len, rights) = buffer.read(&mut buf, false).unwrap();
+        assert_eq!(len, 3);
+        assert_eq!(rights.len(), 1);
+    }
+
+    #[test]
+    fn test_capacity_and_close() {
+        let mut buffer = StreamBuffer::new();
+        let data = vec![0u8; STREAM_BUFFER_SIZE + 1];
+
+        assert_eq!(buffer.write(&data, Vec::new()), Ok(STREAM_BUFFER_SIZE));
+        assert_eq!(
+            buffer.write(&data, Vec::new()),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        buffer.reader_closed = true;
+        assert_eq!(buffer.write(&data, Vec::new()), Err(ErrNo::BrokenPipe));
+    }
+
+    #[test]
+    fn test_end_of_file() {
+        let mut buffer = StreamBuffer::new();
+        buffer.writer_closed = true;
+
+        assert_eq!(buffer.read(&mut [0u8; 4], false).unwrap().0, 0);
+    }
+}
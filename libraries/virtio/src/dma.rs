@@ -0,0 +1,67 @@
+use alloc::sync::Arc;
+use core::ptr::NonNull;
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// Hands out memory that devices access by physical address.
+pub trait IDmaAllocator: Send + Sync {
+    /// Allocates `pages` zeroed, physically contiguous pages, returns their physical address and
+    /// where the kernel reaches them.
+    fn allocate(&self, pages: usize) -> Option<(usize, NonNull<u8>)>;
+
+    /// # Safety
+    ///
+    /// The pages must come from [`IDmaAllocator::allocate`] of the same allocator and no device
+    /// may access them anymore.
+    unsafe fn deallocate(&self, physical: usize, virt: NonNull<u8>, pages: usize);
+}
+
+/// Pages shared with a device, freed on drop.
+pub struct DmaBuffer {
+    allocator: Arc<dyn IDmaAllocator>,
+    physical: usize,
+    virt: NonNull<u8>,
+    pages: usize,
+}
+
+// The buffer is only reached through `&self`/`&mut self`, the device is not a Rust thread
+unsafe impl Send for DmaBuffer {}
+unsafe impl Sync for DmaBuffer {}
+
+impl DmaBuffer {
+    pub fn new(allocator: Arc<dyn IDmaAllocator>, pages: usize) -> Option<DmaBuffer> {
+        let (physical, virt) = allocator.allocate(pages)?;
+
+        Some(DmaBuffer {
+            allocator,
+            physical,
+            virt,
+            pages,
+        })
+    }
+
+    pub fn physical(&self) -> usize {
+        self.physical
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.virt.as_ptr()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages * PAGE_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages == 0
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.allocator
+                .deallocate(self.physical, self.virt, self.pages)
+        };
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Drivers for virtio devices behind the MMIO or the PCI transport, as found on QEMU's `virt`
+//! machines.
+
+#[cfg(feature = "std")]
+extern crate std;
+
+extern crate alloc;
+
+mod dma;
+mod mmio;
+mod net;
+mod pci;
+mod queue;
+mod transport;
+
+#[cfg(test)]
+mod simulated;
+
+pub use dma::{DmaBuffer, IDmaAllocator, PAGE_SIZE};
+pub use mmio::{IMmioRegisters, MmioRegisters, VirtioMmio};
+pub use net::VirtioNet;
+pub use pci::{PciMemoryWindow, VirtioPci, VIRTIO_PCI_VENDOR};
+pub use queue::VirtQueue;
+pub use transport::ITransport;
+
+/// The `compatible` string of virtio MMIO transports in a device tree
+pub const VIRTIO_MMIO_COMPATIBLE: &str = "virtio,mmio";
+
+pub const VIRTIO_DEVICE_NET: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtioError {
+    /// The registers do not belong to a virtio device
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// No device behind the transport, QEMU keeps spare transports around for hotplugging
+    NoDevice,
+    WrongDevice(u32),
+    FeaturesRejected,
+    QueueUnavailable,
+    QueueFull,
+    OutOfMemory,
+    /// A BAR the PCI transport lives in has no address, and there is no room left to give it one
+    BarUnavailable,
+}
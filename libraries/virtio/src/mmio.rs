@@ -0,0 +1,264 @@
+use alloc::boxed::Box;
+use core::ptr::NonNull;
+
+use crate::{dma::PAGE_SIZE, queue::VirtQueue, transport::ITransport, VirtioError};
+
+pub(crate) const MAGIC_VALUE: usize = 0x000;
+pub(crate) const VERSION: usize = 0x004;
+pub(crate) const DEVICE_ID: usize = 0x008;
+pub(crate) const DEVICE_FEATURES: usize = 0x010;
+pub(crate) const DEVICE_FEATURES_SEL: usize = 0x014;
+pub(crate) const DRIVER_FEATURES: usize = 0x020;
+pub(crate) const DRIVER_FEATURES_SEL: usize = 0x024;
+/// Legacy only
+pub(crate) const GUEST_PAGE_SIZE: usize = 0x028;
+pub(crate) const QUEUE_SEL: usize = 0x030;
+pub(crate) const QUEUE_NUM_MAX: usize = 0x034;
+pub(crate) const QUEUE_NUM: usize = 0x038;
+/// Legacy only
+pub(crate) const QUEUE_ALIGN: usize = 0x03c;
+/// Legacy only
+pub(crate) const QUEUE_PFN: usize = 0x040;
+pub(crate) const QUEUE_READY: usize = 0x044;
+pub(crate) const QUEUE_NOTIFY: usize = 0x050;
+pub(crate) const INTERRUPT_STATUS: usize = 0x060;
+pub(crate) const INTERRUPT_ACK: usize = 0x064;
+pub(crate) const STATUS: usize = 0x070;
+pub(crate) const QUEUE_DESC_LOW: usize = 0x080;
+pub(crate) const QUEUE_DESC_HIGH: usize = 0x084;
+pub(crate) const QUEUE_DRIVER_LOW: usize = 0x090;
+pub(crate) const QUEUE_DRIVER_HIGH: usize = 0x094;
+pub(crate) const QUEUE_DEVICE_LOW: usize = 0x0a0;
+pub(crate) const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+pub(crate) const CONFIG: usize = 0x100;
+
+/// "virt" in little endian
+pub(crate) const MAGIC: u32 = 0x7472_6976;
+
+pub(crate) const STATUS_ACKNOWLEDGE: u32 = 1;
+pub(crate) const STATUS_DRIVER: u32 = 2;
+pub(crate) const STATUS_DRIVER_OK: u32 = 4;
+pub(crate) const STATUS_FEATURES_OK: u32 = 8;
+pub(crate) const STATUS_FAILED: u32 = 128;
+
+/// The device follows the virtio 1.0 specification rather than the legacy interface
+pub(crate) const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// A window of device registers, accessed at the width of each register.
+pub trait IMmioRegisters: Send {
+    fn read32(&self, offset: usize) -> u32;
+
+    fn write32(&mut self, offset: usize, value: u32);
+
+    fn read16(&self, offset: usize) -> u16;
+
+    fn write16(&mut self, offset: usize, value: u16);
+
+    fn read8(&self, offset: usize) -> u8;
+
+    fn write8(&mut self, offset: usize, value: u8);
+}
+
+/// Registers reached through memory mapped I/O.
+pub struct MmioRegisters {
+    base: NonNull<u8>,
+}
+
+// Device registers are not tied to a thread
+unsafe impl Send for MmioRegisters {}
+
+impl MmioRegisters {
+    /// # Safety
+    ///
+    /// `base` must be a mapped window of device registers, like those of a virtio MMIO
+    /// transport.
+    pub unsafe fn new(base: NonNull<u8>) -> MmioRegisters {
+        MmioRegisters { base }
+    }
+}
+
+impl IMmioRegisters for MmioRegisters {
+    fn read32(&self, offset: usize) -> u32 {
+        unsafe { self.base.add(offset).cast::<u32>().read_volatile() }
+    }
+
+    fn write32(&mut self, offset: usize, value: u32) {
+        unsafe { self.base.add(offset).cast::<u32>().write_volatile(value) }
+    }
+
+    fn read16(&self, offset: usize) -> u16 {
+        unsafe { self.base.add(offset).cast::<u16>().read_volatile() }
+    }
+
+    fn write16(&mut self, offset: usize, value: u16) {
+        unsafe { self.base.add(offset).cast::<u16>().write_volatile(value) }
+    }
+
+    fn read8(&self, offset: usize) -> u8 {
+        unsafe { self.base.add(offset).read_volatile() }
+    }
+
+    fn write8(&mut self, offset: usize, value: u8) {
+        unsafe { self.base.add(offset).write_volatile(value) }
+    }
+}
+
+/// A virtio MMIO transport, either the legacy (version 1) or the modern (version 2) flavor.
+pub struct VirtioMmio {
+    registers: Box<dyn IMmioRegisters>,
+    version: u32,
+    device_id: u32,
+}
+
+impl VirtioMmio {
+    pub fn probe(registers: Box<dyn IMmioRegisters>) -> Result<VirtioMmio, VirtioError> {
+        if registers.read32(MAGIC_VALUE) != MAGIC {
+            return Err(VirtioError::BadMagic);
+        }
+
+        let version = registers.read32(VERSION);
+
+        if version != 1 && version != 2 {
+            return Err(VirtioError::UnsupportedVersion(version));
+        }
+
+        let device_id = registers.read32(DEVICE_ID);
+
+        if device_id == 0 {
+            return Err(VirtioError::NoDevice);
+        }
+
+        Ok(VirtioMmio {
+            registers,
+            version,
+            device_id,
+        })
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    fn add_status(&mut self, status: u32) {
+        let current = self.registers.read32(STATUS);
+
+        self.registers.write32(STATUS, current | status);
+    }
+
+    fn device_features(&mut self) -> u64 {
+        self.registers.write32(DEVICE_FEATURES_SEL, 0);
+        let low = self.registers.read32(DEVICE_FEATURES) as u64;
+
+        self.registers.write32(DEVICE_FEATURES_SEL, 1);
+        let high = self.registers.read32(DEVICE_FEATURES) as u64;
+
+        high << 32 | low
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        self.registers.write32(DRIVER_FEATURES_SEL, 0);
+        self.registers.write32(DRIVER_FEATURES, features as u32);
+
+        self.registers.write32(DRIVER_FEATURES_SEL, 1);
+        self.registers
+            .write32(DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    /// The number of entries the device supports for queue `index`, 0 if there is no such queue.
+    pub fn max_queue_size(&mut self, index: u16) -> u16 {
+        self.registers.write32(QUEUE_SEL, index as u32);
+
+        self.registers.read32(QUEUE_NUM_MAX).min(u16::MAX as u32) as u16
+    }
+
+    fn write64(&mut self, low: usize, high: usize, value: u64) {
+        self.registers.write32(low, value as u32);
+        self.registers.write32(high, (value >> 32) as u32);
+    }
+}
+
+impl ITransport for VirtioMmio {
+    fn device_id(&self) -> u32 {
+        self.device_id
+    }
+
+    fn initialize(&mut self, wanted: u64) -> Result<u64, VirtioError> {
+        self.registers.write32(STATUS, 0);
+        self.add_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        let mut wanted = wanted;
+
+        if !self.is_legacy() {
+            wanted |= VIRTIO_F_VERSION_1;
+        }
+
+        let features = self.device_features() & wanted;
+        self.set_driver_features(features);
+
+        if self.is_legacy() {
+            self.registers.write32(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+        } else {
+            self.add_status(STATUS_FEATURES_OK);
+
+            if self.registers.read32(STATUS) & STATUS_FEATURES_OK == 0 {
+                self.add_status(STATUS_FAILED);
+                return Err(VirtioError::FeaturesRejected);
+            }
+        }
+
+        Ok(features)
+    }
+
+    fn finish_initialization(&mut self) {
+        self.add_status(STATUS_DRIVER_OK);
+    }
+
+    fn setup_queue(&mut self, index: u16, queue: &VirtQueue) -> Result<(), VirtioError> {
+        let size = queue.size() as u32;
+
+        self.registers.write32(QUEUE_SEL, index as u32);
+
+        let max = self.registers.read32(QUEUE_NUM_MAX);
+
+        if max == 0 || max < size {
+            return Err(VirtioError::QueueUnavailable);
+        }
+
+        self.registers.write32(QUEUE_NUM, size);
+
+        let (descriptors, driver, device) = queue.physical_addresses();
+
+        if self.is_legacy() {
+            let pfn = u32::try_from(descriptors / PAGE_SIZE)
+                .map_err(|_| VirtioError::QueueUnavailable)?;
+
+            self.registers.write32(QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.registers.write32(QUEUE_PFN, pfn);
+        } else {
+            self.write64(QUEUE_DESC_LOW, QUEUE_DESC_HIGH, descriptors as u64);
+            self.write64(QUEUE_DRIVER_LOW, QUEUE_DRIVER_HIGH, driver as u64);
+            self.write64(QUEUE_DEVICE_LOW, QUEUE_DEVICE_HIGH, device as u64);
+            self.registers.write32(QUEUE_READY, 1);
+        }
+
+        Ok(())
+    }
+
+    fn notify(&mut self, index: u16) {
+        self.registers.write32(QUEUE_NOTIFY, index as u32);
+    }
+
+    fn acknowledge_interrupt(&mut self) -> u32 {
+        let status = self.registers.read32(INTERRUPT_STATUS);
+
+        if status != 0 {
+            self.registers.write32(INTERRUPT_ACK, status);
+        }
+
+        status
+    }
+
+    fn read_config(&self, offset: usize) -> u8 {
+        self.registers.read8(CONFIG + offset)
+    }
+}
@@ -0,0 +1,328 @@
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use network_stack::{INetworkDevice, ETHERNET_FRAME_SIZE};
+
+use crate::{
+    dma::{DmaBuffer, IDmaAllocator, PAGE_SIZE},
+    mmio::VIRTIO_F_VERSION_1,
+    queue::VirtQueue,
+    transport::ITransport,
+    VirtioError, VIRTIO_DEVICE_NET,
+};
+
+const RX_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 1;
+
+const QUEUE_SIZE: u16 = 16;
+
+/// Every frame takes one descriptor for the header and one for the data
+const SLOTS: usize = QUEUE_SIZE as usize / 2;
+
+const SLOT_SIZE: usize = 2048;
+
+/// The device has a MAC address in its configuration space
+const VIRTIO_NET_F_MAC: u64 = 1 << 5;
+
+/// `struct virtio_net_hdr` of legacy devices, without `num_buffers`
+const LEGACY_HEADER_SIZE: usize = 10;
+const HEADER_SIZE: usize = 12;
+
+/// Used when the device does not tell, a locally administered address as QEMU would pick
+const FALLBACK_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+/// A virtio network card, driven by polling.
+pub struct VirtioNet {
+    transport: Box<dyn ITransport>,
+    rx: VirtQueue,
+    tx: VirtQueue,
+    rx_buffers: DmaBuffer,
+    tx_buffers: DmaBuffer,
+    /// The slot behind every descriptor chain the device holds, by chain head
+    rx_slots: Vec<Option<usize>>,
+    tx_slots: Vec<Option<usize>>,
+    tx_free: Vec<usize>,
+    header_size: usize,
+    mac: [u8; 6],
+}
+
+impl VirtioNet {
+    pub fn new(
+        mut transport: Box<dyn ITransport>,
+        allocator: Arc<dyn IDmaAllocator>,
+    ) -> Result<VirtioNet, VirtioError> {
+        if transport.device_id() != VIRTIO_DEVICE_NET {
+            return Err(VirtioError::WrongDevice(transport.device_id()));
+        }
+
+        let features = transport.initialize(VIRTIO_NET_F_MAC)?;
+
+        let rx = VirtQueue::new(allocator.clone(), QUEUE_SIZE)?;
+        let tx = VirtQueue::new(allocator.clone(), QUEUE_SIZE)?;
+
+        transport.setup_queue(RX_QUEUE, &rx)?;
+        transport.setup_queue(TX_QUEUE, &tx)?;
+
+        let pages = (SLOTS * SLOT_SIZE).div_ceil(PAGE_SIZE);
+
+        let rx_buffers =
+            DmaBuffer::new(allocator.clone(), pages).ok_or(VirtioError::OutOfMemory)?;
+        let tx_buffers = DmaBuffer::new(allocator, pages).ok_or(VirtioError::OutOfMemory)?;
+
+        let mac = match features & VIRTIO_NET_F_MAC {
+            0 => FALLBACK_MAC,
+            _ => core::array::from_fn(|i| transport.read_config(i)),
+        };
+
+        let mut net = VirtioNet {
+            transport,
+            rx,
+            tx,
+            rx_buffers,
+            tx_buffers,
+            rx_slots: vec![None; QUEUE_SIZE as usize],
+            tx_slots: vec![None; QUEUE_SIZE as usize],
+            tx_free: (0..SLOTS).collect(),
+            header_size: match features & VIRTIO_F_VERSION_1 {
+                0 => LEGACY_HEADER_SIZE,
+                _ => HEADER_SIZE,
+            },
+            mac,
+        };
+
+        for slot in 0..SLOTS {
+            net.post_receive(slot)?;
+        }
+
+        net.transport.finish_initialization();
+        net.transport.notify(RX_QUEUE);
+
+        log::info!(
+            "virtio-net: {:02x?}, {} header",
+            net.mac,
+            match net.header_size {
+                LEGACY_HEADER_SIZE => "legacy",
+                _ => "modern",
+            }
+        );
+
+        Ok(net)
+    }
+
+    fn post_receive(&mut self, slot: usize) -> Result<(), VirtioError> {
+        let address = self.rx_buffers.physical() + slot * SLOT_SIZE;
+
+        let head = self.rx.add(
+            &[],
+            &[
+                (address, self.header_size as u32),
+                (
+                    address + self.header_size,
+                    (SLOT_SIZE - self.header_size) as u32,
+                ),
+            ],
+        )?;
+
+        self.rx_slots[head as usize] = Some(slot);
+
+        Ok(())
+    }
+
+    /// Takes back the transmit slots the device is done with.
+    fn reclaim_transmitted(&mut self) {
+        while let Some((head, _)) = self.tx.pop_used() {
+            if let Some(slot) = self.tx_slots[head as usize].take() {
+                self.tx_free.push(slot);
+            }
+        }
+    }
+}
+
+impl INetworkDevice for VirtioNet {
+    fn mac_address(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        self.transport.acknowledge_interrupt();
+
+        while let Some((head, len)) = self.rx.pop_used() {
+            let slot = self.rx_slots[head as usize].take()?;
+
+            let len = (len as usize).min(SLOT_SIZE);
+
+            let frame = (len > self.header_size).then(|| {
+                let data = unsafe {
+                    core::slice::from_raw_parts(self.rx_buffers.as_ptr().add(slot * SLOT_SIZE), len)
+                };
+
+                data[self.header_size..].to_vec()
+            });
+
+            // A chain of the same shape was just freed, this can not fail
+            self.post_receive(slot).unwrap();
+            self.transport.notify(RX_QUEUE);
+
+            if frame.is_some() {
+                return frame;
+            }
+        }
+
+        None
+    }
+
+    fn can_transmit(&mut self) -> bool {
+        self.reclaim_transmitted();
+
+        !self.tx_free.is_empty()
+    }
+
+    fn transmit(&mut self, frame: &[u8]) {
+        self.reclaim_transmitted();
+
+        let Some(slot) = self.tx_free.pop() else {
+            log::warn!("virtio-net: transmit queue full, dropping a frame");
+            return;
+        };
+
+        let len = frame.len().min(ETHERNET_FRAME_SIZE);
+
+        unsafe {
+            let buffer = self.tx_buffers.as_ptr().add(slot * SLOT_SIZE);
+
+            // No offloads, so the header is all zeros
+            buffer.write_bytes(0, self.header_size);
+            buffer
+                .add(self.header_size)
+                .copy_from_nonoverlapping(frame.as_ptr(), len);
+        }
+
+        let address = self.tx_buffers.physical() + slot * SLOT_SIZE;
+
+        // There are as many slots as chains fit into the queue
+        let head = self
+            .tx
+            .add(
+                &[
+                    (address, self.header_size as u32),
+                    (address + self.header_size, len as u32),
+                ],
+                &[],
+            )
+            .unwrap();
+
+        self.tx_slots[head as usize] = Some(slot);
+        self.transport.notify(TX_QUEUE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+    use crate::{
+        mmio::VirtioMmio,
+        simulated::{SimulatedNet, TestDmaAllocator},
+    };
+
+    const MAC: [u8; 6] = [0x52, 0x54, 0x00, 0xab, 0xcd, 0xef];
+
+    fn setup(version: u32) -> (SimulatedNet, VirtioNet) {
+        let device = SimulatedNet::new(version, VIRTIO_DEVICE_NET, MAC);
+
+        let transport = VirtioMmio::probe(device.registers()).unwrap();
+        let net = VirtioNet::new(Box::new(transport), TestDmaAllocator::new()).unwrap();
+
+        (device, net)
+    }
+
+    fn frame(seed: u8) -> Vec<u8> {
+        (0..64).map(|i| seed.wrapping_add(i)).collect()
+    }
+
+    #[test]
+    fn test_initialization() {
+        for version in [1, 2] {
+            let (device, net) = setup(version);
+
+            assert!(device.driver_ok());
+            assert_eq!(net.mac_address(), MAC);
+            assert_eq!(
+                net.header_size,
+                match version {
+                    1 => LEGACY_HEADER_SIZE,
+                    _ => HEADER_SIZE,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_transmit() {
+        for version in [1, 2] {
+            let (device, mut net) = setup(version);
+
+            for seed in 0..(SLOTS as u8 * 3) {
+                assert!(net.can_transmit());
+                net.transmit(&frame(seed));
+            }
+
+            let transmitted = device.transmitted();
+
+            assert_eq!(transmitted.len(), SLOTS * 3);
+            assert_eq!(transmitted[5], frame(5));
+        }
+    }
+
+    #[test]
+    fn test_receive_recycles_buffers() {
+        for version in [1, 2] {
+            let (device, mut net) = setup(version);
+
+            assert_eq!(net.receive(), None);
+
+            for seed in 0..(SLOTS as u8 * 3) {
+                device.inject(frame(seed));
+
+                assert_eq!(net.receive(), Some(frame(seed)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_receive_queued_frames() {
+        let (device, mut net) = setup(2);
+
+        // More frames than buffers, the rest is delivered as buffers come back
+        for seed in 0..(SLOTS as u8 + 2) {
+            device.inject(frame(seed));
+        }
+
+        for seed in 0..(SLOTS as u8 + 2) {
+            assert_eq!(net.receive(), Some(frame(seed)));
+        }
+
+        assert_eq!(net.receive(), None);
+    }
+
+    #[test]
+    fn test_probe_errors() {
+        let empty = SimulatedNet::new(2, 0, MAC);
+        assert_eq!(
+            VirtioMmio::probe(empty.registers()).err(),
+            Some(VirtioError::NoDevice)
+        );
+
+        let block = SimulatedNet::new(2, 2, MAC);
+        let transport = VirtioMmio::probe(block.registers()).unwrap();
+        assert_eq!(
+            VirtioNet::new(Box::new(transport), TestDmaAllocator::new()).err(),
+            Some(VirtioError::WrongDevice(2))
+        );
+
+        let not_virtio = Box::new(crate::simulated::NotVirtio);
+        assert_eq!(
+            VirtioMmio::probe(not_virtio).err(),
+            Some(VirtioError::BadMagic)
+        );
+    }
+}
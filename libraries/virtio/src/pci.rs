@@ -0,0 +1,545 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    mmio::{
+        IMmioRegisters, STATUS_ACKNOWLEDGE, STATUS_DRIVER, STATUS_DRIVER_OK, STATUS_FAILED,
+        STATUS_FEATURES_OK, VIRTIO_F_VERSION_1,
+    },
+    queue::VirtQueue,
+    transport::ITransport,
+    VirtioError,
+};
+
+pub const VIRTIO_PCI_VENDOR: u16 = 0x1af4;
+
+/// Devices following virtio 1.0 only, the virtio device id is added to it
+const MODERN_DEVICE_BASE: u16 = 0x1040;
+/// Devices that also have the legacy interface, they tell their virtio device id as subsystem
+const TRANSITIONAL_DEVICES: core::ops::RangeInclusive<u16> = 0x1000..=0x103f;
+
+// The configuration space every PCI function has
+pub(crate) const PCI_ID: usize = 0x00;
+pub(crate) const PCI_COMMAND: usize = 0x04;
+pub(crate) const PCI_BAR0: usize = 0x10;
+pub(crate) const PCI_SUBSYSTEM: usize = 0x2c;
+pub(crate) const PCI_CAPABILITIES: usize = 0x34;
+
+pub(crate) const COMMAND_MEMORY: u32 = 1 << 1;
+pub(crate) const COMMAND_BUS_MASTER: u32 = 1 << 2;
+/// In the status register, the upper half of the command one
+pub(crate) const STATUS_CAPABILITY_LIST: u32 = 1 << 20;
+
+const BAR_COUNT: u8 = 6;
+const BAR_IO: u32 = 1;
+pub(crate) const BAR_TYPE_64: u32 = 0b100;
+pub(crate) const BAR_FLAGS: u32 = 0xf;
+
+pub(crate) const CAPABILITY_VENDOR: u32 = 0x09;
+
+pub(crate) const CAP_COMMON: u8 = 1;
+pub(crate) const CAP_NOTIFY: u8 = 2;
+pub(crate) const CAP_ISR: u8 = 3;
+pub(crate) const CAP_DEVICE: u8 = 4;
+
+/// Guards against a capability list pointing back at itself
+const MAX_CAPABILITIES: usize = 48;
+
+// `struct virtio_pci_common_cfg`
+pub(crate) const COMMON_DEVICE_FEATURE_SELECT: usize = 0x00;
+pub(crate) const COMMON_DEVICE_FEATURE: usize = 0x04;
+pub(crate) const COMMON_DRIVER_FEATURE_SELECT: usize = 0x08;
+pub(crate) const COMMON_DRIVER_FEATURE: usize = 0x0c;
+pub(crate) const COMMON_DEVICE_STATUS: usize = 0x14;
+pub(crate) const COMMON_QUEUE_SELECT: usize = 0x16;
+pub(crate) const COMMON_QUEUE_SIZE: usize = 0x18;
+pub(crate) const COMMON_QUEUE_ENABLE: usize = 0x1c;
+pub(crate) const COMMON_QUEUE_NOTIFY_OFF: usize = 0x1e;
+pub(crate) const COMMON_QUEUE_DESC: usize = 0x20;
+pub(crate) const COMMON_QUEUE_DRIVER: usize = 0x28;
+pub(crate) const COMMON_QUEUE_DEVICE: usize = 0x30;
+
+/// The memory a PCI host bridge forwards to its bus. BARs the firmware left unassigned get
+/// their addresses from it.
+pub struct PciMemoryWindow {
+    /// Where the window starts on the bus
+    bus: u64,
+    /// Where the CPU reaches the start of the window
+    cpu: u64,
+    size: u64,
+    used: u64,
+}
+
+impl PciMemoryWindow {
+    pub fn new(bus: u64, cpu: u64, size: u64) -> PciMemoryWindow {
+        PciMemoryWindow {
+            bus,
+            cpu,
+            size,
+            used: 0,
+        }
+    }
+
+    /// Bus addresses of BARs, which are aligned to their size.
+    fn allocate(&mut self, size: u64) -> Option<u64> {
+        let start = (self.bus + self.used).checked_next_multiple_of(size)?;
+        let end = start.checked_add(size)?;
+
+        (end <= self.bus + self.size).then(|| {
+            self.used = end - self.bus;
+            start
+        })
+    }
+
+    fn to_cpu(&self, bus: u64) -> u64 {
+        bus.wrapping_sub(self.bus).wrapping_add(self.cpu)
+    }
+}
+
+/// A virtio PCI transport, only the modern interface which is found through vendor
+/// capabilities.
+pub struct VirtioPci {
+    device_id: u32,
+    common: Box<dyn IMmioRegisters>,
+    notify: Box<dyn IMmioRegisters>,
+    /// Bytes between the notification registers of queues
+    notify_multiplier: u32,
+    isr: Box<dyn IMmioRegisters>,
+    device: Box<dyn IMmioRegisters>,
+    /// Where each queue is notified, in multiples of `notify_multiplier`
+    notify_offsets: Vec<u16>,
+}
+
+impl VirtioPci {
+    /// Sets up the PCI function behind `config`, its configuration space.
+    ///
+    /// BARs the firmware did not assign get addresses from `window`. `map` gives the registers
+    /// at a physical address, the structures of the transport live there.
+    pub fn probe(
+        mut config: Box<dyn IMmioRegisters>,
+        window: &mut PciMemoryWindow,
+        map: &dyn Fn(u64) -> Box<dyn IMmioRegisters>,
+    ) -> Result<VirtioPci, VirtioError> {
+        let id = config.read32(PCI_ID);
+
+        let device_id = match (id as u16, (id >> 16) as u16) {
+            // Nothing in this slot
+            (0xffff, _) => return Err(VirtioError::NoDevice),
+            (VIRTIO_PCI_VENDOR, device) if device >= MODERN_DEVICE_BASE => {
+                (device - MODERN_DEVICE_BASE) as u32
+            }
+            (VIRTIO_PCI_VENDOR, device) if TRANSITIONAL_DEVICES.contains(&device) => {
+                config.read32(PCI_SUBSYSTEM) >> 16
+            }
+            _ => return Err(VirtioError::BadMagic),
+        };
+
+        let capabilities = Self::capabilities(config.as_ref());
+
+        // Legacy only devices are reached through I/O ports, which we do not support
+        let find = |cfg_type: u8| {
+            capabilities
+                .iter()
+                .find(|capability| capability.cfg_type == cfg_type)
+                .ok_or(VirtioError::UnsupportedVersion(1))
+        };
+
+        let common = find(CAP_COMMON)?;
+        let notify = find(CAP_NOTIFY)?;
+        let isr = find(CAP_ISR)?;
+        let device = find(CAP_DEVICE)?;
+
+        // Addresses are only given while the function does not decode them
+        let command = config.read32(PCI_COMMAND) & 0xffff;
+        config.write32(PCI_COMMAND, command & !COMMAND_MEMORY);
+
+        let mut bars = [None; BAR_COUNT as usize];
+
+        for capability in [common, notify, isr, device] {
+            let bar = capability.bar as usize;
+
+            if bars[bar].is_none() {
+                bars[bar] = Self::assign_bar(config.as_mut(), capability.bar, window);
+            }
+        }
+
+        let addresses = [common, notify, isr, device].map(|capability| {
+            bars[capability.bar as usize].map(|bus| window.to_cpu(bus) + capability.offset as u64)
+        });
+
+        // Decoding a BAR without an address would claim the start of the bus
+        let [Some(common_address), Some(notify_address), Some(isr_address), Some(device_address)] =
+            addresses
+        else {
+            return Err(VirtioError::BarUnavailable);
+        };
+
+        config.write32(PCI_COMMAND, command | COMMAND_MEMORY | COMMAND_BUS_MASTER);
+
+        Ok(VirtioPci {
+            device_id,
+            common: map(common_address),
+            notify: map(notify_address),
+            notify_multiplier: notify.multiplier,
+            isr: map(isr_address),
+            device: map(device_address),
+            notify_offsets: Vec::new(),
+        })
+    }
+
+    fn capabilities(config: &dyn IMmioRegisters) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+
+        if config.read32(PCI_COMMAND) & STATUS_CAPABILITY_LIST == 0 {
+            return capabilities;
+        }
+
+        let mut offset = config.read32(PCI_CAPABILITIES) as usize & 0xfc;
+
+        for _ in 0..MAX_CAPABILITIES {
+            if offset == 0 {
+                break;
+            }
+
+            let header = config.read32(offset);
+
+            // `struct virtio_pci_cap`, the notification one has the multiplier after it
+            if header & 0xff == CAPABILITY_VENDOR && (config.read32(offset + 4) as u8) < BAR_COUNT {
+                let cfg_type = (header >> 24) as u8;
+
+                capabilities.push(Capability {
+                    cfg_type,
+                    bar: config.read32(offset + 4) as u8,
+                    offset: config.read32(offset + 8),
+                    multiplier: match cfg_type {
+                        CAP_NOTIFY => config.read32(offset + 16),
+                        _ => 0,
+                    },
+                });
+            }
+
+            offset = (header >> 8) as usize & 0xfc;
+        }
+
+        capabilities
+    }
+
+    /// The bus address of memory BAR `index`, given one from `window` if it has none yet.
+    fn assign_bar(
+        config: &mut dyn IMmioRegisters,
+        index: u8,
+        window: &mut PciMemoryWindow,
+    ) -> Option<u64> {
+        let offset = PCI_BAR0 + index as usize * 4;
+        let low = config.read32(offset);
+
+        if low & BAR_IO != 0 {
+            return None;
+        }
+
+        let wide = low & BAR_TYPE_64 != 0;
+        let high = match wide {
+            true => config.read32(offset + 4),
+            false => 0,
+        };
+
+        let current = (high as u64) << 32 | (low & !BAR_FLAGS) as u64;
+
+        if current != 0 {
+            return Some(current);
+        }
+
+        // The bits that stay clear after writing all ones tell the size
+        config.write32(offset, u32::MAX);
+        let mask_low = config.read32(offset) & !BAR_FLAGS;
+
+        let mask_high = match wide {
+            true => {
+                config.write32(offset + 4, u32::MAX);
+                config.read32(offset + 4)
+            }
+            false => u32::MAX,
+        };
+
+        let size = (!((mask_high as u64) << 32 | mask_low as u64)).wrapping_add(1);
+
+        let address = match size {
+            0 => None,
+            _ => window
+                .allocate(size)
+                .filter(|address| wide || address + size <= 1 << 32),
+        };
+
+        // Left unassigned without room for it
+        let bus = address.unwrap_or(0);
+
+        config.write32(offset, bus as u32);
+
+        if wide {
+            config.write32(offset + 4, (bus >> 32) as u32);
+        }
+
+        address
+    }
+
+    fn add_status(&mut self, status: u32) {
+        let current = self.common.read8(COMMON_DEVICE_STATUS);
+
+        self.common
+            .write8(COMMON_DEVICE_STATUS, current | status as u8);
+    }
+
+    fn device_features(&mut self) -> u64 {
+        self.common.write32(COMMON_DEVICE_FEATURE_SELECT, 0);
+        let low = self.common.read32(COMMON_DEVICE_FEATURE) as u64;
+
+        self.common.write32(COMMON_DEVICE_FEATURE_SELECT, 1);
+        let high = self.common.read32(COMMON_DEVICE_FEATURE) as u64;
+
+        high << 32 | low
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        self.common.write32(COMMON_DRIVER_FEATURE_SELECT, 0);
+        self.common.write32(COMMON_DRIVER_FEATURE, features as u32);
+
+        self.common.write32(COMMON_DRIVER_FEATURE_SELECT, 1);
+        self.common
+            .write32(COMMON_DRIVER_FEATURE, (features >> 32) as u32);
+    }
+
+    /// 64 bit fields may be written as two halves, the low one first.
+    fn write64(&mut self, offset: usize, value: u64) {
+        self.common.write32(offset, value as u32);
+        self.common.write32(offset + 4, (value >> 32) as u32);
+    }
+}
+
+impl ITransport for VirtioPci {
+    fn device_id(&self) -> u32 {
+        self.device_id
+    }
+
+    fn initialize(&mut self, wanted: u64) -> Result<u64, VirtioError> {
+        self.common.write8(COMMON_DEVICE_STATUS, 0);
+
+        // The reset is over once the device reads back 0
+        while self.common.read8(COMMON_DEVICE_STATUS) != 0 {
+            core::hint::spin_loop();
+        }
+
+        self.add_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        let features = self.device_features() & (wanted | VIRTIO_F_VERSION_1);
+
+        if features & VIRTIO_F_VERSION_1 == 0 {
+            self.add_status(STATUS_FAILED);
+            return Err(VirtioError::FeaturesRejected);
+        }
+
+        self.set_driver_features(features);
+        self.add_status(STATUS_FEATURES_OK);
+
+        if self.common.read8(COMMON_DEVICE_STATUS) as u32 & STATUS_FEATURES_OK == 0 {
+            self.add_status(STATUS_FAILED);
+            return Err(VirtioError::FeaturesRejected);
+        }
+
+        Ok(features)
+    }
+
+    fn finish_initialization(&mut self) {
+        self.add_status(STATUS_DRIVER_OK);
+    }
+
+    fn setup_queue(&mut self, index: u16, queue: &VirtQueue) -> Result<(), VirtioError> {
+        let size = queue.size();
+
+        self.common.write16(COMMON_QUEUE_SELECT, index);
+
+        let max = self.common.read16(COMMON_QUEUE_SIZE);
+
+        if max == 0 || max < size {
+            return Err(VirtioError::QueueUnavailable);
+        }
+
+        self.common.write16(COMMON_QUEUE_SIZE, size);
+
+        let (descriptors, driver, device) = queue.physical_addresses();
+
+        self.write64(COMMON_QUEUE_DESC, descriptors as u64);
+        self.write64(COMMON_QUEUE_DRIVER, driver as u64);
+        self.write64(COMMON_QUEUE_DEVICE, device as u64);
+
+        let index = index as usize;
+
+        if self.notify_offsets.len() <= index {
+            self.notify_offsets.resize(index + 1, 0);
+        }
+
+        self.notify_offsets[index] = self.common.read16(COMMON_QUEUE_NOTIFY_OFF);
+
+        self.common.write16(COMMON_QUEUE_ENABLE, 1);
+
+        Ok(())
+    }
+
+    fn notify(&mut self, index: u16) {
+        let offset = self
+            .notify_offsets
+            .get(index as usize)
+            .copied()
+            .unwrap_or(0) as usize
+            * self.notify_multiplier as usize;
+
+        self.notify.write16(offset, index);
+    }
+
+    fn acknowledge_interrupt(&mut self) -> u32 {
+        // Reading the status acknowledges it
+        self.isr.read8(0) as u32
+    }
+
+    fn read_config(&self, offset: usize) -> u8 {
+        self.device.read8(offset)
+    }
+}
+
+/// Where one of the structures of the transport is.
+struct Capability {
+    cfg_type: u8,
+    bar: u8,
+    offset: u32,
+    /// Only for the notification structure
+    multiplier: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use network_stack::INetworkDevice;
+
+    use super::*;
+    use crate::{
+        mmio::IMmioRegisters,
+        simulated::{NotVirtio, SimulatedNet, TestDmaAllocator},
+        VirtioNet, VIRTIO_DEVICE_NET,
+    };
+
+    const MAC: [u8; 6] = [0x52, 0x54, 0x00, 0xab, 0xcd, 0xef];
+
+    const MODERN_NET: u16 = MODERN_DEVICE_BASE + VIRTIO_DEVICE_NET as u16;
+
+    fn window() -> PciMemoryWindow {
+        PciMemoryWindow::new(0x4000_0000, 0x4000_0000, 0x4000_0000)
+    }
+
+    fn probe(
+        device: &SimulatedNet,
+        window: &mut PciMemoryWindow,
+    ) -> Result<VirtioPci, VirtioError> {
+        VirtioPci::probe(device.config(), window, &|address| device.map(address))
+    }
+
+    #[test]
+    fn test_probe_assigns_bar() {
+        let device = SimulatedNet::pci(MODERN_NET, MAC, 0);
+        let mut window = window();
+
+        let transport = probe(&device, &mut window).unwrap();
+
+        assert_eq!(transport.device_id(), VIRTIO_DEVICE_NET);
+        assert_eq!(device.bar(), 0x4000_0000);
+        assert_eq!(
+            device.command() & (COMMAND_MEMORY | COMMAND_BUS_MASTER),
+            COMMAND_MEMORY | COMMAND_BUS_MASTER
+        );
+
+        // The next BAR goes after it, aligned to its size
+        assert_eq!(window.allocate(0x1_0000), Some(0x4001_0000));
+    }
+
+    #[test]
+    fn test_probe_keeps_assigned_bar() {
+        // Transitional devices tell what they are in the subsystem id
+        let device = SimulatedNet::pci(0x1000, MAC, 0x5000_0000);
+
+        // The CPU reaches the bus at another address
+        let mut window = PciMemoryWindow::new(0x4000_0000, 0x1_4000_0000, 0x4000_0000);
+        let transport = VirtioPci::probe(device.config(), &mut window, &|address| {
+            device.map(address - 0x1_0000_0000)
+        })
+        .unwrap();
+
+        assert_eq!(transport.device_id(), VIRTIO_DEVICE_NET);
+        assert_eq!(device.bar(), 0x5000_0000);
+        assert_eq!(window.used, 0);
+    }
+
+    #[test]
+    fn test_net_over_pci() {
+        let device = SimulatedNet::pci(MODERN_NET, MAC, 0);
+        let transport = probe(&device, &mut window()).unwrap();
+
+        let mut net = VirtioNet::new(Box::new(transport), TestDmaAllocator::new()).unwrap();
+
+        assert!(device.driver_ok());
+        assert_eq!(net.mac_address(), MAC);
+
+        let frame = (0..64).collect::<Vec<u8>>();
+
+        net.transmit(&frame);
+        assert_eq!(device.transmitted(), vec![frame.clone()]);
+
+        device.inject(frame.clone());
+        assert_eq!(net.receive(), Some(frame));
+        assert_eq!(net.receive(), None);
+    }
+
+    /// A PCI function of another vendor.
+    struct OtherVendor;
+
+    impl IMmioRegisters for OtherVendor {
+        fn read32(&self, _offset: usize) -> u32 {
+            0x100e_8086
+        }
+
+        fn write32(&mut self, _offset: usize, _value: u32) {}
+
+        fn read16(&self, _offset: usize) -> u16 {
+            0
+        }
+
+        fn write16(&mut self, _offset: usize, _value: u16) {}
+
+        fn read8(&self, _offset: usize) -> u8 {
+            0
+        }
+
+        fn write8(&mut self, _offset: usize, _value: u8) {}
+    }
+
+    #[test]
+    fn test_probe_errors() {
+        let map = |_| -> Box<dyn IMmioRegisters> { panic!("nothing to map") };
+
+        assert_eq!(
+            VirtioPci::probe(Box::new(NotVirtio), &mut window(), &map).err(),
+            Some(VirtioError::NoDevice)
+        );
+        assert_eq!(
+            VirtioPci::probe(Box::new(OtherVendor), &mut window(), &map).err(),
+            Some(VirtioError::BadMagic)
+        );
+
+        // No room for the BAR
+        let device = SimulatedNet::pci(MODERN_NET, MAC, 0);
+        let mut small = PciMemoryWindow::new(0x4000_0000, 0x4000_0000, 0x1000);
+
+        assert_eq!(
+            probe(&device, &mut small).err(),
+            Some(VirtioError::BarUnavailable)
+        );
+        assert_eq!(device.bar(), 0);
+        assert_eq!(device.command() & COMMAND_MEMORY, 0);
+    }
+}
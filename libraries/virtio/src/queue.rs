@@ -0,0 +1,290 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{fence, Ordering};
+
+use crate::{
+    dma::{DmaBuffer, IDmaAllocator, PAGE_SIZE},
+    VirtioError,
+};
+
+pub(crate) const DESCRIPTOR_SIZE: usize = 16;
+
+pub(crate) const DESC_F_NEXT: u16 = 1;
+/// The device writes into the buffer instead of reading it
+pub(crate) const DESC_F_WRITE: u16 = 2;
+
+/// Where the rings are placed in the queue memory, the legacy interface mandates this layout
+/// and the modern one is fine with it.
+pub(crate) struct QueueLayout {
+    pub driver: usize,
+    pub device: usize,
+    pub size: usize,
+}
+
+impl QueueLayout {
+    pub const fn new(entries: u16) -> QueueLayout {
+        let entries = entries as usize;
+
+        let driver = DESCRIPTOR_SIZE * entries;
+        let driver_size = 6 + 2 * entries;
+        let device = (driver + driver_size).next_multiple_of(PAGE_SIZE);
+        let device_size = 6 + 8 * entries;
+
+        QueueLayout {
+            driver,
+            device,
+            size: device + device_size,
+        }
+    }
+}
+
+/// A split virtqueue: the descriptor table, the driver (available) and the device (used) ring.
+pub struct VirtQueue {
+    memory: DmaBuffer,
+    layout: QueueLayout,
+    size: u16,
+    free_head: u16,
+    free_count: u16,
+    /// Our copy of the index of the available ring, the device only reads it
+    available_index: u16,
+    /// The entries of the used ring we have consumed
+    last_used: u16,
+}
+
+impl VirtQueue {
+    pub fn new(allocator: Arc<dyn IDmaAllocator>, size: u16) -> Result<VirtQueue, VirtioError> {
+        if !size.is_power_of_two() {
+            return Err(VirtioError::QueueUnavailable);
+        }
+
+        let layout = QueueLayout::new(size);
+
+        let memory = DmaBuffer::new(allocator, layout.size.div_ceil(PAGE_SIZE))
+            .ok_or(VirtioError::OutOfMemory)?;
+
+        let queue = VirtQueue {
+            memory,
+            layout,
+            size,
+            free_head: 0,
+            free_count: size,
+            available_index: 0,
+            last_used: 0,
+        };
+
+        for index in 0..size {
+            queue.write_descriptor(index, 0, 0, 0, index.wrapping_add(1));
+        }
+
+        Ok(queue)
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Descriptors not handed to the device.
+    pub fn free_count(&self) -> u16 {
+        self.free_count
+    }
+
+    /// Physical addresses of the descriptor table, the driver and the device ring.
+    pub fn physical_addresses(&self) -> (usize, usize, usize) {
+        let base = self.memory.physical();
+
+        (base, base + self.layout.driver, base + self.layout.device)
+    }
+
+    /// Hands a chain of buffers to the device, `readable` ones first. Buffers are given by
+    /// physical address and length. Returns the head of the chain which
+    /// [`VirtQueue::pop_used`] reports once the device is done.
+    pub fn add(
+        &mut self,
+        readable: &[(usize, u32)],
+        writable: &[(usize, u32)],
+    ) -> Result<u16, VirtioError> {
+        let count = readable.len() + writable.len();
+
+        if count == 0 || count > self.free_count as usize {
+            return Err(VirtioError::QueueFull);
+        }
+
+        let head = self.free_head;
+        let mut index = head;
+
+        let buffers = readable
+            .iter()
+            .map(|b| (b, 0))
+            .chain(writable.iter().map(|b| (b, DESC_F_WRITE)));
+
+        for (position, (&(address, len), flags)) in buffers.enumerate() {
+            let next = self.read_descriptor_next(index);
+
+            let flags = match position + 1 == count {
+                true => flags,
+                false => flags | DESC_F_NEXT,
+            };
+
+            self.write_descriptor(index, address as u64, len, flags, next);
+
+            if position + 1 < count {
+                index = next;
+            } else {
+                self.free_head = next;
+            }
+        }
+
+        self.free_count -= count as u16;
+
+        let slot = self.available_index % self.size;
+        self.write_u16(self.layout.driver + 4 + 2 * slot as usize, head);
+
+        // The entry must be visible before the index that publishes it
+        fence(Ordering::SeqCst);
+
+        self.available_index = self.available_index.wrapping_add(1);
+        self.write_u16(self.layout.driver + 2, self.available_index);
+
+        fence(Ordering::SeqCst);
+
+        Ok(head)
+    }
+
+    /// Takes the next chain the device is done with, returns its head and the bytes written.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        let used_index = self.read_u16(self.layout.device + 2);
+
+        if used_index == self.last_used {
+            return None;
+        }
+
+        // Read the entry only after seeing the index that published it
+        fence(Ordering::SeqCst);
+
+        let slot = (self.last_used % self.size) as usize;
+        let entry = self.layout.device + 4 + 8 * slot;
+
+        let head = self.read_u32(entry) as u16;
+        let len = self.read_u32(entry + 4);
+
+        self.last_used = self.last_used.wrapping_add(1);
+        self.free_chain(head);
+
+        Some((head, len))
+    }
+
+    fn free_chain(&mut self, head: u16) {
+        let mut index = head;
+
+        loop {
+            let flags = self.read_u16(index as usize * DESCRIPTOR_SIZE + 12);
+            self.free_count += 1;
+
+            if flags & DESC_F_NEXT == 0 {
+                break;
+            }
+
+            index = self.read_descriptor_next(index);
+        }
+
+        let tail = index;
+        self.write_u16(tail as usize * DESCRIPTOR_SIZE + 14, self.free_head);
+        self.write_u16(tail as usize * DESCRIPTOR_SIZE + 12, 0);
+
+        self.free_head = head;
+    }
+
+    fn write_descriptor(&self, index: u16, address: u64, len: u32, flags: u16, next: u16) {
+        let offset = index as usize * DESCRIPTOR_SIZE;
+
+        unsafe {
+            let base = self.memory.as_ptr().add(offset);
+
+            base.cast::<u64>().write_volatile(address);
+            base.add(8).cast::<u32>().write_volatile(len);
+            base.add(12).cast::<u16>().write_volatile(flags);
+            base.add(14).cast::<u16>().write_volatile(next);
+        }
+    }
+
+    fn read_descriptor_next(&self, index: u16) -> u16 {
+        self.read_u16(index as usize * DESCRIPTOR_SIZE + 14)
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        unsafe {
+            self.memory
+                .as_ptr()
+                .add(offset)
+                .cast::<u16>()
+                .read_volatile()
+        }
+    }
+
+    fn write_u16(&self, offset: usize, value: u16) {
+        unsafe {
+            self.memory
+                .as_ptr()
+                .add(offset)
+                .cast::<u16>()
+                .write_volatile(value)
+        }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        unsafe {
+            self.memory
+                .as_ptr()
+                .add(offset)
+                .cast::<u32>()
+                .read_volatile()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulated::{complete_next, TestDmaAllocator};
+
+    #[test]
+    fn test_layout() {
+        let layout = QueueLayout::new(16);
+
+        assert_eq!(layout.driver, 256);
+        assert_eq!(layout.device, PAGE_SIZE);
+        assert_eq!(layout.size, PAGE_SIZE + 6 + 8 * 16);
+    }
+
+    #[test]
+    fn test_exhaustion_and_reuse() {
+        let mut queue = VirtQueue::new(TestDmaAllocator::new(), 4).unwrap();
+
+        let first = queue.add(&[(0x1000, 10)], &[(0x2000, 10)]).unwrap();
+        let second = queue.add(&[(0x3000, 10)], &[(0x4000, 10)]).unwrap();
+
+        assert_eq!(queue.free_count(), 0);
+        assert_eq!(queue.add(&[(0x5000, 10)], &[]), Err(VirtioError::QueueFull));
+
+        assert_eq!(queue.pop_used(), None);
+
+        complete_next(&queue, 4);
+        assert_eq!(queue.pop_used(), Some((first, 4)));
+        assert_eq!(queue.free_count(), 2);
+
+        complete_next(&queue, 0);
+        assert_eq!(queue.pop_used(), Some((second, 0)));
+
+        // All four descriptors are usable again
+        queue
+            .add(&[(0x1000, 1), (0x2000, 1)], &[(0x3000, 1), (0x4000, 1)])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rejects_odd_sizes() {
+        assert_eq!(
+            VirtQueue::new(TestDmaAllocator::new(), 12).err(),
+            Some(VirtioError::QueueUnavailable)
+        );
+    }
+}
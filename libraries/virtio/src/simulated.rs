@@ -0,0 +1,728 @@
+//! A device model for testing the drivers on the host. DMA memory gets made up physical
+//! addresses the model translates back, so drivers mixing up the two address spaces crash.
+
+use alloc::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    boxed::Box,
+    collections::VecDeque,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use hermit_sync::SpinMutex;
+
+use crate::{
+    dma::{IDmaAllocator, PAGE_SIZE},
+    mmio::*,
+    pci::*,
+    queue::{VirtQueue, DESCRIPTOR_SIZE, DESC_F_NEXT, DESC_F_WRITE},
+    VIRTIO_DEVICE_NET,
+};
+
+/// Physical addresses handed out so far, with the memory behind them.
+static REGIONS: SpinMutex<Vec<(usize, usize, usize)>> = SpinMutex::new(Vec::new());
+
+/// Low enough for the 32 bit page numbers of legacy devices
+static NEXT_PHYSICAL: AtomicUsize = AtomicUsize::new(0x8000_0000);
+
+pub struct TestDmaAllocator;
+
+impl TestDmaAllocator {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> Arc<dyn IDmaAllocator> {
+        Arc::new(TestDmaAllocator)
+    }
+
+    fn layout(pages: usize) -> Layout {
+        Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap()
+    }
+}
+
+impl IDmaAllocator for TestDmaAllocator {
+    fn allocate(&self, pages: usize) -> Option<(usize, NonNull<u8>)> {
+        let memory = NonNull::new(unsafe { alloc_zeroed(Self::layout(pages)) })?;
+
+        let len = pages * PAGE_SIZE;
+        let physical = NEXT_PHYSICAL.fetch_add(len, Ordering::Relaxed);
+
+        REGIONS
+            .lock()
+            .push((physical, memory.as_ptr() as usize, len));
+
+        Some((physical, memory))
+    }
+
+    unsafe fn deallocate(&self, physical: usize, virt: NonNull<u8>, pages: usize) {
+        REGIONS.lock().retain(|region| region.0 != physical);
+
+        dealloc(virt.as_ptr(), Self::layout(pages));
+    }
+}
+
+/// Where the test reaches the physical address a device was given.
+fn translate(physical: usize) -> usize {
+    let regions = REGIONS.lock();
+
+    let &(start, virt, _) = regions
+        .iter()
+        .find(|&&(start, _, len)| (start..start + len).contains(&physical))
+        .unwrap_or_else(|| panic!("device accessed unallocated memory at {physical:#x}"));
+
+    virt + (physical - start)
+}
+
+unsafe fn read<T: Copy>(physical: usize) -> T {
+    (translate(physical) as *const T).read_volatile()
+}
+
+unsafe fn write<T>(physical: usize, value: T) {
+    (translate(physical) as *mut T).write_volatile(value)
+}
+
+/// Address, length and flags of a descriptor
+type Descriptor = (usize, u32, u16);
+
+/// The device side of a split virtqueue.
+#[derive(Default, Clone, Copy)]
+struct DeviceQueue {
+    size: u16,
+    descriptors: usize,
+    driver: usize,
+    device: usize,
+    last_available: u16,
+}
+
+impl DeviceQueue {
+    /// The descriptors of the next chain the driver made available, with its head.
+    fn next_chain(&mut self) -> Option<(u16, Vec<Descriptor>)> {
+        if self.size == 0 || self.last_available == unsafe { read::<u16>(self.driver + 2) } {
+            return None;
+        }
+
+        let slot = (self.last_available % self.size) as usize;
+        let head = unsafe { read::<u16>(self.driver + 4 + 2 * slot) };
+
+        self.last_available = self.last_available.wrapping_add(1);
+
+        let mut chain = Vec::new();
+        let mut index = head;
+
+        loop {
+            let descriptor = self.descriptors + index as usize * DESCRIPTOR_SIZE;
+
+            let (address, len, flags, next) = unsafe {
+                (
+                    read::<u64>(descriptor) as usize,
+                    read::<u32>(descriptor + 8),
+                    read::<u16>(descriptor + 12),
+                    read::<u16>(descriptor + 14),
+                )
+            };
+
+            chain.push((address, len, flags));
+
+            if flags & DESC_F_NEXT == 0 {
+                break;
+            }
+
+            index = next;
+        }
+
+        Some((head, chain))
+    }
+
+    fn push_used(&mut self, head: u16, len: u32) {
+        unsafe {
+            let index = read::<u16>(self.device + 2);
+            let entry = self.device + 4 + 8 * (index % self.size) as usize;
+
+            write::<u32>(entry, head as u32);
+            write::<u32>(entry + 4, len);
+            write::<u16>(self.device + 2, index.wrapping_add(1));
+        }
+    }
+}
+
+/// Completes the next available chain of `queue` as a device would, reporting `len` bytes
+/// written.
+pub fn complete_next(queue: &VirtQueue, len: u32) {
+    let (descriptors, driver, device) = queue.physical_addresses();
+
+    let mut device_queue = DeviceQueue {
+        size: queue.size(),
+        descriptors,
+        driver,
+        device,
+        // Chains are completed in order, so the used index tells how many were consumed
+        last_available: unsafe { read::<u16>(device + 2) },
+    };
+
+    let (head, _) = device_queue.next_chain().unwrap();
+    device_queue.push_used(head, len);
+}
+
+const QUEUE_NUM_MAX_VALUE: u32 = 16;
+
+struct NetState {
+    version: u32,
+    device_id: u32,
+    mac: [u8; 6],
+    status: u32,
+    device_features_sel: u32,
+    driver_features_sel: u32,
+    driver_features: u64,
+    page_size: u32,
+    queue_sel: u32,
+    queues: [DeviceQueue; 2],
+    interrupt_status: u32,
+    pending: VecDeque<Vec<u8>>,
+    transmitted: Vec<Vec<u8>>,
+    /// Only behind the PCI transport
+    pci: PciState,
+}
+
+/// The configuration space of a simulated PCI function.
+#[derive(Default)]
+struct PciState {
+    device_id: u16,
+    command: u32,
+    /// BAR 4 as written, it holds every structure of the transport
+    bar: u64,
+}
+
+impl NetState {
+    fn offered_features(&self) -> u64 {
+        const VIRTIO_NET_F_MAC: u64 = 1 << 5;
+
+        match self.version {
+            1 => VIRTIO_NET_F_MAC,
+            _ => VIRTIO_NET_F_MAC | VIRTIO_F_VERSION_1,
+        }
+    }
+
+    fn header_size(&self) -> usize {
+        match self.driver_features & VIRTIO_F_VERSION_1 {
+            0 => 10,
+            _ => 12,
+        }
+    }
+
+    fn selected_queue(&mut self) -> &mut DeviceQueue {
+        &mut self.queues[self.queue_sel as usize]
+    }
+
+    fn reset(&mut self) {
+        self.status = 0;
+        self.driver_features = 0;
+        self.queues = Default::default();
+        self.interrupt_status = 0;
+    }
+
+    fn deliver(&mut self) {
+        while !self.pending.is_empty() {
+            let Some((head, chain)) = self.queues[0].next_chain() else {
+                return;
+            };
+
+            let frame = self.pending.pop_front().unwrap();
+
+            let mut data = alloc::vec![0u8; self.header_size()];
+            data.extend_from_slice(&frame);
+
+            let mut written = 0;
+
+            for (address, len, flags) in chain {
+                assert_ne!(flags & DESC_F_WRITE, 0, "receive buffers must be writable");
+
+                let count = (len as usize).min(data.len() - written);
+
+                unsafe {
+                    (translate(address) as *mut u8)
+                        .copy_from_nonoverlapping(data[written..].as_ptr(), count)
+                };
+
+                written += count;
+            }
+
+            assert_eq!(written, data.len(), "frame does not fit the receive buffer");
+
+            self.queues[0].push_used(head, written as u32);
+            self.interrupt_status |= 1;
+        }
+    }
+
+    fn transmit(&mut self) {
+        while let Some((head, chain)) = self.queues[1].next_chain() {
+            let mut data = Vec::new();
+
+            for (address, len, flags) in chain {
+                assert_eq!(flags & DESC_F_WRITE, 0, "transmit buffers must be readable");
+
+                data.extend_from_slice(unsafe {
+                    core::slice::from_raw_parts(translate(address) as *const u8, len as usize)
+                });
+            }
+
+            self.transmitted.push(data.split_off(self.header_size()));
+            self.queues[1].push_used(head, 0);
+            self.interrupt_status |= 1;
+        }
+    }
+}
+
+/// A simulated virtio-net device, the handle inspects it while the driver owns the registers.
+pub struct SimulatedNet {
+    state: Arc<SpinMutex<NetState>>,
+}
+
+impl SimulatedNet {
+    pub fn new(version: u32, device_id: u32, mac: [u8; 6]) -> SimulatedNet {
+        SimulatedNet {
+            state: Arc::new(SpinMutex::new(NetState {
+                version,
+                device_id,
+                mac,
+                status: 0,
+                device_features_sel: 0,
+                driver_features_sel: 0,
+                driver_features: 0,
+                page_size: 0,
+                queue_sel: 0,
+                queues: Default::default(),
+                interrupt_status: 0,
+                pending: VecDeque::new(),
+                transmitted: Vec::new(),
+                pci: PciState::default(),
+            })),
+        }
+    }
+
+    /// A virtio-net behind the PCI transport with PCI device id `device_id`, the firmware left
+    /// its BAR at `bar`.
+    pub fn pci(device_id: u16, mac: [u8; 6], bar: u64) -> SimulatedNet {
+        let device = SimulatedNet::new(2, VIRTIO_DEVICE_NET, mac);
+
+        device.state.lock().pci = PciState {
+            device_id,
+            command: 0,
+            bar,
+        };
+
+        device
+    }
+
+    /// The configuration space of the PCI function.
+    pub fn config(&self) -> Box<dyn IMmioRegisters> {
+        Box::new(SimulatedPciRegisters {
+            state: self.state.clone(),
+            region: PciRegion::Config,
+        })
+    }
+
+    /// The registers of the PCI transport at bus address `address`.
+    pub fn map(&self, address: u64) -> Box<dyn IMmioRegisters> {
+        let offset = address - self.bar();
+
+        let region = match offset {
+            PCI_COMMON_OFFSET => PciRegion::Common,
+            PCI_ISR_OFFSET => PciRegion::Isr,
+            PCI_DEVICE_OFFSET => PciRegion::Device,
+            PCI_NOTIFY_OFFSET => PciRegion::Notify,
+            _ => panic!("mapped {offset:#x} into the BAR, which is no structure"),
+        };
+
+        Box::new(SimulatedPciRegisters {
+            state: self.state.clone(),
+            region,
+        })
+    }
+
+    pub fn bar(&self) -> u64 {
+        self.state.lock().pci.bar & !(BAR_FLAGS as u64)
+    }
+
+    pub fn command(&self) -> u32 {
+        self.state.lock().pci.command
+    }
+
+    pub fn registers(&self) -> Box<dyn IMmioRegisters> {
+        Box::new(SimulatedNetRegisters {
+            state: self.state.clone(),
+        })
+    }
+
+    pub fn driver_ok(&self) -> bool {
+        self.state.lock().status & STATUS_DRIVER_OK != 0
+    }
+
+    /// Receives `frame` from the wire, it is delivered once the driver has a buffer for it.
+    pub fn inject(&self, frame: Vec<u8>) {
+        let mut state = self.state.lock();
+
+        state.pending.push_back(frame);
+        state.deliver();
+    }
+
+    pub fn transmitted(&self) -> Vec<Vec<u8>> {
+        self.state.lock().transmitted.clone()
+    }
+}
+
+struct SimulatedNetRegisters {
+    state: Arc<SpinMutex<NetState>>,
+}
+
+impl IMmioRegisters for SimulatedNetRegisters {
+    fn read32(&self, offset: usize) -> u32 {
+        let mut state = self.state.lock();
+
+        match offset {
+            MAGIC_VALUE => MAGIC,
+            VERSION => state.version,
+            DEVICE_ID => state.device_id,
+            DEVICE_FEATURES => {
+                (state.offered_features() >> (32 * state.device_features_sel)) as u32
+            }
+            QUEUE_NUM_MAX if state.queue_sel < 2 => QUEUE_NUM_MAX_VALUE,
+            QUEUE_NUM_MAX => 0,
+            QUEUE_PFN => (state.selected_queue().descriptors / PAGE_SIZE) as u32,
+            INTERRUPT_STATUS => state.interrupt_status,
+            STATUS => state.status,
+            _ => panic!("read from unexpected register {offset:#x}"),
+        }
+    }
+
+    fn write32(&mut self, offset: usize, value: u32) {
+        let mut state = self.state.lock();
+
+        match offset {
+            DEVICE_FEATURES_SEL => state.device_features_sel = value,
+            DRIVER_FEATURES_SEL => state.driver_features_sel = value,
+            DRIVER_FEATURES => {
+                let shift = 32 * state.driver_features_sel;
+
+                state.driver_features &= !((u32::MAX as u64) << shift);
+                state.driver_features |= (value as u64) << shift;
+            }
+            GUEST_PAGE_SIZE => state.page_size = value,
+            QUEUE_SEL => state.queue_sel = value,
+            QUEUE_NUM => state.selected_queue().size = value as u16,
+            QUEUE_ALIGN => assert_eq!(value as usize, PAGE_SIZE),
+            QUEUE_PFN => {
+                let page_size = state.page_size as usize;
+                let queue = state.selected_queue();
+
+                let descriptors = value as usize * page_size;
+                let driver = descriptors + DESCRIPTOR_SIZE * queue.size as usize;
+                let device = (driver + 6 + 2 * queue.size as usize).next_multiple_of(page_size);
+
+                *queue = DeviceQueue {
+                    descriptors,
+                    driver,
+                    device,
+                    ..*queue
+                };
+            }
+            QUEUE_READY => assert_eq!(value, 1),
+            QUEUE_DESC_LOW | QUEUE_DESC_HIGH => {
+                set_half(&mut state.selected_queue().descriptors, offset, value)
+            }
+            QUEUE_DRIVER_LOW | QUEUE_DRIVER_HIGH => {
+                set_half(&mut state.selected_queue().driver, offset, value)
+            }
+            QUEUE_DEVICE_LOW | QUEUE_DEVICE_HIGH => {
+                set_half(&mut state.selected_queue().device, offset, value)
+            }
+            QUEUE_NOTIFY => match value {
+                0 => state.deliver(),
+                1 => state.transmit(),
+                _ => panic!("notified unknown queue {value}"),
+            },
+            INTERRUPT_ACK => state.interrupt_status &= !value,
+            STATUS if value == 0 => state.reset(),
+            STATUS => {
+                let mut value = value;
+
+                // Modern devices refuse features they did not offer
+                if value & STATUS_FEATURES_OK != 0
+                    && state.driver_features & !state.offered_features() != 0
+                {
+                    value &= !STATUS_FEATURES_OK;
+                }
+
+                state.status = value;
+            }
+            _ => panic!("write to unexpected register {offset:#x}"),
+        }
+    }
+
+    fn read16(&self, offset: usize) -> u16 {
+        panic!("16 bit read from {offset:#x}, MMIO registers are 32 bits wide")
+    }
+
+    fn write16(&mut self, offset: usize, _value: u16) {
+        panic!("16 bit write to {offset:#x}, MMIO registers are 32 bits wide")
+    }
+
+    fn read8(&self, offset: usize) -> u8 {
+        let state = self.state.lock();
+
+        match offset - CONFIG {
+            index @ 0..6 => state.mac[index],
+            _ => panic!("read from unexpected config offset {offset:#x}"),
+        }
+    }
+
+    fn write8(&mut self, offset: usize, _value: u8) {
+        panic!("write to read only config offset {offset:#x}")
+    }
+}
+
+/// Where the structures of the PCI transport are in BAR 4, as QEMU lays them out
+const PCI_COMMON_OFFSET: u64 = 0x0000;
+const PCI_ISR_OFFSET: u64 = 0x1000;
+const PCI_DEVICE_OFFSET: u64 = 0x2000;
+const PCI_NOTIFY_OFFSET: u64 = 0x3000;
+const PCI_BAR_SIZE: u64 = 0x4000;
+const PCI_NOTIFY_MULTIPLIER: u32 = 4;
+
+/// A capability that is no virtio one comes first, drivers have to skip it
+const PCI_MSIX_CAPABILITY: u32 = 0x11;
+
+enum PciRegion {
+    Config,
+    Common,
+    Notify,
+    Isr,
+    Device,
+}
+
+struct SimulatedPciRegisters {
+    state: Arc<SpinMutex<NetState>>,
+    region: PciRegion,
+}
+
+/// The header of a capability at `offset` of the configuration space, `next` one after it.
+fn capability(id: u32, cfg_type: u8, next: usize, len: u8) -> u32 {
+    id | (next as u32) << 8 | (len as u32) << 16 | (cfg_type as u32) << 24
+}
+
+impl SimulatedPciRegisters {
+    fn read_config(state: &NetState, offset: usize) -> u32 {
+        let pci = &state.pci;
+
+        // Every virtio capability is in BAR 4, at its offset with the size of a page
+        let structure = |offset: u64| [4, offset as u32, 0x1000];
+
+        let capabilities = [
+            (0x40, capability(PCI_MSIX_CAPABILITY, 0, 0x50, 12), [0; 3]),
+            (
+                0x50,
+                capability(CAPABILITY_VENDOR, CAP_COMMON, 0x60, 16),
+                structure(PCI_COMMON_OFFSET),
+            ),
+            (
+                0x60,
+                capability(CAPABILITY_VENDOR, CAP_ISR, 0x70, 16),
+                structure(PCI_ISR_OFFSET),
+            ),
+            (
+                0x70,
+                capability(CAPABILITY_VENDOR, CAP_DEVICE, 0x80, 16),
+                structure(PCI_DEVICE_OFFSET),
+            ),
+            (
+                0x80,
+                capability(CAPABILITY_VENDOR, CAP_NOTIFY, 0, 20),
+                structure(PCI_NOTIFY_OFFSET),
+            ),
+        ];
+
+        if let Some((start, header, body)) = capabilities
+            .iter()
+            .find(|(start, ..)| (*start..*start + 16).contains(&offset))
+        {
+            return match offset - start {
+                0 => *header,
+                index => body[index / 4 - 1],
+            };
+        }
+
+        match offset {
+            PCI_ID => VIRTIO_PCI_VENDOR as u32 | (pci.device_id as u32) << 16,
+            PCI_COMMAND => pci.command | STATUS_CAPABILITY_LIST,
+            // A 64 bit memory BAR, the bits below its size read as zero
+            0x20 => (pci.bar as u32 & !(PCI_BAR_SIZE as u32 - 1)) | BAR_TYPE_64,
+            0x24 => (pci.bar >> 32) as u32,
+            0x10..0x20 => 0,
+            PCI_SUBSYSTEM => state.device_id << 16,
+            PCI_CAPABILITIES => 0x40,
+            0x90 => PCI_NOTIFY_MULTIPLIER,
+            _ => panic!("read from unexpected config space offset {offset:#x}"),
+        }
+    }
+
+    fn write_config(state: &mut NetState, offset: usize, value: u32) {
+        let pci = &mut state.pci;
+
+        match offset {
+            PCI_COMMAND => pci.command = value & 0xffff,
+            0x20 | 0x24 => {
+                assert_eq!(
+                    pci.command & COMMAND_MEMORY,
+                    0,
+                    "BAR written while decoding it"
+                );
+
+                let mut bar = pci.bar as usize;
+                set_half(&mut bar, offset, value);
+                pci.bar = bar as u64;
+            }
+            _ => panic!("write to unexpected config space offset {offset:#x}"),
+        }
+    }
+}
+
+impl IMmioRegisters for SimulatedPciRegisters {
+    fn read32(&self, offset: usize) -> u32 {
+        let state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Config, _) => Self::read_config(&state, offset),
+            (PciRegion::Common, COMMON_DEVICE_FEATURE) => {
+                (state.offered_features() >> (32 * state.device_features_sel)) as u32
+            }
+            _ => panic!("32 bit read from unexpected offset {offset:#x}"),
+        }
+    }
+
+    fn write32(&mut self, offset: usize, value: u32) {
+        let mut state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Config, _) => Self::write_config(&mut state, offset, value),
+            (PciRegion::Common, COMMON_DEVICE_FEATURE_SELECT) => state.device_features_sel = value,
+            (PciRegion::Common, COMMON_DRIVER_FEATURE_SELECT) => state.driver_features_sel = value,
+            (PciRegion::Common, COMMON_DRIVER_FEATURE) => {
+                let shift = 32 * state.driver_features_sel;
+
+                state.driver_features &= !((u32::MAX as u64) << shift);
+                state.driver_features |= (value as u64) << shift;
+            }
+            (PciRegion::Common, COMMON_QUEUE_DESC | 0x24) => {
+                set_half(&mut state.selected_queue().descriptors, offset, value)
+            }
+            (PciRegion::Common, COMMON_QUEUE_DRIVER | 0x2c) => {
+                set_half(&mut state.selected_queue().driver, offset, value)
+            }
+            (PciRegion::Common, COMMON_QUEUE_DEVICE | 0x34) => {
+                set_half(&mut state.selected_queue().device, offset, value)
+            }
+            _ => panic!("32 bit write to unexpected offset {offset:#x}"),
+        }
+    }
+
+    fn read16(&self, offset: usize) -> u16 {
+        let state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Common, COMMON_QUEUE_SIZE) if state.queue_sel < 2 => {
+                QUEUE_NUM_MAX_VALUE as u16
+            }
+            (PciRegion::Common, COMMON_QUEUE_SIZE) => 0,
+            (PciRegion::Common, COMMON_QUEUE_NOTIFY_OFF) => state.queue_sel as u16,
+            _ => panic!("16 bit read from unexpected offset {offset:#x}"),
+        }
+    }
+
+    fn write16(&mut self, offset: usize, value: u16) {
+        let mut state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Common, COMMON_QUEUE_SELECT) => state.queue_sel = value as u32,
+            (PciRegion::Common, COMMON_QUEUE_SIZE) => state.selected_queue().size = value,
+            (PciRegion::Common, COMMON_QUEUE_ENABLE) => assert_eq!(value, 1),
+            (PciRegion::Notify, offset) => {
+                assert_eq!(offset, value as usize * PCI_NOTIFY_MULTIPLIER as usize);
+
+                match value {
+                    0 => state.deliver(),
+                    1 => state.transmit(),
+                    _ => panic!("notified unknown queue {value}"),
+                }
+            }
+            _ => panic!("16 bit write to unexpected offset {offset:#x}"),
+        }
+    }
+
+    fn read8(&self, offset: usize) -> u8 {
+        let mut state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Common, COMMON_DEVICE_STATUS) => state.status as u8,
+            (PciRegion::Isr, 0) => core::mem::take(&mut state.interrupt_status) as u8,
+            (PciRegion::Device, index @ 0..6) => state.mac[index],
+            _ => panic!("8 bit read from unexpected offset {offset:#x}"),
+        }
+    }
+
+    fn write8(&mut self, offset: usize, value: u8) {
+        let mut state = self.state.lock();
+
+        match (&self.region, offset) {
+            (PciRegion::Common, COMMON_DEVICE_STATUS) if value == 0 => state.reset(),
+            (PciRegion::Common, COMMON_DEVICE_STATUS) => {
+                let mut value = value as u32;
+
+                if value & STATUS_FEATURES_OK != 0
+                    && state.driver_features & !state.offered_features() != 0
+                {
+                    value &= !STATUS_FEATURES_OK;
+                }
+
+                state.status = value;
+            }
+            _ => panic!("8 bit write to unexpected offset {offset:#x}"),
+        }
+    }
+}
+
+/// Registers of a 64 bit address are written in two halves, the high one at the next word.
+fn set_half(target: &mut usize, offset: usize, value: u32) {
+    let shift = match offset % 8 {
+        0 => 0,
+        _ => 32,
+    };
+
+    let mut current = *target as u64;
+    current &= !((u32::MAX as u64) << shift);
+    current |= (value as u64) << shift;
+
+    *target = current as usize;
+}
+
+/// Registers that are not a virtio transport.
+pub struct NotVirtio;
+
+impl IMmioRegisters for NotVirtio {
+    fn read32(&self, _offset: usize) -> u32 {
+        0xffff_ffff
+    }
+
+    fn write32(&mut self, _offset: usize, _value: u32) {}
+
+    fn read16(&self, _offset: usize) -> u16 {
+        0xffff
+    }
+
+    fn write16(&mut self, _offset: usize, _value: u16) {}
+
+    fn read8(&self, _offset: usize) -> u8 {
+        0xff
+    }
+
+    fn write8(&mut self, _offset: usize, _value: u8) {}
+}
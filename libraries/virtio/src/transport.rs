@@ -0,0 +1,23 @@
+use crate::{queue::VirtQueue, VirtioError};
+
+/// How a driver reaches its device, the same for every transport.
+pub trait ITransport: Send {
+    fn device_id(&self) -> u32;
+
+    /// Resets the device and negotiates the features in `wanted` it offers, returns them.
+    fn initialize(&mut self, wanted: u64) -> Result<u64, VirtioError>;
+
+    /// Tells the device the driver is done setting it up.
+    fn finish_initialization(&mut self);
+
+    /// Hands `queue` to the device as its queue `index`.
+    fn setup_queue(&mut self, index: u16, queue: &VirtQueue) -> Result<(), VirtioError>;
+
+    fn notify(&mut self, index: u16);
+
+    /// Acknowledges pending interrupts, returns the reasons.
+    fn acknowledge_interrupt(&mut self) -> u32;
+
+    /// A byte of the device specific configuration.
+    fn read_config(&self, offset: usize) -> u8;
+}
@@ -0,0 +1,355 @@
+use address::{IAddressBase, VirtualAddress};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use constants::ErrNo;
+use filesystem_abstractions::{
+    Credentials, DirectoryTreeNode, FileKey, FileMetadata, Inotify, MountFlags, XATTR_NAME_MAX,
+};
+
+use crate::SyscallContext;
+
+/// The working directory as `dirfd`
+pub(crate) const AT_FDCWD: isize = -100;
+/// Do not follow a symbolic link at the end of the path
+pub(crate) const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+/// Check access with the effective ids instead of the real ones
+pub(crate) const AT_EACCESS: usize = 0x200;
+/// An empty path means `dirfd` itself
+pub(crate) const AT_EMPTY_PATH: usize = 0x1000;
+
+/// Longest path user space can pass, including the terminating nul
+const PATH_MAX: usize = 4096;
+
+impl SyscallContext {
+    /// Reads a nul-terminated path from user space.
+    pub(crate) fn read_path(&self, vaddr: VirtualAddress) -> Result<String, ErrNo> {
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let mut bytes = Vec::new();
+
+        loop {
+            let byte = mmu
+                .import::<u8>(vaddr + bytes.len())
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            if byte == 0 {
+                break;
+            }
+
+            bytes.push(byte);
+
+            if bytes.len() >= PATH_MAX {
+                return Err(ErrNo::FileNameTooLong);
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| ErrNo::InvalidArgument)
+    }
+
+    /// Reads the name of an extended attribute from user space, which may not be empty or longer
+    /// than `XATTR_NAME_MAX`.
+    pub(crate) fn read_xattr_name(&self, vaddr: VirtualAddress) -> Result<String, ErrNo> {
+        let name = match self.read_path(vaddr) {
+            Err(ErrNo::FileNameTooLong) => return Err(ErrNo::NumericalResultOutOfRange),
+            name => name?,
+        };
+
+        match name.is_empty() || name.len() > XATTR_NAME_MAX {
+            true => Err(ErrNo::NumericalResultOutOfRange),
+            false => Ok(name),
+        }
+    }
+
+    /// The file open at `fd` for the `f*xattr` syscalls.
+    pub(crate) fn xattr_node_of(&self, fd: usize) -> Result<Arc<DirectoryTreeNode>, ErrNo> {
+        self.file_of(fd)?
+            .inode()
+            .ok_or(ErrNo::OperationNotSupported)
+    }
+
+    /// Copies `bytes` to a user buffer of `size` bytes for the `get`/`list` xattr syscalls, a size
+    /// of 0 only asks how large they are.
+    pub(crate) fn export_xattr(
+        &self,
+        bytes: &[u8],
+        buffer: VirtualAddress,
+        size: usize,
+    ) -> Result<isize, ErrNo> {
+        if size == 0 {
+            return Ok(bytes.len() as isize);
+        }
+
+        if size < bytes.len() {
+            return Err(ErrNo::NumericalResultOutOfRange);
+        }
+
+        self.task
+            .process()
+            .mmu()
+            .lock()
+            .write_bytes(buffer, bytes)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        Ok(bytes.len() as isize)
+    }
+
+    /// The open file description at `fd` and the key of its inode, for `flock` and `fcntl` locks.
+    pub(crate) fn lockable_file_of(
+        &self,
+        fd: usize,
+    ) -> Result<(Arc<FileMetadata>, FileKey), ErrNo> {
+        let metadata = self.file_of(fd)?.metadata().ok_or(ErrNo::InvalidArgument)?;
+        let key = metadata.inode().file_key().map_err(|e| e.to_errno())?;
+
+        Ok((metadata, key))
+    }
+
+    /// Runs `f` on the inotify instance open at `fd`.
+    pub(crate) fn with_inotify<R>(
+        &self,
+        fd: usize,
+        f: impl FnOnce(&Inotify) -> R,
+    ) -> Result<R, ErrNo> {
+        let file = self.file_of(fd)?;
+
+        file.downcast_ref::<Inotify>()
+            .map(f)
+            .ok_or(ErrNo::InvalidArgument)
+    }
+
+    /// The credentials the calling process acts as.
+    pub(crate) fn credentials(&self) -> Credentials {
+        self.task.process().credentials().lock().clone()
+    }
+
+    /// Opens the path at `pathname` relative to `dirfd` like the `*at` syscalls do, searching
+    /// directories as `credentials`.
+    ///
+    /// A null `pathname` is taken as an empty one, which only refers to `dirfd` itself with
+    /// `AT_EMPTY_PATH`.
+    pub(crate) fn lookup_at(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        flags: usize,
+        credentials: &Credentials,
+    ) -> Result<Arc<DirectoryTreeNode>, ErrNo> {
+        let path = match pathname.is_null() {
+            true => String::new(),
+            false => self.read_path(pathname)?,
+        };
+
+        self.lookup_path_at(dirfd, &path, flags, credentials)
+    }
+
+    /// Opens the directory holding the last component of `pathname` relative to `dirfd`, for the
+    /// syscalls removing or renaming it. Returns the directory and the name in it.
+    pub(crate) fn lookup_parent_at(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        credentials: &Credentials,
+    ) -> Result<(Arc<DirectoryTreeNode>, String), ErrNo> {
+        let path = self.read_path(pathname)?;
+
+        if path.is_empty() {
+            return Err(ErrNo::NoSuchFileOrDirectory);
+        }
+
+        let path = path::trim_end_separator(&path);
+        let name = path::get_filename(path);
+
+        match name {
+            "" => return Err(ErrNo::DeviceOrResourceBusy),
+            "." | ".." => return Err(ErrNo::InvalidArgument),
+            _ => (),
+        }
+
+        let parent = self.lookup_path_at(
+            dirfd,
+            &path[..path.len() - name.len()],
+            AT_EMPTY_PATH,
+            credentials,
+        )?;
+
+        Ok((parent, name.to_string()))
+    }
+
+    fn lookup_path_at(
+        &self,
+        dirfd: isize,
+        path: &str,
+        flags: usize,
+        credentials: &Credentials,
+    ) -> Result<Arc<DirectoryTreeNode>, ErrNo> {
+        if path.is_empty() && flags & AT_EMPTY_PATH == 0 {
+            return Err(ErrNo::NoSuchFileOrDirectory);
+        }
+
+        let root = self.kernel.fs().lock().clone();
+
+        let base = match dirfd {
+            _ if path::is_path_fully_qualified(path) => root.clone(),
+            AT_FDCWD => {
+                let cwd = self.task.process().working_directory();
+
+                root.open(&cwd, Some(&root)).map_err(|e| e.to_errno())?
+            }
+            fd => self
+                .file_of(usize::try_from(fd).map_err(|_| ErrNo::BadFileDescriptor)?)?
+                .inode()
+                .ok_or(ErrNo::NotADirectory)?,
+        };
+
+        let node = match flags & AT_SYMLINK_NOFOLLOW {
+            0 => base.open_as(path, Some(&root), Some(credentials)),
+            _ => base.open_raw_as(path, Some(&root), Some(credentials)),
+        };
+
+        node.map_err(|e| e.to_errno())
+    }
+
+    /// Creates the file `name` alone in a tmpfs of its own, which no path leads to. It goes
+    /// through the page cache when the kernel has one, so its pages can be mapped, and they leave
+    /// the cache once the file is no longer used.
+    pub(crate) fn create_anonymous_file(
+        &self,
+        name: &str,
+    ) -> Result<Arc<DirectoryTreeNode>, ErrNo> {
+        let table = self.kernel.mounts();
+        let tmpfs = table.file_system_type("tmpfs").ok_or(ErrNo::NoSuchDevice)?;
+
+        let fs = tmpfs
+            .create(name, MountFlags::empty())
+            .map_err(|e| e.to_errno())?;
+
+        let root = match table.page_cache() {
+            Some(cache) => DirectoryTreeNode::from_anonymous_filesystem(fs, cache),
+            None => DirectoryTreeNode::from_filesystem(None, fs, Some("")),
+        };
+
+        root.touch(name).map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use address::VirtualAddress;
+    use alloc::{
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        sync::Arc,
+        vec::Vec,
+    };
+    use filesystem_abstractions::{
+        Credentials, DirectoryTreeNode, FileDescriptorTable, FileMode, FileStatistics,
+        InodeAttributes, InotifyMask, OpenFlags,
+    };
+    use memory_space::MemorySpace;
+    use test_utilities::{
+        allocation::segment::TestFrameAllocator,
+        kernel::{SystemClock, TestKernel},
+        task::TestProcess,
+    };
+    use tmpfs::TmpFileSystem;
+
+    use crate::SyscallContext;
+
+    /// A context acting as `credentials` on an empty tmpfs root, which is also the cwd.
+    pub fn setup_fs_context(credentials: Credentials) -> (SyscallContext, Arc<DirectoryTreeNode>) {
+        let root = DirectoryTreeNode::from_filesystem(
+            None,
+            TmpFileSystem::new(Arc::new(SystemClock)),
+            Some(""),
+        );
+
+        let kernel = TestKernel::new().with_fs(Some(root.clone())).build();
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu();
+
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu, alloc)))
+            .with_fd_table(Some(FileDescriptorTable::new()))
+            .with_cwd("/".to_string())
+            .with_credentials(credentials)
+            .build();
+
+        (SyscallContext::new(task, kernel), root)
+    }
+
+    /// A nul-terminated path in the user space of a context, which has to outlive its uses.
+    pub struct UserPath(Box<[u8]>);
+
+    impl UserPath {
+        pub fn new(ctx: &SyscallContext, path: &str) -> UserPath {
+            let bytes = format!("{path}\0").into_bytes().into_boxed_slice();
+            ctx.task.process().mmu().lock().register(&*bytes, false);
+
+            UserPath(bytes)
+        }
+
+        pub fn addr(&self) -> VirtualAddress {
+            VirtualAddress::from_ptr(self.0.as_ptr())
+        }
+    }
+
+    /// Opens `node` in the fd table of `ctx`.
+    pub fn open_fd(ctx: &SyscallContext, node: &Arc<DirectoryTreeNode>, flags: OpenFlags) -> isize {
+        ctx.task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(node.clone().open_as_file(flags, 0))
+            .unwrap() as isize
+    }
+
+    /// Gives `node` to `uid` with `mode`, the group is the same as the user.
+    pub fn own(node: &Arc<DirectoryTreeNode>, uid: u32, mode: u32) {
+        node.set_attributes(&InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(mode)),
+            uid: Some(uid),
+            gid: Some(uid),
+            ..Default::default()
+        })
+        .unwrap();
+    }
+
+    /// Reads the queued events of the inotify instance at `fd` as (wd, mask, cookie, name).
+    pub fn read_events(ctx: &SyscallContext, fd: usize) -> Vec<(i32, InotifyMask, u32, String)> {
+        let mut buf = [0u8; 4096];
+        let len = ctx.file_of(fd).unwrap().read(&mut buf);
+
+        let mut events = Vec::new();
+        let mut offset = 0;
+
+        while offset < len {
+            let field = |at: usize| buf[offset + at..offset + at + 4].try_into().unwrap();
+            let name_len = u32::from_ne_bytes(field(12)) as usize;
+            let name = &buf[offset + 16..offset + 16 + name_len];
+
+            events.push((
+                i32::from_ne_bytes(field(0)),
+                InotifyMask::from_bits_retain(u32::from_ne_bytes(field(4))),
+                u32::from_ne_bytes(field(8)),
+                String::from_utf8_lossy(name)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            ));
+
+            offset += 16 + name_len;
+        }
+
+        events
+    }
+
+    pub fn stat(node: &Arc<DirectoryTreeNode>) -> FileStatistics {
+        let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+        node.stat(&mut stat).unwrap();
+
+        stat
+    }
+}
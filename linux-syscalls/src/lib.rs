@@ -9,13 +9,81 @@ use linux_task_abstractions::ILinuxTask;
 
 extern crate alloc;
 
+mod fs;
+mod mqueue;
+mod socket;
+mod sysv_ipc;
+mod transfer;
+
+pub mod sys_accept;
+pub mod sys_bind;
 pub mod sys_clone;
+pub mod sys_close;
+pub mod sys_connect;
+pub mod sys_copy_file_range;
 pub mod sys_execve;
 pub mod sys_exit;
+pub mod sys_faccessat;
+pub mod sys_fallocate;
+pub mod sys_fchmodat;
+pub mod sys_fchownat;
+pub mod sys_fcntl;
+pub mod sys_flock;
+pub mod sys_fsync;
+pub mod sys_ftruncate;
+pub mod sys_getsockname;
+pub mod sys_getsockopt;
+pub mod sys_getuid;
+pub mod sys_getxattr;
+pub mod sys_inotify_add_watch;
+pub mod sys_inotify_init1;
+pub mod sys_inotify_rm_watch;
+pub mod sys_listen;
+pub mod sys_listxattr;
+pub mod sys_memfd_create;
 pub mod sys_mmap;
+pub mod sys_mount;
+pub mod sys_mq_getsetattr;
+pub mod sys_mq_notify;
+pub mod sys_mq_open;
+pub mod sys_mq_timedreceive;
+pub mod sys_mq_timedsend;
+pub mod sys_mq_unlink;
+pub mod sys_msgctl;
+pub mod sys_msgget;
+pub mod sys_msgrcv;
+pub mod sys_msgsnd;
 pub mod sys_nanosleep;
+pub mod sys_pipe2;
+pub mod sys_read;
+pub mod sys_recvfrom;
+pub mod sys_recvmsg;
+pub mod sys_removexattr;
+pub mod sys_renameat2;
 pub mod sys_sched_yield;
+pub mod sys_semctl;
+pub mod sys_semget;
+pub mod sys_semop;
+pub mod sys_sendfile;
+pub mod sys_sendmsg;
+pub mod sys_sendto;
+pub mod sys_setgroups;
+pub mod sys_setsockopt;
+pub mod sys_setuid;
+pub mod sys_setxattr;
+pub mod sys_shmat;
+pub mod sys_shmctl;
+pub mod sys_shmdt;
+pub mod sys_shmget;
+pub mod sys_shutdown;
+pub mod sys_socket;
+pub mod sys_socketpair;
+pub mod sys_splice;
+pub mod sys_tee;
+pub mod sys_umount2;
 pub mod sys_uname;
+pub mod sys_unlinkat;
+pub mod sys_utimensat;
 pub mod sys_write;
 
 pub type SyscallResult = Result<isize, ErrNo>;
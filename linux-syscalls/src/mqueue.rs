@@ -0,0 +1,94 @@
+use address::VirtualAddress;
+use alloc::{string::String, sync::Arc};
+use constants::ErrNo;
+use filesystem_abstractions::{IFile, IInode, OpenFlags};
+use ipc::{MessageQueueAttributes, PosixMessageQueue, MQ_MAXMSG_MAX, MQ_MSGSIZE_MAX};
+
+use crate::SyscallContext;
+
+/// Longest name of a queue, which is a file name
+const NAME_MAX: usize = 255;
+
+/// `struct mq_attr`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MqAttr {
+    /// Only `O_NONBLOCK`, which is a flag of the open file rather than the queue
+    pub mq_flags: i64,
+    pub mq_maxmsg: i64,
+    pub mq_msgsize: i64,
+    pub mq_curmsgs: i64,
+    pub reserved: [i64; 4],
+}
+
+impl MqAttr {
+    /// The attributes of a queue to create, which have to be positive and within the limits.
+    pub(crate) fn to_attributes(self) -> Result<MessageQueueAttributes, ErrNo> {
+        if !(1..=MQ_MAXMSG_MAX as i64).contains(&self.mq_maxmsg)
+            || !(1..=MQ_MSGSIZE_MAX as i64).contains(&self.mq_msgsize)
+        {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        Ok(MessageQueueAttributes {
+            max_messages: self.mq_maxmsg as usize,
+            message_size: self.mq_msgsize as usize,
+        })
+    }
+}
+
+/// A POSIX message queue open as a file descriptor.
+pub(crate) struct OpenMessageQueue {
+    pub file: Arc<dyn IFile>,
+    inode: Arc<dyn IInode>,
+}
+
+impl OpenMessageQueue {
+    pub fn queue(&self) -> &PosixMessageQueue {
+        // Checked by `message_queue_of`
+        self.inode.downcast_ref().unwrap()
+    }
+
+    /// Whether it is open for receiving, unlike other files which `O_WRONLY` doesn't keep
+    /// from reading.
+    pub fn readable(&self) -> bool {
+        self.file.flags().bits() & OpenFlags::O_ACCMODE.bits() != OpenFlags::O_WRONLY.bits()
+    }
+
+    pub fn writable(&self) -> bool {
+        self.file.flags().bits() & OpenFlags::O_ACCMODE.bits() != OpenFlags::O_RDONLY.bits()
+    }
+
+    pub fn nonblocking(&self) -> bool {
+        self.file.flags().contains(OpenFlags::O_NONBLOCK)
+    }
+}
+
+impl SyscallContext {
+    /// The POSIX message queue open as `fd`, which fails with `EBADF` for any other file.
+    pub(crate) fn message_queue_of(&self, fd: usize) -> Result<OpenMessageQueue, ErrNo> {
+        let file = self.file_of(fd)?;
+
+        let inode = file
+            .inode()
+            .and_then(|node| node.inode())
+            .filter(|inode| inode.downcast_ref::<PosixMessageQueue>().is_some())
+            .ok_or(ErrNo::BadFileDescriptor)?;
+
+        Ok(OpenMessageQueue { file, inode })
+    }
+
+    /// Reads the name of a queue from user space, which the C library passes without its
+    /// leading slash.
+    pub(crate) fn read_queue_name(&self, vaddr: VirtualAddress) -> Result<String, ErrNo> {
+        let name = self.read_path(vaddr)?;
+
+        match name.as_str() {
+            "" => Err(ErrNo::NoSuchFileOrDirectory),
+            "." | ".." => Err(ErrNo::PermissionDenied),
+            name if name.contains('/') => Err(ErrNo::PermissionDenied),
+            name if name.len() > NAME_MAX => Err(ErrNo::FileNameTooLong),
+            _ => Ok(name),
+        }
+    }
+}
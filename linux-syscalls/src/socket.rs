@@ -0,0 +1,261 @@
+use address::{IAddressBase, VirtualAddress};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use constants::ErrNo;
+use filesystem_abstractions::{IFile, OpenFlags};
+use socket_abstractions::{
+    ISocket, SocketAddress, SocketFile, SocketType, UnixAddress, AF_INET, AF_UNIX, IPPROTO_IP,
+    IPPROTO_TCP, IPPROTO_UDP,
+};
+use unix_socket::UnixSocket;
+
+use crate::SyscallContext;
+
+/// Maximum number of `struct iovec` in one call
+const IOV_MAX: usize = 1024;
+
+/// Size of `struct sockaddr_storage`, no address is larger than this
+const SOCKADDR_STORAGE_SIZE: usize = 128;
+
+/// `struct msghdr`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MessageHeader {
+    pub name: VirtualAddress,
+    pub name_len: u32,
+    pub iov: VirtualAddress,
+    pub iov_len: usize,
+    pub control: VirtualAddress,
+    pub control_len: usize,
+    pub flags: i32,
+}
+
+/// `struct iovec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IoVector {
+    pub base: VirtualAddress,
+    pub len: usize,
+}
+
+/// `struct cmsghdr`, followed by the data aligned to `usize`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ControlMessageHeader {
+    pub len: usize,
+    pub level: i32,
+    pub ty: i32,
+}
+
+impl ControlMessageHeader {
+    pub const SIZE: usize = core::mem::size_of::<ControlMessageHeader>();
+
+    /// `CMSG_ALIGN`
+    pub const fn align(len: usize) -> usize {
+        (len + core::mem::size_of::<usize>() - 1) & !(core::mem::size_of::<usize>() - 1)
+    }
+}
+
+impl SyscallContext {
+    pub(crate) fn create_socket(
+        &self,
+        domain: u16,
+        socket_type: SocketType,
+        protocol: usize,
+    ) -> Result<Arc<dyn ISocket>, ErrNo> {
+        match (domain, socket_type, protocol) {
+            (AF_UNIX, _, 0) => Ok(UnixSocket::new(socket_type, self.kernel.unix_sockets())),
+            (AF_INET, _, IPPROTO_IP)
+            | (AF_INET, SocketType::Stream, IPPROTO_TCP)
+            | (AF_INET, SocketType::Datagram, IPPROTO_UDP) => {
+                Ok(self.kernel.network().create_socket(socket_type))
+            }
+            (AF_UNIX | AF_INET, _, _) => Err(ErrNo::ProtocolNotSupported),
+            _ => Err(ErrNo::AddressFamilyNotSupportedByProtocol),
+        }
+    }
+
+    /// Returns the socket behind `fd`, and whether it was opened with `O_NONBLOCK`.
+    pub(crate) fn socket_of(&self, fd: usize) -> Result<(Arc<dyn ISocket>, bool), ErrNo> {
+        let file = self.file_of(fd)?;
+
+        let socket = SocketFile::socket_of(&file).ok_or(ErrNo::SocketOperationOnNonSocket)?;
+
+        Ok((socket, file.flags().contains(OpenFlags::O_NONBLOCK)))
+    }
+
+    pub(crate) fn file_of(&self, fd: usize) -> Result<Arc<dyn IFile>, ErrNo> {
+        let process = self.task.linux_process();
+        let fd_table = process.fd_table().lock();
+
+        fd_table.get(fd).cloned().ok_or(ErrNo::BadFileDescriptor)
+    }
+
+    pub(crate) fn allocate_socket_fd(
+        &self,
+        socket: Arc<dyn ISocket>,
+        flags: OpenFlags,
+    ) -> Result<usize, ErrNo> {
+        self.task
+            .linux_process()
+            .fd_table()
+            .lock()
+            .allocate(SocketFile::new(socket, flags))
+            .ok_or(ErrNo::TooManyOpenFiles)
+    }
+
+    /// Reads a `struct sockaddr` from user space, relative pathnames are resolved against the
+    /// working directory.
+    pub(crate) fn read_socket_address(
+        &self,
+        addr: VirtualAddress,
+        addrlen: usize,
+    ) -> Result<SocketAddress, ErrNo> {
+        if addrlen > SOCKADDR_STORAGE_SIZE {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let address = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let bytes = mmu
+                .map_buffer(addr, addrlen)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            SocketAddress::from_bytes(&bytes)?
+        };
+
+        match address {
+            SocketAddress::Unix(UnixAddress::Pathname(path)) => {
+                let cwd = self.task.process().working_directory();
+
+                let full_path: String =
+                    path::get_full_path(&path, Some(&cwd)).ok_or(ErrNo::InvalidArgument)?;
+
+                Ok(SocketAddress::Unix(UnixAddress::Pathname(full_path)))
+            }
+            address => Ok(address),
+        }
+    }
+
+    /// Writes `address` to a `struct sockaddr` in user space.
+    ///
+    /// `*addrlen` is the size of the buffer on input and the size of the address on output,
+    /// the address is silently truncated to fit, as Linux does. Does nothing if `addr` is null.
+    pub(crate) fn write_socket_address(
+        &self,
+        address: &SocketAddress,
+        addr: VirtualAddress,
+        addrlen: VirtualAddress,
+    ) -> Result<(), ErrNo> {
+        if addr.is_null() {
+            return Ok(());
+        }
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let capacity = mmu.import::<u32>(addrlen).map_err(|_| ErrNo::BadAddress)? as usize;
+
+        let bytes = address.to_bytes();
+        let len = bytes.len().min(capacity);
+
+        mmu.write_bytes(addr, &bytes[..len])
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        mmu.export::<u32>(addrlen, bytes.len() as u32)
+            .map_err(|_| ErrNo::BadAddress)
+    }
+
+    pub(crate) fn read_io_vectors(
+        &self,
+        iov: VirtualAddress,
+        iov_len: usize,
+    ) -> Result<Vec<IoVector>, ErrNo> {
+        if iov_len > IOV_MAX {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        (0..iov_len)
+            .map(|i| {
+                mmu.import::<IoVector>(iov + i * core::mem::size_of::<IoVector>())
+                    .map_err(|_| ErrNo::BadAddress)
+            })
+            .collect()
+    }
+}
+
+/// Retries an operation on `socket` whenever it may have become ready, until the operation stops
+/// failing with `EAGAIN`. Tries only once if `nonblocking` is set.
+pub(crate) async fn wait_for_socket<R>(
+    socket: &dyn ISocket,
+    nonblocking: bool,
+    mut operation: impl FnMut() -> Result<R, ErrNo>,
+) -> Result<R, ErrNo> {
+    if nonblocking {
+        return operation();
+    }
+
+    socket
+        .readiness()
+        .wait_for(|| match operation() {
+            Err(ErrNo::ResourceTemporarilyUnavailable) => None,
+            result => Some(result),
+        })
+        .await
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{string::ToString, sync::Arc};
+    use allocation_abstractions::IFrameAllocator;
+    use filesystem_abstractions::{DirectoryTreeNode, FileDescriptorTable};
+    use hermit_sync::SpinMutex;
+    use kernel_abstractions::IKernel;
+    use memory_space::MemorySpace;
+    use mmu_abstractions::IMMU;
+    use test_utilities::{
+        allocation::contiguous::TestFrameAllocator, kernel::TestKernel, task::TestProcess,
+    };
+
+    use crate::SyscallContext;
+
+    /// A context with an empty fd table, a root filesystem holding `/tmp` and `/tmp` as cwd.
+    pub fn setup_socket_context() -> (SyscallContext, Arc<SpinMutex<dyn IMMU>>) {
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu(1024 * 1024 * 1024);
+
+        let root = DirectoryTreeNode::from_empty(None, "".to_string());
+        root.mount_empty("tmp").unwrap();
+
+        let kernel = TestKernel::new()
+            .with_fs(Some(root))
+            .with_allocator(Some(alloc.clone()))
+            .build();
+
+        setup_task_context(kernel, alloc, mmu)
+    }
+
+    /// A context of another process running on the same kernel as `ctx`.
+    pub fn setup_peer_context(ctx: &SyscallContext) -> (SyscallContext, Arc<SpinMutex<dyn IMMU>>) {
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu(1024 * 1024 * 1024);
+
+        setup_task_context(ctx.kernel.clone(), alloc, mmu)
+    }
+
+    fn setup_task_context(
+        kernel: Arc<dyn IKernel>,
+        alloc: Arc<SpinMutex<dyn IFrameAllocator>>,
+        mmu: Arc<SpinMutex<dyn IMMU>>,
+    ) -> (SyscallContext, Arc<SpinMutex<dyn IMMU>>) {
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu.clone(), alloc)))
+            .with_fd_table(Some(FileDescriptorTable::new()))
+            .with_cwd("/tmp".to_string())
+            .build();
+
+        (SyscallContext::new(task, kernel), mmu)
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{socket::wait_for_socket, SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::OpenFlags;
+use socket_abstractions::SocketFlags;
+
+impl SyscallContext {
+    pub async fn sys_accept(
+        &self,
+        sockfd: usize,
+        addr: VirtualAddress,
+        addrlen: VirtualAddress,
+    ) -> SyscallResult {
+        self.sys_accept4(sockfd, addr, addrlen, 0).await
+    }
+
+    pub async fn sys_accept4(
+        &self,
+        sockfd: usize,
+        addr: VirtualAddress,
+        addrlen: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_accept4: sockfd: {}, addr: {}, addrlen: {}, flags: {:#x}",
+            sockfd,
+            addr,
+            addrlen,
+            flags
+        );
+
+        let flags = SocketFlags::from_bits(flags).ok_or(ErrNo::InvalidArgument)?;
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+
+        let accepted = wait_for_socket(socket.as_ref(), nonblocking, || socket.accept()).await?;
+
+        self.write_socket_address(&accepted.peer_address()?, addr, addrlen)?;
+
+        self.allocate_socket_fd(accepted, OpenFlags::from_bits_truncate(flags.bits()))
+            .map(|fd| fd as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use core::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+    use socket_abstractions::{ISocket, SocketAddress, SocketType, UnixAddress, AF_UNIX};
+    use threading::block_on;
+    use unix_socket::UnixSocket;
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    fn listening_socket(ctx: &SyscallContext, path: &str, flags: SocketFlags) -> usize {
+        let fd = ctx
+            .sys_socket(
+                AF_UNIX as usize,
+                SocketType::Stream as usize | flags.bits(),
+                0,
+            )
+            .unwrap() as usize;
+
+        let (socket, _) = ctx.socket_of(fd).unwrap();
+        socket.bind(&pathname(path)).unwrap();
+        socket.listen(8).unwrap();
+
+        fd
+    }
+
+    fn pathname(path: &str) -> SocketAddress {
+        SocketAddress::Unix(UnixAddress::Pathname(path.into()))
+    }
+
+    #[test]
+    fn test_nonblocking_accept() {
+        let (ctx, _) = setup_socket_context();
+
+        let fd = listening_socket(&ctx, "/tmp/nb", SocketFlags::SOCK_NONBLOCK);
+
+        assert_eq!(
+            block_on!(ctx.sys_accept(fd, VirtualAddress::null(), VirtualAddress::null())),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_blocks_until_connected() {
+        let (ctx, _) = setup_socket_context();
+
+        let fd = listening_socket(&ctx, "/tmp/blocking", SocketFlags::empty());
+
+        let mut fut = ctx.sys_accept4(
+            fd,
+            VirtualAddress::null(),
+            VirtualAddress::null(),
+            SocketFlags::SOCK_CLOEXEC.bits(),
+        );
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for _ in 0..10 {
+            let poll = unsafe { Pin::new_unchecked(&mut fut).poll(&mut cx) };
+
+            assert_eq!(poll, Poll::Pending);
+        }
+
+        // Waiting to be woken rather than spinning
+        let (listener, _) = ctx.socket_of(fd).unwrap();
+        assert_eq!(listener.readiness().len(), 1);
+
+        let client = UnixSocket::new(SocketType::Stream, ctx.kernel.unix_sockets());
+        client.connect(&pathname("/tmp/blocking")).unwrap();
+
+        let poll = unsafe { Pin::new_unchecked(&mut fut).poll(&mut cx) };
+        let Poll::Ready(Ok(accepted)) = poll else {
+            panic!("accept did not complete: {:?}", poll);
+        };
+
+        let file = ctx.file_of(accepted as usize).unwrap();
+        assert!(file.flags().contains(OpenFlags::O_CLOEXEC));
+    }
+
+    #[test]
+    fn test_writes_peer_address() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = listening_socket(&ctx, "/tmp/peer", SocketFlags::empty());
+
+        let client = UnixSocket::new(SocketType::Stream, ctx.kernel.unix_sockets());
+        client.bind(&pathname("/tmp/client")).unwrap();
+        client.connect(&pathname("/tmp/peer")).unwrap();
+
+        let addr = Box::new([0u8; 128]);
+        let addrlen = Box::new(128u32);
+        let addr_ptr = mmu.lock().register(addr.as_ref(), true);
+        let len_ptr = mmu.lock().register(addrlen.as_ref(), true);
+
+        block_on!(ctx.sys_accept(fd, addr_ptr, len_ptr)).unwrap();
+
+        let expected = pathname("/tmp/client").to_bytes();
+        let mut written = alloc::vec![0u8; expected.len()];
+
+        let mmu = mmu.lock();
+        mmu.read_bytes(addr_ptr, &mut written).unwrap();
+
+        assert_eq!(mmu.import::<u32>(len_ptr), Ok(expected.len() as u32));
+        assert_eq!(written, expected);
+    }
+}
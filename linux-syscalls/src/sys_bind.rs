@@ -0,0 +1,82 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+
+impl SyscallContext {
+    pub fn sys_bind(&self, sockfd: usize, addr: VirtualAddress, addrlen: usize) -> SyscallResult {
+        log::debug!(
+            "sys_bind: sockfd: {}, addr: {}, addrlen: {}",
+            sockfd,
+            addr,
+            addrlen
+        );
+
+        let (socket, _) = self.socket_of(sockfd)?;
+        let address = self.read_socket_address(addr, addrlen)?;
+
+        socket.bind(&address).map(|_| 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::ErrNo;
+    use socket_abstractions::{SocketAddress, SocketType, UnixAddress, AF_UNIX};
+
+    use crate::socket::tests::setup_socket_context;
+
+    fn sockaddr(path: &str) -> alloc::vec::Vec<u8> {
+        SocketAddress::Unix(UnixAddress::Pathname(path.into())).to_bytes()
+    }
+
+    #[test]
+    fn test_relative_path_uses_cwd() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let addr = sockaddr("server.sock");
+        let ptr = mmu.lock().register(addr.as_slice(), false);
+
+        assert_eq!(ctx.sys_bind(fd, ptr, addr.len()), Ok(0));
+
+        let root = ctx.kernel.fs().lock().clone();
+        assert!(root.open("/tmp/server.sock", Some(&root)).is_ok());
+
+        let (socket, _) = ctx.socket_of(fd).unwrap();
+        assert_eq!(
+            socket.local_address(),
+            SocketAddress::Unix(UnixAddress::Pathname("/tmp/server.sock".into()))
+        );
+    }
+
+    #[test]
+    fn test_not_a_socket() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let addr = sockaddr("/tmp/a");
+        let ptr = mmu.lock().register(addr.as_slice(), false);
+
+        assert_eq!(
+            ctx.sys_bind(0, ptr, addr.len()),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+
+    #[test]
+    fn test_address_in_use() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let addr = sockaddr("/tmp/taken");
+        let ptr = mmu.lock().register(addr.as_slice(), false);
+
+        for expected in [Ok(0), Err(ErrNo::AddressAlreadyInUse)] {
+            let fd = ctx
+                .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+                .unwrap() as usize;
+
+            assert_eq!(ctx.sys_bind(fd, ptr, addr.len()), expected);
+        }
+    }
+}
@@ -13,6 +13,13 @@ impl SyscallContext {
 
         let tid = forked.tid();
 
+        // Threads share the process and with it its semaphore adjustments anyway
+        if flags.contains(TaskCloneFlags::SYSVSEM) && !flags.contains(TaskCloneFlags::THREAD) {
+            self.kernel
+                .semaphores()
+                .share_undo(self.task.process().pid(), forked.process().pid());
+        }
+
         if !stack_top.is_null() {
             forked
                 .trap_context_mut()
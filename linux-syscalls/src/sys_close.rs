@@ -0,0 +1,46 @@
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Closes `fd`. The POSIX locks of the process on the file go with any of its descriptors,
+    /// OFD and `flock` locks only once the open file description is not referred to anymore.
+    pub fn sys_close(&self, fd: usize) -> SyscallResult {
+        let file = {
+            let process = self.task.process();
+            let mut fd_table = process.fd_table().lock();
+
+            let file = fd_table.get(fd).cloned().ok_or(ErrNo::BadFileDescriptor)?;
+            fd_table.remove(fd);
+
+            file
+        };
+
+        if let Some(key) = file.inode().and_then(|node| node.file_key().ok()) {
+            self.kernel
+                .locks()
+                .release_process_file(key, self.task.process().pid());
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, setup_fs_context};
+
+    #[test]
+    fn test_close() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+
+        assert_eq!(ctx.sys_close(fd), Ok(0));
+        assert!(ctx.task.process().fd_table().lock().get(fd).is_none());
+        assert_eq!(ctx.sys_close(fd), Err(ErrNo::BadFileDescriptor));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{socket::wait_for_socket, SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use constants::ErrNo;
+
+impl SyscallContext {
+    pub async fn sys_connect(
+        &self,
+        sockfd: usize,
+        addr: VirtualAddress,
+        addrlen: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_connect: sockfd: {}, addr: {}, addrlen: {}",
+            sockfd,
+            addr,
+            addrlen
+        );
+
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+        let address = self.read_socket_address(addr, addrlen)?;
+
+        match wait_for_socket(socket.as_ref(), nonblocking, || socket.connect(&address)).await {
+            // The handshake goes on in the background, wait for it unless asked not to
+            Err(ErrNo::OperationNowInProgress) if !nonblocking => {
+                socket
+                    .readiness()
+                    .wait_for(|| match socket.connect(&address) {
+                        Err(ErrNo::OperationAlreadyInProgress) => None,
+                        // Someone else saw the connection complete first
+                        Err(ErrNo::TransportEndpointIsAlreadyConnected) => Some(Ok(0)),
+                        result => Some(result.map(|_| 0)),
+                    })
+                    .await
+            }
+            result => result.map(|_| 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::vec;
+    use core::net::{Ipv4Addr, SocketAddrV4};
+    use socket_abstractions::{
+        ISocket, SocketAddress, SocketType, UnixAddress, AF_INET, AF_UNIX, IPPROTO_TCP,
+    };
+
+    use super::*;
+    use threading::block_on;
+    use unix_socket::UnixSocket;
+
+    use crate::socket::tests::{setup_peer_context, setup_socket_context};
+
+    #[test]
+    fn test_connect_to_listener() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let server = UnixSocket::new(SocketType::Stream, ctx.kernel.unix_sockets());
+        let address = SocketAddress::Unix(UnixAddress::Pathname("/tmp/listener".into()));
+        server.bind(&address).unwrap();
+        server.listen(1).unwrap();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let addr = SocketAddress::Unix(UnixAddress::Pathname("listener".into())).to_bytes();
+        let ptr = mmu.lock().register(addr.as_slice(), false);
+
+        assert_eq!(block_on!(ctx.sys_connect(fd, ptr, addr.len())), Ok(0));
+        assert!(server.accept().is_ok());
+    }
+
+    #[test]
+    fn test_connect_refused() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let addr =
+            SocketAddress::Unix(UnixAddress::Abstract(b"nobody-listens".to_vec())).to_bytes();
+        let ptr = mmu.lock().register(addr.as_slice(), false);
+
+        assert_eq!(
+            block_on!(ctx.sys_connect(fd, ptr, addr.len())),
+            Err(ErrNo::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn test_loopback_between_tasks() {
+        let (server, server_mmu) = setup_socket_context();
+        let (client, client_mmu) = setup_peer_context(&server);
+
+        let address = SocketAddress::Inet(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 7000)).to_bytes();
+        let server_addr = server_mmu.lock().register(address.as_slice(), false);
+        let client_addr = client_mmu.lock().register(address.as_slice(), false);
+
+        let listen_fd = server
+            .sys_socket(AF_INET as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+        server
+            .sys_bind(listen_fd, server_addr, address.len())
+            .unwrap();
+        server.sys_listen(listen_fd, 1).unwrap();
+
+        let client_fd = client
+            .sys_socket(AF_INET as usize, SocketType::Stream as usize, IPPROTO_TCP)
+            .unwrap() as usize;
+
+        let request = b"ping".to_vec();
+        let request_ptr = client_mmu.lock().register(request.as_slice(), false);
+        let reply_buf = vec![0u8; 16];
+        let reply_ptr = client_mmu.lock().register(reply_buf.as_slice(), true);
+
+        let request_buf = vec![0u8; 16];
+        let request_buf_ptr = server_mmu.lock().register(request_buf.as_slice(), true);
+        let reply = b"pong".to_vec();
+        let reply_src_ptr = server_mmu.lock().register(reply.as_slice(), false);
+
+        let serve = async {
+            let null = VirtualAddress::null();
+            let fd = server.sys_accept(listen_fd, null, null).await? as usize;
+
+            let len = server
+                .sys_recvfrom(fd, request_buf_ptr, 16, 0, null, null)
+                .await?;
+            assert_eq!(len, 4);
+
+            server.sys_sendto(fd, reply_src_ptr, 4, 0, null, 0).await
+        };
+
+        let request = async {
+            let null = VirtualAddress::null();
+            client
+                .sys_connect(client_fd, client_addr, address.len())
+                .await?;
+            client
+                .sys_sendto(client_fd, request_ptr, 4, 0, null, 0)
+                .await?;
+
+            client
+                .sys_recvfrom(client_fd, reply_ptr, 16, 0, null, null)
+                .await
+        };
+
+        let (served, replied) = block_on!(serve, request);
+        assert_eq!(served, Ok(4));
+        assert_eq!(replied, Ok(4));
+
+        assert_eq!(&request_buf[..4], b"ping");
+        assert_eq!(&reply_buf[..4], b"pong");
+    }
+}
@@ -0,0 +1,179 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::{DirectoryEntryType, IFile, OpenFlags, TransferEnd};
+
+use crate::{transfer::transfer_when_ready, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Copies up to `len` bytes from one regular file to another, or within one, without going
+    /// through user space.
+    ///
+    /// Each file is read or written at the offset `off_in` or `off_out` points to, which is moved
+    /// along, or at its file position if the pointer is null. Ranges in the same file must not
+    /// overlap.
+    pub async fn sys_copy_file_range(
+        &self,
+        fd_in: usize,
+        off_in: VirtualAddress,
+        fd_out: usize,
+        off_out: VirtualAddress,
+        len: usize,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let source = self.file_of(fd_in)?;
+        let sink = self.file_of(fd_out)?;
+
+        if !source.can_read() || !sink.can_write() || sink.flags().contains(OpenFlags::O_APPEND) {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        ensure_regular(&*source)?;
+        ensure_regular(&*sink)?;
+
+        let mut source = TransferEnd::new(source, self.import_offset(off_in)?);
+        let mut sink = TransferEnd::new(sink, self.import_offset(off_out)?);
+
+        let same_file = matches!(
+            (source.file().inode(), sink.file().inode()),
+            (Some(a), Some(b)) if a.file_key() == b.file_key()
+        );
+
+        if same_file {
+            let (Some(from), Some(to)) = (source.position(), sink.position()) else {
+                return Err(ErrNo::InvalidArgument);
+            };
+
+            if from < to.saturating_add(len) && to < from.saturating_add(len) {
+                return Err(ErrNo::InvalidArgument);
+            }
+        }
+
+        let moved = transfer_when_ready(&mut source, &mut sink, len, false).await?;
+
+        self.export_offset(off_in, &source)?;
+        self.export_offset(off_out, &sink)?;
+
+        Ok(moved)
+    }
+}
+
+/// Only regular files have ranges to copy.
+fn ensure_regular(file: &dyn IFile) -> Result<(), ErrNo> {
+    let node = file.inode().ok_or(ErrNo::InvalidArgument)?;
+
+    match node.metadata().entry_type {
+        DirectoryEntryType::File => Ok(()),
+        DirectoryEntryType::Directory => Err(ErrNo::IsADirectory),
+        _ => Err(ErrNo::InvalidArgument),
+    }
+}
+
+// The tests run on a scratch host directory, which only unix hosts have
+#[cfg(all(test, unix))]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::open_fd,
+        transfer::tests::{pipe_fds, setup_cached_context},
+    };
+
+    #[test]
+    fn test_between_host_files() {
+        let (ctx, root, host) = setup_cached_context();
+
+        let source = root.touch("source").unwrap();
+        source.writeat(0, &[7; 5000]).unwrap();
+        let source = open_fd(&ctx, &source, OpenFlags::O_RDONLY) as usize;
+
+        let sink = root.touch("sink").unwrap();
+        let sink_fd = open_fd(&ctx, &sink, OpenFlags::O_WRONLY) as usize;
+
+        let off_out = Box::new(100i64);
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(off_out.as_ref(), true);
+        let null = VirtualAddress::null();
+
+        // Across a page boundary, up to the end of the source
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(source, null, sink_fd, ptr, 8192, 0)),
+            Ok(5000)
+        );
+        assert_eq!(*off_out, 5100);
+        assert_eq!(
+            ctx.file_of(source).unwrap().metadata().unwrap().offset(),
+            5000
+        );
+
+        sink.sync().unwrap();
+        let content = std::fs::read(host.host_path("/sink")).unwrap();
+        assert_eq!(content.len(), 5100);
+        assert!(content[..100].iter().all(|&b| b == 0));
+        assert!(content[100..].iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn test_within_a_file() {
+        let (ctx, root, _) = setup_cached_context();
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"0123456789").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+
+        let off_in = Box::new(0i64);
+        let off_out = Box::new(4i64);
+        let mmu = ctx.task.process().mmu();
+        let in_ptr = mmu.lock().register(off_in.as_ref(), true);
+        let out_ptr = mmu.lock().register(off_out.as_ref(), true);
+
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(fd, in_ptr, fd, out_ptr, 5, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(fd, in_ptr, fd, out_ptr, 4, 0)),
+            Ok(4)
+        );
+
+        let mut buf = [0; 10];
+        file.readat(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"0123012389");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root, _) = setup_cached_context();
+
+        let file = root.touch("file").unwrap();
+        let file = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let dir = root.mkdir("dir").unwrap();
+        let dir = open_fd(&ctx, &dir, OpenFlags::O_RDONLY) as usize;
+        let (read_end, _write_end) = pipe_fds(&ctx, OpenFlags::NONE);
+
+        let null = VirtualAddress::null();
+
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(dir, null, file, null, 8, 0)),
+            Err(ErrNo::IsADirectory)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(read_end, null, file, null, 8, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_copy_file_range(file, null, file, null, 8, 1)),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
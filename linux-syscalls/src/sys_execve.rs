@@ -1,22 +1,65 @@
 use abstractions::IUsizeAlias;
-use address::VirtualAddress;
+use address::{IAddressBase, VirtualAddress};
+use alloc::{string::String, vec::Vec};
 use constants::ErrNo;
 use linux_loader::auxv::AuxVecValues;
 use linux_loader::{IExecSource, LinuxLoader, ProcessContext, RawMemorySpace};
 use platform_specific::ITaskContext;
 use platform_specific::TaskTrapContext;
-use task_abstractions::status::TaskStatus;
+use task_abstractions::{status::TaskStatus, ProcessImage};
 
-use crate::{SyscallContext, SyscallResult};
+use crate::{fs::AT_FDCWD, SyscallContext, SyscallResult};
 
 impl SyscallContext {
+    /// Replaces the program of the calling process with the executable at `pathname`, which
+    /// has to be a regular file the caller may execute.
     pub fn sys_execve(
         &self,
-        _pathname: VirtualAddress,
-        _argv: VirtualAddress,
-        _envp: VirtualAddress,
+        pathname: VirtualAddress,
+        argv: VirtualAddress,
+        envp: VirtualAddress,
     ) -> SyscallResult {
-        todo!()
+        let credentials = self.credentials();
+        let path = self.read_path(pathname)?;
+        let executable = self.lookup_at(AT_FDCWD, pathname, 0, &credentials)?;
+
+        executable
+            .check_exec(&credentials)
+            .map_err(|e| e.to_errno())?;
+
+        let argv = self.read_string_array(argv)?;
+        let envp = self.read_string_array(envp)?;
+
+        let argv = argv.iter().map(String::as_str).collect::<Vec<_>>();
+        let envp = envp.iter().map(String::as_str).collect::<Vec<_>>();
+
+        self.sys_execve_internal(executable, &path, &argv, &envp)
+    }
+
+    /// Reads a null-terminated array of strings like `argv` from user space, null is taken as
+    /// an empty one.
+    fn read_string_array(&self, vaddr: VirtualAddress) -> Result<Vec<String>, ErrNo> {
+        let mut strings = Vec::new();
+
+        if vaddr.is_null() {
+            return Ok(strings);
+        }
+
+        loop {
+            let pointer = self
+                .task
+                .process()
+                .mmu()
+                .lock()
+                .import::<usize>(vaddr + strings.len() * size_of::<usize>())
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            if pointer == 0 {
+                return Ok(strings);
+            }
+
+            strings.push(self.read_path(VirtualAddress::from_usize(pointer))?);
+        }
     }
 
     /// Perform an execve-like replacement of the current task's address space with a new executable.
@@ -49,7 +92,6 @@ impl SyscallContext {
     /// // Given a `ctx: SyscallContext`, an executable `exe` and path:
     /// let _ = ctx.sys_execve_internal(exe, "/bin/app", &["app", "--help"], &[]);
     /// ```
-    #[expect(unused)]
     fn sys_execve_internal(
         &self,
         executable: impl IExecSource,
@@ -64,7 +106,7 @@ impl SyscallContext {
             (mem.mmu().clone(), mem.allocator().clone())
         };
 
-        let mut process_ctx = ProcessContext::new();
+        let process_ctx = ProcessContext::new();
 
         // FIXME: Pass argv, envp
 
@@ -85,7 +127,13 @@ impl SyscallContext {
 
         let calling_thread = self.task.tid();
 
-        process.execve(loader.memory_space, calling_thread);
+        let image = ProcessImage {
+            executable: pathname.to_string(),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            envp: envp.iter().map(|env| env.to_string()).collect(),
+        };
+
+        process.execve(loader.memory_space, calling_thread, image);
 
         let trap_ctx = TaskTrapContext::new(
             loader.entry_pc.as_usize(),
@@ -102,3 +150,36 @@ impl SyscallContext {
         Ok(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::tests::{own, setup_fs_context, UserPath};
+
+    fn execve(ctx: &SyscallContext, path: &str) -> SyscallResult {
+        let path = UserPath::new(ctx, path);
+
+        ctx.sys_execve(path.addr(), VirtualAddress::null(), VirtualAddress::null())
+    }
+
+    #[test]
+    fn test_only_executable_files() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        own(&root.touch("script").unwrap(), 1000, 0o644);
+        own(&root.mkdir("bin").unwrap(), 1000, 0o755);
+
+        assert_eq!(execve(&ctx, "script"), Err(ErrNo::PermissionDenied));
+        assert_eq!(execve(&ctx, "bin"), Err(ErrNo::PermissionDenied));
+        assert_eq!(execve(&ctx, "missing"), Err(ErrNo::NoSuchFileOrDirectory));
+    }
+
+    #[test]
+    fn test_root_needs_an_execute_bit() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        own(&root.touch("data").unwrap(), 1000, 0o666);
+
+        assert_eq!(execve(&ctx, "/data"), Err(ErrNo::PermissionDenied));
+    }
+}
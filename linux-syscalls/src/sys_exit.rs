@@ -7,13 +7,38 @@ impl SyscallContext {
         self.task.update_status(TaskStatus::Exited);
         *self.task.linux_process().exit_code().lock() = Some(code);
 
+        let process = self.task.process();
+        let pid = process.pid();
+
+        // The process exits with its last thread
+        let last = process
+            .threads()
+            .iter()
+            .all(|thread| thread.status() == TaskStatus::Exited);
+
+        // Locks of open file descriptions go once the descriptor table is dropped
+        if last {
+            self.kernel.locks().release_process(pid);
+        }
+
+        // Semaphore adjustments stay while a process cloned with `CLONE_SYSVSEM` shares them
+        if last {
+            self.kernel.semaphores().release_process(pid);
+        }
+
         Ok(code as isize)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use test_utilities::{kernel::TestKernel, task::TestProcess};
+    use filesystem_abstractions::{Credentials, FileKey, LockKind, LockOwner, RecordLock};
+    use ipc::{IpcGetFlags, SemaphoreOperation, SemaphoreSet, IPC_PRIVATE};
+    use test_utilities::{
+        kernel::TestKernel,
+        task::{TestProcess, TestTask},
+    };
+    use threading::block_on;
 
     use super::*;
 
@@ -24,6 +49,23 @@ mod tests {
         SyscallContext::new(task, kernel)
     }
 
+    /// Two threads of the same process.
+    fn setup_threads() -> (SyscallContext, SyscallContext) {
+        let kernel = TestKernel::new().build();
+        let (process, task) = TestProcess::new().with_pid(1).build();
+
+        let other = TestTask::new()
+            .with_tid(2)
+            .with_linux_process(Some(process.clone()))
+            .build();
+        process.push_thread(other.clone());
+
+        (
+            SyscallContext::new(task, kernel.clone()),
+            SyscallContext::new(other, kernel),
+        )
+    }
+
     #[test]
     fn test_no_exit_code_before_call() {
         let ctx = setup_env();
@@ -60,4 +102,67 @@ mod tests {
 
         assert_eq!(ctx.task.status(), TaskStatus::Exited);
     }
+
+    #[test]
+    fn test_semaphore_adjustments_undone() {
+        let (ctx, thread) = setup_threads();
+        let pid = ctx.task.process().pid();
+        let semaphores = ctx.kernel.semaphores();
+
+        let id = semaphores
+            .sets()
+            .get(
+                IPC_PRIVATE,
+                IpcGetFlags::IPC_CREAT,
+                0o600,
+                &Credentials::root(),
+                |_| Ok(()),
+                |permissions| Ok(SemaphoreSet::new(permissions, 1, 0)),
+            )
+            .unwrap();
+        let set = semaphores.sets().find(id).unwrap();
+
+        let operations = [SemaphoreOperation {
+            num: 0,
+            op: 2,
+            flags: SemaphoreOperation::SEM_UNDO,
+        }];
+        let undo = semaphores.undo_of(pid);
+        block_on!(set.operate(id, &operations, pid, Some(&undo), 0)).unwrap();
+        drop(undo);
+
+        // Undone with the process, not with the thread that made them
+        ctx.sys_exit(0).unwrap();
+        assert_eq!(set.values(), [2]);
+
+        thread.sys_exit(0).unwrap();
+        assert_eq!(set.values(), [0]);
+    }
+
+    #[test]
+    fn test_locks_kept_until_last_thread_exits() {
+        let (ctx, thread) = setup_threads();
+        let locks = ctx.kernel.locks();
+
+        let key = FileKey { mount: 0, inode: 1 };
+        let lock = RecordLock {
+            owner: LockOwner::Process(1),
+            kind: LockKind::Exclusive,
+            start: 0,
+            end: u64::MAX,
+            pid: 1,
+        };
+        let other = RecordLock {
+            owner: LockOwner::Process(2),
+            pid: 2,
+            ..lock
+        };
+        locks.try_lock_records(key, lock).unwrap();
+
+        ctx.sys_exit(0).unwrap();
+        assert!(locks.conflicting(key, &other).is_some());
+
+        thread.sys_exit(0).unwrap();
+        assert!(locks.conflicting(key, &other).is_none());
+    }
 }
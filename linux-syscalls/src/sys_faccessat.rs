@@ -0,0 +1,119 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::AccessMode;
+
+use crate::{
+    fs::{AT_EACCESS, AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    pub fn sys_faccessat(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        mode: u32,
+    ) -> SyscallResult {
+        self.sys_faccessat2(dirfd, pathname, mode, 0)
+    }
+
+    /// Checks the file could be accessed with `mode`, or that it exists for `F_OK` (0).
+    ///
+    /// The real ids are checked, so set-user-ID programs can tell what their caller may do,
+    /// unless `AT_EACCESS` asks for the effective ones.
+    pub fn sys_faccessat2(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        mode: u32,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let access = AccessMode::from_bits(mode).ok_or(ErrNo::InvalidArgument)?;
+
+        let mut credentials = self.credentials();
+
+        if flags & AT_EACCESS == 0 {
+            credentials.fsuid = credentials.uid;
+            credentials.fsgid = credentials.gid;
+        }
+
+        let node = self.lookup_at(dirfd, pathname, flags, &credentials)?;
+
+        node.check_access(&credentials, access)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::{
+        tests::{own, setup_fs_context, UserPath},
+        AT_FDCWD,
+    };
+
+    const R_OK: u32 = 4;
+    const W_OK: u32 = 2;
+    const X_OK: u32 = 1;
+
+    #[test]
+    fn test_access_modes() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        own(&root.touch("file").unwrap(), 1000, 0o640);
+
+        let path = UserPath::new(&ctx, "file");
+        let access = |mode| ctx.sys_faccessat(AT_FDCWD, path.addr(), mode);
+
+        assert_eq!(access(0), Ok(0));
+        assert_eq!(access(R_OK | W_OK), Ok(0));
+        assert_eq!(access(X_OK), Err(ErrNo::PermissionDenied));
+        assert_eq!(access(8), Err(ErrNo::InvalidArgument));
+
+        let missing = UserPath::new(&ctx, "missing");
+        assert_eq!(
+            ctx.sys_faccessat(AT_FDCWD, missing.addr(), 0),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+    }
+
+    #[test]
+    fn test_real_and_effective_ids() {
+        let mut credentials = Credentials::new(2000, 2000);
+        credentials.euid = 1000;
+        credentials.fsuid = 1000;
+
+        let (ctx, root) = setup_fs_context(credentials);
+        own(&root.touch("file").unwrap(), 1000, 0o600);
+
+        let path = UserPath::new(&ctx, "file");
+        assert_eq!(
+            ctx.sys_faccessat2(AT_FDCWD, path.addr(), R_OK, 0),
+            Err(ErrNo::PermissionDenied)
+        );
+        assert_eq!(
+            ctx.sys_faccessat2(AT_FDCWD, path.addr(), R_OK, AT_EACCESS),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_search_permission() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let private = root.mkdir("private").unwrap();
+        own(&private, 1000, 0o700);
+        own(&private.touch("file").unwrap(), 1000, 0o666);
+
+        let path = UserPath::new(&ctx, "/private/file");
+        assert_eq!(
+            ctx.sys_faccessat(AT_FDCWD, path.addr(), 0),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+}
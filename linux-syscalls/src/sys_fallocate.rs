@@ -0,0 +1,107 @@
+use constants::ErrNo;
+use filesystem_abstractions::DirectoryEntryType;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Allocate without changing the size of the file
+const FALLOC_FL_KEEP_SIZE: usize = 0x1;
+
+impl SyscallContext {
+    /// Makes sure `len` bytes from `offset` can be written, growing the file unless
+    /// `FALLOC_FL_KEEP_SIZE` is given.
+    ///
+    /// No filesystem reserves blocks ahead, so this only changes the size. Punching holes and the
+    /// other modes are not supported.
+    pub fn sys_fallocate(
+        &self,
+        fd: usize,
+        mode: usize,
+        offset: isize,
+        len: isize,
+    ) -> SyscallResult {
+        if mode & !FALLOC_FL_KEEP_SIZE != 0 {
+            return Err(ErrNo::OperationNotSupported);
+        }
+
+        if offset < 0 || len <= 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let end = offset.checked_add(len).ok_or(ErrNo::FileTooLarge)? as u64;
+
+        let file = self.file_of(fd)?;
+        let node = file.inode().ok_or(ErrNo::NoSuchDevice)?;
+
+        if !file.can_write() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        match node.metadata().entry_type {
+            DirectoryEntryType::File => (),
+            DirectoryEntryType::Directory => return Err(ErrNo::IsADirectory),
+            _ => return Err(ErrNo::NoSuchDevice),
+        }
+
+        if mode & FALLOC_FL_KEEP_SIZE != 0 || end <= node.metadata().size as u64 {
+            return Ok(0);
+        }
+
+        node.resize_inode(end).map(|_| 0).map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, setup_fs_context};
+
+    #[test]
+    fn test_grows_file() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"data").unwrap();
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_WRONLY) as usize;
+
+        assert_eq!(ctx.sys_fallocate(fd, 0, 0, 2), Ok(0));
+        assert_eq!(file.metadata().size, 4);
+
+        assert_eq!(ctx.sys_fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, 4096), Ok(0));
+        assert_eq!(file.metadata().size, 4);
+
+        assert_eq!(ctx.sys_fallocate(fd, 0, 4000, 96), Ok(0));
+        assert_eq!(file.metadata().size, 4096);
+        assert_eq!(&file.readall().unwrap()[..6], b"data\0\0");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let dir = root.mkdir("dir").unwrap();
+
+        let readonly = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        let writable = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let dir = open_fd(&ctx, &dir, OpenFlags::O_RDWR) as usize;
+
+        assert_eq!(
+            ctx.sys_fallocate(writable, 0x2, 0, 1),
+            Err(ErrNo::OperationNotSupported)
+        );
+        assert_eq!(
+            ctx.sys_fallocate(writable, 0, -1, 1),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_fallocate(writable, 0, 0, 0),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_fallocate(readonly, 0, 0, 1),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(ctx.sys_fallocate(dir, 0, 0, 1), Err(ErrNo::IsADirectory));
+    }
+}
@@ -0,0 +1,74 @@
+use address::VirtualAddress;
+use filesystem_abstractions::{FileMode, InodeAttributes};
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Changes the permission bits, only the owner or root may. Symbolic links are followed.
+    pub fn sys_fchmodat(&self, dirfd: isize, pathname: VirtualAddress, mode: u32) -> SyscallResult {
+        let credentials = self.credentials();
+        let node = self.lookup_at(dirfd, pathname, 0, &credentials)?;
+
+        let attributes = InodeAttributes {
+            mode: Some(FileMode::from_bits_truncate(mode)),
+            ..Default::default()
+        };
+
+        node.set_attributes_as(&attributes, &credentials)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::ErrNo;
+    use filesystem_abstractions::Credentials;
+
+    use crate::fs::{
+        tests::{own, setup_fs_context, stat, UserPath},
+        AT_FDCWD,
+    };
+
+    #[test]
+    fn test_owner_changes_mode() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+
+        let path = UserPath::new(&ctx, "file");
+        assert_eq!(ctx.sys_fchmodat(AT_FDCWD, path.addr(), 0o4700), Ok(0));
+        assert_eq!(stat(&file).mode.bits(), 0o104700);
+    }
+
+    #[test]
+    fn test_others_can_not() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o666);
+
+        let path = UserPath::new(&ctx, "/file");
+        assert_eq!(
+            ctx.sys_fchmodat(AT_FDCWD, path.addr(), 0o777),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(stat(&file).mode.bits(), 0o100666);
+
+        let missing = UserPath::new(&ctx, "missing");
+        assert_eq!(
+            ctx.sys_fchmodat(AT_FDCWD, missing.addr(), 0o777),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+    }
+
+    #[test]
+    fn test_follows_links() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        root.soft_link("link", "file").unwrap();
+
+        let path = UserPath::new(&ctx, "link");
+        assert_eq!(ctx.sys_fchmodat(AT_FDCWD, path.addr(), 0o600), Ok(0));
+        assert_eq!(stat(&file).mode.bits(), 0o100600);
+    }
+}
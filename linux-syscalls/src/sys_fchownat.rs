@@ -0,0 +1,121 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::InodeAttributes;
+
+use crate::{
+    fs::{AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Changes the owner and group, -1 leaves one as it is. Only root may give files away, the
+    /// owner may change the group to one it is in.
+    pub fn sys_fchownat(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        uid: u32,
+        gid: u32,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let credentials = self.credentials();
+        let node = self.lookup_at(dirfd, pathname, flags, &credentials)?;
+
+        let attributes = InodeAttributes {
+            uid: (uid != u32::MAX).then_some(uid),
+            gid: (gid != u32::MAX).then_some(gid),
+            ..Default::default()
+        };
+
+        node.set_attributes_as(&attributes, &credentials)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::fs::{
+        tests::{open_fd, own, setup_fs_context, stat, UserPath},
+        AT_FDCWD,
+    };
+
+    #[test]
+    fn test_root_gives_away() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        own(&file, 0, 0o6755);
+
+        let path = UserPath::new(&ctx, "file");
+        assert_eq!(ctx.sys_fchownat(AT_FDCWD, path.addr(), 1000, 100, 0), Ok(0));
+
+        let stat = stat(&file);
+        assert_eq!((stat.uid, stat.gid), (1000, 100));
+        // Set-user-ID and set-group-ID do not move along with the file
+        assert_eq!(stat.mode.bits(), 0o100755);
+    }
+
+    #[test]
+    fn test_owner_changes_group() {
+        let mut credentials = Credentials::new(1000, 1000);
+        credentials.groups = alloc::vec![100];
+
+        let (ctx, root) = setup_fs_context(credentials);
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+
+        let path = UserPath::new(&ctx, "file");
+        assert_eq!(
+            ctx.sys_fchownat(AT_FDCWD, path.addr(), u32::MAX, 100, 0),
+            Ok(0)
+        );
+        assert_eq!(stat(&file).gid, 100);
+
+        assert_eq!(
+            ctx.sys_fchownat(AT_FDCWD, path.addr(), u32::MAX, 200, 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            ctx.sys_fchownat(AT_FDCWD, path.addr(), 2000, u32::MAX, 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            ctx.sys_fchownat(AT_FDCWD, path.addr(), 1000, 1000, 0x8000),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_links_and_empty_paths() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let link = root.soft_link("link", "file").unwrap();
+
+        let path = UserPath::new(&ctx, "link");
+        assert_eq!(
+            ctx.sys_fchownat(AT_FDCWD, path.addr(), 5, 5, AT_SYMLINK_NOFOLLOW),
+            Ok(0)
+        );
+        assert_eq!(stat(&link).uid, 5);
+        assert_eq!(stat(&file).uid, 0);
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY);
+        let empty = UserPath::new(&ctx, "");
+        assert_eq!(
+            ctx.sys_fchownat(fd, empty.addr(), 7, 7, 0),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(
+            ctx.sys_fchownat(fd, empty.addr(), 7, 7, AT_EMPTY_PATH),
+            Ok(0)
+        );
+        assert_eq!(stat(&file).uid, 7);
+    }
+}
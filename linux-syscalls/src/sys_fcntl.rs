@@ -0,0 +1,509 @@
+use abstractions::IUsizeAlias;
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::{
+    FileMetadata, FileSeals, LockKind, LockOwner, OpenFlags, RecordLock,
+};
+
+use crate::{SyscallContext, SyscallResult};
+
+const F_GETLK: usize = 5;
+const F_SETLK: usize = 6;
+const F_SETLKW: usize = 7;
+const F_OFD_GETLK: usize = 36;
+const F_OFD_SETLK: usize = 37;
+const F_OFD_SETLKW: usize = 38;
+const F_ADD_SEALS: usize = 1033;
+const F_GET_SEALS: usize = 1034;
+
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+
+/// `struct flock`, describing a record lock.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flock {
+    pub l_type: i16,
+    /// What `l_start` is relative to, like the whence of `lseek`
+    pub l_whence: i16,
+    pub l_start: i64,
+    /// How many bytes are locked, 0 for up to the end of the file and negative for the bytes
+    /// before `l_start`
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+impl SyscallContext {
+    /// Only the record lock commands are supported, POSIX locks owned by the process and OFD
+    /// locks owned by the open file description, along with the seals of memfds.
+    pub async fn sys_fcntl(&self, fd: usize, cmd: usize, arg: VirtualAddress) -> SyscallResult {
+        match cmd {
+            F_GETLK | F_OFD_GETLK => self.get_record_lock(fd, arg, cmd == F_OFD_GETLK),
+            F_SETLK | F_OFD_SETLK => {
+                self.set_record_lock(fd, arg, cmd == F_OFD_SETLK, false)
+                    .await
+            }
+            F_SETLKW | F_OFD_SETLKW => {
+                self.set_record_lock(fd, arg, cmd == F_OFD_SETLKW, true)
+                    .await
+            }
+            F_ADD_SEALS => self.add_seals(fd, arg.as_usize()),
+            F_GET_SEALS => self.get_seals(fd),
+            _ => Err(ErrNo::InvalidArgument),
+        }
+    }
+
+    /// Seals the memfd at `fd`, which has to be open for writing.
+    fn add_seals(&self, fd: usize, seals: usize) -> SyscallResult {
+        let seals = u32::try_from(seals)
+            .ok()
+            .and_then(FileSeals::from_bits)
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        let file = self.file_of(fd)?;
+        let node = file.inode().ok_or(ErrNo::InvalidArgument)?;
+
+        if node.seals().is_none() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        if !file.can_write() {
+            return Err(ErrNo::OperationNotPermitted);
+        }
+
+        node.add_seals(seals).map(|_| 0).map_err(|e| e.to_errno())
+    }
+
+    fn get_seals(&self, fd: usize) -> SyscallResult {
+        let node = self.file_of(fd)?.inode().ok_or(ErrNo::InvalidArgument)?;
+
+        node.seals()
+            .map(|seals| seals.bits() as isize)
+            .ok_or(ErrNo::InvalidArgument)
+    }
+
+    fn import_flock(&self, arg: VirtualAddress, ofd: bool) -> Result<Flock, ErrNo> {
+        let flock = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<Flock>(arg)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        // Reserved for future use with OFD locks
+        if ofd && flock.l_pid != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        Ok(flock)
+    }
+
+    /// Works out the bytes `flock` covers in the file open with `metadata`.
+    fn lock_range(flock: &Flock, metadata: &FileMetadata) -> Result<(u64, u64), ErrNo> {
+        const SEEK_SET: i16 = 0;
+        const SEEK_CUR: i16 = 1;
+        const SEEK_END: i16 = 2;
+
+        let base = match flock.l_whence {
+            SEEK_SET => 0,
+            SEEK_CUR => metadata.offset() as i64,
+            SEEK_END => metadata.inode().metadata().size as i64,
+            _ => return Err(ErrNo::InvalidArgument),
+        };
+
+        let start = base
+            .checked_add(flock.l_start)
+            .ok_or(ErrNo::ValueTooLargeForDefinedDataType)?;
+
+        let (start, end) = match flock.l_len {
+            0 => (start, i64::MAX),
+            len if len > 0 => (start, start.saturating_add(len)),
+            len => (start + len, start),
+        };
+
+        if start < 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let end = match end {
+            i64::MAX => u64::MAX,
+            end => end as u64,
+        };
+
+        Ok((start as u64, end))
+    }
+
+    fn record_lock(
+        &self,
+        flock: &Flock,
+        metadata: &FileMetadata,
+        ofd: bool,
+    ) -> Result<RecordLock, ErrNo> {
+        let kind = match flock.l_type {
+            F_RDLCK => LockKind::Shared,
+            F_WRLCK => LockKind::Exclusive,
+            _ => return Err(ErrNo::InvalidArgument),
+        };
+
+        let (start, end) = Self::lock_range(flock, metadata)?;
+        let pid = self.task.process().pid();
+
+        Ok(RecordLock {
+            owner: match ofd {
+                true => LockOwner::File(metadata.lock_owner()),
+                false => LockOwner::Process(pid),
+            },
+            kind,
+            start,
+            end,
+            pid,
+        })
+    }
+
+    /// Tells whether the lock in `arg` could be placed, otherwise it is overwritten with one of
+    /// the locks in its way.
+    fn get_record_lock(&self, fd: usize, arg: VirtualAddress, ofd: bool) -> SyscallResult {
+        let (metadata, key) = self.lockable_file_of(fd)?;
+        let mut flock = self.import_flock(arg, ofd)?;
+        let lock = self.record_lock(&flock, &metadata, ofd)?;
+
+        match self.kernel.locks().conflicting(key, &lock) {
+            None => flock.l_type = F_UNLCK,
+            Some(holder) => {
+                flock = Flock {
+                    l_type: match holder.kind {
+                        LockKind::Shared => F_RDLCK,
+                        LockKind::Exclusive => F_WRLCK,
+                    },
+                    l_whence: 0,
+                    l_start: holder.start as i64,
+                    l_len: match holder.end {
+                        u64::MAX => 0,
+                        end => (end - holder.start) as i64,
+                    },
+                    // OFD locks do not belong to any one process
+                    l_pid: match holder.owner {
+                        LockOwner::Process(_) => holder.pid as i32,
+                        LockOwner::File(_) => -1,
+                    },
+                }
+            }
+        }
+
+        self.task
+            .process()
+            .mmu()
+            .lock()
+            .export(arg, flock)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        Ok(0)
+    }
+
+    async fn set_record_lock(
+        &self,
+        fd: usize,
+        arg: VirtualAddress,
+        ofd: bool,
+        wait: bool,
+    ) -> SyscallResult {
+        let (metadata, key) = self.lockable_file_of(fd)?;
+        let flock = self.import_flock(arg, ofd)?;
+        let locks = self.kernel.locks();
+
+        if flock.l_type == F_UNLCK {
+            let (start, end) = Self::lock_range(&flock, &metadata)?;
+            let owner = match ofd {
+                true => LockOwner::File(metadata.lock_owner()),
+                false => LockOwner::Process(self.task.process().pid()),
+            };
+
+            locks.unlock_records(key, owner, start, end);
+
+            return Ok(0);
+        }
+
+        let lock = self.record_lock(&flock, &metadata, ofd)?;
+
+        let flags = *metadata.flags();
+        let permitted = match lock.kind {
+            LockKind::Shared => !flags.contains(OpenFlags::O_WRONLY),
+            LockKind::Exclusive => flags.intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR),
+        };
+
+        if !permitted {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        if ofd {
+            metadata.hold_locks_in(&locks);
+        }
+
+        match wait {
+            true => locks.lock_records(key, lock).await,
+            false => locks.try_lock_records(key, lock),
+        }
+        .map(|_| 0)
+        .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IToPageNum;
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use filesystem_abstractions::{Credentials, FileDescriptorTable, FileSystemError};
+    use memory_space::MemorySpace;
+    use test_utilities::{allocation::segment::TestFrameAllocator, task::TestProcess};
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::{open_fd, setup_fs_context},
+        sys_memfd_create::{
+            tests::{map_shared, memfd_create, setup_memfd_context},
+            MFD_ALLOW_SEALING,
+        },
+    };
+
+    fn flock(l_type: i16, l_start: i64, l_len: i64) -> Flock {
+        Flock {
+            l_type,
+            l_start,
+            l_len,
+            ..Default::default()
+        }
+    }
+
+    /// Another process with its own descriptor of the file at `/file`, sharing the kernel.
+    fn other_process(ctx: &SyscallContext, pid: u32) -> (SyscallContext, usize) {
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu();
+        let (_, task) = TestProcess::new()
+            .with_pid(pid)
+            .with_memory_space(Some(MemorySpace::new(mmu, alloc)))
+            .with_fd_table(Some(FileDescriptorTable::new()))
+            .build();
+        let other = SyscallContext::new(task, ctx.kernel.clone());
+
+        let root = ctx.kernel.fs().lock().clone();
+        let file = root.open("/file", Some(&root)).unwrap();
+        let fd = open_fd(&other, &file, OpenFlags::O_RDWR) as usize;
+
+        (other, fd)
+    }
+
+    fn fcntl(ctx: &SyscallContext, fd: usize, cmd: usize, lock: &mut Flock) -> SyscallResult {
+        let mmu = ctx.task.process().mmu();
+        mmu.lock().register(&*lock, true);
+
+        let result = block_on!(ctx.sys_fcntl(fd, cmd, VirtualAddress::from_ref(&*lock)));
+
+        *lock = mmu.lock().import(VirtualAddress::from_ref(&*lock)).unwrap();
+        mmu.lock().unregister(&*lock);
+
+        result
+    }
+
+    #[test]
+    fn test_posix_locks_between_processes() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let (other, other_fd) = other_process(&ctx, 42);
+
+        assert_eq!(fcntl(&ctx, fd, F_SETLK, &mut flock(F_WRLCK, 10, 20)), Ok(0));
+        assert_eq!(
+            fcntl(&other, other_fd, F_SETLK, &mut flock(F_RDLCK, 0, 15)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let mut probe = flock(F_WRLCK, 0, 0);
+        assert_eq!(fcntl(&other, other_fd, F_GETLK, &mut probe), Ok(0));
+        assert_eq!(
+            probe,
+            Flock {
+                l_type: F_WRLCK,
+                l_start: 10,
+                l_len: 20,
+                l_pid: 0,
+                ..Default::default()
+            }
+        );
+
+        // Bytes before 10 are free
+        assert_eq!(
+            fcntl(&other, other_fd, F_SETLK, &mut flock(F_WRLCK, 10, -10)),
+            Ok(0)
+        );
+
+        // Closing any descriptor of the file drops the locks of the process
+        let again = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        assert_eq!(ctx.sys_close(again), Ok(0));
+
+        let mut probe = flock(F_WRLCK, 10, 0);
+        assert_eq!(fcntl(&other, other_fd, F_GETLK, &mut probe), Ok(0));
+        assert_eq!(probe.l_type, F_UNLCK);
+    }
+
+    #[test]
+    fn test_wait_for_release() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let (other, other_fd) = other_process(&ctx, 42);
+
+        assert_eq!(fcntl(&ctx, fd, F_SETLK, &mut flock(F_WRLCK, 0, 0)), Ok(0));
+
+        let lock = flock(F_WRLCK, 100, 1);
+        other.task.process().mmu().lock().register(&lock, false);
+
+        let mut waiting =
+            pin!(other.sys_fcntl(other_fd, F_SETLKW, VirtualAddress::from_ref(&lock)));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        ctx.sys_exit(0).unwrap();
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(Ok(0)));
+    }
+
+    #[test]
+    fn test_ofd_locks() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let first = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let second = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+
+        assert_eq!(
+            fcntl(&ctx, first, F_OFD_SETLK, &mut flock(F_WRLCK, 0, 10)),
+            Ok(0)
+        );
+
+        // Descriptions conflict even within one process
+        assert_eq!(
+            fcntl(&ctx, second, F_OFD_SETLK, &mut flock(F_WRLCK, 5, 1)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+        assert_eq!(
+            fcntl(&ctx, second, F_SETLK, &mut flock(F_WRLCK, 5, 1)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let mut probe = flock(F_RDLCK, 0, 0);
+        assert_eq!(fcntl(&ctx, second, F_OFD_GETLK, &mut probe), Ok(0));
+        assert_eq!((probe.l_type, probe.l_pid), (F_WRLCK, -1));
+
+        let mut with_pid = Flock {
+            l_pid: 1,
+            ..flock(F_RDLCK, 0, 0)
+        };
+        assert_eq!(
+            fcntl(&ctx, second, F_OFD_GETLK, &mut with_pid),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        // Released once the description is gone
+        assert_eq!(ctx.sys_close(first), Ok(0));
+        assert_eq!(
+            fcntl(&ctx, second, F_OFD_SETLK, &mut flock(F_WRLCK, 5, 1)),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_lock_needs_access() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+
+        assert_eq!(
+            fcntl(&ctx, fd, F_SETLK, &mut flock(F_WRLCK, 0, 0)),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(
+            fcntl(&ctx, fd, F_SETLK, &mut flock(F_RDLCK, -1, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(fcntl(&ctx, fd, F_SETLK, &mut flock(F_RDLCK, 0, 0)), Ok(0));
+    }
+
+    fn seals(ctx: &SyscallContext, fd: usize, cmd: usize, seals: FileSeals) -> SyscallResult {
+        let arg = VirtualAddress::from_usize(seals.bits() as usize);
+
+        block_on!(ctx.sys_fcntl(fd, cmd, arg))
+    }
+
+    #[test]
+    fn test_seal_memfd() {
+        let (ctx, _) = setup_memfd_context();
+        let fd = memfd_create(&ctx, "sealed", MFD_ALLOW_SEALING).unwrap() as usize;
+        let node = ctx.file_of(fd).unwrap().inode().unwrap();
+        node.writeat(0, b"hello").unwrap();
+
+        let none = FileSeals::empty();
+        assert_eq!(seals(&ctx, fd, F_GET_SEALS, none), Ok(0));
+
+        let size = FileSeals::F_SEAL_SHRINK | FileSeals::F_SEAL_GROW;
+        assert_eq!(seals(&ctx, fd, F_ADD_SEALS, size), Ok(0));
+        assert_eq!(ctx.sys_ftruncate(fd, 2), Err(ErrNo::OperationNotPermitted));
+        assert_eq!(ctx.sys_ftruncate(fd, 8), Err(ErrNo::OperationNotPermitted));
+        assert_eq!(node.writeat(3, b"LO"), Ok(2));
+        assert_eq!(node.writeat(4, b"!!"), Err(FileSystemError::NotPermitted));
+
+        // Not while it is mapped writable
+        let addr = map_shared(&ctx, fd).unwrap() as usize;
+        assert_eq!(
+            seals(&ctx, fd, F_ADD_SEALS, FileSeals::F_SEAL_WRITE),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+
+        let process = ctx.task.process();
+        let vpn = VirtualAddress::from_usize(addr).to_floor_page_num();
+        assert!(process.memory_space().lock().unmap_area_starts_with(vpn));
+
+        assert_eq!(seals(&ctx, fd, F_ADD_SEALS, FileSeals::F_SEAL_WRITE), Ok(0));
+        assert_eq!(node.writeat(0, b"J"), Err(FileSystemError::NotPermitted));
+        assert_eq!(map_shared(&ctx, fd), Err(ErrNo::OperationNotPermitted));
+
+        assert_eq!(seals(&ctx, fd, F_ADD_SEALS, FileSeals::F_SEAL_SEAL), Ok(0));
+        assert_eq!(
+            seals(&ctx, fd, F_GET_SEALS, none),
+            Ok((size | FileSeals::F_SEAL_WRITE | FileSeals::F_SEAL_SEAL).bits() as isize)
+        );
+        assert_eq!(
+            seals(&ctx, fd, F_ADD_SEALS, FileSeals::F_SEAL_FUTURE_WRITE),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+
+    #[test]
+    fn test_seals_need_sealable_file() {
+        let (ctx, _) = setup_memfd_context();
+        let fd = memfd_create(&ctx, "unsealable", 0).unwrap() as usize;
+
+        let seal = FileSeals::F_SEAL_SEAL;
+        assert_eq!(seals(&ctx, fd, F_GET_SEALS, seal), Ok(seal.bits() as isize));
+        assert_eq!(
+            seals(&ctx, fd, F_ADD_SEALS, FileSeals::F_SEAL_GROW),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            seals(&ctx, fd, F_ADD_SEALS, FileSeals::from_bits_retain(0x100)),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+
+        assert_eq!(
+            seals(&ctx, fd, F_GET_SEALS, seal),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use constants::ErrNo;
+use filesystem_abstractions::LockKind;
+
+use crate::{SyscallContext, SyscallResult};
+
+const LOCK_SH: usize = 1;
+const LOCK_EX: usize = 2;
+/// Fail instead of waiting for a conflicting lock
+const LOCK_NB: usize = 4;
+const LOCK_UN: usize = 8;
+
+impl SyscallContext {
+    /// Takes or releases a whole-file lock for the open file description at `fd`, which is
+    /// shared by its duplicates and released once all of them are closed.
+    pub async fn sys_flock(&self, fd: usize, operation: usize) -> SyscallResult {
+        let (metadata, key) = self.lockable_file_of(fd)?;
+
+        let kind = match operation & !LOCK_NB {
+            LOCK_SH => LockKind::Shared,
+            LOCK_EX => LockKind::Exclusive,
+            LOCK_UN => {
+                self.kernel.locks().unflock(key, metadata.lock_owner());
+                return Ok(0);
+            }
+            _ => return Err(ErrNo::InvalidArgument),
+        };
+
+        let locks = self.kernel.locks();
+        metadata.hold_locks_in(&locks);
+
+        match operation & LOCK_NB {
+            0 => locks.flock(key, metadata.lock_owner(), kind).await,
+            _ => locks
+                .try_flock(key, metadata.lock_owner(), kind)
+                .map_err(|e| e.to_errno())?,
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use filesystem_abstractions::{Credentials, OpenFlags};
+    use threading::block_on;
+
+    use super::*;
+    use crate::fs::tests::{open_fd, setup_fs_context};
+
+    #[test]
+    fn test_exclusive_between_descriptions() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+
+        let first = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        let second = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+
+        assert_eq!(block_on!(ctx.sys_flock(first, LOCK_EX)), Ok(0));
+        assert_eq!(
+            block_on!(ctx.sys_flock(second, LOCK_SH | LOCK_NB)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let mut waiting = pin!(ctx.sys_flock(second, LOCK_SH));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(block_on!(ctx.sys_flock(first, LOCK_UN)), Ok(0));
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(Ok(0)));
+
+        assert_eq!(
+            block_on!(ctx.sys_flock(first, LOCK_SH | LOCK_EX)),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_released_with_last_descriptor() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        let duplicate = ctx.task.process().fd_table().lock().get(fd).cloned();
+        let duplicate = ctx
+            .task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(duplicate.unwrap())
+            .unwrap();
+        let other = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+
+        assert_eq!(block_on!(ctx.sys_flock(fd, LOCK_EX)), Ok(0));
+
+        // The duplicate shares the description and with it the lock
+        assert_eq!(
+            block_on!(ctx.sys_flock(duplicate, LOCK_EX | LOCK_NB)),
+            Ok(0)
+        );
+
+        assert_eq!(ctx.sys_close(fd), Ok(0));
+        assert_eq!(
+            block_on!(ctx.sys_flock(other, LOCK_EX | LOCK_NB)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        assert_eq!(ctx.sys_close(duplicate), Ok(0));
+        assert_eq!(block_on!(ctx.sys_flock(other, LOCK_EX | LOCK_NB)), Ok(0));
+    }
+}
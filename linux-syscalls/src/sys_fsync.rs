@@ -0,0 +1,100 @@
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Writes back what is cached of the file at `fd` and flushes it to its filesystem.
+    pub fn sys_fsync(&self, fd: usize) -> SyscallResult {
+        let node = self.file_of(fd)?.inode().ok_or(ErrNo::InvalidArgument)?;
+
+        node.sync().map(|_| 0).map_err(|e| e.to_errno())
+    }
+
+    /// The same as [`SyscallContext::sys_fsync`], metadata is never written separately.
+    pub fn sys_fdatasync(&self, fd: usize) -> SyscallResult {
+        self.sys_fsync(fd)
+    }
+
+    /// Writes back everything the page cache holds, never fails like on Linux.
+    pub fn sys_sync(&self) -> SyscallResult {
+        if let Some(cache) = self.kernel.mounts().page_cache() {
+            let _ = cache.sync_all();
+        }
+
+        Ok(0)
+    }
+}
+
+// The tests run on a scratch host directory, which only unix hosts have
+#[cfg(all(test, unix))]
+mod tests {
+    use alloc::sync::Arc;
+    use filesystem_abstractions::{
+        DirectoryTreeNode, FileDescriptorTable, MountTable, OpenFlags, PageCache,
+    };
+    use memory_space::MemorySpace;
+    use test_utilities::{
+        allocation::contiguous::TestFrameAllocator, fs::TestPageAllocator, hostfs::HostFileSystem,
+        kernel::TestKernel, memory::TestMMU, task::TestProcess,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_writes_back_cached_pages() {
+        let alloc = TestFrameAllocator::new(64 * 1024 * 1024);
+        let host = HostFileSystem::scratch();
+
+        let cache = PageCache::new(TestPageAllocator::new(alloc.clone()), 16);
+        let root = DirectoryTreeNode::from_cached_filesystem(None, host.clone(), cache, Some(""));
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"cached").unwrap();
+
+        let mut fd_table = FileDescriptorTable::new();
+        let fd = fd_table
+            .allocate(file.open_as_file(OpenFlags::O_RDWR, 0))
+            .unwrap();
+
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(
+                TestMMU::new(alloc.clone()),
+                alloc.clone(),
+            )))
+            .with_fd_table(Some(fd_table))
+            .build();
+        let ctx = SyscallContext::new(task, TestKernel::new().build());
+
+        // Only in the page cache until synced
+        assert_ne!(std::fs::read(host.host_path("/file")).unwrap(), b"cached");
+
+        assert_eq!(ctx.sys_fsync(fd), Ok(0));
+        assert_eq!(std::fs::read(host.host_path("/file")).unwrap(), b"cached");
+
+        assert_eq!(ctx.sys_fdatasync(fd), Ok(0));
+        assert_eq!(ctx.sys_fsync(fd + 1), Err(ErrNo::BadFileDescriptor));
+    }
+
+    #[test]
+    fn test_sync_writes_back_every_file() {
+        let alloc = TestFrameAllocator::new(64 * 1024 * 1024);
+        let host = HostFileSystem::scratch();
+
+        let cache = PageCache::new(TestPageAllocator::new(alloc.clone()), 16);
+        let root =
+            DirectoryTreeNode::from_cached_filesystem(None, host.clone(), cache.clone(), Some(""));
+
+        root.touch("a").unwrap().writeat(0, b"first").unwrap();
+        root.touch("b").unwrap().writeat(0, b"second").unwrap();
+
+        let (_, task) = TestProcess::new().build();
+        let kernel = TestKernel::new()
+            .with_mounts(Arc::new(MountTable::new(Some(cache))))
+            .build();
+        let ctx = SyscallContext::new(task, kernel);
+
+        assert_eq!(ctx.sys_sync(), Ok(0));
+        assert_eq!(std::fs::read(host.host_path("/a")).unwrap(), b"first");
+        assert_eq!(std::fs::read(host.host_path("/b")).unwrap(), b"second");
+    }
+}
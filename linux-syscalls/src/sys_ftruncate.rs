@@ -0,0 +1,61 @@
+use constants::ErrNo;
+use filesystem_abstractions::DirectoryEntryType;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Cuts or extends the file open for writing at `fd` to `length` bytes.
+    pub fn sys_ftruncate(&self, fd: usize, length: isize) -> SyscallResult {
+        let length = u64::try_from(length).map_err(|_| ErrNo::InvalidArgument)?;
+
+        let file = self.file_of(fd)?;
+        let node = file.inode().ok_or(ErrNo::InvalidArgument)?;
+
+        if node.metadata().entry_type != DirectoryEntryType::File || !file.can_write() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        node.resize_inode(length)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, setup_fs_context};
+
+    #[test]
+    fn test_truncate_and_extend() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"hello world").unwrap();
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+
+        assert_eq!(ctx.sys_ftruncate(fd, 5), Ok(0));
+        assert_eq!(file.readall().unwrap(), b"hello");
+
+        assert_eq!(ctx.sys_ftruncate(fd, 8), Ok(0));
+        assert_eq!(file.readall().unwrap(), b"hello\0\0\0");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+        let dir = root.mkdir("dir").unwrap();
+
+        let readonly = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        let writable = open_fd(&ctx, &file, OpenFlags::O_WRONLY) as usize;
+        let dir = open_fd(&ctx, &dir, OpenFlags::O_RDONLY) as usize;
+
+        assert_eq!(ctx.sys_ftruncate(readonly, 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_ftruncate(writable, -1), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_ftruncate(dir, 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_ftruncate(42, 0), Err(ErrNo::BadFileDescriptor));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+
+impl SyscallContext {
+    pub fn sys_getsockname(
+        &self,
+        sockfd: usize,
+        addr: VirtualAddress,
+        addrlen: VirtualAddress,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_getsockname: sockfd: {}, addr: {}, addrlen: {}",
+            sockfd,
+            addr,
+            addrlen
+        );
+
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        self.write_socket_address(&socket.local_address(), addr, addrlen)
+            .map(|_| 0)
+    }
+
+    pub fn sys_getpeername(
+        &self,
+        sockfd: usize,
+        addr: VirtualAddress,
+        addrlen: VirtualAddress,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_getpeername: sockfd: {}, addr: {}, addrlen: {}",
+            sockfd,
+            addr,
+            addrlen
+        );
+
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        self.write_socket_address(&socket.peer_address()?, addr, addrlen)
+            .map(|_| 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use constants::ErrNo;
+    use socket_abstractions::{SocketAddress, SocketType, UnixAddress, AF_UNIX};
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_truncated_name() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let (socket, _) = ctx.socket_of(fd).unwrap();
+        let address = SocketAddress::Unix(UnixAddress::Pathname("/tmp/named".into()));
+        socket.bind(&address).unwrap();
+
+        let addr = Box::new([0u8; 4]);
+        let addrlen = Box::new(4u32);
+        let addr_ptr = mmu.lock().register(addr.as_ref(), true);
+        let len_ptr = mmu.lock().register(addrlen.as_ref(), true);
+
+        assert_eq!(ctx.sys_getsockname(fd, addr_ptr, len_ptr), Ok(0));
+
+        let expected = address.to_bytes();
+        let mmu = mmu.lock();
+
+        // the full length is reported even if the buffer is too small
+        assert_eq!(mmu.import::<u32>(len_ptr), Ok(expected.len() as u32));
+        assert_eq!(mmu.import::<[u8; 4]>(addr_ptr).unwrap(), expected[..4]);
+    }
+
+    #[test]
+    fn test_peer_name_not_connected() {
+        let (ctx, _) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        assert_eq!(
+            ctx.sys_getpeername(fd, VirtualAddress::null(), VirtualAddress::null()),
+            Err(ErrNo::TransportEndpointIsNotConnected)
+        );
+    }
+}
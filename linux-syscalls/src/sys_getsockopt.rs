@@ -0,0 +1,95 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use constants::ErrNo;
+
+impl SyscallContext {
+    pub fn sys_getsockopt(
+        &self,
+        sockfd: usize,
+        level: usize,
+        optname: usize,
+        optval: VirtualAddress,
+        optlen: VirtualAddress,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_getsockopt: sockfd: {}, level: {}, optname: {}, optval: {}, optlen: {}",
+            sockfd,
+            level,
+            optname,
+            optval,
+            optlen
+        );
+
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        let value = socket.get_option(level, optname)? as i32;
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let len = mmu.import::<u32>(optlen).map_err(|_| ErrNo::BadAddress)? as usize;
+
+        if len < size_of::<i32>() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        mmu.export(optval, value)
+            .and_then(|_| mmu.export(optlen, size_of::<i32>() as u32))
+            .map(|_| 0)
+            .map_err(|_| ErrNo::BadAddress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use socket_abstractions::{SocketType, AF_UNIX, SOL_SOCKET, SO_TYPE};
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_socket_type() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let value = Box::new(0i32);
+        let len = Box::new(16u32);
+        let value_ptr = mmu.lock().register(value.as_ref(), true);
+        let len_ptr = mmu.lock().register(len.as_ref(), true);
+
+        assert_eq!(
+            ctx.sys_getsockopt(fd, SOL_SOCKET, SO_TYPE, value_ptr, len_ptr),
+            Ok(0)
+        );
+
+        let mmu = mmu.lock();
+        assert_eq!(
+            mmu.import::<i32>(value_ptr),
+            Ok(SocketType::Datagram as i32)
+        );
+        assert_eq!(mmu.import::<u32>(len_ptr), Ok(4));
+    }
+
+    #[test]
+    fn test_short_buffer() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let value = Box::new(0u16);
+        let len = Box::new(2u32);
+        let value_ptr = mmu.lock().register(value.as_ref(), true);
+        let len_ptr = mmu.lock().register(len.as_ref(), true);
+
+        assert_eq!(
+            ctx.sys_getsockopt(fd, SOL_SOCKET, SO_TYPE, value_ptr, len_ptr),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    pub fn sys_getuid(&self) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().uid as isize)
+    }
+
+    pub fn sys_geteuid(&self) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().euid as isize)
+    }
+
+    pub fn sys_getgid(&self) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().gid as isize)
+    }
+
+    pub fn sys_getegid(&self) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().egid as isize)
+    }
+
+    pub fn sys_getresuid(
+        &self,
+        ruid: VirtualAddress,
+        euid: VirtualAddress,
+        suid: VirtualAddress,
+    ) -> SyscallResult {
+        let ids = {
+            let process = self.task.process();
+            let credentials = process.credentials().lock();
+            [credentials.uid, credentials.euid, credentials.suid]
+        };
+
+        self.export_ids([ruid, euid, suid], ids)
+    }
+
+    pub fn sys_getresgid(
+        &self,
+        rgid: VirtualAddress,
+        egid: VirtualAddress,
+        sgid: VirtualAddress,
+    ) -> SyscallResult {
+        let ids = {
+            let process = self.task.process();
+            let credentials = process.credentials().lock();
+            [credentials.gid, credentials.egid, credentials.sgid]
+        };
+
+        self.export_ids([rgid, egid, sgid], ids)
+    }
+
+    fn export_ids(&self, addresses: [VirtualAddress; 3], ids: [u32; 3]) -> SyscallResult {
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        for (address, id) in addresses.into_iter().zip(ids) {
+            mmu.export(address, id).map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+    use memory_space::MemorySpace;
+    use test_utilities::{
+        allocation::segment::TestFrameAllocator, kernel::TestKernel, task::TestProcess,
+    };
+
+    use super::*;
+
+    fn setup_context(credentials: Credentials) -> SyscallContext {
+        let kernel = TestKernel::new().build();
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu();
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu, alloc)))
+            .with_credentials(credentials)
+            .build();
+
+        SyscallContext::new(task, kernel)
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            uid: 1000,
+            euid: 1001,
+            suid: 1002,
+            fsuid: 1001,
+            gid: 100,
+            egid: 101,
+            sgid: 102,
+            fsgid: 101,
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_ids() {
+        let ctx = setup_context(credentials());
+
+        assert_eq!(ctx.sys_getuid(), Ok(1000));
+        assert_eq!(ctx.sys_geteuid(), Ok(1001));
+        assert_eq!(ctx.sys_getgid(), Ok(100));
+        assert_eq!(ctx.sys_getegid(), Ok(101));
+    }
+
+    #[test]
+    fn test_getres_ids() {
+        let ctx = setup_context(credentials());
+        let ids = [0u32; 3];
+        ctx.task.process().mmu().lock().register(&ids, true);
+
+        let [r, e, s] = core::array::from_fn(|i| VirtualAddress::from_ref(&ids[i]));
+
+        let mmu = ctx.task.process().mmu();
+
+        assert_eq!(ctx.sys_getresuid(r, e, s), Ok(0));
+        assert_eq!(mmu.lock().import::<[u32; 3]>(r), Ok([1000, 1001, 1002]));
+
+        assert_eq!(ctx.sys_getresgid(r, e, s), Ok(0));
+        assert_eq!(mmu.lock().import::<[u32; 3]>(r), Ok([100, 101, 102]));
+    }
+
+    #[test]
+    fn test_getres_ids_bad_address() {
+        let ctx = setup_context(credentials());
+        let ids = [0u32; 3];
+        ctx.task.process().mmu().lock().register(&ids, false);
+
+        let [r, e, s] = core::array::from_fn(|i| VirtualAddress::from_ref(&ids[i]));
+
+        assert_eq!(ctx.sys_getresuid(r, e, s), Err(ErrNo::BadAddress));
+    }
+}
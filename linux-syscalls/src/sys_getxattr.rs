@@ -0,0 +1,143 @@
+use address::VirtualAddress;
+use alloc::sync::Arc;
+use filesystem_abstractions::{AccessMode, DirectoryTreeNode};
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Copies the value of the extended attribute `name` to `value`, following symbolic links. A
+    /// `size` of 0 only returns how long the value is.
+    pub fn sys_getxattr(
+        &self,
+        pathname: VirtualAddress,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, 0, &self.credentials())?;
+
+        self.getxattr_internal(&node, name, value, size)
+    }
+
+    /// The same as [`SyscallContext::sys_getxattr`] on a symbolic link itself.
+    pub fn sys_lgetxattr(
+        &self,
+        pathname: VirtualAddress,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, AT_SYMLINK_NOFOLLOW, &self.credentials())?;
+
+        self.getxattr_internal(&node, name, value, size)
+    }
+
+    pub fn sys_fgetxattr(
+        &self,
+        fd: usize,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let node = self.xattr_node_of(fd)?;
+
+        self.getxattr_internal(&node, name, value, size)
+    }
+
+    fn getxattr_internal(
+        &self,
+        node: &Arc<DirectoryTreeNode>,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let name = self.read_xattr_name(name)?;
+
+        let bytes = node
+            .check_xattr(&name, &self.credentials(), AccessMode::READ)
+            .and_then(|_| node.get_xattr(&name))
+            .map_err(|e| e.to_errno())?;
+
+        self.export_xattr(&bytes, value, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use constants::ErrNo;
+    use filesystem_abstractions::{Credentials, OpenFlags, XattrFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, own, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_get_value() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+        file.set_xattr("user.mime", b"text/plain", XattrFlags::empty())
+            .unwrap();
+
+        let path = UserPath::new(&ctx, "file");
+        let name = UserPath::new(&ctx, "user.mime");
+        let missing = UserPath::new(&ctx, "user.missing");
+
+        assert_eq!(
+            ctx.sys_getxattr(path.addr(), name.addr(), VirtualAddress::null(), 0),
+            Ok(10)
+        );
+
+        let buffer = [0u8; 16];
+        let mmu = ctx.task.process().mmu();
+        mmu.lock().register(&buffer, true);
+        let buffer = VirtualAddress::from_ref(&buffer);
+
+        assert_eq!(
+            ctx.sys_getxattr(path.addr(), name.addr(), buffer, 4),
+            Err(ErrNo::NumericalResultOutOfRange)
+        );
+        assert_eq!(
+            ctx.sys_getxattr(path.addr(), name.addr(), buffer, 16),
+            Ok(10)
+        );
+        assert_eq!(mmu.lock().import::<[u8; 10]>(buffer), Ok(*b"text/plain"));
+
+        assert_eq!(
+            ctx.sys_getxattr(path.addr(), missing.addr(), buffer, 16),
+            Err(ErrNo::NoDataAvailable)
+        );
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        assert_eq!(ctx.sys_fgetxattr(fd, name.addr(), buffer, 16), Ok(10));
+    }
+
+    #[test]
+    fn test_hidden_namespaces() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o600);
+        file.set_xattr("trusted.secret", b"x", XattrFlags::empty())
+            .unwrap();
+        root.soft_link("link", "file").unwrap();
+
+        let path = UserPath::new(&ctx, "file");
+        let link = UserPath::new(&ctx, "link");
+        let trusted = UserPath::new(&ctx, "trusted.secret");
+        let user = UserPath::new(&ctx, "user.tag");
+        let null = VirtualAddress::null();
+
+        // Attributes only root may see do not seem to exist
+        assert_eq!(
+            ctx.sys_getxattr(path.addr(), trusted.addr(), null, 0),
+            Err(ErrNo::NoDataAvailable)
+        );
+        assert_eq!(
+            ctx.sys_lgetxattr(link.addr(), user.addr(), null, 0),
+            Err(ErrNo::NoDataAvailable)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::{AccessMode, DirectoryEntryType, InotifyMask};
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Watches the file at `pathname` for the events in `mask` with the inotify instance at `fd`,
+    /// returning the watch descriptor. A file watched already keeps its descriptor and gets the
+    /// new mask, or the union with `IN_MASK_ADD`.
+    pub fn sys_inotify_add_watch(
+        &self,
+        fd: usize,
+        pathname: VirtualAddress,
+        mask: u32,
+    ) -> SyscallResult {
+        let mask = InotifyMask::from_bits_truncate(mask);
+
+        // Make sure it is an inotify instance before looking anything up
+        self.with_inotify(fd, |_| ())?;
+
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS)
+            || mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE)
+        {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let flags = match mask.contains(InotifyMask::IN_DONT_FOLLOW) {
+            true => AT_SYMLINK_NOFOLLOW,
+            false => 0,
+        };
+
+        let credentials = self.credentials();
+        let node = self.lookup_at(AT_FDCWD, pathname, flags, &credentials)?;
+
+        if mask.contains(InotifyMask::IN_ONLYDIR)
+            && node.metadata().entry_type != DirectoryEntryType::Directory
+        {
+            return Err(ErrNo::NotADirectory);
+        }
+
+        // Watching tells about the file, which needs the right to read it
+        node.check_access(&credentials, AccessMode::READ)
+            .map_err(|e| e.to_errno())?;
+
+        self.with_inotify(fd, |inotify| inotify.add_watch(&node, mask))?
+            .map(|wd| wd as isize)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, InodeAttributes, OpenFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, own, read_events, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_reports_directory_events() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let path = UserPath::new(&ctx, "/");
+        let wd = ctx
+            .sys_inotify_add_watch(fd, path.addr(), InotifyMask::IN_ALL_EVENTS.bits())
+            .unwrap() as i32;
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"data").unwrap();
+        file.set_attributes(&InodeAttributes::default()).unwrap();
+
+        let written = open_fd(&ctx, &file, OpenFlags::O_WRONLY) as usize;
+        ctx.sys_close(written).unwrap();
+        drop(file);
+
+        root.rename("file", "moved").unwrap();
+        root.remove("moved").unwrap();
+        root.mkdir("dir").unwrap();
+
+        let events = read_events(&ctx, fd);
+        let cookie = events[4].2;
+
+        assert_ne!(cookie, 0);
+        assert_eq!(
+            events,
+            [
+                (wd, InotifyMask::IN_CREATE, 0, "file".into()),
+                (wd, InotifyMask::IN_MODIFY, 0, "file".into()),
+                (wd, InotifyMask::IN_ATTRIB, 0, "file".into()),
+                (wd, InotifyMask::IN_CLOSE_WRITE, 0, "file".into()),
+                (wd, InotifyMask::IN_MOVED_FROM, cookie, "file".into()),
+                (wd, InotifyMask::IN_MOVED_TO, cookie, "moved".into()),
+                (wd, InotifyMask::IN_DELETE, 0, "moved".into()),
+                (
+                    wd,
+                    InotifyMask::IN_CREATE | InotifyMask::IN_ISDIR,
+                    0,
+                    "dir".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_file_events() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let path = UserPath::new(&ctx, "file");
+        let mask = InotifyMask::IN_MODIFY | InotifyMask::IN_DELETE_SELF;
+        let wd = ctx
+            .sys_inotify_add_watch(fd, path.addr(), mask.bits())
+            .unwrap() as i32;
+
+        // Watching again keeps the descriptor
+        let add = mask | InotifyMask::IN_MASK_ADD;
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, path.addr(), add.bits()),
+            Ok(wd as isize)
+        );
+
+        // Not asked for
+        file.set_attributes(&InodeAttributes::default()).unwrap();
+
+        file.writeat(0, b"data").unwrap();
+        drop(file);
+        root.remove("file").unwrap();
+
+        assert_eq!(
+            read_events(&ctx, fd),
+            [
+                (wd, InotifyMask::IN_MODIFY, 0, "".into()),
+                (wd, InotifyMask::IN_DELETE_SELF, 0, "".into()),
+                (wd, InotifyMask::IN_IGNORED, 0, "".into()),
+            ]
+        );
+        assert!(!ctx.file_of(fd).unwrap().read_avaliable());
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 0, 0o600);
+        let other = root.touch("other").unwrap();
+        own(&other, 1000, 0o600);
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let regular = open_fd(&ctx, &other, OpenFlags::O_RDONLY) as usize;
+
+        let path = UserPath::new(&ctx, "file");
+        let other_path = UserPath::new(&ctx, "other");
+        let missing = UserPath::new(&ctx, "missing");
+        let modify = InotifyMask::IN_MODIFY.bits();
+
+        assert_eq!(
+            ctx.sys_inotify_add_watch(99, path.addr(), modify),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(
+            ctx.sys_inotify_add_watch(regular, path.addr(), modify),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, path.addr(), InotifyMask::IN_ONESHOT.bits()),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, missing.addr(), modify),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, path.addr(), modify),
+            Err(ErrNo::PermissionDenied)
+        );
+
+        let only_dir = InotifyMask::IN_MODIFY | InotifyMask::IN_ONLYDIR;
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, other_path.addr(), only_dir.bits()),
+            Err(ErrNo::NotADirectory)
+        );
+
+        let create = InotifyMask::IN_MODIFY | InotifyMask::IN_MASK_CREATE;
+        assert!(ctx
+            .sys_inotify_add_watch(fd, other_path.addr(), create.bits())
+            .is_ok());
+        assert_eq!(
+            ctx.sys_inotify_add_watch(fd, other_path.addr(), create.bits()),
+            Err(ErrNo::FileExists)
+        );
+    }
+}
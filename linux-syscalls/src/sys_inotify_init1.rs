@@ -0,0 +1,49 @@
+use constants::ErrNo;
+use filesystem_abstractions::{Inotify, OpenFlags};
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Flags `inotify_init1` takes, the same bits as the open flags
+const IN_NONBLOCK: usize = OpenFlags::O_NONBLOCK.bits();
+const IN_CLOEXEC: usize = OpenFlags::O_CLOEXEC.bits();
+
+impl SyscallContext {
+    /// Creates an inotify instance with no watches, returning its file descriptor.
+    pub fn sys_inotify_init1(&self, flags: usize) -> SyscallResult {
+        if flags & !(IN_NONBLOCK | IN_CLOEXEC) != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        self.task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(Inotify::new(OpenFlags::from_bits_truncate(flags)))
+            .map(|fd| fd as isize)
+            .ok_or(ErrNo::TooManyOpenFiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    #[test]
+    fn test_creates_instance() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        let fd = ctx.sys_inotify_init1(IN_NONBLOCK | IN_CLOEXEC).unwrap() as usize;
+        let file = ctx.file_of(fd).unwrap();
+
+        assert!(file.is::<Inotify>());
+        assert!(file
+            .flags()
+            .contains(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC));
+        assert!(!file.read_avaliable());
+
+        assert_eq!(ctx.sys_inotify_init1(1), Err(ErrNo::InvalidArgument));
+    }
+}
@@ -0,0 +1,74 @@
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Ends the watch `wd` of the inotify instance at `fd`, which queues an `IN_IGNORED` for it.
+    pub fn sys_inotify_rm_watch(&self, fd: usize, wd: i32) -> SyscallResult {
+        match self.with_inotify(fd, |inotify| inotify.remove_watch(wd))? {
+            true => Ok(0),
+            false => Err(ErrNo::InvalidArgument),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, InotifyMask};
+
+    use super::*;
+    use crate::fs::tests::{read_events, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_removes_watch() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let path = UserPath::new(&ctx, "file");
+        let wd = ctx
+            .sys_inotify_add_watch(fd, path.addr(), InotifyMask::IN_MODIFY.bits())
+            .unwrap() as i32;
+
+        assert_eq!(ctx.sys_inotify_rm_watch(fd, wd), Ok(0));
+        assert_eq!(
+            ctx.sys_inotify_rm_watch(fd, wd),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        file.writeat(0, b"data").unwrap();
+
+        assert_eq!(
+            read_events(&ctx, fd),
+            [(wd, InotifyMask::IN_IGNORED, 0, "".into())]
+        );
+    }
+
+    #[test]
+    fn test_oneshot_ends_itself() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        let file = root.touch("file").unwrap();
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let path = UserPath::new(&ctx, "file");
+        let mask = InotifyMask::IN_MODIFY | InotifyMask::IN_ONESHOT;
+        let wd = ctx
+            .sys_inotify_add_watch(fd, path.addr(), mask.bits())
+            .unwrap() as i32;
+
+        file.writeat(0, b"data").unwrap();
+        file.writeat(4, b"more").unwrap();
+
+        assert_eq!(
+            read_events(&ctx, fd),
+            [
+                (wd, InotifyMask::IN_MODIFY, 0, "".into()),
+                (wd, InotifyMask::IN_IGNORED, 0, "".into()),
+            ]
+        );
+        assert_eq!(
+            ctx.sys_inotify_rm_watch(fd, wd),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
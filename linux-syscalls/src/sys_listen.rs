@@ -0,0 +1,15 @@
+use crate::{SyscallContext, SyscallResult};
+
+/// `SOMAXCONN`, larger backlogs are clamped to it
+const MAX_BACKLOG: usize = 4096;
+
+impl SyscallContext {
+    pub fn sys_listen(&self, sockfd: usize, backlog: usize) -> SyscallResult {
+        log::debug!("sys_listen: sockfd: {}, backlog: {}", sockfd, backlog);
+
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        // a negative backlog is passed in as a huge one
+        socket.listen(backlog.min(MAX_BACKLOG)).map(|_| 0)
+    }
+}
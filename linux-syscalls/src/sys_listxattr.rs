@@ -0,0 +1,110 @@
+use address::VirtualAddress;
+use alloc::{sync::Arc, vec::Vec};
+use filesystem_abstractions::DirectoryTreeNode;
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Copies the nul-terminated names of the extended attributes the caller may see to `list`,
+    /// following symbolic links. A `size` of 0 only returns how much space they need.
+    pub fn sys_listxattr(
+        &self,
+        pathname: VirtualAddress,
+        list: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, 0, &self.credentials())?;
+
+        self.listxattr_internal(&node, list, size)
+    }
+
+    /// The same as [`SyscallContext::sys_listxattr`] on a symbolic link itself.
+    pub fn sys_llistxattr(
+        &self,
+        pathname: VirtualAddress,
+        list: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, AT_SYMLINK_NOFOLLOW, &self.credentials())?;
+
+        self.listxattr_internal(&node, list, size)
+    }
+
+    pub fn sys_flistxattr(&self, fd: usize, list: VirtualAddress, size: usize) -> SyscallResult {
+        let node = self.xattr_node_of(fd)?;
+
+        self.listxattr_internal(&node, list, size)
+    }
+
+    fn listxattr_internal(
+        &self,
+        node: &Arc<DirectoryTreeNode>,
+        list: VirtualAddress,
+        size: usize,
+    ) -> SyscallResult {
+        let names = node
+            .list_xattr_as(&self.credentials())
+            .map_err(|e| e.to_errno())?;
+
+        let mut bytes = Vec::new();
+
+        for name in names {
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+        }
+
+        self.export_xattr(&bytes, list, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use constants::ErrNo;
+    use filesystem_abstractions::{Credentials, OpenFlags, XattrFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, own, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_lists_visible_names() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+
+        for name in ["user.a", "user.bc", "trusted.hidden"] {
+            file.set_xattr(name, b"", XattrFlags::empty()).unwrap();
+        }
+
+        let path = UserPath::new(&ctx, "file");
+        assert_eq!(
+            ctx.sys_listxattr(path.addr(), VirtualAddress::null(), 0),
+            Ok(15)
+        );
+
+        let buffer = [0u8; 15];
+        let mmu = ctx.task.process().mmu();
+        mmu.lock().register(&buffer, true);
+        let buffer = VirtualAddress::from_ref(&buffer);
+
+        assert_eq!(
+            ctx.sys_listxattr(path.addr(), buffer, 8),
+            Err(ErrNo::NumericalResultOutOfRange)
+        );
+        assert_eq!(ctx.sys_listxattr(path.addr(), buffer, 15), Ok(15));
+        assert_eq!(
+            mmu.lock().import::<[u8; 15]>(buffer),
+            Ok(*b"user.a\0user.bc\0")
+        );
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        assert_eq!(ctx.sys_flistxattr(fd, buffer, 15), Ok(15));
+        assert_eq!(
+            ctx.sys_flistxattr(fd + 1, buffer, 15),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use address::VirtualAddress;
+use alloc::format;
+use constants::ErrNo;
+use filesystem_abstractions::{FileSeals, OpenFlags};
+
+use crate::{SyscallContext, SyscallResult};
+
+pub(crate) const MFD_CLOEXEC: usize = 0x1;
+/// Without it the file starts sealed with `F_SEAL_SEAL`, so it can never be sealed
+pub(crate) const MFD_ALLOW_SEALING: usize = 0x2;
+
+/// Longest name of a memfd, without the `memfd:` prefix
+const MFD_NAME_MAX: usize = 249;
+
+impl SyscallContext {
+    /// Creates an anonymous file living in memory and returns a file descriptor open for reading
+    /// and writing it.
+    ///
+    /// The file is the only one in a tmpfs of its own, so it goes away as it is closed and unmapped
+    /// everywhere. Its pages come from the page cache like those of any tmpfs file, so every
+    /// process mapping it shared sees the same ones. `name` only shows up in its path.
+    pub fn sys_memfd_create(&self, name: VirtualAddress, flags: usize) -> SyscallResult {
+        if flags & !(MFD_CLOEXEC | MFD_ALLOW_SEALING) != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let name = self.read_path(name).map_err(|e| match e {
+            ErrNo::FileNameTooLong => ErrNo::InvalidArgument,
+            e => e,
+        })?;
+
+        if name.len() > MFD_NAME_MAX {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let node = self.create_anonymous_file(&format!("memfd:{name}"))?;
+
+        node.make_sealable(match flags & MFD_ALLOW_SEALING {
+            0 => FileSeals::F_SEAL_SEAL,
+            _ => FileSeals::empty(),
+        });
+
+        let open_flags = match flags & MFD_CLOEXEC {
+            0 => OpenFlags::O_RDWR,
+            _ => OpenFlags::O_RDWR | OpenFlags::O_CLOEXEC,
+        };
+
+        self.task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(node.open_as_file(open_flags, 0))
+            .map(|fd| fd as isize)
+            .ok_or(ErrNo::TooManyOpenFiles)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use abstractions::IUsizeAlias;
+    use address::IAddressBase;
+    use alloc::sync::Arc;
+    use filesystem_abstractions::{FileDescriptorTable, MountTable, PageCache};
+    use hermit_sync::SpinMutex;
+    use kernel_abstractions::IKernel;
+    use memory_space::MemorySpace;
+    use mmap_abstractions::{MemoryMapFlags, MemoryMapProt};
+    use test_utilities::{
+        allocation::contiguous::TestFrameAllocator,
+        fs::TestPageAllocator,
+        kernel::{SystemClock, TestKernel},
+        memory::TestMMU,
+        task::TestProcess,
+    };
+    use tmpfs::TmpFileSystemType;
+
+    use super::*;
+    use crate::fs::tests::UserPath;
+
+    pub type TestAllocator = Arc<SpinMutex<TestFrameAllocator>>;
+
+    /// A process on a kernel whose tmpfs goes through a page cache, so memfds can be mapped.
+    pub fn setup_memfd_context() -> (SyscallContext, TestAllocator) {
+        let alloc = TestFrameAllocator::new(256 * 1024 * 1024);
+
+        let cache = PageCache::new(TestPageAllocator::new(alloc.clone()), 64);
+        let mounts = Arc::new(MountTable::new(Some(cache)));
+        mounts.register(TmpFileSystemType::new(Arc::new(SystemClock)));
+
+        let kernel = TestKernel::new()
+            .with_allocator(Some(alloc.clone()))
+            .with_mounts(mounts)
+            .build();
+
+        (process_on(kernel, &alloc), alloc)
+    }
+
+    /// A process of its own on `kernel`, with its memory coming from `alloc`.
+    pub fn process_on(kernel: Arc<dyn IKernel>, alloc: &TestAllocator) -> SyscallContext {
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(
+                TestMMU::new(alloc.clone()),
+                alloc.clone(),
+            )))
+            .with_fd_table(Some(FileDescriptorTable::new()))
+            .build();
+
+        SyscallContext::new(task, kernel)
+    }
+
+    pub fn memfd_create(ctx: &SyscallContext, name: &str, flags: usize) -> SyscallResult {
+        let name = UserPath::new(ctx, name);
+
+        ctx.sys_memfd_create(name.addr(), flags)
+    }
+
+    /// Maps the first page of the file at `fd` shared and writable.
+    pub fn map_shared(ctx: &SyscallContext, fd: usize) -> SyscallResult {
+        ctx.sys_mmap(
+            VirtualAddress::null(),
+            4096,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::SHARED,
+            fd,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_shared_between_processes() {
+        let (ctx, alloc) = setup_memfd_context();
+
+        let fd = memfd_create(&ctx, "shared", MFD_CLOEXEC).unwrap() as usize;
+        let file = ctx.file_of(fd).unwrap();
+
+        assert!(file.flags().contains(OpenFlags::O_CLOEXEC));
+        assert_eq!(file.inode().unwrap().fullpath(), "/memfd:shared");
+        assert_eq!(file.write(b"hello"), 5);
+
+        // Passed to another process like over a unix socket
+        let other = process_on(ctx.kernel.clone(), &alloc);
+        let other_fd = other
+            .task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(file.clone())
+            .unwrap();
+
+        let here = VirtualAddress::from_usize(map_shared(&ctx, fd).unwrap() as usize);
+        let there = VirtualAddress::from_usize(map_shared(&other, other_fd).unwrap() as usize);
+
+        let mut buf = [0; 5];
+        other
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .read_bytes(there, &mut buf)
+            .unwrap();
+        assert_eq!(&buf, b"hello");
+
+        ctx.task
+            .process()
+            .mmu()
+            .lock()
+            .write_bytes(here, b"J")
+            .unwrap();
+        other
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .read_bytes(there, &mut buf)
+            .unwrap();
+        assert_eq!(&buf, b"Jello");
+    }
+
+    #[test]
+    fn test_pages_leave_cache_when_closed() {
+        let (ctx, _) = setup_memfd_context();
+        let cache = ctx.kernel.mounts().page_cache().unwrap();
+
+        let fd = memfd_create(&ctx, "gone", 0).unwrap() as usize;
+        assert_eq!(ctx.file_of(fd).unwrap().write(b"hello"), 5);
+
+        let addr = VirtualAddress::from_usize(map_shared(&ctx, fd).unwrap() as usize);
+        assert_eq!(cache.cached_pages(), 1);
+
+        ctx.sys_close(fd).unwrap();
+        assert_eq!(cache.cached_pages(), 0);
+
+        // The mapping keeps its page
+        let mut buf = [0; 5];
+        ctx.task
+            .process()
+            .mmu()
+            .lock()
+            .read_bytes(addr, &mut buf)
+            .unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_memfd_context();
+
+        assert_eq!(memfd_create(&ctx, "huge", 0x4), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            memfd_create(&ctx, &"n".repeat(MFD_NAME_MAX + 1), 0),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        // No tmpfs to create it on
+        let alloc = TestFrameAllocator::new(16 * 1024 * 1024);
+        let kernel = TestKernel::new()
+            .with_allocator(Some(alloc.clone()))
+            .build();
+
+        assert_eq!(
+            memfd_create(&process_on(kernel, &alloc), "none", 0),
+            Err(ErrNo::NoSuchDevice)
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use abstractions::IUsizeAlias;
 use address::{
-    IAddressBase, IAlignableAddress, IPageNum, IToPageNum, VirtualAddress, VirtualPageNumRange,
+    IAddressBase, IAlignableAddress, IPageNum, IToPageNum, PhysicalAddress, VirtualAddress,
+    VirtualPageNumRange,
 };
-use alloc::vec::Vec;
-use constants::SyscallError;
-use memory_space::{AreaType, MapType, MappingArea, MemorySpace};
+use alloc::{sync::Arc, vec::Vec};
+use constants::{ErrNo, SyscallError};
+use filesystem_abstractions::{FileSeals, FileSystemError, OpenFlags, PageMapping};
+use memory_space::{AreaType, ISharedPage, MapType, MappingArea, MemorySpace};
 use mmap_abstractions::{MemoryMapFlags, MemoryMapProt};
 use mmu_abstractions::GenericMappingFlags;
 
@@ -22,7 +24,6 @@ impl SyscallContext {
         len: usize,
         prot: MemoryMapProt,
         flags: MemoryMapFlags,
-        #[expect(unused)] // we don't use fd for anonymous mapping
         fd: usize,
         offset: usize,
     ) -> SyscallResult {
@@ -50,10 +51,104 @@ impl SyscallContext {
 
         match flags {
             MemoryMapFlags::ANONYMOUS => self.sys_mmap_anonymous(addr, len, permissions, offset),
+            MemoryMapFlags::SHARED | MemoryMapFlags::PRIVATE => {
+                self.sys_mmap_file(addr, len, permissions, flags, fd, offset)
+            }
             _ => SyscallError::InvalidArgument, // not implemented
         }
     }
 
+    fn sys_mmap_file(
+        &self,
+        mut addr: VirtualAddress,
+        len: usize,
+        permissions: GenericMappingFlags,
+        flags: MemoryMapFlags,
+        fd: usize,
+        offset: usize,
+    ) -> SyscallResult {
+        let file = self.file_of(fd)?;
+        let node = file.inode().ok_or(ErrNo::NoSuchDevice)?;
+
+        let shared = flags == MemoryMapFlags::SHARED;
+        let writable = permissions.contains(GenericMappingFlags::Writable);
+
+        let access = file.flags() & OpenFlags::O_ACCMODE;
+        if access.bits() == OpenFlags::O_WRONLY.bits()
+            || (shared && writable && access.bits() != OpenFlags::O_RDWR.bits())
+        {
+            return SyscallError::PermissionDenied;
+        }
+
+        // A sealed memfd can't be written through a new mapping either
+        if shared
+            && writable
+            && node
+                .seals()
+                .is_some_and(|s| s.intersects(FileSeals::WRITES))
+        {
+            return SyscallError::OperationNotPermitted;
+        }
+
+        // Only files in the page cache have pages to map
+        let first = offset / constants::PAGE_SIZE;
+        let pages = (first..first + len / constants::PAGE_SIZE)
+            .map(|index| node.cached_page(index))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| match e {
+                FileSystemError::Unimplemented => ErrNo::NoSuchDevice,
+                e => e.to_errno(),
+            })?;
+
+        let process = self.task.process();
+
+        let mut mem = process.memory_space().lock();
+
+        addr = Self::sys_mmap_select_addr(&mut mem, addr, len);
+
+        // No avaliable address
+        if addr.is_null() {
+            return SyscallError::CannotAllocateMemory;
+        }
+
+        let area = MappingArea {
+            range: VirtualPageNumRange::from_start_end(
+                addr.to_floor_page_num(),
+                (addr + len).to_ceil_page_num(),
+            ),
+            area_type: AreaType::VMA,
+            map_type: MapType::Framed,
+            permissions,
+            allocation: None,
+        };
+
+        // A private mapping that can't be written may as well use the file's pages
+        if shared || !writable {
+            mem.map_shared_area(
+                area,
+                pages.iter().map(|page| {
+                    Arc::new(FilePage(page.map(shared && writable))) as Arc<dyn ISharedPage>
+                }),
+            );
+
+            return Ok(addr.as_usize() as isize);
+        }
+
+        mem.alloc_and_map_area(area);
+
+        let mut buffer = [0; constants::PAGE_SIZE];
+        let mmu = mem.mmu().lock();
+
+        for (index, page) in pages.iter().enumerate() {
+            page.read(0, &mut buffer);
+
+            mmu.write_bytes(addr + index * constants::PAGE_SIZE, &buffer)
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        Ok(addr.as_usize() as isize)
+    }
+
     fn sys_mmap_anonymous(
         &self,
         mut addr: VirtualAddress,
@@ -92,7 +187,7 @@ impl SyscallContext {
         Ok(addr.as_usize() as isize)
     }
 
-    fn sys_mmap_select_addr(
+    pub(crate) fn sys_mmap_select_addr(
         mem: &mut MemorySpace,
         addr: VirtualAddress,
         len: usize,
@@ -153,20 +248,35 @@ impl SyscallContext {
     }
 }
 
+/// A page of a file mapped into a process.
+struct FilePage(PageMapping);
+
+impl ISharedPage for FilePage {
+    fn physical(&self) -> PhysicalAddress {
+        PhysicalAddress::from_usize(self.0.physical())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
     use address::{VirtualAddress, VirtualPageNum};
     use allocation_abstractions::IFrameAllocator;
+    use filesystem_abstractions::{DirectoryTreeNode, FileDescriptorTable, IFileSystem, PageCache};
     use hermit_sync::SpinMutex;
     use kernel_abstractions::IKernel;
     use memory_space::{MappingAreaAllocation, MemorySpace};
     use mmap_abstractions::MemoryMapProt;
     use mmu_abstractions::IMMU;
     use test_utilities::{
-        allocation::contiguous::TestFrameAllocator, kernel::TestKernel, task::TestProcess,
+        allocation::contiguous::TestFrameAllocator,
+        fs::TestPageAllocator,
+        kernel::{SystemClock, TestKernel},
+        memory::TestMMU,
+        task::TestProcess,
     };
+    use tmpfs::TmpFileSystem;
 
     use super::*;
 
@@ -678,4 +788,201 @@ mod tests {
 
         assert_eq!(ret, SyscallError::CannotAllocateMemory);
     }
+
+    type FileSetup = (
+        SyscallContext,
+        Arc<DirectoryTreeNode>,
+        Arc<SpinMutex<TestFrameAllocator>>,
+    );
+
+    fn setup_file_context(cached: bool, flags: OpenFlags) -> FileSetup {
+        setup_file_context_on(TmpFileSystem::new(Arc::new(SystemClock)), cached, flags)
+    }
+
+    fn setup_file_context_on(
+        fs: Arc<dyn IFileSystem>,
+        cached: bool,
+        flags: OpenFlags,
+    ) -> FileSetup {
+        let alloc = TestFrameAllocator::new(1024 * 1024 * 1024);
+        let kernel = TestKernel::new()
+            .with_allocator(Some(alloc.clone()))
+            .build();
+
+        let root = match cached {
+            true => {
+                let cache = PageCache::new(TestPageAllocator::new(alloc.clone()), 64);
+                DirectoryTreeNode::from_cached_filesystem(None, fs, cache, Some(""))
+            }
+            false => DirectoryTreeNode::from_filesystem(None, fs, Some("")),
+        };
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"hello").unwrap();
+
+        let mut fd_table = FileDescriptorTable::new();
+        let fd = fd_table
+            .allocate(file.clone().open_as_file(flags, 0))
+            .unwrap();
+        assert_eq!(fd, 0);
+
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(
+                TestMMU::new(alloc.clone()),
+                alloc.clone(),
+            )))
+            .with_fd_table(Some(fd_table))
+            .build();
+
+        (SyscallContext::new(task, kernel), file, alloc)
+    }
+
+    fn mmap_file(
+        ctx: &SyscallContext,
+        prot: MemoryMapProt,
+        flags: MemoryMapFlags,
+    ) -> SyscallResult {
+        ctx.sys_mmap(VirtualAddress::null(), 8192, prot, flags, 0, 0)
+    }
+
+    #[test]
+    fn test_syscall_shared_file_mapping_is_coherent() {
+        let (ctx, file, _) = setup_file_context(true, OpenFlags::O_RDWR);
+
+        let ret = mmap_file(
+            &ctx,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::SHARED,
+        );
+        let vaddr = VirtualAddress::from_usize(ret.unwrap() as usize);
+
+        let process = ctx.task.process();
+        let mmu = process.mmu();
+
+        let mut buffer = [0; 5];
+        mmu.lock().read_bytes(vaddr, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hello");
+
+        // Written through the mapping, read through the file
+        mmu.lock().write_bytes(vaddr, b"J").unwrap();
+        file.readat(0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"Jello");
+
+        // And the other way around
+        file.writeat(1, b"E").unwrap();
+        mmu.lock().read_bytes(vaddr, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"JEllo");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_syscall_shared_file_mapping_writes_back_to_host() {
+        use test_utilities::hostfs::HostFileSystem;
+
+        let host = HostFileSystem::scratch();
+        let (ctx, file, _) = setup_file_context_on(host.clone(), true, OpenFlags::O_RDWR);
+
+        file.sync().unwrap();
+        assert_eq!(std::fs::read(host.host_path("/file")).unwrap(), b"hello");
+
+        let ret = mmap_file(
+            &ctx,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::SHARED,
+        );
+        let vaddr = VirtualAddress::from_usize(ret.unwrap() as usize);
+
+        let process = ctx.task.process();
+        process.mmu().lock().write_bytes(vaddr, b"J").unwrap();
+
+        // Only in the page cache until synced
+        assert_eq!(std::fs::read(host.host_path("/file")).unwrap(), b"hello");
+
+        file.sync().unwrap();
+        assert_eq!(std::fs::read(host.host_path("/file")).unwrap(), b"Jello");
+    }
+
+    #[test]
+    fn test_syscall_private_file_mapping_is_a_copy() {
+        let (ctx, file, _) = setup_file_context(true, OpenFlags::O_RDONLY);
+
+        let ret = mmap_file(
+            &ctx,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::PRIVATE,
+        );
+        let vaddr = VirtualAddress::from_usize(ret.unwrap() as usize);
+
+        let process = ctx.task.process();
+        let mmu = process.mmu();
+        mmu.lock().write_bytes(vaddr, b"J").unwrap();
+
+        let mut buffer = [0; 5];
+        mmu.lock().read_bytes(vaddr, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"Jello");
+
+        file.readat(0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hello");
+    }
+
+    #[test]
+    fn test_syscall_shared_file_mapping_is_shared_with_child() {
+        let (ctx, file, alloc) = setup_file_context(true, OpenFlags::O_RDWR);
+
+        let ret = mmap_file(
+            &ctx,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::SHARED,
+        );
+        let vaddr = VirtualAddress::from_usize(ret.unwrap() as usize);
+
+        let process = ctx.task.process();
+        let child =
+            MemorySpace::clone_existing(&process.memory_space().lock(), TestMMU::new(alloc), None);
+
+        child.mmu().lock().write_bytes(vaddr, b"J").unwrap();
+
+        let mut buffer = [0; 5];
+        file.readat(0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"Jello");
+    }
+
+    #[test]
+    fn test_syscall_shared_writable_mapping_needs_read_write_file() {
+        let (ctx, _file, _) = setup_file_context(true, OpenFlags::O_RDONLY);
+
+        let ret = mmap_file(
+            &ctx,
+            MemoryMapProt::READ | MemoryMapProt::WRITE,
+            MemoryMapFlags::SHARED,
+        );
+
+        assert_eq!(ret, SyscallError::PermissionDenied);
+        assert!(mmap_file(&ctx, MemoryMapProt::READ, MemoryMapFlags::SHARED).is_ok());
+    }
+
+    #[test]
+    fn test_syscall_uncached_file_can_not_be_mapped() {
+        let (ctx, _file, _) = setup_file_context(false, OpenFlags::O_RDWR);
+
+        let ret = mmap_file(&ctx, MemoryMapProt::READ, MemoryMapFlags::SHARED);
+
+        assert_eq!(ret, SyscallError::NoSuchDevice);
+    }
+
+    #[test]
+    fn test_syscall_file_mapping_bad_fd() {
+        let (ctx, _file, _) = setup_file_context(true, OpenFlags::O_RDWR);
+
+        let ret = ctx.sys_mmap(
+            VirtualAddress::null(),
+            4096,
+            MemoryMapProt::READ,
+            MemoryMapFlags::PRIVATE,
+            1,
+            0,
+        );
+
+        assert_eq!(ret, SyscallError::BadFileDescriptor);
+    }
 }
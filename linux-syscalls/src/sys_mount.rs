@@ -0,0 +1,284 @@
+use address::VirtualAddress;
+use alloc::string::String;
+use constants::ErrNo;
+use filesystem_abstractions::{DirectoryEntryType, DirectoryTreeNode, MountEntry, MountFlags};
+
+use crate::{fs::AT_FDCWD, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Mounts a new filesystem of type `fstype` from `source` at `target`, makes `source` show up
+    /// at `target` too with `MS_BIND`, or changes the flags of the mount at `target` with
+    /// `MS_REMOUNT`. Filesystem specific options in `data` are ignored.
+    pub fn sys_mount(
+        &self,
+        source: VirtualAddress,
+        target: VirtualAddress,
+        fstype: VirtualAddress,
+        flags: usize,
+        _data: VirtualAddress,
+    ) -> SyscallResult {
+        let flags = MountFlags::from_bits_truncate(flags);
+
+        let credentials = self.credentials();
+
+        if !credentials.is_admin() {
+            return Err(ErrNo::OperationNotPermitted);
+        }
+
+        // Moving mounts and changing how they propagate are not supported
+        if flags.contains(MountFlags::MS_MOVE) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let node = self.lookup_at(AT_FDCWD, target, 0, &credentials)?;
+        let table = self.kernel.mounts();
+        let path = node.fullpath();
+
+        if flags.contains(MountFlags::MS_REMOUNT) {
+            if table.get(&path).is_none() {
+                return Err(ErrNo::InvalidArgument);
+            }
+
+            node.set_readonly(flags.contains(MountFlags::MS_RDONLY));
+            table.set_flags(&path, flags);
+
+            return Ok(0);
+        }
+
+        let parent = node.parent().ok_or(ErrNo::DeviceOrResourceBusy)?.clone();
+        let is_dir = node.metadata().entry_type == DirectoryEntryType::Directory;
+
+        let (mounted, entry) = match flags.contains(MountFlags::MS_BIND) {
+            true => {
+                let source = self.lookup_at(AT_FDCWD, source, 0, &credentials)?;
+                let is_source_dir = source.metadata().entry_type == DirectoryEntryType::Directory;
+
+                if is_dir != is_source_dir {
+                    return Err(ErrNo::NotADirectory);
+                }
+
+                let mounted =
+                    DirectoryTreeNode::from_bind(Some(parent.clone()), &source, Some(node.name()))
+                        .map_err(|e| e.to_errno())?;
+
+                // The new mount copies the flags of the one `source` is in, `MS_RDONLY` is only
+                // applied by a remount afterwards like Linux does
+                let entry = match table.containing(&source.fullpath()) {
+                    Some(containing) => MountEntry {
+                        target: path,
+                        ..containing
+                    },
+                    None => MountEntry {
+                        source: source.fullpath(),
+                        target: path,
+                        fs_type: String::from("none"),
+                        flags: MountFlags::empty(),
+                    },
+                };
+
+                mounted.set_readonly(entry.flags.contains(MountFlags::MS_RDONLY));
+
+                (mounted, entry)
+            }
+            false => {
+                if !is_dir {
+                    return Err(ErrNo::NotADirectory);
+                }
+
+                let source = self.read_path(source)?;
+                let fs_type = self.read_path(fstype)?;
+                let fs_type = table
+                    .file_system_type(&fs_type)
+                    .ok_or(ErrNo::NoSuchDevice)?;
+
+                let fs = fs_type.create(&source, flags).map_err(|e| e.to_errno())?;
+
+                let name = Some(node.name());
+                let mounted = match (fs_type.is_cacheable(), table.page_cache()) {
+                    (true, Some(cache)) => DirectoryTreeNode::from_cached_filesystem(
+                        Some(parent.clone()),
+                        fs,
+                        cache,
+                        name,
+                    ),
+                    _ => DirectoryTreeNode::from_filesystem(Some(parent.clone()), fs, name),
+                };
+
+                mounted.set_readonly(flags.contains(MountFlags::MS_RDONLY));
+
+                let entry = MountEntry {
+                    source,
+                    target: path,
+                    fs_type: String::from(fs_type.name()),
+                    flags: flags & MountFlags::PER_MOUNT,
+                };
+
+                (mounted, entry)
+            }
+        };
+
+        if let Err(e) = parent.mount_as(mounted, Some(node.name())) {
+            return e.to_syscall_error();
+        }
+
+        table.add(entry);
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use address::IAddressBase;
+    use alloc::{string::ToString, sync::Arc};
+    use filesystem_abstractions::{Credentials, FileSystemError};
+    use test_utilities::kernel::SystemClock;
+    use tmpfs::TmpFileSystemType;
+
+    use super::*;
+    use crate::fs::tests::{setup_fs_context, UserPath};
+
+    /// A root context whose kernel can mount tmpfs, with the root tmpfs in its mount table.
+    pub fn setup_mount_context() -> (SyscallContext, Arc<DirectoryTreeNode>) {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+
+        let table = ctx.kernel.mounts();
+        table.register(TmpFileSystemType::new(Arc::new(SystemClock)));
+        table.add(MountEntry {
+            source: "tmpfs".to_string(),
+            target: "/".to_string(),
+            fs_type: "tmpfs".to_string(),
+            flags: MountFlags::empty(),
+        });
+
+        (ctx, root)
+    }
+
+    #[test]
+    fn test_mount_tmpfs() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap().touch("hidden").unwrap();
+
+        let source = UserPath::new(&ctx, "none");
+        let target = UserPath::new(&ctx, "/mnt");
+        let fstype = UserPath::new(&ctx, "tmpfs");
+
+        let flags = (MountFlags::MS_NOSUID | MountFlags::MS_SILENT).bits();
+        assert_eq!(
+            ctx.sys_mount(
+                source.addr(),
+                target.addr(),
+                fstype.addr(),
+                flags,
+                VirtualAddress::null()
+            ),
+            Ok(0)
+        );
+
+        let mnt = root.open("/mnt", Some(&root)).unwrap();
+        assert!(mnt.open("hidden", None).is_err());
+        mnt.touch("file").unwrap();
+
+        assert_eq!(
+            ctx.kernel.mounts().get("/mnt").unwrap(),
+            MountEntry {
+                source: "none".to_string(),
+                target: "/mnt".to_string(),
+                fs_type: "tmpfs".to_string(),
+                flags: MountFlags::MS_NOSUID,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bind_and_remount() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("data").unwrap().touch("file").unwrap();
+        root.mkdir("view").unwrap();
+
+        let source = UserPath::new(&ctx, "/data");
+        let target = UserPath::new(&ctx, "/view");
+        let null = VirtualAddress::null();
+
+        let bind = MountFlags::MS_BIND.bits();
+        assert_eq!(
+            ctx.sys_mount(source.addr(), target.addr(), null, bind, null),
+            Ok(0)
+        );
+
+        // The same file through both paths
+        let file = root.open("/view/file", Some(&root)).unwrap();
+        file.writeat(0, b"data").unwrap();
+        assert_eq!(
+            file.file_key(),
+            root.open("/data/file", Some(&root)).unwrap().file_key()
+        );
+        drop(file);
+
+        let remount = (MountFlags::MS_REMOUNT | MountFlags::MS_BIND | MountFlags::MS_RDONLY).bits();
+        assert_eq!(
+            ctx.sys_mount(null, target.addr(), null, remount, null),
+            Ok(0)
+        );
+
+        let view = root.open("/view", Some(&root)).unwrap();
+        assert_eq!(view.touch("other").err(), Some(FileSystemError::ReadOnly));
+        assert_eq!(
+            view.open("file", None).unwrap().writeat(0, b"more").err(),
+            Some(FileSystemError::ReadOnly)
+        );
+        assert!(root
+            .open("/data", Some(&root))
+            .unwrap()
+            .touch("other")
+            .is_ok());
+
+        assert_eq!(ctx.kernel.mounts().get("/view").unwrap().options(), "ro");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap();
+        root.touch("file").unwrap();
+
+        let none = UserPath::new(&ctx, "none");
+        let mnt = UserPath::new(&ctx, "/mnt");
+        let file = UserPath::new(&ctx, "/file");
+        let tmpfs = UserPath::new(&ctx, "tmpfs");
+        let unknown = UserPath::new(&ctx, "nofs");
+        let null = VirtualAddress::null();
+
+        assert_eq!(
+            ctx.sys_mount(none.addr(), mnt.addr(), unknown.addr(), 0, null),
+            Err(ErrNo::NoSuchDevice)
+        );
+        assert_eq!(
+            ctx.sys_mount(none.addr(), file.addr(), tmpfs.addr(), 0, null),
+            Err(ErrNo::NotADirectory)
+        );
+        assert_eq!(
+            ctx.sys_mount(
+                mnt.addr(),
+                file.addr(),
+                null,
+                MountFlags::MS_BIND.bits(),
+                null
+            ),
+            Err(ErrNo::NotADirectory)
+        );
+
+        // Not mounted there
+        let remount = MountFlags::MS_REMOUNT.bits();
+        assert_eq!(
+            ctx.sys_mount(null, mnt.addr(), null, remount, null),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        ctx.task.process().credentials().lock().euid = 1000;
+        assert_eq!(
+            ctx.sys_mount(none.addr(), mnt.addr(), tmpfs.addr(), 0, null),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use filesystem_abstractions::OpenFlags;
+
+use crate::{mqueue::MqAttr, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Writes the attributes of the POSIX message queue open as `mqdes` to `oldattr`, then
+    /// sets its flags from `newattr`, either of which may be null.
+    ///
+    /// Only `O_NONBLOCK` can be changed, and only for this open file.
+    pub fn sys_mq_getsetattr(
+        &self,
+        mqdes: usize,
+        newattr: VirtualAddress,
+        oldattr: VirtualAddress,
+    ) -> SyscallResult {
+        let open = self.message_queue_of(mqdes)?;
+        let queue = open.queue();
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let new = match newattr.is_null() {
+            true => None,
+            false => Some(
+                mmu.import::<MqAttr>(newattr)
+                    .map_err(|_| ErrNo::BadAddress)?,
+            ),
+        };
+
+        if new.is_some_and(|new| new.mq_flags & !(OpenFlags::O_NONBLOCK.bits() as i64) != 0) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        if !oldattr.is_null() {
+            let attributes = queue.attributes();
+            let old = MqAttr {
+                mq_flags: (open.file.flags() & OpenFlags::O_NONBLOCK).bits() as i64,
+                mq_maxmsg: attributes.max_messages as i64,
+                mq_msgsize: attributes.message_size as i64,
+                mq_curmsgs: queue.len() as i64,
+                ..Default::default()
+            };
+
+            mmu.export(oldattr, old).map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        if let Some(new) = new {
+            let mut flags = open.file.flags();
+            flags.set(OpenFlags::O_NONBLOCK, new.mq_flags != 0);
+
+            open.file.set_flags(flags);
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context, sys_mq_open::tests::create, sys_mq_timedsend::tests::send,
+    };
+
+    fn getsetattr(
+        ctx: &SyscallContext,
+        mqdes: usize,
+        new: Option<MqAttr>,
+    ) -> Result<MqAttr, ErrNo> {
+        let new = new.map(Box::new);
+        let old = Box::new(MqAttr::default());
+
+        let mmu = ctx.task.process().mmu();
+        let newattr = match &new {
+            Some(new) => mmu.lock().register(new.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+        let oldattr = mmu.lock().register(old.as_ref(), true);
+
+        ctx.sys_mq_getsetattr(mqdes, newattr, oldattr).map(|_| *old)
+    }
+
+    fn flags(mq_flags: i64) -> MqAttr {
+        MqAttr {
+            mq_flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+        send(&ctx, fd, b"message", 0, None).unwrap();
+
+        let attr = getsetattr(&ctx, fd, None).unwrap();
+        assert_eq!(
+            (
+                attr.mq_flags,
+                attr.mq_maxmsg,
+                attr.mq_msgsize,
+                attr.mq_curmsgs
+            ),
+            (0, 4, 8, 1)
+        );
+
+        let nonblocking = OpenFlags::O_NONBLOCK.bits() as i64;
+        let attr = getsetattr(&ctx, fd, Some(flags(nonblocking))).unwrap();
+        assert_eq!(attr.mq_flags, 0);
+        assert!(ctx.message_queue_of(fd).unwrap().nonblocking());
+
+        // The size of a queue stays
+        let mut larger = flags(nonblocking);
+        larger.mq_maxmsg = 8;
+        let attr = getsetattr(&ctx, fd, Some(larger)).unwrap();
+        assert_eq!((attr.mq_flags, attr.mq_maxmsg), (nonblocking, 4));
+        assert_eq!(getsetattr(&ctx, fd, None).unwrap().mq_maxmsg, 4);
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+
+        let cloexec = OpenFlags::O_CLOEXEC.bits() as i64;
+        assert_eq!(
+            getsetattr(&ctx, fd, Some(flags(cloexec))),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(getsetattr(&ctx, 42, None), Err(ErrNo::BadFileDescriptor));
+    }
+}
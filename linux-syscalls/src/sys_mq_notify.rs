@@ -0,0 +1,147 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use ipc::Notification;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Sends `sigev_signo`
+const SIGEV_SIGNAL: i32 = 0;
+/// Only takes up the registration
+const SIGEV_NONE: i32 = 1;
+/// Starts a thread, which the C library does through a netlink socket
+const SIGEV_THREAD: i32 = 2;
+
+/// Signals go up to `SIGRTMAX`
+const SIGNAL_MAX: i32 = 64;
+
+/// `struct sigevent`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigEvent {
+    pub sigev_value: u64,
+    pub sigev_signo: i32,
+    pub sigev_notify: i32,
+    pub pad: [i32; 12],
+}
+
+impl SyscallContext {
+    /// Registers the calling process to be notified as described at `sevp` when a message
+    /// arrives in the empty POSIX message queue open as `mqdes`, or takes its registration
+    /// back if `sevp` is null. Only one process can be registered with a queue at a time.
+    ///
+    /// The registration is recorded and used up like on Linux, but no signal is sent yet.
+    pub fn sys_mq_notify(&self, mqdes: usize, sevp: VirtualAddress) -> SyscallResult {
+        let open = self.message_queue_of(mqdes)?;
+        let pid = self.task.process().pid();
+
+        if sevp.is_null() {
+            open.queue().set_notification(None, pid)?;
+
+            return Ok(0);
+        }
+
+        let event = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<SigEvent>(sevp)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        match event.sigev_notify {
+            SIGEV_NONE => (),
+            SIGEV_SIGNAL if (1..=SIGNAL_MAX).contains(&event.sigev_signo) => (),
+            SIGEV_THREAD => return Err(ErrNo::FunctionNotImplemented),
+            _ => return Err(ErrNo::InvalidArgument),
+        }
+
+        let notification = Notification {
+            pid,
+            method: event.sigev_notify,
+            signo: event.sigev_signo,
+            value: event.sigev_value,
+        };
+
+        open.queue().set_notification(Some(notification), pid)?;
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context, sys_mq_open::tests::create, sys_mq_timedsend::tests::send,
+    };
+
+    fn notify(ctx: &SyscallContext, mqdes: usize, event: Option<(i32, i32)>) -> SyscallResult {
+        let event = event.map(|(sigev_notify, sigev_signo)| {
+            Box::new(SigEvent {
+                sigev_value: 42,
+                sigev_signo,
+                sigev_notify,
+                pad: [0; 12],
+            })
+        });
+
+        let sevp = match &event {
+            Some(event) => ctx
+                .task
+                .process()
+                .mmu()
+                .lock()
+                .register(event.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+
+        ctx.sys_mq_notify(mqdes, sevp)
+    }
+
+    #[test]
+    fn test_registration() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+
+        assert_eq!(notify(&ctx, fd, Some((SIGEV_SIGNAL, 10))), Ok(0));
+        assert_eq!(
+            notify(&ctx, fd, Some((SIGEV_NONE, 0))),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+
+        let open = ctx.message_queue_of(fd).unwrap();
+        let notification = open.queue().notification().unwrap();
+        assert_eq!((notification.signo, notification.value), (10, 42));
+
+        // Used up by the first message
+        send(&ctx, fd, b"message", 0, None).unwrap();
+        assert_eq!(open.queue().notification(), None);
+
+        assert_eq!(notify(&ctx, fd, Some((SIGEV_NONE, 0))), Ok(0));
+        assert_eq!(notify(&ctx, fd, None), Ok(0));
+        assert_eq!(open.queue().notification(), None);
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+
+        assert_eq!(
+            notify(&ctx, fd, Some((SIGEV_SIGNAL, 0))),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            notify(&ctx, fd, Some((42, 10))),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            notify(&ctx, fd, Some((SIGEV_THREAD, 3))),
+            Err(ErrNo::FunctionNotImplemented)
+        );
+        assert_eq!(notify(&ctx, 42, None), Err(ErrNo::BadFileDescriptor));
+    }
+}
@@ -0,0 +1,203 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use filesystem_abstractions::{FileSystemError, OpenFlags};
+use ipc::{MessageQueueAttributes, MqueueFileSystem};
+
+use crate::{mqueue::MqAttr, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Opens the POSIX message queue `name`, creating it with `mode` and the attributes at
+    /// `attr` if `oflag` has `O_CREAT` and there is none.
+    ///
+    /// A queue larger than the default needs an admin to create it. Queues live in the mqueue
+    /// filesystem of the kernel, which shows up wherever it is mounted.
+    pub fn sys_mq_open(
+        &self,
+        name: VirtualAddress,
+        oflag: usize,
+        mode: usize,
+        attr: VirtualAddress,
+    ) -> SyscallResult {
+        let flags = OpenFlags::from_bits_truncate(oflag);
+
+        if flags.bits() & OpenFlags::O_ACCMODE.bits() == OpenFlags::O_ACCMODE.bits() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let name = self.read_queue_name(name)?;
+        let root = self.kernel.mqueue();
+        let credentials = self.credentials();
+
+        let node = match root.open_child(&name) {
+            Ok(_) if flags.contains(OpenFlags::O_CREAT | OpenFlags::O_EXCL) => {
+                return Err(ErrNo::FileExists)
+            }
+            Ok(node) => {
+                node.check_open(flags, &credentials)
+                    .map_err(|e| e.to_errno())?;
+
+                node
+            }
+            Err(FileSystemError::NotFound) if flags.contains(OpenFlags::O_CREAT) => {
+                let attributes = match attr.is_null() {
+                    true => MessageQueueAttributes::default(),
+                    false => self
+                        .task
+                        .process()
+                        .mmu()
+                        .lock()
+                        .import::<MqAttr>(attr)
+                        .map_err(|_| ErrNo::BadAddress)?
+                        .to_attributes()?,
+                };
+
+                if !attributes.within_defaults() && !credentials.is_admin() {
+                    return Err(ErrNo::InvalidArgument);
+                }
+
+                let fs = MqueueFileSystem::of(&root).ok_or(ErrNo::NoSuchDevice)?;
+
+                fs.create(
+                    &name,
+                    attributes,
+                    mode as u32,
+                    credentials.fsuid,
+                    credentials.fsgid,
+                )
+                .map_err(|e| e.to_errno())?;
+
+                root.open_child(&name).map_err(|e| e.to_errno())?
+            }
+            Err(e) => return Err(e.to_errno()),
+        };
+
+        let flags = flags & (OpenFlags::O_ACCMODE | OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC);
+
+        self.task
+            .process()
+            .fd_table()
+            .lock()
+            .allocate(node.open_as_file(flags, 0))
+            .map(|fd| fd as isize)
+            .ok_or(ErrNo::TooManyOpenFiles)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{boxed::Box, format};
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    /// Opens the queue `name` with `oflag`, creating it with `mode` and `attr`.
+    pub fn open(
+        ctx: &SyscallContext,
+        name: &str,
+        oflag: OpenFlags,
+        mode: usize,
+        attr: Option<MqAttr>,
+    ) -> SyscallResult {
+        // Writable, so a later buffer at the same address is not taken for read-only
+        let path = Box::<[u8]>::from(format!("{name}\0").as_bytes());
+        let name = ctx.task.process().mmu().lock().register(&*path, true);
+        let attr = attr.map(Box::new);
+        let attr = match &attr {
+            Some(attr) => ctx
+                .task
+                .process()
+                .mmu()
+                .lock()
+                .register(attr.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+
+        ctx.sys_mq_open(name, oflag.bits(), mode, attr)
+    }
+
+    pub fn attr(maxmsg: i64, msgsize: i64) -> MqAttr {
+        MqAttr {
+            mq_maxmsg: maxmsg,
+            mq_msgsize: msgsize,
+            ..Default::default()
+        }
+    }
+
+    /// Creates the queue `name` for reading and writing.
+    pub fn create(ctx: &SyscallContext, name: &str, maxmsg: i64, msgsize: i64) -> usize {
+        let flags = OpenFlags::O_RDWR | OpenFlags::O_CREAT | OpenFlags::O_EXCL;
+
+        open(ctx, name, flags, 0o600, Some(attr(maxmsg, msgsize))).unwrap() as usize
+    }
+
+    #[test]
+    fn test_create_and_open() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let creating = OpenFlags::O_WRONLY | OpenFlags::O_CREAT;
+
+        assert_eq!(
+            open(&ctx, "queue", OpenFlags::O_RDONLY, 0, None),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert!(open(&ctx, "queue", creating, 0o640, None).is_ok());
+        assert!(open(&ctx, "queue", OpenFlags::O_RDONLY, 0, None).is_ok());
+        assert_eq!(
+            open(&ctx, "queue", creating | OpenFlags::O_EXCL, 0o640, None),
+            Err(ErrNo::FileExists)
+        );
+
+        let node = ctx.kernel.mqueue().open_child("queue").unwrap();
+        let inode = node.inode().unwrap();
+        let queue = inode.downcast_ref::<ipc::PosixMessageQueue>().unwrap();
+        assert_eq!(queue.attributes(), MessageQueueAttributes::default());
+
+        create(&ctx, "sized", 4, 64);
+        let node = ctx.kernel.mqueue().open_child("sized").unwrap();
+        let inode = node.inode().unwrap();
+        let queue = inode.downcast_ref::<ipc::PosixMessageQueue>().unwrap();
+        assert_eq!(queue.attributes().max_messages, 4);
+    }
+
+    #[test]
+    fn test_checks_permissions() {
+        let (ctx, _) = setup_fs_context(Credentials::new(1000, 100));
+        create(&ctx, "queue", 4, 64);
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1001, 100);
+        assert_eq!(
+            open(&ctx, "queue", OpenFlags::O_RDONLY, 0, None),
+            Err(ErrNo::PermissionDenied)
+        );
+
+        // Beyond the defaults only for an admin
+        let flags = OpenFlags::O_RDWR | OpenFlags::O_CREAT;
+        assert_eq!(
+            open(&ctx, "large", flags, 0o600, Some(attr(11, 64))),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let flags = OpenFlags::O_RDWR | OpenFlags::O_CREAT;
+
+        assert_eq!(
+            open(&ctx, "", flags, 0o600, None),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(
+            open(&ctx, "a/b", flags, 0o600, None),
+            Err(ErrNo::PermissionDenied)
+        );
+        assert_eq!(
+            open(&ctx, "queue", flags, 0o600, Some(attr(0, 64))),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            open(&ctx, "queue", OpenFlags::O_ACCMODE, 0o600, None),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
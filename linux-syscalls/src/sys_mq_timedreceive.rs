@@ -0,0 +1,142 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use timing::TimeSpec;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Takes the oldest message of the highest priority from the POSIX message queue open as
+    /// `mqdes` into `msg_ptr`, waiting until there is one unless it is open with `O_NONBLOCK`.
+    /// Returns how long the message is, and writes its priority to `msg_prio` if not null.
+    ///
+    /// `msg_len` has to hold the largest message of the queue. `abs_timeout`, if not null, is
+    /// when to give up with `ETIMEDOUT`.
+    pub async fn sys_mq_timedreceive(
+        &self,
+        mqdes: usize,
+        msg_ptr: VirtualAddress,
+        msg_len: usize,
+        msg_prio: VirtualAddress,
+        abs_timeout: VirtualAddress,
+    ) -> SyscallResult {
+        let open = self.message_queue_of(mqdes)?;
+
+        if !open.readable() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        let deadline = match abs_timeout.is_null() {
+            true => None,
+            false => {
+                let deadline = self
+                    .task
+                    .process()
+                    .mmu()
+                    .lock()
+                    .import::<TimeSpec>(abs_timeout)
+                    .map_err(|_| ErrNo::BadAddress)?;
+
+                Self::check_time_validity(deadline)?;
+
+                Some(deadline)
+            }
+        };
+
+        let receive = open.queue().receive(msg_len, open.nonblocking());
+
+        let (data, priority) = match deadline {
+            None => receive.await?,
+            Some(deadline) => self
+                .kernel
+                .timer()
+                .timeout(deadline, receive)
+                .await
+                .map_err(|_| ErrNo::ConnectionTimedOut)??,
+        };
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        mmu.write_bytes(msg_ptr, &data)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        if !msg_prio.is_null() {
+            mmu.export(msg_prio, priority)
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        Ok(data.len() as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use filesystem_abstractions::{Credentials, OpenFlags};
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context,
+        sys_mq_open::tests::{create, open},
+        sys_mq_timedsend::tests::send,
+    };
+
+    fn receive(
+        ctx: &SyscallContext,
+        mqdes: usize,
+        len: usize,
+        deadline: Option<TimeSpec>,
+    ) -> Result<(Vec<u8>, u32), ErrNo> {
+        let buf = vec![0u8; len].into_boxed_slice();
+        let prio = Box::new(0u32);
+        let deadline = deadline.map(Box::new);
+
+        let mmu = ctx.task.process().mmu();
+        let msg_ptr = mmu.lock().register(&*buf, true);
+        let msg_prio = mmu.lock().register(prio.as_ref(), true);
+        let abs_timeout = match &deadline {
+            Some(deadline) => mmu.lock().register(deadline.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+
+        let received =
+            block_on!(ctx.sys_mq_timedreceive(mqdes, msg_ptr, len, msg_prio, abs_timeout))?;
+
+        Ok((buf[..received as usize].to_vec(), *prio))
+    }
+
+    #[test]
+    fn test_by_priority() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+
+        send(&ctx, fd, b"low", 1, None).unwrap();
+        send(&ctx, fd, b"high", 7, None).unwrap();
+
+        assert_eq!(receive(&ctx, fd, 8, None), Ok((b"high".to_vec(), 7)));
+        assert_eq!(receive(&ctx, fd, 8, None), Ok((b"low".to_vec(), 1)));
+        assert_eq!(
+            receive(&ctx, fd, 8, Some(ctx.kernel.time())),
+            Err(ErrNo::ConnectionTimedOut)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 4, 8);
+        let write_only = open(&ctx, "queue", OpenFlags::O_WRONLY, 0, None).unwrap() as usize;
+        let nonblocking = open(&ctx, "queue", OpenFlags::O_NONBLOCK, 0, None).unwrap() as usize;
+
+        assert_eq!(receive(&ctx, fd, 4, None), Err(ErrNo::MessageTooLong));
+        assert_eq!(
+            receive(&ctx, write_only, 8, None),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(
+            receive(&ctx, nonblocking, 8, None),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+}
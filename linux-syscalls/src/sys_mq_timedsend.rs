@@ -0,0 +1,161 @@
+use address::{IAddressBase, VirtualAddress};
+use alloc::vec;
+use constants::ErrNo;
+use ipc::MQ_PRIO_MAX;
+use timing::TimeSpec;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Sends the `msg_len` bytes at `msg_ptr` with `msg_prio` to the POSIX message queue open as
+    /// `mqdes`, waiting until it has room unless it is open with `O_NONBLOCK`.
+    ///
+    /// `abs_timeout`, if not null, is when to give up with `ETIMEDOUT`.
+    pub async fn sys_mq_timedsend(
+        &self,
+        mqdes: usize,
+        msg_ptr: VirtualAddress,
+        msg_len: usize,
+        msg_prio: usize,
+        abs_timeout: VirtualAddress,
+    ) -> SyscallResult {
+        let open = self.message_queue_of(mqdes)?;
+
+        if !open.writable() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        if msg_prio >= MQ_PRIO_MAX as usize {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let queue = open.queue();
+
+        if msg_len > queue.attributes().message_size {
+            return Err(ErrNo::MessageTooLong);
+        }
+
+        let (data, deadline) = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let mut data = vec![0; msg_len];
+            mmu.read_bytes(msg_ptr, &mut data)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            let deadline = match abs_timeout.is_null() {
+                true => None,
+                false => Some(
+                    mmu.import::<TimeSpec>(abs_timeout)
+                        .map_err(|_| ErrNo::BadAddress)?,
+                ),
+            };
+
+            (data, deadline)
+        };
+
+        if let Some(deadline) = deadline {
+            Self::check_time_validity(deadline)?;
+        }
+
+        let send = queue.send(data, msg_prio as u32, open.nonblocking());
+
+        // There are no signals to deliver yet, so a registration is used up without one
+        let _notification = match deadline {
+            None => send.await?,
+            Some(deadline) => self
+                .kernel
+                .timer()
+                .timeout(deadline, send)
+                .await
+                .map_err(|_| ErrNo::ConnectionTimedOut)??,
+        };
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::{Credentials, OpenFlags};
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context,
+        sys_mq_open::tests::{create, open},
+    };
+
+    /// Sends `data` with `prio` from user memory, waiting until `deadline` at most.
+    pub fn send(
+        ctx: &SyscallContext,
+        mqdes: usize,
+        data: &[u8],
+        prio: usize,
+        deadline: Option<TimeSpec>,
+    ) -> SyscallResult {
+        let data = Box::<[u8]>::from(data);
+        let deadline = deadline.map(Box::new);
+
+        let mmu = ctx.task.process().mmu();
+        let msg_ptr = mmu.lock().register(&*data, true);
+        let abs_timeout = match &deadline {
+            Some(deadline) => mmu.lock().register(deadline.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+
+        block_on!(ctx.sys_mq_timedsend(mqdes, msg_ptr, data.len(), prio, abs_timeout))
+    }
+
+    #[test]
+    fn test_until_full() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 2, 8);
+
+        assert_eq!(send(&ctx, fd, b"one", 1, None), Ok(0));
+        assert_eq!(send(&ctx, fd, b"two", 2, None), Ok(0));
+
+        let past = ctx.kernel.time();
+        assert_eq!(
+            send(&ctx, fd, b"three", 3, Some(past)),
+            Err(ErrNo::ConnectionTimedOut)
+        );
+
+        let nonblocking = open(
+            &ctx,
+            "queue",
+            OpenFlags::O_WRONLY | OpenFlags::O_NONBLOCK,
+            0,
+            None,
+        );
+        assert_eq!(
+            send(&ctx, nonblocking.unwrap() as usize, b"three", 3, None),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let fd = create(&ctx, "queue", 2, 8);
+        let read_only = open(&ctx, "queue", OpenFlags::O_RDONLY, 0, None).unwrap() as usize;
+
+        assert_eq!(
+            send(&ctx, fd, b"too long!", 0, None),
+            Err(ErrNo::MessageTooLong)
+        );
+        assert_eq!(
+            send(&ctx, fd, b"high", MQ_PRIO_MAX as usize, None),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            send(&ctx, read_only, b"one", 0, None),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(
+            send(&ctx, 42, b"one", 0, None),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use address::VirtualAddress;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Removes the POSIX message queue `name`, which stays usable through the descriptors
+    /// open to it until they are closed.
+    pub fn sys_mq_unlink(&self, name: VirtualAddress) -> SyscallResult {
+        let name = self.read_queue_name(name)?;
+        let root = self.kernel.mqueue();
+
+        root.check_delete(&name, &self.credentials())
+            .map_err(|e| e.to_errno())?;
+        root.remove(&name).map_err(|e| e.to_errno())?;
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::ErrNo;
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::{
+        fs::tests::{setup_fs_context, UserPath},
+        sys_mq_open::tests::{create, open},
+    };
+
+    fn unlink(ctx: &SyscallContext, name: &str) -> SyscallResult {
+        let name = UserPath::new(ctx, name);
+
+        ctx.sys_mq_unlink(name.addr())
+    }
+
+    #[test]
+    fn test_unlink() {
+        let (ctx, _) = setup_fs_context(Credentials::new(1000, 100));
+        create(&ctx, "queue", 4, 64);
+
+        // Only the owner may remove it from the sticky directory
+        *ctx.task.process().credentials().lock() = Credentials::new(1001, 100);
+        assert_eq!(unlink(&ctx, "queue"), Err(ErrNo::OperationNotPermitted));
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(unlink(&ctx, "queue"), Ok(0));
+        assert_eq!(
+            open(&ctx, "queue", OpenFlags::O_RDONLY, 0, None),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(unlink(&ctx, "queue"), Err(ErrNo::NoSuchFileOrDirectory));
+    }
+}
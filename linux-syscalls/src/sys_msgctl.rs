@@ -0,0 +1,260 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use ipc::{IIpcObject, MSGMAX, MSGMNB, MSGMNI};
+
+use crate::{
+    sysv_ipc::{IpcPerm, IPC_INFO, IPC_RMID, IPC_SET, IPC_STAT},
+    SyscallContext, SyscallResult,
+};
+
+/// `IPC_STAT` returning the id
+const MSG_STAT: usize = 11;
+/// `IPC_INFO` with what is in use rather than the limits
+const MSG_INFO: usize = 12;
+
+/// `struct msqid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsqidDs {
+    pub msg_perm: IpcPerm,
+    pub msg_stime: i64,
+    pub msg_rtime: i64,
+    pub msg_ctime: i64,
+    pub msg_cbytes: u64,
+    pub msg_qnum: u64,
+    pub msg_qbytes: u64,
+    pub msg_lspid: i32,
+    pub msg_lrpid: i32,
+    pub unused: [u64; 2],
+}
+
+/// `struct msginfo`, the limits reported by `IPC_INFO`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsgInfo {
+    pub msgpool: i32,
+    pub msgmap: i32,
+    pub msgmax: i32,
+    pub msgmnb: i32,
+    pub msgmni: i32,
+    pub msgssz: i32,
+    pub msgtql: i32,
+    pub msgseg: u16,
+}
+
+impl SyscallContext {
+    /// Reports, changes or removes the System V message queue `msqid`.
+    ///
+    /// Only an admin may let a queue hold more than `MSGMNB` bytes. Removing a queue fails
+    /// every send and receive waiting on it with `EIDRM`.
+    pub fn sys_msgctl(&self, msqid: usize, cmd: usize, buf: VirtualAddress) -> SyscallResult {
+        let queues = self.kernel.message_queues();
+
+        if cmd == IPC_INFO || cmd == MSG_INFO {
+            let mut info = MsgInfo {
+                msgpool: (MSGMNI * MSGMNB / 1024) as i32,
+                msgmap: MSGMNB as i32,
+                msgmax: MSGMAX as i32,
+                msgmnb: MSGMNB as i32,
+                msgmni: MSGMNI as i32,
+                msgssz: 16,
+                msgtql: MSGMNB as i32,
+                msgseg: u16::MAX,
+            };
+
+            if cmd == MSG_INFO {
+                let objects = queues.objects();
+
+                info.msgpool = objects.len() as i32;
+                info.msgmap = objects.iter().map(|(_, queue)| queue.len() as i32).sum();
+                info.msgtql = objects.iter().map(|(_, queue)| queue.bytes() as i32).sum();
+            }
+
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .export(buf, info)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            return Ok(queues.highest_id().unwrap_or(0) as isize);
+        }
+
+        let queue = queues.find(msqid).ok_or(ErrNo::InvalidArgument)?;
+        let credentials = self.credentials();
+
+        match cmd {
+            IPC_STAT | MSG_STAT => {
+                if !queue.permissions().allows(&credentials, 0o444) {
+                    return Err(ErrNo::PermissionDenied);
+                }
+
+                let status = *queue.status().lock();
+                let ds = MsqidDs {
+                    msg_perm: status.permissions.into(),
+                    msg_stime: status.send_time,
+                    msg_rtime: status.receive_time,
+                    msg_ctime: status.change_time,
+                    msg_cbytes: queue.bytes() as u64,
+                    msg_qnum: queue.len() as u64,
+                    msg_qbytes: status.max_bytes as u64,
+                    msg_lspid: status.last_sender as i32,
+                    msg_lrpid: status.last_receiver as i32,
+                    ..Default::default()
+                };
+
+                self.task
+                    .process()
+                    .mmu()
+                    .lock()
+                    .export(buf, ds)
+                    .map_err(|_| ErrNo::BadAddress)?;
+
+                match cmd {
+                    MSG_STAT => Ok(msqid as isize),
+                    _ => Ok(0),
+                }
+            }
+            IPC_SET | IPC_RMID => {
+                if !queue.permissions().can_change(&credentials) {
+                    return Err(ErrNo::OperationNotPermitted);
+                }
+
+                match cmd {
+                    IPC_SET => {
+                        let ds = self
+                            .task
+                            .process()
+                            .mmu()
+                            .lock()
+                            .import::<MsqidDs>(buf)
+                            .map_err(|_| ErrNo::BadAddress)?;
+
+                        let max_bytes = ds.msg_qbytes as usize;
+
+                        if max_bytes > MSGMNB && !credentials.is_admin() {
+                            return Err(ErrNo::OperationNotPermitted);
+                        }
+
+                        let mut status = queue.status().lock();
+                        let perm = ds.msg_perm;
+
+                        status.permissions.set(perm.uid, perm.gid, perm.mode);
+                        status.change_time = self.kernel.time().tv_sec;
+                        drop(status);
+
+                        queue.set_max_bytes(max_bytes);
+                    }
+                    _ => {
+                        queues.remove(msqid);
+                        queue.remove();
+                    }
+                }
+
+                Ok(0)
+            }
+            _ => Err(ErrNo::InvalidArgument),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context,
+        sys_msgrcv::tests::receive,
+        sys_msgsnd::tests::{create_queue, send},
+        sysv_ipc::IPC_NOWAIT,
+    };
+
+    fn stat(ctx: &SyscallContext, msqid: usize) -> Result<MsqidDs, ErrNo> {
+        let ds = Box::new(MsqidDs::default());
+        let ptr = ctx.task.process().mmu().lock().register(ds.as_ref(), true);
+
+        ctx.sys_msgctl(msqid, IPC_STAT, ptr).map(|_| *ds)
+    }
+
+    fn set(ctx: &SyscallContext, msqid: usize, ds: MsqidDs) -> SyscallResult {
+        let ds = Box::new(ds);
+        let ptr = ctx.task.process().mmu().lock().register(ds.as_ref(), true);
+
+        ctx.sys_msgctl(msqid, IPC_SET, ptr)
+    }
+
+    #[test]
+    fn test_stat_and_set() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = ctx.sys_msgget(42, 0o1000 | 0o640).unwrap() as usize;
+        send(&ctx, id, 1, b"hello", 0).unwrap();
+
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!(ds.msg_perm.key, 42);
+        assert_eq!(ds.msg_perm.mode, 0o640);
+        assert_eq!((ds.msg_qnum, ds.msg_cbytes), (1, 5));
+        assert_eq!(ds.msg_qbytes, MSGMNB as u64);
+        assert_eq!(ds.msg_lspid, ctx.task.process().pid() as i32);
+
+        let mut changed = ds;
+        changed.msg_perm.uid = 1000;
+        changed.msg_qbytes = 4;
+        assert_eq!(set(&ctx, id, changed), Ok(0));
+
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!((ds.msg_perm.uid, ds.msg_qbytes), (1000, 4));
+
+        // The owner now, but can't raise the limit past the default
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        changed.msg_qbytes = MSGMNB as u64 + 1;
+        assert_eq!(set(&ctx, id, changed), Err(ErrNo::OperationNotPermitted));
+        changed.msg_qbytes = MSGMNB as u64;
+        assert_eq!(set(&ctx, id, changed), Ok(0));
+    }
+
+    #[test]
+    fn test_remove() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+        send(&ctx, id, 1, b"hello", 0).unwrap();
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(
+            ctx.sys_msgctl(id, IPC_RMID, VirtualAddress::null()),
+            Err(ErrNo::OperationNotPermitted)
+        );
+
+        *ctx.task.process().credentials().lock() = Credentials::root();
+        assert_eq!(ctx.sys_msgctl(id, IPC_RMID, VirtualAddress::null()), Ok(0));
+        assert_eq!(stat(&ctx, id), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            receive(&ctx, id, 8, 0, IPC_NOWAIT),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_info() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        create_queue(&ctx);
+        let last = create_queue(&ctx);
+        send(&ctx, last, 1, b"hello", 0).unwrap();
+
+        let info = Box::new(MsgInfo::default());
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(info.as_ref(), true);
+
+        assert_eq!(ctx.sys_msgctl(0, MSG_INFO, ptr), Ok(last as isize));
+        assert_eq!((info.msgpool, info.msgmap, info.msgtql), (2, 1, 5));
+        assert_eq!(ctx.sys_msgctl(0, IPC_INFO, ptr), Ok(last as isize));
+        assert_eq!(info.msgmni, MSGMNI as i32);
+        assert_eq!(ctx.sys_msgctl(0, 42, ptr), Err(ErrNo::InvalidArgument));
+    }
+}
@@ -0,0 +1,56 @@
+use ipc::{IpcGetFlags, MessageQueue};
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Returns the id of the System V message queue with `key`, creating one if `msgflg` asks
+    /// for it, with the permissions in its lower bits.
+    pub fn sys_msgget(&self, key: usize, msgflg: usize) -> SyscallResult {
+        let key = key as i32;
+        let flags = IpcGetFlags::from_bits_truncate(msgflg as u32);
+        let now = self.kernel.time().tv_sec;
+
+        let id = self.kernel.message_queues().get(
+            key,
+            flags,
+            msgflg as u32 & 0o777,
+            &self.credentials(),
+            |_| Ok(()),
+            |permissions| Ok(MessageQueue::new(permissions, now)),
+        )?;
+
+        Ok(id as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::ErrNo;
+    use filesystem_abstractions::Credentials;
+    use ipc::{IPC_PRIVATE, MSGMNB};
+
+    use crate::fs::tests::setup_fs_context;
+
+    const IPC_CREAT: usize = 0o1000;
+    const IPC_EXCL: usize = 0o2000;
+
+    #[test]
+    fn test_get_by_key() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        assert_eq!(ctx.sys_msgget(42, 0o600), Err(ErrNo::NoSuchFileOrDirectory));
+
+        let id = ctx.sys_msgget(42, IPC_CREAT | 0o600).unwrap();
+        assert_eq!(ctx.sys_msgget(42, 0), Ok(id));
+        assert_eq!(
+            ctx.sys_msgget(42, IPC_CREAT | IPC_EXCL | 0o600),
+            Err(ErrNo::FileExists)
+        );
+
+        let queue = ctx.kernel.message_queues().find(id as usize).unwrap();
+        assert_eq!(queue.status().lock().max_bytes, MSGMNB);
+        assert!(queue.is_empty());
+
+        assert_ne!(ctx.sys_msgget(IPC_PRIVATE as usize, 0o600), Ok(id));
+    }
+}
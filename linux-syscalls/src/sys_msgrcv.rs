@@ -0,0 +1,154 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use ipc::{IIpcObject, MessageSelector};
+
+use crate::{sysv_ipc::IPC_NOWAIT, SyscallContext, SyscallResult};
+
+/// Truncates messages longer than the buffer instead of failing with `E2BIG`
+const MSG_NOERROR: usize = 0o10000;
+/// Takes a message of any type but `msgtyp`
+const MSG_EXCEPT: usize = 0o20000;
+/// Copies the message at an index without taking it, which only checkpointing uses
+const MSG_COPY: usize = 0o40000;
+
+impl SyscallContext {
+    /// Takes a message from the System V message queue `msqid` into `msgp`, a `long` type
+    /// followed by up to `msgsz` bytes, waiting until there is one unless `msgflg` has
+    /// `IPC_NOWAIT`. Returns how many bytes the message has.
+    ///
+    /// A `msgtyp` of zero takes the first message, a positive one the first of that type and a
+    /// negative one the first of the lowest type up to its absolute value.
+    pub async fn sys_msgrcv(
+        &self,
+        msqid: usize,
+        msgp: VirtualAddress,
+        msgsz: usize,
+        msgtyp: usize,
+        msgflg: usize,
+    ) -> SyscallResult {
+        if (msgsz as isize) < 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        if msgflg & MSG_COPY != 0 {
+            return Err(ErrNo::FunctionNotImplemented);
+        }
+
+        let queue = self
+            .kernel
+            .message_queues()
+            .find(msqid)
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        if !queue.permissions().allows(&self.credentials(), 0o444) {
+            return Err(ErrNo::PermissionDenied);
+        }
+
+        let message = queue
+            .receive(
+                MessageSelector::new(msgtyp as i64, msgflg & MSG_EXCEPT != 0),
+                msgsz,
+                msgflg & MSG_NOERROR != 0,
+                msgflg & IPC_NOWAIT != 0,
+                self.task.process().pid(),
+                self.kernel.time().tv_sec,
+            )
+            .await?;
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        mmu.export(msgp, message.kind)
+            .map_err(|_| ErrNo::BadAddress)?;
+        mmu.write_bytes(msgp + size_of::<i64>(), &message.data)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        Ok(message.data.len() as isize)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{vec, vec::Vec};
+    use filesystem_abstractions::Credentials;
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context,
+        sys_msgsnd::tests::{create_queue, send},
+    };
+
+    /// Receives a message of up to `len` bytes into user memory, returning its type and data.
+    pub fn receive(
+        ctx: &SyscallContext,
+        msqid: usize,
+        len: usize,
+        msgtyp: i64,
+        msgflg: usize,
+    ) -> Result<(i64, Vec<u8>), ErrNo> {
+        let buf = vec![0u8; size_of::<i64>() + len].into_boxed_slice();
+        let msgp = ctx.task.process().mmu().lock().register(&*buf, true);
+
+        let received = block_on!(ctx.sys_msgrcv(msqid, msgp, len, msgtyp as usize, msgflg))?;
+        let kind = i64::from_ne_bytes(buf[..8].try_into().unwrap());
+
+        Ok((kind, buf[8..8 + received as usize].to_vec()))
+    }
+
+    #[test]
+    fn test_by_type() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+
+        for (kind, data) in [(3, b"three"), (1, b"one.1"), (2, b"two.1"), (1, b"one.2")] {
+            send(&ctx, id, kind, data, 0).unwrap();
+        }
+
+        assert_eq!(receive(&ctx, id, 8, 2, 0), Ok((2, b"two.1".to_vec())));
+        assert_eq!(receive(&ctx, id, 8, -2, 0), Ok((1, b"one.1".to_vec())));
+        assert_eq!(
+            receive(&ctx, id, 8, 1, MSG_EXCEPT),
+            Ok((3, b"three".to_vec()))
+        );
+        assert_eq!(receive(&ctx, id, 8, 0, 0), Ok((1, b"one.2".to_vec())));
+        assert_eq!(
+            receive(&ctx, id, 8, 0, IPC_NOWAIT),
+            Err(ErrNo::NoMessageOfDesiredType)
+        );
+    }
+
+    #[test]
+    fn test_too_long() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+        send(&ctx, id, 1, b"message", 0).unwrap();
+
+        assert_eq!(receive(&ctx, id, 4, 0, 0), Err(ErrNo::ArgumentListTooLong));
+        assert_eq!(
+            receive(&ctx, id, 4, 0, MSG_NOERROR),
+            Ok((1, b"mess".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+
+        assert_eq!(
+            receive(&ctx, id, 8, 0, MSG_COPY | IPC_NOWAIT),
+            Err(ErrNo::FunctionNotImplemented)
+        );
+        assert_eq!(
+            receive(&ctx, id + 1, 8, 0, IPC_NOWAIT),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(
+            receive(&ctx, id, 8, 0, IPC_NOWAIT),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+}
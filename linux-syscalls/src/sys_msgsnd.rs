@@ -0,0 +1,141 @@
+use address::VirtualAddress;
+use alloc::vec;
+use constants::ErrNo;
+use ipc::{IIpcObject, Message, MSGMAX};
+
+use crate::{sysv_ipc::IPC_NOWAIT, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Sends the message at `msgp`, a `long` type followed by `msgsz` bytes, to the System V
+    /// message queue `msqid`, waiting until it has room unless `msgflg` has `IPC_NOWAIT`.
+    pub async fn sys_msgsnd(
+        &self,
+        msqid: usize,
+        msgp: VirtualAddress,
+        msgsz: usize,
+        msgflg: usize,
+    ) -> SyscallResult {
+        if msgsz > MSGMAX {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let queue = self
+            .kernel
+            .message_queues()
+            .find(msqid)
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        let message = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let kind = mmu.import::<i64>(msgp).map_err(|_| ErrNo::BadAddress)?;
+
+            if kind < 1 {
+                return Err(ErrNo::InvalidArgument);
+            }
+
+            let mut data = vec![0; msgsz];
+            mmu.read_bytes(msgp + size_of::<i64>(), &mut data)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            Message { kind, data }
+        };
+
+        if !queue.permissions().allows(&self.credentials(), 0o222) {
+            return Err(ErrNo::PermissionDenied);
+        }
+
+        queue
+            .send(
+                message,
+                msgflg & IPC_NOWAIT != 0,
+                self.task.process().pid(),
+                self.kernel.time().tv_sec,
+            )
+            .await?;
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::{boxed::Box, vec::Vec};
+    use filesystem_abstractions::Credentials;
+    use threading::block_on;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    /// A queue that only root can use, with a private key.
+    pub fn create_queue(ctx: &SyscallContext) -> usize {
+        ctx.sys_msgget(0, 0o1000 | 0o600).unwrap() as usize
+    }
+
+    /// Sends a message of `kind` with `data` from user memory.
+    pub fn send(
+        ctx: &SyscallContext,
+        msqid: usize,
+        kind: i64,
+        data: &[u8],
+        msgflg: usize,
+    ) -> SyscallResult {
+        let mut buf = Vec::from(kind.to_ne_bytes());
+        buf.extend_from_slice(data);
+
+        let buf: Box<[u8]> = buf.into();
+        let msgp = ctx.task.process().mmu().lock().register(&*buf, true);
+
+        block_on!(ctx.sys_msgsnd(msqid, msgp, data.len(), msgflg))
+    }
+
+    #[test]
+    fn test_send() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+
+        assert_eq!(send(&ctx, id, 1, b"hello", 0), Ok(0));
+
+        let queue = ctx.kernel.message_queues().find(id).unwrap();
+        assert_eq!((queue.len(), queue.bytes()), (1, 5));
+        assert_eq!(queue.status().lock().last_sender, ctx.task.process().pid());
+    }
+
+    #[test]
+    fn test_full() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+
+        let queue = ctx.kernel.message_queues().find(id).unwrap();
+        queue.set_max_bytes(8);
+
+        assert_eq!(send(&ctx, id, 1, &[0; 6], IPC_NOWAIT), Ok(0));
+        assert_eq!(
+            send(&ctx, id, 1, &[0; 6], IPC_NOWAIT),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_queue(&ctx);
+
+        assert_eq!(send(&ctx, id, 0, b"zero", 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            send(&ctx, id, 1, &[0; MSGMAX + 1], 0),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            send(&ctx, id + 1, 1, b"none", 0),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(
+            send(&ctx, id, 1, b"denied", 0),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+}
@@ -1,6 +1,5 @@
 use address::VirtualAddress;
 use constants::ErrNo;
-use threading::yield_now;
 use timing::TimeSpec;
 
 use crate::{SyscallContext, SyscallResult};
@@ -38,16 +37,13 @@ impl SyscallContext {
             Ok(0isize)
         };
 
-        while start + req > self.kernel.time() {
-            yield_now().await;
-
-            // TODO: check interrupt and call _interrupted()?
-        }
+        // TODO: check interrupt and call _interrupted()?
+        self.kernel.timer().sleep_until(start + req).await;
 
         Ok(0)
     }
 
-    fn check_time_validity(t: TimeSpec) -> Result<(), ErrNo> {
+    pub(crate) fn check_time_validity(t: TimeSpec) -> Result<(), ErrNo> {
         // see man nanosleep
         if t.tv_sec < 0 || t.tv_nsec < 0 || t.tv_nsec > 999999999 {
             return Err(ErrNo::InvalidArgument);
@@ -59,7 +55,11 @@ impl SyscallContext {
 
 #[cfg(test)]
 mod tests {
-    use core::time::Duration;
+    use core::{
+        future::Future,
+        task::{Context, Waker},
+        time::Duration,
+    };
     use std::time::SystemTime;
 
     use address::IAddressBase;
@@ -134,6 +134,23 @@ mod tests {
         assert!(duration < THRESHOLD);
     }
 
+    #[test]
+    fn test_syscall_waits_on_timer() {
+        let (mmu, ctx) = setup_syscall_context();
+
+        let req = TimeSpec::new(60, 0);
+
+        mmu.lock().register(&req, false);
+
+        let mut fut = core::pin::pin!(
+            ctx.sys_nanosleep(VirtualAddress::from_ref(&req), VirtualAddress::null())
+        );
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(ctx.kernel.timer().pending(), 1);
+    }
+
     #[test]
     fn test_syscall_sec_negative() {
         let req = TimeSpec::new(-1, 0);
@@ -0,0 +1,82 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::{OpenFlags, Pipe};
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Creates a pipe and writes the file descriptors of its read end and its write end to `fds`.
+    pub fn sys_pipe2(&self, fds: VirtualAddress, flags: usize) -> SyscallResult {
+        let flags = OpenFlags::from_bits(flags).ok_or(ErrNo::InvalidArgument)?;
+
+        if !(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC).contains(flags) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let (read_end, write_end) = Pipe::new(flags);
+
+        let process = self.task.linux_process();
+        let mut fd_table = process.fd_table().lock();
+
+        let read_end = fd_table.allocate(read_end).ok_or(ErrNo::TooManyOpenFiles)?;
+        let write_end = match fd_table.allocate(write_end) {
+            Some(fd) => fd,
+            None => {
+                fd_table.remove(read_end);
+                return Err(ErrNo::TooManyOpenFiles);
+            }
+        };
+
+        let exported = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .export(fds, [read_end as i32, write_end as i32]);
+
+        if exported.is_err() {
+            fd_table.remove(read_end);
+            fd_table.remove(write_end);
+
+            return Err(ErrNo::BadAddress);
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    #[test]
+    fn test_creates_connected_ends() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        let fds = Box::new([-1i32; 2]);
+        let ptr = ctx.task.process().mmu().lock().register(fds.as_ref(), true);
+
+        assert_eq!(ctx.sys_pipe2(ptr, OpenFlags::O_CLOEXEC.bits()), Ok(0));
+
+        let read_end = ctx.file_of(fds[0] as usize).unwrap();
+        let write_end = ctx.file_of(fds[1] as usize).unwrap();
+
+        assert!(read_end.can_read() && !read_end.can_write());
+        assert!(write_end.flags().contains(OpenFlags::O_CLOEXEC));
+
+        assert_eq!(write_end.write(b"data"), 4);
+
+        let mut buf = [0; 8];
+        assert_eq!(read_end.read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"data");
+
+        assert_eq!(
+            ctx.sys_pipe2(ptr, OpenFlags::O_RDWR.bits()),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use alloc::{sync::Arc, vec};
+use constants::ErrNo;
+use filesystem_abstractions::{IFile, OpenFlags};
+use threading::yield_now;
+
+impl SyscallContext {
+    pub async fn sys_read(&self, fd: usize, buf: VirtualAddress, count: usize) -> SyscallResult {
+        log::debug!("sys_read: fd: {}, buf: {}, count: {}", fd, buf, count);
+
+        let file = {
+            let process = self.task.linux_process();
+
+            let fd_table = process.fd_table().lock();
+
+            fd_table.get(fd).ok_or(ErrNo::BadFileDescriptor)?.clone()
+        };
+
+        if !file.can_read() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        self.sys_read_internal(file, buf, count).await
+    }
+
+    async fn sys_read_internal(
+        &self,
+        file: Arc<dyn IFile>,
+        buf: VirtualAddress,
+        count: usize,
+    ) -> SyscallResult {
+        while !file.read_avaliable() {
+            if file.flags().contains(OpenFlags::O_NONBLOCK) {
+                return Err(ErrNo::ResourceTemporarilyUnavailable);
+            }
+
+            yield_now().await;
+        }
+
+        let mut data = vec![0u8; count];
+        let bytes_read = file.try_read(&mut data).map_err(|e| e.to_errno())?;
+
+        if bytes_read != 0 {
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .write_bytes(buf, &data[..bytes_read])
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        Ok(bytes_read as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, InotifyMask};
+    use threading::block_on;
+
+    use super::*;
+    use crate::fs::tests::{open_fd, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_reads_file_content() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"Hello, world").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+
+        let first = vec![0u8; 5];
+        let second = vec![0u8; 32];
+        let first_ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(first.as_slice(), true);
+        let second_ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(second.as_slice(), true);
+
+        assert_eq!(block_on!(ctx.sys_read(fd, first_ptr, 5)), Ok(5));
+        assert_eq!(block_on!(ctx.sys_read(fd, second_ptr, 32)), Ok(7));
+        assert_eq!(&first[..], b"Hello");
+        assert_eq!(&second[..7], b", world");
+
+        assert_eq!(
+            block_on!(ctx.sys_read(fd + 1, second_ptr, 32)),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+
+    #[test]
+    fn test_inotify_rejects_buffer_smaller_than_an_event() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+
+        let fd = ctx.sys_inotify_init1(OpenFlags::O_NONBLOCK.bits()).unwrap() as usize;
+        let path = UserPath::new(&ctx, "/");
+        ctx.sys_inotify_add_watch(fd, path.addr(), InotifyMask::IN_CREATE.bits())
+            .unwrap();
+
+        let buf = vec![0u8; 64];
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(buf.as_slice(), true);
+
+        assert_eq!(
+            block_on!(ctx.sys_read(fd, ptr, 64)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        root.touch("file").unwrap();
+
+        assert_eq!(
+            block_on!(ctx.sys_read(fd, ptr, 16)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(block_on!(ctx.sys_read(fd, ptr, 64)), Ok(32));
+    }
+}
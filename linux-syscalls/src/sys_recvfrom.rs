@@ -0,0 +1,139 @@
+use crate::{socket::wait_for_socket, SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use alloc::vec;
+use constants::ErrNo;
+use socket_abstractions::MessageFlags;
+
+impl SyscallContext {
+    pub async fn sys_recvfrom(
+        &self,
+        sockfd: usize,
+        buf: VirtualAddress,
+        len: usize,
+        flags: usize,
+        src_addr: VirtualAddress,
+        addrlen: VirtualAddress,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_recvfrom: sockfd: {}, buf: {}, len: {}, flags: {:#x}, src_addr: {}, addrlen: {}",
+            sockfd,
+            buf,
+            len,
+            flags,
+            src_addr,
+            addrlen
+        );
+
+        let flags = MessageFlags::from_bits_truncate(flags);
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+
+        let nonblocking = nonblocking || flags.contains(MessageFlags::MSG_DONTWAIT);
+
+        let mut data = vec![0u8; len];
+        let message = wait_for_socket(socket.as_ref(), nonblocking, || {
+            socket.receive(&mut data, flags)
+        })
+        .await?;
+
+        if message.length != 0 {
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .write_bytes(buf, &data[..message.length])
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        self.write_socket_address(&message.source, src_addr, addrlen)?;
+
+        // Files passed along are closed when they are received this way
+        match flags.contains(MessageFlags::MSG_TRUNC) {
+            true => Ok(message.full_length as isize),
+            false => Ok(message.length as isize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use socket_abstractions::{
+        ISocket, SocketAddress, SocketFlags, SocketType, UnixAddress, AF_UNIX,
+    };
+    use threading::block_on;
+    use unix_socket::UnixSocket;
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_receives_with_source() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let (socket, _) = ctx.socket_of(fd).unwrap();
+        let address = SocketAddress::Unix(UnixAddress::Pathname("/tmp/dgram".into()));
+        socket.bind(&address).unwrap();
+
+        let sender = UnixSocket::new(SocketType::Datagram, ctx.kernel.unix_sockets());
+        let source = SocketAddress::Unix(UnixAddress::Pathname("/tmp/sender".into()));
+        sender.bind(&source).unwrap();
+        sender
+            .send(b"truncated", alloc::vec::Vec::new(), Some(&address))
+            .unwrap();
+
+        let buf = Box::new([0u8; 5]);
+        let addr = Box::new([0u8; 128]);
+        let addrlen = Box::new(128u32);
+        let buf_ptr = mmu.lock().register(buf.as_ref(), true);
+        let addr_ptr = mmu.lock().register(addr.as_ref(), true);
+        let len_ptr = mmu.lock().register(addrlen.as_ref(), true);
+
+        let ret = block_on!(ctx.sys_recvfrom(
+            fd,
+            buf_ptr,
+            5,
+            MessageFlags::MSG_TRUNC.bits(),
+            addr_ptr,
+            len_ptr
+        ));
+
+        // MSG_TRUNC reports the real size of the datagram
+        assert_eq!(ret, Ok(9));
+
+        let mmu = mmu.lock();
+        assert_eq!(&mmu.import::<[u8; 5]>(buf_ptr).unwrap(), b"trunc");
+        assert_eq!(
+            mmu.import::<u32>(len_ptr),
+            Ok(source.to_bytes().len() as u32)
+        );
+    }
+
+    #[test]
+    fn test_nonblocking_socket() {
+        let (ctx, _) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(
+                AF_UNIX as usize,
+                SocketType::Datagram as usize | SocketFlags::SOCK_NONBLOCK.bits(),
+                0,
+            )
+            .unwrap() as usize;
+
+        let ret = block_on!(ctx.sys_recvfrom(
+            fd,
+            VirtualAddress::null(),
+            0,
+            0,
+            VirtualAddress::null(),
+            VirtualAddress::null()
+        ));
+
+        assert_eq!(ret, Err(ErrNo::ResourceTemporarilyUnavailable));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    socket::{wait_for_socket, ControlMessageHeader, MessageHeader},
+    SyscallContext, SyscallResult,
+};
+use address::{IAddressBase, VirtualAddress};
+use alloc::{sync::Arc, vec, vec::Vec};
+use constants::ErrNo;
+use filesystem_abstractions::IFile;
+use socket_abstractions::{MessageFlags, SCM_RIGHTS, SOL_SOCKET};
+
+impl SyscallContext {
+    pub async fn sys_recvmsg(
+        &self,
+        sockfd: usize,
+        msg: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_recvmsg: sockfd: {}, msg: {}, flags: {:#x}",
+            sockfd,
+            msg,
+            flags
+        );
+
+        let flags = MessageFlags::from_bits_truncate(flags);
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+
+        let mut header = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<MessageHeader>(msg)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        let vectors = self.read_io_vectors(header.iov, header.iov_len)?;
+        let capacity = vectors.iter().map(|v| v.len).sum();
+
+        let nonblocking = nonblocking || flags.contains(MessageFlags::MSG_DONTWAIT);
+
+        let mut data = vec![0u8; capacity];
+        let message = wait_for_socket(socket.as_ref(), nonblocking, || {
+            socket.receive(&mut data, flags)
+        })
+        .await?;
+
+        {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let mut copied = 0;
+
+            for vector in vectors.iter() {
+                let len = vector.len.min(message.length - copied);
+
+                if len == 0 {
+                    continue;
+                }
+
+                mmu.write_bytes(vector.base, &data[copied..copied + len])
+                    .map_err(|_| ErrNo::BadAddress)?;
+
+                copied += len;
+            }
+        }
+
+        let mut message_flags = MessageFlags::empty();
+
+        if message.full_length > message.length {
+            message_flags |= MessageFlags::MSG_TRUNC;
+        }
+
+        if !header.name.is_null() {
+            let bytes = message.source.to_bytes();
+            let len = bytes.len().min(header.name_len as usize);
+
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .write_bytes(header.name, &bytes[..len])
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            header.name_len = bytes.len() as u32;
+        }
+
+        let (control_len, truncated) =
+            self.install_rights(header.control, header.control_len, message.rights)?;
+
+        if truncated {
+            message_flags |= MessageFlags::MSG_CTRUNC;
+        }
+
+        header.control_len = control_len;
+        header.flags = message_flags.bits() as i32;
+
+        self.task
+            .process()
+            .mmu()
+            .lock()
+            .export(msg, header)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        match flags.contains(MessageFlags::MSG_TRUNC) {
+            true => Ok(message.full_length as isize),
+            false => Ok(message.length as isize),
+        }
+    }
+
+    /// Installs received files into the fd table and writes one `SCM_RIGHTS` message for them.
+    ///
+    /// Returns the length of the control data written and whether files had to be dropped
+    /// because the buffer is too small.
+    fn install_rights(
+        &self,
+        control: VirtualAddress,
+        control_len: usize,
+        rights: Vec<Arc<dyn IFile>>,
+    ) -> Result<(usize, bool), ErrNo> {
+        if rights.is_empty() {
+            return Ok((0, false));
+        }
+
+        let room = control_len.saturating_sub(ControlMessageHeader::SIZE) / size_of::<i32>();
+        let count = room.min(rights.len());
+        let truncated = count < rights.len();
+
+        if count == 0 {
+            return Ok((0, truncated));
+        }
+
+        let fds = {
+            let process = self.task.linux_process();
+            let mut fd_table = process.fd_table().lock();
+
+            let mut fds = Vec::with_capacity(count);
+
+            for file in rights.into_iter().take(count) {
+                match fd_table.allocate(file) {
+                    Some(fd) => fds.push(fd as i32),
+                    None => {
+                        fds.iter().for_each(|fd| fd_table.remove(*fd as usize));
+                        return Err(ErrNo::TooManyOpenFiles);
+                    }
+                }
+            }
+
+            fds
+        };
+
+        let len = ControlMessageHeader::SIZE + fds.len() * size_of::<i32>();
+        let header = ControlMessageHeader {
+            len,
+            level: SOL_SOCKET as i32,
+            ty: SCM_RIGHTS,
+        };
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let fd_bytes = fds
+            .iter()
+            .flat_map(|fd| fd.to_ne_bytes())
+            .collect::<Vec<_>>();
+
+        mmu.export(control, header)
+            .and_then(|_| mmu.write_bytes(control + ControlMessageHeader::SIZE, &fd_bytes))
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        Ok((ControlMessageHeader::align(len).min(control_len), truncated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::OpenFlags;
+    use socket_abstractions::SocketType;
+    use threading::block_on;
+
+    use super::*;
+    use crate::socket::{tests::setup_socket_context, IoVector};
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct RightsMessage {
+        header: ControlMessageHeader,
+        fds: [i32; 2],
+    }
+
+    fn socket_pair(ctx: &SyscallContext) -> (usize, usize) {
+        let (first, second) =
+            unix_socket::UnixSocket::new_pair(SocketType::Stream, ctx.kernel.unix_sockets());
+
+        (
+            ctx.allocate_socket_fd(first, OpenFlags::empty()).unwrap(),
+            ctx.allocate_socket_fd(second, OpenFlags::empty()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_scatters_and_installs_rights() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let (first, second) = socket_pair(&ctx);
+
+        let (sender, _) = ctx.socket_of(first).unwrap();
+        let passed = ctx.file_of(first).unwrap();
+        sender.send(b"abcdef", vec![passed.clone()], None).unwrap();
+
+        let (head, tail) = (Box::new([0u8; 2]), Box::new([0u8; 8]));
+        let head_ptr = mmu.lock().register(head.as_ref(), true);
+        let tail_ptr = mmu.lock().register(tail.as_ref(), true);
+
+        let iov = Box::new([
+            IoVector {
+                base: head_ptr,
+                len: 2,
+            },
+            IoVector {
+                base: tail_ptr,
+                len: 8,
+            },
+        ]);
+        let iov_ptr = mmu.lock().register(iov.as_ref(), false);
+
+        let control = Box::new(RightsMessage {
+            header: ControlMessageHeader {
+                len: 0,
+                level: 0,
+                ty: 0,
+            },
+            fds: [-1; 2],
+        });
+        let control_ptr = mmu.lock().register(control.as_ref(), true);
+
+        let msg = Box::new(MessageHeader {
+            name: VirtualAddress::null(),
+            name_len: 0,
+            iov: iov_ptr,
+            iov_len: 2,
+            control: control_ptr,
+            control_len: size_of::<RightsMessage>(),
+            flags: 0,
+        });
+        let msg_ptr = mmu.lock().register(msg.as_ref(), true);
+
+        assert_eq!(block_on!(ctx.sys_recvmsg(second, msg_ptr, 0)), Ok(6));
+
+        let mmu = mmu.lock();
+
+        assert_eq!(&mmu.import::<[u8; 2]>(head_ptr).unwrap(), b"ab");
+        assert_eq!(&mmu.import::<[u8; 4]>(tail_ptr).unwrap(), b"cdef");
+
+        let received = mmu.import::<RightsMessage>(control_ptr).unwrap();
+        assert_eq!(received.header.ty, SCM_RIGHTS);
+        assert_eq!(
+            received.header.len,
+            ControlMessageHeader::SIZE + size_of::<i32>()
+        );
+
+        let installed = ctx.file_of(received.fds[0] as usize).unwrap();
+        assert!(Arc::ptr_eq(&installed, &passed));
+    }
+
+    #[test]
+    fn test_control_truncated() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let (first, second) = socket_pair(&ctx);
+
+        let (sender, _) = ctx.socket_of(first).unwrap();
+        sender
+            .send(b"x", vec![ctx.file_of(first).unwrap()], None)
+            .unwrap();
+
+        let buf = Box::new([0u8; 1]);
+        let iov = Box::new(IoVector {
+            base: mmu.lock().register(buf.as_ref(), true),
+            len: 1,
+        });
+
+        let msg = Box::new(MessageHeader {
+            name: VirtualAddress::null(),
+            name_len: 0,
+            iov: mmu.lock().register(iov.as_ref(), false),
+            iov_len: 1,
+            control: VirtualAddress::null(),
+            control_len: 0,
+            flags: 0,
+        });
+        let msg_ptr = mmu.lock().register(msg.as_ref(), true);
+
+        assert_eq!(block_on!(ctx.sys_recvmsg(second, msg_ptr, 0)), Ok(1));
+
+        let header = mmu.lock().import::<MessageHeader>(msg_ptr).unwrap();
+
+        assert_eq!(header.flags, MessageFlags::MSG_CTRUNC.bits() as i32);
+        assert_eq!(header.control_len, 0);
+    }
+}
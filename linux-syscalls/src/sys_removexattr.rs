@@ -0,0 +1,95 @@
+use address::VirtualAddress;
+use alloc::sync::Arc;
+use filesystem_abstractions::{AccessMode, DirectoryTreeNode};
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Removes the extended attribute `name`, following symbolic links.
+    pub fn sys_removexattr(&self, pathname: VirtualAddress, name: VirtualAddress) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, 0, &self.credentials())?;
+
+        self.removexattr_internal(&node, name)
+    }
+
+    /// The same as [`SyscallContext::sys_removexattr`] on a symbolic link itself.
+    pub fn sys_lremovexattr(
+        &self,
+        pathname: VirtualAddress,
+        name: VirtualAddress,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, AT_SYMLINK_NOFOLLOW, &self.credentials())?;
+
+        self.removexattr_internal(&node, name)
+    }
+
+    pub fn sys_fremovexattr(&self, fd: usize, name: VirtualAddress) -> SyscallResult {
+        let node = self.xattr_node_of(fd)?;
+
+        self.removexattr_internal(&node, name)
+    }
+
+    fn removexattr_internal(
+        &self,
+        node: &Arc<DirectoryTreeNode>,
+        name: VirtualAddress,
+    ) -> SyscallResult {
+        let name = self.read_xattr_name(name)?;
+
+        node.check_xattr(&name, &self.credentials(), AccessMode::WRITE)
+            .and_then(|_| node.remove_xattr(&name))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use constants::ErrNo;
+    use filesystem_abstractions::{Credentials, FileSystemError, XattrFlags};
+
+    use crate::fs::tests::{own, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_remove() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+        file.set_xattr("user.tag", b"x", XattrFlags::empty())
+            .unwrap();
+
+        let path = UserPath::new(&ctx, "file");
+        let name = UserPath::new(&ctx, "user.tag");
+
+        assert_eq!(ctx.sys_removexattr(path.addr(), name.addr()), Ok(0));
+        assert_eq!(
+            file.get_xattr("user.tag"),
+            Err(FileSystemError::NoAttribute)
+        );
+        assert_eq!(
+            ctx.sys_removexattr(path.addr(), name.addr()),
+            Err(ErrNo::NoDataAvailable)
+        );
+    }
+
+    #[test]
+    fn test_others_can_not() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+        file.set_xattr("security.label", b"x", XattrFlags::empty())
+            .unwrap();
+
+        let path = UserPath::new(&ctx, "file");
+        let name = UserPath::new(&ctx, "security.label");
+
+        assert_eq!(
+            ctx.sys_removexattr(path.addr(), name.addr()),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(file.get_xattr("security.label").unwrap(), b"x");
+    }
+}
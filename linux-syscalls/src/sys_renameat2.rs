@@ -0,0 +1,168 @@
+use address::VirtualAddress;
+use alloc::sync::Arc;
+use constants::ErrNo;
+use filesystem_abstractions::{AccessMode, DirectoryEntryType, DirectoryTreeNode, FileSystemError};
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Fail with `EEXIST` instead of replacing the new path
+const RENAME_NOREPLACE: usize = 1;
+
+impl SyscallContext {
+    /// Renames `oldpath` to `newpath`, replacing what is there unless `RENAME_NOREPLACE` is given.
+    ///
+    /// The directory tree only renames inside a directory, moving to another one fails with
+    /// `EXDEV` so callers fall back to copying.
+    pub fn sys_renameat2(
+        &self,
+        olddirfd: isize,
+        oldpath: VirtualAddress,
+        newdirfd: isize,
+        newpath: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        // Neither exchanging nor leaving whiteouts is supported
+        if flags & !RENAME_NOREPLACE != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let credentials = self.credentials();
+        let (old_parent, old_name) = self.lookup_parent_at(olddirfd, oldpath, &credentials)?;
+        let (new_parent, new_name) = self.lookup_parent_at(newdirfd, newpath, &credentials)?;
+
+        if !Arc::ptr_eq(&old_parent, &new_parent) {
+            return Err(ErrNo::InvalidCrossDeviceLink);
+        }
+
+        old_parent
+            .check_delete(&old_name, &credentials)
+            .map_err(|e| e.to_errno())?;
+
+        let source = old_parent.open_child(&old_name).map_err(|e| e.to_errno())?;
+
+        match new_parent.open_child(&new_name) {
+            Ok(_) if flags & RENAME_NOREPLACE != 0 => return Err(ErrNo::FileExists),
+            Ok(target) => {
+                new_parent
+                    .check_delete(&new_name, &credentials)
+                    .map_err(|e| e.to_errno())?;
+
+                let is_directory = |node: &Arc<DirectoryTreeNode>| {
+                    node.metadata().entry_type == DirectoryEntryType::Directory
+                };
+
+                match (is_directory(&source), is_directory(&target)) {
+                    (true, false) => return Err(ErrNo::NotADirectory),
+                    (false, true) => return Err(ErrNo::IsADirectory),
+                    _ => (),
+                }
+            }
+            Err(FileSystemError::NotFound) => new_parent
+                .check_access(&credentials, AccessMode::WRITE | AccessMode::EXECUTE)
+                .map_err(|e| e.to_errno())?,
+            Err(e) => return Err(e.to_errno()),
+        }
+
+        if old_name == new_name {
+            return Ok(0);
+        }
+
+        old_parent
+            .rename(&old_name, &new_name)
+            .map_err(|e| e.to_errno())?;
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::{
+        tests::{own, setup_fs_context, UserPath},
+        AT_FDCWD,
+    };
+
+    fn rename(ctx: &SyscallContext, old: &str, new: &str, flags: usize) -> SyscallResult {
+        let old = UserPath::new(ctx, old);
+        let new = UserPath::new(ctx, new);
+
+        ctx.sys_renameat2(AT_FDCWD, old.addr(), AT_FDCWD, new.addr(), flags)
+    }
+
+    #[test]
+    fn test_rename() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        root.touch("old").unwrap().writeat(0, b"data").unwrap();
+        root.touch("existing").unwrap();
+
+        assert_eq!(rename(&ctx, "old", "new", 0), Ok(0));
+        assert_eq!(root.open_child("new").unwrap().readall().unwrap(), b"data");
+        assert_eq!(
+            root.open_child("old").err(),
+            Some(FileSystemError::NotFound)
+        );
+
+        assert_eq!(
+            rename(&ctx, "new", "existing", RENAME_NOREPLACE),
+            Err(ErrNo::FileExists)
+        );
+        assert_eq!(rename(&ctx, "new", "existing", 0), Ok(0));
+        assert_eq!(
+            root.open_child("existing").unwrap().readall().unwrap(),
+            b"data"
+        );
+
+        assert_eq!(rename(&ctx, "existing", "existing", 0), Ok(0));
+        assert_eq!(
+            rename(&ctx, "missing", "new", 0),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+        assert_eq!(
+            rename(&ctx, "existing", "new", 2),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_rename_types_and_directories() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        root.mkdir("dir").unwrap();
+        root.touch("file").unwrap();
+
+        assert_eq!(rename(&ctx, "dir", "file", 0), Err(ErrNo::NotADirectory));
+        assert_eq!(rename(&ctx, "file", "dir", 0), Err(ErrNo::IsADirectory));
+        assert_eq!(
+            rename(&ctx, "file", "/dir/file", 0),
+            Err(ErrNo::InvalidCrossDeviceLink)
+        );
+    }
+
+    #[test]
+    fn test_rename_checks_permissions() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let tmp = root.mkdir("tmp").unwrap();
+        own(&tmp, 0, 0o1777);
+        own(&tmp.touch("theirs").unwrap(), 2000, 0o666);
+        own(&tmp.touch("mine").unwrap(), 1000, 0o644);
+
+        // Neither out of nor over a file of someone else in a sticky directory
+        assert_eq!(
+            rename(&ctx, "/tmp/theirs", "/tmp/stolen", 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            rename(&ctx, "/tmp/mine", "/tmp/theirs", 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(rename(&ctx, "/tmp/mine", "/tmp/renamed", 0), Ok(0));
+
+        own(&tmp, 2000, 0o755);
+        assert_eq!(
+            rename(&ctx, "/tmp/renamed", "/tmp/again", 0),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use abstractions::IUsizeAlias;
+use address::VirtualAddress;
+use alloc::vec::Vec;
+use constants::ErrNo;
+use ipc::{IIpcObject, SEMMNI, SEMMSL, SEMOPM, SEMVMX};
+
+use crate::{
+    sysv_ipc::{IpcPerm, IPC_INFO, IPC_RMID, IPC_SET, IPC_STAT},
+    SyscallContext, SyscallResult,
+};
+
+/// The last process that operated on the semaphore
+const GETPID: usize = 11;
+const GETVAL: usize = 12;
+const GETALL: usize = 13;
+/// How many wait for the semaphore to grow
+const GETNCNT: usize = 14;
+/// How many wait for the semaphore to be zero
+const GETZCNT: usize = 15;
+const SETVAL: usize = 16;
+const SETALL: usize = 17;
+/// `IPC_STAT` returning the id
+const SEM_STAT: usize = 18;
+/// `IPC_INFO` with what is in use rather than the limits
+const SEM_INFO: usize = 19;
+
+/// `struct semid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SemidDs {
+    pub sem_perm: IpcPerm,
+    pub sem_otime: i64,
+    pub sem_ctime: i64,
+    pub sem_nsems: u64,
+    pub unused: [u64; 2],
+}
+
+/// `struct seminfo`, the limits reported by `IPC_INFO`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SemInfo {
+    pub semmap: i32,
+    pub semmni: i32,
+    pub semmns: i32,
+    pub semmnu: i32,
+    pub semmsl: i32,
+    pub semopm: i32,
+    pub semume: i32,
+    pub semusz: i32,
+    pub semvmx: i32,
+    pub semaem: i32,
+}
+
+impl SyscallContext {
+    /// Reports, changes or removes the System V semaphore set `semid`, or reads or sets the
+    /// values of its semaphores.
+    ///
+    /// `arg` is `union semun` passed by value, the value of `SETVAL` or a pointer for the
+    /// others. Setting a value drops what `SEM_UNDO` would add back to it.
+    pub fn sys_semctl(&self, semid: usize, semnum: usize, cmd: usize, arg: usize) -> SyscallResult {
+        let semaphores = self.kernel.semaphores();
+        let sets = semaphores.sets();
+        let buf = VirtualAddress::from_usize(arg);
+
+        if cmd == IPC_INFO || cmd == SEM_INFO {
+            let mut info = SemInfo {
+                semmap: SEMMNI as i32,
+                semmni: SEMMNI as i32,
+                semmns: (SEMMNI * SEMMSL) as i32,
+                semmnu: SEMMNI as i32,
+                semmsl: SEMMSL as i32,
+                semopm: SEMOPM as i32,
+                semume: SEMOPM as i32,
+                semusz: 20,
+                semvmx: SEMVMX,
+                semaem: SEMVMX,
+            };
+
+            if cmd == SEM_INFO {
+                let objects = sets.objects();
+
+                info.semusz = objects.len() as i32;
+                info.semaem = objects.iter().map(|(_, set)| set.len() as i32).sum();
+            }
+
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .export(buf, info)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            return Ok(sets.highest_id().unwrap_or(0) as isize);
+        }
+
+        let set = sets.find(semid).ok_or(ErrNo::InvalidArgument)?;
+        let credentials = self.credentials();
+        let pid = self.task.process().pid();
+        let now = self.kernel.time().tv_sec;
+
+        match cmd {
+            IPC_SET | IPC_RMID => {
+                if !set.permissions().can_change(&credentials) {
+                    return Err(ErrNo::OperationNotPermitted);
+                }
+
+                match cmd {
+                    IPC_SET => {
+                        let ds = self
+                            .task
+                            .process()
+                            .mmu()
+                            .lock()
+                            .import::<SemidDs>(buf)
+                            .map_err(|_| ErrNo::BadAddress)?;
+
+                        let mut status = set.status().lock();
+                        let perm = ds.sem_perm;
+
+                        status.permissions.set(perm.uid, perm.gid, perm.mode);
+                        status.change_time = now;
+                    }
+                    _ => {
+                        semaphores.remove_set(semid);
+                    }
+                }
+
+                Ok(0)
+            }
+            SETVAL | SETALL => {
+                if !set.permissions().allows(&credentials, 0o222) {
+                    return Err(ErrNo::PermissionDenied);
+                }
+
+                match cmd {
+                    SETVAL => {
+                        set.set_value(semnum, arg as i32, pid, now)?;
+                        semaphores.forget_adjustments(semid, Some(semnum));
+                    }
+                    _ => {
+                        let values = {
+                            let mmu = self.task.process().mmu();
+                            let mmu = mmu.lock();
+
+                            (0..set.len())
+                                .map(|i| mmu.import::<u16>(buf + i * size_of::<u16>()))
+                                .collect::<Result<Vec<_>, _>>()
+                                .map_err(|_| ErrNo::BadAddress)?
+                        };
+
+                        set.set_values(&values, pid, now)?;
+                        semaphores.forget_adjustments(semid, None);
+                    }
+                }
+
+                Ok(0)
+            }
+            IPC_STAT | SEM_STAT | GETALL | GETPID | GETVAL | GETNCNT | GETZCNT => {
+                if !set.permissions().allows(&credentials, 0o444) {
+                    return Err(ErrNo::PermissionDenied);
+                }
+
+                let mmu = self.task.process().mmu();
+                let mmu = mmu.lock();
+
+                match cmd {
+                    IPC_STAT | SEM_STAT => {
+                        let status = *set.status().lock();
+                        let ds = SemidDs {
+                            sem_perm: status.permissions.into(),
+                            sem_otime: status.operation_time,
+                            sem_ctime: status.change_time,
+                            sem_nsems: set.len() as u64,
+                            ..Default::default()
+                        };
+
+                        mmu.export(buf, ds).map_err(|_| ErrNo::BadAddress)?;
+
+                        match cmd {
+                            SEM_STAT => Ok(semid as isize),
+                            _ => Ok(0),
+                        }
+                    }
+                    GETALL => {
+                        for (i, value) in set.values().into_iter().enumerate() {
+                            mmu.export(buf + i * size_of::<u16>(), value)
+                                .map_err(|_| ErrNo::BadAddress)?;
+                        }
+
+                        Ok(0)
+                    }
+                    _ => {
+                        let semaphore = set.semaphore(semnum).ok_or(ErrNo::InvalidArgument)?;
+                        let (ncnt, zcnt) = set.waiting(semnum);
+
+                        match cmd {
+                            GETPID => Ok(semaphore.pid as isize),
+                            GETVAL => Ok(semaphore.value as isize),
+                            GETNCNT => Ok(ncnt as isize),
+                            _ => Ok(zcnt as isize),
+                        }
+                    }
+                }
+            }
+            _ => Err(ErrNo::InvalidArgument),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+    use ipc::SemaphoreOperation;
+
+    use super::*;
+    use crate::{
+        fs::tests::setup_fs_context,
+        sys_semop::tests::{create_set, op, semop},
+    };
+
+    fn stat(ctx: &SyscallContext, semid: usize) -> Result<SemidDs, ErrNo> {
+        let ds = Box::new(SemidDs::default());
+        let ptr = ctx.task.process().mmu().lock().register(ds.as_ref(), true);
+
+        ctx.sys_semctl(semid, 0, IPC_STAT, ptr.as_usize())
+            .map(|_| *ds)
+    }
+
+    #[test]
+    fn test_values() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 3);
+
+        assert_eq!(ctx.sys_semctl(id, 1, SETVAL, 7), Ok(0));
+        assert_eq!(ctx.sys_semctl(id, 1, GETVAL, 0), Ok(7));
+        assert_eq!(
+            ctx.sys_semctl(id, 1, GETPID, 0),
+            Ok(ctx.task.process().pid() as isize)
+        );
+        assert_eq!(
+            ctx.sys_semctl(id, 3, GETVAL, 0),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_semctl(id, 0, SETVAL, SEMVMX as usize + 1),
+            Err(ErrNo::NumericalResultOutOfRange)
+        );
+
+        let values = Box::new([1u16, 2, 3]);
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(values.as_ref(), true);
+        assert_eq!(ctx.sys_semctl(id, 0, SETALL, ptr.as_usize()), Ok(0));
+
+        let all = Box::new([0u16; 3]);
+        let ptr = ctx.task.process().mmu().lock().register(all.as_ref(), true);
+        assert_eq!(ctx.sys_semctl(id, 0, GETALL, ptr.as_usize()), Ok(0));
+        assert_eq!(*all, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_set_value_forgets_undo() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 1);
+
+        let undo = SemaphoreOperation::SEM_UNDO;
+        assert_eq!(semop(&ctx, id, &[op(0, 2, undo)], None), Ok(0));
+        assert_eq!(ctx.sys_semctl(id, 0, SETVAL, 5), Ok(0));
+
+        let semaphores = ctx.kernel.semaphores();
+        semaphores.release_process(ctx.task.process().pid());
+        assert_eq!(ctx.sys_semctl(id, 0, GETVAL, 0), Ok(5));
+    }
+
+    #[test]
+    fn test_stat_and_remove() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = ctx.sys_semget(42, 2, 0o1000 | 0o640).unwrap() as usize;
+
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!(ds.sem_perm.key, 42);
+        assert_eq!(ds.sem_perm.mode, 0o640);
+        assert_eq!(ds.sem_nsems, 2);
+        assert_eq!(ds.sem_otime, 0);
+
+        let info = Box::new(SemInfo::default());
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(info.as_ref(), true);
+        assert_eq!(
+            ctx.sys_semctl(0, 0, SEM_INFO, ptr.as_usize()),
+            Ok(id as isize)
+        );
+        assert_eq!((info.semusz, info.semaem), (1, 2));
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(stat(&ctx, id), Err(ErrNo::PermissionDenied));
+        assert_eq!(
+            ctx.sys_semctl(id, 0, IPC_RMID, 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+
+        *ctx.task.process().credentials().lock() = Credentials::root();
+        assert_eq!(ctx.sys_semctl(id, 0, IPC_RMID, 0), Ok(0));
+        assert_eq!(stat(&ctx, id), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_semget(42, 2, 0), Err(ErrNo::NoSuchFileOrDirectory));
+    }
+}
@@ -0,0 +1,85 @@
+use constants::ErrNo;
+use ipc::{IpcGetFlags, SemaphoreSet, SEMMSL};
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Returns the id of the System V semaphore set with `key`, creating one of `nsems`
+    /// semaphores if `semflg` asks for it, with the permissions in its lower bits.
+    pub fn sys_semget(&self, key: usize, nsems: usize, semflg: usize) -> SyscallResult {
+        if nsems > SEMMSL {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let key = key as i32;
+        let flags = IpcGetFlags::from_bits_truncate(semflg as u32);
+        let now = self.kernel.time().tv_sec;
+
+        let id = self.kernel.semaphores().sets().get(
+            key,
+            flags,
+            semflg as u32 & 0o777,
+            &self.credentials(),
+            |set| match nsems > set.len() {
+                true => Err(ErrNo::InvalidArgument),
+                false => Ok(()),
+            },
+            |permissions| match nsems {
+                0 => Err(ErrNo::InvalidArgument),
+                _ => Ok(SemaphoreSet::new(permissions, nsems, now)),
+            },
+        )?;
+
+        Ok(id as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+    use ipc::IPC_PRIVATE;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    const IPC_CREAT: usize = 0o1000;
+    const IPC_EXCL: usize = 0o2000;
+
+    #[test]
+    fn test_get_by_key() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        assert_eq!(
+            ctx.sys_semget(42, 2, 0o600),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+
+        let id = ctx.sys_semget(42, 2, IPC_CREAT | 0o600).unwrap();
+        assert_eq!(ctx.sys_semget(42, 0, 0), Ok(id));
+        assert_eq!(ctx.sys_semget(42, 3, 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            ctx.sys_semget(42, 2, IPC_CREAT | IPC_EXCL | 0o600),
+            Err(ErrNo::FileExists)
+        );
+
+        let set = ctx.kernel.semaphores().sets().find(id as usize).unwrap();
+        assert_eq!(set.values(), [0, 0]);
+        assert_eq!(set.status().lock().permissions.mode, 0o600);
+
+        assert_ne!(ctx.sys_semget(IPC_PRIVATE as usize, 1, 0o600), Ok(id));
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        assert_eq!(
+            ctx.sys_semget(42, 0, IPC_CREAT | 0o600),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_semget(42, SEMMSL + 1, IPC_CREAT | 0o600),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use address::{IAddressBase, VirtualAddress};
+use alloc::vec::Vec;
+use constants::ErrNo;
+use ipc::{IIpcObject, SemaphoreOperation, SEMOPM};
+use timing::TimeSpec;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Does the `nsops` operations of `sops` on the System V semaphore set `semid` all at once,
+    /// waiting until they can be done.
+    pub async fn sys_semop(
+        &self,
+        semid: usize,
+        sops: VirtualAddress,
+        nsops: usize,
+    ) -> SyscallResult {
+        self.sys_semtimedop(semid, sops, nsops, VirtualAddress::null())
+            .await
+    }
+
+    /// Like `semop`, but gives up with `EAGAIN` once the relative `timeout` has passed, if it
+    /// is not null.
+    pub async fn sys_semtimedop(
+        &self,
+        semid: usize,
+        sops: VirtualAddress,
+        nsops: usize,
+        timeout: VirtualAddress,
+    ) -> SyscallResult {
+        if nsops == 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        if nsops > SEMOPM {
+            return Err(ErrNo::ArgumentListTooLong);
+        }
+
+        let (operations, timeout) = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let operations = (0..nsops)
+                .map(|i| {
+                    mmu.import::<SemaphoreOperation>(sops + i * size_of::<SemaphoreOperation>())
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            let timeout = match timeout.is_null() {
+                true => None,
+                false => Some(
+                    mmu.import::<TimeSpec>(timeout)
+                        .map_err(|_| ErrNo::BadAddress)?,
+                ),
+            };
+
+            (operations, timeout)
+        };
+
+        if let Some(timeout) = timeout {
+            Self::check_time_validity(timeout)?;
+        }
+
+        let semaphores = self.kernel.semaphores();
+        let set = semaphores
+            .sets()
+            .find(semid)
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        if operations.iter().any(|op| op.num as usize >= set.len()) {
+            return Err(ErrNo::FileTooLarge);
+        }
+
+        let requested = match operations.iter().any(SemaphoreOperation::alters) {
+            true => 0o222,
+            false => 0o444,
+        };
+
+        if !set.permissions().allows(&self.credentials(), requested) {
+            return Err(ErrNo::PermissionDenied);
+        }
+
+        let pid = self.task.process().pid();
+
+        let undo = operations
+            .iter()
+            .any(|op| op.flags & SemaphoreOperation::SEM_UNDO != 0)
+            .then(|| semaphores.undo_of(pid));
+
+        let operate = set.operate(
+            semid,
+            &operations,
+            pid,
+            undo.as_deref(),
+            self.kernel.time().tv_sec,
+        );
+
+        match timeout {
+            None => operate.await?,
+            Some(timeout) => {
+                let timer = self.kernel.timer();
+                let deadline = self.kernel.time() + timeout;
+
+                timer
+                    .timeout(deadline, operate)
+                    .await
+                    .map_err(|_| ErrNo::ResourceTemporarilyUnavailable)??
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+    use threading::block_on;
+
+    use super::*;
+    use crate::fs::tests::setup_fs_context;
+
+    /// A set of `count` semaphores that only root can use, with a private key.
+    pub fn create_set(ctx: &SyscallContext, count: usize) -> usize {
+        ctx.sys_semget(0, count, 0o1000 | 0o600).unwrap() as usize
+    }
+
+    pub fn op(num: u16, op: i16, flags: i16) -> SemaphoreOperation {
+        SemaphoreOperation { num, op, flags }
+    }
+
+    /// Does `operations` on `semid` from user memory, waiting at most `timeout`.
+    pub fn semop(
+        ctx: &SyscallContext,
+        semid: usize,
+        operations: &[SemaphoreOperation],
+        timeout: Option<TimeSpec>,
+    ) -> SyscallResult {
+        let operations = Box::<[SemaphoreOperation]>::from(operations);
+        let timeout = timeout.map(Box::new);
+
+        let mmu = ctx.task.process().mmu();
+        let sops = mmu.lock().register(&*operations, true);
+        let timeout = match &timeout {
+            Some(timeout) => mmu.lock().register(timeout.as_ref(), true),
+            None => VirtualAddress::null(),
+        };
+
+        block_on!(ctx.sys_semtimedop(semid, sops, operations.len(), timeout))
+    }
+
+    #[test]
+    fn test_operations() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 2);
+        let nowait = SemaphoreOperation::IPC_NOWAIT;
+
+        assert_eq!(semop(&ctx, id, &[op(0, 2, 0), op(1, 1, 0)], None), Ok(0));
+        assert_eq!(
+            semop(&ctx, id, &[op(0, -1, 0), op(1, -2, nowait)], None),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        let set = ctx.kernel.semaphores().sets().find(id).unwrap();
+        assert_eq!(set.values(), [2, 1]);
+        assert_eq!(set.semaphore(0).unwrap().pid, ctx.task.process().pid());
+    }
+
+    #[test]
+    fn test_times_out() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 1);
+
+        assert_eq!(
+            semop(&ctx, id, &[op(0, -1, 0)], Some(TimeSpec::new(0, 1000))),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+        assert_eq!(
+            semop(&ctx, id, &[op(0, 1, 0)], Some(TimeSpec::new(0, -1))),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            semop(&ctx, id, &[op(0, 0, 0)], Some(TimeSpec::new(1, 0))),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 1);
+
+        assert_eq!(semop(&ctx, id, &[], None), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            semop(&ctx, id, &[op(0, 1, 0); SEMOPM + 1], None),
+            Err(ErrNo::ArgumentListTooLong)
+        );
+        assert_eq!(
+            semop(&ctx, id, &[op(1, 1, 0)], None),
+            Err(ErrNo::FileTooLarge)
+        );
+        assert_eq!(
+            semop(&ctx, id + 1, &[op(0, 1, 0)], None),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 100);
+        assert_eq!(
+            semop(&ctx, id, &[op(0, 0, 0)], None),
+            Err(ErrNo::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_undone_on_exit() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+        let id = create_set(&ctx, 1);
+        let undo = SemaphoreOperation::SEM_UNDO;
+
+        assert_eq!(semop(&ctx, id, &[op(0, 3, undo)], None), Ok(0));
+
+        let semaphores = ctx.kernel.semaphores();
+        semaphores.release_process(ctx.task.process().pid());
+        assert_eq!(semaphores.sets().find(id).unwrap().values(), [0]);
+    }
+}
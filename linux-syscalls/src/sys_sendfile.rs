@@ -0,0 +1,118 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::TransferEnd;
+
+use crate::{transfer::transfer_when_ready, SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Copies up to `count` bytes from `in_fd` to `out_fd` without going through user space.
+    ///
+    /// `in_fd` is read at `*offset`, which is moved along, or at its file position if `offset` is
+    /// null. `out_fd` is always written at its file position.
+    pub async fn sys_sendfile(
+        &self,
+        out_fd: usize,
+        in_fd: usize,
+        offset: VirtualAddress,
+        count: usize,
+    ) -> SyscallResult {
+        let source = self.file_of(in_fd)?;
+        let sink = self.file_of(out_fd)?;
+
+        if !source.can_read() || !sink.can_write() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        if source.is_dir() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let mut source = TransferEnd::new(source, self.import_offset(offset)?);
+        let mut sink = TransferEnd::new(sink, None);
+
+        let moved = transfer_when_ready(&mut source, &mut sink, count, false).await?;
+
+        self.export_offset(offset, &source)?;
+
+        Ok(moved)
+    }
+}
+
+// The tests run on a scratch host directory, which only unix hosts have
+#[cfg(all(test, unix))]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use filesystem_abstractions::OpenFlags;
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::open_fd,
+        transfer::tests::{pipe_fds, setup_cached_context},
+    };
+
+    #[test]
+    fn test_file_to_pipe() {
+        let (ctx, root, _) = setup_cached_context();
+
+        let file = root.touch("file").unwrap();
+        file.writeat(0, b"hello, world").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        let (read_end, write_end) = pipe_fds(&ctx, OpenFlags::NONE);
+
+        let offset = Box::new(7i64);
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(offset.as_ref(), true);
+
+        assert_eq!(block_on!(ctx.sys_sendfile(write_end, fd, ptr, 100)), Ok(5));
+        assert_eq!(*offset, 12);
+
+        // The file position is used and moved without an offset
+        assert_eq!(
+            block_on!(ctx.sys_sendfile(write_end, fd, VirtualAddress::null(), 5)),
+            Ok(5)
+        );
+        assert_eq!(ctx.file_of(fd).unwrap().metadata().unwrap().offset(), 5);
+
+        let mut buf = [0; 16];
+        assert_eq!(ctx.file_of(read_end).unwrap().read(&mut buf), 10);
+        assert_eq!(&buf[..10], b"worldhello");
+    }
+
+    #[test]
+    fn test_pipe_to_host_file() {
+        let (ctx, root, host) = setup_cached_context();
+
+        let file = root.touch("file").unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_WRONLY) as usize;
+        let (read_end, write_end) = pipe_fds(&ctx, OpenFlags::O_NONBLOCK);
+
+        let null = VirtualAddress::null();
+
+        // Nothing to send yet
+        assert_eq!(
+            block_on!(ctx.sys_sendfile(fd, read_end, null, 16)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        ctx.file_of(write_end).unwrap().write(b"piped");
+        assert_eq!(block_on!(ctx.sys_sendfile(fd, read_end, null, 16)), Ok(5));
+
+        file.sync().unwrap();
+        assert_eq!(std::fs::read(host.host_path("/file")).unwrap(), b"piped");
+
+        // Reading the end of a pipe nothing writes to anymore
+        ctx.sys_close(write_end).unwrap();
+        assert_eq!(block_on!(ctx.sys_sendfile(fd, read_end, null, 16)), Ok(0));
+
+        assert_eq!(
+            block_on!(ctx.sys_sendfile(read_end, fd, null, 16)),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    socket::{wait_for_socket, ControlMessageHeader, MessageHeader},
+    SyscallContext, SyscallResult,
+};
+use address::{IAddressBase, VirtualAddress};
+use alloc::{sync::Arc, vec::Vec};
+use constants::ErrNo;
+use filesystem_abstractions::IFile;
+use socket_abstractions::{MessageFlags, SCM_RIGHTS, SOL_SOCKET};
+
+impl SyscallContext {
+    pub async fn sys_sendmsg(
+        &self,
+        sockfd: usize,
+        msg: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_sendmsg: sockfd: {}, msg: {}, flags: {:#x}",
+            sockfd,
+            msg,
+            flags
+        );
+
+        let flags = MessageFlags::from_bits_truncate(flags);
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+
+        let header = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<MessageHeader>(msg)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        let destination = match header.name.is_null() {
+            true => None,
+            false => Some(self.read_socket_address(header.name, header.name_len as usize)?),
+        };
+
+        let data = self.gather(header.iov, header.iov_len)?;
+        let rights = self.read_rights(header.control, header.control_len)?;
+
+        let nonblocking = nonblocking || flags.contains(MessageFlags::MSG_DONTWAIT);
+
+        wait_for_socket(socket.as_ref(), nonblocking, || {
+            socket.send(&data, rights.clone(), destination.as_ref())
+        })
+        .await
+        .map(|sent| sent as isize)
+    }
+
+    fn gather(&self, iov: VirtualAddress, iov_len: usize) -> Result<Vec<u8>, ErrNo> {
+        let vectors = self.read_io_vectors(iov, iov_len)?;
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        let mut data = Vec::new();
+
+        for vector in vectors.iter().filter(|v| v.len != 0) {
+            let buf = mmu
+                .map_buffer(vector.base, vector.len)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            data.extend_from_slice(&buf);
+        }
+
+        Ok(data)
+    }
+
+    /// Collects the files of all `SCM_RIGHTS` messages in the control buffer.
+    fn read_rights(
+        &self,
+        control: VirtualAddress,
+        control_len: usize,
+    ) -> Result<Vec<Arc<dyn IFile>>, ErrNo> {
+        let mut rights = Vec::new();
+        let mut offset = 0;
+
+        while offset + ControlMessageHeader::SIZE <= control_len {
+            let (header, fds) = {
+                let mmu = self.task.process().mmu();
+                let mmu = mmu.lock();
+
+                let header = mmu
+                    .import::<ControlMessageHeader>(control + offset)
+                    .map_err(|_| ErrNo::BadAddress)?;
+
+                if header.len < ControlMessageHeader::SIZE || offset + header.len > control_len {
+                    return Err(ErrNo::InvalidArgument);
+                }
+
+                if header.level != SOL_SOCKET as i32 || header.ty != SCM_RIGHTS {
+                    return Err(ErrNo::InvalidArgument);
+                }
+
+                let count = (header.len - ControlMessageHeader::SIZE) / size_of::<i32>();
+                let data = control + offset + ControlMessageHeader::SIZE;
+
+                let fds = (0..count)
+                    .map(|i| {
+                        mmu.import::<i32>(data + i * size_of::<i32>())
+                            .map_err(|_| ErrNo::BadAddress)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                (header, fds)
+            };
+
+            for fd in fds {
+                let fd = usize::try_from(fd).map_err(|_| ErrNo::BadFileDescriptor)?;
+
+                rights.push(self.file_of(fd)?);
+            }
+
+            offset += ControlMessageHeader::align(header.len);
+        }
+
+        Ok(rights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::FileDescriptorTable;
+    use socket_abstractions::{SocketType, AF_UNIX};
+    use threading::block_on;
+
+    use super::*;
+    use crate::socket::{tests::setup_socket_context, IoVector};
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct RightsMessage {
+        header: ControlMessageHeader,
+        fds: [i32; 2],
+    }
+
+    #[test]
+    fn test_gathers_and_passes_rights() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let sv = Box::new([-1i32; 2]);
+        let sv_ptr = mmu.lock().register(sv.as_ref(), true);
+
+        ctx.sys_socketpair(AF_UNIX as usize, SocketType::Stream as usize, 0, sv_ptr)
+            .unwrap();
+        let [first, second] = mmu.lock().import::<[i32; 2]>(sv_ptr).unwrap();
+
+        let (hello, world) = (b"hello ", b"world");
+        let hello_ptr = mmu.lock().register(hello, false);
+        let world_ptr = mmu.lock().register(world, false);
+
+        let iov = Box::new([
+            IoVector {
+                base: hello_ptr,
+                len: hello.len(),
+            },
+            IoVector {
+                base: world_ptr,
+                len: world.len(),
+            },
+        ]);
+        let iov_ptr = mmu.lock().register(iov.as_ref(), false);
+
+        let control = Box::new(RightsMessage {
+            header: ControlMessageHeader {
+                len: ControlMessageHeader::SIZE + 2 * size_of::<i32>(),
+                level: SOL_SOCKET as i32,
+                ty: SCM_RIGHTS,
+            },
+            fds: [first, first],
+        });
+        let control_ptr = mmu.lock().register(control.as_ref(), false);
+
+        let msg = Box::new(MessageHeader {
+            name: VirtualAddress::null(),
+            name_len: 0,
+            iov: iov_ptr,
+            iov_len: 2,
+            control: control_ptr,
+            control_len: size_of::<RightsMessage>(),
+            flags: 0,
+        });
+        let msg_ptr = mmu.lock().register(msg.as_ref(), false);
+
+        assert_eq!(
+            block_on!(ctx.sys_sendmsg(first as usize, msg_ptr, 0)),
+            Ok(11)
+        );
+
+        let (receiver, _) = ctx.socket_of(second as usize).unwrap();
+        let mut buf = [0u8; 11];
+        let message = receiver.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        assert_eq!(&buf, b"hello world");
+        assert_eq!(message.rights.len(), 2);
+    }
+
+    #[test]
+    fn test_bad_fd_in_rights() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let control = Box::new(RightsMessage {
+            header: ControlMessageHeader {
+                len: ControlMessageHeader::SIZE + 2 * size_of::<i32>(),
+                level: SOL_SOCKET as i32,
+                ty: SCM_RIGHTS,
+            },
+            fds: [fd as i32, FileDescriptorTable::MAX_SIZE as i32],
+        });
+
+        let msg = Box::new(MessageHeader {
+            name: VirtualAddress::null(),
+            name_len: 0,
+            iov: VirtualAddress::null(),
+            iov_len: 0,
+            control: mmu.lock().register(control.as_ref(), false),
+            control_len: size_of::<RightsMessage>(),
+            flags: 0,
+        });
+        let msg_ptr = mmu.lock().register(msg.as_ref(), false);
+
+        assert_eq!(
+            block_on!(ctx.sys_sendmsg(fd, msg_ptr, 0)),
+            Err(ErrNo::BadFileDescriptor)
+        );
+    }
+}
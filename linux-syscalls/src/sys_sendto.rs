@@ -0,0 +1,137 @@
+use crate::{socket::wait_for_socket, SyscallContext, SyscallResult};
+use address::{IAddressBase, VirtualAddress};
+use alloc::vec::Vec;
+use constants::ErrNo;
+use socket_abstractions::MessageFlags;
+
+impl SyscallContext {
+    pub async fn sys_sendto(
+        &self,
+        sockfd: usize,
+        buf: VirtualAddress,
+        len: usize,
+        flags: usize,
+        dest_addr: VirtualAddress,
+        addrlen: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_sendto: sockfd: {}, buf: {}, len: {}, flags: {:#x}, dest_addr: {}, addrlen: {}",
+            sockfd,
+            buf,
+            len,
+            flags,
+            dest_addr,
+            addrlen
+        );
+
+        let flags = MessageFlags::from_bits_truncate(flags);
+        let (socket, nonblocking) = self.socket_of(sockfd)?;
+
+        let destination = match dest_addr.is_null() {
+            true => None,
+            false => Some(self.read_socket_address(dest_addr, addrlen)?),
+        };
+
+        let data = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            let data = mmu.map_buffer(buf, len).map_err(|_| ErrNo::BadAddress)?;
+
+            data.to_vec()
+        };
+
+        let nonblocking = nonblocking || flags.contains(MessageFlags::MSG_DONTWAIT);
+
+        wait_for_socket(socket.as_ref(), nonblocking, || {
+            socket.send(&data, Vec::new(), destination.as_ref())
+        })
+        .await
+        .map(|sent| sent as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socket_abstractions::{ISocket, SocketAddress, SocketType, UnixAddress, AF_UNIX};
+    use threading::block_on;
+    use unix_socket::UnixSocket;
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_sendto_destination() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let receiver = UnixSocket::new(SocketType::Datagram, ctx.kernel.unix_sockets());
+        let address = SocketAddress::Unix(UnixAddress::Pathname("/tmp/receiver".into()));
+        receiver.bind(&address).unwrap();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let data = b"datagram";
+        let addr = address.to_bytes();
+        let data_ptr = mmu.lock().register(data, false);
+        let addr_ptr = mmu.lock().register(addr.as_slice(), false);
+
+        let ret = block_on!(ctx.sys_sendto(fd, data_ptr, data.len(), 0, addr_ptr, addr.len()));
+        assert_eq!(ret, Ok(data.len() as isize));
+
+        let mut buf = [0u8; 16];
+        let message = receiver.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        assert_eq!(&buf[..message.length], data);
+    }
+
+    #[test]
+    fn test_destination_required() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Datagram as usize, 0)
+            .unwrap() as usize;
+
+        let data = b"lost";
+        let data_ptr = mmu.lock().register(data, false);
+
+        let ret = block_on!(ctx.sys_sendto(fd, data_ptr, data.len(), 0, VirtualAddress::null(), 0));
+
+        assert_eq!(ret, Err(ErrNo::DestinationAddressRequired));
+    }
+
+    #[test]
+    fn test_dontwait_on_full_buffer() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let sv = alloc::boxed::Box::new([-1i32; 2]);
+        let sv_ptr = mmu.lock().register(sv.as_ref(), true);
+
+        ctx.sys_socketpair(AF_UNIX as usize, SocketType::Stream as usize, 0, sv_ptr)
+            .unwrap();
+
+        let fd = mmu.lock().import::<[i32; 2]>(sv_ptr).unwrap()[0] as usize;
+
+        let data = alloc::vec![0u8; 64 * 1024];
+        let data_ptr = mmu.lock().register(data.as_slice(), false);
+
+        let send = |flags: MessageFlags| {
+            block_on!(ctx.sys_sendto(
+                fd,
+                data_ptr,
+                data.len(),
+                flags.bits(),
+                VirtualAddress::null(),
+                0
+            ))
+        };
+
+        assert_eq!(send(MessageFlags::empty()), Ok(data.len() as isize));
+        assert_eq!(
+            send(MessageFlags::MSG_DONTWAIT),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+}
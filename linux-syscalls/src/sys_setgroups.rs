@@ -0,0 +1,121 @@
+use address::VirtualAddress;
+use alloc::vec::Vec;
+use constants::ErrNo;
+use filesystem_abstractions::Credentials;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Writes up to `size` supplementary groups to `list`, a size of 0 only counts them.
+    pub fn sys_getgroups(&self, size: usize, list: VirtualAddress) -> SyscallResult {
+        let groups = self.task.process().credentials().lock().groups.clone();
+
+        if size == 0 {
+            return Ok(groups.len() as isize);
+        }
+
+        if size < groups.len() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let mmu = self.task.process().mmu();
+        let mmu = mmu.lock();
+
+        for (i, gid) in groups.iter().enumerate() {
+            mmu.export(list + i * size_of::<u32>(), *gid)
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        Ok(groups.len() as isize)
+    }
+
+    pub fn sys_setgroups(&self, size: usize, list: VirtualAddress) -> SyscallResult {
+        if size > Credentials::MAX_GROUPS {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let groups = {
+            let mmu = self.task.process().mmu();
+            let mmu = mmu.lock();
+
+            (0..size)
+                .map(|i| mmu.import::<u32>(list + i * size_of::<u32>()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ErrNo::BadAddress)?
+        };
+
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_groups(groups)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use memory_space::MemorySpace;
+    use test_utilities::{
+        allocation::segment::TestFrameAllocator, kernel::TestKernel, task::TestProcess,
+    };
+
+    use super::*;
+
+    fn setup_context(credentials: Credentials) -> SyscallContext {
+        let kernel = TestKernel::new().build();
+        let (alloc, mmu) = TestFrameAllocator::new_with_mmu();
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu, alloc)))
+            .with_credentials(credentials)
+            .build();
+
+        SyscallContext::new(task, kernel)
+    }
+
+    #[test]
+    fn test_set_and_get_groups() {
+        let ctx = setup_context(Credentials::root());
+        let mmu = ctx.task.process().mmu();
+
+        let groups = [10u32, 20, 30];
+        mmu.lock().register(&groups, false);
+        assert_eq!(
+            ctx.sys_setgroups(3, VirtualAddress::from_ref(&groups)),
+            Ok(0)
+        );
+
+        assert_eq!(ctx.sys_getgroups(0, VirtualAddress::null()), Ok(3));
+
+        let buffer = [0u32; 4];
+        mmu.lock().register(&buffer, true);
+        let buffer = VirtualAddress::from_ref(&buffer);
+
+        assert_eq!(ctx.sys_getgroups(2, buffer), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_getgroups(4, buffer), Ok(3));
+        assert_eq!(mmu.lock().import::<[u32; 3]>(buffer), Ok([10, 20, 30]));
+
+        // Dropping all of them
+        assert_eq!(ctx.sys_setgroups(0, VirtualAddress::null()), Ok(0));
+        assert_eq!(ctx.sys_getgroups(0, VirtualAddress::null()), Ok(0));
+    }
+
+    #[test]
+    fn test_set_groups_unprivileged() {
+        let ctx = setup_context(Credentials::new(1000, 100));
+
+        let groups = [0u32];
+        ctx.task.process().mmu().lock().register(&groups, false);
+
+        assert_eq!(
+            ctx.sys_setgroups(1, VirtualAddress::from_ref(&groups)),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            ctx.sys_setgroups(Credentials::MAX_GROUPS + 1, VirtualAddress::null()),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use constants::ErrNo;
+
+impl SyscallContext {
+    pub fn sys_setsockopt(
+        &self,
+        sockfd: usize,
+        level: usize,
+        optname: usize,
+        optval: VirtualAddress,
+        optlen: usize,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_setsockopt: sockfd: {}, level: {}, optname: {}, optval: {}, optlen: {}",
+            sockfd,
+            level,
+            optname,
+            optval,
+            optlen
+        );
+
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        if optlen < size_of::<i32>() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let value = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<i32>(optval)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        socket
+            .set_option(level, optname, value.max(0) as usize)
+            .map(|_| 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socket_abstractions::{SocketType, AF_UNIX, SOL_SOCKET, SO_RCVBUF};
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_set_receive_buffer() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let value = 4096i32;
+        let ptr = mmu.lock().register(&value, false);
+
+        assert_eq!(ctx.sys_setsockopt(fd, SOL_SOCKET, SO_RCVBUF, ptr, 4), Ok(0));
+
+        let (socket, _) = ctx.socket_of(fd).unwrap();
+        assert_eq!(socket.get_option(SOL_SOCKET, SO_RCVBUF), Ok(4096));
+    }
+
+    #[test]
+    fn test_unknown_level() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let value = 1i32;
+        let ptr = mmu.lock().register(&value, false);
+
+        assert_eq!(
+            ctx.sys_setsockopt(fd, 6, 1, ptr, 4),
+            Err(ErrNo::ProtocolNotAvailable)
+        );
+    }
+}
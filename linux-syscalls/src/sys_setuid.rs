@@ -0,0 +1,165 @@
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// An id argument, where -1 leaves the id as it is.
+fn optional_id(id: u32) -> Option<u32> {
+    match id {
+        u32::MAX => None,
+        id => Some(id),
+    }
+}
+
+fn required_id(id: u32) -> Result<u32, ErrNo> {
+    optional_id(id).ok_or(ErrNo::InvalidArgument)
+}
+
+impl SyscallContext {
+    pub fn sys_setuid(&self, uid: u32) -> SyscallResult {
+        let uid = required_id(uid)?;
+
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_uid(uid)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    pub fn sys_setgid(&self, gid: u32) -> SyscallResult {
+        let gid = required_id(gid)?;
+
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_gid(gid)
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    pub fn sys_setreuid(&self, ruid: u32, euid: u32) -> SyscallResult {
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_reuid(optional_id(ruid), optional_id(euid))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    pub fn sys_setregid(&self, rgid: u32, egid: u32) -> SyscallResult {
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_regid(optional_id(rgid), optional_id(egid))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    pub fn sys_setresuid(&self, ruid: u32, euid: u32, suid: u32) -> SyscallResult {
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_resuid(optional_id(ruid), optional_id(euid), optional_id(suid))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    pub fn sys_setresgid(&self, rgid: u32, egid: u32, sgid: u32) -> SyscallResult {
+        self.task
+            .process()
+            .credentials()
+            .lock()
+            .set_resgid(optional_id(rgid), optional_id(egid), optional_id(sgid))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+
+    /// Never fails, returns the previous filesystem uid whether it was changed or not.
+    pub fn sys_setfsuid(&self, fsuid: u32) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().set_fsuid(fsuid) as isize)
+    }
+
+    pub fn sys_setfsgid(&self, fsgid: u32) -> SyscallResult {
+        Ok(self.task.process().credentials().lock().set_fsgid(fsgid) as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+    use test_utilities::{kernel::TestKernel, task::TestProcess};
+
+    use super::*;
+
+    fn setup_context(credentials: Credentials) -> SyscallContext {
+        let kernel = TestKernel::new().build();
+        let (_, task) = TestProcess::new().with_credentials(credentials).build();
+
+        SyscallContext::new(task, kernel)
+    }
+
+    fn credentials(ctx: &SyscallContext) -> Credentials {
+        ctx.task.process().credentials().lock().clone()
+    }
+
+    #[test]
+    fn test_root_drops_privileges() {
+        let ctx = setup_context(Credentials::root());
+
+        assert_eq!(ctx.sys_setgid(100), Ok(0));
+        assert_eq!(ctx.sys_setuid(1000), Ok(0));
+
+        assert_eq!(credentials(&ctx), Credentials::new(1000, 100));
+
+        // Nothing to go back to
+        assert_eq!(ctx.sys_setuid(0), Err(ErrNo::OperationNotPermitted));
+        assert_eq!(ctx.sys_setgid(0), Err(ErrNo::OperationNotPermitted));
+    }
+
+    #[test]
+    fn test_minus_one() {
+        let ctx = setup_context(Credentials::root());
+
+        assert_eq!(ctx.sys_setuid(u32::MAX), Err(ErrNo::InvalidArgument));
+
+        assert_eq!(ctx.sys_setresuid(u32::MAX, 1000, u32::MAX), Ok(0));
+        let credentials = credentials(&ctx);
+        assert_eq!(
+            (credentials.uid, credentials.euid, credentials.suid),
+            (0, 1000, 0)
+        );
+    }
+
+    #[test]
+    fn test_temporarily_drop_privileges() {
+        let ctx = setup_context(Credentials::root());
+
+        assert_eq!(ctx.sys_setresgid(100, 100, u32::MAX), Ok(0));
+        assert_eq!(ctx.sys_setresuid(1000, 1000, u32::MAX), Ok(0));
+        assert_eq!(
+            ctx.sys_setresgid(u32::MAX, 200, u32::MAX),
+            Err(ErrNo::OperationNotPermitted)
+        );
+
+        // Root is still saved
+        assert_eq!(ctx.sys_setreuid(u32::MAX, 0), Ok(0));
+        assert_eq!(credentials(&ctx).euid, 0);
+        assert_eq!(ctx.sys_setregid(u32::MAX, 200), Ok(0));
+        assert_eq!(credentials(&ctx).egid, 200);
+    }
+
+    #[test]
+    fn test_filesystem_ids() {
+        let ctx = setup_context(Credentials::new(1000, 100));
+
+        assert_eq!(ctx.sys_setfsuid(0), Ok(1000));
+        assert_eq!(ctx.sys_setfsuid(u32::MAX), Ok(1000));
+        assert_eq!(ctx.sys_setfsgid(0), Ok(100));
+        assert_eq!(credentials(&ctx), Credentials::new(1000, 100));
+    }
+}
@@ -0,0 +1,177 @@
+use address::VirtualAddress;
+use alloc::{sync::Arc, vec};
+use constants::ErrNo;
+use filesystem_abstractions::{AccessMode, DirectoryTreeNode, XattrFlags, XATTR_SIZE_MAX};
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Sets the extended attribute `name` to the `size` bytes at `value`, following symbolic
+    /// links. `XATTR_CREATE` and `XATTR_REPLACE` in `flags` require it to be new or to exist.
+    pub fn sys_setxattr(
+        &self,
+        pathname: VirtualAddress,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+        flags: u32,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, 0, &self.credentials())?;
+
+        self.setxattr_internal(&node, name, value, size, flags)
+    }
+
+    /// The same as [`SyscallContext::sys_setxattr`] on a symbolic link itself.
+    pub fn sys_lsetxattr(
+        &self,
+        pathname: VirtualAddress,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+        flags: u32,
+    ) -> SyscallResult {
+        let node = self.lookup_at(AT_FDCWD, pathname, AT_SYMLINK_NOFOLLOW, &self.credentials())?;
+
+        self.setxattr_internal(&node, name, value, size, flags)
+    }
+
+    pub fn sys_fsetxattr(
+        &self,
+        fd: usize,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+        flags: u32,
+    ) -> SyscallResult {
+        let node = self.xattr_node_of(fd)?;
+
+        self.setxattr_internal(&node, name, value, size, flags)
+    }
+
+    fn setxattr_internal(
+        &self,
+        node: &Arc<DirectoryTreeNode>,
+        name: VirtualAddress,
+        value: VirtualAddress,
+        size: usize,
+        flags: u32,
+    ) -> SyscallResult {
+        let flags = XattrFlags::from_bits(flags).ok_or(ErrNo::InvalidArgument)?;
+
+        if size > XATTR_SIZE_MAX {
+            return Err(ErrNo::ArgumentListTooLong);
+        }
+
+        let name = self.read_xattr_name(name)?;
+
+        // An empty value may come with a null pointer
+        let mut bytes = vec![0; size];
+        if size != 0 {
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .read_bytes(value, &mut bytes)
+                .map_err(|_| ErrNo::BadAddress)?;
+        }
+
+        node.check_xattr(&name, &self.credentials(), AccessMode::WRITE)
+            .and_then(|_| node.set_xattr(&name, &bytes, flags))
+            .map(|_| 0)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use filesystem_abstractions::{Credentials, OpenFlags};
+
+    use super::*;
+    use crate::fs::tests::{open_fd, own, setup_fs_context, UserPath};
+
+    #[test]
+    fn test_create_and_replace() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+
+        let path = UserPath::new(&ctx, "file");
+        let name = UserPath::new(&ctx, "user.mime");
+        let value = UserPath::new(&ctx, "text");
+
+        let create = XattrFlags::XATTR_CREATE.bits();
+        let replace = XattrFlags::XATTR_REPLACE.bits();
+
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), name.addr(), value.addr(), 4, replace),
+            Err(ErrNo::NoDataAvailable)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), name.addr(), value.addr(), 4, create),
+            Ok(0)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), name.addr(), value.addr(), 2, create),
+            Err(ErrNo::FileExists)
+        );
+
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDONLY) as usize;
+        assert_eq!(
+            ctx.sys_fsetxattr(fd, name.addr(), value.addr(), 2, replace),
+            Ok(0)
+        );
+
+        assert_eq!(file.get_xattr("user.mime").unwrap(), b"te");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+        root.soft_link("link", "file").unwrap();
+
+        let path = UserPath::new(&ctx, "file");
+        let link = UserPath::new(&ctx, "link");
+        let user = UserPath::new(&ctx, "user.tag");
+        let trusted = UserPath::new(&ctx, "trusted.tag");
+        let unknown = UserPath::new(&ctx, "unknown.tag");
+        let empty = UserPath::new(&ctx, "");
+        let null = VirtualAddress::null();
+
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), user.addr(), null, 0, 0),
+            Err(ErrNo::PermissionDenied)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), trusted.addr(), null, 0, 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), unknown.addr(), null, 0, 0),
+            Err(ErrNo::OperationNotSupported)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), empty.addr(), null, 0, 0),
+            Err(ErrNo::NumericalResultOutOfRange)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), user.addr(), null, XATTR_SIZE_MAX + 1, 0),
+            Err(ErrNo::ArgumentListTooLong)
+        );
+        assert_eq!(
+            ctx.sys_setxattr(path.addr(), user.addr(), null, 0, 4),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        // No user attributes on the link itself
+        assert_eq!(
+            ctx.sys_lsetxattr(link.addr(), user.addr(), null, 0, 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use abstractions::IUsizeAlias;
+use address::{IAddressBase, IAlignableAddress, IToPageNum, VirtualAddress, VirtualPageNumRange};
+use constants::ErrNo;
+use ipc::IIpcObject;
+use memory_space::{AreaType, MapType, MappingArea};
+use mmu_abstractions::GenericMappingFlags;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Attaches the segment for reading only
+const SHM_RDONLY: usize = 0o10000;
+/// Rounds `shmaddr` down to a page instead of failing
+const SHM_RND: usize = 0o20000;
+/// Lets the segment be executed
+const SHM_EXEC: usize = 0o100000;
+
+impl SyscallContext {
+    /// Maps the System V shared memory segment `shmid` at `shmaddr`, or wherever there is room if
+    /// it is null, and returns where.
+    ///
+    /// Every process attaching the segment maps the same pages, and so does a child forked after
+    /// it. Replacing existing mappings with `SHM_REMAP` is not supported.
+    pub fn sys_shmat(&self, shmid: usize, shmaddr: VirtualAddress, shmflg: usize) -> SyscallResult {
+        let segments = self.kernel.shared_memory();
+        let segment = segments.find(shmid).ok_or(ErrNo::InvalidArgument)?;
+
+        let mut permissions = GenericMappingFlags::User | GenericMappingFlags::Readable;
+        let mut requested = 0o444;
+
+        if shmflg & SHM_RDONLY == 0 {
+            permissions |= GenericMappingFlags::Writable;
+            requested |= 0o222;
+        }
+
+        if shmflg & SHM_EXEC != 0 {
+            permissions |= GenericMappingFlags::Executable;
+            requested |= 0o111;
+        }
+
+        if !segment.permissions().allows(&self.credentials(), requested) {
+            return Err(ErrNo::PermissionDenied);
+        }
+
+        let mut addr = shmaddr;
+
+        if shmflg & SHM_RND != 0 {
+            addr = addr.page_down();
+        } else if !addr.is_page_aligned() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let len = segment.size().div_ceil(constants::PAGE_SIZE) * constants::PAGE_SIZE;
+
+        let process = self.task.process();
+        let mut mem = process.memory_space().lock();
+
+        if addr.is_null() {
+            addr = Self::sys_mmap_select_addr(&mut mem, addr, len);
+
+            if addr.is_null() {
+                return Err(ErrNo::CannotAllocateMemory);
+            }
+        }
+
+        let range = VirtualPageNumRange::from_start_end(
+            addr.to_floor_page_num(),
+            (addr + len).to_ceil_page_num(),
+        );
+
+        let overlapping = mem
+            .mappings()
+            .iter()
+            .any(|area| area.range().start() < range.end() && range.start() < area.range().end());
+
+        if overlapping {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        mem.map_shared_area(
+            MappingArea {
+                range,
+                area_type: AreaType::VMA,
+                map_type: MapType::Framed,
+                permissions,
+                allocation: None,
+            },
+            segment.attach(&segments, shmid),
+        );
+
+        let mut status = segment.status().lock();
+        status.attach_time = self.kernel.time().tv_sec;
+        status.last_pid = process.pid();
+
+        Ok(addr.as_usize() as isize)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use address::{IAddressBase, IPageNum};
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::sys_memfd_create::tests::{process_on, setup_memfd_context};
+
+    /// Creates a private segment of `size` bytes that anyone can read and write.
+    pub fn create_segment(ctx: &SyscallContext, size: usize) -> usize {
+        ctx.sys_shmget(0, size, 0o666).unwrap() as usize
+    }
+
+    pub fn attach(ctx: &SyscallContext, shmid: usize, shmflg: usize) -> SyscallResult {
+        ctx.sys_shmat(shmid, VirtualAddress::null(), shmflg)
+    }
+
+    pub fn write(ctx: &SyscallContext, addr: isize, bytes: &[u8]) {
+        let addr = VirtualAddress::from_usize(addr as usize);
+
+        ctx.task
+            .process()
+            .mmu()
+            .lock()
+            .write_bytes(addr, bytes)
+            .unwrap();
+    }
+
+    pub fn read(ctx: &SyscallContext, addr: isize, len: usize) -> Vec<u8> {
+        let addr = VirtualAddress::from_usize(addr as usize);
+        let mut buf = vec![0; len];
+
+        ctx.task
+            .process()
+            .mmu()
+            .lock()
+            .read_bytes(addr, &mut buf)
+            .unwrap();
+
+        buf
+    }
+
+    #[test]
+    fn test_shared_between_processes() {
+        let (ctx, alloc) = setup_memfd_context();
+        let other = process_on(ctx.kernel.clone(), &alloc);
+
+        let id = create_segment(&ctx, 5000);
+        let here = attach(&ctx, id, 0).unwrap();
+        let there = attach(&other, id, 0).unwrap();
+
+        // It starts zeroed, up to the end of its last page
+        assert_eq!(read(&other, there, 8192), vec![0; 8192]);
+
+        write(&ctx, here + 4094, b"shared");
+        assert_eq!(read(&other, there + 4094, 6), b"shared");
+
+        let segment = ctx.kernel.shared_memory().find(id).unwrap();
+        assert_eq!(segment.attaches(), 2);
+        assert_eq!(segment.status().lock().last_pid, other.task.process().pid());
+    }
+
+    #[test]
+    fn test_at_an_address() {
+        let (ctx, _) = setup_memfd_context();
+        let id = create_segment(&ctx, 4096);
+
+        let addr = VirtualAddress::from_usize(0x4000_0000);
+        assert_eq!(ctx.sys_shmat(id, addr + 12, 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.sys_shmat(id, addr + 12, SHM_RND), Ok(0x4000_0000));
+
+        // Taken by the first attach now
+        assert_eq!(ctx.sys_shmat(id, addr, 0), Err(ErrNo::InvalidArgument));
+
+        let area = ctx.task.process().memory_space().lock().mappings()[0].permissions();
+        assert!(area.contains(GenericMappingFlags::Writable));
+        assert!(!area.contains(GenericMappingFlags::Executable));
+    }
+
+    #[test]
+    fn test_checks_permissions() {
+        let (ctx, _) = setup_memfd_context();
+        let id = ctx.sys_shmget(0, 4096, 0o640).unwrap() as usize;
+
+        *ctx.task.process().credentials().lock() = Credentials::new(1000, 0);
+
+        assert_eq!(attach(&ctx, id, 0), Err(ErrNo::PermissionDenied));
+        assert_eq!(
+            attach(&ctx, id, SHM_RDONLY | SHM_EXEC),
+            Err(ErrNo::PermissionDenied)
+        );
+
+        assert_eq!(attach(&ctx, 42, 0), Err(ErrNo::InvalidArgument));
+
+        let addr = VirtualAddress::from_usize(attach(&ctx, id, SHM_RDONLY).unwrap() as usize);
+        let process = ctx.task.process();
+        let mem = process.memory_space().lock();
+        let area = mem
+            .mappings()
+            .iter()
+            .find(|area| area.range().start().start_addr() == addr)
+            .unwrap();
+        assert!(!area.permissions().contains(GenericMappingFlags::Writable));
+    }
+}
@@ -0,0 +1,247 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use ipc::{IIpcObject, SHMMAX, SHMMNI};
+
+use crate::{
+    sysv_ipc::{IpcPerm, IPC_INFO, IPC_RMID, IPC_SET, IPC_STAT},
+    SyscallContext, SyscallResult,
+};
+
+/// Keeps the segment in memory, which it always is
+const SHM_LOCK: usize = 11;
+const SHM_UNLOCK: usize = 12;
+/// `IPC_STAT` returning the id
+const SHM_STAT: usize = 13;
+
+/// `struct shmid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShmidDs {
+    pub shm_perm: IpcPerm,
+    pub shm_segsz: usize,
+    pub shm_atime: i64,
+    pub shm_dtime: i64,
+    pub shm_ctime: i64,
+    pub shm_cpid: i32,
+    pub shm_lpid: i32,
+    pub shm_nattch: u64,
+    pub unused: [u64; 2],
+}
+
+/// `struct shminfo64`, the limits reported by `IPC_INFO`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShmInfo {
+    pub shmmax: u64,
+    pub shmmin: u64,
+    pub shmmni: u64,
+    pub shmseg: u64,
+    pub shmall: u64,
+    pub unused: [u64; 4],
+}
+
+impl SyscallContext {
+    /// Reports, changes or removes the System V shared memory segment `shmid`.
+    ///
+    /// A segment removed while attached stays until its last detach, but can't be found by its
+    /// key anymore.
+    pub fn sys_shmctl(&self, shmid: usize, cmd: usize, buf: VirtualAddress) -> SyscallResult {
+        let segments = self.kernel.shared_memory();
+
+        if cmd == IPC_INFO {
+            let info = ShmInfo {
+                shmmax: SHMMAX as u64,
+                shmmin: 1,
+                shmmni: SHMMNI as u64,
+                shmseg: SHMMNI as u64,
+                shmall: (SHMMAX / constants::PAGE_SIZE) as u64,
+                ..Default::default()
+            };
+
+            self.task
+                .process()
+                .mmu()
+                .lock()
+                .export(buf, info)
+                .map_err(|_| ErrNo::BadAddress)?;
+
+            return Ok(segments.highest_id().unwrap_or(0) as isize);
+        }
+
+        let segment = segments.find(shmid).ok_or(ErrNo::InvalidArgument)?;
+        let credentials = self.credentials();
+
+        match cmd {
+            IPC_STAT | SHM_STAT => {
+                if !segment.permissions().allows(&credentials, 0o444) {
+                    return Err(ErrNo::PermissionDenied);
+                }
+
+                let status = *segment.status().lock();
+                let ds = ShmidDs {
+                    shm_perm: status.permissions.into(),
+                    shm_segsz: segment.size(),
+                    shm_atime: status.attach_time,
+                    shm_dtime: status.detach_time,
+                    shm_ctime: status.change_time,
+                    shm_cpid: status.creator_pid as i32,
+                    shm_lpid: status.last_pid as i32,
+                    shm_nattch: segment.attaches() as u64,
+                    ..Default::default()
+                };
+
+                self.task
+                    .process()
+                    .mmu()
+                    .lock()
+                    .export(buf, ds)
+                    .map_err(|_| ErrNo::BadAddress)?;
+
+                match cmd {
+                    SHM_STAT => Ok(shmid as isize),
+                    _ => Ok(0),
+                }
+            }
+            IPC_SET | IPC_RMID | SHM_LOCK | SHM_UNLOCK => {
+                if !segment.permissions().can_change(&credentials) {
+                    return Err(ErrNo::OperationNotPermitted);
+                }
+
+                match cmd {
+                    IPC_SET => {
+                        let ds = self
+                            .task
+                            .process()
+                            .mmu()
+                            .lock()
+                            .import::<ShmidDs>(buf)
+                            .map_err(|_| ErrNo::BadAddress)?;
+
+                        let mut status = segment.status().lock();
+                        let perm = ds.shm_perm;
+
+                        status.permissions.set(perm.uid, perm.gid, perm.mode);
+                        status.change_time = self.kernel.time().tv_sec;
+                    }
+                    IPC_RMID => {
+                        drop(segment);
+                        segments.remove_segment(shmid);
+                    }
+                    _ => (),
+                }
+
+                Ok(0)
+            }
+            _ => Err(ErrNo::InvalidArgument),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use filesystem_abstractions::Credentials;
+    use ipc::SHM_DEST;
+
+    use super::*;
+    use crate::{
+        sys_memfd_create::tests::setup_memfd_context,
+        sys_shmat::tests::{attach, create_segment},
+    };
+
+    fn stat(ctx: &SyscallContext, shmid: usize) -> Result<ShmidDs, ErrNo> {
+        let ds = Box::new(ShmidDs::default());
+        let ptr = ctx.task.process().mmu().lock().register(ds.as_ref(), true);
+
+        ctx.sys_shmctl(shmid, IPC_STAT, ptr).map(|_| *ds)
+    }
+
+    #[test]
+    fn test_stat_and_set() {
+        let (ctx, _) = setup_memfd_context();
+        let id = ctx.sys_shmget(42, 5000, 0o1000 | 0o640).unwrap() as usize;
+        attach(&ctx, id, 0).unwrap();
+
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!(ds.shm_perm.key, 42);
+        assert_eq!(ds.shm_perm.mode, 0o640);
+        assert_eq!(ds.shm_segsz, 5000);
+        assert_eq!(ds.shm_nattch, 1);
+        assert_eq!(ds.shm_cpid, ctx.task.process().pid() as i32);
+        assert_ne!(ds.shm_atime, 0);
+
+        let mut changed = Box::new(ds);
+        changed.shm_perm.uid = 1000;
+        changed.shm_perm.mode = 0o600;
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(changed.as_ref(), true);
+        assert_eq!(ctx.sys_shmctl(id, IPC_SET, ptr), Ok(0));
+
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!((ds.shm_perm.uid, ds.shm_perm.cuid), (1000, 0));
+        assert_eq!(ds.shm_perm.mode, 0o600);
+
+        // Neither the owner nor the creator
+        *ctx.task.process().credentials().lock() = Credentials::new(1001, 0);
+        assert_eq!(stat(&ctx, id), Err(ErrNo::PermissionDenied));
+        assert_eq!(
+            ctx.sys_shmctl(id, IPC_SET, ptr),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(
+            ctx.sys_shmctl(id, IPC_RMID, VirtualAddress::null()),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let (ctx, _) = setup_memfd_context();
+        let id = ctx.sys_shmget(42, 4096, 0o1000 | 0o600).unwrap() as usize;
+        let unattached = create_segment(&ctx, 4096);
+        attach(&ctx, id, 0).unwrap();
+
+        let null = VirtualAddress::null();
+        assert_eq!(ctx.sys_shmctl(id, IPC_RMID, null), Ok(0));
+        assert_eq!(ctx.sys_shmctl(unattached, IPC_RMID, null), Ok(0));
+
+        // Still attached, but its key is free for a new segment
+        let ds = stat(&ctx, id).unwrap();
+        assert_eq!(ds.shm_perm.key, 0);
+        assert_eq!(ds.shm_perm.mode, 0o600 | SHM_DEST);
+        assert_eq!(stat(&ctx, unattached), Err(ErrNo::InvalidArgument));
+        assert_ne!(ctx.sys_shmget(42, 4096, 0o1000 | 0o600), Ok(id as isize));
+
+        // Its last attach goes away with the memory space
+        ctx.task
+            .process()
+            .memory_space()
+            .lock()
+            .unmap_all_areas_that(|_| true);
+        assert_eq!(stat(&ctx, id), Err(ErrNo::InvalidArgument));
+    }
+
+    #[test]
+    fn test_info() {
+        let (ctx, _) = setup_memfd_context();
+        create_segment(&ctx, 4096);
+        let last = create_segment(&ctx, 4096);
+
+        let info = Box::new(ShmInfo::default());
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(info.as_ref(), true);
+
+        assert_eq!(ctx.sys_shmctl(0, IPC_INFO, ptr), Ok(last as isize));
+        assert_eq!(info.shmmni, SHMMNI as u64);
+        assert_eq!(ctx.sys_shmctl(0, 42, ptr), Err(ErrNo::InvalidArgument));
+    }
+}
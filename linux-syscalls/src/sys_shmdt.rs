@@ -0,0 +1,133 @@
+use address::{IAlignableAddress, IToPageNum, VirtualAddress};
+use constants::ErrNo;
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Detaches the System V shared memory segment attached at `shmaddr`.
+    ///
+    /// A segment removed with `IPC_RMID` goes away with its last detach.
+    pub fn sys_shmdt(&self, shmaddr: VirtualAddress) -> SyscallResult {
+        if !shmaddr.is_page_aligned() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let vpn = shmaddr.to_floor_page_num();
+        let segments = self.kernel.shared_memory();
+
+        let process = self.task.process();
+        let mut mem = process.memory_space().lock();
+
+        let first = mem
+            .mappings()
+            .iter()
+            .find(|area| area.range().start() == vpn)
+            .and_then(|area| area.allocation.as_ref()?.pages.get(&vpn).cloned())
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        let (_, segment) = segments
+            .segment_of(first.as_ref())
+            .ok_or(ErrNo::InvalidArgument)?;
+
+        drop(first);
+        mem.unmap_area_starts_with(vpn);
+        drop(mem);
+
+        let mut status = segment.status().lock();
+        status.detach_time = self.kernel.time().tv_sec;
+        status.last_pid = process.pid();
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstractions::IUsizeAlias;
+    use address::IAddressBase;
+    use memory_space::MemorySpace;
+    use test_utilities::memory::TestMMU;
+
+    use super::*;
+    use crate::{
+        sys_memfd_create::tests::{map_shared, memfd_create, process_on, setup_memfd_context},
+        sys_shmat::tests::{attach, create_segment, read, write},
+        sysv_ipc::IPC_RMID,
+    };
+
+    fn detach(ctx: &SyscallContext, addr: isize) -> SyscallResult {
+        ctx.sys_shmdt(VirtualAddress::from_usize(addr as usize))
+    }
+
+    #[test]
+    fn test_detach() {
+        let (ctx, _) = setup_memfd_context();
+        let id = create_segment(&ctx, 4096);
+
+        let first = attach(&ctx, id, 0).unwrap();
+        let second = attach(&ctx, id, 0).unwrap();
+        write(&ctx, first, b"kept");
+
+        assert_eq!(detach(&ctx, first), Ok(0));
+        assert_eq!(detach(&ctx, first), Err(ErrNo::InvalidArgument));
+        assert_eq!(detach(&ctx, second + 1), Err(ErrNo::InvalidArgument));
+
+        let segment = ctx.kernel.shared_memory().find(id).unwrap();
+        assert_eq!(segment.attaches(), 1);
+        assert_eq!(read(&ctx, second, 4), b"kept");
+
+        // The segment stays until it is removed, even with nothing attached
+        assert_eq!(detach(&ctx, second), Ok(0));
+        assert_eq!(segment.attaches(), 0);
+        assert!(ctx.kernel.shared_memory().find(id).is_some());
+    }
+
+    #[test]
+    fn test_removed_with_last_detach() {
+        let (ctx, _) = setup_memfd_context();
+        let cache = ctx.kernel.mounts().page_cache().unwrap();
+        let id = create_segment(&ctx, 4096);
+        let addr = attach(&ctx, id, 0).unwrap();
+
+        let null = VirtualAddress::null();
+        assert_eq!(ctx.sys_shmctl(id, IPC_RMID, null), Ok(0));
+        assert!(ctx.kernel.shared_memory().find(id).is_some());
+        assert_eq!(cache.cached_pages(), 1);
+
+        // Its pages leave the page cache along with it
+        assert_eq!(detach(&ctx, addr), Ok(0));
+        assert!(ctx.kernel.shared_memory().find(id).is_none());
+        assert_eq!(cache.cached_pages(), 0);
+    }
+
+    #[test]
+    fn test_removed_with_memory_space() {
+        let (ctx, alloc) = setup_memfd_context();
+        let other = process_on(ctx.kernel.clone(), &alloc);
+
+        let id = create_segment(&ctx, 4096);
+        attach(&other, id, 0).unwrap();
+
+        let null = VirtualAddress::null();
+        assert_eq!(ctx.sys_shmctl(id, IPC_RMID, null), Ok(0));
+        assert!(ctx.kernel.shared_memory().find(id).is_some());
+
+        // Dropping the memory space of a process, like exiting does, detaches it as well
+        let fresh = MemorySpace::new(TestMMU::new(alloc.clone()), alloc.clone());
+        drop(core::mem::replace(
+            &mut *other.task.process().memory_space().lock(),
+            fresh,
+        ));
+        assert!(ctx.kernel.shared_memory().find(id).is_none());
+    }
+
+    #[test]
+    fn test_not_a_segment() {
+        let (ctx, _) = setup_memfd_context();
+
+        let fd = memfd_create(&ctx, "file", 0).unwrap() as usize;
+        let addr = map_shared(&ctx, fd).unwrap();
+
+        assert_eq!(detach(&ctx, addr), Err(ErrNo::InvalidArgument));
+    }
+}
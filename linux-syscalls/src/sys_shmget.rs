@@ -0,0 +1,99 @@
+use alloc::format;
+use constants::ErrNo;
+use ipc::{IpcGetFlags, SharedMemorySegment, SHMMAX};
+
+use crate::{SyscallContext, SyscallResult};
+
+impl SyscallContext {
+    /// Returns the id of the System V shared memory segment with `key`, creating one of `size`
+    /// bytes if `shmflg` asks for it, with the permissions in its lower bits.
+    ///
+    /// The segment is a file of its own on a tmpfs that no path leads to, like on Linux.
+    pub fn sys_shmget(&self, key: usize, size: usize, shmflg: usize) -> SyscallResult {
+        let key = key as i32;
+        let flags = IpcGetFlags::from_bits_truncate(shmflg as u32);
+
+        let segments = self.kernel.shared_memory();
+
+        let pid = self.task.process().pid();
+        let now = self.kernel.time().tv_sec;
+
+        let id = segments.get(
+            key,
+            flags,
+            shmflg as u32 & 0o777,
+            &self.credentials(),
+            |segment| match size > segment.size() {
+                true => Err(ErrNo::InvalidArgument),
+                false => Ok(()),
+            },
+            |permissions| {
+                if size == 0 || size > SHMMAX {
+                    return Err(ErrNo::InvalidArgument);
+                }
+
+                let file = self.create_anonymous_file(&format!("SYSV{key:08x}"))?;
+
+                SharedMemorySegment::new(file, size, permissions, pid, now)
+            },
+        )?;
+
+        Ok(id as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ipc::IPC_PRIVATE;
+
+    use super::*;
+    use crate::sys_memfd_create::tests::setup_memfd_context;
+
+    const IPC_CREAT: usize = 0o1000;
+    const IPC_EXCL: usize = 0o2000;
+
+    #[test]
+    fn test_get_by_key() {
+        let (ctx, _) = setup_memfd_context();
+
+        assert_eq!(
+            ctx.sys_shmget(42, 8192, 0o600),
+            Err(ErrNo::NoSuchFileOrDirectory)
+        );
+
+        let id = ctx.sys_shmget(42, 8192, IPC_CREAT | 0o600).unwrap();
+        assert_eq!(ctx.sys_shmget(42, 100, 0), Ok(id));
+        assert_eq!(ctx.sys_shmget(42, 8193, 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            ctx.sys_shmget(42, 8192, IPC_CREAT | IPC_EXCL | 0o600),
+            Err(ErrNo::FileExists)
+        );
+
+        let segment = ctx.kernel.shared_memory().find(id as usize).unwrap();
+        assert_eq!(segment.size(), 8192);
+        assert_eq!(segment.attaches(), 0);
+        assert_eq!(segment.status().lock().permissions.mode, 0o600);
+
+        // A private key always gets a new segment
+        let private = ctx.sys_shmget(IPC_PRIVATE as usize, 4096, 0o600).unwrap();
+        assert_ne!(private, id);
+        assert_ne!(
+            ctx.sys_shmget(IPC_PRIVATE as usize, 4096, 0o600),
+            Ok(private)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, _) = setup_memfd_context();
+
+        assert_eq!(
+            ctx.sys_shmget(42, 0, IPC_CREAT | 0o600),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_shmget(42, SHMMAX + 1, IPC_CREAT | 0o600),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
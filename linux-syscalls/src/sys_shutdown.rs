@@ -0,0 +1,14 @@
+use crate::{SyscallContext, SyscallResult};
+use constants::ErrNo;
+use socket_abstractions::Shutdown;
+
+impl SyscallContext {
+    pub fn sys_shutdown(&self, sockfd: usize, how: usize) -> SyscallResult {
+        log::debug!("sys_shutdown: sockfd: {}, how: {}", sockfd, how);
+
+        let how = Shutdown::from_raw(how).ok_or(ErrNo::InvalidArgument)?;
+        let (socket, _) = self.socket_of(sockfd)?;
+
+        socket.shutdown(how).map(|_| 0)
+    }
+}
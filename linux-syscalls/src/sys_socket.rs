@@ -0,0 +1,109 @@
+use crate::{SyscallContext, SyscallResult};
+use constants::ErrNo;
+use filesystem_abstractions::OpenFlags;
+use socket_abstractions::{SocketFlags, SocketType};
+
+impl SyscallContext {
+    pub fn sys_socket(&self, domain: usize, socket_type: usize, protocol: usize) -> SyscallResult {
+        log::debug!(
+            "sys_socket: domain: {}, type: {:#x}, protocol: {}",
+            domain,
+            socket_type,
+            protocol
+        );
+
+        let (ty, flags) = Self::parse_socket_type(socket_type)?;
+
+        let socket = self.create_socket(domain as u16, ty, protocol)?;
+
+        self.allocate_socket_fd(socket, flags).map(|fd| fd as isize)
+    }
+
+    /// Splits the `type` argument of `socket(2)` into the socket type and the open flags.
+    pub(crate) fn parse_socket_type(raw: usize) -> Result<(SocketType, OpenFlags), ErrNo> {
+        let ty = SocketType::from_raw(raw).ok_or(ErrNo::SocketTypeNotSupported)?;
+
+        let flags =
+            SocketFlags::from_bits(raw & !SocketType::MASK).ok_or(ErrNo::InvalidArgument)?;
+
+        Ok((ty, OpenFlags::from_bits_truncate(flags.bits())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socket_abstractions::{SocketFile, AF_INET, AF_UNIX, IPPROTO_TCP, IPPROTO_UDP};
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_creates_socket_file() {
+        let (ctx, _) = setup_socket_context();
+
+        let fd = ctx
+            .sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 0)
+            .unwrap() as usize;
+
+        let file = ctx.file_of(fd).unwrap();
+
+        assert!(SocketFile::socket_of(&file).is_some());
+        assert!(!file.flags().contains(OpenFlags::O_NONBLOCK));
+    }
+
+    #[test]
+    fn test_creates_inet_sockets() {
+        let (ctx, _) = setup_socket_context();
+
+        for (ty, protocol) in [
+            (SocketType::Stream, 0),
+            (SocketType::Stream, IPPROTO_TCP),
+            (SocketType::Datagram, IPPROTO_UDP),
+        ] {
+            let fd = ctx
+                .sys_socket(AF_INET as usize, ty as usize, protocol)
+                .unwrap() as usize;
+
+            let (socket, _) = ctx.socket_of(fd).unwrap();
+
+            assert_eq!((socket.family(), socket.socket_type()), (AF_INET, ty));
+        }
+    }
+
+    #[test]
+    fn test_type_flags() {
+        let (ctx, _) = setup_socket_context();
+
+        let raw = SocketType::Datagram as usize
+            | SocketFlags::SOCK_NONBLOCK.bits()
+            | SocketFlags::SOCK_CLOEXEC.bits();
+
+        let fd = ctx.sys_socket(AF_UNIX as usize, raw, 0).unwrap() as usize;
+
+        let flags = ctx.file_of(fd).unwrap().flags();
+
+        assert!(flags.contains(OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let (ctx, _) = setup_socket_context();
+
+        assert_eq!(
+            ctx.sys_socket(42, SocketType::Stream as usize, 0),
+            Err(ErrNo::AddressFamilyNotSupportedByProtocol)
+        );
+        assert_eq!(
+            ctx.sys_socket(AF_UNIX as usize, 5, 0),
+            Err(ErrNo::SocketTypeNotSupported)
+        );
+        assert_eq!(
+            ctx.sys_socket(AF_UNIX as usize, SocketType::Stream as usize, 6),
+            Err(ErrNo::ProtocolNotSupported)
+        );
+        assert_eq!(
+            ctx.sys_socket(AF_INET as usize, SocketType::Datagram as usize, IPPROTO_TCP),
+            Err(ErrNo::ProtocolNotSupported)
+        );
+    }
+}
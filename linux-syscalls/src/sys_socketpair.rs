@@ -0,0 +1,111 @@
+use crate::{SyscallContext, SyscallResult};
+use address::VirtualAddress;
+use constants::ErrNo;
+use socket_abstractions::AF_UNIX;
+use unix_socket::UnixSocket;
+
+impl SyscallContext {
+    pub fn sys_socketpair(
+        &self,
+        domain: usize,
+        socket_type: usize,
+        protocol: usize,
+        sv: VirtualAddress,
+    ) -> SyscallResult {
+        log::debug!(
+            "sys_socketpair: domain: {}, type: {:#x}, protocol: {}, sv: {}",
+            domain,
+            socket_type,
+            protocol,
+            sv
+        );
+
+        if domain != AF_UNIX as usize {
+            return Err(ErrNo::OperationNotSupported);
+        }
+
+        let (ty, flags) = Self::parse_socket_type(socket_type)?;
+
+        if protocol != 0 {
+            return Err(ErrNo::ProtocolNotSupported);
+        }
+
+        let (first, second) = UnixSocket::new_pair(ty, self.kernel.unix_sockets());
+
+        let first = self.allocate_socket_fd(first, flags)?;
+        let second = match self.allocate_socket_fd(second, flags) {
+            Ok(fd) => fd,
+            Err(e) => {
+                self.task.linux_process().fd_table().lock().remove(first);
+                return Err(e);
+            }
+        };
+
+        let exported = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .export(sv, [first as i32, second as i32]);
+
+        if exported.is_err() {
+            let process = self.task.linux_process();
+            let mut fd_table = process.fd_table().lock();
+
+            fd_table.remove(first);
+            fd_table.remove(second);
+
+            return Err(ErrNo::BadAddress);
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use alloc::boxed::Box;
+    use socket_abstractions::{MessageFlags, SocketType};
+
+    use super::*;
+    use crate::socket::tests::setup_socket_context;
+
+    #[test]
+    fn test_pair_is_connected() {
+        let (ctx, mmu) = setup_socket_context();
+
+        let sv = Box::new([-1i32; 2]);
+        let ptr = mmu.lock().register(sv.as_ref(), true);
+
+        let ret = ctx.sys_socketpair(AF_UNIX as usize, SocketType::Stream as usize, 0, ptr);
+        assert_eq!(ret, Ok(0));
+
+        let sv = mmu.lock().import::<[i32; 2]>(ptr).unwrap();
+
+        let (first, _) = ctx.socket_of(sv[0] as usize).unwrap();
+        let (second, _) = ctx.socket_of(sv[1] as usize).unwrap();
+
+        first.send(b"hi", alloc::vec::Vec::new(), None).unwrap();
+
+        let mut buf = [0u8; 2];
+        second.receive(&mut buf, MessageFlags::empty()).unwrap();
+
+        assert_eq!(&buf, b"hi");
+    }
+
+    #[test]
+    fn test_bad_address_releases_fds() {
+        let (ctx, _) = setup_socket_context();
+
+        let ret = ctx.sys_socketpair(
+            AF_UNIX as usize,
+            SocketType::Stream as usize,
+            0,
+            VirtualAddress::null(),
+        );
+
+        assert_eq!(ret, Err(ErrNo::BadAddress));
+        assert!(ctx.file_of(0).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use filesystem_abstractions::{Pipe, TransferEnd};
+
+use crate::{
+    transfer::{transfer_when_ready, SPLICE_FLAGS, SPLICE_F_NONBLOCK},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Moves up to `len` bytes between two files without going through user space, one of them
+    /// has to be a pipe.
+    ///
+    /// The other file is read or written at the offset `off_in` or `off_out` points to, which is
+    /// moved along, or at its file position if the pointer is null. Pipes have no offsets.
+    pub async fn sys_splice(
+        &self,
+        fd_in: usize,
+        off_in: VirtualAddress,
+        fd_out: usize,
+        off_out: VirtualAddress,
+        len: usize,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !SPLICE_FLAGS != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let source = self.file_of(fd_in)?;
+        let sink = self.file_of(fd_out)?;
+
+        if !source.can_read() || !sink.can_write() {
+            return Err(ErrNo::BadFileDescriptor);
+        }
+
+        let source_pipe = source.downcast_ref::<Pipe>();
+        let sink_pipe = sink.downcast_ref::<Pipe>();
+
+        match (source_pipe, sink_pipe) {
+            (None, None) => return Err(ErrNo::InvalidArgument),
+            (Some(source), Some(sink)) if source.is_same_pipe(sink) => {
+                return Err(ErrNo::InvalidArgument)
+            }
+            _ => (),
+        }
+
+        if (source_pipe.is_some() && !off_in.is_null())
+            || (sink_pipe.is_some() && !off_out.is_null())
+        {
+            return Err(ErrNo::IllegalSeek);
+        }
+
+        if source.is_dir() || sink.is_dir() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let mut source = TransferEnd::new(source.clone(), self.import_offset(off_in)?);
+        let mut sink = TransferEnd::new(sink.clone(), self.import_offset(off_out)?);
+
+        let nonblocking = flags & SPLICE_F_NONBLOCK != 0;
+        let moved = transfer_when_ready(&mut source, &mut sink, len, nonblocking).await?;
+
+        self.export_offset(off_in, &source)?;
+        self.export_offset(off_out, &sink)?;
+
+        Ok(moved)
+    }
+}
+
+// The tests run on a scratch host directory, which only unix hosts have
+#[cfg(all(test, unix))]
+mod tests {
+    use alloc::boxed::Box;
+    use filesystem_abstractions::OpenFlags;
+    use threading::block_on;
+
+    use super::*;
+    use crate::{
+        fs::tests::open_fd,
+        transfer::tests::{pipe_fds, setup_cached_context},
+    };
+
+    #[test]
+    fn test_through_a_pipe() {
+        let (ctx, root, host) = setup_cached_context();
+
+        let source = root.touch("source").unwrap();
+        source.writeat(0, b"spliced data").unwrap();
+        let source = open_fd(&ctx, &source, OpenFlags::O_RDONLY) as usize;
+
+        let sink = root.touch("sink").unwrap();
+        let sink_fd = open_fd(&ctx, &sink, OpenFlags::O_WRONLY) as usize;
+
+        let (read_end, write_end) = pipe_fds(&ctx, OpenFlags::NONE);
+
+        let off_in = Box::new(8i64);
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(off_in.as_ref(), true);
+        let null = VirtualAddress::null();
+
+        assert_eq!(
+            block_on!(ctx.sys_splice(source, ptr, write_end, null, 64, 0)),
+            Ok(4)
+        );
+        assert_eq!(*off_in, 12);
+
+        assert_eq!(
+            block_on!(ctx.sys_splice(source, null, write_end, null, 8, 0)),
+            Ok(8)
+        );
+
+        assert_eq!(
+            block_on!(ctx.sys_splice(read_end, null, sink_fd, null, 64, 0)),
+            Ok(12)
+        );
+
+        sink.sync().unwrap();
+        assert_eq!(
+            std::fs::read(host.host_path("/sink")).unwrap(),
+            b"dataspliced "
+        );
+
+        // The pipe is empty, and still has a writer
+        assert_eq!(
+            block_on!(ctx.sys_splice(read_end, null, sink_fd, null, 64, SPLICE_F_NONBLOCK)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root, _) = setup_cached_context();
+
+        let file = root.touch("file").unwrap();
+        let file = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        let (read_end, write_end) = pipe_fds(&ctx, OpenFlags::NONE);
+
+        let offset = Box::new(0i64);
+        let ptr = ctx
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .register(offset.as_ref(), true);
+        let null = VirtualAddress::null();
+
+        // Neither is a pipe
+        assert_eq!(
+            block_on!(ctx.sys_splice(file, null, file, null, 8, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_splice(read_end, null, write_end, null, 8, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_splice(read_end, ptr, file, null, 8, 0)),
+            Err(ErrNo::IllegalSeek)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_splice(write_end, null, file, null, 8, 0)),
+            Err(ErrNo::BadFileDescriptor)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_splice(file, null, write_end, null, 8, 0x10)),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
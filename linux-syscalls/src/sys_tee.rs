@@ -0,0 +1,125 @@
+use constants::ErrNo;
+use filesystem_abstractions::{IFile, OpenFlags, Pipe};
+
+use crate::{
+    transfer::{SPLICE_FLAGS, SPLICE_F_NONBLOCK},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Copies up to `len` bytes queued in the pipe `fd_in` into the pipe `fd_out`, leaving them in
+    /// `fd_in` to be read again.
+    pub async fn sys_tee(
+        &self,
+        fd_in: usize,
+        fd_out: usize,
+        len: usize,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !SPLICE_FLAGS != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let source = self.file_of(fd_in)?;
+        let sink = self.file_of(fd_out)?;
+
+        let (Some(source), Some(sink)) =
+            (source.downcast_ref::<Pipe>(), sink.downcast_ref::<Pipe>())
+        else {
+            return Err(ErrNo::InvalidArgument);
+        };
+
+        if source.is_write_end() || !sink.is_write_end() || source.is_same_pipe(sink) {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let nonblocking = flags & SPLICE_F_NONBLOCK != 0
+            || source.flags().contains(OpenFlags::O_NONBLOCK)
+            || sink.flags().contains(OpenFlags::O_NONBLOCK);
+
+        while !source.read_avaliable() || !sink.write_avaliable() {
+            if nonblocking {
+                return Err(ErrNo::ResourceTemporarilyUnavailable);
+            }
+
+            match source.read_avaliable() {
+                false => source.wait_readable().await,
+                true => sink.wait_writable().await,
+            }
+        }
+
+        source
+            .tee_into(sink, len)
+            .map(|copied| copied as isize)
+            .map_err(|e| e.to_errno())
+    }
+}
+
+// The tests run on a scratch host directory, which only unix hosts have
+#[cfg(all(test, unix))]
+mod tests {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    use threading::block_on;
+
+    use super::*;
+    use crate::transfer::tests::{pipe_fds, setup_cached_context};
+
+    #[test]
+    fn test_duplicates_queued_bytes() {
+        let (ctx, _, _) = setup_cached_context();
+
+        let (first_read, first_write) = pipe_fds(&ctx, OpenFlags::NONE);
+        let (second_read, second_write) = pipe_fds(&ctx, OpenFlags::O_NONBLOCK);
+
+        ctx.file_of(first_write).unwrap().write(b"teed");
+
+        assert_eq!(
+            block_on!(ctx.sys_tee(first_read, second_write, 3, 0)),
+            Ok(3)
+        );
+
+        let mut buf = [0; 8];
+        assert_eq!(ctx.file_of(second_read).unwrap().read(&mut buf), 3);
+        assert_eq!(&buf[..3], b"tee");
+        assert_eq!(ctx.file_of(first_read).unwrap().read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"teed");
+
+        assert_eq!(
+            block_on!(ctx.sys_tee(first_read, second_write, 3, 0)),
+            Err(ErrNo::ResourceTemporarilyUnavailable)
+        );
+
+        // Both the wrong way around, or the same pipe
+        assert_eq!(
+            block_on!(ctx.sys_tee(second_write, first_read, 3, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            block_on!(ctx.sys_tee(first_read, first_write, 3, 0)),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_waits_for_the_source() {
+        let (ctx, _, _) = setup_cached_context();
+
+        let (first_read, first_write) = pipe_fds(&ctx, OpenFlags::NONE);
+        let (_second_read, second_write) = pipe_fds(&ctx, OpenFlags::NONE);
+
+        let mut waiting = pin!(ctx.sys_tee(first_read, second_write, 8, 0));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        ctx.file_of(first_write).unwrap().write(b"late");
+        assert_eq!(waiting.as_mut().poll(&mut cx), Poll::Ready(Ok(4)));
+    }
+}
@@ -0,0 +1,222 @@
+use address::VirtualAddress;
+use alloc::sync::Arc;
+use constants::ErrNo;
+use filesystem_abstractions::UnmountFlags;
+
+use crate::{
+    fs::{AT_FDCWD, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+impl SyscallContext {
+    /// Unmounts the filesystem visible at `target`, showing what it was mounted over again.
+    ///
+    /// A mount with open files or mounts below it is busy, unless `MNT_DETACH` takes it out of the
+    /// tree anyway together with its submounts. The files keep working until they are closed.
+    /// Inotify watches on the files hear `IN_UNMOUNT` once no other mount shows them.
+    pub fn sys_umount2(&self, target: VirtualAddress, flags: usize) -> SyscallResult {
+        let flags = UnmountFlags::from_bits(flags).ok_or(ErrNo::InvalidArgument)?;
+
+        if flags.contains(UnmountFlags::MNT_EXPIRE)
+            && flags.intersects(UnmountFlags::MNT_FORCE | UnmountFlags::MNT_DETACH)
+        {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let credentials = self.credentials();
+
+        if !credentials.is_admin() {
+            return Err(ErrNo::OperationNotPermitted);
+        }
+
+        let lookup_flags = match flags.contains(UnmountFlags::UMOUNT_NOFOLLOW) {
+            true => AT_SYMLINK_NOFOLLOW,
+            false => 0,
+        };
+
+        let node = self.lookup_at(AT_FDCWD, target, lookup_flags, &credentials)?;
+        let table = self.kernel.mounts();
+        let path = node.fullpath();
+
+        if table.get(&path).is_none() {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let parent = node.parent().ok_or(ErrNo::DeviceOrResourceBusy)?.clone();
+        let detach = flags.contains(UnmountFlags::MNT_DETACH);
+
+        // Besides us, only the parent holds the node of a mount nothing below is in use of
+        if !detach && (Arc::strong_count(&node) > 2 || !table.submounts(&path).is_empty()) {
+            return Err(ErrNo::DeviceOrResourceBusy);
+        }
+
+        if let Err(e) = parent.umount_at(node.name()) {
+            return e.to_syscall_error();
+        }
+
+        table.remove(&path, detach);
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use address::IAddressBase;
+    use filesystem_abstractions::{InotifyMask, MountFlags, OpenFlags};
+
+    use super::*;
+    use crate::{
+        fs::tests::{open_fd, read_events, UserPath},
+        sys_mount::tests::setup_mount_context,
+    };
+
+    #[test]
+    fn test_unmount_stacked() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap();
+
+        let none = UserPath::new(&ctx, "none");
+        let mnt = UserPath::new(&ctx, "/mnt");
+        let tmpfs = UserPath::new(&ctx, "tmpfs");
+        let null = VirtualAddress::null();
+
+        for file in ["first", "second"] {
+            ctx.sys_mount(none.addr(), mnt.addr(), tmpfs.addr(), 0, null)
+                .unwrap();
+            root.open("/mnt", Some(&root)).unwrap().touch(file).unwrap();
+        }
+
+        assert_eq!(ctx.sys_umount2(mnt.addr(), 0), Ok(0));
+        assert!(root.open("/mnt/first", Some(&root)).is_ok());
+        assert!(root.open("/mnt/second", Some(&root)).is_err());
+
+        assert_eq!(ctx.sys_umount2(mnt.addr(), 0), Ok(0));
+        assert!(root.open("/mnt/first", Some(&root)).is_err());
+
+        assert_eq!(ctx.sys_umount2(mnt.addr(), 0), Err(ErrNo::InvalidArgument));
+        assert_eq!(ctx.kernel.mounts().entries().len(), 1);
+    }
+
+    #[test]
+    fn test_busy_and_detach() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap();
+
+        let none = UserPath::new(&ctx, "none");
+        let mnt = UserPath::new(&ctx, "/mnt");
+        let sub = UserPath::new(&ctx, "/mnt/sub");
+        let tmpfs = UserPath::new(&ctx, "tmpfs");
+        let null = VirtualAddress::null();
+
+        ctx.sys_mount(none.addr(), mnt.addr(), tmpfs.addr(), 0, null)
+            .unwrap();
+        let file = root
+            .open("/mnt", Some(&root))
+            .unwrap()
+            .touch("file")
+            .unwrap();
+        let fd = open_fd(&ctx, &file, OpenFlags::O_RDWR) as usize;
+        drop(file);
+
+        assert_eq!(
+            ctx.sys_umount2(mnt.addr(), 0),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+
+        ctx.sys_close(fd).unwrap();
+
+        root.open("/mnt", Some(&root))
+            .unwrap()
+            .mkdir("sub")
+            .unwrap();
+        ctx.sys_mount(none.addr(), sub.addr(), tmpfs.addr(), 0, null)
+            .unwrap();
+
+        assert_eq!(
+            ctx.sys_umount2(mnt.addr(), 0),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+
+        let detach = UnmountFlags::MNT_DETACH.bits();
+        assert_eq!(ctx.sys_umount2(mnt.addr(), detach), Ok(0));
+        assert!(root.open("/mnt/sub", Some(&root)).is_err());
+        assert_eq!(ctx.kernel.mounts().entries().len(), 1);
+
+        // Only mounts can be unmounted
+        assert_eq!(ctx.sys_umount2(mnt.addr(), 0), Err(ErrNo::InvalidArgument));
+    }
+
+    #[test]
+    fn test_ends_watches() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap();
+        root.mkdir("view").unwrap();
+
+        let none = UserPath::new(&ctx, "none");
+        let mnt = UserPath::new(&ctx, "/mnt");
+        let view = UserPath::new(&ctx, "/view");
+        let file = UserPath::new(&ctx, "/mnt/file");
+        let tmpfs = UserPath::new(&ctx, "tmpfs");
+        let null = VirtualAddress::null();
+
+        ctx.sys_mount(none.addr(), mnt.addr(), tmpfs.addr(), 0, null)
+            .unwrap();
+        root.open("/mnt", Some(&root))
+            .unwrap()
+            .touch("file")
+            .unwrap();
+        ctx.sys_mount(
+            mnt.addr(),
+            view.addr(),
+            null,
+            MountFlags::MS_BIND.bits(),
+            null,
+        )
+        .unwrap();
+
+        let fd = ctx.sys_inotify_init1(0).unwrap() as usize;
+        let mask = InotifyMask::IN_ALL_EVENTS.bits();
+        let dir_wd = ctx.sys_inotify_add_watch(fd, mnt.addr(), mask).unwrap() as i32;
+        let file_wd = ctx.sys_inotify_add_watch(fd, file.addr(), mask).unwrap() as i32;
+
+        // Watches do not keep the mount busy, and last as long as a bind mount shows the files
+        assert_eq!(ctx.sys_umount2(view.addr(), 0), Ok(0));
+        assert!(read_events(&ctx, fd).is_empty());
+
+        assert_eq!(ctx.sys_umount2(mnt.addr(), 0), Ok(0));
+        assert_eq!(
+            read_events(&ctx, fd),
+            [
+                (dir_wd, InotifyMask::IN_UNMOUNT, 0, "".into()),
+                (dir_wd, InotifyMask::IN_IGNORED, 0, "".into()),
+                (file_wd, InotifyMask::IN_UNMOUNT, 0, "".into()),
+                (file_wd, InotifyMask::IN_IGNORED, 0, "".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (ctx, root) = setup_mount_context();
+        root.mkdir("mnt").unwrap();
+
+        let mnt = UserPath::new(&ctx, "/mnt");
+        let expire = UnmountFlags::MNT_EXPIRE | UnmountFlags::MNT_DETACH;
+
+        assert_eq!(
+            ctx.sys_umount2(mnt.addr(), expire.bits()),
+            Err(ErrNo::InvalidArgument)
+        );
+        assert_eq!(
+            ctx.sys_umount2(mnt.addr(), 0x100),
+            Err(ErrNo::InvalidArgument)
+        );
+
+        ctx.task.process().credentials().lock().euid = 1000;
+        assert_eq!(
+            ctx.sys_umount2(mnt.addr(), 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use address::VirtualAddress;
+use constants::ErrNo;
+use filesystem_abstractions::DirectoryEntryType;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Remove a directory instead, like `rmdir`
+const AT_REMOVEDIR: usize = 0x200;
+
+impl SyscallContext {
+    /// Removes the name at `pathname`, which has to be a directory with `AT_REMOVEDIR` and must
+    /// not be one otherwise.
+    pub fn sys_unlinkat(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !AT_REMOVEDIR != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let credentials = self.credentials();
+        let (parent, name) = self.lookup_parent_at(dirfd, pathname, &credentials)?;
+
+        parent
+            .check_delete(&name, &credentials)
+            .map_err(|e| e.to_errno())?;
+
+        let is_directory = parent
+            .open_child(&name)
+            .map_err(|e| e.to_errno())?
+            .metadata()
+            .entry_type
+            == DirectoryEntryType::Directory;
+
+        match (flags & AT_REMOVEDIR != 0, is_directory) {
+            (true, false) => return Err(ErrNo::NotADirectory),
+            (false, true) => return Err(ErrNo::IsADirectory),
+            (true, true) => parent.rmdir(&name),
+            (false, false) => parent.remove(&name),
+        }
+        .map_err(|e| e.to_errno())?;
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::{Credentials, FileSystemError};
+
+    use super::*;
+    use crate::fs::{
+        tests::{own, setup_fs_context, UserPath},
+        AT_FDCWD,
+    };
+
+    fn unlink(ctx: &SyscallContext, path: &str, flags: usize) -> SyscallResult {
+        let path = UserPath::new(ctx, path);
+
+        ctx.sys_unlinkat(AT_FDCWD, path.addr(), flags)
+    }
+
+    #[test]
+    fn test_unlink_file_and_directory() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        root.mkdir("dir").unwrap().touch("file").unwrap();
+
+        assert_eq!(unlink(&ctx, "dir", 0), Err(ErrNo::IsADirectory));
+        assert_eq!(
+            unlink(&ctx, "/dir/file", AT_REMOVEDIR),
+            Err(ErrNo::NotADirectory)
+        );
+
+        assert_eq!(unlink(&ctx, "/dir/file", 0), Ok(0));
+        assert_eq!(unlink(&ctx, "dir/", AT_REMOVEDIR), Ok(0));
+
+        assert_eq!(
+            root.open_child("dir").err(),
+            Some(FileSystemError::NotFound)
+        );
+        assert_eq!(unlink(&ctx, "dir", 0), Err(ErrNo::NoSuchFileOrDirectory));
+    }
+
+    #[test]
+    fn test_unlink_invalid() {
+        let (ctx, _) = setup_fs_context(Credentials::root());
+
+        assert_eq!(unlink(&ctx, "", 0), Err(ErrNo::NoSuchFileOrDirectory));
+        assert_eq!(unlink(&ctx, ".", AT_REMOVEDIR), Err(ErrNo::InvalidArgument));
+        assert_eq!(
+            unlink(&ctx, "/", AT_REMOVEDIR),
+            Err(ErrNo::DeviceOrResourceBusy)
+        );
+        assert_eq!(unlink(&ctx, "file", 1), Err(ErrNo::InvalidArgument));
+    }
+
+    #[test]
+    fn test_unlink_needs_write_permission() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let dir = root.mkdir("dir").unwrap();
+        own(&dir.touch("file").unwrap(), 1000, 0o644);
+
+        own(&dir, 2000, 0o755);
+        assert_eq!(unlink(&ctx, "dir/file", 0), Err(ErrNo::PermissionDenied));
+
+        own(&dir, 1000, 0o755);
+        assert_eq!(unlink(&ctx, "dir/file", 0), Ok(0));
+    }
+
+    #[test]
+    fn test_unlink_in_sticky_directory() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let tmp = root.mkdir("tmp").unwrap();
+        own(&tmp, 0, 0o1777);
+        own(&tmp.touch("theirs").unwrap(), 2000, 0o666);
+        own(&tmp.touch("mine").unwrap(), 1000, 0o644);
+
+        assert_eq!(
+            unlink(&ctx, "/tmp/theirs", 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+        assert_eq!(unlink(&ctx, "/tmp/mine", 0), Ok(0));
+    }
+}
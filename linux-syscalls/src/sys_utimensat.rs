@@ -0,0 +1,192 @@
+use address::{IAddressBase, VirtualAddress};
+use constants::ErrNo;
+use filesystem_abstractions::{FileStatistics, InodeAttributes};
+use timing::TimeSpec;
+
+use crate::{
+    fs::{AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW},
+    SyscallContext, SyscallResult,
+};
+
+/// Sets a time to the current time
+const UTIME_NOW: i64 = (1 << 30) - 1;
+/// Leaves a time as it is
+const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+/// What a null `times` stands for
+const BOTH_NOW: [TimeSpec; 2] = [TimeSpec {
+    tv_sec: 0,
+    tv_nsec: UTIME_NOW,
+}; 2];
+
+impl SyscallContext {
+    /// Sets the access and modification times, a null `times` sets both to now.
+    ///
+    /// Setting them to now is allowed to anyone who may write the file, other times only to the
+    /// owner. A null `pathname` changes `dirfd` itself.
+    pub fn sys_utimensat(
+        &self,
+        dirfd: isize,
+        pathname: VirtualAddress,
+        times: VirtualAddress,
+        flags: usize,
+    ) -> SyscallResult {
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        let [atime, mtime] = match times.is_null() {
+            true => BOTH_NOW,
+            false => self
+                .task
+                .process()
+                .mmu()
+                .lock()
+                .import::<[TimeSpec; 2]>(times)
+                .map_err(|_| ErrNo::BadAddress)?,
+        };
+
+        for time in [atime, mtime] {
+            let special = time.tv_nsec == UTIME_NOW || time.tv_nsec == UTIME_OMIT;
+
+            if !special && !(0..1_000_000_000).contains(&time.tv_nsec) {
+                return Err(ErrNo::InvalidArgument);
+            }
+        }
+
+        if atime.tv_nsec == UTIME_OMIT && mtime.tv_nsec == UTIME_OMIT {
+            return Ok(0);
+        }
+
+        let flags = match pathname.is_null() {
+            true => flags | AT_EMPTY_PATH,
+            false => flags,
+        };
+
+        let credentials = self.credentials();
+        let node = self.lookup_at(dirfd, pathname, flags, &credentials)?;
+
+        let now = self.kernel.time();
+        let resolve = |time: TimeSpec| match time.tv_nsec {
+            UTIME_OMIT => None,
+            UTIME_NOW => Some(now),
+            _ => Some(time),
+        };
+
+        let attributes = InodeAttributes {
+            atime: resolve(atime),
+            mtime: resolve(mtime),
+            ..Default::default()
+        };
+
+        let only_now = [atime, mtime]
+            .iter()
+            .all(|time| time.tv_nsec == UTIME_NOW || time.tv_nsec == UTIME_OMIT);
+
+        let result = match only_now {
+            true => {
+                let mut stat: FileStatistics = unsafe { core::mem::zeroed() };
+
+                node.stat(&mut stat)
+                    .and_then(|_| credentials.check_touch(&stat))
+                    .and_then(|_| node.set_attributes(&attributes))
+            }
+            false => node.set_attributes_as(&attributes, &credentials),
+        };
+
+        result.map(|_| 0).map_err(|e| e.to_errno())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::Credentials;
+
+    use super::*;
+    use crate::fs::{
+        tests::{own, setup_fs_context, stat, UserPath},
+        AT_FDCWD,
+    };
+
+    fn register_times(ctx: &SyscallContext, times: &[TimeSpec; 2]) -> VirtualAddress {
+        ctx.task.process().mmu().lock().register(times, false)
+    }
+
+    #[test]
+    fn test_explicit_times() {
+        let (ctx, root) = setup_fs_context(Credentials::new(1000, 1000));
+        let file = root.touch("file").unwrap();
+        own(&file, 1000, 0o644);
+        let before = stat(&file);
+
+        let times = [
+            TimeSpec {
+                tv_sec: 1000,
+                tv_nsec: 1,
+            },
+            TimeSpec {
+                tv_sec: 0,
+                tv_nsec: UTIME_OMIT,
+            },
+        ];
+        let path = UserPath::new(&ctx, "file");
+
+        assert_eq!(
+            ctx.sys_utimensat(AT_FDCWD, path.addr(), register_times(&ctx, &times), 0),
+            Ok(0)
+        );
+
+        let after = stat(&file);
+        assert_eq!(after.atime, times[0]);
+        assert_eq!(after.mtime, before.mtime);
+    }
+
+    #[test]
+    fn test_now_needs_write_permission() {
+        let (ctx, root) = setup_fs_context(Credentials::new(2000, 2000));
+        let writable = root.touch("writable").unwrap();
+        own(&writable, 1000, 0o666);
+        let readonly = root.touch("readonly").unwrap();
+        own(&readonly, 1000, 0o644);
+
+        let path = UserPath::new(&ctx, "writable");
+        assert_eq!(
+            ctx.sys_utimensat(AT_FDCWD, path.addr(), VirtualAddress::null(), 0),
+            Ok(0)
+        );
+
+        let path = UserPath::new(&ctx, "readonly");
+        assert_eq!(
+            ctx.sys_utimensat(AT_FDCWD, path.addr(), VirtualAddress::null(), 0),
+            Err(ErrNo::PermissionDenied)
+        );
+
+        // Other times than now only for the owner
+        let times = [TimeSpec::zero(); 2];
+        let path = UserPath::new(&ctx, "writable");
+        assert_eq!(
+            ctx.sys_utimensat(AT_FDCWD, path.addr(), register_times(&ctx, &times), 0),
+            Err(ErrNo::OperationNotPermitted)
+        );
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        let (ctx, root) = setup_fs_context(Credentials::root());
+        root.touch("file").unwrap();
+
+        let times = [
+            TimeSpec {
+                tv_sec: 0,
+                tv_nsec: 1_000_000_000,
+            },
+            TimeSpec::zero(),
+        ];
+        let path = UserPath::new(&ctx, "file");
+
+        assert_eq!(
+            ctx.sys_utimensat(AT_FDCWD, path.addr(), register_times(&ctx, &times), 0),
+            Err(ErrNo::InvalidArgument)
+        );
+    }
+}
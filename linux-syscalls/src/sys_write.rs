@@ -195,6 +195,38 @@ mod tests {
         assert_eq!(test_file.content(), content);
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_written_to_host_file() {
+        use filesystem_abstractions::{DirectoryTreeNode, OpenFlags};
+        use test_utilities::hostfs::HostFileSystem;
+
+        let (kernel, alloc, mmu) = setup_kernel_with_memory();
+
+        let host = HostFileSystem::scratch();
+        let root = DirectoryTreeNode::from_filesystem(None, host.clone(), Some(""));
+        let file = root.mkdir("logs").unwrap().touch("out.txt").unwrap();
+
+        let mut fd_table = FileDescriptorTable::new();
+        fd_table.allocate(file.open_as_file(OpenFlags::O_WRONLY, 0));
+
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu.clone(), alloc)))
+            .with_fd_table(Some(fd_table))
+            .build();
+
+        let ctx = SyscallContext::new(task, kernel);
+
+        let buf = b"Hello, world";
+        mmu.lock().register(buf, false); // let the mmu know about the buffer
+
+        let ret = block_on!(ctx.sys_write(0, buf.into(), buf.len()));
+
+        assert_eq!(ret, Ok(buf.len() as isize));
+
+        assert_eq!(std::fs::read(host.host_path("/logs/out.txt")).unwrap(), buf);
+    }
+
     #[test]
     fn test_bad_fd_if_not_exist() {
         let (kernel, alloc, mmu) = setup_kernel_with_memory();
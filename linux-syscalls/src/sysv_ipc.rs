@@ -0,0 +1,42 @@
+use ipc::IpcPermissions;
+
+/// Removes the object
+pub(crate) const IPC_RMID: usize = 0;
+/// Changes the owner and permissions of the object
+pub(crate) const IPC_SET: usize = 1;
+/// Reports the status of the object
+pub(crate) const IPC_STAT: usize = 2;
+/// Reports the limits of all objects of a kind
+pub(crate) const IPC_INFO: usize = 3;
+
+/// Fails with `EAGAIN` or `ENOMSG` instead of waiting
+pub(crate) const IPC_NOWAIT: usize = 0o4000;
+
+/// `struct ipc64_perm`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u32,
+    pub seq: u16,
+    pub pad: u16,
+    pub unused: [u64; 2],
+}
+
+impl From<IpcPermissions> for IpcPerm {
+    fn from(permissions: IpcPermissions) -> IpcPerm {
+        IpcPerm {
+            key: permissions.key,
+            uid: permissions.uid,
+            gid: permissions.gid,
+            cuid: permissions.cuid,
+            cgid: permissions.cgid,
+            mode: permissions.mode,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use address::{IAddressBase, VirtualAddress};
+use alloc::sync::Arc;
+use constants::ErrNo;
+use filesystem_abstractions::{transfer, IFile, OpenFlags, Pipe, TransferEnd};
+use threading::yield_now;
+
+use crate::{SyscallContext, SyscallResult};
+
+/// Do not wait for the pipes of `splice` and `tee`, the other file may still block
+pub(crate) const SPLICE_F_NONBLOCK: usize = 2;
+
+/// Flags `splice` and `tee` take, moving and gifting pages are hints and data is always copied
+pub(crate) const SPLICE_FLAGS: usize = 1 /* SPLICE_F_MOVE */
+    | SPLICE_F_NONBLOCK
+    | 4 /* SPLICE_F_MORE */
+    | 8 /* SPLICE_F_GIFT */;
+
+impl SyscallContext {
+    /// Reads the offset `sendfile`, `splice` and `copy_file_range` take a pointer to, `None` if
+    /// the pointer is null and the file position is used.
+    pub(crate) fn import_offset(&self, offset: VirtualAddress) -> Result<Option<u64>, ErrNo> {
+        if offset.is_null() {
+            return Ok(None);
+        }
+
+        let offset = self
+            .task
+            .process()
+            .mmu()
+            .lock()
+            .import::<i64>(offset)
+            .map_err(|_| ErrNo::BadAddress)?;
+
+        if offset < 0 {
+            return Err(ErrNo::InvalidArgument);
+        }
+
+        Ok(Some(offset as u64))
+    }
+
+    /// Writes back where the offset imported by [`SyscallContext::import_offset`] has moved to.
+    pub(crate) fn export_offset(
+        &self,
+        offset: VirtualAddress,
+        end: &TransferEnd,
+    ) -> Result<(), ErrNo> {
+        match end.offset() {
+            Some(moved) if !offset.is_null() => self
+                .task
+                .process()
+                .mmu()
+                .lock()
+                .export(offset, moved as i64)
+                .map_err(|_| ErrNo::BadAddress),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Moves up to `len` bytes from `source` to `sink` once there is something to read and room to
+/// write, waiting for that unless `nonblocking` or either file is nonblocking.
+pub(crate) async fn transfer_when_ready(
+    source: &mut TransferEnd,
+    sink: &mut TransferEnd,
+    len: usize,
+    nonblocking: bool,
+) -> SyscallResult {
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let nonblocking = nonblocking
+        || source.file().flags().contains(OpenFlags::O_NONBLOCK)
+        || sink.file().flags().contains(OpenFlags::O_NONBLOCK);
+
+    while !source.file().read_avaliable() || !sink.file().write_avaliable() {
+        if nonblocking {
+            return Err(ErrNo::ResourceTemporarilyUnavailable);
+        }
+
+        wait_for_ends(source.file(), sink.file()).await;
+    }
+
+    transfer(source, sink, len)
+        .map(|moved| moved as isize)
+        .map_err(|e| e.to_errno())
+}
+
+/// Suspends until whichever of `source` and `sink` is not ready may have become ready.
+async fn wait_for_ends(source: &Arc<dyn IFile>, sink: &Arc<dyn IFile>) {
+    let (source_pipe, sink_pipe) = (source.downcast_ref::<Pipe>(), sink.downcast_ref::<Pipe>());
+
+    match (source.read_avaliable(), source_pipe, sink_pipe) {
+        (false, Some(pipe), _) => pipe.wait_readable().await,
+        (true, _, Some(pipe)) => pipe.wait_writable().await,
+        // Only pipes say when they become ready, check the others again later
+        _ => yield_now().await,
+    }
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use alloc::{string::ToString, sync::Arc};
+    use filesystem_abstractions::{DirectoryTreeNode, FileDescriptorTable, PageCache, Pipe};
+    use memory_space::MemorySpace;
+    use test_utilities::{
+        allocation::{contiguous, segment},
+        fs::TestPageAllocator,
+        hostfs::HostFileSystem,
+        kernel::TestKernel,
+        task::TestProcess,
+    };
+
+    use super::*;
+
+    /// A context on a scratch host directory whose regular files go through a page cache.
+    pub fn setup_cached_context() -> (SyscallContext, Arc<DirectoryTreeNode>, Arc<HostFileSystem>) {
+        let pages = contiguous::TestFrameAllocator::new(64 * 1024 * 1024);
+        let host = HostFileSystem::scratch();
+
+        let cache = PageCache::new(TestPageAllocator::new(pages), 16);
+        let root = DirectoryTreeNode::from_cached_filesystem(None, host.clone(), cache, Some(""));
+
+        let kernel = TestKernel::new().with_fs(Some(root.clone())).build();
+        let (alloc, mmu) = segment::TestFrameAllocator::new_with_mmu();
+
+        let (_, task) = TestProcess::new()
+            .with_memory_space(Some(MemorySpace::new(mmu, alloc)))
+            .with_fd_table(Some(FileDescriptorTable::new()))
+            .with_cwd("/".to_string())
+            .build();
+
+        (SyscallContext::new(task, kernel), root, host)
+    }
+
+    /// Opens a new pipe in the fd table of `ctx`, returning its read end and its write end.
+    pub fn pipe_fds(ctx: &SyscallContext, flags: OpenFlags) -> (usize, usize) {
+        let (read_end, write_end) = Pipe::new(flags);
+
+        let process = ctx.task.process();
+        let mut fd_table = process.fd_table().lock();
+
+        (
+            fd_table.allocate(read_end).unwrap(),
+            fd_table.allocate(write_end).unwrap(),
+        )
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use block_abstractions::{check_range, IBlockDevice};
+use filesystem_abstractions::{FileSystemError, FileSystemResult};
+use hermit_sync::SpinMutex;
+
+/// A block device backed by a disk image file on the host.
+pub struct DiskImage {
+    /// Seeked before every access, so it works the same on every host
+    file: SpinMutex<File>,
+    block_size: usize,
+    block_count: u64,
+    readonly: bool,
+    /// Removed when the image is dropped
+    temporary: Option<PathBuf>,
+}
+
+impl DiskImage {
+    /// Opens an existing image, whose size must be a multiple of `block_size`.
+    pub fn open(
+        path: impl AsRef<Path>,
+        block_size: usize,
+        readonly: bool,
+    ) -> Result<Arc<DiskImage>, Error> {
+        let file = OpenOptions::new().read(true).write(!readonly).open(path)?;
+
+        let len = file.metadata()?.len();
+
+        if block_size == 0 || !len.is_multiple_of(block_size as u64) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "image size is not a multiple of the block size",
+            ));
+        }
+
+        Ok(Arc::new(DiskImage {
+            file: SpinMutex::new(file),
+            block_size,
+            block_count: len / block_size as u64,
+            readonly,
+            temporary: None,
+        }))
+    }
+
+    /// Creates a zeroed, sparse image of `block_count` blocks, replacing any file at `path`.
+    pub fn create(
+        path: impl AsRef<Path>,
+        block_size: usize,
+        block_count: u64,
+    ) -> Result<Arc<DiskImage>, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        file.set_len(block_size as u64 * block_count)?;
+
+        Ok(Arc::new(DiskImage {
+            file: SpinMutex::new(file),
+            block_size,
+            block_count,
+            readonly: false,
+            temporary: None,
+        }))
+    }
+
+    /// A writable copy of the image at `path` that is removed once dropped, so tests can modify
+    /// images checked into the repository.
+    pub fn copy_of(path: impl AsRef<Path>, block_size: usize) -> Result<Arc<DiskImage>, Error> {
+        let copy = temporary_path();
+        std::fs::copy(path, &copy)?;
+
+        Self::open_temporary(copy, block_size)
+    }
+
+    /// A zeroed image of `block_count` blocks that is removed once dropped.
+    pub fn temporary(block_size: usize, block_count: u64) -> Result<Arc<DiskImage>, Error> {
+        let path = temporary_path();
+        drop(Self::create(&path, block_size, block_count)?);
+
+        Self::open_temporary(path, block_size)
+    }
+
+    fn open_temporary(path: PathBuf, block_size: usize) -> Result<Arc<DiskImage>, Error> {
+        let image = match Self::open(&path, block_size, false) {
+            Ok(image) => image,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+
+        let mut image = Arc::into_inner(image).unwrap();
+        image.temporary = Some(path);
+
+        Ok(Arc::new(image))
+    }
+
+    /// Where the image lives on the host, for handing it to host tools like `fsck`.
+    pub fn path(&self) -> Option<&Path> {
+        self.temporary.as_deref()
+    }
+}
+
+fn temporary_path() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    std::env::temp_dir().join(format!(
+        "bakaos-disk-{}-{}.img",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+impl IBlockDevice for DiskImage {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        self.block_count
+    }
+
+    fn read_blocks(&self, first: u64, buffer: &mut [u8]) -> FileSystemResult<()> {
+        check_range(self, first, buffer.len())?;
+
+        let mut file = self.file.lock();
+
+        file.seek(SeekFrom::Start(first * self.block_size as u64))
+            .and_then(|_| file.read_exact(buffer))
+            .map_err(|_| FileSystemError::InternalError)
+    }
+
+    fn write_blocks(&self, first: u64, buffer: &[u8]) -> FileSystemResult<()> {
+        if self.readonly {
+            return Err(FileSystemError::ReadOnly);
+        }
+
+        check_range(self, first, buffer.len())?;
+
+        let mut file = self.file.lock();
+
+        file.seek(SeekFrom::Start(first * self.block_size as u64))
+            .and_then(|_| file.write_all(buffer))
+            .map_err(|_| FileSystemError::InternalError)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        self.file
+            .lock()
+            .sync_data()
+            .map_err(|_| FileSystemError::InternalError)
+    }
+
+    fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+}
+
+impl Drop for DiskImage {
+    fn drop(&mut self) {
+        if let Some(path) = &self.temporary {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -4,13 +4,17 @@ use std::{
     fs::{File, Metadata},
     io::{Error, Read, Seek, Write},
     path::{Path, PathBuf},
+    ptr::NonNull,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use abstractions::IUsizeAlias;
+use address::PhysicalAddress;
+use allocation_abstractions::{FrameDesc, IFrameAllocator};
 use filesystem_abstractions::{
     DirectoryEntry, DirectoryEntryType, DirectoryTreeNode, FileStatisticsMode, FileSystemError,
-    FileSystemResult, IInode, InodeMetadata,
+    FileSystemResult, IInode, IPageAllocator, InodeMetadata,
 };
 use hermit_sync::SpinMutex;
 use timing::TimeSpec;
@@ -74,11 +78,24 @@ fn to_entry_type(meta: &Metadata) -> DirectoryEntryType {
         DirectoryEntryType::File
     } else if meta.is_symlink() {
         DirectoryEntryType::Symlink
+    } else if is_socket(meta) {
+        DirectoryEntryType::Socket
     } else {
         unimplemented!("Not implemented for {:?}", meta);
     }
 }
 
+#[cfg(unix)]
+fn is_socket(meta: &Metadata) -> bool {
+    std::os::unix::fs::FileTypeExt::is_socket(&meta.file_type())
+}
+
+/// Other hosts have no sockets in their filesystems
+#[cfg(not(unix))]
+fn is_socket(_meta: &Metadata) -> bool {
+    false
+}
+
 impl IInode for HostFile {
     fn metadata(&self) -> InodeMetadata<'_> {
         let meta = self.inner.lock().metadata().unwrap();
@@ -206,6 +223,7 @@ impl IInode for HostFile {
             DirectoryEntryType::BlockDevice => FileStatisticsMode::BLOCK,
             DirectoryEntryType::File => FileStatisticsMode::FILE,
             DirectoryEntryType::Symlink => FileStatisticsMode::LINK,
+            DirectoryEntryType::Socket => FileStatisticsMode::SOCKET,
             DirectoryEntryType::Unknown => FileStatisticsMode::NULL,
         };
 
@@ -233,3 +251,36 @@ fn systime_to_timespec(time: SystemTime) -> TimeSpec {
         tv_nsec: duration.subsec_nanos() as i64,
     }
 }
+
+/// Page cache pages from a contiguous `TestFrameAllocator`, whose physical addresses are host
+/// addresses, so `TestMMU` can map them.
+pub struct TestPageAllocator {
+    allocator: Arc<SpinMutex<dyn IFrameAllocator>>,
+}
+
+unsafe impl Send for TestPageAllocator {}
+unsafe impl Sync for TestPageAllocator {}
+
+impl TestPageAllocator {
+    pub fn new(allocator: Arc<SpinMutex<dyn IFrameAllocator>>) -> Arc<TestPageAllocator> {
+        Arc::new(TestPageAllocator { allocator })
+    }
+}
+
+impl IPageAllocator for TestPageAllocator {
+    fn allocate(&self) -> Option<(usize, NonNull<u8>)> {
+        let frame = self.allocator.lock().alloc_frame()?;
+        let physical = frame.0.as_usize();
+
+        // Handed back through `deallocate`
+        std::mem::forget(frame);
+
+        Some((physical, NonNull::new(physical as *mut u8)?))
+    }
+
+    unsafe fn deallocate(&self, physical: usize, _page: NonNull<u8>) {
+        let frame = unsafe { FrameDesc::new(PhysicalAddress::from_usize(physical)) };
+
+        self.allocator.lock().dealloc(frame);
+    }
+}
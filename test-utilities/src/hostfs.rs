@@ -0,0 +1,665 @@
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    fs::{File, FileType, Metadata, OpenOptions, Permissions},
+    io::{Error, ErrorKind},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+};
+
+use filesystem_abstractions::{
+    DirectoryEntry, DirectoryEntryType, FileStatistics, FileStatisticsMode, FileSystemError,
+    FileSystemResult, IFileSystem, IInode, InodeAttributes, InodeMetadata,
+};
+use timing::TimeSpec;
+
+/// A host directory mapped into the kernel tree, read and written through the host filesystem.
+///
+/// Everything stays below the root directory. Directories are kept open and their children are
+/// opened relative to them one name at a time with `O_NOFOLLOW`, so the host never follows a link
+/// and renaming a directory does not affect the inodes below it. Link targets are kept as they
+/// are given and only resolved by the directory tree, an absolute one points into the kernel
+/// tree, not the host one.
+///
+/// Regular files keep their host file open, they can be used after being renamed or removed.
+/// Other inodes are found by their name in the directory they were looked up in.
+pub struct HostFileSystem {
+    this: Weak<HostFileSystem>,
+    root: PathBuf,
+    scratch: bool,
+}
+
+impl HostFileSystem {
+    /// Maps the existing host directory `root`, which is left as it is once dropped.
+    pub fn new(root: impl AsRef<Path>) -> Arc<HostFileSystem> {
+        let root = root.as_ref().canonicalize().unwrap();
+        assert!(root.is_dir(), "{} is not a directory", root.display());
+
+        Self::create(root, false)
+    }
+
+    /// Maps a new empty directory in the host temporary directory, removed once dropped.
+    pub fn scratch() -> Arc<HostFileSystem> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let root = std::env::temp_dir().join(format!(
+            "hostfs-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        std::fs::create_dir_all(&root).unwrap();
+
+        Self::create(root.canonicalize().unwrap(), true)
+    }
+
+    fn create(root: PathBuf, scratch: bool) -> Arc<HostFileSystem> {
+        Arc::new_cyclic(|this| HostFileSystem {
+            this: this.clone(),
+            root,
+            scratch,
+        })
+    }
+
+    /// The host directory everything is in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where the file at `path` in this filesystem is on the host.
+    pub fn host_path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+impl Drop for HostFileSystem {
+    fn drop(&mut self) {
+        if self.scratch {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+impl IFileSystem for HostFileSystem {
+    fn root_dir(&self) -> Arc<dyn IInode> {
+        let root = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(&self.root)
+            .unwrap();
+
+        Arc::new(HostInode {
+            fs: self.this.upgrade().unwrap(),
+            name: String::new(),
+            entry_type: DirectoryEntryType::Directory,
+            parent: None,
+            file: Some(Arc::new(root)),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "hostfs"
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        Ok(())
+    }
+}
+
+pub struct HostInode {
+    fs: Arc<HostFileSystem>,
+    name: String,
+    entry_type: DirectoryEntryType,
+    // The directory it was looked up in, None for the root
+    parent: Option<Arc<File>>,
+    // Regular files and directories stay open
+    file: Option<Arc<File>>,
+}
+
+fn to_error(error: Error) -> FileSystemError {
+    match error.raw_os_error() {
+        Some(libc::ENOENT) => FileSystemError::NotFound,
+        Some(libc::EEXIST) => FileSystemError::AlreadyExists,
+        Some(libc::ENOTEMPTY) => FileSystemError::DirectoryNotEmpty,
+        Some(libc::ENOTDIR) => FileSystemError::NotADirectory,
+        Some(libc::EISDIR) => FileSystemError::NotAFile,
+        Some(libc::ENOSPC) | Some(libc::EDQUOT) => FileSystemError::SpaceNotEnough,
+        Some(libc::EPERM) | Some(libc::EACCES) => FileSystemError::NotPermitted,
+        Some(libc::EROFS) => FileSystemError::ReadOnly,
+        Some(libc::ENAMETOOLONG) => FileSystemError::PathNameLengthExceeded,
+        Some(libc::ELOOP) => FileSystemError::LinkTooDepth,
+        Some(libc::EINVAL) => FileSystemError::InvalidInput,
+        _ => match error.kind() {
+            ErrorKind::UnexpectedEof => FileSystemError::UnexpectedEof,
+            ErrorKind::WriteZero => FileSystemError::WriteZero,
+            _ => FileSystemError::InternalError,
+        },
+    }
+}
+
+fn to_entry_type(file_type: FileType) -> DirectoryEntryType {
+    if file_type.is_dir() {
+        DirectoryEntryType::Directory
+    } else if file_type.is_file() {
+        DirectoryEntryType::File
+    } else if file_type.is_symlink() {
+        DirectoryEntryType::Symlink
+    } else if file_type.is_socket() {
+        DirectoryEntryType::Socket
+    } else if file_type.is_fifo() {
+        DirectoryEntryType::NamedPipe
+    } else if file_type.is_char_device() {
+        DirectoryEntryType::CharDevice
+    } else if file_type.is_block_device() {
+        DirectoryEntryType::BlockDevice
+    } else {
+        DirectoryEntryType::Unknown
+    }
+}
+
+fn to_timespec(seconds: i64, nanoseconds: i64) -> TimeSpec {
+    TimeSpec {
+        tv_sec: seconds,
+        tv_nsec: nanoseconds,
+    }
+}
+
+fn to_c_path(path: &Path) -> FileSystemResult<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| FileSystemError::PathContainsInvalidCharacter)
+}
+
+fn to_result(ret: libc::c_int) -> FileSystemResult<()> {
+    match ret {
+        0 => Ok(()),
+        _ => Err(to_error(Error::last_os_error())),
+    }
+}
+
+/// Opens `name` right inside `directory`, never following it if it's a link.
+fn open_at(
+    directory: &File,
+    name: &str,
+    flags: libc::c_int,
+    mode: libc::mode_t,
+) -> FileSystemResult<File> {
+    let name = to_c_path(Path::new(name))?;
+
+    let fd = unsafe {
+        libc::openat(
+            directory.as_raw_fd(),
+            name.as_ptr(),
+            flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            mode as libc::c_uint,
+        )
+    };
+
+    match fd {
+        -1 => Err(to_error(Error::last_os_error())),
+        fd => Ok(unsafe { File::from_raw_fd(fd) }),
+    }
+}
+
+/// The metadata of `name` itself in `directory`, even if it's a link.
+fn metadata_at(directory: &File, name: &str) -> FileSystemResult<Metadata> {
+    open_at(directory, name, libc::O_PATH, 0)?
+        .metadata()
+        .map_err(to_error)
+}
+
+/// A path the host resolves to the open file itself, whatever it's named now.
+fn descriptor_path(file: &File) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+fn check_name(name: &str) -> FileSystemResult<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FileSystemError::InvalidInput);
+    }
+
+    if name.contains(['/', '\0']) {
+        return Err(FileSystemError::PathContainsInvalidCharacter);
+    }
+
+    Ok(())
+}
+
+impl HostInode {
+    fn open(
+        fs: Arc<HostFileSystem>,
+        parent: Arc<File>,
+        name: &str,
+        entry_type: DirectoryEntryType,
+    ) -> FileSystemResult<Arc<HostInode>> {
+        let file = match entry_type {
+            // Read-only files can still be read
+            DirectoryEntryType::File => Some(
+                open_at(&parent, name, libc::O_RDWR, 0)
+                    .or_else(|_| open_at(&parent, name, libc::O_RDONLY, 0))?,
+            ),
+            DirectoryEntryType::Directory => Some(open_at(
+                &parent,
+                name,
+                libc::O_RDONLY | libc::O_DIRECTORY,
+                0,
+            )?),
+            _ => None,
+        };
+
+        Ok(Arc::new(HostInode {
+            fs,
+            name: name.to_string(),
+            entry_type,
+            parent: Some(parent),
+            file: file.map(Arc::new),
+        }))
+    }
+
+    fn directory(&self) -> FileSystemResult<&Arc<File>> {
+        match (&self.file, self.entry_type) {
+            (Some(directory), DirectoryEntryType::Directory) => Ok(directory),
+            _ => Err(FileSystemError::NotADirectory),
+        }
+    }
+
+    /// The directory to create or find `name` in.
+    fn child_directory(&self, name: &str) -> FileSystemResult<&Arc<File>> {
+        check_name(name)?;
+
+        self.directory()
+    }
+
+    fn child(&self, name: &str) -> FileSystemResult<Arc<HostInode>> {
+        let directory = self.child_directory(name)?;
+        let meta = metadata_at(directory, name)?;
+
+        HostInode::open(
+            self.fs.clone(),
+            directory.clone(),
+            name,
+            to_entry_type(meta.file_type()),
+        )
+    }
+
+    fn file(&self) -> FileSystemResult<&File> {
+        match (&self.file, self.entry_type) {
+            (Some(file), DirectoryEntryType::File) => Ok(file),
+            _ => Err(FileSystemError::NotAFile),
+        }
+    }
+
+    /// The directory and the name to reach an inode which is not kept open.
+    fn location(&self) -> FileSystemResult<(&File, CString)> {
+        let parent = self.parent.as_ref().ok_or(FileSystemError::InternalError)?;
+
+        Ok((parent, to_c_path(Path::new(&self.name))?))
+    }
+
+    fn host_metadata(&self) -> FileSystemResult<Metadata> {
+        match (&self.file, &self.parent) {
+            (Some(file), _) => file.metadata().map_err(to_error),
+            (None, Some(parent)) => metadata_at(parent, &self.name),
+            (None, None) => Err(FileSystemError::InternalError),
+        }
+    }
+
+    fn set_times(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        let to_host = |time: Option<TimeSpec>| match time {
+            Some(time) => libc::timespec {
+                tv_sec: time.tv_sec,
+                tv_nsec: time.tv_nsec,
+            },
+            None => libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_OMIT,
+            },
+        };
+
+        let times = [to_host(attributes.atime), to_host(attributes.mtime)];
+
+        to_result(match &self.file {
+            Some(file) => unsafe { libc::futimens(file.as_raw_fd(), times.as_ptr()) },
+            None => {
+                let (parent, name) = self.location()?;
+
+                unsafe {
+                    libc::utimensat(
+                        parent.as_raw_fd(),
+                        name.as_ptr(),
+                        times.as_ptr(),
+                        libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                }
+            }
+        })
+    }
+
+    fn set_mode(&self, mode: u32) -> FileSystemResult<()> {
+        match (&self.file, self.entry_type) {
+            (Some(file), _) => file
+                .set_permissions(Permissions::from_mode(mode))
+                .map_err(to_error),
+            // Symlinks have no permissions of their own on the host
+            (None, DirectoryEntryType::Symlink) => Ok(()),
+            (None, _) => {
+                let (parent, name) = self.location()?;
+
+                to_result(unsafe {
+                    libc::fchmodat(
+                        parent.as_raw_fd(),
+                        name.as_ptr(),
+                        mode as libc::mode_t,
+                        libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                })
+            }
+        }
+    }
+
+    fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> FileSystemResult<()> {
+        match &self.file {
+            Some(file) => std::os::unix::fs::fchown(file.as_ref(), uid, gid).map_err(to_error),
+            None => {
+                let (parent, name) = self.location()?;
+
+                to_result(unsafe {
+                    libc::fchownat(
+                        parent.as_raw_fd(),
+                        name.as_ptr(),
+                        uid.unwrap_or(u32::MAX),
+                        gid.unwrap_or(u32::MAX),
+                        libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                })
+            }
+        }
+    }
+}
+
+impl IInode for HostInode {
+    fn metadata(&self) -> InodeMetadata<'_> {
+        InodeMetadata {
+            filename: &self.name,
+            entry_type: self.entry_type,
+            size: self.host_metadata().map_or(0, |meta| meta.len() as usize),
+        }
+    }
+
+    fn readat(&self, offset: usize, buffer: &mut [u8]) -> FileSystemResult<usize> {
+        std::os::unix::fs::FileExt::read_at(self.file()?, buffer, offset as u64).map_err(to_error)
+    }
+
+    fn writeat(&self, offset: usize, buffer: &[u8]) -> FileSystemResult<usize> {
+        std::os::unix::fs::FileExt::write_at(self.file()?, buffer, offset as u64).map_err(to_error)
+    }
+
+    fn mkdir(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        let directory = self.child_directory(name)?;
+        let c_name = to_c_path(Path::new(name))?;
+
+        to_result(unsafe { libc::mkdirat(directory.as_raw_fd(), c_name.as_ptr(), 0o755) })?;
+
+        Ok(self.child(name)?)
+    }
+
+    fn rmdir(&self, name: &str) -> FileSystemResult<()> {
+        let directory = self.child_directory(name)?;
+
+        if !metadata_at(directory, name)?.is_dir() {
+            return Err(FileSystemError::NotADirectory);
+        }
+
+        let name = to_c_path(Path::new(name))?;
+
+        to_result(unsafe {
+            libc::unlinkat(directory.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR)
+        })
+    }
+
+    fn remove(&self, name: &str) -> FileSystemResult<()> {
+        let directory = self.child_directory(name)?;
+
+        if metadata_at(directory, name)?.is_dir() {
+            return Err(FileSystemError::NotAFile);
+        }
+
+        let name = to_c_path(Path::new(name))?;
+
+        to_result(unsafe { libc::unlinkat(directory.as_raw_fd(), name.as_ptr(), 0) })
+    }
+
+    fn touch(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        open_at(
+            self.child_directory(name)?,
+            name,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL,
+            0o644,
+        )?;
+
+        Ok(self.child(name)?)
+    }
+
+    fn read_cache_dir(
+        &self,
+        _caches: &mut BTreeMap<String, Arc<dyn IInode>>,
+    ) -> FileSystemResult<Vec<DirectoryEntry>> {
+        let directory = self.directory()?;
+
+        let mut entries = Vec::new();
+
+        for entry in std::fs::read_dir(descriptor_path(directory)).map_err(to_error)? {
+            let entry = entry.map_err(to_error)?;
+
+            entries.push(DirectoryEntry {
+                filename: entry.file_name().to_string_lossy().to_string(),
+                entry_type: to_entry_type(entry.file_type().map_err(to_error)?),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn lookup(&self, name: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        Ok(self.child(name)?)
+    }
+
+    fn flush(&self) -> FileSystemResult<()> {
+        match &self.file {
+            Some(file) => file.sync_all().map_err(to_error),
+            None => Ok(()),
+        }
+    }
+
+    fn stat(&self, stat: &mut FileStatistics) -> FileSystemResult<()> {
+        let meta = self.host_metadata()?;
+
+        stat.device_id = meta.dev();
+        stat.inode_id = meta.ino();
+        stat.mode = FileStatisticsMode::from_bits_retain(meta.mode());
+        stat.link_count = meta.nlink() as u32;
+        stat.uid = meta.uid();
+        stat.gid = meta.gid();
+        stat.rdev = meta.rdev();
+        stat.size = meta.size();
+        stat.block_size = meta.blksize() as u32;
+        stat.block_count = meta.blocks();
+        stat.atime = to_timespec(meta.atime(), meta.atime_nsec());
+        stat.mtime = to_timespec(meta.mtime(), meta.mtime_nsec());
+        stat.ctime = to_timespec(meta.ctime(), meta.ctime_nsec());
+
+        Ok(())
+    }
+
+    fn set_attributes(&self, attributes: &InodeAttributes) -> FileSystemResult<()> {
+        if let Some(mode) = attributes.mode {
+            self.set_mode(mode.bits())?;
+        }
+
+        if attributes.uid.is_some() || attributes.gid.is_some() {
+            self.set_owner(attributes.uid, attributes.gid)?;
+        }
+
+        if attributes.atime.is_some() || attributes.mtime.is_some() {
+            self.set_times(attributes)?;
+        }
+
+        Ok(())
+    }
+
+    fn hard_link(&self, name: &str, inode: &Arc<dyn IInode>) -> FileSystemResult<()> {
+        let Some(source) = inode.downcast_ref::<HostInode>() else {
+            return Err(FileSystemError::NotPermitted);
+        };
+
+        if !Arc::ptr_eq(&source.fs, &self.fs) || source.entry_type == DirectoryEntryType::Directory
+        {
+            return Err(FileSystemError::NotPermitted);
+        }
+
+        let directory = self.child_directory(name)?;
+        let name = to_c_path(Path::new(name))?;
+
+        // An open file is linked through its descriptor, it may have been renamed since.
+        // Anything else is linked as it is, without following it
+        let ret = match &source.file {
+            Some(file) => unsafe {
+                libc::linkat(
+                    libc::AT_FDCWD,
+                    to_c_path(&descriptor_path(file))?.as_ptr(),
+                    directory.as_raw_fd(),
+                    name.as_ptr(),
+                    libc::AT_SYMLINK_FOLLOW,
+                )
+            },
+            None => {
+                let (parent, source_name) = source.location()?;
+
+                unsafe {
+                    libc::linkat(
+                        parent.as_raw_fd(),
+                        source_name.as_ptr(),
+                        directory.as_raw_fd(),
+                        name.as_ptr(),
+                        0,
+                    )
+                }
+            }
+        };
+
+        to_result(ret)
+    }
+
+    fn soft_link(&self, name: &str, point_to: &str) -> FileSystemResult<Arc<dyn IInode>> {
+        if point_to.is_empty() {
+            return Err(FileSystemError::NotFound);
+        }
+
+        let directory = self.child_directory(name)?;
+        let c_name = to_c_path(Path::new(name))?;
+        let target = to_c_path(Path::new(point_to))?;
+
+        to_result(unsafe {
+            libc::symlinkat(target.as_ptr(), directory.as_raw_fd(), c_name.as_ptr())
+        })?;
+
+        Ok(self.child(name)?)
+    }
+
+    fn resolve_link(&self) -> Option<String> {
+        if self.entry_type != DirectoryEntryType::Symlink {
+            return None;
+        }
+
+        let (parent, name) = self.location().ok()?;
+        let mut buffer = [0u8; libc::PATH_MAX as usize];
+
+        let len = unsafe {
+            libc::readlinkat(
+                parent.as_raw_fd(),
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+
+        let len = usize::try_from(len).ok()?;
+
+        Some(String::from_utf8_lossy(&buffer[..len]).to_string())
+    }
+
+    fn resize(&self, new_size: u64) -> FileSystemResult<u64> {
+        self.file()?.set_len(new_size).map_err(to_error)?;
+
+        Ok(new_size)
+    }
+
+    fn rename(&self, old_name: &str, new_name: &str) -> FileSystemResult<()> {
+        check_name(new_name)?;
+
+        let directory = self.child_directory(old_name)?;
+        let old_name = to_c_path(Path::new(old_name))?;
+        let new_name = to_c_path(Path::new(new_name))?;
+
+        to_result(unsafe {
+            libc::renameat(
+                directory.as_raw_fd(),
+                old_name.as_ptr(),
+                directory.as_raw_fd(),
+                new_name.as_ptr(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filesystem_abstractions::DirectoryTreeNode;
+
+    use super::*;
+
+    #[test]
+    fn test_directory_survives_rename() {
+        let host = HostFileSystem::scratch();
+        let root = DirectoryTreeNode::from_filesystem(None, host.clone(), Some(""));
+
+        let old = root.mkdir("old").unwrap();
+        root.rename("old", "new").unwrap();
+
+        old.touch("file").unwrap().writeat(0, b"moved").unwrap();
+
+        assert_eq!(
+            std::fs::read(host.host_path("/new/file")).unwrap(),
+            b"moved"
+        );
+    }
+
+    #[test]
+    fn test_links_are_not_followed_by_the_host() {
+        let host = HostFileSystem::scratch();
+        let root = DirectoryTreeNode::from_filesystem(None, host.clone(), Some(""));
+        let outside = host.root().parent().unwrap().to_path_buf();
+
+        let link = root.soft_link("escape", "../../..").unwrap();
+        assert_eq!(link.resolve_link().as_deref(), Some("../../.."));
+        assert_eq!(
+            link.touch("file").err(),
+            Some(FileSystemError::NotADirectory)
+        );
+
+        // Swapped for a link on the host after it was opened
+        let dir = root.mkdir("dir").unwrap();
+        std::fs::remove_dir(host.host_path("/dir")).unwrap();
+        std::os::unix::fs::symlink(&outside, host.host_path("/dir")).unwrap();
+
+        assert!(dir.touch("file").is_err());
+        assert!(!outside.join("file").exists());
+    }
+}
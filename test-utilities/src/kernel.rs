@@ -1,19 +1,34 @@
 use allocation_abstractions::IFrameAllocator;
-use filesystem_abstractions::DirectoryTreeNode;
+use filesystem_abstractions::{DirectoryTreeNode, FileLockManager, MountTable};
 use hermit_sync::SpinMutex;
+use ipc::{
+    IpcTable, MessageQueue, MqueueFileSystem, SemaphoreSets, SharedMemorySegment, MSGMNI, SHMMNI,
+};
 use kernel_abstractions::{IKernel, IKernelSerial};
+use network_stack::NetworkStack;
 use std::{
     collections::vec_deque::VecDeque,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
     vec::Vec,
 };
+use threading::{IClock, TimerQueue};
 use timing::TimeSpec;
+use unix_socket::UnixNamespace;
 
 pub struct TestKernel {
     pub serial: Option<Arc<dyn IKernelSerial>>,
     pub fs: Option<Arc<SpinMutex<Arc<DirectoryTreeNode>>>>,
     pub allocator: Option<Arc<SpinMutex<dyn IFrameAllocator>>>,
+    pub timer: Arc<TimerQueue>,
+    pub network: Arc<NetworkStack>,
+    pub unix_sockets: Option<Arc<UnixNamespace>>,
+    pub locks: Arc<FileLockManager>,
+    pub mounts: Arc<MountTable>,
+    pub shared_memory: Arc<IpcTable<SharedMemorySegment>>,
+    pub semaphores: Arc<SemaphoreSets>,
+    pub message_queues: Arc<IpcTable<MessageQueue>>,
+    pub mqueue: Arc<DirectoryTreeNode>,
 }
 
 unsafe impl Send for TestKernel {}
@@ -31,6 +46,15 @@ impl TestKernel {
             serial: None,
             fs: None,
             allocator: None,
+            timer: TimerQueue::new(Arc::new(SystemClock)),
+            network: NetworkStack::new(Arc::new(SystemClock)),
+            unix_sockets: None,
+            locks: Arc::new(FileLockManager::new()),
+            mounts: Arc::new(MountTable::new(None)),
+            shared_memory: Arc::new(IpcTable::new(SHMMNI)),
+            semaphores: Arc::new(SemaphoreSets::new()),
+            message_queues: Arc::new(IpcTable::new(MSGMNI)),
+            mqueue: DirectoryTreeNode::from_filesystem(None, MqueueFileSystem::new(), Some("")),
         }
     }
 
@@ -41,6 +65,7 @@ impl TestKernel {
 
     pub fn with_fs(mut self, fs: Option<Arc<DirectoryTreeNode>>) -> Self {
         self.fs = fs.map(|f| Arc::new(SpinMutex::new(f)));
+        self.unix_sockets = self.fs.clone().map(UnixNamespace::new);
         self
     }
 
@@ -49,6 +74,21 @@ impl TestKernel {
         self
     }
 
+    pub fn with_timer(mut self, timer: Arc<TimerQueue>) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    pub fn with_network(mut self, network: Arc<NetworkStack>) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn with_mounts(mut self, mounts: Arc<MountTable>) -> Self {
+        self.mounts = mounts;
+        self
+    }
+
     pub fn build(self) -> Arc<dyn IKernel> {
         Arc::new(self)
     }
@@ -70,6 +110,50 @@ impl IKernel for TestKernel {
     fn activate_mmu(&self, _pt: &dyn mmu_abstractions::IMMU) {}
 
     fn time(&self) -> TimeSpec {
+        self.timer.now()
+    }
+
+    fn timer(&self) -> Arc<TimerQueue> {
+        self.timer.clone()
+    }
+
+    fn network(&self) -> Arc<NetworkStack> {
+        self.network.clone()
+    }
+
+    fn unix_sockets(&self) -> Arc<UnixNamespace> {
+        self.unix_sockets.as_ref().unwrap().clone()
+    }
+
+    fn locks(&self) -> Arc<FileLockManager> {
+        self.locks.clone()
+    }
+
+    fn mounts(&self) -> Arc<MountTable> {
+        self.mounts.clone()
+    }
+
+    fn shared_memory(&self) -> Arc<IpcTable<SharedMemorySegment>> {
+        self.shared_memory.clone()
+    }
+
+    fn semaphores(&self) -> Arc<SemaphoreSets> {
+        self.semaphores.clone()
+    }
+
+    fn message_queues(&self) -> Arc<IpcTable<MessageQueue>> {
+        self.message_queues.clone()
+    }
+
+    fn mqueue(&self) -> Arc<DirectoryTreeNode> {
+        self.mqueue.clone()
+    }
+}
+
+pub struct SystemClock;
+
+impl IClock for SystemClock {
+    fn now(&self) -> TimeSpec {
         let now = SystemTime::now();
         let unix = now.duration_since(UNIX_EPOCH).unwrap();
         TimeSpec {
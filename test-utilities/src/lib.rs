@@ -1,8 +1,12 @@
 pub mod allocation;
+pub mod block;
 pub mod fs;
+#[cfg(unix)]
+pub mod hostfs;
 pub mod kernel;
 pub mod memory;
 pub mod task;
+pub mod tempdir;
 
 #[cfg(feature = "test_log")]
 mod logging;
@@ -1,12 +1,12 @@
 use core::cell::UnsafeCell;
 use std::sync::{Arc, Weak};
 
-use filesystem_abstractions::FileDescriptorTable;
+use filesystem_abstractions::{Credentials, FileDescriptorTable};
 use hermit_sync::SpinMutex;
 use linux_task_abstractions::{ILinuxProcess, ILinuxTask};
 use memory_space::MemorySpace;
 use platform_specific::TaskTrapContext;
-use task_abstractions::{status::TaskStatus, IProcess, ITask, UserTaskStatistics};
+use task_abstractions::{status::TaskStatus, IProcess, ITask, ProcessImage, UserTaskStatistics};
 use trap_abstractions::ITaskTrapContext;
 
 pub struct TestTask {
@@ -143,6 +143,8 @@ pub struct TestProcess {
     pub memory_space: Option<SpinMutex<MemorySpace>>,
     pub fd_table: Option<SpinMutex<FileDescriptorTable>>,
     pub working_directory: String,
+    pub credentials: SpinMutex<Credentials>,
+    pub image: ProcessImage,
     pub main_thread: Option<TestTask>,
     pub exit_code: SpinMutex<Option<u8>>,
 }
@@ -167,6 +169,8 @@ impl TestProcess {
             memory_space: None,
             fd_table: None,
             working_directory: String::new(),
+            credentials: SpinMutex::new(Credentials::root()),
+            image: ProcessImage::default(),
             main_thread: Some(TestTask::new()),
             exit_code: SpinMutex::new(None),
         }
@@ -229,6 +233,16 @@ impl TestProcess {
         self.working_directory = cwd;
         self
     }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = SpinMutex::new(credentials);
+        self
+    }
+
+    pub fn with_image(mut self, image: ProcessImage) -> Self {
+        self.image = image;
+        self
+    }
 }
 
 impl IProcess for TestProcess {
@@ -268,6 +282,14 @@ impl IProcess for TestProcess {
         self.working_directory.clone()
     }
 
+    fn credentials(&self) -> &SpinMutex<Credentials> {
+        &self.credentials
+    }
+
+    fn image(&self) -> ProcessImage {
+        self.image.clone()
+    }
+
     fn exit_code(&self) -> &SpinMutex<Option<u8>> {
         &self.exit_code
     }
@@ -284,7 +306,7 @@ impl IProcess for TestProcess {
 }
 
 impl ILinuxProcess for TestProcess {
-    fn execve(&self, _: MemorySpace, _: u32) {
+    fn execve(&self, _: MemorySpace, _: u32, _: ProcessImage) {
         unimplemented!(
             "TestProcess is intended for light-weight mock testing. Use task::Process instead, which also supports unit test"
         )